@@ -0,0 +1,33 @@
+// Lexes, parses and runs whatever SQL it is given, none of which may panic. Run with
+//
+//     cargo +nightly fuzz run lex_parse_eval
+//
+// from the directory of the crate. Inputs that crashed go in the tests of the lexer and
+// parser once fixed.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use postgrustql::backend_memory::{MemoryBackend, ResultLimits};
+use postgrustql::lexer::Lexer;
+
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    let _ = Lexer::new().lex(source);
+    let _ = postgrustql::parser::parse(source);
+
+    // A table to read from, and limits so a query asking for many rows fails instead of
+    // running out of memory
+    let mut backend = MemoryBackend::new();
+    backend.set_result_limits(ResultLimits {
+        max_result_rows: Some(10_000),
+        max_result_bytes: Some(1 << 20),
+    });
+    backend
+        .eval_query("CREATE TABLE t (id INT, name TEXT);")
+        .and_then(|_| backend.eval_query("INSERT INTO t VALUES (1, 'a'), (2, NULL);"))
+        .expect("The table to fuzz with can't be created");
+    let _ = backend.eval_query(source);
+});
@@ -1,7 +1,13 @@
-use crate::sql_types::SqlType;
+use crate::quote::{quote_identifier, quote_text};
+use crate::sql_types::{SqlType, SqlValue};
 
 use super::lexer::*;
 
+// The name the recovering parser gives to the names and expressions it makes up where
+// the source is missing one. No identifier lexed from SQL holds a NUL, so it can't be
+// mistaken for one that was written, and the backend refuses to run a statement with it.
+pub const PLACEHOLDER_NAME: &str = "\u{0}error";
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Ast {
     pub statements: Vec<Statement>,
@@ -11,9 +17,122 @@ pub struct Ast {
 pub enum Statement {
     SelectStatement(SelectStatement),
     CreateTableStatement(CreateTableStatement),
+    CreateTableAsStatement(CreateTableAsStatement),
     CreateIndexStatement(CreateIndexStatement),
+    CreateSequenceStatement(CreateSequenceStatement),
+    CreateViewStatement(CreateViewStatement),
+    CreateTriggerStatement(CreateTriggerStatement),
     DropTableStatement(DropTableStatement),
+    DropIndexStatement(DropIndexStatement),
+    DropSequenceStatement(DropSequenceStatement),
+    DropViewStatement(DropViewStatement),
+    DropTriggerStatement(DropTriggerStatement),
+    AlterTableStatement(AlterTableStatement),
+    ReindexStatement(ReindexStatement),
+    VacuumStatement(VacuumStatement),
+    ExplainStatement(ExplainStatement),
     InsertStatement(InsertStatement),
+    UpdateStatement(UpdateStatement),
+    DeleteStatement(DeleteStatement),
+    WithStatement(WithStatement),
+    SetStatement(SetStatement),
+    ShowStatement(ShowStatement),
+    ResetStatement(ResetStatement),
+    ListenStatement(ListenStatement),
+    NotifyStatement(NotifyStatement),
+    UnlistenStatement(UnlistenStatement),
+    DeclareCursorStatement(DeclareCursorStatement),
+    FetchStatement(FetchStatement),
+    CloseStatement(CloseStatement),
+    TransactionStatement(TransactionStatement),
+}
+
+impl Statement {
+    // Whether the statement leaves every table and the schema as they are, temporary
+    // tables included. SET and the like only change the session, COMMIT and ROLLBACK
+    // only settle what the statements before them did.
+    pub fn is_read_only(&self) -> bool {
+        if let Statement::WithStatement(with) = self {
+            return with.writing_statement().is_none();
+        }
+        matches!(
+            self,
+            Statement::SelectStatement(_)
+                | Statement::ExplainStatement(_)
+                | Statement::SetStatement(_)
+                | Statement::ShowStatement(_)
+                | Statement::ResetStatement(_)
+                | Statement::ListenStatement(_)
+                | Statement::NotifyStatement(_)
+                | Statement::UnlistenStatement(_)
+                | Statement::DeclareCursorStatement(_)
+                | Statement::FetchStatement(_)
+                | Statement::CloseStatement(_)
+                | Statement::TransactionStatement(_)
+        )
+    }
+
+    // The table whose rows an INSERT, UPDATE or DELETE changes
+    pub fn changed_table(&self) -> Option<&str> {
+        match self {
+            Statement::InsertStatement(insert) => Some(&insert.table),
+            Statement::UpdateStatement(update) => Some(&update.table),
+            Statement::DeleteStatement(delete) => Some(&delete.table),
+            _ => None,
+        }
+    }
+
+    // The items of the RETURNING of an INSERT, UPDATE or DELETE, empty without one
+    pub fn returning(&self) -> &[SelectItem] {
+        match self {
+            Statement::InsertStatement(insert) => &insert.returning,
+            Statement::UpdateStatement(update) => &update.returning,
+            Statement::DeleteStatement(delete) => &delete.returning,
+            _ => &[],
+        }
+    }
+
+    // The command as Postgres names it in messages
+    pub fn command(&self) -> &'static str {
+        match self {
+            Statement::SelectStatement(_) => "SELECT",
+            Statement::CreateTableStatement(_) => "CREATE TABLE",
+            Statement::CreateTableAsStatement(_) => "CREATE TABLE AS",
+            Statement::CreateIndexStatement(_) => "CREATE INDEX",
+            Statement::CreateSequenceStatement(_) => "CREATE SEQUENCE",
+            Statement::CreateViewStatement(_) => "CREATE VIEW",
+            Statement::CreateTriggerStatement(_) => "CREATE TRIGGER",
+            Statement::DropTableStatement(_) => "DROP TABLE",
+            Statement::DropIndexStatement(_) => "DROP INDEX",
+            Statement::DropSequenceStatement(_) => "DROP SEQUENCE",
+            Statement::DropViewStatement(_) => "DROP VIEW",
+            Statement::DropTriggerStatement(_) => "DROP TRIGGER",
+            Statement::AlterTableStatement(_) => "ALTER TABLE",
+            Statement::ReindexStatement(_) => "REINDEX",
+            Statement::VacuumStatement(_) => "VACUUM",
+            Statement::ExplainStatement(_) => "EXPLAIN",
+            Statement::InsertStatement(_) => "INSERT",
+            Statement::UpdateStatement(_) => "UPDATE",
+            Statement::DeleteStatement(_) => "DELETE",
+            // Named after what it changes, like Postgres does in read-only transactions
+            Statement::WithStatement(with) => with
+                .writing_statement()
+                .unwrap_or(with.statement.as_ref())
+                .command(),
+            Statement::SetStatement(_) => "SET",
+            Statement::ShowStatement(_) => "SHOW",
+            Statement::ResetStatement(_) => "RESET",
+            Statement::ListenStatement(_) => "LISTEN",
+            Statement::NotifyStatement(_) => "NOTIFY",
+            Statement::UnlistenStatement(_) => "UNLISTEN",
+            Statement::DeclareCursorStatement(_) => "DECLARE CURSOR",
+            Statement::FetchStatement(_) => "FETCH",
+            Statement::CloseStatement(_) => "CLOSE CURSOR",
+            Statement::TransactionStatement(TransactionStatement::Begin) => "BEGIN",
+            Statement::TransactionStatement(TransactionStatement::Commit) => "COMMIT",
+            Statement::TransactionStatement(TransactionStatement::Rollback) => "ROLLBACK",
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -39,16 +158,45 @@ pub enum JoinKind {
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum RowDataSource {
+    // A subquery, which with LATERAL can read the columns of the FROM items before it
     SubSelect {
-        select: SelectStatement,
+        select: Box<SelectStatement>,
         as_clause: String,
+        lateral: bool,
         joins: Vec<JoinClause>,
     },
     Table {
         table_name: String,
         as_clause: Option<String>,
+        sample: Option<TableSample>,
         joins: Vec<JoinClause>,
     },
+    // A constant relation, `(VALUES (1, 'a'), (2, 'b')) AS t(id, name)`
+    Values {
+        rows: Vec<Vec<Expression>>,
+        as_clause: String,
+        column_aliases: Vec<String>,
+        joins: Vec<JoinClause>,
+    },
+    // A function that gives rows, like `generate_series(1, 10) AS g(n)`, its arguments
+    // can read earlier FROM items with LATERAL
+    Function {
+        call: FunctionCall,
+        as_clause: Option<String>,
+        column_aliases: Vec<String>,
+        lateral: bool,
+        joins: Vec<JoinClause>,
+    },
+}
+
+// `TABLESAMPLE method (percentage) REPEATABLE (seed)` after a table in FROM, reading
+// about that percentage of its rows. The method name is lowercase, whether there is such
+// a method is left to the backend.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TableSample {
+    pub method: String,
+    pub percentage: Expression,
+    pub repeatable: Option<Expression>,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -57,6 +205,28 @@ pub struct TableColumn {
     pub table_name: Option<String>,
 }
 
+// A call like `upper(name)`, the name is lowercase unless it was quoted. `count(*)` has
+// no arguments and `asterisk` set. Calls with `over` are window functions. `distinct`
+// and `filter` come from `count(DISTINCT a) FILTER (WHERE b)` and only apply to
+// aggregates.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: Vec<Expression>,
+    pub asterisk: bool,
+    pub distinct: bool,
+    pub filter: Option<Box<Expression>>,
+    pub over: Option<WindowSpec>,
+}
+
+// OVER ([PARTITION BY ...] [ORDER BY ...]), the frame is always the default one: the
+// whole partition, or with ORDER BY, its rows up to the last peer of the current one
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WindowSpec {
+    pub partition_by: Vec<Expression>,
+    pub order_by: Vec<OrderByClause>,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ProcessedTableColumn {
     pub col_name: Option<String>,
@@ -66,20 +236,126 @@ pub struct ProcessedTableColumn {
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct InsertStatement {
     pub table: String,
-    pub values: Vec<Expression>,
+    // One list of values per row, any of which can be DEFAULT
+    pub rows: Vec<Vec<Expression>>,
+    // `INSERT INTO table SELECT ...` takes its rows from the query instead
+    pub query: Option<SelectStatement>,
+    // The items of RETURNING, worked out from the rows inserted, empty without it
+    pub returning: Vec<SelectItem>,
+}
+
+// UPDATE table [[AS] alias] SET column = value, ... [WHERE condition] [ORDER BY
+// expression, ...] [LIMIT count] [RETURNING items]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UpdateStatement {
+    pub table: String,
+    pub as_clause: Option<String>,
+    // In the order given, `(a, b) = (x, y)` giving one for each column
+    pub assignments: Vec<Assignment>,
+    pub where_clause: Expression,
+    // Not in Postgres, as in MySQL and SQLite. Of the rows WHERE holds for only the first
+    // `limit` in this order change, and they change in it. Without ORDER BY they are
+    // the first in the order of the table.
+    pub order_by: Vec<OrderByClause>,
+    pub limit: Option<usize>,
+    // Worked out from the rows as they are after the update
+    pub returning: Vec<SelectItem>,
+}
+
+// DELETE FROM table [[AS] alias] [WHERE condition] [ORDER BY expression, ...]
+// [LIMIT count] [RETURNING items]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DeleteStatement {
+    pub table: String,
+    pub as_clause: Option<String>,
+    pub where_clause: Expression,
+    // Pick the rows that go, like those of an UPDATE
+    pub order_by: Vec<OrderByClause>,
+    pub limit: Option<usize>,
+    // Worked out from the rows deleted
+    pub returning: Vec<SelectItem>,
+}
+
+// WITH name [(columns)] AS (query), ... statement. Each query runs once, before the
+// statement, and can read those before it by name. Queries that change rows, INSERT,
+// UPDATE or DELETE, give the rows of their RETURNING. Like in Postgres all of them read
+// the tables as they were when the statement started, what they change only shows once
+// it is done.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WithStatement {
+    pub queries: Vec<CommonTableExpression>,
+    pub statement: Box<Statement>,
+}
+
+impl WithStatement {
+    // The first of the queries or the statement that changes rows, if any does
+    pub fn writing_statement(&self) -> Option<&Statement> {
+        self.queries
+            .iter()
+            .map(|query| query.statement.as_ref())
+            .chain(std::iter::once(self.statement.as_ref()))
+            .find(|statement| !statement.is_read_only())
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CommonTableExpression {
+    pub name: String,
+    // Names for the columns, the query's own are kept for those not given one
+    pub columns: Vec<String>,
+    pub statement: Box<Statement>,
+}
+
+// A column set by an UPDATE and its new value, which can be DEFAULT
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Assignment {
+    pub column: String,
+    pub value: Expression,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct CreateTableStatement {
     pub name: String,
     pub cols: Vec<ColumnDefinition>,
+    // Those given with a column as well as those given on their own
+    pub checks: Vec<CheckConstraint>,
+    pub is_temporary: bool,
+}
+
+// [CONSTRAINT name] CHECK (expression), unnamed ones get a name made up from the table's
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CheckConstraint {
+    pub name: Option<String>,
+    pub expression: Expression,
+}
+
+// CREATE TABLE name [(columns)] AS SELECT ... [WITH [NO] DATA], or SELECT ... INTO name.
+// The columns take their types from the query, and their names too unless listed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CreateTableAsStatement {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub query: SelectStatement,
+    pub with_data: bool,
+    pub is_temporary: bool,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ColumnDefinition {
     pub name: String,
     pub data_type: TokenContainer,
+    // The length of `VARCHAR(n)` and `CHAR(n)`
+    pub type_modifier: Option<usize>,
     pub is_primary_key: bool,
+    pub default: Option<Expression>,
+    pub not_null: bool,
+    // GENERATED ALWAYS AS (expression) STORED, computed from the other columns of the row
+    pub generated: Option<Expression>,
+    // The name given with COLLATE, the binary collation is used without one
+    pub collation: Option<String>,
+    // SMALLSERIAL, SERIAL or BIGSERIAL, the integer type in `data_type`. The column takes
+    // its values from a sequence of its own.
+    pub serial: bool,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -87,8 +363,11 @@ pub struct SelectStatement {
     pub items: Vec<SelectItem>,
     pub from: Vec<RowDataSource>,
     pub where_clause: Expression,
+    pub group_by: Vec<Expression>,
+    pub having: Expression,
     pub is_distinct: bool,
-    pub order_by: Option<OrderByClause>,
+    // Rows are sorted by the first clause, those equal by it by the next, and so on
+    pub order_by: Vec<OrderByClause>,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
 }
@@ -99,8 +378,10 @@ impl SelectStatement {
             items: Vec::with_capacity(10),
             from: vec![],
             where_clause: Expression::new(),
+            group_by: vec![],
+            having: Expression::new(),
             is_distinct: false,
-            order_by: None,
+            order_by: vec![],
             limit: None,
             offset: None,
         }
@@ -154,9 +435,189 @@ impl CreateConstraintStatement {
     }
 }
 
+// `DROP TABLE name [CASCADE | RESTRICT]`, CASCADE drops the views reading the table too
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct DropTableStatement {
     pub name: String,
+    pub cascade: bool,
+}
+
+// `DROP INDEX [IF EXISTS] name`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DropIndexStatement {
+    pub name: String,
+    pub if_exists: bool,
+}
+
+// `CREATE SEQUENCE name [INCREMENT [BY] n] [MINVALUE n | NO MINVALUE]
+// [MAXVALUE n | NO MAXVALUE] [START [WITH] n] [[NO] CYCLE]`, None where left to the default
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CreateSequenceStatement {
+    pub name: String,
+    pub increment: Option<i64>,
+    pub min_value: Option<i64>,
+    pub max_value: Option<i64>,
+    pub start: Option<i64>,
+    pub cycle: bool,
+}
+
+// `DROP SEQUENCE [IF EXISTS] name`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DropSequenceStatement {
+    pub name: String,
+    pub if_exists: bool,
+}
+
+// `CREATE VIEW name AS query`, with the text of the query as it was written
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CreateViewStatement {
+    pub name: String,
+    pub query: SelectStatement,
+    pub definition: String,
+}
+
+// `DROP VIEW [IF EXISTS] name [CASCADE | RESTRICT]`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DropViewStatement {
+    pub name: String,
+    pub if_exists: bool,
+    pub cascade: bool,
+}
+
+// `CREATE TRIGGER name AFTER event [OR event ...] ON table [FOR [EACH] {ROW | STATEMENT}]
+// EXECUTE (statement)`. A trigger for each row runs the statement for every row changed,
+// which it reads as NEW and as it was before as OLD. One for each statement, the default,
+// runs it once.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CreateTriggerStatement {
+    pub name: String,
+    pub table: String,
+    pub events: Vec<TriggerEvent>,
+    pub for_each_row: bool,
+    pub action: Box<Statement>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TriggerEvent {
+    Insert,
+    Update,
+    Delete,
+}
+
+// `DROP TRIGGER [IF EXISTS] name ON table`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DropTriggerStatement {
+    pub name: String,
+    pub table: String,
+    pub if_exists: bool,
+}
+
+// `REINDEX TABLE name`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ReindexStatement {
+    pub table: String,
+}
+
+// `VACUUM [table]`, every table when none is given
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct VacuumStatement {
+    pub table: Option<String>,
+}
+
+// `EXPLAIN [ANALYZE] select`, ANALYZE runs the query to time its operators
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ExplainStatement {
+    pub analyze: bool,
+    pub select: SelectStatement,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AlterTableStatement {
+    pub name: String,
+    pub action: AlterTableAction,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum AlterTableAction {
+    // `DROP [COLUMN] [IF EXISTS] name [CASCADE | RESTRICT]`, RESTRICT unless CASCADE is
+    // given
+    DropColumn {
+        name: String,
+        if_exists: bool,
+        cascade: bool,
+    },
+}
+
+// `SET [SESSION | LOCAL] name {= | TO} value`, the value being `Token::Default` for
+// `DEFAULT`. A LOCAL setting only lasts until the transaction ends. Where the name and the
+// value start is kept for errors about them.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SetStatement {
+    pub name: String,
+    pub value: Token,
+    pub local: bool,
+    pub name_loc: TokenLocation,
+    pub value_loc: TokenLocation,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ShowStatement {
+    pub name: String,
+    pub name_loc: TokenLocation,
+}
+
+// `RESET name`, or `RESET ALL` which leaves `name` None
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ResetStatement {
+    pub name: Option<String>,
+    pub name_loc: TokenLocation,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ListenStatement {
+    pub channel: String,
+}
+
+// `NOTIFY channel [, 'payload']`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NotifyStatement {
+    pub channel: String,
+    pub payload: Option<String>,
+}
+
+// `UNLISTEN channel`, or `UNLISTEN *` for every channel, which leaves `channel` None
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UnlistenStatement {
+    pub channel: Option<String>,
+}
+
+// `DECLARE name CURSOR FOR query`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DeclareCursorStatement {
+    pub name: String,
+    pub query: SelectStatement,
+}
+
+// `FETCH [NEXT | count | ALL | FORWARD [count | ALL]] [FROM | IN] name`, where ALL leaves
+// `count` None
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FetchStatement {
+    pub name: String,
+    pub count: Option<usize>,
+}
+
+// `CLOSE name`, or `CLOSE ALL` which leaves `name` None
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CloseStatement {
+    pub name: Option<String>,
+}
+
+// BEGIN, COMMIT or ROLLBACK, each of which may be followed by WORK or TRANSACTION
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TransactionStatement {
+    Begin,
+    Commit,
+    Rollback,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -167,7 +628,20 @@ pub enum Expression {
     SubSelect(Box<SelectStatement>),
     TableColumn(TableColumn),
     ProcessedTableColumn(ProcessedTableColumn),
-    Cast { data: Box<Expression>, typ: SqlType },
+    Cast {
+        data: Box<Expression>,
+        typ: SqlType,
+    },
+    // `expression COLLATE name`, which compares, sorts and groups its value by the rules
+    // of the named collation
+    Collate {
+        data: Box<Expression>,
+        collation: String,
+    },
+    FunctionCall(FunctionCall),
+    Quantified(QuantifiedExpression),
+    // `EXISTS (SELECT ...)`, whose subquery may read columns of the query it is in
+    Exists(Box<SelectStatement>),
     Empty,
 }
 
@@ -179,21 +653,21 @@ impl Expression {
     pub fn new_literal_id(value: String) -> Expression {
         Expression::Literal(LiteralExpression {
             literal: Token::IdentifierValue {
-                value: value.clone(),
+                value: value.into(),
             },
         })
     }
     pub fn new_literal_num(value: String) -> Expression {
         Expression::Literal(LiteralExpression {
             literal: Token::NumericValue {
-                value: value.clone(),
+                value: value.into(),
             },
         })
     }
     pub fn new_literal_string(value: String) -> Expression {
         Expression::Literal(LiteralExpression {
             literal: Token::StringValue {
-                value: value.clone(),
+                value: value.into(),
             },
         })
     }
@@ -213,8 +687,8 @@ impl Expression {
     pub fn generate_code(&self) -> Result<String, String> {
         match self {
             Expression::Literal(value) => match &value.literal {
-                Token::IdentifierValue { value } => Ok(format!("\"{}\"", value)),
-                Token::StringValue { value } => Ok(format!("'{}'", value)),
+                Token::IdentifierValue { value } => Ok(quote_identifier(value)),
+                Token::StringValue { value } => Ok(quote_text(value)),
                 _ => Err("Unknown Literal Kind".to_string()),
             },
             Expression::Binary(value) => value.generate_code(),
@@ -253,6 +727,35 @@ impl Expression {
             _ => false,
         }
     }
+
+    // Whether the recovering parser made this expression up where one was missing
+    #[inline]
+    pub fn is_placeholder(&self) -> bool {
+        match self {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => value == PLACEHOLDER_NAME,
+            Expression::TableColumn(column) => column.col_name == PLACEHOLDER_NAME,
+            _ => false,
+        }
+    }
+}
+
+// `first = ANY (SELECT ...)`, or with ALL, and any comparison in place of `=`. SOME is
+// the same as ANY. The subquery does not depend on the outer row, so it is run once and
+// swapped for the values it gave before any row is looked at.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct QuantifiedExpression {
+    pub first: Box<Expression>,
+    pub operand: Token,
+    pub all: bool,
+    pub set: QuantifiedSet,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum QuantifiedSet {
+    SubSelect(Box<SelectStatement>),
+    Values(Vec<SqlValue>),
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -292,6 +795,22 @@ impl UnaryExpression {
             self.first.generate_code()?,
         ))
     }
+
+    // The number a `-` before a numeric literal gives, as Postgres types it like a literal
+    // of its own so that e.g. -2147483648 is still an INT
+    pub fn negated_literal(&self) -> Option<Token> {
+        match (&self.operand, self.first.as_ref()) {
+            (
+                Token::Minus,
+                Expression::Literal(LiteralExpression {
+                    literal: Token::NumericValue { value },
+                }),
+            ) if !value.starts_with('-') => Some(Token::NumericValue {
+                value: format!("-{}", value).into(),
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl Token {
@@ -318,7 +837,7 @@ impl Token {
             Token::From => FROM_KEYWORD.to_string(),
             Token::GreaterThan => GREATER_THAN_SYMBOL.to_string(),
             Token::GreaterThanOrEqual => GREATER_THAN_OR_EQUAL_SYMBOL.to_string(),
-            Token::IdentifierValue { value } => format!("\"{}\"", value),
+            Token::IdentifierValue { value } => quote_identifier(value),
             Token::Index => INDEX_KEYWORD.to_string(),
             Token::Insert => INSERT_KEYWORD.to_string(),
             Token::Int => INT_KEYWORD.to_string(),
@@ -330,7 +849,8 @@ impl Token {
             Token::Minus => MINUS_SYMBOL.to_string(),
             Token::NotEqual => NOT_EQUAL_SYMBOL.to_string(),
             Token::Null => NULL_KEYWORD.to_string(),
-            Token::NumericValue { value } => value.clone(),
+            Token::NumericValue { value } => value.to_string(),
+            Token::Parameter { index } => format!("${}", index),
             Token::On => ON_KEYWORD.to_string(),
             Token::Or => OR_KEYWORD.to_string(),
             Token::Not => NOT_KEYWORD.to_string(),
@@ -347,13 +867,17 @@ impl Token {
             Token::BitwiseOr => BITWISE_OR_SYMBOL.to_string(),
             Token::BitwiseXor => BITWISE_XOR_SYMBOL.to_string(),
             Token::BitwiseNot => BITWISE_NOT_SYMBOL.to_string(),
+            Token::RegexMatch => BITWISE_NOT_SYMBOL.to_string(),
+            Token::RegexMatchInsensitive => REGEX_MATCH_INSENSITIVE_SYMBOL.to_string(),
+            Token::RegexNotMatch => REGEX_NOT_MATCH_SYMBOL.to_string(),
+            Token::RegexNotMatchInsensitive => REGEX_NOT_MATCH_INSENSITIVE_SYMBOL.to_string(),
             Token::BitwiseShiftLeft => BITWISE_SHIFT_LEFT_SYMBOL.to_string(),
             Token::BitwiseShiftRight => BITWISE_SHIFT_RIGHT_SYMBOL.to_string(),
             Token::Primary => PRIMARY_KEYWORD.to_string(),
             Token::RightParenthesis => RIGHT_PARENTHESIS_SYMBOL.to_string(),
             Token::Select => SELECT_KEYWORD.to_string(),
             Token::Semicolon => SEMICOLON_SYMBOL.to_string(),
-            Token::StringValue { value } => format!("'{}'", value),
+            Token::StringValue { value } => quote_text(value),
             Token::Table => TABLE_KEYWORD.to_string(),
             Token::Text => TEXT_KEYWORD.to_string(),
             Token::True => TRUE_KEYWORD.to_string(),
@@ -390,6 +914,58 @@ impl Token {
             Token::Dot => DOT_SYMBOL.to_string(),
             Token::Outer => OUTER_KEYWORD.to_string(),
             Token::Full => FULL_KEYWORD.to_string(),
+            Token::With => WITH_KEYWORD.to_string(),
+            Token::No => NO_KEYWORD.to_string(),
+            Token::Data => DATA_KEYWORD.to_string(),
+            Token::Temporary => TEMPORARY_KEYWORD.to_string(),
+            Token::Over => OVER_KEYWORD.to_string(),
+            Token::Partition => PARTITION_KEYWORD.to_string(),
+            Token::Rows => ROWS_KEYWORD.to_string(),
+            Token::Any => ANY_KEYWORD.to_string(),
+            Token::All => ALL_KEYWORD.to_string(),
+            Token::Group => GROUP_KEYWORD.to_string(),
+            Token::GroupBy => "GROUP BY".to_string(),
+            Token::Having => HAVING_KEYWORD.to_string(),
+            Token::Filter => FILTER_KEYWORD.to_string(),
+            Token::Default => DEFAULT_KEYWORD.to_string(),
+            Token::Check => CHECK_KEYWORD.to_string(),
+            Token::Set => SET_KEYWORD.to_string(),
+            Token::Show => SHOW_KEYWORD.to_string(),
+            Token::To => TO_KEYWORD.to_string(),
+            Token::If => IF_KEYWORD.to_string(),
+            Token::Exists => EXISTS_KEYWORD.to_string(),
+            Token::Column => COLUMN_KEYWORD.to_string(),
+            Token::Cascade => CASCADE_KEYWORD.to_string(),
+            Token::Restrict => RESTRICT_KEYWORD.to_string(),
+            Token::Reindex => REINDEX_KEYWORD.to_string(),
+            Token::Vacuum => VACUUM_KEYWORD.to_string(),
+            Token::Explain => EXPLAIN_KEYWORD.to_string(),
+            Token::Analyze => ANALYZE_KEYWORD.to_string(),
+            Token::Listen => LISTEN_KEYWORD.to_string(),
+            Token::Notify => NOTIFY_KEYWORD.to_string(),
+            Token::Unlisten => UNLISTEN_KEYWORD.to_string(),
+            Token::Reset => RESET_KEYWORD.to_string(),
+            Token::Generated => GENERATED_KEYWORD.to_string(),
+            Token::Always => ALWAYS_KEYWORD.to_string(),
+            Token::Stored => STORED_KEYWORD.to_string(),
+            Token::Collate => COLLATE_KEYWORD.to_string(),
+            Token::Lateral => LATERAL_KEYWORD.to_string(),
+            Token::Tablesample => TABLESAMPLE_KEYWORD.to_string(),
+            Token::Declare => DECLARE_KEYWORD.to_string(),
+            Token::Cursor => CURSOR_KEYWORD.to_string(),
+            Token::Close => CLOSE_KEYWORD.to_string(),
+            Token::Fetch => FETCH_KEYWORD.to_string(),
+            Token::Next => NEXT_KEYWORD.to_string(),
+            Token::Forward => FORWARD_KEYWORD.to_string(),
+            Token::For => FOR_KEYWORD.to_string(),
+            Token::In => IN_KEYWORD.to_string(),
+            Token::Returning => RETURNING_KEYWORD.to_string(),
+            Token::Similar => format!("{} {}", SIMILAR_KEYWORD, TO_KEYWORD),
+            Token::Begin => BEGIN_KEYWORD.to_string(),
+            Token::Commit => COMMIT_KEYWORD.to_string(),
+            Token::Rollback => ROLLBACK_KEYWORD.to_string(),
+            Token::Transaction => TRANSACTION_KEYWORD.to_string(),
+            Token::Work => WORK_KEYWORD.to_string(),
             Token::Comment => "".to_string(),
         }
     }
@@ -400,6 +976,19 @@ pub struct SelectItem {
     pub expression: Expression,
     pub as_clause: Option<String>,
     pub asterisk: bool,
+    // The FROM item of `name.*`, None for a bare `*` going over every one of them
+    pub asterisk_source: Option<String>,
+    // EXCEPT and REPLACE after a `*`
+    pub asterisk_modifiers: Option<AsteriskModifiers>,
+}
+
+// The columns `*` leaves out, by name or qualified with their FROM item, and the items
+// it lists in place of columns. A replacement is named by its AS after the columns of
+// that name it takes the place of.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct AsteriskModifiers {
+    pub except: Vec<TableColumn>,
+    pub replace: Vec<SelectItem>,
 }
 
 impl SelectItem {
@@ -408,6 +997,8 @@ impl SelectItem {
             expression: Expression::new(),
             as_clause: None,
             asterisk: false,
+            asterisk_source: None,
+            asterisk_modifiers: None,
         }
     }
 }
@@ -430,18 +1021,20 @@ mod ast_tests {
                 ast: Ast {
                     statements: vec![Statement::InsertStatement(InsertStatement {
                         table: "users".to_owned(),
-                        values: vec![
+                        rows: vec![vec![
                             Expression::Literal(LiteralExpression {
                                 literal: Token::NumericValue {
-                                    value: "105".to_owned(),
+                                    value: "105".into(),
                                 },
                             }),
                             Expression::Literal(LiteralExpression {
                                 literal: Token::StringValue {
-                                    value: "George".to_owned(),
+                                    value: "George".into(),
                                 },
                             }),
-                        ],
+                        ]],
+                        query: None,
+                        returning: vec![],
                     })],
                 },
             },
@@ -456,18 +1049,42 @@ mod ast_tests {
                                 data_type: TokenContainer {
                                     loc: TokenLocation { col: 23, line: 0 },
                                     token: Token::Int,
+                                    span: Span {
+                                        start: 23,
+                                        end: 26,
+                                        end_loc: TokenLocation { col: 26, line: 0 },
+                                    },
                                 },
+                                type_modifier: None,
                                 is_primary_key: false,
+                                default: None,
+                                not_null: false,
+                                generated: None,
+                                collation: None,
+                                serial: false,
                             },
                             ColumnDefinition {
                                 name: "name".to_owned(),
                                 data_type: TokenContainer {
                                     loc: TokenLocation { col: 33, line: 0 },
                                     token: Token::Text,
+                                    span: Span {
+                                        start: 33,
+                                        end: 37,
+                                        end_loc: TokenLocation { col: 37, line: 0 },
+                                    },
                                 },
+                                type_modifier: None,
                                 is_primary_key: false,
+                                default: None,
+                                not_null: false,
+                                generated: None,
+                                collation: None,
+                                serial: false,
                             },
                         ],
+                        checks: vec![],
+                        is_temporary: false,
                     })],
                 },
             },
@@ -478,6 +1095,8 @@ mod ast_tests {
                         items: vec![
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: None,
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "id".to_owned(),
@@ -486,6 +1105,8 @@ mod ast_tests {
                             },
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: Some("fullname".to_owned()),
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "name".to_owned(),
@@ -496,16 +1117,110 @@ mod ast_tests {
                         from: vec![RowDataSource::Table {
                             table_name: "users".to_string(),
                             as_clause: None,
+                            sample: None,
                             joins: vec![],
                         }],
                         where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
                         is_distinct: false,
-                        order_by: None,
+                        order_by: vec![],
                         limit: None,
                         offset: None,
                     })],
                 },
             },
+            ParseTest {
+                input: "UPDATE users u SET (id, name) = (105, u.name);",
+                ast: Ast {
+                    statements: vec![Statement::UpdateStatement(UpdateStatement {
+                        table: "users".to_owned(),
+                        as_clause: Some("u".to_owned()),
+                        assignments: vec![
+                            Assignment {
+                                column: "id".to_owned(),
+                                value: Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue {
+                                        value: "105".into(),
+                                    },
+                                }),
+                            },
+                            Assignment {
+                                column: "name".to_owned(),
+                                value: Expression::TableColumn(TableColumn {
+                                    col_name: "name".to_owned(),
+                                    table_name: Some("u".to_owned()),
+                                }),
+                            },
+                        ],
+                        where_clause: Expression::Empty,
+                        order_by: vec![],
+                        limit: None,
+                        returning: vec![],
+                    })],
+                },
+            },
+            ParseTest {
+                input: "DELETE FROM users ORDER BY id DESC, name LIMIT 10;",
+                ast: Ast {
+                    statements: vec![Statement::DeleteStatement(DeleteStatement {
+                        table: "users".to_owned(),
+                        as_clause: None,
+                        where_clause: Expression::Empty,
+                        order_by: vec![
+                            OrderByClause {
+                                asc: false,
+                                exp: Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                }),
+                            },
+                            OrderByClause {
+                                asc: true,
+                                exp: Expression::TableColumn(TableColumn {
+                                    col_name: "name".to_owned(),
+                                    table_name: None,
+                                }),
+                            },
+                        ],
+                        limit: Some(10),
+                        returning: vec![],
+                    })],
+                },
+            },
+            ParseTest {
+                input: "DELETE FROM users d WHERE d.id = 105 RETURNING name AS gone;",
+                ast: Ast {
+                    statements: vec![Statement::DeleteStatement(DeleteStatement {
+                        table: "users".to_owned(),
+                        as_clause: Some("d".to_owned()),
+                        where_clause: Expression::Binary(BinaryExpression {
+                            first: Box::new(Expression::TableColumn(TableColumn {
+                                col_name: "id".to_owned(),
+                                table_name: Some("d".to_owned()),
+                            })),
+                            second: Box::new(Expression::Literal(LiteralExpression {
+                                literal: Token::NumericValue {
+                                    value: "105".into(),
+                                },
+                            })),
+                            operand: Token::Equal,
+                        }),
+                        order_by: vec![],
+                        limit: None,
+                        returning: vec![SelectItem {
+                            expression: Expression::TableColumn(TableColumn {
+                                col_name: "name".to_owned(),
+                                table_name: None,
+                            }),
+                            as_clause: Some("gone".to_owned()),
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                        }],
+                    })],
+                },
+            },
         ];
 
         let mut found_faults = false;
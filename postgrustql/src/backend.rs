@@ -1,22 +1,45 @@
-use byteorder::{BigEndian, ReadBytesExt};
-
-use crate::sql_types::{SqlType, SqlValue};
+use crate::lexer::TokenLocation;
+use crate::parser::ParsingError;
+use crate::row::MapError;
+use crate::sql_types::{SqlNumeric, SqlType, SqlTypeError, SqlValue};
 
 use super::ast::*;
 use serde::{Deserialize, Serialize};
-use std::{io::Read, time::Duration};
+use std::time::Duration;
 
 pub trait Cell {
+    // Cells of text never fail to read as text, their values were checked when they came in
     fn as_text(&self) -> Result<String, &str>;
     fn as_int(&self) -> Result<i32, &str>;
     fn as_num(&self, typ: SqlType) -> Result<f64, &str>;
     fn as_bool(&self) -> Result<bool, &str>;
     fn equals(&self, other: Self) -> bool;
 }
-#[derive(Debug, Clone, Deserialize, Serialize)]
+// A column of query results. Its name is the AS name of the select item if it has one,
+// otherwise the name Postgres would give it: the column read, the function called, or
+// `?column?`. A column read from a table is named `table.column` instead when another
+// result column of the same name comes from a different FROM item, so both `id`s of
+// `a JOIN b` come out as `a.id` and `b.id`. `*` lists the columns of each FROM item in
+// the order they were declared.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ResultColumn {
     pub col_type: SqlType,
     pub name: String,
+    // The length a column read from a table was declared with, like the 20 of VARCHAR(20)
+    pub type_modifier: Option<usize>,
+    // Whether the column can hold NULL. Only columns read from a NOT NULL or primary key
+    // column can't, unless they are on the nullable side of an outer join.
+    pub nullable: bool,
+    // The column of a table the column reads as it is, through aliases, subqueries and
+    // views. None for expressions, aggregates among them.
+    pub origin: Option<ColumnOrigin>,
+}
+
+// A column of a table, temporary or not, that a result column comes from
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ColumnOrigin {
+    pub table: String,
+    pub column: String,
 }
 
 impl From<SqlType> for String {
@@ -66,19 +89,260 @@ pub enum EvalResult<C> {
     Select {
         results: QueryResults<C>,
         time: Duration,
+        warnings: Vec<Warning>,
     },
     Insert {
         success: bool,
         time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Update {
+        rows_updated: usize,
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Delete {
+        rows_deleted: usize,
+        time: Duration,
+        warnings: Vec<Warning>,
     },
     CreateTable {
         success: bool,
         time: Duration,
+        warnings: Vec<Warning>,
+    },
+    CreateTableAs {
+        success: bool,
+        rows_inserted: usize,
+        time: Duration,
+        warnings: Vec<Warning>,
     },
     DropTable {
         success: bool,
         time: Duration,
+        warnings: Vec<Warning>,
+    },
+    DropIndex {
+        success: bool,
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    AlterTable {
+        success: bool,
+        time: Duration,
+        warnings: Vec<Warning>,
     },
+    Reindex {
+        success: bool,
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    // What VACUUM freed, see `VacuumStats`
+    Vacuum {
+        rows_reclaimed: usize,
+        bytes_reclaimed: usize,
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Set {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Listen {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Notify {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Unlisten {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    DeclareCursor {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    CloseCursor {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Begin {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    Commit {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+    // Also what COMMIT gives when the transaction failed, which it can only roll back
+    Rollback {
+        time: Duration,
+        warnings: Vec<Warning>,
+    },
+}
+
+impl<C> EvalResult<C> {
+    // What the statement warned about as it ran, once for every distinct condition, in the
+    // order they were first met
+    pub fn warnings(&self) -> &[Warning] {
+        match self {
+            EvalResult::Select { warnings, .. }
+            | EvalResult::Insert { warnings, .. }
+            | EvalResult::Update { warnings, .. }
+            | EvalResult::Delete { warnings, .. }
+            | EvalResult::CreateTable { warnings, .. }
+            | EvalResult::CreateTableAs { warnings, .. }
+            | EvalResult::DropTable { warnings, .. }
+            | EvalResult::DropIndex { warnings, .. }
+            | EvalResult::AlterTable { warnings, .. }
+            | EvalResult::Reindex { warnings, .. }
+            | EvalResult::Vacuum { warnings, .. }
+            | EvalResult::Set { warnings, .. }
+            | EvalResult::Listen { warnings, .. }
+            | EvalResult::Notify { warnings, .. }
+            | EvalResult::Unlisten { warnings, .. }
+            | EvalResult::DeclareCursor { warnings, .. }
+            | EvalResult::CloseCursor { warnings, .. }
+            | EvalResult::Begin { warnings, .. }
+            | EvalResult::Commit { warnings, .. }
+            | EvalResult::Rollback { warnings, .. } => warnings,
+        }
+    }
+
+    pub(crate) fn warnings_mut(&mut self) -> &mut Vec<Warning> {
+        match self {
+            EvalResult::Select { warnings, .. }
+            | EvalResult::Insert { warnings, .. }
+            | EvalResult::Update { warnings, .. }
+            | EvalResult::Delete { warnings, .. }
+            | EvalResult::CreateTable { warnings, .. }
+            | EvalResult::CreateTableAs { warnings, .. }
+            | EvalResult::DropTable { warnings, .. }
+            | EvalResult::DropIndex { warnings, .. }
+            | EvalResult::AlterTable { warnings, .. }
+            | EvalResult::Reindex { warnings, .. }
+            | EvalResult::Vacuum { warnings, .. }
+            | EvalResult::Set { warnings, .. }
+            | EvalResult::Listen { warnings, .. }
+            | EvalResult::Notify { warnings, .. }
+            | EvalResult::Unlisten { warnings, .. }
+            | EvalResult::DeclareCursor { warnings, .. }
+            | EvalResult::CloseCursor { warnings, .. }
+            | EvalResult::Begin { warnings, .. }
+            | EvalResult::Commit { warnings, .. }
+            | EvalResult::Rollback { warnings, .. } => warnings,
+        }
+    }
+
+    // How long the statement took to plan and run, lexing and parsing left out, see
+    // `QueryTiming`. Zero when the backend doesn't time statements.
+    pub fn time(&self) -> Duration {
+        match self {
+            EvalResult::Select { time, .. }
+            | EvalResult::Insert { time, .. }
+            | EvalResult::Update { time, .. }
+            | EvalResult::Delete { time, .. }
+            | EvalResult::CreateTable { time, .. }
+            | EvalResult::CreateTableAs { time, .. }
+            | EvalResult::DropTable { time, .. }
+            | EvalResult::DropIndex { time, .. }
+            | EvalResult::AlterTable { time, .. }
+            | EvalResult::Reindex { time, .. }
+            | EvalResult::Vacuum { time, .. }
+            | EvalResult::Set { time, .. }
+            | EvalResult::Listen { time, .. }
+            | EvalResult::Notify { time, .. }
+            | EvalResult::Unlisten { time, .. }
+            | EvalResult::DeclareCursor { time, .. }
+            | EvalResult::CloseCursor { time, .. }
+            | EvalResult::Begin { time, .. }
+            | EvalResult::Commit { time, .. }
+            | EvalResult::Rollback { time, .. } => *time,
+        }
+    }
+
+    pub(crate) fn time_mut(&mut self) -> &mut Duration {
+        match self {
+            EvalResult::Select { time, .. }
+            | EvalResult::Insert { time, .. }
+            | EvalResult::Update { time, .. }
+            | EvalResult::Delete { time, .. }
+            | EvalResult::CreateTable { time, .. }
+            | EvalResult::CreateTableAs { time, .. }
+            | EvalResult::DropTable { time, .. }
+            | EvalResult::DropIndex { time, .. }
+            | EvalResult::AlterTable { time, .. }
+            | EvalResult::Reindex { time, .. }
+            | EvalResult::Vacuum { time, .. }
+            | EvalResult::Set { time, .. }
+            | EvalResult::Listen { time, .. }
+            | EvalResult::Notify { time, .. }
+            | EvalResult::Unlisten { time, .. }
+            | EvalResult::DeclareCursor { time, .. }
+            | EvalResult::CloseCursor { time, .. }
+            | EvalResult::Begin { time, .. }
+            | EvalResult::Commit { time, .. }
+            | EvalResult::Rollback { time, .. } => time,
+        }
+    }
+}
+
+// Where the session stands with transactions between statements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransactionStatus {
+    Idle,
+    // BEGIN started a transaction that COMMIT or ROLLBACK haven't ended yet
+    InTransaction,
+    // A statement of the transaction failed, every other one fails until it is ended
+    Failed,
+}
+
+impl TransactionStatus {
+    // The status byte of Postgres' ReadyForQuery message
+    pub fn ready_for_query(self) -> u8 {
+        match self {
+            TransactionStatus::Idle => b'I',
+            TransactionStatus::InTransaction => b'T',
+            TransactionStatus::Failed => b'E',
+        }
+    }
+}
+
+// A condition a statement met that didn't stop it, like a value changed to fit where it
+// went. `code` is the SQLSTATE Postgres would send with it, of class 01 like its
+// warnings, which lets a server send it on as a NoticeResponse.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Warning {
+    pub code: String,
+    pub message: String,
+    // Where in the query it is about, when that is known
+    pub location: Option<TokenLocation>,
+}
+
+impl Warning {
+    pub fn new(code: &str, message: String) -> Self {
+        Warning {
+            code: code.to_string(),
+            message,
+            location: None,
+        }
+    }
+
+    pub fn at(mut self, location: TokenLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some(loc) => write!(f, "[{}, {}]: {}", loc.line + 1, loc.col + 1, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
 }
 
 pub type ResultColumns = Vec<ResultColumn>;
@@ -91,15 +355,375 @@ pub struct QueryResults<C> {
 
 pub const ERR_TABLE_DOES_NOT_EXIST: &'static str = "Table does not exist.";
 pub const ERR_COLUMN_DOES_NOT_EXIST: &'static str = "Column does not exist.";
+pub const ERR_COLUMN_NOT_IN_CONTEXT: &str = "Column does not exist in this context.";
+pub const ERR_FUNCTION_DOES_NOT_EXIST: &str = "Function does not exist.";
 pub const ERR_INVALID_SELECT_ITEM: &'static str = "Select item is not valid.";
 pub const ERR_INVALID_DATA_TYPE: &'static str = "Invalid data type.";
 pub const ERR_MISSING_VALUES: &'static str = "Missing values.";
+pub const ERR_RESULT_LIMIT_EXCEEDED: &str = "Result limit exceeded.";
+pub const ERR_WINDOW_FUNCTION_NOT_ALLOWED: &str = "Window functions are not allowed here.";
+pub const ERR_WINDOW_FUNCTION_IN_WHERE: &str = "Window functions are not allowed in WHERE.";
+pub const ERR_AGGREGATE_FUNCTION_IN_WHERE: &str = "Aggregate functions are not allowed in WHERE.";
+pub const ERR_AGGREGATE_FUNCTION_IN_GROUP_BY: &str =
+    "Aggregate functions are not allowed in GROUP BY.";
+pub const ERR_NESTED_AGGREGATE: &str = "Aggregate function calls cannot be nested.";
+pub const ERR_COLUMN_NOT_GROUPED: &str =
+    "Column must appear in the GROUP BY clause or be used in an aggregate function.";
+pub const ERR_SUBQUERY_IN_CHECK: &str = "Cannot use subquery in check constraint.";
+pub const ERR_OTHER_TABLE_IN_CHECK: &str =
+    "Check constraints can only use columns of their own table.";
+pub const ERR_AGGREGATE_FUNCTION_IN_CHECK: &str =
+    "Aggregate functions are not allowed in check constraints.";
+pub const ERR_WINDOW_FUNCTION_IN_CHECK: &str =
+    "Window functions are not allowed in check constraints.";
+pub const ERR_SUBQUERY_IN_GENERATED: &str = "Cannot use subquery in column generation expression.";
+pub const ERR_OTHER_TABLE_IN_GENERATED: &str =
+    "Column generation expressions can only use columns of their own table.";
+pub const ERR_AGGREGATE_FUNCTION_IN_GENERATED: &str =
+    "Aggregate functions are not allowed in column generation expressions.";
+pub const ERR_WINDOW_FUNCTION_IN_GENERATED: &str =
+    "Window functions are not allowed in column generation expressions.";
+pub const ERR_CORRELATED_SUBQUERY_NOT_SUPPORTED: &str =
+    "Subqueries reading columns of the outer query are only supported as conditions of WHERE.";
+pub const ERR_ASTERISK_WITHOUT_FROM: &str = "SELECT * with no tables specified is not valid.";
+
+// An error of the backend. Each kind carries the message and reports the SQLSTATE code
+// Postgres uses for that class of error, so clients can tell them apart without parsing
+// messages.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BackendError {
+    NoData(String),
+    ProtocolViolation(String),
+    FeatureNotSupported(String),
+    CardinalityViolation(String),
+    StringDataRightTruncation(String),
+    NumericValueOutOfRange(String),
+    SequenceGeneratorLimitExceeded(String),
+    DivisionByZero(String),
+    CharacterNotInRepertoire(String),
+    InvalidParameterValue(String),
+    InvalidTablesampleRepeat(String),
+    InvalidTablesampleArgument(String),
+    InvalidTextRepresentation(String),
+    UntranslatableCharacter(String),
+    InvalidRegularExpression(String),
+    NotNullViolation(String),
+    ForeignKeyViolation(String),
+    UniqueViolation(String),
+    CheckViolation(String),
+    InvalidCursorState(String),
+    ReadOnlySqlTransaction(String),
+    ActiveSqlTransaction(String),
+    NoActiveSqlTransaction(String),
+    InFailedSqlTransaction(String),
+    DependentObjectsStillExist(String),
+    InvalidCursorName(String),
+    SerializationFailure(String),
+    SyntaxError(String),
+    InsufficientPrivilege(String),
+    GeneratedAlways(String),
+    UndefinedColumn(String),
+    UndefinedFunction(String),
+    UndefinedTable(String),
+    DuplicateColumn(String),
+    DuplicateTable(String),
+    DuplicateCursor(String),
+    DuplicateObject(String),
+    DuplicateAlias(String),
+    NameTooLong(String),
+    UndefinedObject(String),
+    InvalidColumnReference(String),
+    InvalidTableDefinition(String),
+    DatatypeMismatch(String),
+    WrongObjectType(String),
+    GroupingError(String),
+    WindowingError(String),
+    CollationMismatch(String),
+    IndeterminateCollation(String),
+    IndeterminateDatatype(String),
+    ProgramLimitExceeded(String),
+    ObjectNotInPrerequisiteState(String),
+    LockNotAvailable(String),
+    QueryCanceled(String),
+    CantChangeRuntimeParam(String),
+    IoError(String),
+    Internal(String),
+}
+
+impl BackendError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BackendError::NoData(_) => "02000",
+            BackendError::ProtocolViolation(_) => "08P01",
+            BackendError::FeatureNotSupported(_) => "0A000",
+            BackendError::CardinalityViolation(_) => "21000",
+            BackendError::StringDataRightTruncation(_) => "22001",
+            BackendError::NumericValueOutOfRange(_) => "22003",
+            BackendError::SequenceGeneratorLimitExceeded(_) => "2200H",
+            BackendError::DivisionByZero(_) => "22012",
+            BackendError::CharacterNotInRepertoire(_) => "22021",
+            BackendError::InvalidParameterValue(_) => "22023",
+            BackendError::InvalidTablesampleRepeat(_) => "2202G",
+            BackendError::InvalidTablesampleArgument(_) => "2202H",
+            BackendError::InvalidTextRepresentation(_) => "22P02",
+            BackendError::UntranslatableCharacter(_) => "22P05",
+            BackendError::InvalidRegularExpression(_) => "2201B",
+            BackendError::NotNullViolation(_) => "23502",
+            BackendError::ForeignKeyViolation(_) => "23503",
+            BackendError::UniqueViolation(_) => "23505",
+            BackendError::CheckViolation(_) => "23514",
+            BackendError::InvalidCursorState(_) => "24000",
+            BackendError::ReadOnlySqlTransaction(_) => "25006",
+            BackendError::ActiveSqlTransaction(_) => "25001",
+            BackendError::NoActiveSqlTransaction(_) => "25P01",
+            BackendError::InFailedSqlTransaction(_) => "25P02",
+            BackendError::DependentObjectsStillExist(_) => "2BP01",
+            BackendError::InvalidCursorName(_) => "34000",
+            BackendError::SerializationFailure(_) => "40001",
+            BackendError::SyntaxError(_) => "42601",
+            BackendError::InsufficientPrivilege(_) => "42501",
+            BackendError::GeneratedAlways(_) => "428C9",
+            BackendError::UndefinedColumn(_) => "42703",
+            BackendError::UndefinedFunction(_) => "42883",
+            BackendError::UndefinedTable(_) => "42P01",
+            BackendError::DuplicateColumn(_) => "42701",
+            BackendError::DuplicateTable(_) => "42P07",
+            BackendError::DuplicateCursor(_) => "42P03",
+            BackendError::DuplicateObject(_) => "42710",
+            BackendError::DuplicateAlias(_) => "42712",
+            BackendError::NameTooLong(_) => "42622",
+            BackendError::UndefinedObject(_) => "42704",
+            BackendError::InvalidColumnReference(_) => "42P10",
+            BackendError::InvalidTableDefinition(_) => "42P16",
+            BackendError::DatatypeMismatch(_) => "42804",
+            BackendError::WrongObjectType(_) => "42809",
+            BackendError::GroupingError(_) => "42803",
+            BackendError::WindowingError(_) => "42P20",
+            BackendError::CollationMismatch(_) => "42P21",
+            BackendError::IndeterminateCollation(_) => "42P22",
+            BackendError::IndeterminateDatatype(_) => "42P18",
+            BackendError::ProgramLimitExceeded(_) => "54000",
+            BackendError::ObjectNotInPrerequisiteState(_) => "55000",
+            BackendError::LockNotAvailable(_) => "55P03",
+            BackendError::QueryCanceled(_) => "57014",
+            BackendError::CantChangeRuntimeParam(_) => "55P02",
+            BackendError::IoError(_) => "58030",
+            BackendError::Internal(_) => "XX000",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            BackendError::NoData(msg)
+            | BackendError::ProtocolViolation(msg)
+            | BackendError::FeatureNotSupported(msg)
+            | BackendError::CardinalityViolation(msg)
+            | BackendError::StringDataRightTruncation(msg)
+            | BackendError::NumericValueOutOfRange(msg)
+            | BackendError::SequenceGeneratorLimitExceeded(msg)
+            | BackendError::DivisionByZero(msg)
+            | BackendError::CharacterNotInRepertoire(msg)
+            | BackendError::InvalidParameterValue(msg)
+            | BackendError::InvalidTablesampleRepeat(msg)
+            | BackendError::InvalidTablesampleArgument(msg)
+            | BackendError::InvalidTextRepresentation(msg)
+            | BackendError::UntranslatableCharacter(msg)
+            | BackendError::InvalidRegularExpression(msg)
+            | BackendError::NotNullViolation(msg)
+            | BackendError::ForeignKeyViolation(msg)
+            | BackendError::UniqueViolation(msg)
+            | BackendError::CheckViolation(msg)
+            | BackendError::InvalidCursorState(msg)
+            | BackendError::ReadOnlySqlTransaction(msg)
+            | BackendError::ActiveSqlTransaction(msg)
+            | BackendError::NoActiveSqlTransaction(msg)
+            | BackendError::InFailedSqlTransaction(msg)
+            | BackendError::DependentObjectsStillExist(msg)
+            | BackendError::InvalidCursorName(msg)
+            | BackendError::SerializationFailure(msg)
+            | BackendError::SyntaxError(msg)
+            | BackendError::InsufficientPrivilege(msg)
+            | BackendError::GeneratedAlways(msg)
+            | BackendError::UndefinedColumn(msg)
+            | BackendError::UndefinedFunction(msg)
+            | BackendError::UndefinedTable(msg)
+            | BackendError::DuplicateColumn(msg)
+            | BackendError::DuplicateTable(msg)
+            | BackendError::DuplicateCursor(msg)
+            | BackendError::DuplicateObject(msg)
+            | BackendError::DuplicateAlias(msg)
+            | BackendError::NameTooLong(msg)
+            | BackendError::UndefinedObject(msg)
+            | BackendError::InvalidColumnReference(msg)
+            | BackendError::InvalidTableDefinition(msg)
+            | BackendError::DatatypeMismatch(msg)
+            | BackendError::WrongObjectType(msg)
+            | BackendError::GroupingError(msg)
+            | BackendError::WindowingError(msg)
+            | BackendError::CollationMismatch(msg)
+            | BackendError::IndeterminateCollation(msg)
+            | BackendError::IndeterminateDatatype(msg)
+            | BackendError::ProgramLimitExceeded(msg)
+            | BackendError::ObjectNotInPrerequisiteState(msg)
+            | BackendError::LockNotAvailable(msg)
+            | BackendError::QueryCanceled(msg)
+            | BackendError::CantChangeRuntimeParam(msg)
+            | BackendError::IoError(msg)
+            | BackendError::Internal(msg) => msg,
+        }
+    }
+
+    // The same kind of error with its message rewritten, e.g. to add context
+    pub fn map_message(self, f: impl FnOnce(String) -> String) -> Self {
+        let (kind, msg): (fn(String) -> Self, String) = match self {
+            BackendError::NoData(msg) => (BackendError::NoData, msg),
+            BackendError::ProtocolViolation(msg) => (BackendError::ProtocolViolation, msg),
+            BackendError::FeatureNotSupported(msg) => (BackendError::FeatureNotSupported, msg),
+            BackendError::CardinalityViolation(msg) => (BackendError::CardinalityViolation, msg),
+            BackendError::StringDataRightTruncation(msg) => {
+                (BackendError::StringDataRightTruncation, msg)
+            }
+            BackendError::NumericValueOutOfRange(msg) => {
+                (BackendError::NumericValueOutOfRange, msg)
+            }
+            BackendError::SequenceGeneratorLimitExceeded(msg) => {
+                (BackendError::SequenceGeneratorLimitExceeded, msg)
+            }
+            BackendError::DivisionByZero(msg) => (BackendError::DivisionByZero, msg),
+            BackendError::CharacterNotInRepertoire(msg) => {
+                (BackendError::CharacterNotInRepertoire, msg)
+            }
+            BackendError::InvalidParameterValue(msg) => (BackendError::InvalidParameterValue, msg),
+            BackendError::InvalidTablesampleRepeat(msg) => {
+                (BackendError::InvalidTablesampleRepeat, msg)
+            }
+            BackendError::InvalidTablesampleArgument(msg) => {
+                (BackendError::InvalidTablesampleArgument, msg)
+            }
+            BackendError::InvalidTextRepresentation(msg) => {
+                (BackendError::InvalidTextRepresentation, msg)
+            }
+            BackendError::UntranslatableCharacter(msg) => {
+                (BackendError::UntranslatableCharacter, msg)
+            }
+            BackendError::InvalidRegularExpression(msg) => {
+                (BackendError::InvalidRegularExpression, msg)
+            }
+            BackendError::NotNullViolation(msg) => (BackendError::NotNullViolation, msg),
+            BackendError::ForeignKeyViolation(msg) => (BackendError::ForeignKeyViolation, msg),
+            BackendError::UniqueViolation(msg) => (BackendError::UniqueViolation, msg),
+            BackendError::CheckViolation(msg) => (BackendError::CheckViolation, msg),
+            BackendError::InvalidCursorState(msg) => (BackendError::InvalidCursorState, msg),
+            BackendError::ReadOnlySqlTransaction(msg) => {
+                (BackendError::ReadOnlySqlTransaction, msg)
+            }
+            BackendError::ActiveSqlTransaction(msg) => (BackendError::ActiveSqlTransaction, msg),
+            BackendError::NoActiveSqlTransaction(msg) => {
+                (BackendError::NoActiveSqlTransaction, msg)
+            }
+            BackendError::InFailedSqlTransaction(msg) => {
+                (BackendError::InFailedSqlTransaction, msg)
+            }
+            BackendError::DependentObjectsStillExist(msg) => {
+                (BackendError::DependentObjectsStillExist, msg)
+            }
+            BackendError::InvalidCursorName(msg) => (BackendError::InvalidCursorName, msg),
+            BackendError::SerializationFailure(msg) => (BackendError::SerializationFailure, msg),
+            BackendError::SyntaxError(msg) => (BackendError::SyntaxError, msg),
+            BackendError::InsufficientPrivilege(msg) => (BackendError::InsufficientPrivilege, msg),
+            BackendError::GeneratedAlways(msg) => (BackendError::GeneratedAlways, msg),
+            BackendError::UndefinedColumn(msg) => (BackendError::UndefinedColumn, msg),
+            BackendError::UndefinedFunction(msg) => (BackendError::UndefinedFunction, msg),
+            BackendError::UndefinedTable(msg) => (BackendError::UndefinedTable, msg),
+            BackendError::DuplicateColumn(msg) => (BackendError::DuplicateColumn, msg),
+            BackendError::DuplicateTable(msg) => (BackendError::DuplicateTable, msg),
+            BackendError::DuplicateCursor(msg) => (BackendError::DuplicateCursor, msg),
+            BackendError::DuplicateObject(msg) => (BackendError::DuplicateObject, msg),
+            BackendError::DuplicateAlias(msg) => (BackendError::DuplicateAlias, msg),
+            BackendError::NameTooLong(msg) => (BackendError::NameTooLong, msg),
+            BackendError::UndefinedObject(msg) => (BackendError::UndefinedObject, msg),
+            BackendError::InvalidColumnReference(msg) => {
+                (BackendError::InvalidColumnReference, msg)
+            }
+            BackendError::InvalidTableDefinition(msg) => {
+                (BackendError::InvalidTableDefinition, msg)
+            }
+            BackendError::DatatypeMismatch(msg) => (BackendError::DatatypeMismatch, msg),
+            BackendError::WrongObjectType(msg) => (BackendError::WrongObjectType, msg),
+            BackendError::GroupingError(msg) => (BackendError::GroupingError, msg),
+            BackendError::WindowingError(msg) => (BackendError::WindowingError, msg),
+            BackendError::CollationMismatch(msg) => (BackendError::CollationMismatch, msg),
+            BackendError::IndeterminateCollation(msg) => {
+                (BackendError::IndeterminateCollation, msg)
+            }
+            BackendError::IndeterminateDatatype(msg) => (BackendError::IndeterminateDatatype, msg),
+            BackendError::ProgramLimitExceeded(msg) => (BackendError::ProgramLimitExceeded, msg),
+            BackendError::ObjectNotInPrerequisiteState(msg) => {
+                (BackendError::ObjectNotInPrerequisiteState, msg)
+            }
+            BackendError::LockNotAvailable(msg) => (BackendError::LockNotAvailable, msg),
+            BackendError::QueryCanceled(msg) => (BackendError::QueryCanceled, msg),
+            BackendError::CantChangeRuntimeParam(msg) => {
+                (BackendError::CantChangeRuntimeParam, msg)
+            }
+            BackendError::IoError(msg) => (BackendError::IoError, msg),
+            BackendError::Internal(msg) => (BackendError::Internal, msg),
+        };
+        kind(f(msg))
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<BackendError> for String {
+    fn from(err: BackendError) -> Self {
+        err.to_string()
+    }
+}
+
+impl From<SqlTypeError> for BackendError {
+    fn from(err: SqlTypeError) -> Self {
+        let msg = err.to_string();
+        match err {
+            SqlTypeError::ConversionError(_) | SqlTypeError::TypeMismatchError(_) => {
+                BackendError::DatatypeMismatch(msg)
+            }
+            SqlTypeError::ParseError(_) => BackendError::InvalidTextRepresentation(msg),
+            SqlTypeError::OverflowError(_) => BackendError::NumericValueOutOfRange(msg),
+            SqlTypeError::DivisionByZero => BackendError::DivisionByZero(msg),
+            SqlTypeError::OperationError(_) => BackendError::InvalidParameterValue(msg),
+            SqlTypeError::CharacterNotInRepertoire(_) => {
+                BackendError::CharacterNotInRepertoire(msg)
+            }
+            SqlTypeError::UntranslatableCharacter(_) => BackendError::UntranslatableCharacter(msg),
+            SqlTypeError::DecodeError(_) | SqlTypeError::Infallible => BackendError::Internal(msg),
+        }
+    }
+}
+
+impl From<ParsingError> for BackendError {
+    fn from(err: ParsingError) -> Self {
+        BackendError::SyntaxError(err.to_string())
+    }
+}
+
+impl From<MapError> for BackendError {
+    fn from(err: MapError) -> Self {
+        BackendError::DatatypeMismatch(err.to_string())
+    }
+}
 
 pub trait Backend<C> {
-    fn create_table(_: CreateTableStatement) -> Result<bool, String>;
-    fn insert(_: InsertStatement) -> Result<bool, String>;
-    fn select(_: SelectStatement) -> Result<QueryResults<C>, String>;
-    fn eval_query(query: String) -> Result<Vec<EvalResult<C>>, String>;
+    fn create_table(_: CreateTableStatement) -> Result<bool, BackendError>;
+    fn insert(_: InsertStatement) -> Result<bool, BackendError>;
+    fn select(_: SelectStatement) -> Result<QueryResults<C>, BackendError>;
+    fn eval_query(query: String) -> Result<Vec<EvalResult<C>>, BackendError>;
 }
 
 pub type MemoryCellData = Vec<u8>;
@@ -111,45 +735,30 @@ pub struct MemoryCell {
 
 impl Cell for MemoryCell {
     fn as_int(&self) -> Result<i32, &'static str> {
-        let mut rdr = std::io::Cursor::new(&self.bytes);
-        match rdr.read_i32::<BigEndian>() {
-            Ok(result) => {
-                return Ok(result);
-            }
-            Err(_err) => {
-                return Err("Failed to parse bytes to int32.");
-            }
+        match SqlValue::decode(self, SqlType::Int) {
+            Ok(SqlValue::Numeric(SqlNumeric::Int { value })) => Ok(value),
+            _ => Err("Failed to parse bytes to int32."),
         }
     }
 
     fn as_num(&self, typ: SqlType) -> Result<f64, &'static str> {
-        let text = match SqlValue::decode_type(&self, typ) {
-            Ok(val) => val.to_string(),
-            Err(_) => {
-                return Err("Failed to parse bytes to double precision.");
-            }
-        };
-        match text.parse::<f64>() {
-            Ok(val) => Ok(val),
-            Err(_) => Err("Failed to parse bytes to double precision."),
+        match SqlValue::decode(self, typ).map(|val| val.to_type(SqlType::DoublePrecision)) {
+            Ok(Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision { value }))) => Ok(value),
+            _ => Err("Failed to parse bytes to double precision."),
         }
     }
 
     fn as_bool(&self) -> Result<bool, &'static str> {
-        return Ok(self.bytes != vec![0]);
+        match SqlValue::decode(self, SqlType::Boolean) {
+            Ok(SqlValue::Boolean(value)) => Ok(value),
+            _ => Err("Failed to parse bytes to bool."),
+        }
     }
 
     fn as_text(&self) -> Result<String, &'static str> {
-        let mut rdr = std::io::Cursor::new(&self.bytes);
-
-        let mut text = "".to_owned();
-        match rdr.read_to_string(&mut text) {
-            Ok(_) => {
-                return Ok(text);
-            }
-            Err(_err) => {
-                return Err("Failed to parse bytes to String.");
-            }
+        match SqlValue::decode(self, SqlType::Text) {
+            Ok(SqlValue::Text(text)) => Ok(text.to_string()),
+            _ => Err("Failed to parse bytes to String."),
         }
     }
 
@@ -0,0 +1,807 @@
+use super::collation::collation_keys;
+use super::stored_row::StoredRow;
+use super::values::big_int;
+use super::{
+    contains_window_function, Collation, CompiledExpression, Table, AGGREGATE_FUNCTIONS,
+    ANONYMOUS_COL_NAME,
+};
+use crate::ast::*;
+use crate::backend::{
+    BackendError, MemoryCellData, ERR_AGGREGATE_FUNCTION_IN_GROUP_BY, ERR_COLUMN_NOT_GROUPED,
+    ERR_FUNCTION_DOES_NOT_EXIST, ERR_NESTED_AGGREGATE, ERR_WINDOW_FUNCTION_NOT_ALLOWED,
+};
+use crate::lexer::Token;
+use crate::sql_types::{SqlNumeric, SqlType, SqlValue};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+
+// Functions that fold the rows of a group into one value. All of them skip NULLs, only
+// `count(*)` counts every row.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn from_call(call: &FunctionCall) -> Result<Self, BackendError> {
+        let function = match call.name.as_str() {
+            "count" => AggregateFunction::Count,
+            "sum" => AggregateFunction::Sum,
+            "avg" => AggregateFunction::Avg,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            _ => {
+                return Err(BackendError::UndefinedFunction(format!(
+                    "{}: {}",
+                    call.name, ERR_FUNCTION_DOES_NOT_EXIST
+                )))
+            }
+        };
+        if call.distinct && call.args.len() > 1 {
+            return Err(BackendError::FeatureNotSupported(format!(
+                "{}: DISTINCT with more than one argument is not supported.",
+                call.name
+            )));
+        }
+        let arg_count = if call.asterisk && function == AggregateFunction::Count {
+            0
+        } else {
+            1
+        };
+        if call.args.len() != arg_count || (call.asterisk && function != AggregateFunction::Count) {
+            return Err(BackendError::UndefinedFunction(format!(
+                "{}: Expected {} argument(s), got {}.",
+                call.name,
+                arg_count,
+                call.args.len()
+            )));
+        }
+        Ok(function)
+    }
+
+    // `arg_type` is the type of the argument, when there is one
+    fn result_type(&self, arg_type: Option<SqlType>) -> SqlType {
+        match (self, arg_type) {
+            (AggregateFunction::Count, _) => SqlType::BigInt,
+            (AggregateFunction::Avg, _) => SqlType::DoublePrecision,
+            (AggregateFunction::Sum, Some(SqlType::SmallInt))
+            | (AggregateFunction::Sum, Some(SqlType::Int)) => SqlType::BigInt,
+            (_, Some(typ)) => typ,
+            (_, None) => SqlType::Null,
+        }
+    }
+}
+
+// Whether `call` is an aggregate, rather than a window function of the same name
+pub fn is_aggregate(call: &FunctionCall) -> bool {
+    call.over.is_none() && AGGREGATE_FUNCTIONS.contains(&call.name.as_str())
+}
+
+pub fn contains_aggregate(expression: &Expression) -> bool {
+    match expression {
+        Expression::FunctionCall(call) => {
+            is_aggregate(call)
+                || call.args.iter().any(contains_aggregate)
+                || call.over.as_ref().is_some_and(|over| {
+                    over.partition_by.iter().any(contains_aggregate)
+                        || over
+                            .order_by
+                            .iter()
+                            .any(|order_by| contains_aggregate(&order_by.exp))
+                })
+        }
+        Expression::Binary(binary) => {
+            contains_aggregate(&binary.first) || contains_aggregate(&binary.second)
+        }
+        Expression::Unary(unary) => contains_aggregate(&unary.first),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            contains_aggregate(data)
+        }
+        Expression::Quantified(quantified) => contains_aggregate(&quantified.first),
+        Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => false,
+    }
+}
+
+impl Table {
+    // Groups the rows by the GROUP BY expressions and computes the aggregates of the
+    // select items, HAVING and ORDER BY for each group. `group` goes through the rows
+    // with what grouping them takes. The groups make up a new table with a column for
+    // each GROUP BY expression followed by one for each aggregate, and the expressions
+    // are rewritten to read from it. Gives nothing when the query does not group its
+    // rows.
+    pub(super) fn grouped(
+        &self,
+        group_by: &[Expression],
+        collations: &[Option<Collation>],
+        items: &mut [SelectItem],
+        having: &mut Expression,
+        order_by: &mut [OrderByClause],
+        group: impl FnOnce(&Aggregation) -> Result<Groups, BackendError>,
+    ) -> Result<Option<Table>, BackendError> {
+        let is_grouped = !group_by.is_empty()
+            || !having.is_empty()
+            || items
+                .iter()
+                .any(|item| contains_aggregate(&item.expression))
+            || order_by
+                .iter()
+                .any(|order_by| contains_aggregate(&order_by.exp));
+        if !is_grouped {
+            return Ok(None);
+        }
+        for key in group_by {
+            if contains_aggregate(key) {
+                return Err(BackendError::GroupingError(
+                    ERR_AGGREGATE_FUNCTION_IN_GROUP_BY.to_string(),
+                ));
+            }
+            if contains_window_function(key) {
+                return Err(BackendError::WindowingError(
+                    ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string(),
+                ));
+            }
+        }
+
+        let mut calls = vec![];
+        for item in items.iter_mut() {
+            // A bare call keeps the name of its function, like in postgres
+            if let Expression::FunctionCall(call) = &item.expression {
+                if is_aggregate(call) && item.as_clause.is_none() {
+                    item.as_clause = Some(call.name.clone());
+                }
+            }
+            self.take_aggregate_calls(&mut item.expression, group_by, &mut calls)?;
+        }
+        self.take_aggregate_calls(having, group_by, &mut calls)?;
+        for order_by in order_by.iter_mut() {
+            self.take_aggregate_calls(&mut order_by.exp, group_by, &mut calls)?;
+        }
+
+        let functions = calls
+            .iter()
+            .map(AggregateFunction::from_call)
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<CompiledExpression> = group_by
+            .iter()
+            .map(|key| self.compile_expression(key))
+            .collect();
+        let args: Vec<Option<CompiledExpression>> = calls
+            .iter()
+            .map(|call| call.args.first().map(|arg| self.compile_expression(arg)))
+            .collect();
+        let filters: Vec<Option<CompiledExpression>> = calls
+            .iter()
+            .map(|call| {
+                call.filter
+                    .as_ref()
+                    .map(|filter| self.compile_expression(filter))
+            })
+            .collect();
+        let aggregation = Aggregation {
+            collations,
+            keys,
+            calls: &calls,
+            functions: &functions,
+            args,
+            filters,
+        };
+        let groups = group(&aggregation)?;
+
+        let mut columns = Vec::with_capacity(group_by.len() + calls.len());
+        let mut column_types = Vec::with_capacity(group_by.len() + calls.len());
+        for key in group_by {
+            columns.push(self.key_name(key).to_string());
+            column_types.push(self.sample_type(key));
+        }
+        for (call, function) in calls.iter().zip(&functions) {
+            columns.push(ANONYMOUS_COL_NAME.to_string());
+            let arg_type = call.args.first().map(|arg| self.sample_type(arg));
+            column_types.push(function.result_type(arg_type));
+        }
+        let mut rows = Vec::with_capacity(groups.groups.len());
+        for (mut row, aggregates) in groups.groups {
+            for aggregate in aggregates {
+                row.push(aggregate.result()?);
+            }
+            rows.push(StoredRow::new(&row));
+        }
+
+        Ok(Some(Table {
+            name: self.name.clone(),
+            columns,
+            column_types,
+            rows,
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        }))
+    }
+
+    // Swaps the aggregate calls in `expression` for references to the columns their
+    // results will be put in, and the GROUP BY expressions for references to the columns
+    // of their values. The calls are collected in column order, the same call only once.
+    // Columns of the table can only be used inside one of the two.
+    fn take_aggregate_calls(
+        &self,
+        expression: &mut Expression,
+        group_by: &[Expression],
+        calls: &mut Vec<FunctionCall>,
+    ) -> Result<(), BackendError> {
+        if let Some(col_idx) = self.group_key_index(group_by, expression) {
+            *expression = Expression::ProcessedTableColumn(ProcessedTableColumn {
+                col_name: None,
+                col_idx,
+            });
+            return Ok(());
+        }
+        match expression {
+            Expression::FunctionCall(call) if is_aggregate(call) => {
+                let inner = call.args.iter().chain(call.filter.as_deref());
+                for exp in inner {
+                    if contains_aggregate(exp) {
+                        return Err(BackendError::GroupingError(format!(
+                            "{}: {}",
+                            call.name, ERR_NESTED_AGGREGATE
+                        )));
+                    }
+                    if contains_window_function(exp) {
+                        return Err(BackendError::WindowingError(
+                            ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string(),
+                        ));
+                    }
+                }
+                let position = match calls.iter().position(|taken| taken == call) {
+                    Some(position) => position,
+                    None => {
+                        calls.push(call.clone());
+                        calls.len() - 1
+                    }
+                };
+                *expression = Expression::ProcessedTableColumn(ProcessedTableColumn {
+                    col_name: None,
+                    col_idx: group_by.len() + position,
+                });
+            }
+            Expression::FunctionCall(call) => {
+                for arg in call.args.iter_mut() {
+                    self.take_aggregate_calls(arg, group_by, calls)?;
+                }
+                if let Some(filter) = call.filter.as_mut() {
+                    self.take_aggregate_calls(filter, group_by, calls)?;
+                }
+                if let Some(over) = call.over.as_mut() {
+                    for exp in over.partition_by.iter_mut() {
+                        self.take_aggregate_calls(exp, group_by, calls)?;
+                    }
+                    for order_by in over.order_by.iter_mut() {
+                        self.take_aggregate_calls(&mut order_by.exp, group_by, calls)?;
+                    }
+                }
+            }
+            Expression::Binary(binary) => {
+                self.take_aggregate_calls(&mut binary.first, group_by, calls)?;
+                self.take_aggregate_calls(&mut binary.second, group_by, calls)?;
+            }
+            Expression::Unary(unary) => {
+                self.take_aggregate_calls(&mut unary.first, group_by, calls)?
+            }
+            Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+                self.take_aggregate_calls(data, group_by, calls)?
+            }
+            Expression::Quantified(quantified) => {
+                self.take_aggregate_calls(&mut quantified.first, group_by, calls)?
+            }
+            Expression::TableColumn(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { .. },
+            }) => {
+                return Err(BackendError::GroupingError(format!(
+                    "{}: {}",
+                    self.key_name(expression),
+                    ERR_COLUMN_NOT_GROUPED
+                )));
+            }
+            Expression::SubSelect(_)
+            | Expression::Exists(_)
+            | Expression::Literal(_)
+            | Expression::Empty => {}
+        }
+        Ok(())
+    }
+
+    // The GROUP BY expression that `expression` is, either written the same or naming
+    // the same column
+    fn group_key_index(&self, group_by: &[Expression], expression: &Expression) -> Option<usize> {
+        let column = self.column_index(expression);
+        group_by.iter().position(|key| {
+            key == expression || column.is_some() && self.column_index(key) == column
+        })
+    }
+
+    fn column_index(&self, expression: &Expression) -> Option<usize> {
+        let col_name = match expression {
+            Expression::TableColumn(TableColumn { col_name, .. }) => col_name.as_str(),
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => value.as_str(),
+            Expression::ProcessedTableColumn(column) => return Some(column.col_idx),
+            _ => return None,
+        };
+        self.columns.iter().position(|column| column == col_name)
+    }
+
+    // Name of the column of a GROUP BY expression, a plain column keeps its name
+    fn key_name<'a>(&'a self, expression: &'a Expression) -> &'a str {
+        match expression {
+            Expression::TableColumn(TableColumn { col_name, .. }) => col_name,
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => value.as_str(),
+            Expression::ProcessedTableColumn(column) => self
+                .columns
+                .get(column.col_idx)
+                .map_or(ANONYMOUS_COL_NAME, String::as_str),
+            _ => ANONYMOUS_COL_NAME,
+        }
+    }
+
+    fn sample_type(&self, expression: &Expression) -> SqlType {
+        let item = SelectItem {
+            expression: expression.clone(),
+            as_clause: None,
+            asterisk: false,
+            asterisk_source: None,
+            asterisk_modifiers: None,
+        };
+        self.sample_types(&[item])[0]
+    }
+}
+
+// What grouping rows takes, worked out once for all of them: the GROUP BY expressions
+// and the collations their values are compared under, and the aggregate calls with
+// their arguments and FILTER conditions
+pub(super) struct Aggregation<'a> {
+    collations: &'a [Option<Collation>],
+    keys: Vec<CompiledExpression>,
+    calls: &'a [FunctionCall],
+    functions: &'a [AggregateFunction],
+    args: Vec<Option<CompiledExpression>>,
+    filters: Vec<Option<CompiledExpression>>,
+}
+
+impl Aggregation<'_> {
+    // The groups of the rows that pass `where_clause`
+    pub(super) fn groups(
+        &self,
+        rows: &[StoredRow],
+        where_clause: &Option<CompiledExpression>,
+    ) -> Result<Groups, BackendError> {
+        let mut groups = Groups {
+            indexes: BTreeMap::new(),
+            groups: vec![],
+        };
+        // Without GROUP BY all of the rows make up one group, even when there are none
+        if self.keys.is_empty() {
+            groups.indexes.insert(vec![], 0);
+            groups.groups.push((vec![], self.new_aggregates()));
+        }
+        for row in rows {
+            if let Some(where_clause) = where_clause {
+                if !where_clause.is_true(row)? {
+                    continue;
+                }
+            }
+            let key = self
+                .keys
+                .iter()
+                .map(|key| key.evaluate(row).map(Cow::into_owned))
+                .collect::<Result<Vec<_>, _>>()?;
+            // Keys that are the same under the collations of the GROUP BY expressions go
+            // in one group, which shows the values of its first row
+            let lookup = collation_keys(self.collations, &key);
+            let group = match groups.indexes.get(&*lookup) {
+                Some(group) => *group,
+                None => {
+                    let group = groups.groups.len();
+                    groups.indexes.insert(lookup.into_owned(), group);
+                    groups.groups.push((key, self.new_aggregates()));
+                    group
+                }
+            };
+            for (idx, aggregate) in groups.groups[group].1.iter_mut().enumerate() {
+                if let Some(filter) = &self.filters[idx] {
+                    if !filter.is_true(row)? {
+                        continue;
+                    }
+                }
+                let value = match &self.args[idx] {
+                    Some(arg) => arg.evaluate(row)?.into_owned(),
+                    // count(*) counts the row whatever is in it
+                    None => SqlValue::Boolean(true),
+                };
+                aggregate.add(&self.calls[idx].name, value)?;
+            }
+        }
+        Ok(groups)
+    }
+
+    // Whether the rows can be grouped a part at a time, the groups of the parts merged
+    // after. Aggregates of DISTINCT values need all of the values of a group together,
+    // and volatile functions are called for the rows in order.
+    #[cfg(feature = "parallel")]
+    pub(super) fn is_divisible(&self) -> bool {
+        !self.calls.iter().any(|call| call.distinct)
+            && !self.keys.iter().any(CompiledExpression::is_volatile)
+            && !self
+                .args
+                .iter()
+                .chain(&self.filters)
+                .flatten()
+                .any(CompiledExpression::is_volatile)
+    }
+
+    fn new_aggregates(&self) -> Vec<GroupAggregate> {
+        self.calls
+            .iter()
+            .zip(self.functions)
+            .map(|(call, function)| GroupAggregate::new(*function, call.distinct))
+            .collect()
+    }
+}
+
+// Groups of rows in the order their first rows came in, each with its values of the
+// GROUP BY expressions and its aggregates. They are found by the values under the
+// collations of the expressions.
+pub(super) struct Groups {
+    indexes: BTreeMap<Vec<SqlValue>, usize>,
+    groups: Vec<(Vec<SqlValue>, Vec<GroupAggregate>)>,
+}
+
+impl Groups {
+    // Adds the groups of rows that came after those of these groups. Groups of both keep
+    // the values of the first row they got and fold in the aggregates of the later ones.
+    #[cfg(feature = "parallel")]
+    pub(super) fn merge(&mut self, later: Groups) -> Result<(), BackendError> {
+        let mut lookups = vec![vec![]; later.groups.len()];
+        for (lookup, idx) in later.indexes {
+            lookups[idx] = lookup;
+        }
+        for (lookup, (key, aggregates)) in lookups.into_iter().zip(later.groups) {
+            match self.indexes.get(&lookup) {
+                Some(group) => {
+                    let merged = self.groups[*group].1.iter_mut().zip(aggregates);
+                    for (aggregate, later) in merged {
+                        aggregate.merge(later)?;
+                    }
+                }
+                None => {
+                    self.indexes.insert(lookup, self.groups.len());
+                    self.groups.push((key, aggregates));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The state of one aggregate over the rows of a group
+struct GroupAggregate {
+    function: AggregateFunction,
+    running: RunningAggregate,
+    // The smallest or largest value so far, for min and max
+    extreme: SqlValue,
+    // Encoded values already aggregated, for DISTINCT
+    seen: Option<HashSet<MemoryCellData>>,
+}
+
+impl GroupAggregate {
+    fn new(function: AggregateFunction, distinct: bool) -> Self {
+        GroupAggregate {
+            function,
+            running: RunningAggregate::default(),
+            extreme: SqlValue::Null,
+            seen: if distinct { Some(HashSet::new()) } else { None },
+        }
+    }
+
+    fn add(&mut self, name: &str, value: SqlValue) -> Result<(), BackendError> {
+        if value.is_null() {
+            return Ok(());
+        }
+        if let Some(seen) = self.seen.as_mut() {
+            if !seen.insert(value.encode().bytes) {
+                return Ok(());
+            }
+        }
+        match (self.function, &value) {
+            (AggregateFunction::Sum, SqlValue::Boolean(_))
+            | (AggregateFunction::Sum, SqlValue::Text(_))
+            | (AggregateFunction::Avg, SqlValue::Boolean(_))
+            | (AggregateFunction::Avg, SqlValue::Text(_)) => Err(BackendError::UndefinedFunction(
+                format!("{}: Expected a numeric argument.", name),
+            )),
+            (AggregateFunction::Min, _) => {
+                if self.extreme.is_null() || value < self.extreme {
+                    self.extreme = value;
+                }
+                Ok(())
+            }
+            (AggregateFunction::Max, _) => {
+                if self.extreme.is_null() || value > self.extreme {
+                    self.extreme = value;
+                }
+                Ok(())
+            }
+            _ => self.running.add(&value),
+        }
+    }
+
+    // Folds in the state of the same aggregate over rows that came after. Sums of
+    // floating point values can come out different in the last digits, as they are
+    // added in another order.
+    #[cfg(feature = "parallel")]
+    fn merge(&mut self, later: GroupAggregate) -> Result<(), BackendError> {
+        match self.function {
+            AggregateFunction::Min | AggregateFunction::Max => {
+                let replaces = !later.extreme.is_null()
+                    && (self.extreme.is_null()
+                        || match self.function {
+                            AggregateFunction::Min => later.extreme < self.extreme,
+                            _ => later.extreme > self.extreme,
+                        });
+                if replaces {
+                    self.extreme = later.extreme;
+                }
+                Ok(())
+            }
+            _ => self.running.merge(later.running),
+        }
+    }
+
+    fn result(self) -> Result<SqlValue, BackendError> {
+        match self.function {
+            AggregateFunction::Count => Ok(big_int(self.running.count)),
+            AggregateFunction::Sum => Ok(self.running.sum),
+            AggregateFunction::Avg => self.running.avg(),
+            AggregateFunction::Min | AggregateFunction::Max => Ok(self.extreme),
+        }
+    }
+}
+
+// Running count and sum of the non NULL values added so far
+pub(super) struct RunningAggregate {
+    pub(super) count: usize,
+    pub(super) sum: SqlValue,
+}
+
+impl Default for RunningAggregate {
+    fn default() -> Self {
+        RunningAggregate {
+            count: 0,
+            sum: SqlValue::Null,
+        }
+    }
+}
+
+impl RunningAggregate {
+    pub(super) fn add(&mut self, value: &SqlValue) -> Result<(), BackendError> {
+        let value = match value {
+            SqlValue::Null => return Ok(()),
+            // Integers are summed as bigint so that they do not overflow as easily
+            SqlValue::Numeric(SqlNumeric::SmallInt { .. })
+            | SqlValue::Numeric(SqlNumeric::Int { .. }) => value.to_type(SqlType::BigInt)?,
+            SqlValue::Numeric(_) => value.clone(),
+            // Only counted
+            SqlValue::Boolean(_) | SqlValue::Text(_) | SqlValue::Type(_) => {
+                self.count += 1;
+                return Ok(());
+            }
+        };
+        self.count += 1;
+        self.sum = match &self.sum {
+            SqlValue::Null => value,
+            sum => sum.add(&value)?,
+        };
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    fn merge(&mut self, later: RunningAggregate) -> Result<(), BackendError> {
+        self.count += later.count;
+        self.sum = match (&self.sum, later.sum) {
+            (_, SqlValue::Null) => return Ok(()),
+            (SqlValue::Null, sum) => sum,
+            (sum, later) => sum.add(&later)?,
+        };
+        Ok(())
+    }
+
+    pub(super) fn avg(&self) -> Result<SqlValue, BackendError> {
+        match &self.sum {
+            SqlValue::Null => Ok(SqlValue::Null),
+            sum => match sum.to_type(SqlType::DoublePrecision)? {
+                SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => {
+                    Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                        value: value / self.count as f64,
+                    }))
+                }
+                _ => Ok(SqlValue::Null),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod aggregate_tests {
+    use crate::backend::EvalResult;
+    use crate::backend_memory::MemoryBackend;
+
+    struct AggregateTest {
+        query: &'static str,
+        // The result rows joined by commas, or the error
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let tests = vec![
+            AggregateTest {
+                query: "SELECT count(*), count(amount), sum(amount), avg(amount), min(status),
+                    max(amount) FROM orders;",
+                result: Ok(vec!["6,5,52,10.4,open,20"]),
+            },
+            // Without GROUP BY there is one group, even when no row makes it through WHERE
+            AggregateTest {
+                query: "SELECT count(*), sum(amount) FROM orders WHERE amount > 100;",
+                result: Ok(vec!["0,NULL"]),
+            },
+            AggregateTest {
+                query: "SELECT user_id, count(*) FROM orders WHERE amount > 100 GROUP BY user_id;",
+                result: Ok(vec![]),
+            },
+            AggregateTest {
+                query: "SELECT user_id, sum(amount) FROM orders GROUP BY user_id
+                    HAVING sum(amount) > 10 ORDER BY user_id;",
+                result: Ok(vec!["1,40", "2,12"]),
+            },
+            AggregateTest {
+                query: "SELECT user_id FROM orders GROUP BY user_id ORDER BY count(*) DESC;",
+                result: Ok(vec!["1", "2", "3"]),
+            },
+            AggregateTest {
+                query: "SELECT user_id + 1, count(*) FROM orders GROUP BY user_id + 1
+                    ORDER BY user_id + 1;",
+                result: Ok(vec!["2,3", "3,2", "4,1"]),
+            },
+            // The NULL sum of user 3 comes first when descending
+            AggregateTest {
+                query: "SELECT user_id, rank() OVER (ORDER BY sum(amount) DESC) FROM orders
+                    GROUP BY user_id ORDER BY user_id;",
+                result: Ok(vec!["1,2", "2,3", "3,1"]),
+            },
+            AggregateTest {
+                query: "SELECT status, id FROM orders GROUP BY status;",
+                result: Err(
+                    "id: Column must appear in the GROUP BY clause or be used in an aggregate function.",
+                ),
+            },
+            AggregateTest {
+                query: "SELECT sum(count(*)) FROM orders;",
+                result: Err("sum: Aggregate function calls cannot be nested."),
+            },
+            AggregateTest {
+                query: "SELECT id FROM orders WHERE count(*) > 1;",
+                result: Err("Aggregate functions are not allowed in WHERE."),
+            },
+            AggregateTest {
+                query: "SELECT count(*) FROM orders GROUP BY count(*);",
+                result: Err("Aggregate functions are not allowed in GROUP BY."),
+            },
+            AggregateTest {
+                query: "SELECT sum(status) FROM orders;",
+                result: Err("sum: Expected a numeric argument."),
+            },
+        ];
+        run_tests(tests);
+    }
+
+    #[test]
+    fn test_aggregate_distinct_and_filter() {
+        let tests = vec![
+            AggregateTest {
+                query: "SELECT count(DISTINCT user_id), count(user_id) FROM orders;",
+                result: Ok(vec!["3,6"]),
+            },
+            AggregateTest {
+                query: "SELECT status, count(DISTINCT user_id), sum(DISTINCT amount) FROM orders
+                    GROUP BY status ORDER BY status;",
+                result: Ok(vec!["open,3,15", "paid,2,37"]),
+            },
+            AggregateTest {
+                query: "SELECT user_id, sum(amount) FILTER (WHERE status = 'paid') AS paid
+                    FROM orders GROUP BY user_id ORDER BY user_id;",
+                result: Ok(vec!["1,30", "2,7", "3,NULL"]),
+            },
+            AggregateTest {
+                query: "SELECT count(*) FILTER (WHERE amount >= 10), count(*) FROM orders;",
+                result: Ok(vec!["3,6"]),
+            },
+            AggregateTest {
+                query: "SELECT user_id FROM orders GROUP BY user_id
+                    HAVING count(DISTINCT status) FILTER (WHERE amount > 5) > 1;",
+                result: Ok(vec!["1"]),
+            },
+            AggregateTest {
+                query: "SELECT id, count(*) FILTER (WHERE status = 'paid') OVER (ORDER BY id)
+                    FROM orders;",
+                result: Ok(vec!["1,1", "2,2", "3,2", "4,3", "5,3", "6,3"]),
+            },
+            AggregateTest {
+                query: "SELECT count(DISTINCT user_id, status) FROM orders;",
+                result: Err("count: DISTINCT with more than one argument is not supported."),
+            },
+            AggregateTest {
+                query: "SELECT upper(DISTINCT status) FROM orders;",
+                result: Err("upper: DISTINCT specified, but it is not an aggregate function."),
+            },
+            AggregateTest {
+                query: "SELECT upper(status) FILTER (WHERE id > 1) FROM orders;",
+                result: Err("upper: FILTER specified, but it is not an aggregate function."),
+            },
+            AggregateTest {
+                query: "SELECT count(DISTINCT id) OVER () FROM orders;",
+                result: Err("count: DISTINCT is not implemented for window functions."),
+            },
+        ];
+        run_tests(tests);
+    }
+
+    fn run_tests(tests: Vec<AggregateTest>) {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE orders (id INT, user_id INT, status TEXT, amount INT);
+            INSERT INTO orders VALUES (1, 1, 'paid', 10);
+            INSERT INTO orders VALUES (2, 1, 'paid', 20);
+            INSERT INTO orders VALUES (3, 2, 'open', 5);
+            INSERT INTO orders VALUES (4, 2, 'paid', 7);
+            INSERT INTO orders VALUES (5, 3, 'open', NULL);
+            INSERT INTO orders VALUES (6, 1, 'open', 10);",
+        )
+        .unwrap();
+
+        for test in tests {
+            let rows = match mb.eval_query(test.query).map(|mut results| results.pop()) {
+                Ok(Some(EvalResult::Select { results, .. })) => Ok(results
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    })
+                    .collect::<Vec<_>>()),
+                Ok(_) => panic!("Expected select results for {}", test.query),
+                Err(err) => Err(err.to_string()),
+            };
+            match (rows, test.result) {
+                (Ok(rows), Ok(expected)) => assert_eq!(rows, expected, "{}", test.query),
+                (Err(err), Err(expected)) => assert_eq!(err, expected, "{}", test.query),
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+    }
+}
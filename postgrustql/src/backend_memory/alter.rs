@@ -0,0 +1,348 @@
+use super::stored_row::StoredRow;
+use super::{ChangeOperation, MemoryBackend};
+use crate::ast::*;
+use crate::backend::BackendError;
+use crate::lexer::Token;
+
+impl MemoryBackend {
+    // Drops an index of whichever table has it, temporary tables first. The index of a
+    // PRIMARY KEY belongs to the constraint, it only goes along with its column or table.
+    pub fn drop_index(&mut self, statement: DropIndexStatement) -> Result<bool, BackendError> {
+        let (is_temporary, table_name) = match self.index_table(&statement.name) {
+            Some(found) => found,
+            None if statement.if_exists => return Ok(false),
+            None => {
+                return Err(BackendError::UndefinedObject(format!(
+                    "Index \"{}\" doesn't exist.",
+                    statement.name
+                )));
+            }
+        };
+        let table = match self.namespace_mut(is_temporary).table_mut(&table_name)? {
+            Some(table) => table,
+            None => return Err(BackendError::Internal("Error accesing table".to_string())),
+        };
+
+        if let Some(index) = table
+            .indexes
+            .iter()
+            .find(|index| index.name == statement.name && index.primary_key)
+        {
+            return Err(BackendError::DependentObjectsStillExist(format!(
+                "Cannot drop index \"{}\" because constraint \"{}\" on table \"{}\" requires it.",
+                index.name, index.name, table_name
+            )));
+        }
+        table.indexes.retain(|index| index.name != statement.name);
+
+        self.mark_written(&table_name);
+        self.schema_changed(&table_name);
+        Ok(true)
+    }
+
+    pub fn alter_table(&mut self, statement: AlterTableStatement) -> Result<bool, BackendError> {
+        match statement.action {
+            AlterTableAction::DropColumn {
+                name,
+                if_exists,
+                cascade,
+            } => self.drop_column(&statement.name, &name, if_exists, cascade),
+        }
+    }
+
+    // Drops a column and its values. Indexes and CHECK constraints reading the column keep
+    // it from being dropped, unless CASCADE was given and they are dropped with it.
+    fn drop_column(
+        &mut self,
+        table_name: &str,
+        column: &str,
+        if_exists: bool,
+        cascade: bool,
+    ) -> Result<bool, BackendError> {
+        let table = match self.table_mut(table_name)? {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Table \"{}\" doesn't exist.",
+                    table_name
+                )));
+            }
+        };
+        let position = match table.columns.iter().position(|name| name == column) {
+            Some(position) => position,
+            None if if_exists => return Ok(false),
+            None => {
+                return Err(BackendError::UndefinedColumn(format!(
+                    "Column \"{}\" of relation \"{}\" doesn't exist.",
+                    column, table_name
+                )));
+            }
+        };
+
+        // Generated columns computed from the column go with it, and so does whatever
+        // depends on them
+        let generated = table.generated_using(position);
+        let mut positions = generated.clone();
+        positions.push(position);
+        positions.sort_unstable();
+        let dropped: Vec<&String> = positions.iter().map(|idx| &table.columns[*idx]).collect();
+
+        let indexes: Vec<String> = table
+            .indexes
+            .iter()
+            .filter(|index| index.columns.iter().any(|name| dropped.contains(&name)))
+            .map(|index| index.name.clone())
+            .collect();
+        let mut checks = vec![];
+        for idx in &positions {
+            for check in table.checks_using(*idx) {
+                if !checks.contains(&check) {
+                    checks.push(check);
+                }
+            }
+        }
+        if !cascade {
+            let dependent = generated
+                .iter()
+                .map(|idx| format!("column \"{}\"", table.columns[*idx]))
+                .chain(indexes.iter().map(|name| format!("index \"{}\"", name)))
+                .chain(checks.iter().map(|name| format!("constraint \"{}\"", name)))
+                .next();
+            if let Some(dependent) = dependent {
+                return Err(BackendError::DependentObjectsStillExist(format!(
+                    "Cannot drop column \"{}\" of table \"{}\" because {} depends on it.",
+                    column, table_name, dependent
+                )));
+            }
+        }
+
+        let dropped: Vec<String> = dropped.into_iter().cloned().collect();
+        table.indexes.retain(|index| !indexes.contains(&index.name));
+        table.drop_checks(&checks);
+        for position in positions.into_iter().rev() {
+            table.columns.remove(position);
+            table.column_types.remove(position);
+            if position < table.column_constraints.len() {
+                table.column_constraints.remove(position);
+            }
+            for row in 0..table.rows.len() {
+                let mut values = table.rows[row].values();
+                values.remove(position);
+                table.replace_stored_row(row, StoredRow::new(&values));
+            }
+        }
+
+        // The sequences of SERIAL columns go with them
+        let is_temporary = self.is_temporary(table_name);
+        for column in &dropped {
+            self.drop_owned_sequences(table_name, Some(column), is_temporary);
+        }
+        self.mark_written(table_name);
+        self.changes.record(ChangeOperation::DropColumn, table_name);
+        self.schema_changed(table_name);
+        Ok(true)
+    }
+
+    // Whether the table with the index is temporary and its name
+    pub(super) fn index_table(&self, name: &str) -> Option<(bool, String)> {
+        [true, false].iter().find_map(|is_temporary| {
+            let namespace = self.namespace(*is_temporary);
+            namespace.table_names().into_iter().find_map(|table_name| {
+                let table = namespace.table(&table_name)?;
+                match table.indexes.iter().any(|index| index.name == name) {
+                    true => Some((*is_temporary, table_name)),
+                    false => None,
+                }
+            })
+        })
+    }
+}
+
+// The columns an index expression reads, kept with the index so dropping one of them finds
+// the indexes depending on it
+pub(super) fn index_columns(expression: &Expression, columns: &mut Vec<String>) {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => {
+            let value = value.to_string();
+            if !columns.contains(&value) {
+                columns.push(value);
+            }
+        }
+        Expression::TableColumn(table_column) => {
+            if !columns.contains(&table_column.col_name) {
+                columns.push(table_column.col_name.clone());
+            }
+        }
+        Expression::Binary(binary) => {
+            index_columns(&binary.first, columns);
+            index_columns(&binary.second, columns);
+        }
+        Expression::Unary(unary) => index_columns(&unary.first, columns),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            index_columns(data, columns)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in &call.args {
+                index_columns(arg, columns);
+            }
+        }
+        Expression::Quantified(quantified) => index_columns(&quantified.first, columns),
+        Expression::Literal(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Empty => {}
+    }
+}
+
+#[cfg(test)]
+mod alter_tests {
+    use crate::backend::{BackendError, EvalResult};
+    use crate::backend_memory::test_support::backend;
+    use crate::backend_memory::MemoryBackend;
+
+    const SETUP: &str =
+        "CREATE TABLE t (id INT PRIMARY KEY, name TEXT, age INT CHECK (age > 0), qty INT);
+        INSERT INTO t VALUES (1, 'a', 20, 5), (2, 'b', 30, 6);
+        CREATE INDEX t_name_idx ON t (name);";
+
+    fn columns(mb: &mut MemoryBackend) -> Vec<String> {
+        match mb.eval_query("SELECT * FROM t;").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results
+                .columns
+                .into_iter()
+                .map(|column| column.name)
+                .collect(),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    fn error(mb: &mut MemoryBackend, query: &str) -> BackendError {
+        match mb.eval_query(query) {
+            Ok(_) => panic!("Expected {} to fail", query),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn test_drop_index() {
+        let mut mb = backend(SETUP);
+        match mb.eval_query("DROP INDEX t_name_idx;").unwrap().pop() {
+            Some(EvalResult::DropIndex { success: true, .. }) => {}
+            _ => panic!("Expected the index to be dropped"),
+        }
+
+        let err = error(&mut mb, "DROP INDEX t_name_idx;");
+        assert_eq!(err.code(), "42704");
+        assert_eq!(err.message(), "Index \"t_name_idx\" doesn't exist.");
+        match mb
+            .eval_query("DROP INDEX IF EXISTS t_name_idx;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::DropIndex { success: false, .. }) => {}
+            _ => panic!("Expected nothing to be dropped"),
+        }
+
+        mb.eval_query("CREATE INDEX t_name_idx ON t (name);")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_drop_primary_key_index() {
+        let mut mb = backend(SETUP);
+        let err = error(&mut mb, "DROP INDEX t_pkey;");
+        assert_eq!(err.code(), "2BP01");
+        assert_eq!(
+            err.message(),
+            "Cannot drop index \"t_pkey\" because constraint \"t_pkey\" on table \"t\" requires it."
+        );
+        assert!(mb
+            .eval_query("INSERT INTO t VALUES (1, 'c', 40, 7);")
+            .is_err());
+    }
+
+    #[test]
+    fn test_drop_table_drops_indexes() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("DROP TABLE t;").unwrap();
+        assert_eq!(error(&mut mb, "DROP INDEX t_name_idx;").code(), "42704");
+        assert_eq!(error(&mut mb, "DROP INDEX t_pkey;").code(), "42704");
+    }
+
+    #[test]
+    fn test_drop_column() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("ALTER TABLE t DROP COLUMN qty;").unwrap();
+        assert_eq!(columns(&mut mb), vec!["id", "name", "age"]);
+        mb.eval_query("INSERT INTO t VALUES (3, 'c', 40);").unwrap();
+
+        let err = error(&mut mb, "ALTER TABLE t DROP qty;");
+        assert_eq!(err.code(), "42703");
+        assert_eq!(
+            err.message(),
+            "Column \"qty\" of relation \"t\" doesn't exist."
+        );
+        match mb
+            .eval_query("ALTER TABLE t DROP COLUMN IF EXISTS qty;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::AlterTable { success: false, .. }) => {}
+            _ => panic!("Expected nothing to be dropped"),
+        }
+        assert_eq!(error(&mut mb, "ALTER TABLE u DROP qty;").code(), "42P01");
+    }
+
+    #[test]
+    fn test_drop_column_restrict() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            (
+                "ALTER TABLE t DROP COLUMN name;",
+                "Cannot drop column \"name\" of table \"t\" because index \"t_name_idx\" depends on it.",
+            ),
+            (
+                "ALTER TABLE t DROP COLUMN id RESTRICT;",
+                "Cannot drop column \"id\" of table \"t\" because index \"t_pkey\" depends on it.",
+            ),
+            (
+                "ALTER TABLE t DROP COLUMN age;",
+                "Cannot drop column \"age\" of table \"t\" because constraint \"t_age_check\" depends on it.",
+            ),
+        ];
+        for (query, message) in tests {
+            let err = error(&mut mb, query);
+            assert_eq!(err.code(), "2BP01");
+            assert_eq!(err.message(), message);
+        }
+        assert_eq!(columns(&mut mb), vec!["id", "name", "age", "qty"]);
+    }
+
+    #[test]
+    fn test_drop_column_cascade() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("ALTER TABLE t DROP COLUMN name CASCADE;")
+            .unwrap();
+        assert_eq!(error(&mut mb, "DROP INDEX t_name_idx;").code(), "42704");
+
+        mb.eval_query("ALTER TABLE t DROP COLUMN age CASCADE;")
+            .unwrap();
+        mb.eval_query("INSERT INTO t VALUES (3, 7);").unwrap();
+
+        mb.eval_query("ALTER TABLE t DROP COLUMN id CASCADE;")
+            .unwrap();
+        mb.eval_query("INSERT INTO t VALUES (7);").unwrap();
+        assert_eq!(error(&mut mb, "DROP INDEX t_pkey;").code(), "42704");
+
+        match mb.eval_query("SELECT * FROM t;").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                let rows: Vec<String> = results.rows.iter().map(|row| row[0].to_string()).collect();
+                assert_eq!(rows, vec!["5", "6", "7", "7"]);
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use super::warnings::{has_fraction, warn, STRING_DATA_RIGHT_TRUNCATION, WARNING};
+use super::Table;
+use crate::backend::{BackendError, Warning};
+use crate::sql_types::{SqlNumeric, SqlText, SqlType, SqlValue};
+use std::convert::TryFrom;
+
+// What becomes of text too long for the column it is assigned to. Postgres fails, which
+// stays the default, 'truncate' cuts it to the length of the column with a warning.
+// `postgrustql.cast_mode` to SET, as 'strict' or 'truncate'.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CastMode {
+    #[default]
+    Strict,
+    Truncate,
+}
+
+impl CastMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "strict" => Some(CastMode::Strict),
+            "truncate" => Some(CastMode::Truncate),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CastMode::Strict => "strict",
+            CastMode::Truncate => "truncate",
+        }
+    }
+}
+
+fn is_number(typ: SqlType) -> bool {
+    matches!(
+        typ,
+        SqlType::SmallInt
+            | SqlType::Int
+            | SqlType::BigInt
+            | SqlType::Real
+            | SqlType::DoublePrecision
+    )
+}
+
+fn is_text(typ: SqlType) -> bool {
+    matches!(typ, SqlType::Text | SqlType::VarChar | SqlType::Char)
+}
+
+// Whether a value of type `from` can go in a column of type `to` without an explicit
+// cast. Like Postgres' assignment casts that is a number into any number type and any
+// value into text, written as it is output. Text never goes in other columns unless cast.
+fn is_assignable(from: SqlType, to: SqlType) -> bool {
+    from == to
+        || from == SqlType::Null
+        || (is_number(from) && is_number(to))
+        || (is_text(to) && from != SqlType::Type)
+}
+
+// A number as a whole number of type `typ`, rounding to the nearest even one
+fn to_integer(num: &SqlNumeric, typ: SqlType) -> Option<SqlNumeric> {
+    let rounded = |value: f64| {
+        let value = value.round_ties_even();
+        if value >= i64::MIN as f64 && value < i64::MAX as f64 {
+            Some(value as i64)
+        } else {
+            None
+        }
+    };
+    let value = match num {
+        SqlNumeric::SmallInt { value } => i64::from(*value),
+        SqlNumeric::Int { value } => i64::from(*value),
+        SqlNumeric::BigInt { value } => *value,
+        SqlNumeric::Real { value } => rounded(f64::from(*value))?,
+        SqlNumeric::DoublePrecision { value } => rounded(*value)?,
+    };
+    match typ {
+        SqlType::SmallInt => i16::try_from(value)
+            .ok()
+            .map(|value| SqlNumeric::SmallInt { value }),
+        SqlType::Int => i32::try_from(value)
+            .ok()
+            .map(|value| SqlNumeric::Int { value }),
+        _ => Some(SqlNumeric::BigInt { value }),
+    }
+}
+
+// `value` converted for a column of type `typ` declared with `type_modifier`, as long as
+// the two are assignable. Numbers rounded to fit are warned about, as is text cut in
+// 'truncate' mode.
+fn assignment_cast(
+    value: &SqlValue,
+    typ: SqlType,
+    type_modifier: Option<usize>,
+    mode: CastMode,
+) -> Result<SqlValue, BackendError> {
+    let out_of_range = || {
+        BackendError::NumericValueOutOfRange(format!(
+            "Value {} is out of range for type {}.",
+            value, typ
+        ))
+    };
+    match (value, typ) {
+        (SqlValue::Null, _) => Ok(SqlValue::Null),
+        (SqlValue::Numeric(num), SqlType::SmallInt | SqlType::Int | SqlType::BigInt) => {
+            let fraction = match num {
+                SqlNumeric::Real { value } => has_fraction(f64::from(*value)),
+                SqlNumeric::DoublePrecision { value } => has_fraction(*value),
+                _ => false,
+            };
+            match to_integer(num, typ) {
+                Some(num) => {
+                    if fraction {
+                        warn(Warning::new(
+                            WARNING,
+                            format!("Value rounded to fit type {}.", typ),
+                        ));
+                    }
+                    Ok(SqlValue::Numeric(num))
+                }
+                None => Err(out_of_range()),
+            }
+        }
+        (SqlValue::Numeric(_), SqlType::Real) => match value.to_type(typ)? {
+            SqlValue::Numeric(SqlNumeric::Real { value: real }) if real.is_infinite() => {
+                Err(out_of_range())
+            }
+            real => Ok(real),
+        },
+        (value, typ) if is_text(typ) => {
+            let text = value.to_string();
+            let text = match type_modifier {
+                // Like Postgres, only spaces are cut off silently
+                Some(length) if text.chars().count() > length => {
+                    let (kept, cut) = text.split_at(
+                        text.char_indices()
+                            .nth(length)
+                            .map_or(text.len(), |(idx, _)| idx),
+                    );
+                    if cut.chars().any(|c| c != ' ') {
+                        if mode == CastMode::Strict {
+                            return Err(BackendError::StringDataRightTruncation(format!(
+                                "Value too long for type {}({}).",
+                                typ, length
+                            )));
+                        }
+                        warn(Warning::new(
+                            STRING_DATA_RIGHT_TRUNCATION,
+                            format!("Value truncated to fit type {}({}).", typ, length),
+                        ));
+                    }
+                    kept.to_string()
+                }
+                _ => text,
+            };
+            Ok(SqlValue::Text(SqlText::Text { value: text }).to_type(typ)?)
+        }
+        (value, typ) => Ok(value.to_type(typ)?),
+    }
+}
+
+impl Table {
+    // `value` as the column at `column` holds it, for the row `row` of an INSERT, counted
+    // from 1, which along with the column the error names if it doesn't fit
+    pub(super) fn assign(
+        &self,
+        column: usize,
+        value: &SqlValue,
+        row: usize,
+        mode: CastMode,
+    ) -> Result<SqlValue, BackendError> {
+        let typ = self.column_types[column];
+        let from = value.get_type();
+        if !is_assignable(from, typ) {
+            return Err(BackendError::DatatypeMismatch(format!(
+                "Cannot assign {} value in row {} to column \"{}\" of type {} without an explicit cast.",
+                from, row, self.columns[column], typ
+            )));
+        }
+        let type_modifier = self
+            .column_constraints
+            .get(column)
+            .and_then(|constraints| constraints.type_modifier);
+        assignment_cast(value, typ, type_modifier, mode).map_err(|err| {
+            err.map_message(|msg| {
+                format!(
+                    "Cannot assign {} value in row {} to column \"{}\" of type {}: {}",
+                    from, row, self.columns[column], typ, msg
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod assign_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::{BatchOptions, MemoryBackend};
+    use crate::sql_types::{SqlNumeric, SqlText, ALL_TYPES};
+
+    // The column types each type can be assigned to, in the order of `ALL_TYPES`:
+    // SMALLINT, INT, BIGINT, REAL, DOUBLE PRECISION, TEXT, CHAR, VARCHAR, BOOL, NULL, TYPE
+    const ASSIGNABLE: [(SqlType, &str); 11] = [
+        (SqlType::SmallInt, "yyyyyyyy..."),
+        (SqlType::Int, "yyyyyyyy..."),
+        (SqlType::BigInt, "yyyyyyyy..."),
+        (SqlType::Real, "yyyyyyyy..."),
+        (SqlType::DoublePrecision, "yyyyyyyy..."),
+        (SqlType::Text, ".....yyy..."),
+        (SqlType::Char, ".....yyy..."),
+        (SqlType::VarChar, ".....yyy..."),
+        (SqlType::Boolean, ".....yyyy.."),
+        (SqlType::Null, "yyyyyyyyyyy"),
+        (SqlType::Type, "..........y"),
+    ];
+
+    #[test]
+    fn test_assignable_types() {
+        for (from, expected) in ASSIGNABLE.iter() {
+            for (to, expected) in ALL_TYPES.iter().zip(expected.chars()) {
+                assert_eq!(
+                    is_assignable(*from, *to),
+                    expected == 'y',
+                    "{} into {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_every_type_pair() {
+        let columns = [
+            (SqlType::SmallInt, "SMALLINT"),
+            (SqlType::Int, "INT"),
+            (SqlType::BigInt, "BIGINT"),
+            (SqlType::Real, "REAL"),
+            (SqlType::DoublePrecision, "DOUBLE PRECISION"),
+            (SqlType::Text, "TEXT"),
+            (SqlType::Char, "CHAR(5)"),
+            (SqlType::VarChar, "VARCHAR(5)"),
+            (SqlType::Boolean, "BOOLEAN"),
+        ];
+        let values = [
+            (SqlType::SmallInt, "2::SMALLINT"),
+            (SqlType::Int, "2"),
+            (SqlType::BigInt, "2::BIGINT"),
+            (SqlType::Real, "2::REAL"),
+            (SqlType::DoublePrecision, "2.0"),
+            (SqlType::Text, "'2'"),
+            (SqlType::Char, "'2'::CHAR"),
+            (SqlType::VarChar, "'2'::VARCHAR"),
+            (SqlType::Boolean, "TRUE"),
+            (SqlType::Null, "NULL"),
+        ];
+        let mut mb = MemoryBackend::new();
+        for (to, declared) in columns.iter() {
+            mb.eval_query(&format!("CREATE TABLE t (v {});", declared))
+                .unwrap();
+            for (from, value) in values.iter() {
+                let result = mb.eval_query(&format!("INSERT INTO t VALUES ({});", value));
+                match result {
+                    Ok(_) => assert!(is_assignable(*from, *to), "{} into {}", value, declared),
+                    Err(BackendError::DatatypeMismatch(msg)) => {
+                        assert!(!is_assignable(*from, *to), "{} into {}", value, declared);
+                        assert_eq!(
+                            msg,
+                            format!(
+                                "Cannot assign {} value in row 1 to column \"v\" of type {} \
+                                without an explicit cast.",
+                                from, to
+                            )
+                        );
+                    }
+                    Err(err) => panic!("{} into {}: {:?}", value, declared, err),
+                }
+            }
+            mb.eval_query("DROP TABLE t;").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_assignment_conversions() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (s SMALLINT, i INT, r REAL, name VARCHAR(4), flag TEXT);
+            INSERT INTO t VALUES (1.5, 2.5, 3, 'ab  ', TRUE), (-7, 1e9, 0.5, 'abcd ', 12);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM t;"),
+            vec!["2|2|3|ab  |true", "-7|1000000000|0.5|abcd|12"]
+        );
+    }
+
+    #[test]
+    fn test_assignment_errors() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (s SMALLINT, name VARCHAR(3));")
+            .unwrap();
+        match mb.eval_query("INSERT INTO t VALUES (1, 'a'), (2, 'b'), ('3', 'c');") {
+            Err(BackendError::DatatypeMismatch(msg)) => assert_eq!(
+                msg,
+                "Cannot assign Text value in row 3 to column \"s\" of type Smallint \
+                without an explicit cast."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        match mb.eval_query("INSERT INTO t VALUES (1, 'a'), (40000, 'b');") {
+            Err(BackendError::NumericValueOutOfRange(msg)) => assert_eq!(
+                msg,
+                "Cannot assign Int value in row 2 to column \"s\" of type Smallint: \
+                Value 40000 is out of range for type Smallint."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        match mb.eval_query("INSERT INTO t VALUES (1, 'abcd');") {
+            Err(BackendError::StringDataRightTruncation(msg)) => assert_eq!(
+                msg,
+                "Cannot assign Text value in row 1 to column \"name\" of type Varchar: \
+                Value too long for type Varchar(3)."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        // None of the rows of a failed INSERT went in, and an explicit cast is let through
+        assert!(rows(&mut mb, "SELECT * FROM t;").is_empty());
+        mb.eval_query("INSERT INTO t VALUES ('3'::SMALLINT, 'c');")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), vec!["3|c"]);
+    }
+
+    #[test]
+    fn test_assignment_errors_in_batches() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (s SMALLINT, name VARCHAR(3));")
+            .unwrap();
+        let int = |value| SqlValue::Numeric(SqlNumeric::Int { value });
+        let text = |value: &str| {
+            SqlValue::Text(SqlText::Text {
+                value: value.to_string(),
+            })
+        };
+        let insert = mb.prepare("INSERT INTO t VALUES ($1, $2);").unwrap();
+        let result = insert
+            .execute_many_with(
+                &mut mb,
+                vec![
+                    vec![int(1), text("abc")],
+                    vec![int(2), text("abcdef")],
+                    vec![int(40000), text("b")],
+                    vec![text("4"), text("c")],
+                ],
+                BatchOptions {
+                    continue_on_error: true,
+                },
+            )
+            .unwrap();
+        assert_eq!(result.executed, 1);
+        let failures: Vec<String> = result
+            .failures
+            .iter()
+            .map(|failure| format!("{}: {}", failure.index, failure.error))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                "1: Cannot assign Text value in row 1 to column \"name\" of type Varchar: \
+                Value too long for type Varchar(3).",
+                "2: Cannot assign Int value in row 1 to column \"s\" of type Smallint: \
+                Value 40000 is out of range for type Smallint.",
+                "3: Cannot assign Text value in row 1 to column \"s\" of type Smallint \
+                without an explicit cast.",
+            ]
+        );
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), vec!["1|abc"]);
+    }
+}
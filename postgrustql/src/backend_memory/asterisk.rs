@@ -0,0 +1,67 @@
+use crate::ast::{AsteriskModifiers, SelectItem, TableColumn};
+use crate::backend::BackendError;
+
+// What `*` lists for one of the columns it goes over
+#[derive(Clone, Copy, Debug)]
+pub(super) enum AsteriskColumn<'a> {
+    Kept,
+    Excluded,
+    Replaced(&'a SelectItem),
+}
+
+// Works out what a `*` with EXCEPT and REPLACE lists for each of `columns`, given by the
+// name of their FROM item and their own. An unqualified name goes for every column of
+// that name, like both `id`s of two joined tables. Naming a column the `*` doesn't go
+// over is an error, and so is naming one twice.
+pub(super) fn expand_asterisk<'a>(
+    modifiers: &'a AsteriskModifiers,
+    columns: &[(&str, &str)],
+) -> Result<Vec<AsteriskColumn<'a>>, BackendError> {
+    let mut expanded = vec![AsteriskColumn::Kept; columns.len()];
+    let mut apply = |named: &TableColumn, to: AsteriskColumn<'a>| {
+        let mut found = false;
+        for (idx, (source, name)) in columns.iter().enumerate() {
+            let matches = *name == named.col_name
+                && named
+                    .table_name
+                    .as_deref()
+                    .is_none_or(|table| table == *source);
+            if !matches {
+                continue;
+            }
+            if !matches!(expanded[idx], AsteriskColumn::Kept) {
+                return Err(BackendError::DuplicateColumn(format!(
+                    "Column \"{}\" is named more than once after *.",
+                    display_name(named)
+                )));
+            }
+            expanded[idx] = to;
+            found = true;
+        }
+        match found {
+            true => Ok(()),
+            false => Err(BackendError::UndefinedColumn(format!(
+                "Column \"{}\" is not one of the columns of *.",
+                display_name(named)
+            ))),
+        }
+    };
+    for column in &modifiers.except {
+        apply(column, AsteriskColumn::Excluded)?;
+    }
+    for item in &modifiers.replace {
+        let column = TableColumn {
+            col_name: item.as_clause.clone().unwrap_or_default(),
+            table_name: None,
+        };
+        apply(&column, AsteriskColumn::Replaced(item))?;
+    }
+    Ok(expanded)
+}
+
+fn display_name(column: &TableColumn) -> String {
+    match &column.table_name {
+        Some(table_name) => format!("{}.{}", table_name, column.col_name),
+        None => column.col_name.clone(),
+    }
+}
@@ -0,0 +1,838 @@
+use super::cache::statement_tables;
+use super::resolver::local_name;
+use super::MemoryBackend;
+use crate::ast::*;
+use crate::backend::{BackendError, EvalResult};
+use crate::lexer::Token;
+use crate::parser::parse_condition;
+use crate::sql_types::SqlValue;
+
+// Decides which statements a connection may run, see `Connection::set_authorizer`
+pub type Authorizer = dyn Fn(&StatementInfo) -> AuthDecision + Send + Sync;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StatementKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    CreateTable,
+    CreateTableAs,
+    CreateIndex,
+    DropTable,
+    DropIndex,
+    CreateSequence,
+    DropSequence,
+    CreateView,
+    DropView,
+    CreateTrigger,
+    DropTrigger,
+    AlterTable,
+    Reindex,
+    Vacuum,
+    Set,
+    Show,
+    Reset,
+    Explain,
+    Listen,
+    Notify,
+    Unlisten,
+    DeclareCursor,
+    Fetch,
+    Close,
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl StatementKind {
+    pub fn of(statement: &Statement) -> Self {
+        match statement {
+            Statement::SelectStatement(_) => StatementKind::Select,
+            Statement::InsertStatement(_) => StatementKind::Insert,
+            Statement::UpdateStatement(_) => StatementKind::Update,
+            Statement::DeleteStatement(_) => StatementKind::Delete,
+            // A WITH is told apart by what it changes, if anything
+            Statement::WithStatement(with) => {
+                StatementKind::of(with.writing_statement().unwrap_or(&with.statement))
+            }
+            Statement::CreateTableStatement(_) => StatementKind::CreateTable,
+            Statement::CreateTableAsStatement(_) => StatementKind::CreateTableAs,
+            Statement::CreateIndexStatement(_) => StatementKind::CreateIndex,
+            Statement::DropTableStatement(_) => StatementKind::DropTable,
+            Statement::DropIndexStatement(_) => StatementKind::DropIndex,
+            Statement::CreateSequenceStatement(_) => StatementKind::CreateSequence,
+            Statement::DropSequenceStatement(_) => StatementKind::DropSequence,
+            Statement::CreateViewStatement(_) => StatementKind::CreateView,
+            Statement::DropViewStatement(_) => StatementKind::DropView,
+            Statement::CreateTriggerStatement(_) => StatementKind::CreateTrigger,
+            Statement::DropTriggerStatement(_) => StatementKind::DropTrigger,
+            Statement::AlterTableStatement(_) => StatementKind::AlterTable,
+            Statement::ReindexStatement(_) => StatementKind::Reindex,
+            Statement::VacuumStatement(_) => StatementKind::Vacuum,
+            Statement::SetStatement(_) => StatementKind::Set,
+            Statement::ShowStatement(_) => StatementKind::Show,
+            Statement::ResetStatement(_) => StatementKind::Reset,
+            Statement::ListenStatement(_) => StatementKind::Listen,
+            Statement::NotifyStatement(_) => StatementKind::Notify,
+            Statement::UnlistenStatement(_) => StatementKind::Unlisten,
+            Statement::DeclareCursorStatement(_) => StatementKind::DeclareCursor,
+            Statement::FetchStatement(_) => StatementKind::Fetch,
+            Statement::CloseStatement(_) => StatementKind::Close,
+            Statement::TransactionStatement(TransactionStatement::Begin) => StatementKind::Begin,
+            Statement::TransactionStatement(TransactionStatement::Commit) => StatementKind::Commit,
+            Statement::TransactionStatement(TransactionStatement::Rollback) => {
+                StatementKind::Rollback
+            }
+            Statement::ExplainStatement(_) => StatementKind::Explain,
+        }
+    }
+}
+
+// A column named by a statement, with the table or alias it was qualified by. `*` stands
+// for every column.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ColumnReference {
+    pub table: Option<String>,
+    pub name: String,
+}
+
+// What an authorizer is told about a statement before it runs
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StatementInfo {
+    pub kind: StatementKind,
+    // Tables read, written or changed, each once in the order they first come up
+    pub tables: Vec<String>,
+    // Columns named anywhere in the statement, subqueries included
+    pub columns: Vec<ColumnReference>,
+    // Whether the statement changes a table or the schema, SET only changes the session
+    pub writes: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AuthDecision {
+    Allow,
+    // The statement fails with an insufficient privilege error, with this message
+    Deny(String),
+    // The statement runs as if each table held just the rows its filter holds for.
+    // Only reads are filtered, rows can still be inserted whatever they hold.
+    Filter(Vec<RowFilter>),
+}
+
+// A condition on the columns of a table, like `tenant_id = 7`, in SQL
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RowFilter {
+    pub table: String,
+    pub condition: String,
+}
+
+impl RowFilter {
+    pub fn new(table: impl Into<String>, condition: impl Into<String>) -> Self {
+        RowFilter {
+            table: table.into(),
+            condition: condition.into(),
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Runs the statements of `query` once the authorizer allowed every one of them, so a
+    // denied statement keeps those before it from running as well. Like `eval_query_on`
+    // those `eval_results` has the results of are skipped.
+    pub(super) fn eval_authorized(
+        &mut self,
+        query: &str,
+        authorizer: &Authorizer,
+        eval_results: &mut Vec<EvalResult<SqlValue>>,
+    ) -> Result<(), BackendError> {
+        let mut statements = self.parse_cached(query)?;
+        // The authorizer is told of the tables views read, and filters them
+        for statement in statements.iter_mut() {
+            self.expand_views(statement)?;
+            self.authorize(statement, authorizer)?;
+        }
+
+        let done = eval_results.len();
+        for statement in statements.into_iter().skip(done) {
+            eval_results.push(self.eval_statement(statement)?);
+        }
+        Ok(())
+    }
+
+    // Fails for a statement the authorizer denies, or adds the filters it asks for
+    pub(super) fn authorize(
+        &self,
+        statement: &mut Statement,
+        authorizer: &Authorizer,
+    ) -> Result<(), BackendError> {
+        match authorizer(&self.statement_info(statement)) {
+            AuthDecision::Allow => Ok(()),
+            AuthDecision::Deny(msg) => Err(BackendError::InsufficientPrivilege(msg)),
+            AuthDecision::Filter(filters) => filter_statement(statement, &filters),
+        }
+    }
+
+    pub fn statement_info(&self, statement: &Statement) -> StatementInfo {
+        let mut tables = vec![];
+        let mut columns = vec![];
+        match statement {
+            Statement::CreateTableStatement(create_table) => {
+                tables.push(create_table.name.clone());
+                for column in &create_table.cols {
+                    add_column(&mut columns, Some(&create_table.name), &column.name);
+                }
+            }
+            Statement::CreateTableAsStatement(create_table_as) => {
+                tables.push(create_table_as.name.clone());
+                for column in &create_table_as.columns {
+                    add_column(&mut columns, Some(&create_table_as.name), column);
+                }
+            }
+            Statement::CreateIndexStatement(create_index) => {
+                tables.push(create_index.table.clone())
+            }
+            Statement::DropTableStatement(drop_table) => tables.push(drop_table.name.clone()),
+            Statement::CreateViewStatement(create_view) => tables.push(create_view.name.clone()),
+            Statement::DropViewStatement(drop_view) => tables.push(drop_view.name.clone()),
+            Statement::CreateTriggerStatement(create_trigger) => {
+                tables.push(create_trigger.table.clone());
+                statement_tables(&create_trigger.action, &mut tables);
+                tables.extend(create_trigger.action.changed_table().map(str::to_string));
+            }
+            Statement::DropTriggerStatement(drop_trigger) => {
+                tables.push(drop_trigger.table.clone())
+            }
+            Statement::DropIndexStatement(drop_index) => {
+                tables.extend(self.index_table(&drop_index.name).map(|(_, table)| table))
+            }
+            Statement::AlterTableStatement(alter_table) => {
+                tables.push(alter_table.name.clone());
+                match &alter_table.action {
+                    AlterTableAction::DropColumn { name, .. } => {
+                        add_column(&mut columns, Some(&alter_table.name), name)
+                    }
+                }
+            }
+            Statement::ReindexStatement(reindex) => tables.push(reindex.table.clone()),
+            Statement::VacuumStatement(vacuum) => tables.extend(vacuum.table.clone()),
+            Statement::SelectStatement(_)
+            | Statement::CreateSequenceStatement(_)
+            | Statement::DropSequenceStatement(_)
+            | Statement::InsertStatement(_)
+            | Statement::UpdateStatement(_)
+            | Statement::DeleteStatement(_)
+            | Statement::WithStatement(_)
+            | Statement::ExplainStatement(_)
+            | Statement::SetStatement(_)
+            | Statement::ShowStatement(_)
+            | Statement::ResetStatement(_)
+            | Statement::ListenStatement(_)
+            | Statement::NotifyStatement(_)
+            | Statement::UnlistenStatement(_)
+            | Statement::DeclareCursorStatement(_)
+            | Statement::FetchStatement(_)
+            | Statement::CloseStatement(_)
+            | Statement::TransactionStatement(_) => {}
+        }
+        statement_tables(statement, &mut tables);
+        // Tables are named without the schema they may have been qualified by
+        let mut tables: Vec<String> = tables
+            .iter()
+            .map(|table| local_name(table).to_string())
+            .collect();
+        let mut seen = vec![];
+        tables.retain(|table| {
+            let first = !seen.contains(table);
+            seen.push(table.clone());
+            first
+        });
+        statement_columns(statement, &mut columns);
+
+        StatementInfo {
+            kind: StatementKind::of(statement),
+            tables,
+            columns,
+            writes: !statement.is_read_only(),
+        }
+    }
+}
+
+fn add_column(columns: &mut Vec<ColumnReference>, table: Option<&String>, name: &str) {
+    let column = ColumnReference {
+        table: table.cloned(),
+        name: name.to_string(),
+    };
+    if !columns.contains(&column) {
+        columns.push(column);
+    }
+}
+
+fn statement_columns(statement: &Statement, columns: &mut Vec<ColumnReference>) {
+    match statement {
+        Statement::SelectStatement(select) => select_columns(select, columns),
+        Statement::CreateTableAsStatement(create_table_as) => {
+            select_columns(&create_table_as.query, columns)
+        }
+        Statement::ExplainStatement(explain) => select_columns(&explain.select, columns),
+        Statement::DeclareCursorStatement(declare) => select_columns(&declare.query, columns),
+        Statement::CreateViewStatement(create_view) => select_columns(&create_view.query, columns),
+        Statement::InsertStatement(insert) => {
+            for value in insert.rows.iter().flatten() {
+                expression_columns(value, columns);
+            }
+            if let Some(query) = &insert.query {
+                select_columns(query, columns);
+            }
+            item_columns(&insert.returning, columns);
+        }
+        Statement::UpdateStatement(update) => {
+            for assignment in &update.assignments {
+                add_column(columns, Some(&update.table), &assignment.column);
+                expression_columns(&assignment.value, columns);
+            }
+            expression_columns(&update.where_clause, columns);
+            for order_by in &update.order_by {
+                expression_columns(&order_by.exp, columns);
+            }
+            item_columns(&update.returning, columns);
+        }
+        Statement::DeleteStatement(delete) => {
+            expression_columns(&delete.where_clause, columns);
+            for order_by in &delete.order_by {
+                expression_columns(&order_by.exp, columns);
+            }
+            item_columns(&delete.returning, columns);
+        }
+        Statement::WithStatement(with) => {
+            for query in &with.queries {
+                statement_columns(&query.statement, columns);
+            }
+            statement_columns(&with.statement, columns);
+        }
+        Statement::CreateTableStatement(create_table) => {
+            for check in &create_table.checks {
+                expression_columns(&check.expression, columns);
+            }
+        }
+        Statement::CreateIndexStatement(create_index) => {
+            expression_columns(&create_index.expression, columns)
+        }
+        Statement::DropTableStatement(_)
+        | Statement::DropIndexStatement(_)
+        | Statement::CreateSequenceStatement(_)
+        | Statement::DropSequenceStatement(_)
+        | Statement::DropViewStatement(_)
+        | Statement::CreateTriggerStatement(_)
+        | Statement::DropTriggerStatement(_)
+        | Statement::AlterTableStatement(_)
+        | Statement::ReindexStatement(_)
+        | Statement::VacuumStatement(_)
+        | Statement::SetStatement(_)
+        | Statement::ShowStatement(_)
+        | Statement::ResetStatement(_)
+        | Statement::ListenStatement(_)
+        | Statement::NotifyStatement(_)
+        | Statement::UnlistenStatement(_)
+        | Statement::FetchStatement(_)
+        | Statement::CloseStatement(_)
+        | Statement::TransactionStatement(_) => {}
+    }
+}
+
+fn select_columns(select: &SelectStatement, columns: &mut Vec<ColumnReference>) {
+    item_columns(&select.items, columns);
+    for source in &select.from {
+        source_columns(source, columns);
+    }
+    expression_columns(&select.where_clause, columns);
+    for exp in &select.group_by {
+        expression_columns(exp, columns);
+    }
+    expression_columns(&select.having, columns);
+    for order_by in &select.order_by {
+        expression_columns(&order_by.exp, columns);
+    }
+}
+
+fn item_columns(items: &[SelectItem], columns: &mut Vec<ColumnReference>) {
+    for item in items {
+        if item.asterisk {
+            add_column(columns, item.asterisk_source.as_ref(), "*");
+            if let Some(modifiers) = &item.asterisk_modifiers {
+                item_columns(&modifiers.replace, columns);
+            }
+        } else {
+            expression_columns(&item.expression, columns);
+        }
+    }
+}
+
+fn source_columns(source: &RowDataSource, columns: &mut Vec<ColumnReference>) {
+    let joins = match source {
+        RowDataSource::SubSelect { select, joins, .. } => {
+            select_columns(select, columns);
+            joins
+        }
+        RowDataSource::Function { call, joins, .. } => {
+            for arg in &call.args {
+                expression_columns(arg, columns);
+            }
+            joins
+        }
+        RowDataSource::Values { rows, joins, .. } => {
+            for exp in rows.iter().flatten() {
+                expression_columns(exp, columns);
+            }
+            joins
+        }
+        RowDataSource::Table { joins, .. } => joins,
+    };
+    for join in joins {
+        source_columns(&join.source, columns);
+        expression_columns(&join.on, columns);
+    }
+}
+
+fn expression_columns(expression: &Expression, columns: &mut Vec<ColumnReference>) {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => add_column(columns, None, value),
+        Expression::TableColumn(table_column) => add_column(
+            columns,
+            table_column.table_name.as_ref(),
+            &table_column.col_name,
+        ),
+        Expression::Binary(binary) => {
+            expression_columns(&binary.first, columns);
+            expression_columns(&binary.second, columns);
+        }
+        Expression::Unary(unary) => expression_columns(&unary.first, columns),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            expression_columns(data, columns)
+        }
+        Expression::SubSelect(select) | Expression::Exists(select) => {
+            select_columns(select, columns)
+        }
+        Expression::Quantified(quantified) => {
+            expression_columns(&quantified.first, columns);
+            if let QuantifiedSet::SubSelect(select) = &quantified.set {
+                select_columns(select, columns);
+            }
+        }
+        Expression::FunctionCall(call) => {
+            for arg in &call.args {
+                expression_columns(arg, columns);
+            }
+            if let Some(filter) = &call.filter {
+                expression_columns(filter, columns);
+            }
+            if let Some(over) = &call.over {
+                for exp in &over.partition_by {
+                    expression_columns(exp, columns);
+                }
+                for order_by in &over.order_by {
+                    expression_columns(&order_by.exp, columns);
+                }
+            }
+        }
+        Expression::Literal(_) | Expression::ProcessedTableColumn(_) | Expression::Empty => {}
+    }
+}
+
+// Makes every read of a filtered table, in subqueries and joins too, read a subquery
+// keeping the rows its condition holds for instead. The subquery takes the name or alias
+// of the table, so the rest of the statement sees no difference but the missing rows.
+fn filter_statement(statement: &mut Statement, filters: &[RowFilter]) -> Result<(), BackendError> {
+    let mut conditions = vec![];
+    for filter in filters {
+        match parse_condition(&filter.condition) {
+            Ok(condition) => conditions.push((filter.table.as_str(), condition)),
+            Err(err) => {
+                return Err(BackendError::SyntaxError(format!(
+                    "Invalid filter on table \"{}\": {}",
+                    filter.table, err
+                )))
+            }
+        }
+    }
+
+    match statement {
+        Statement::SelectStatement(select) => filter_select(select, &conditions),
+        Statement::CreateTableAsStatement(create_table_as) => {
+            filter_select(&mut create_table_as.query, &conditions)
+        }
+        Statement::ExplainStatement(explain) => filter_select(&mut explain.select, &conditions),
+        Statement::DeclareCursorStatement(declare) => {
+            filter_select(&mut declare.query, &conditions)
+        }
+        Statement::InsertStatement(insert) => {
+            for value in insert.rows.iter_mut().flatten() {
+                filter_expression(value, &conditions);
+            }
+            if let Some(query) = &mut insert.query {
+                filter_select(query, &conditions);
+            }
+            filter_items(&mut insert.returning, &conditions);
+        }
+        // Rows the condition doesn't hold for are left as they are
+        Statement::UpdateStatement(update) => {
+            for assignment in update.assignments.iter_mut() {
+                filter_expression(&mut assignment.value, &conditions);
+            }
+            filter_expression(&mut update.where_clause, &conditions);
+            for order_by in update.order_by.iter_mut() {
+                filter_expression(&mut order_by.exp, &conditions);
+            }
+            filter_items(&mut update.returning, &conditions);
+            filter_where(&mut update.where_clause, &update.table, &conditions);
+        }
+        Statement::DeleteStatement(delete) => {
+            filter_expression(&mut delete.where_clause, &conditions);
+            for order_by in delete.order_by.iter_mut() {
+                filter_expression(&mut order_by.exp, &conditions);
+            }
+            filter_items(&mut delete.returning, &conditions);
+            filter_where(&mut delete.where_clause, &delete.table, &conditions);
+        }
+        Statement::WithStatement(with) => {
+            for query in with.queries.iter_mut() {
+                filter_statement(&mut query.statement, filters)?;
+            }
+            filter_statement(&mut with.statement, filters)?;
+        }
+        Statement::CreateTableStatement(_)
+        | Statement::CreateIndexStatement(_)
+        | Statement::DropTableStatement(_)
+        | Statement::DropIndexStatement(_)
+        | Statement::CreateSequenceStatement(_)
+        | Statement::DropSequenceStatement(_)
+        | Statement::CreateViewStatement(_)
+        | Statement::DropViewStatement(_)
+        | Statement::CreateTriggerStatement(_)
+        | Statement::DropTriggerStatement(_)
+        | Statement::AlterTableStatement(_)
+        | Statement::ReindexStatement(_)
+        | Statement::VacuumStatement(_)
+        | Statement::SetStatement(_)
+        | Statement::ShowStatement(_)
+        | Statement::ResetStatement(_)
+        | Statement::ListenStatement(_)
+        | Statement::NotifyStatement(_)
+        | Statement::UnlistenStatement(_)
+        | Statement::FetchStatement(_)
+        | Statement::CloseStatement(_)
+        | Statement::TransactionStatement(_) => {}
+    }
+    Ok(())
+}
+
+// Adds the condition on `table` to the WHERE of a statement changing its rows
+fn filter_where(where_clause: &mut Expression, table: &str, conditions: &[(&str, Expression)]) {
+    let condition = conditions
+        .iter()
+        .find(|(filtered, _)| *filtered == table)
+        .map(|(_, condition)| condition.clone());
+    if let Some(condition) = condition {
+        *where_clause = match std::mem::replace(where_clause, Expression::Empty) {
+            Expression::Empty => condition,
+            where_clause => Expression::Binary(BinaryExpression {
+                first: Box::new(condition),
+                second: Box::new(where_clause),
+                operand: Token::And,
+            }),
+        };
+    }
+}
+
+fn filter_select(select: &mut SelectStatement, conditions: &[(&str, Expression)]) {
+    filter_items(&mut select.items, conditions);
+    for source in select.from.iter_mut() {
+        filter_source(source, conditions);
+    }
+    filter_expression(&mut select.where_clause, conditions);
+    for exp in select.group_by.iter_mut() {
+        filter_expression(exp, conditions);
+    }
+    filter_expression(&mut select.having, conditions);
+    for order_by in select.order_by.iter_mut() {
+        filter_expression(&mut order_by.exp, conditions);
+    }
+}
+
+fn filter_items(items: &mut [SelectItem], conditions: &[(&str, Expression)]) {
+    for item in items.iter_mut() {
+        filter_expression(&mut item.expression, conditions);
+    }
+}
+
+fn filter_source(source: &mut RowDataSource, conditions: &[(&str, Expression)]) {
+    let joins = match source {
+        RowDataSource::SubSelect { select, joins, .. } => {
+            filter_select(select, conditions);
+            joins
+        }
+        RowDataSource::Function { call, joins, .. } => {
+            for arg in call.args.iter_mut() {
+                filter_expression(arg, conditions);
+            }
+            joins
+        }
+        RowDataSource::Values { rows, joins, .. } => {
+            for exp in rows.iter_mut().flatten() {
+                filter_expression(exp, conditions);
+            }
+            joins
+        }
+        RowDataSource::Table { joins, .. } => joins,
+    };
+    for join in joins.iter_mut() {
+        filter_source(&mut join.source, conditions);
+        filter_expression(&mut join.on, conditions);
+    }
+
+    if let RowDataSource::Table {
+        table_name,
+        as_clause,
+        sample,
+        joins,
+    } = source
+    {
+        let condition = conditions
+            .iter()
+            .find(|(table, _)| *table == local_name(table_name))
+            .map(|(_, condition)| condition.clone());
+        if let Some(where_clause) = condition {
+            let mut filtered = SelectStatement::new();
+            filtered.items.push(SelectItem {
+                expression: Expression::new(),
+                as_clause: None,
+                asterisk: true,
+                asterisk_source: None,
+                asterisk_modifiers: None,
+            });
+            // The rows are sampled before the filter, as they are before WHERE
+            filtered.from.push(RowDataSource::Table {
+                table_name: table_name.clone(),
+                as_clause: None,
+                sample: sample.take(),
+                joins: vec![],
+            });
+            filtered.where_clause = where_clause;
+            *source = RowDataSource::SubSelect {
+                as_clause: as_clause.clone().unwrap_or_else(|| table_name.clone()),
+                select: Box::new(filtered),
+                lateral: false,
+                joins: std::mem::take(joins),
+            };
+        }
+    }
+}
+
+fn filter_expression(expression: &mut Expression, conditions: &[(&str, Expression)]) {
+    match expression {
+        Expression::Binary(binary) => {
+            filter_expression(&mut binary.first, conditions);
+            filter_expression(&mut binary.second, conditions);
+        }
+        Expression::Unary(unary) => filter_expression(&mut unary.first, conditions),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            filter_expression(data, conditions)
+        }
+        Expression::SubSelect(select) | Expression::Exists(select) => {
+            filter_select(select, conditions)
+        }
+        Expression::Quantified(quantified) => {
+            filter_expression(&mut quantified.first, conditions);
+            if let QuantifiedSet::SubSelect(select) = &mut quantified.set {
+                filter_select(select, conditions);
+            }
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                filter_expression(arg, conditions);
+            }
+            if let Some(filter) = &mut call.filter {
+                filter_expression(filter, conditions);
+            }
+        }
+        Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+#[cfg(test)]
+mod authorize_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{rows, shared};
+    use crate::backend_memory::Connection;
+
+    fn connection() -> Connection {
+        Connection::open(shared(
+            "CREATE TABLE orders (id INT, tenant_id INT, item TEXT);
+            CREATE TABLE notes (order_id INT, tenant_id INT, note TEXT);
+            INSERT INTO orders VALUES (1, 1, 'apple'), (2, 2, 'pear'), (3, 1, 'plum');
+            INSERT INTO notes VALUES (1, 1, 'ripe'), (2, 2, 'green'), (3, 2, 'odd');",
+        ))
+    }
+
+    #[test]
+    fn test_read_only_authorizer() {
+        let mut connection = connection();
+        connection.set_authorizer(|info| {
+            if info.writes {
+                AuthDecision::Deny(format!("{:?} is not allowed", info.kind))
+            } else {
+                AuthDecision::Allow
+            }
+        });
+
+        let tests = vec![
+            "INSERT INTO orders VALUES (4, 1, 'fig');",
+            "CREATE TABLE other (id INT);",
+            "DROP TABLE orders;",
+            // Nothing runs when any statement is denied
+            "SELECT 1; INSERT INTO orders VALUES (4, 1, 'fig');",
+        ];
+        for query in tests {
+            let err = connection.eval_query(query).unwrap_err();
+            assert_eq!(err.code(), "42501", "{}", query);
+        }
+        assert_eq!(
+            connection
+                .eval_query("INSERT INTO orders VALUES (4, 1, 'fig');")
+                .unwrap_err()
+                .message(),
+            "Insert is not allowed"
+        );
+        assert_eq!(rows(&mut connection, "SELECT count(*) FROM orders;"), ["3"]);
+
+        connection.clear_authorizer();
+        connection
+            .eval_query("INSERT INTO orders VALUES (4, 1, 'fig');")
+            .unwrap();
+        assert_eq!(rows(&mut connection, "SELECT count(*) FROM orders;"), ["4"]);
+    }
+
+    #[test]
+    fn test_tenant_filter() {
+        let mut connection = connection();
+        connection.set_authorizer(|info| {
+            AuthDecision::Filter(
+                info.tables
+                    .iter()
+                    .map(|table| RowFilter::new(table.as_str(), "tenant_id = 1"))
+                    .collect(),
+            )
+        });
+
+        let tests = vec![
+            ("SELECT id, item FROM orders;", vec!["1|apple", "3|plum"]),
+            ("SELECT item FROM orders WHERE id > 1;", vec!["plum"]),
+            ("SELECT item FROM orders WHERE id = 2;", vec![]),
+            (
+                "SELECT o.item FROM orders AS o WHERE o.item <> 'apple';",
+                vec!["plum"],
+            ),
+            (
+                "SELECT item, note FROM orders INNER JOIN notes ON notes.order_id = orders.id;",
+                vec!["apple|ripe"],
+            ),
+            (
+                "SELECT item FROM orders LEFT JOIN notes ON notes.order_id = orders.id ORDER BY id;",
+                vec!["apple", "plum"],
+            ),
+            (
+                "SELECT count(*) FROM orders WHERE id = ANY (SELECT order_id FROM notes);",
+                vec!["1"],
+            ),
+            ("SELECT note FROM (SELECT note FROM notes) AS n;", vec!["ripe"]),
+            ("SELECT orders.id FROM public.orders;", vec!["1", "3"]),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(rows(&mut connection, query), expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        let mut connection = connection();
+        connection.set_authorizer(|_| {
+            AuthDecision::Filter(vec![RowFilter::new("orders", "tenant_id =")])
+        });
+        let err = connection.eval_query("SELECT id FROM orders;").unwrap_err();
+        assert_eq!(err.code(), "42601");
+    }
+
+    #[test]
+    fn test_statement_info() {
+        let mut connection = connection();
+        let column = |table: Option<&str>, name: &str| ColumnReference {
+            table: table.map(String::from),
+            name: name.to_string(),
+        };
+        let tests = vec![
+            (
+                "SELECT o.item FROM orders AS o INNER JOIN notes ON order_id = o.id WHERE tenant_id = 1;",
+                StatementKind::Select,
+                vec!["orders", "notes"],
+                vec![
+                    column(Some("o"), "item"),
+                    column(None, "order_id"),
+                    column(Some("o"), "id"),
+                    column(None, "tenant_id"),
+                ],
+                false,
+            ),
+            (
+                "INSERT INTO notes VALUES ((SELECT max(id) FROM orders), 1, 'x');",
+                StatementKind::Insert,
+                vec!["notes", "orders"],
+                vec![column(None, "id")],
+                true,
+            ),
+            (
+                "SELECT * FROM orders;",
+                StatementKind::Select,
+                vec!["orders"],
+                vec![column(None, "*")],
+                false,
+            ),
+            (
+                "ALTER TABLE orders DROP COLUMN item;",
+                StatementKind::AlterTable,
+                vec!["orders"],
+                vec![column(Some("orders"), "item")],
+                true,
+            ),
+            (
+                "SET collect_timing = true;",
+                StatementKind::Set,
+                vec![],
+                vec![],
+                false,
+            ),
+        ];
+        for (query, kind, tables, columns, writes) in tests {
+            let info = connection
+                .with_session(|backend| {
+                    let statement = backend.parse_cached(query)?.remove(0);
+                    Ok(backend.statement_info(&statement))
+                })
+                .unwrap();
+            assert_eq!(
+                info,
+                StatementInfo {
+                    kind,
+                    tables: tables.into_iter().map(String::from).collect(),
+                    columns,
+                    writes,
+                },
+                "{}",
+                query
+            );
+        }
+    }
+}
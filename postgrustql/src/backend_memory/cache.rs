@@ -0,0 +1,688 @@
+use std::collections::HashMap;
+
+use super::identifiers::truncate_identifier;
+use super::{
+    syntax_error, visit_statement_literals, visit_statement_literals_mut, MemoryBackend,
+    QueryTiming, StorageEngine,
+};
+use crate::ast::*;
+use crate::backend::BackendError;
+use crate::lexer::{is_literal, Lexer, NormalizedQuery, Token};
+use crate::parser::parse_tokens;
+
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct StatementCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // Entries dropped because the schema of a table they use changed
+    pub invalidations: u64,
+    // Entries dropped to make room for new ones
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+// Type of a literal replaced by a placeholder when normalizing. Queries only share a
+// cache entry if their literals line up type for type.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum LiteralKind {
+    Text,
+    Integer,
+    Decimal,
+    Boolean,
+}
+
+impl LiteralKind {
+    fn of(token: &Token) -> Self {
+        match token {
+            Token::StringValue { .. } => LiteralKind::Text,
+            Token::NumericValue { value } if value.contains(&['.', 'e', 'E'][..]) => {
+                LiteralKind::Decimal
+            }
+            Token::NumericValue { .. } => LiteralKind::Integer,
+            _ => LiteralKind::Boolean,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct CacheKey {
+    fingerprint: u64,
+    signature: Vec<LiteralKind>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CacheEntry {
+    // Query the statements were parsed from, repeating it verbatim skips lexing too
+    source: String,
+    // Normalized text, compared on lookup so fingerprint collisions can't mix up queries
+    text: String,
+    statements: Vec<Statement>,
+    // Set when not every literal of the query ended up as a literal expression, LIMIT and
+    // OFFSET counts for one. Those statements are only reused for the very same literals.
+    exact_literals: Option<Vec<Token>>,
+    // Schema version of every table the statements use, None for tables that did not exist
+    tables: Vec<(String, Option<u64>)>,
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn matches(&self, text: &str, literals: &[Token]) -> bool {
+        if self.text != text {
+            return false;
+        }
+        match &self.exact_literals {
+            Some(exact_literals) => exact_literals.as_slice() == literals,
+            None => true,
+        }
+    }
+}
+
+// Parsed statements of recently run queries, least recently used ones are evicted first
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct StatementCache {
+    capacity: usize,
+    entries: HashMap<CacheKey, CacheEntry>,
+    sources: HashMap<String, CacheKey>,
+    clock: u64,
+    stats: StatementCacheStats,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        StatementCache {
+            capacity,
+            entries: HashMap::new(),
+            sources: HashMap::new(),
+            clock: 0,
+            stats: StatementCacheStats::default(),
+        }
+    }
+
+    // Looks up the entry for `key`, dropping it if a table it uses changed since. This
+    // catches schema changes that bypassed invalidation, like a rolled back CREATE TABLE.
+    fn current_entry(
+        &mut self,
+        key: &CacheKey,
+        schema_version: impl Fn(&str) -> Option<u64>,
+    ) -> Option<&mut CacheEntry> {
+        let is_current = self
+            .entries
+            .get(key)?
+            .tables
+            .iter()
+            .all(|(table_name, version)| schema_version(table_name) == *version);
+        if !is_current {
+            self.remove(key);
+            self.stats.invalidations += 1;
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(entry)
+    }
+
+    fn get_source(
+        &mut self,
+        source: &str,
+        schema_version: impl Fn(&str) -> Option<u64>,
+    ) -> Option<Vec<Statement>> {
+        let key = self.sources.get(source)?.clone();
+        let statements = self
+            .current_entry(&key, schema_version)
+            .map(|entry| entry.statements.clone());
+        if statements.is_some() {
+            self.stats.hits += 1;
+        }
+        statements
+    }
+
+    fn get(
+        &mut self,
+        key: &CacheKey,
+        text: &str,
+        literals: &[Token],
+        schema_version: impl Fn(&str) -> Option<u64>,
+    ) -> Option<Vec<Statement>> {
+        let entry = match self.current_entry(key, schema_version) {
+            Some(entry) if entry.matches(text, literals) => entry,
+            _ => {
+                self.stats.misses += 1;
+                return None;
+            }
+        };
+
+        let mut statements = entry.statements.clone();
+        let mut literals = literals.iter();
+        for statement in statements.iter_mut() {
+            visit_statement_literals_mut(statement, &mut |token| {
+                if is_literal(token) {
+                    if let Some(literal) = literals.next() {
+                        *token = literal.clone();
+                    }
+                }
+            });
+        }
+        self.stats.hits += 1;
+        Some(statements)
+    }
+
+    fn insert(&mut self, key: CacheKey, mut entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.contains_key(&key) {
+            self.remove(&key);
+        } else {
+            self.evict_to(self.capacity - 1);
+        }
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.sources.insert(entry.source.clone(), key.clone());
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.sources.remove(&entry.source);
+        }
+    }
+
+    fn evict_to(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                self.remove(&key);
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    pub fn invalidate_table(&mut self, table_name: &str) {
+        let stale: Vec<CacheKey> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .tables
+                    .iter()
+                    .any(|(entry_table, _)| entry_table == table_name)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            self.remove(key);
+        }
+        self.stats.invalidations += stale.len() as u64;
+    }
+}
+
+impl MemoryBackend {
+    pub fn statement_cache_stats(&self) -> StatementCacheStats {
+        StatementCacheStats {
+            entries: self.statement_cache.entries.len(),
+            capacity: self.statement_cache.capacity,
+            ..self.statement_cache.stats
+        }
+    }
+
+    // A capacity of 0 turns the cache off
+    pub fn set_statement_cache_capacity(&mut self, capacity: usize) {
+        self.statement_cache.capacity = capacity;
+        self.statement_cache.evict_to(capacity);
+    }
+
+    // Gives the table a new schema version, dropping the cached statements that use it
+    pub(super) fn schema_changed(&mut self, table_name: &str) {
+        self.catalog_changed();
+        self.schema_version += 1;
+        let schema_version = self.schema_version;
+        if let Ok(Some(table)) = self.table_mut(table_name) {
+            table.schema_version = schema_version;
+        }
+        self.statement_cache.invalidate_table(table_name);
+    }
+
+    // Parses `query`, reusing the statements of an earlier query that differed from it at
+    // most in its literal values. Looking the query up counts as parsing it.
+    pub(super) fn parse_cached(&mut self, query: &str) -> Result<Vec<Statement>, BackendError> {
+        self.timing.last = QueryTiming::default();
+        let start = self.timing.now();
+        let statements = self.lookup_or_parse(query);
+        let elapsed = self.timing.since(start);
+        self.timing.last.parse = elapsed.saturating_sub(self.timing.last.lex);
+        if statements.is_err() {
+            self.fail_transaction_block();
+        }
+        statements
+    }
+
+    fn lookup_or_parse(&mut self, query: &str) -> Result<Vec<Statement>, BackendError> {
+        let (storage, temp_tables) = (&self.storage, &self.temp_tables);
+        let max_identifier_length = self.max_identifier_length;
+        // The statements are kept as written, the names they look up are cut down
+        let schema_version = |table_name: &str| {
+            let table_name = truncate_identifier(table_name, max_identifier_length);
+            temp_tables
+                .table(table_name)
+                .or_else(|| storage.table(table_name))
+                .map(|table| table.schema_version)
+        };
+        // Strings lex differently without standard_conforming_strings, the statements of
+        // such queries aren't cached
+        let options = self.lex_options();
+        let cached = self.statement_cache.capacity > 0 && options.standard_conforming_strings;
+        if cached {
+            if let Some(statements) = self.statement_cache.get_source(query, schema_version) {
+                return Ok(statements);
+            }
+        }
+
+        let lexing = self.timing.now();
+        let tokens = match Lexer::with_options(options).lex(query) {
+            Ok(tokens) => tokens,
+            Err(err) => return Err(syntax_error(err.into(), query, options)),
+        };
+        self.timing.last.lex = self.timing.since(lexing);
+        if !cached {
+            return match parse_tokens(query, tokens) {
+                Ok(ast) => Ok(ast.statements),
+                Err(err) => Err(syntax_error(err, query, options)),
+            };
+        }
+
+        let normalized = NormalizedQuery::from_tokens(&tokens, None);
+        let literals: Vec<Token> = tokens
+            .iter()
+            .filter(|token| is_literal(&token.token))
+            .map(|token| token.token.clone())
+            .collect();
+        let key = CacheKey {
+            fingerprint: normalized.fingerprint,
+            signature: literals.iter().map(LiteralKind::of).collect(),
+        };
+        if let Some(statements) =
+            self.statement_cache
+                .get(&key, &normalized.text, &literals, schema_version)
+        {
+            return Ok(statements);
+        }
+
+        let statements = match parse_tokens(query, tokens) {
+            Ok(ast) => ast.statements,
+            Err(err) => return Err(syntax_error(err, query, options)),
+        };
+
+        // Schema changes invalidate the entries of the tables they touch, their own included
+        let changes_schema = statements.iter().any(|statement| {
+            !matches!(
+                statement,
+                Statement::SelectStatement(_)
+                    | Statement::InsertStatement(_)
+                    | Statement::UpdateStatement(_)
+                    | Statement::DeleteStatement(_)
+                    | Statement::WithStatement(_)
+                    | Statement::ExplainStatement(_)
+            )
+        });
+        if changes_schema {
+            return Ok(statements);
+        }
+
+        let mut parsed_literals = vec![];
+        let mut table_names = vec![];
+        for statement in &statements {
+            visit_statement_literals(statement, &mut |token| {
+                if is_literal(token) {
+                    parsed_literals.push(token.clone());
+                }
+            });
+            statement_tables(statement, &mut table_names);
+        }
+        table_names.sort();
+        table_names.dedup();
+
+        let entry = CacheEntry {
+            source: query.to_string(),
+            text: normalized.text,
+            statements: statements.clone(),
+            exact_literals: if parsed_literals == literals {
+                None
+            } else {
+                Some(literals)
+            },
+            tables: table_names
+                .into_iter()
+                .map(|table_name| {
+                    let version = schema_version(&table_name);
+                    (table_name, version)
+                })
+                .collect(),
+            last_used: 0,
+        };
+        self.statement_cache.insert(key, entry);
+
+        Ok(statements)
+    }
+}
+
+// The tables a statement reads and changes, and the functions it calls, by name
+#[derive(Debug, Default)]
+pub(super) struct StatementReads {
+    pub tables: Vec<String>,
+    pub functions: Vec<String>,
+}
+
+pub(super) fn statement_tables(statement: &Statement, tables: &mut Vec<String>) {
+    let mut reads = StatementReads::default();
+    statement_reads(statement, &mut reads);
+    tables.extend(reads.tables);
+}
+
+pub(super) fn select_tables(select: &SelectStatement, tables: &mut Vec<String>) {
+    let mut reads = StatementReads::default();
+    select_reads(select, &mut reads);
+    tables.extend(reads.tables);
+}
+
+pub(super) fn statement_reads(statement: &Statement, reads: &mut StatementReads) {
+    match statement {
+        Statement::SelectStatement(select) => select_reads(select, reads),
+        Statement::InsertStatement(insert) => {
+            reads.tables.push(insert.table.clone());
+            for value in insert.rows.iter().flatten() {
+                expression_reads(value, reads);
+            }
+            if let Some(query) = &insert.query {
+                select_reads(query, reads);
+            }
+            item_reads(&insert.returning, reads);
+        }
+        Statement::UpdateStatement(update) => {
+            reads.tables.push(update.table.clone());
+            for assignment in &update.assignments {
+                expression_reads(&assignment.value, reads);
+            }
+            expression_reads(&update.where_clause, reads);
+            for order_by in &update.order_by {
+                expression_reads(&order_by.exp, reads);
+            }
+            item_reads(&update.returning, reads);
+        }
+        Statement::DeleteStatement(delete) => {
+            reads.tables.push(delete.table.clone());
+            expression_reads(&delete.where_clause, reads);
+            for order_by in &delete.order_by {
+                expression_reads(&order_by.exp, reads);
+            }
+            item_reads(&delete.returning, reads);
+        }
+        // The queries are read by name like tables, they are left out. The tables changed
+        // are kept even when a query has the same name, since they can't be queries.
+        Statement::WithStatement(with) => {
+            let statements = with.queries.iter().map(|query| query.statement.as_ref());
+            for statement in statements.chain(std::iter::once(with.statement.as_ref())) {
+                let mut read = StatementReads::default();
+                statement_reads(statement, &mut read);
+                reads
+                    .tables
+                    .extend(statement.changed_table().map(str::to_string));
+                reads.tables.extend(
+                    read.tables
+                        .into_iter()
+                        .filter(|table| !with.queries.iter().any(|query| query.name == *table)),
+                );
+                reads.functions.extend(read.functions);
+            }
+        }
+        Statement::CreateTableAsStatement(create_table_as) => {
+            select_reads(&create_table_as.query, reads)
+        }
+        Statement::ExplainStatement(explain) => select_reads(&explain.select, reads),
+        Statement::DeclareCursorStatement(declare) => select_reads(&declare.query, reads),
+        Statement::CreateViewStatement(create_view) => select_reads(&create_view.query, reads),
+        Statement::CreateTableStatement(_)
+        | Statement::CreateIndexStatement(_)
+        | Statement::DropTableStatement(_)
+        | Statement::DropIndexStatement(_)
+        | Statement::CreateSequenceStatement(_)
+        | Statement::DropSequenceStatement(_)
+        | Statement::DropViewStatement(_)
+        | Statement::CreateTriggerStatement(_)
+        | Statement::DropTriggerStatement(_)
+        | Statement::AlterTableStatement(_)
+        | Statement::ReindexStatement(_)
+        | Statement::VacuumStatement(_)
+        | Statement::SetStatement(_)
+        | Statement::ShowStatement(_)
+        | Statement::ResetStatement(_)
+        | Statement::ListenStatement(_)
+        | Statement::NotifyStatement(_)
+        | Statement::UnlistenStatement(_)
+        | Statement::FetchStatement(_)
+        | Statement::CloseStatement(_)
+        | Statement::TransactionStatement(_) => {}
+    }
+}
+
+fn select_reads(select: &SelectStatement, reads: &mut StatementReads) {
+    item_reads(&select.items, reads);
+    for source in &select.from {
+        source_reads(source, reads);
+    }
+    expression_reads(&select.where_clause, reads);
+    for exp in &select.group_by {
+        expression_reads(exp, reads);
+    }
+    expression_reads(&select.having, reads);
+    for order_by in &select.order_by {
+        expression_reads(&order_by.exp, reads);
+    }
+}
+
+fn item_reads(items: &[SelectItem], reads: &mut StatementReads) {
+    for item in items {
+        expression_reads(&item.expression, reads);
+    }
+}
+
+fn source_reads(source: &RowDataSource, reads: &mut StatementReads) {
+    let joins = match source {
+        RowDataSource::SubSelect { select, joins, .. } => {
+            select_reads(select, reads);
+            joins
+        }
+        RowDataSource::Table {
+            table_name, joins, ..
+        } => {
+            reads.tables.push(table_name.clone());
+            joins
+        }
+        RowDataSource::Function { call, joins, .. } => {
+            reads.functions.push(call.name.clone());
+            for arg in &call.args {
+                expression_reads(arg, reads);
+            }
+            joins
+        }
+        RowDataSource::Values { rows, joins, .. } => {
+            for exp in rows.iter().flatten() {
+                expression_reads(exp, reads);
+            }
+            joins
+        }
+    };
+    for join in joins {
+        source_reads(&join.source, reads);
+        expression_reads(&join.on, reads);
+    }
+}
+
+fn expression_reads(expression: &Expression, reads: &mut StatementReads) {
+    match expression {
+        Expression::Binary(binary) => {
+            expression_reads(&binary.first, reads);
+            expression_reads(&binary.second, reads);
+        }
+        Expression::Unary(unary) => expression_reads(&unary.first, reads),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            expression_reads(data, reads)
+        }
+        Expression::SubSelect(select) | Expression::Exists(select) => select_reads(select, reads),
+        Expression::Quantified(quantified) => {
+            expression_reads(&quantified.first, reads);
+            if let QuantifiedSet::SubSelect(select) = &quantified.set {
+                select_reads(select, reads);
+            }
+        }
+        Expression::FunctionCall(call) => {
+            reads.functions.push(call.name.clone());
+            for arg in &call.args {
+                expression_reads(arg, reads);
+            }
+            if let Some(filter) = &call.filter {
+                expression_reads(filter, reads);
+            }
+            if let Some(over) = &call.over {
+                for exp in &over.partition_by {
+                    expression_reads(exp, reads);
+                }
+                for order_by in &over.order_by {
+                    expression_reads(&order_by.exp, reads);
+                }
+            }
+        }
+        Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows, try_rows};
+
+    const SETUP: &str = "CREATE TABLE people (id INT, name TEXT);
+        INSERT INTO people VALUES (1, 'Baam');
+        INSERT INTO people VALUES (2, 'Rachel');
+        INSERT INTO people VALUES (3, 'Khun');";
+
+    #[test]
+    fn test_cache_substitutes_literals() {
+        let mut mb = backend(SETUP);
+        let before = mb.statement_cache_stats();
+
+        assert_eq!(
+            rows(&mut mb, "SELECT name FROM people WHERE id = 1;"),
+            vec!["Baam"]
+        );
+        assert_eq!(
+            rows(&mut mb, "select NAME from people where id = 3;"),
+            vec!["Khun"]
+        );
+        let stats = mb.statement_cache_stats();
+        assert_eq!(stats.misses - before.misses, 1);
+        assert_eq!(stats.hits - before.hits, 1);
+
+        // A literal of another type gets its own entry
+        assert!(try_rows(&mut mb, "SELECT name FROM people WHERE id = 'Baam';").is_err());
+        assert_eq!(mb.statement_cache_stats().misses - before.misses, 2);
+
+        // LIMIT counts are not literal expressions, other counts are parsed again
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM people WHERE id > 1 LIMIT 1;"),
+            vec!["2"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM people WHERE id > 1 LIMIT 1;"),
+            vec!["2"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM people WHERE id > 0 LIMIT 2;"),
+            vec!["1", "2"]
+        );
+        let stats = mb.statement_cache_stats();
+        assert_eq!(stats.hits - before.hits, 2);
+        assert_eq!(stats.misses - before.misses, 4);
+    }
+
+    #[test]
+    fn test_cache_invalidation() {
+        let mut mb = backend(SETUP);
+        let select = "SELECT id FROM people WHERE name = 'Rachel';";
+        assert_eq!(rows(&mut mb, select), vec!["2"]);
+        let before = mb.statement_cache_stats();
+
+        mb.eval_query("CREATE TABLE pets (owner INT);").unwrap();
+        assert_eq!(
+            mb.statement_cache_stats().invalidations,
+            before.invalidations
+        );
+
+        mb.eval_query("DROP TABLE people;").unwrap();
+        assert!(mb.statement_cache_stats().invalidations > before.invalidations);
+        mb.eval_query(
+            "CREATE TABLE people (id INT, nickname TEXT);
+            INSERT INTO people VALUES (2, 'Rachel');",
+        )
+        .unwrap();
+        assert_eq!(
+            try_rows(&mut mb, select),
+            Err(BackendError::UndefinedColumn(
+                "name: Column does not exist.".to_string()
+            ))
+        );
+
+        // Schema changes undone by a rollback are caught when an entry gets reused
+        let select = "SELECT * FROM scratch;";
+        assert!(try_rows(&mut mb, select).is_err());
+        let _ = mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query("CREATE TABLE scratch (id INT);")?;
+            assert!(rows(mb, select).is_empty());
+            Err(BackendError::Internal("rollback".to_string()))
+        });
+        let before = mb.statement_cache_stats();
+        assert!(try_rows(&mut mb, select).is_err());
+        let stats = mb.statement_cache_stats();
+        assert_eq!(stats.hits, before.hits);
+        assert_eq!(stats.invalidations, before.invalidations + 1);
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let mut mb = backend(SETUP);
+        mb.set_statement_cache_capacity(2);
+        let queries = vec![
+            "SELECT id FROM people;",
+            "SELECT name FROM people;",
+            "SELECT id FROM people;",
+            "SELECT * FROM people;",
+            "SELECT id FROM people;",
+            "SELECT name FROM people;",
+        ];
+        for query_text in queries {
+            rows(&mut mb, query_text);
+        }
+        let stats = mb.statement_cache_stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.hits, 2);
+
+        mb.set_statement_cache_capacity(0);
+        rows(&mut mb, "SELECT id FROM people;");
+        let stats = mb.statement_cache_stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.hits, 2);
+    }
+}
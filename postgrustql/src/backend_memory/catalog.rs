@@ -0,0 +1,684 @@
+use super::cache::{statement_reads, StatementReads};
+use super::resolver::{local_name, Resolution};
+use super::storage::StorageEngine;
+use super::stored_row::StoredRow;
+use super::table_size::{RelationSizesGuard, TableSize};
+use super::values::{big_int, int, small_int, text};
+use super::variables::SERVER_VERSION;
+use super::{MemoryBackend, Table};
+use crate::ast::{Statement, TriggerEvent};
+use crate::backend::BackendError;
+use crate::lexer::{fnv1a, FNV_OFFSET_BASIS};
+use crate::sql_types::{SqlType, SqlValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// The schema tables are created in, and the one current_schema() gives
+pub const PUBLIC_SCHEMA: &str = "public";
+pub(super) const CATALOG_SCHEMA: &str = "pg_catalog";
+// Where the temporary tables of the session are, Postgres numbers one per backend
+pub(super) const TEMP_SCHEMA: &str = "pg_temp_1";
+
+// The OIDs Postgres gives its own schemas and the superuser owning everything
+const CATALOG_SCHEMA_OID: i32 = 11;
+const PUBLIC_SCHEMA_OID: i32 = 2200;
+const OWNER_OID: i32 = 10;
+const OWNER: &str = "postgres";
+// Postgres hands out OIDs from here on to the objects users create
+const FIRST_USER_OID: u32 = 16384;
+
+// The tables of pg_catalog answered from the tables of the backend, enough for tools to
+// list tables and their columns. They are in the schema themselves, so that listing them
+// lists the catalog as well. Those of information_schema aren't listed, nor is
+// postgrustql_tables, which Postgres doesn't have.
+const CATALOG_TABLES: &[(&str, &[(&str, SqlType)])] = &[
+    (
+        "information_schema.views",
+        &[
+            ("table_schema", SqlType::Text),
+            ("table_name", SqlType::Text),
+            ("view_definition", SqlType::Text),
+            ("check_option", SqlType::Text),
+            ("is_updatable", SqlType::Text),
+            ("is_insertable_into", SqlType::Text),
+        ],
+    ),
+    (
+        "pg_attribute",
+        &[
+            ("attrelid", SqlType::Int),
+            ("attname", SqlType::Text),
+            ("atttypid", SqlType::Int),
+            ("attnum", SqlType::SmallInt),
+            ("atttypmod", SqlType::Int),
+            ("attnotnull", SqlType::Boolean),
+            ("atthasdef", SqlType::Boolean),
+            ("attgenerated", SqlType::Text),
+            ("attisdropped", SqlType::Boolean),
+        ],
+    ),
+    (
+        "pg_class",
+        &[
+            ("oid", SqlType::Int),
+            ("relname", SqlType::Text),
+            ("relnamespace", SqlType::Int),
+            ("relowner", SqlType::Int),
+            ("relkind", SqlType::Text),
+            ("relpersistence", SqlType::Text),
+            ("relnatts", SqlType::SmallInt),
+            ("relhasindex", SqlType::Boolean),
+        ],
+    ),
+    (
+        "pg_namespace",
+        &[
+            ("oid", SqlType::Int),
+            ("nspname", SqlType::Text),
+            ("nspowner", SqlType::Int),
+        ],
+    ),
+    (
+        "pg_tables",
+        &[
+            ("schemaname", SqlType::Text),
+            ("tablename", SqlType::Text),
+            ("tableowner", SqlType::Text),
+            ("tablespace", SqlType::Text),
+            ("hasindexes", SqlType::Boolean),
+            ("hasrules", SqlType::Boolean),
+            ("hastriggers", SqlType::Boolean),
+            ("rowsecurity", SqlType::Boolean),
+        ],
+    ),
+    (
+        "pg_trigger",
+        &[
+            ("oid", SqlType::Int),
+            ("tgrelid", SqlType::Int),
+            ("tgname", SqlType::Text),
+            ("tgtype", SqlType::SmallInt),
+            ("tgenabled", SqlType::Text),
+        ],
+    ),
+    (
+        "postgrustql_tables",
+        &[
+            ("table_schema", SqlType::Text),
+            ("table_name", SqlType::Text),
+            ("row_count", SqlType::BigInt),
+            ("table_bytes", SqlType::BigInt),
+            ("index_bytes", SqlType::BigInt),
+            ("total_bytes", SqlType::BigInt),
+        ],
+    ),
+];
+
+// The bits of pg_trigger.tgtype, triggers are always AFTER ones
+const TRIGGER_TYPE_ROW: usize = 1;
+const TRIGGER_TYPE_INSERT: usize = 1 << 2;
+const TRIGGER_TYPE_DELETE: usize = 1 << 3;
+const TRIGGER_TYPE_UPDATE: usize = 1 << 4;
+
+thread_local! {
+    // Whether each relation of the catalog read by the running statement is the one its
+    // name finds, by OID
+    static VISIBLE_RELATIONS: RefCell<Option<HashMap<i64, bool>>> = const { RefCell::new(None) };
+}
+
+// Puts back the relations known to the statement that was running before when dropped
+#[cfg(feature = "parallel")]
+pub(super) struct VisibleRelationsGuard {
+    outer: Option<HashMap<i64, bool>>,
+}
+
+#[cfg(feature = "parallel")]
+impl Drop for VisibleRelationsGuard {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        VISIBLE_RELATIONS.with(|visible| *visible.borrow_mut() = outer);
+    }
+}
+
+// The catalog tables a statement read, taken out again by `leave_catalog`
+pub(super) struct CatalogGuard {
+    tables: Vec<&'static str>,
+    // The relations known before, if the statement made them known
+    outer: Option<Option<HashMap<i64, bool>>>,
+    _sizes: RelationSizesGuard,
+}
+
+// A relation as pg_class lists it, a table, an index or a sequence
+struct Relation<'a> {
+    oid: i32,
+    name: &'a str,
+    schema: &'static str,
+    kind: &'static str,
+    // The table of the backend it is, None for indexes and the tables of the catalog
+    table: Option<&'a Table>,
+    columns: Vec<(&'a str, SqlType)>,
+    visible: bool,
+    has_triggers: bool,
+}
+
+impl Relation<'_> {
+    fn has_index(&self) -> bool {
+        self.table.is_some_and(|table| !table.indexes.is_empty())
+    }
+
+    // Temporary or permanent
+    fn persistence(&self) -> &'static str {
+        match self.schema {
+            TEMP_SCHEMA => "t",
+            _ => "p",
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Fills in the catalog tables the statement reads from the tables there are, those
+    // their names find rather than a temporary table or one of the public schema, see
+    // `resolve`. The sizes of the relations and which of them are visible are known to
+    // the functions it calls.
+    pub(super) fn enter_catalog(
+        &mut self,
+        statement: &Statement,
+    ) -> Result<CatalogGuard, BackendError> {
+        let mut read = StatementReads::default();
+        statement_reads(statement, &mut read);
+        let sizes = self.enter_relation_sizes(&read.functions)?;
+        let tables: Vec<&'static str> = CATALOG_TABLES
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| {
+                read.tables.iter().any(|table| {
+                    local_name(table) == *name
+                        && self.resolve_in_schemas(table) == Some(Resolution::Catalog)
+                })
+            })
+            .collect();
+        let checks_visibility = read
+            .functions
+            .iter()
+            .any(|function| function == "pg_table_is_visible");
+        if tables.is_empty() && !checks_visibility {
+            return Ok(CatalogGuard {
+                tables,
+                outer: None,
+                _sizes: sizes,
+            });
+        }
+
+        let table_sizes = match tables.contains(&"postgrustql_tables") {
+            true => self.table_sizes()?,
+            false => vec![],
+        };
+        let relations = self.relations();
+        let visible = relations
+            .iter()
+            .map(|relation| (i64::from(relation.oid), relation.visible))
+            .collect();
+        let catalog: Vec<Table> = tables
+            .iter()
+            .map(|name| {
+                let rows = match *name {
+                    "pg_attribute" => attribute_rows(&relations),
+                    "pg_class" => class_rows(&relations),
+                    "pg_namespace" => namespace_rows(),
+                    "information_schema.views" => self.view_rows(),
+                    "pg_trigger" => self.trigger_rows(),
+                    "postgrustql_tables" => size_rows(&table_sizes),
+                    _ => table_rows(&relations),
+                };
+                catalog_table(name, rows)
+            })
+            .collect();
+        for table in catalog {
+            self.with_tables.create_table(table)?;
+        }
+        let outer = VISIBLE_RELATIONS.with(|relations| relations.replace(Some(visible)));
+        Ok(CatalogGuard {
+            tables,
+            outer: Some(outer),
+            _sizes: sizes,
+        })
+    }
+
+    pub(super) fn leave_catalog(&mut self, guard: CatalogGuard) -> Result<(), BackendError> {
+        for name in &guard.tables {
+            self.with_tables.drop_table(name)?;
+        }
+        if let Some(outer) = guard.outer {
+            VISIBLE_RELATIONS.with(|visible| *visible.borrow_mut() = outer);
+        }
+        Ok(())
+    }
+
+    // Every relation, those of the catalog first, then the temporary ones and then the rest.
+    // The tables of each schema go by name, each followed by its indexes, then come the
+    // views and the sequences. Tables and views are visible when their name finds them,
+    // indexes and sequences unless a relation before them has the name.
+    fn relations(&self) -> Vec<Relation<'_>> {
+        let mut relations = vec![];
+        for (name, columns) in CATALOG_TABLES {
+            if name.contains('.') || *name == "postgrustql_tables" {
+                continue;
+            }
+            relations.push(Relation {
+                oid: oid(CATALOG_SCHEMA, name),
+                name,
+                schema: CATALOG_SCHEMA,
+                kind: "r",
+                table: None,
+                columns: columns.to_vec(),
+                visible: self.resolve_in_schemas(name) == Some(Resolution::Catalog),
+                has_triggers: false,
+            });
+        }
+
+        for (schema, engine, resolution) in [
+            (
+                TEMP_SCHEMA,
+                &self.temp_tables as &dyn StorageEngine,
+                Resolution::Temporary,
+            ),
+            (PUBLIC_SCHEMA, self.storage.as_ref(), Resolution::Table),
+        ] {
+            let mut names = engine.table_names();
+            names.sort();
+            for name in names {
+                let table = match engine.table(&name) {
+                    Some(table) => table,
+                    None => continue,
+                };
+                let visible = self.resolve_in_schemas(&table.name) == Some(resolution);
+                relations.push(Relation {
+                    oid: oid(schema, &table.name),
+                    name: &table.name,
+                    schema,
+                    kind: "r",
+                    table: Some(table),
+                    columns: table
+                        .columns
+                        .iter()
+                        .map(String::as_str)
+                        .zip(table.column_types.iter().copied())
+                        .collect(),
+                    visible,
+                    has_triggers: schema == PUBLIC_SCHEMA && self.triggers.on(&table.name),
+                });
+                for index in &table.indexes {
+                    relations.push(Relation {
+                        oid: oid(schema, &index.name),
+                        name: &index.name,
+                        schema,
+                        kind: "i",
+                        table: None,
+                        columns: vec![],
+                        visible,
+                        has_triggers: false,
+                    });
+                }
+            }
+        }
+
+        for (name, _) in self.views.sorted() {
+            relations.push(Relation {
+                oid: oid(PUBLIC_SCHEMA, name),
+                name,
+                schema: PUBLIC_SCHEMA,
+                kind: "v",
+                table: None,
+                columns: vec![],
+                visible: self.resolve_in_schemas(name) == Some(Resolution::View),
+                has_triggers: false,
+            });
+        }
+
+        let mut sequences = self.sequences.names();
+        sequences.sort_unstable();
+        for name in sequences {
+            relations.push(Relation {
+                oid: oid(PUBLIC_SCHEMA, name),
+                name,
+                schema: PUBLIC_SCHEMA,
+                kind: "S",
+                table: None,
+                columns: vec![],
+                visible: !relations.iter().any(|relation| relation.name == name),
+                has_triggers: false,
+            });
+        }
+        relations
+    }
+
+    fn trigger_rows(&self) -> Vec<Vec<SqlValue>> {
+        self.triggers
+            .iter()
+            .map(|trigger| {
+                let mut typ = 0;
+                if trigger.for_each_row {
+                    typ |= TRIGGER_TYPE_ROW;
+                }
+                for event in &trigger.events {
+                    typ |= match event {
+                        TriggerEvent::Insert => TRIGGER_TYPE_INSERT,
+                        TriggerEvent::Delete => TRIGGER_TYPE_DELETE,
+                        TriggerEvent::Update => TRIGGER_TYPE_UPDATE,
+                    };
+                }
+                let name = format!("{}.{}", trigger.table, trigger.name);
+                vec![
+                    int(oid(PUBLIC_SCHEMA, &name)),
+                    int(oid(PUBLIC_SCHEMA, &trigger.table)),
+                    text(&trigger.name),
+                    small_int(typ),
+                    text("O"),
+                ]
+            })
+            .collect()
+    }
+
+    // Views are never updatable, WITH CHECK OPTION is not supported
+    fn view_rows(&self) -> Vec<Vec<SqlValue>> {
+        self.views
+            .sorted()
+            .into_iter()
+            .map(|(name, view)| {
+                vec![
+                    text(PUBLIC_SCHEMA),
+                    text(name),
+                    text(&view.definition),
+                    text("NONE"),
+                    text("NO"),
+                    text("NO"),
+                ]
+            })
+            .collect()
+    }
+}
+
+// Whether a statement reading a table of this name reads the catalog
+pub(super) fn is_catalog_table(name: &str) -> bool {
+    CATALOG_TABLES.iter().any(|(table, _)| *table == name)
+}
+
+// Whether the relation of an OID is the one its name finds, None if there is no such
+// relation or outside of statements
+pub(super) fn is_visible(oid: i64) -> Option<bool> {
+    VISIBLE_RELATIONS.with(|visible| visible.borrow().as_ref()?.get(&oid).copied())
+}
+
+// The relations known to the statement running on this thread, for the threads of a
+// parallel scan to evaluate its expressions with
+#[cfg(feature = "parallel")]
+pub(super) fn visible_relations() -> Option<HashMap<i64, bool>> {
+    VISIBLE_RELATIONS.with(|visible| visible.borrow().clone())
+}
+
+// Makes `visible` the relations known on this thread until the guard is dropped
+#[cfg(feature = "parallel")]
+pub(super) fn enter_visible_relations(
+    visible: Option<HashMap<i64, bool>>,
+) -> VisibleRelationsGuard {
+    let outer = VISIBLE_RELATIONS.with(|current| current.replace(visible));
+    VisibleRelationsGuard { outer }
+}
+
+// What version() gives, the version of Postgres the backend answers as and its own
+pub(super) fn server_version() -> String {
+    format!(
+        "PostgreSQL {} (postgrustql {})",
+        SERVER_VERSION,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+// The OID of a relation, from its name so that it stays the same for as long as the
+// relation is there, and across restarts
+fn oid(schema: &str, name: &str) -> i32 {
+    let hash = fnv1a(FNV_OFFSET_BASIS, format!("{}.{}", schema, name).as_bytes());
+    let span = i32::MAX as u32 - FIRST_USER_OID;
+    (FIRST_USER_OID + (hash % u64::from(span)) as u32) as i32
+}
+
+fn schema_oid(schema: &str) -> i32 {
+    match schema {
+        CATALOG_SCHEMA => CATALOG_SCHEMA_OID,
+        PUBLIC_SCHEMA => PUBLIC_SCHEMA_OID,
+        _ => oid("", schema),
+    }
+}
+
+fn catalog_table(name: &str, rows: Vec<Vec<SqlValue>>) -> Table {
+    let columns = CATALOG_TABLES
+        .iter()
+        .find(|(table, _)| *table == name)
+        .map_or(&[][..], |(_, columns)| columns);
+    Table {
+        name: name.to_string(),
+        columns: columns.iter().map(|(name, _)| name.to_string()).collect(),
+        column_types: columns.iter().map(|(_, typ)| *typ).collect(),
+        rows: rows.into_iter().map(StoredRow::from).collect(),
+        indexes: vec![],
+        column_constraints: vec![],
+        checks: vec![],
+        schema_version: 0,
+        heap_bytes: 0,
+        dead_rows: 0,
+    }
+}
+
+fn namespace_rows() -> Vec<Vec<SqlValue>> {
+    [CATALOG_SCHEMA, PUBLIC_SCHEMA, TEMP_SCHEMA]
+        .iter()
+        .map(|schema| vec![int(schema_oid(schema)), text(schema), int(OWNER_OID)])
+        .collect()
+}
+
+fn class_rows(relations: &[Relation]) -> Vec<Vec<SqlValue>> {
+    relations
+        .iter()
+        .map(|relation| {
+            vec![
+                int(relation.oid),
+                text(relation.name),
+                int(schema_oid(relation.schema)),
+                int(OWNER_OID),
+                text(relation.kind),
+                text(relation.persistence()),
+                small_int(relation.columns.len()),
+                SqlValue::Boolean(relation.has_index()),
+            ]
+        })
+        .collect()
+}
+
+// The columns of the tables, numbered from 1. Only tables of the backend have constraints.
+fn attribute_rows(relations: &[Relation]) -> Vec<Vec<SqlValue>> {
+    let mut rows = vec![];
+    for relation in relations {
+        for (idx, (name, typ)) in relation.columns.iter().enumerate() {
+            let constraints = relation
+                .table
+                .and_then(|table| table.column_constraints.get(idx));
+            let primary_key = relation.table.is_some_and(|table| {
+                table
+                    .indexes
+                    .iter()
+                    .any(|index| index.primary_key && index.columns.iter().any(|c| c == name))
+            });
+            let not_null = primary_key || constraints.is_some_and(|c| c.not_null);
+            // Postgres counts the 4 bytes of the length in
+            let type_modifier = match constraints.and_then(|c| c.type_modifier) {
+                Some(length) => length as i32 + 4,
+                None => -1,
+            };
+            let generated = constraints.is_some_and(|c| c.generated.is_some());
+            rows.push(vec![
+                int(relation.oid),
+                text(name),
+                int(typ.oid() as i32),
+                small_int(idx + 1),
+                int(type_modifier),
+                SqlValue::Boolean(not_null),
+                SqlValue::Boolean(constraints.is_some_and(|c| c.default.is_some())),
+                text(if generated { "s" } else { "" }),
+                SqlValue::Boolean(false),
+            ]);
+        }
+    }
+    rows
+}
+
+fn table_rows(relations: &[Relation]) -> Vec<Vec<SqlValue>> {
+    relations
+        .iter()
+        .filter(|relation| relation.kind == "r")
+        .map(|relation| {
+            vec![
+                text(relation.schema),
+                text(relation.name),
+                text(OWNER),
+                SqlValue::Null,
+                SqlValue::Boolean(relation.has_index()),
+                SqlValue::Boolean(false),
+                SqlValue::Boolean(relation.has_triggers),
+                SqlValue::Boolean(false),
+            ]
+        })
+        .collect()
+}
+
+fn size_rows(sizes: &[(&str, String, TableSize)]) -> Vec<Vec<SqlValue>> {
+    sizes
+        .iter()
+        .map(|(schema, name, size)| {
+            let index_bytes = size.total_index_bytes();
+            vec![
+                text(schema),
+                text(name),
+                big_int(size.rows),
+                big_int(size.heap_bytes),
+                big_int(index_bytes),
+                big_int(size.heap_bytes + index_bytes),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod catalog_tests {
+    use crate::backend_memory::test_support::{backend, rows};
+    use crate::backend_memory::MemoryBackend;
+
+    const SETUP: &str =
+        "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20) NOT NULL, age INT);
+        CREATE TABLE posts (id INT, body TEXT DEFAULT '');
+        CREATE INDEX users_age ON users (age);";
+
+    #[test]
+    fn test_catalog_tables() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT schemaname, tablename, hasindexes FROM pg_catalog.pg_tables \
+                WHERE schemaname = 'public';"
+            ),
+            vec!["public|posts|false", "public|users|true"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT n.nspname, c.relname, c.relkind FROM pg_class c \
+                JOIN pg_namespace n ON n.oid = c.relnamespace \
+                WHERE n.nspname <> 'pg_catalog' AND pg_table_is_visible(c.oid);"
+            ),
+            vec![
+                "public|posts|r",
+                "public|users|r",
+                "public|users_pkey|i",
+                "public|users_age|i"
+            ]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT a.attnum, a.attname, a.atttypid, a.atttypmod, a.attnotnull, a.atthasdef \
+                FROM pg_attribute a JOIN pg_class c ON c.oid = a.attrelid \
+                WHERE c.relname = 'users' OR c.relname = 'posts';"
+            ),
+            vec![
+                "1|id|23|-1|false|false",
+                "2|body|25|-1|false|true",
+                "1|id|23|-1|true|false",
+                "2|name|1043|24|true|false",
+                "3|age|23|-1|false|false"
+            ]
+        );
+
+        // The OIDs stay the same as long as the table is there
+        let oids = rows(&mut mb, "SELECT oid FROM pg_class WHERE relname = 'users';");
+        mb.eval_query("CREATE TABLE later (id INT);").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT oid FROM pg_class WHERE relname = 'users';"),
+            oids
+        );
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut mb = backend(SETUP);
+        // A temporary table hides the table of the same name
+        mb.eval_query("CREATE TEMPORARY TABLE users (id INT);")
+            .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT n.nspname, c.relpersistence, pg_table_is_visible(c.oid) \
+                FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+                WHERE c.relname = 'users' ORDER BY nspname;"
+            ),
+            vec!["pg_temp_1|t|true", "public|p|false"]
+        );
+        // OIDs are known without reading the catalog, those of no relation give NULL
+        let oids = rows(
+            &mut mb,
+            "SELECT c.oid FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+            WHERE c.relname = 'users' ORDER BY nspname;",
+        );
+        for (oid, visible) in oids.iter().zip(["true", "false"]) {
+            let query = format!("SELECT pg_table_is_visible({});", oid);
+            assert_eq!(rows(&mut mb, &query), vec![visible]);
+        }
+        assert_eq!(
+            rows(&mut mb, "SELECT pg_table_is_visible(1);"),
+            vec!["NULL"]
+        );
+        // The catalog comes first, and the tables read in its place are gone after
+        mb.eval_query("CREATE TABLE pg_namespace (id INT);")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT count(*) FROM pg_namespace;"),
+            vec!["3"]
+        );
+        assert_eq!(
+            mb.eval_query("SELECT * FROM other.users;")
+                .unwrap_err()
+                .code(),
+            "42P01"
+        );
+    }
+
+    #[test]
+    fn test_catalog_functions() {
+        let mut mb = MemoryBackend::new();
+        assert_eq!(
+            rows(&mut mb, "SELECT current_schema(), pg_catalog.upper('a');"),
+            vec!["public|A"]
+        );
+        let version = rows(&mut mb, "SELECT version();");
+        assert!(version[0].starts_with("PostgreSQL 13.0 "), "{}", version[0]);
+    }
+}
@@ -0,0 +1,281 @@
+use super::MemoryBackend;
+use crate::sql_types::SqlValue;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChangeOperation {
+    // A row went into the table, by INSERT or CREATE TABLE AS
+    Insert,
+    // A row had some of its values changed by UPDATE
+    Update,
+    // A row was taken out of the table by DELETE
+    Delete,
+    // The table and its rows are gone, a single event for the whole table
+    DropTable,
+    // A column was dropped from every row, a single event for the whole table
+    DropColumn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub operation: ChangeOperation,
+    pub table: String,
+    // The row before and after the change, both None for changes to the whole table
+    pub old_row: Option<Vec<SqlValue>>,
+    pub new_row: Option<Vec<SqlValue>>,
+}
+
+pub type ChangeHook = dyn Fn(ChangeEvent) + Send + Sync;
+
+// Holds back the changes of a statement or transaction until it succeeds, the changes of
+// one that fails are never seen
+#[derive(Default)]
+pub(super) struct ChangeNotifier {
+    hook: Option<Box<ChangeHook>>,
+    pending: Vec<ChangeEvent>,
+    // Statements and transactions running, events go out when the outermost one ends
+    depth: usize,
+}
+
+// Hooks are not part of the state of a backend
+impl PartialEq for ChangeNotifier {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl ChangeNotifier {
+    // Whether there is anyone to tell about changes, rows are only copied for events then
+    pub(super) fn hooked(&self) -> bool {
+        self.hook.is_some()
+    }
+
+    pub(super) fn record(&mut self, operation: ChangeOperation, table: &str) {
+        self.push(ChangeEvent {
+            operation,
+            table: table.to_string(),
+            old_row: None,
+            new_row: None,
+        });
+    }
+
+    pub(super) fn record_inserts(&mut self, table: &str, rows: Vec<Vec<SqlValue>>) {
+        for row in rows {
+            self.push(ChangeEvent {
+                operation: ChangeOperation::Insert,
+                table: table.to_string(),
+                old_row: None,
+                new_row: Some(row),
+            });
+        }
+    }
+
+    pub(super) fn record_updates(
+        &mut self,
+        table: &str,
+        rows: Vec<(Vec<SqlValue>, Vec<SqlValue>)>,
+    ) {
+        for (old_row, new_row) in rows {
+            self.push(ChangeEvent {
+                operation: ChangeOperation::Update,
+                table: table.to_string(),
+                old_row: Some(old_row),
+                new_row: Some(new_row),
+            });
+        }
+    }
+
+    pub(super) fn record_deletes(&mut self, table: &str, rows: Vec<Vec<SqlValue>>) {
+        for row in rows {
+            self.push(ChangeEvent {
+                operation: ChangeOperation::Delete,
+                table: table.to_string(),
+                old_row: Some(row),
+                new_row: None,
+            });
+        }
+    }
+
+    fn push(&mut self, event: ChangeEvent) {
+        if self.hook.is_none() {
+            return;
+        }
+        self.pending.push(event);
+        if self.depth == 0 {
+            self.flush();
+        }
+    }
+
+    // Starts a statement or transaction, giving what `end` needs to undo its events
+    pub(super) fn begin(&mut self) -> usize {
+        self.depth += 1;
+        self.pending.len()
+    }
+
+    pub(super) fn end(&mut self, start: usize, succeeded: bool) {
+        self.depth -= 1;
+        if !succeeded {
+            self.pending.truncate(start);
+        }
+        if self.depth == 0 {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        if let Some(hook) = &self.hook {
+            for event in pending {
+                hook(event);
+            }
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Calls `hook` with every row inserted, updated or deleted and every table dropped or
+    // altered, once the statement, or the transaction it runs in, succeeded. A deleted row
+    // comes as the old row of a Delete event, with no new row.
+    pub fn set_change_hook(&mut self, hook: impl Fn(ChangeEvent) + Send + Sync + 'static) {
+        self.changes.hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_change_hook(&mut self) {
+        self.changes.hook = None;
+        self.changes.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod changes_tests {
+    use super::*;
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support;
+    use crate::sql_types::{SqlNumeric, SqlText};
+    use std::sync::{Arc, Mutex};
+
+    fn backend() -> (MemoryBackend, Arc<Mutex<Vec<ChangeEvent>>>) {
+        let mut mb = test_support::backend(
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT);
+            INSERT INTO t VALUES (1, 'a');",
+        );
+        let events = Arc::new(Mutex::new(vec![]));
+        let seen = events.clone();
+        mb.set_change_hook(move |event| seen.lock().unwrap().push(event));
+        (mb, events)
+    }
+
+    // `operation table old -> new`, for the events seen so far
+    fn seen(events: &Mutex<Vec<ChangeEvent>>) -> Vec<String> {
+        let row = |row: &Option<Vec<SqlValue>>| match row {
+            Some(row) => row
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+            None => "-".to_string(),
+        };
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| {
+                format!(
+                    "{:?} {} {} -> {}",
+                    event.operation,
+                    event.table,
+                    row(&event.old_row),
+                    row(&event.new_row)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_change_events() {
+        let tests = vec![
+            (
+                "INSERT INTO t VALUES (2, 'b'), (3, 'c');",
+                vec!["Insert t - -> 2,b", "Insert t - -> 3,c"],
+            ),
+            (
+                "UPDATE t SET name = 'z' WHERE id = 1;",
+                vec!["Update t 1,a -> 1,z"],
+            ),
+            (
+                "CREATE TABLE u AS SELECT name FROM t;",
+                vec!["Insert u - -> a"],
+            ),
+            (
+                "ALTER TABLE t DROP COLUMN name;",
+                vec!["DropColumn t - -> -"],
+            ),
+            ("DROP TABLE t;", vec!["DropTable t - -> -"]),
+            // A failed statement leaves no events, even for the rows it got through
+            ("INSERT INTO t VALUES (2, 'b'), (1, 'c');", vec![]),
+            ("UPDATE t SET name = 'z', id = NULL;", vec![]),
+            ("SELECT name FROM t;", vec![]),
+        ];
+        for (query, expected) in tests {
+            let (mut mb, events) = backend();
+            let _ = mb.eval_query(query);
+            assert_eq!(seen(&events), expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_execute_many_events() {
+        let (mut mb, events) = backend();
+        let int = |value| SqlValue::Numeric(SqlNumeric::Int { value });
+        let text = |value: &str| {
+            SqlValue::Text(SqlText::Text {
+                value: value.to_string(),
+            })
+        };
+        let insert = mb.prepare("INSERT INTO t VALUES ($1, $2);").unwrap();
+        insert
+            .execute_many(
+                &mut mb,
+                vec![vec![int(2), text("b")], vec![int(3), text("c")]],
+            )
+            .unwrap();
+        assert_eq!(seen(&events), ["Insert t - -> 2,b", "Insert t - -> 3,c"]);
+
+        // A batch that fails leaves no events, even for the rows it got through
+        let batch = vec![vec![int(4), text("d")], vec![int(1), text("e")]];
+        assert!(insert.execute_many(&mut mb, batch).is_err());
+        assert_eq!(seen(&events).len(), 2);
+    }
+
+    #[test]
+    fn test_rolled_back_transaction() {
+        let (mut mb, events) = backend();
+        let result = mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query("INSERT INTO t VALUES (2, 'b');")?;
+            // Nothing is told before the transaction ends
+            assert!(events.lock().unwrap().is_empty());
+            mb.eval_query("INSERT INTO t VALUES (1, 'c');")?;
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert!(events.lock().unwrap().is_empty());
+
+        mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query("INSERT INTO t VALUES (2, 'b');")?;
+            // A nested transaction that fails takes back its own changes alone
+            let _ = mb.transaction(|mb| {
+                mb.eval_query("INSERT INTO t VALUES (3, 'c');")?;
+                mb.eval_query("INSERT INTO t VALUES (1, 'd');")
+            });
+            let _ = mb.transaction(|mb| mb.eval_query("INSERT INTO t VALUES (4, 'e');"));
+            assert!(events.lock().unwrap().is_empty());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(seen(&events), ["Insert t - -> 2,b", "Insert t - -> 4,e"]);
+
+        events.lock().unwrap().clear();
+        mb.clear_change_hook();
+        mb.eval_query("INSERT INTO t VALUES (5, 'f');").unwrap();
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
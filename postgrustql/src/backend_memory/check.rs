@@ -0,0 +1,239 @@
+use super::{is_aggregate, Collation, CompiledExpression, ScalarFunction, Table};
+use crate::ast::*;
+use crate::backend::{
+    BackendError, ERR_AGGREGATE_FUNCTION_IN_CHECK, ERR_AGGREGATE_FUNCTION_IN_GENERATED,
+    ERR_COLUMN_DOES_NOT_EXIST, ERR_OTHER_TABLE_IN_CHECK, ERR_OTHER_TABLE_IN_GENERATED,
+    ERR_SUBQUERY_IN_CHECK, ERR_SUBQUERY_IN_GENERATED, ERR_WINDOW_FUNCTION_IN_CHECK,
+    ERR_WINDOW_FUNCTION_IN_GENERATED,
+};
+use crate::lexer::Token;
+use crate::sql_types::SqlValue;
+
+// A CHECK constraint of a table. Rows it gives FALSE for are kept out, NULL lets them in
+// just like TRUE does.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Check {
+    name: String,
+    expression: Expression,
+}
+
+impl Table {
+    // Adds the CHECK constraints of a table being created. Those without a name get one
+    // the way Postgres makes them up: the table, the column if only one is used, "check"
+    // and a number if that is taken already.
+    pub(super) fn add_checks(&mut self, checks: Vec<CheckConstraint>) -> Result<(), BackendError> {
+        for check in checks {
+            let mut used = vec![];
+            let columns = RowExpression::Check.columns(&self.name, &self.columns);
+            columns.collect(&check.expression, &mut used)?;
+
+            let name = match check.name {
+                Some(name) if self.checks.iter().any(|check| check.name == name) => {
+                    return Err(BackendError::DuplicateObject(format!(
+                        "Constraint \"{}\" for relation \"{}\" already exists.",
+                        name, self.name
+                    )));
+                }
+                Some(name) => name,
+                None => {
+                    let base = match used.as_slice() {
+                        [column] => format!("{}_{}_check", self.name, self.columns[*column]),
+                        _ => format!("{}_check", self.name),
+                    };
+                    let mut name = base.clone();
+                    let mut n = 0;
+                    while self.checks.iter().any(|check| check.name == name) {
+                        n += 1;
+                        name = format!("{}{}", base, n);
+                    }
+                    name
+                }
+            };
+
+            self.checks.push(Check {
+                name,
+                expression: check.expression,
+            });
+        }
+        Ok(())
+    }
+
+    // Names of the CHECK constraints reading the column at `column`
+    pub(super) fn checks_using(&self, column: usize) -> Vec<String> {
+        self.checks
+            .iter()
+            .filter(|check| {
+                let mut used = vec![];
+                let columns = RowExpression::Check.columns(&self.name, &self.columns);
+                let _ = columns.collect(&check.expression, &mut used);
+                used.contains(&column)
+            })
+            .map(|check| check.name.clone())
+            .collect()
+    }
+
+    pub(super) fn drop_checks(&mut self, names: &[String]) {
+        self.checks.retain(|check| !names.contains(&check.name));
+    }
+
+    // The CHECK constraints resolved against the columns, to be given to `check_row`
+    pub(super) fn compile_checks(&self) -> Vec<CompiledExpression> {
+        self.checks
+            .iter()
+            .map(|check| self.compile_expression(&check.expression))
+            .collect()
+    }
+
+    pub(super) fn check_row(
+        &self,
+        checks: &[CompiledExpression],
+        row: &[SqlValue],
+    ) -> Result<(), BackendError> {
+        for (check, compiled) in self.checks.iter().zip(checks) {
+            match *compiled.evaluate(row)? {
+                SqlValue::Boolean(true) | SqlValue::Null => {}
+                SqlValue::Boolean(false) => {
+                    return Err(BackendError::CheckViolation(format!(
+                        "New row for relation \"{}\" violates check constraint \"{}\".",
+                        self.name, check.name
+                    )))
+                }
+                ref value => {
+                    return Err(BackendError::DatatypeMismatch(format!(
+                        "Check constraint \"{}\" must give a boolean, not {}.",
+                        check.name,
+                        value.get_type()
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Expressions of a table definition that may only read the row they are about
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum RowExpression {
+    Check,
+    Generated,
+}
+
+impl RowExpression {
+    pub(super) fn columns<'a>(self, table: &'a str, columns: &'a [String]) -> RowColumns<'a> {
+        RowColumns {
+            kind: self,
+            table,
+            columns,
+        }
+    }
+
+    fn subquery_error(self) -> BackendError {
+        BackendError::FeatureNotSupported(
+            match self {
+                RowExpression::Check => ERR_SUBQUERY_IN_CHECK,
+                RowExpression::Generated => ERR_SUBQUERY_IN_GENERATED,
+            }
+            .to_string(),
+        )
+    }
+}
+
+// The columns of a table, for collecting those a CHECK or generation expression reads
+pub(super) struct RowColumns<'a> {
+    kind: RowExpression,
+    table: &'a str,
+    columns: &'a [String],
+}
+
+impl RowColumns<'_> {
+    // Makes sure the expression only reads the row it is about, collecting the columns
+    // it uses into `used`. Generation expressions can only call immutable functions too.
+    pub(super) fn collect(
+        &self,
+        expression: &Expression,
+        used: &mut Vec<usize>,
+    ) -> Result<(), BackendError> {
+        match expression {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => use_column(value, self.columns, used),
+            Expression::TableColumn(table_column) => match &table_column.table_name {
+                Some(table_name) if table_name != self.table => {
+                    Err(BackendError::UndefinedTable(format!(
+                        "{}: {}",
+                        table_name,
+                        match self.kind {
+                            RowExpression::Check => ERR_OTHER_TABLE_IN_CHECK,
+                            RowExpression::Generated => ERR_OTHER_TABLE_IN_GENERATED,
+                        }
+                    )))
+                }
+                _ => use_column(&table_column.col_name, self.columns, used),
+            },
+            Expression::Literal(_) | Expression::ProcessedTableColumn(_) | Expression::Empty => {
+                Ok(())
+            }
+            Expression::SubSelect(_) | Expression::Exists(_) => Err(self.kind.subquery_error()),
+            Expression::Quantified(quantified) => match quantified.set {
+                QuantifiedSet::SubSelect(_) => Err(self.kind.subquery_error()),
+                QuantifiedSet::Values(_) => self.collect(&quantified.first, used),
+            },
+            Expression::Binary(binary) => {
+                self.collect(&binary.first, used)?;
+                self.collect(&binary.second, used)
+            }
+            Expression::Unary(unary) => self.collect(&unary.first, used),
+            Expression::Cast { data, .. } => self.collect(data, used),
+            Expression::Collate { data, collation } => {
+                Collation::from_name(collation)?;
+                self.collect(data, used)
+            }
+            Expression::FunctionCall(call) => {
+                if call.over.is_some() {
+                    return Err(BackendError::WindowingError(
+                        match self.kind {
+                            RowExpression::Check => ERR_WINDOW_FUNCTION_IN_CHECK,
+                            RowExpression::Generated => ERR_WINDOW_FUNCTION_IN_GENERATED,
+                        }
+                        .to_string(),
+                    ));
+                }
+                if is_aggregate(call) {
+                    return Err(BackendError::GroupingError(
+                        match self.kind {
+                            RowExpression::Check => ERR_AGGREGATE_FUNCTION_IN_CHECK,
+                            RowExpression::Generated => ERR_AGGREGATE_FUNCTION_IN_GENERATED,
+                        }
+                        .to_string(),
+                    ));
+                }
+                let function = ScalarFunction::from_call(call)?;
+                if self.kind == RowExpression::Generated && !function.is_immutable() {
+                    return Err(BackendError::InvalidTableDefinition(format!(
+                        "{}: Generation expression is not immutable.",
+                        call.name
+                    )));
+                }
+                for arg in &call.args {
+                    self.collect(arg, used)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn use_column(name: &str, columns: &[String], used: &mut Vec<usize>) -> Result<(), BackendError> {
+    match columns.iter().position(|column| column == name) {
+        Some(idx) => {
+            if !used.contains(&idx) {
+                used.push(idx);
+            }
+            Ok(())
+        }
+        None => Err(BackendError::UndefinedColumn(format!(
+            "{}: {}",
+            name, ERR_COLUMN_DOES_NOT_EXIST
+        ))),
+    }
+}
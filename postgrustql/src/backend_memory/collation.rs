@@ -0,0 +1,601 @@
+use std::borrow::Cow;
+
+use super::{apply_binary_operator, apply_quantified_operator, CompiledExpression, Index, Table};
+use crate::ast::*;
+use crate::backend::BackendError;
+use crate::lexer::Token;
+use crate::sql_types::{SqlText, SqlType, SqlValue};
+
+// The rules text is compared, sorted and grouped by. A column gets one with COLLATE in
+// CREATE TABLE and an expression with `expression COLLATE name`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Collation {
+    // Byte order, what text goes by unless told otherwise
+    Binary,
+    // Byte order of the lowercased text, 'a' and 'A' are the same
+    NoCase,
+    // Alphabetical order, with accents and then case only breaking ties
+    Und,
+}
+
+impl Collation {
+    pub fn from_name(name: &str) -> Result<Collation, BackendError> {
+        match name {
+            "binary" => Ok(Collation::Binary),
+            "nocase" => Ok(Collation::NoCase),
+            "und" => Ok(Collation::Und),
+            _ => Err(BackendError::UndefinedObject(format!(
+                "Collation \"{}\" does not exist.",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Collation::Binary => "binary",
+            Collation::NoCase => "nocase",
+            Collation::Und => "und",
+        }
+    }
+
+    // What a value is compared by in its place. Values that aren't text are their own key.
+    pub fn key(self, value: &SqlValue) -> Cow<'_, SqlValue> {
+        let text = match value {
+            SqlValue::Text(SqlText::Text { value })
+            | SqlValue::Text(SqlText::VarChar { value, .. })
+            | SqlValue::Text(SqlText::Char { value, .. }) => value,
+            _ => return Cow::Borrowed(value),
+        };
+        let key = match self {
+            Collation::Binary => return Cow::Borrowed(value),
+            Collation::NoCase => text.to_lowercase(),
+            Collation::Und => und_key(text),
+        };
+        Cow::Owned(SqlValue::Text(SqlText::Text { value: key }))
+    }
+}
+
+// Letters without their accents and case first, then with accents, then with case, lower
+// before upper. The last level tells apart any two different texts, so only equal texts
+// are equal.
+fn und_key(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut key = String::with_capacity(text.len() * 3 + 2);
+    for c in lower.chars() {
+        match base_letters(c) {
+            Some(base) => key.push_str(base),
+            None => key.push(c),
+        }
+    }
+    key.push('\u{1}');
+    key.push_str(&lower);
+    key.push('\u{1}');
+    for c in text.chars() {
+        if c.is_lowercase() {
+            key.extend(c.to_uppercase());
+        } else {
+            key.extend(c.to_lowercase());
+        }
+    }
+    key
+}
+
+// The letters an accented lowercase Latin letter sorts as
+fn base_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+// The values of a row as they compare under the collation of each, for grouping them.
+// Without collations the row is its own key.
+pub(super) fn collation_keys<'a>(
+    collations: &[Option<Collation>],
+    row: &'a [SqlValue],
+) -> Cow<'a, [SqlValue]> {
+    if collations.iter().all(|collation| collation.is_none()) {
+        return Cow::Borrowed(row);
+    }
+    Cow::Owned(
+        row.iter()
+            .enumerate()
+            .map(|(idx, value)| match collations.get(idx) {
+                Some(Some(collation)) => collation.key(value).into_owned(),
+                _ => value.clone(),
+            })
+            .collect(),
+    )
+}
+
+fn is_comparison(operand: &Token) -> bool {
+    matches!(
+        operand,
+        Token::Equal
+            | Token::NotEqual
+            | Token::LessThan
+            | Token::LessThanOrEqual
+            | Token::GreaterThan
+            | Token::GreaterThanOrEqual
+    )
+}
+
+// Like `apply_binary_operator`, but comparisons compare the keys of the operands under
+// `collation`
+pub(super) fn apply_collated_operator(
+    operand: &Token,
+    collation: Option<Collation>,
+    first_val: &SqlValue,
+    second_val: &SqlValue,
+) -> Result<SqlValue, BackendError> {
+    match collation {
+        Some(collation) if is_comparison(operand) => apply_binary_operator(
+            operand,
+            &collation.key(first_val),
+            &collation.key(second_val),
+        ),
+        _ => apply_binary_operator(operand, first_val, second_val),
+    }
+}
+
+// Like `apply_quantified_operator`, with the values compared under `collation`
+pub(super) fn apply_collated_quantified_operator(
+    operand: &Token,
+    all: bool,
+    collation: Option<Collation>,
+    first_val: &SqlValue,
+    values: &[SqlValue],
+) -> Result<SqlValue, BackendError> {
+    match collation {
+        Some(collation) if collation != Collation::Binary => {
+            let values: Vec<SqlValue> = values
+                .iter()
+                .map(|value| collation.key(value).into_owned())
+                .collect();
+            apply_quantified_operator(operand, all, &collation.key(first_val), &values)
+        }
+        _ => apply_quantified_operator(operand, all, first_val, values),
+    }
+}
+
+// The collation a comparison of the two operands goes by, that of whichever is given one
+// with COLLATE. Working out the collations of a query leaves the first operand with the
+// collation of any comparison that doesn't go by byte order.
+pub(super) fn operand_collation(first: &Expression, second: &Expression) -> Option<Collation> {
+    given_collation(first).or_else(|| given_collation(second))
+}
+
+// The collation given to the expression with COLLATE, if it is given one
+pub(super) fn given_collation(expression: &Expression) -> Option<Collation> {
+    match expression {
+        Expression::Collate { collation, .. } => Collation::from_name(collation).ok(),
+        _ => None,
+    }
+}
+
+impl CompiledExpression {
+    pub(super) fn collation(&self) -> Option<Collation> {
+        match self {
+            CompiledExpression::Collate { collation, .. } => Some(*collation),
+            _ => None,
+        }
+    }
+}
+
+// Where the collation of an expression comes from. When the operands of a comparison
+// disagree, one given with COLLATE wins over one from a column, while two of the same
+// kind are a mismatch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Derivation {
+    // Nothing in the expression asks for a collation, it goes by byte order
+    Default,
+    // Read from a column declared with the collation
+    Implicit(Collation),
+    // Given with COLLATE
+    Explicit(Collation),
+    // Read from columns of different collations, fine until something compares by it
+    Indeterminate,
+}
+
+impl Derivation {
+    fn combine(self, other: Derivation) -> Result<Derivation, BackendError> {
+        Ok(match (self, other) {
+            (Derivation::Explicit(a), Derivation::Explicit(b)) if a != b => {
+                return Err(BackendError::CollationMismatch(format!(
+                    "Collation mismatch between explicit collations \"{}\" and \"{}\".",
+                    a.name(),
+                    b.name()
+                )))
+            }
+            (Derivation::Explicit(a), _) | (_, Derivation::Explicit(a)) => Derivation::Explicit(a),
+            (Derivation::Indeterminate, _) | (_, Derivation::Indeterminate) => {
+                Derivation::Indeterminate
+            }
+            (Derivation::Implicit(a), Derivation::Implicit(b)) if a != b => {
+                Derivation::Indeterminate
+            }
+            (Derivation::Implicit(a), _) | (_, Derivation::Implicit(a)) => Derivation::Implicit(a),
+            (Derivation::Default, Derivation::Default) => Derivation::Default,
+        })
+    }
+
+    // The collation to compare, sort or group by
+    pub(super) fn collation(self) -> Result<Option<Collation>, BackendError> {
+        match self {
+            Derivation::Default => Ok(None),
+            Derivation::Implicit(collation) | Derivation::Explicit(collation) => {
+                Ok(Some(collation))
+            }
+            Derivation::Indeterminate => Err(BackendError::IndeterminateCollation(
+                "Could not determine which collation to use for string comparison.".to_string(),
+            )),
+        }
+    }
+
+    // The collation a comparison of operands with these derivations goes by
+    fn comparison(self, other: Derivation) -> Result<Option<Collation>, BackendError> {
+        match (self, other) {
+            (Derivation::Implicit(a), Derivation::Implicit(b)) if a != b => {
+                Err(BackendError::CollationMismatch(format!(
+                    "Collation mismatch between implicit collations \"{}\" and \"{}\".",
+                    a.name(),
+                    b.name()
+                )))
+            }
+            _ => self.combine(other)?.collation(),
+        }
+    }
+}
+
+// The collations declared on the columns of the FROM items of a query, by the alias of
+// the FROM item, or else its name, and the name of the column
+pub(super) struct ColumnCollations {
+    pub(super) columns: Vec<(String, String, Collation)>,
+}
+
+impl ColumnCollations {
+    // The collation of `column` of the FROM item called `source`, or of the first that
+    // has such a column when it isn't qualified
+    pub(super) fn lookup(&self, source: Option<&str>, column: &str) -> Option<Collation> {
+        self.columns
+            .iter()
+            .find(|(relation, name, _)| name == column && source.is_none_or(|s| s == relation))
+            .map(|(_, _, collation)| *collation)
+    }
+
+    // Works out the collation of every comparison in the expression, wrapping the first
+    // operand of each that doesn't go by byte order in a COLLATE of its collation. Returns
+    // where the collation of the expression itself comes from.
+    pub(super) fn resolve(&self, expression: &mut Expression) -> Result<Derivation, BackendError> {
+        Ok(match expression {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => self.column(None, value),
+            Expression::TableColumn(table_column) => {
+                self.column(table_column.table_name.as_deref(), &table_column.col_name)
+            }
+            Expression::Literal(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::SubSelect(_)
+            | Expression::Exists(_)
+            | Expression::Empty => Derivation::Default,
+            Expression::Collate { data, collation } => {
+                self.resolve(data)?;
+                Derivation::Explicit(Collation::from_name(collation)?)
+            }
+            Expression::Cast { data, .. } => {
+                self.resolve(data)?;
+                Derivation::Default
+            }
+            Expression::Binary(binary) => {
+                let first = self.resolve(&mut binary.first)?;
+                let second = self.resolve(&mut binary.second)?;
+                if is_comparison(&binary.operand) {
+                    collate_operand(&mut binary.first, first.comparison(second)?);
+                    Derivation::Default
+                } else if matches!(binary.operand, Token::And | Token::Or) {
+                    Derivation::Default
+                } else {
+                    first.combine(second)?
+                }
+            }
+            Expression::Unary(unary) => {
+                let derivation = self.resolve(&mut unary.first)?;
+                match unary.operand {
+                    Token::Not => Derivation::Default,
+                    _ => derivation,
+                }
+            }
+            Expression::Quantified(quantified) => {
+                let first = self.resolve(&mut quantified.first)?;
+                collate_operand(&mut quantified.first, first.collation()?);
+                Derivation::Default
+            }
+            Expression::FunctionCall(call) => {
+                let mut derivation = Derivation::Default;
+                for arg in call.args.iter_mut() {
+                    derivation = derivation.combine(self.resolve(arg)?)?;
+                }
+                if let Some(filter) = &mut call.filter {
+                    self.resolve(filter)?;
+                }
+                derivation
+            }
+        })
+    }
+
+    fn column(&self, source: Option<&str>, column: &str) -> Derivation {
+        match self.lookup(source, column) {
+            Some(collation) => Derivation::Implicit(collation),
+            None => Derivation::Default,
+        }
+    }
+}
+
+// Gives the operand the collation its comparison goes by, if that isn't byte order
+fn collate_operand(operand: &mut Expression, collation: Option<Collation>) {
+    let collation = match collation {
+        Some(collation) if collation != Collation::Binary => collation,
+        _ => return,
+    };
+    if let Expression::Collate {
+        collation: name, ..
+    } = operand
+    {
+        if name == collation.name() {
+            return;
+        }
+    }
+    let data = std::mem::replace(operand, Expression::Empty);
+    *operand = Expression::Collate {
+        data: Box::new(data),
+        collation: collation.name().to_string(),
+    };
+}
+
+impl Table {
+    // The value of the index expression for a row, as the index keeps it
+    pub(super) fn index_value(
+        &self,
+        index: &Index,
+        row_index: usize,
+    ) -> Result<SqlValue, BackendError> {
+        let (value, _, _) = self.evaluate_cell(row_index, &index.expression)?;
+        Ok(match self.collation_of(&index.expression) {
+            Some(collation) => collation.key(&value).into_owned(),
+            None => value,
+        })
+    }
+
+    // The collation an index keeps the values of its expression by, given with COLLATE
+    // or declared on the column it is made of
+    pub(super) fn collation_of(&self, expression: &Expression) -> Option<Collation> {
+        let column = match expression {
+            Expression::Collate { .. } => return given_collation(expression),
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => value.as_str(),
+            Expression::TableColumn(table_column) => &table_column.col_name,
+            _ => return None,
+        };
+        let idx = self.columns.iter().position(|name| name == column)?;
+        self.column_constraints.get(idx)?.collation
+    }
+}
+
+// Only text has collations
+pub(super) fn check_collatable(typ: SqlType) -> Result<(), BackendError> {
+    match typ {
+        SqlType::Text | SqlType::VarChar | SqlType::Char => Ok(()),
+        typ => Err(BackendError::DatatypeMismatch(format!(
+            "Collations are not supported by type {}.",
+            typ
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod collation_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str =
+        "CREATE TABLE words (word TEXT, folded TEXT COLLATE nocase, sorted TEXT COLLATE und);
+        INSERT INTO words VALUES ('b', 'b', 'b'), ('Z', 'Z', 'Z'), ('a', 'a', 'a'),
+            ('A', 'A', 'A'), ('é', 'é', 'é'), ('e', 'e', 'e');";
+
+    #[test]
+    fn test_collation_keys() {
+        let text = |value: &str| {
+            SqlValue::Text(SqlText::Text {
+                value: value.to_string(),
+            })
+        };
+        assert_eq!(*Collation::NoCase.key(&text("AbC")), text("abc"));
+        assert_eq!(*Collation::Binary.key(&text("AbC")), text("AbC"));
+        let mut words = vec!["b", "Résumé", "resume", "Z", "a", "résumé", "A", "Straße"];
+        words.sort_by_key(|word| Collation::Und.key(&text(word)).into_owned().to_string());
+        assert_eq!(
+            words,
+            vec!["a", "A", "b", "resume", "résumé", "Résumé", "Straße", "Z"]
+        );
+    }
+
+    #[test]
+    fn test_collated_ordering() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words ORDER BY word;"),
+            vec!["A", "Z", "a", "b", "e", "é"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT sorted FROM words ORDER BY sorted;"),
+            vec!["a", "A", "b", "e", "é", "Z"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words ORDER BY word COLLATE und DESC;"
+            ),
+            vec!["Z", "é", "e", "b", "A", "a"]
+        );
+    }
+
+    #[test]
+    fn test_collated_comparisons() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE folded = 'a';"),
+            vec!["a", "A"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word COLLATE nocase IN (SELECT 'B');"
+            ),
+            vec!["b"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE 'a' = folded COLLATE binary;"
+            ),
+            vec!["a"]
+        );
+        // Und orders by letters first, but only equal texts are equal
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE sorted < 'b' AND sorted <> 'a';"
+            ),
+            vec!["A"]
+        );
+        // The collation of a comparison in a select item
+        assert_eq!(
+            rows(&mut mb, "SELECT 'X' COLLATE nocase = 'x', 'X' = 'x';"),
+            vec!["true|false"]
+        );
+    }
+
+    #[test]
+    fn test_collated_grouping() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT folded, count(*) FROM words GROUP BY folded;"
+            ),
+            vec!["b|1", "Z|1", "a|2", "é|1", "e|1"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT DISTINCT word COLLATE nocase FROM words;"),
+            vec!["b", "Z", "a", "é", "e"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT DISTINCT word FROM words;").len(), 6);
+    }
+
+    #[test]
+    fn test_collated_unique_index() {
+        let mut mb = backend(
+            "CREATE TABLE users (name TEXT COLLATE nocase);
+            CREATE UNIQUE INDEX users_name ON users (name);
+            INSERT INTO users VALUES ('Ann');",
+        );
+        match mb.eval_query("INSERT INTO users VALUES ('ANN');") {
+            Err(BackendError::UniqueViolation(_)) => {}
+            result => panic!("Expected a unique violation, got {:?}", result),
+        }
+        mb.eval_query("INSERT INTO users VALUES ('Bob');").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT name FROM users WHERE name = 'bob';"),
+            vec!["Bob"]
+        );
+    }
+
+    #[test]
+    fn test_collation_errors() {
+        let tests = vec![
+            (
+                "SELECT folded = sorted FROM words;",
+                "42P21",
+                "Collation mismatch between implicit collations \"nocase\" and \"und\".",
+            ),
+            (
+                "SELECT 'a' COLLATE nocase = 'b' COLLATE und;",
+                "42P21",
+                "Collation mismatch between explicit collations \"nocase\" and \"und\".",
+            ),
+            (
+                "SELECT word FROM words WHERE folded || sorted = 'x';",
+                "42P22",
+                "Could not determine which collation to use for string comparison.",
+            ),
+            (
+                "SELECT word FROM words ORDER BY word COLLATE klingon;",
+                "42704",
+                "Collation \"klingon\" does not exist.",
+            ),
+            (
+                "CREATE TABLE bad (n INT COLLATE nocase);",
+                "42804",
+                "Collations are not supported by type Int.",
+            ),
+            (
+                "CREATE TABLE bad (t TEXT COLLATE klingon);",
+                "42704",
+                "Collation \"klingon\" does not exist.",
+            ),
+        ];
+
+        let mut mb = backend(SETUP);
+        for (query, code, message) in tests {
+            match mb.eval_query(query) {
+                Err(err) => {
+                    assert_eq!(err.code(), code, "{}", query);
+                    assert_eq!(err.message(), message, "{}", query);
+                }
+                Ok(_) => panic!("{}: expected an error", query),
+            }
+        }
+        let err = mb.eval_query("SELECT 'a' COLLATE 3;").unwrap_err();
+        assert_eq!(err.code(), "42601");
+        assert!(
+            err.message().contains("Expected collation name"),
+            "{}",
+            err.message()
+        );
+        // Either side given explicitly settles it
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE folded = sorted COLLATE binary;"
+            )
+            .len(),
+            6
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use super::collation::ColumnCollations;
+use super::resolver::{local_name, Resolution};
+use super::{output_name, source_joins, MemoryBackend, Table};
+use crate::ast::*;
+use crate::backend::ColumnOrigin;
+
+// The FROM item and joins of a query, for what the result columns read from them tell
+// about themselves: the type modifier their table declared, whether they can be NULL and
+// the column of a table they come from
+pub(super) struct ColumnOrigins<'a> {
+    relations: Vec<Relation<'a>>,
+}
+
+struct Relation<'a> {
+    // The alias of the FROM item, or else its name
+    name: &'a str,
+    // The table it reads, if it reads one
+    table: Option<&'a Table>,
+    // The name of that table when it is one of the backend's rather than a WITH query's
+    table_name: Option<&'a str>,
+    // The columns of a subquery, views included, by name with the column they come from
+    derived: Vec<(String, Option<ColumnOrigin>)>,
+    // Whether it is on the side of an outer join that gets NULLs for rows without a match
+    outer: bool,
+}
+
+// What a result column tells about itself, see `ResultColumn`
+#[derive(Clone, Debug)]
+pub(super) struct DescribedColumn {
+    pub(super) type_modifier: Option<usize>,
+    pub(super) nullable: bool,
+    pub(super) origin: Option<ColumnOrigin>,
+}
+
+impl DescribedColumn {
+    // That of a column computed by an expression
+    pub(super) fn computed() -> Self {
+        DescribedColumn {
+            type_modifier: None,
+            nullable: true,
+            origin: None,
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub(super) fn column_origins<'a>(
+        &'a self,
+        from: Option<&'a RowDataSource>,
+    ) -> ColumnOrigins<'a> {
+        let from = match from {
+            Some(from) => from,
+            None => return ColumnOrigins { relations: vec![] },
+        };
+        let mut relations = vec![self.relation(from)];
+        // Joins nest to the left, a RIGHT JOIN makes everything joined before it nullable
+        for join in source_joins(from) {
+            let mut relation = self.relation(&join.source);
+            match join.kind {
+                JoinKind::Inner => {}
+                JoinKind::LeftOuter => relation.outer = true,
+                JoinKind::RightOuter => relations.iter_mut().for_each(|r| r.outer = true),
+                JoinKind::FullOuter => {
+                    relations.iter_mut().for_each(|r| r.outer = true);
+                    relation.outer = true;
+                }
+            }
+            relations.push(relation);
+        }
+        ColumnOrigins { relations }
+    }
+
+    fn relation<'a>(&'a self, source: &'a RowDataSource) -> Relation<'a> {
+        let mut derived = vec![];
+        let (name, table, table_name) = match source {
+            RowDataSource::Table {
+                table_name,
+                as_clause,
+                ..
+            } => (
+                as_clause.as_deref().unwrap_or(table_name),
+                self.table(table_name),
+                match self.resolve(table_name) {
+                    Some(Resolution::WithQuery) => None,
+                    _ => Some(local_name(table_name)),
+                },
+            ),
+            RowDataSource::SubSelect {
+                as_clause, select, ..
+            } => {
+                derived = self.derived_origins(select);
+                (as_clause.as_str(), None, None)
+            }
+            RowDataSource::Values { as_clause, .. } => (as_clause.as_str(), None, None),
+            RowDataSource::Function { as_clause, .. } => {
+                (as_clause.as_deref().unwrap_or(""), None, None)
+            }
+        };
+        Relation {
+            name,
+            table,
+            table_name,
+            derived,
+            outer: false,
+        }
+    }
+
+    // The columns of a subquery that come from a column of a table, found from how it is
+    // written, like the runner names and resolves them. The others come from nowhere.
+    fn derived_origins(&self, select: &SelectStatement) -> Vec<(String, Option<ColumnOrigin>)> {
+        let origins = self.column_origins(select.from.first());
+        let mut columns = vec![];
+        for item in &select.items {
+            if item.asterisk {
+                let replaced = item
+                    .asterisk_modifiers
+                    .iter()
+                    .flat_map(|modifiers| &modifiers.replace)
+                    .filter_map(|replacement| replacement.as_clause.as_deref());
+                let replaced: Vec<&str> = replaced.collect();
+                let relations = origins.relations.iter().filter(|relation| {
+                    item.asterisk_source
+                        .as_ref()
+                        .is_none_or(|source| relation.name == source)
+                });
+                for relation in relations {
+                    for column in relation.columns() {
+                        let origin = match replaced.contains(&column) {
+                            true => None,
+                            false => relation.origin(column),
+                        };
+                        columns.push((column.to_string(), origin));
+                    }
+                }
+                continue;
+            }
+            let origin = match &item.expression {
+                Expression::TableColumn(TableColumn {
+                    col_name,
+                    table_name,
+                }) => origins.origin(table_name.as_deref(), col_name),
+                _ => None,
+            };
+            let name = match &item.as_clause {
+                Some(as_clause) => as_clause.clone(),
+                None => output_name(&item.expression),
+            };
+            columns.push((name, origin));
+        }
+        columns
+    }
+}
+
+impl<'a> Relation<'a> {
+    // The columns known to be there, those of VALUES and functions are not
+    fn columns(&self) -> Vec<&str> {
+        match self.table {
+            Some(table) => table.columns.iter().map(String::as_str).collect(),
+            None => self.derived.iter().map(|(name, _)| name.as_str()).collect(),
+        }
+    }
+
+    fn has_column(&self, column: &str) -> bool {
+        self.columns().contains(&column)
+    }
+
+    fn origin(&self, column: &str) -> Option<ColumnOrigin> {
+        match (self.table, self.table_name) {
+            (Some(table), Some(table_name)) if table.columns.iter().any(|c| c == column) => {
+                Some(ColumnOrigin {
+                    table: table_name.to_string(),
+                    column: column.to_string(),
+                })
+            }
+            (Some(_), _) => None,
+            (None, _) => self
+                .derived
+                .iter()
+                .find(|(name, _)| name == column)
+                .and_then(|(_, origin)| origin.clone()),
+        }
+    }
+}
+
+impl ColumnOrigins<'_> {
+    // The columns of the tables read that are declared with a collation
+    pub(super) fn collations(&self) -> ColumnCollations {
+        let mut columns = vec![];
+        for relation in &self.relations {
+            let table = match relation.table {
+                Some(table) => table,
+                None => continue,
+            };
+            for (column, constraints) in table.columns.iter().zip(&table.column_constraints) {
+                if let Some(collation) = constraints.collation {
+                    columns.push((relation.name.to_string(), column.clone(), collation));
+                }
+            }
+        }
+        ColumnCollations { columns }
+    }
+
+    // The column `column` of the FROM item called `source` comes from, or without one of
+    // the first FROM item that has a column of the name
+    fn origin(&self, source: Option<&str>, column: &str) -> Option<ColumnOrigin> {
+        let relation = match source {
+            Some(source) => self.relations.iter().find(|r| r.name == source),
+            None => self.relations.iter().find(|r| r.has_column(column)),
+        };
+        relation.and_then(|relation| relation.origin(column))
+    }
+
+    // How `column` of the FROM item called `source` is described. Columns of subqueries,
+    // VALUES and functions have no modifier and can be NULL.
+    pub(super) fn describe(&self, source: &str, column: &str) -> DescribedColumn {
+        let relation = match self.relations.iter().find(|r| r.name == source) {
+            Some(relation) => relation,
+            None => return DescribedColumn::computed(),
+        };
+        let origin = relation.origin(column);
+        let table = match relation.table {
+            Some(table) => table,
+            None => {
+                return DescribedColumn {
+                    origin,
+                    ..DescribedColumn::computed()
+                }
+            }
+        };
+        let constraints = match table.columns.iter().position(|c| c == column) {
+            Some(idx) => table.column_constraints.get(idx),
+            None => return DescribedColumn::computed(),
+        };
+        // A primary key can't hold NULL either
+        let is_primary_key = table
+            .indexes
+            .iter()
+            .any(|index| index.primary_key && index.columns == [column]);
+        let (type_modifier, not_null) = match constraints {
+            Some(constraints) => (
+                constraints.type_modifier,
+                constraints.not_null || is_primary_key,
+            ),
+            None => (None, is_primary_key),
+        };
+        DescribedColumn {
+            type_modifier,
+            nullable: relation.outer || !not_null,
+            origin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod column_origin_tests {
+    use super::super::EvalResult;
+    use super::*;
+
+    struct ColumnOriginTest {
+        query: &'static str,
+        // The type modifier and nullability of each result column
+        columns: Vec<(Option<usize>, bool)>,
+    }
+
+    #[test]
+    fn test_result_column_origins() {
+        let tests = vec![
+            ColumnOriginTest {
+                query: "SELECT * FROM a;",
+                columns: vec![(None, false), (Some(20), true), (Some(2), false)],
+            },
+            ColumnOriginTest {
+                query: "SELECT code AS c, a.name, id + 1, upper(name) FROM a;",
+                columns: vec![
+                    (Some(2), false),
+                    (Some(20), true),
+                    (None, true),
+                    (None, true),
+                ],
+            },
+            ColumnOriginTest {
+                query: "SELECT a.id, b.id FROM a JOIN b ON a.id = b.a_id;",
+                columns: vec![(None, false), (None, false)],
+            },
+            ColumnOriginTest {
+                query: "SELECT a.id, b.id, b.label FROM a LEFT JOIN b ON a.id = b.a_id;",
+                columns: vec![(None, false), (None, true), (Some(5), true)],
+            },
+            ColumnOriginTest {
+                query: "SELECT x.id, y.id FROM a AS x RIGHT JOIN b AS y ON x.id = y.a_id;",
+                columns: vec![(None, true), (None, false)],
+            },
+            ColumnOriginTest {
+                query: "SELECT a.id, b.id FROM a FULL OUTER JOIN b ON a.id = b.a_id;",
+                columns: vec![(None, true), (None, true)],
+            },
+            ColumnOriginTest {
+                query: "SELECT code, count(*) FROM a GROUP BY code;",
+                columns: vec![(Some(2), false), (None, true)],
+            },
+            // Without rows the columns are described the same
+            ColumnOriginTest {
+                query: "SELECT id, code FROM a WHERE id > 10;",
+                columns: vec![(None, false), (Some(2), false)],
+            },
+            ColumnOriginTest {
+                query: "SELECT * FROM (SELECT code FROM a) AS s;",
+                columns: vec![(None, true)],
+            },
+            ColumnOriginTest {
+                query: "SELECT * FROM copy;",
+                columns: vec![(None, true), (Some(20), true), (Some(2), true)],
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE a (id INT PRIMARY KEY, name VARCHAR(20), code CHAR(2) NOT NULL);
+            CREATE TABLE b (id INT NOT NULL, a_id INT, label VARCHAR(5));
+            INSERT INTO a VALUES (1, 'x', 'aa');
+            INSERT INTO b VALUES (10, 1, 't');
+            CREATE TABLE copy AS SELECT * FROM a;",
+        )
+        .unwrap();
+
+        for test in tests {
+            let results = match mb.eval_query(test.query).expect(test.query).pop() {
+                Some(EvalResult::Select { results, .. }) => results,
+                _ => panic!("Expected select results for {}", test.query),
+            };
+            let columns: Vec<(Option<usize>, bool)> = results
+                .columns
+                .iter()
+                .map(|c| (c.type_modifier, c.nullable))
+                .collect();
+            assert_eq!(columns, test.columns, "{}", test.query);
+        }
+    }
+
+    #[test]
+    fn test_origins_through_views() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT PRIMARY KEY, name TEXT);
+            CREATE TABLE pets (id INT, owner INT, name TEXT);
+            INSERT INTO people VALUES (1, 'Baam');
+            INSERT INTO pets VALUES (10, 1, 'Rex');
+            CREATE VIEW owned AS SELECT people.name AS owner_name, pets.name, pets.id + 0 \
+            AS pet_id FROM people JOIN pets ON people.id = pets.owner;
+            CREATE VIEW named AS SELECT *, upper(name) AS shout FROM owned AS o;",
+        )
+        .unwrap();
+
+        let origin = |table: &str, column: &str| {
+            Some(ColumnOrigin {
+                table: table.to_string(),
+                column: column.to_string(),
+            })
+        };
+        let tests = vec![
+            (
+                "SELECT n.owner_name, name AS pet, pet_id, shout FROM named AS n;",
+                vec![origin("people", "name"), origin("pets", "name"), None, None],
+            ),
+            (
+                "SELECT * FROM (SELECT p.id, count(*) FROM people AS p GROUP BY p.id) AS s;",
+                vec![origin("people", "id"), None],
+            ),
+            (
+                "SELECT * REPLACE (upper(name) AS name) FROM (SELECT id, name FROM pets) AS s;",
+                vec![origin("pets", "id"), None],
+            ),
+            (
+                "WITH w AS (SELECT id FROM people) SELECT w.id, owner FROM w, pets;",
+                vec![None, origin("pets", "owner")],
+            ),
+        ];
+        for (query, expected) in tests {
+            let results = match mb.eval_query(query).expect(query).pop() {
+                Some(EvalResult::Select { results, .. }) => results,
+                _ => panic!("Expected select results for {}", query),
+            };
+            let origins: Vec<Option<ColumnOrigin>> =
+                results.columns.into_iter().map(|c| c.origin).collect();
+            assert_eq!(origins, expected, "{}", query);
+        }
+    }
+}
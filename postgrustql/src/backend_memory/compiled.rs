@@ -0,0 +1,477 @@
+use std::borrow::Cow;
+
+use super::collation::{apply_collated_operator, apply_collated_quantified_operator};
+use super::division::null_on_division_by_zero;
+use super::pattern::apply_match_operator;
+use super::stored_row::RowValues;
+use super::warnings::explicit_cast;
+use super::{Collation, ScalarFunction, Table, ERR_INVALID_CELL};
+use crate::ast::*;
+use crate::backend::{BackendError, ERR_COLUMN_DOES_NOT_EXIST, ERR_WINDOW_FUNCTION_NOT_ALLOWED};
+use crate::lexer::Token;
+use crate::sql_types::{SqlType, SqlValue};
+
+// CompiledExpression is an expression resolved against the columns of one table, so
+// it can be evaluated for many rows without walking the AST again. Column references
+// become row offsets, literals are decoded once and constant subtrees are folded.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CompiledExpression {
+    Const(SqlValue),
+    Column(usize),
+    Binary {
+        first: Box<CompiledExpression>,
+        second: Box<CompiledExpression>,
+        operand: Token,
+    },
+    Unary {
+        first: Box<CompiledExpression>,
+        operand: Token,
+    },
+    Cast {
+        data: Box<CompiledExpression>,
+        typ: SqlType,
+    },
+    // The value of `data`, which comparisons compare under the collation
+    Collate {
+        data: Box<CompiledExpression>,
+        collation: Collation,
+    },
+    Function {
+        function: ScalarFunction,
+        args: Vec<CompiledExpression>,
+    },
+    // A comparison against every value a subquery gave
+    Quantified {
+        first: Box<CompiledExpression>,
+        operand: Token,
+        all: bool,
+        values: Vec<SqlValue>,
+    },
+    // A single column subquery evaluated against the current row
+    SubSelect {
+        condition: Option<Box<CompiledExpression>>,
+        item: Box<CompiledExpression>,
+    },
+    // Evaluating the expression fails, but only once there is a row to evaluate, the
+    // same as it does when interpreted
+    Error(BackendError),
+}
+
+impl CompiledExpression {
+    #[inline]
+    pub fn evaluate<'a, R: RowValues + ?Sized>(
+        &'a self,
+        row: &'a R,
+    ) -> Result<Cow<'a, SqlValue>, BackendError> {
+        match self {
+            CompiledExpression::Const(value) => Ok(Cow::Borrowed(value)),
+            CompiledExpression::Column(idx) => match row.column(*idx) {
+                Some(value) => Ok(value),
+                None => Err(BackendError::Internal(
+                    "Error accesing row's column".to_string(),
+                )),
+            },
+            CompiledExpression::Binary {
+                first,
+                second,
+                operand,
+            } => {
+                let first_val = first.evaluate(row)?;
+                let second_val = second.evaluate(row)?;
+                Ok(Cow::Owned(apply_collated_operator(
+                    operand,
+                    first.collation().or_else(|| second.collation()),
+                    &first_val,
+                    &second_val,
+                )?))
+            }
+            CompiledExpression::Unary { first, operand } => {
+                let val = first.evaluate(row)?;
+                Ok(Cow::Owned(apply_unary_operator(operand, &val)?))
+            }
+            CompiledExpression::Cast { data, typ } => {
+                let val = data.evaluate(row)?;
+                Ok(Cow::Owned(explicit_cast(&val, *typ)?))
+            }
+            CompiledExpression::Collate { data, .. } => data.evaluate(row),
+            CompiledExpression::Function { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(row).map(Cow::into_owned))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Cow::Owned(function.call(&args)?))
+            }
+            CompiledExpression::Quantified {
+                first,
+                operand,
+                all,
+                values,
+            } => {
+                let first_val = first.evaluate(row)?;
+                Ok(Cow::Owned(apply_collated_quantified_operator(
+                    operand,
+                    *all,
+                    first.collation(),
+                    &first_val,
+                    values,
+                )?))
+            }
+            CompiledExpression::SubSelect { condition, item } => {
+                if let Some(condition) = condition {
+                    if let SqlValue::Boolean(false) = *condition.evaluate(row)? {
+                        return Ok(Cow::Owned(SqlValue::Null));
+                    }
+                }
+                item.evaluate(row)
+            }
+            CompiledExpression::Error(msg) => Err(msg.clone()),
+        }
+    }
+
+    // Whether the expression holds for `row`, anything besides TRUE counts as false
+    #[inline]
+    pub fn is_true<R: RowValues + ?Sized>(&self, row: &R) -> Result<bool, BackendError> {
+        Ok(matches!(*self.evaluate(row)?, SqlValue::Boolean(true)))
+    }
+}
+
+impl Table {
+    pub fn compile_expression(&self, expression: &Expression) -> CompiledExpression {
+        compile_expression(&self.columns, expression)
+    }
+}
+
+// Resolves `expression` against a table with the given columns
+pub fn compile_expression(columns: &[String], expression: &Expression) -> CompiledExpression {
+    let column = |name: &str| match columns.iter().position(|col| col == name) {
+        Some(idx) => CompiledExpression::Column(idx),
+        None => CompiledExpression::Error(BackendError::UndefinedColumn(format!(
+            "{}: {}",
+            name, ERR_COLUMN_DOES_NOT_EXIST
+        ))),
+    };
+
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => column(value),
+        Expression::Literal(LiteralExpression { literal }) => match SqlValue::from_token(literal) {
+            Ok(value) => CompiledExpression::Const(value),
+            Err(err) => CompiledExpression::Error(err.into()),
+        },
+        Expression::TableColumn(table_column) => column(&table_column.col_name),
+        Expression::ProcessedTableColumn(table_column) => {
+            if table_column.col_idx < columns.len() {
+                CompiledExpression::Column(table_column.col_idx)
+            } else {
+                CompiledExpression::Error(BackendError::UndefinedColumn(
+                    ERR_COLUMN_DOES_NOT_EXIST.to_string(),
+                ))
+            }
+        }
+        Expression::Binary(binary_expression) => fold(CompiledExpression::Binary {
+            first: Box::new(compile_expression(columns, &binary_expression.first)),
+            second: Box::new(compile_expression(columns, &binary_expression.second)),
+            operand: binary_expression.operand.clone(),
+        }),
+        Expression::Unary(unary_expression) => match unary_expression.negated_literal() {
+            Some(literal) => {
+                compile_expression(columns, &Expression::Literal(LiteralExpression { literal }))
+            }
+            None => fold(CompiledExpression::Unary {
+                first: Box::new(compile_expression(columns, &unary_expression.first)),
+                operand: unary_expression.operand.clone(),
+            }),
+        },
+        Expression::Cast { data, typ } => fold(CompiledExpression::Cast {
+            data: Box::new(compile_expression(columns, data)),
+            typ: *typ,
+        }),
+        // Kept unfolded, the comparison it is an operand of needs to see it
+        Expression::Collate { data, collation } => match Collation::from_name(collation) {
+            Ok(collation) => CompiledExpression::Collate {
+                data: Box::new(compile_expression(columns, data)),
+                collation,
+            },
+            Err(err) => CompiledExpression::Error(err),
+        },
+        Expression::FunctionCall(call) if call.over.is_some() => CompiledExpression::Error(
+            BackendError::WindowingError(ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string()),
+        ),
+        Expression::FunctionCall(call) => match ScalarFunction::from_call(call) {
+            Ok(function) => fold(CompiledExpression::Function {
+                function,
+                args: call
+                    .args
+                    .iter()
+                    .map(|arg| compile_expression(columns, arg))
+                    .collect(),
+            }),
+            Err(err) => CompiledExpression::Error(err),
+        },
+        Expression::Quantified(quantified) => match &quantified.set {
+            QuantifiedSet::Values(values) => fold(CompiledExpression::Quantified {
+                first: Box::new(compile_expression(columns, &quantified.first)),
+                operand: quantified.operand.clone(),
+                all: quantified.all,
+                values: values.clone(),
+            }),
+            QuantifiedSet::SubSelect(_) => {
+                CompiledExpression::Error(BackendError::Internal(ERR_INVALID_CELL.to_string()))
+            }
+        },
+        Expression::SubSelect(select_statement) => {
+            let item = match select_statement.items.as_slice() {
+                [item] => compile_expression(columns, &item.expression),
+                _ => {
+                    return CompiledExpression::Error(BackendError::SyntaxError(
+                        "Subquery must return only one column".to_string(),
+                    ))
+                }
+            };
+            let condition = match select_statement.where_clause {
+                Expression::Empty => None,
+                ref where_clause => Some(Box::new(compile_expression(columns, where_clause))),
+            };
+            CompiledExpression::SubSelect {
+                condition,
+                item: Box::new(item),
+            }
+        }
+        // EXISTS has been run by the time anything is compiled
+        Expression::Exists(_) | Expression::Empty => {
+            CompiledExpression::Error(BackendError::Internal(ERR_INVALID_CELL.to_string()))
+        }
+    }
+}
+
+// Evaluates an operator whose operands are all constants. Failures are kept as errors
+// to be raised during evaluation, a row is needed for them to happen.
+fn fold(expression: CompiledExpression) -> CompiledExpression {
+    let is_const = match &expression {
+        CompiledExpression::Binary { first, second, .. } => first.is_const() && second.is_const(),
+        CompiledExpression::Unary { first, .. } => first.is_const(),
+        CompiledExpression::Cast { data, .. } => data.is_const(),
+        CompiledExpression::Function { function, args } => {
+            !function.is_volatile() && args.iter().all(|arg| arg.is_const())
+        }
+        CompiledExpression::Quantified { first, .. } => first.is_const(),
+        _ => false,
+    };
+    if !is_const {
+        return expression;
+    }
+
+    match expression.evaluate(&[]) {
+        Ok(value) => CompiledExpression::Const(value.into_owned()),
+        Err(msg) => CompiledExpression::Error(msg),
+    }
+}
+
+impl CompiledExpression {
+    fn is_const(&self) -> bool {
+        match self {
+            CompiledExpression::Const(_) => true,
+            CompiledExpression::Collate { data, .. } => data.is_const(),
+            _ => false,
+        }
+    }
+
+    // Whether evaluating the expression calls a volatile function
+    pub fn is_volatile(&self) -> bool {
+        match self {
+            CompiledExpression::Binary { first, second, .. } => {
+                first.is_volatile() || second.is_volatile()
+            }
+            CompiledExpression::Unary { first, .. }
+            | CompiledExpression::Quantified { first, .. } => first.is_volatile(),
+            CompiledExpression::Cast { data, .. } | CompiledExpression::Collate { data, .. } => {
+                data.is_volatile()
+            }
+            CompiledExpression::Function { function, args } => {
+                function.is_volatile() || args.iter().any(|arg| arg.is_volatile())
+            }
+            CompiledExpression::SubSelect { condition, item } => {
+                matches!(condition, Some(c) if c.is_volatile()) || item.is_volatile()
+            }
+            CompiledExpression::Const(_)
+            | CompiledExpression::Column(_)
+            | CompiledExpression::Error(_) => false,
+        }
+    }
+}
+
+pub fn apply_binary_operator(
+    operand: &Token,
+    first_val: &SqlValue,
+    second_val: &SqlValue,
+) -> Result<SqlValue, BackendError> {
+    // Besides AND and OR, which have rules of their own, operators give NULL for NULL
+    if (first_val.is_null() || second_val.is_null()) && !matches!(operand, Token::And | Token::Or) {
+        return Ok(SqlValue::Null);
+    }
+    let result = match operand {
+        Token::Equal => SqlValue::equals(first_val, second_val)?,
+        Token::NotEqual => SqlValue::not_equal(first_val, second_val)?,
+        Token::GreaterThan => SqlValue::greater_than(first_val, second_val)?,
+        Token::GreaterThanOrEqual => SqlValue::greater_than_or_equals(first_val, second_val)?,
+        Token::LessThan => SqlValue::less_than(first_val, second_val)?,
+        Token::LessThanOrEqual => SqlValue::less_than_or_equals(first_val, second_val)?,
+        Token::Concat => SqlValue::concat(first_val, second_val)?,
+        Token::Plus => SqlValue::add(first_val, second_val)?,
+        Token::Minus => SqlValue::subtract(first_val, second_val)?,
+        Token::Asterisk => SqlValue::multiply(first_val, second_val)?,
+        Token::Slash => null_on_division_by_zero(SqlValue::divide(first_val, second_val))?,
+        Token::Modulo => null_on_division_by_zero(SqlValue::modulo(first_val, second_val))?,
+        Token::And => SqlValue::and(first_val, second_val)?,
+        Token::Or => SqlValue::or(first_val, second_val)?,
+        Token::Exponentiation => SqlValue::exponentiation(first_val, second_val)?,
+        Token::BitwiseAnd => SqlValue::bitwise_and(first_val, second_val)?,
+        Token::BitwiseOr => SqlValue::bitwise_or(first_val, second_val)?,
+        Token::BitwiseXor => SqlValue::bitwise_xor(first_val, second_val)?,
+        Token::BitwiseShiftLeft => SqlValue::bitwise_shift_left(first_val, second_val)?,
+        Token::BitwiseShiftRight => SqlValue::bitwise_shift_right(first_val, second_val)?,
+        Token::RegexMatch
+        | Token::RegexMatchInsensitive
+        | Token::RegexNotMatch
+        | Token::RegexNotMatchInsensitive
+        | Token::Similar => apply_match_operator(operand, first_val, second_val)?,
+        _ => return Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
+    };
+    Ok(result)
+}
+
+// Compares `first_val` with each of `values`. ANY holds when a comparison does and ALL
+// when all of them do, so ANY is false and ALL true for no values at all. When the
+// outcome hangs on comparisons with NULL, it is NULL.
+pub fn apply_quantified_operator(
+    operand: &Token,
+    all: bool,
+    first_val: &SqlValue,
+    values: &[SqlValue],
+) -> Result<SqlValue, BackendError> {
+    let mut unknown = false;
+    for value in values {
+        match apply_binary_operator(operand, first_val, value)? {
+            SqlValue::Boolean(verdict) if verdict != all => return Ok(SqlValue::Boolean(verdict)),
+            SqlValue::Null => unknown = true,
+            _ => {}
+        }
+    }
+    if unknown {
+        Ok(SqlValue::Null)
+    } else {
+        Ok(SqlValue::Boolean(all))
+    }
+}
+
+pub fn apply_unary_operator(operand: &Token, val: &SqlValue) -> Result<SqlValue, BackendError> {
+    if val.is_null() {
+        return Ok(SqlValue::Null);
+    }
+    let result = match operand {
+        Token::Minus => SqlValue::minus(val)?,
+        Token::SquareRoot => SqlValue::square_root(val)?,
+        Token::CubeRoot => SqlValue::cube_root(val)?,
+        Token::Factorial | Token::FactorialPrefix => SqlValue::factorial(val)?,
+        Token::Not => SqlValue::not(val)?,
+        Token::AbsoluteValue => SqlValue::abs(val)?,
+        Token::BitwiseNot => SqlValue::bitwise_not(val)?,
+        _ => return Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod compiled_tests {
+    use super::*;
+    use crate::backend_memory::MemoryBackend;
+    use crate::parser::parse;
+
+    fn where_clause(query: &str) -> Expression {
+        match parse(query).unwrap().statements.pop() {
+            Some(Statement::SelectStatement(select)) => select.where_clause,
+            _ => panic!("Expected a select statement"),
+        }
+    }
+
+    #[test]
+    fn test_compiled_matches_interpreted() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT, name TEXT, age INT, score DOUBLE PRECISION);
+            INSERT INTO people VALUES (1, 'Baam', 17, 2.5);
+            INSERT INTO people VALUES (2, 'Rachel', NULL, 10.0);
+            INSERT INTO people VALUES (3, NULL, 40, NULL);
+            INSERT INTO people VALUES (4, 'Khun', 25, 0.0);",
+        )
+        .unwrap();
+        let table = mb.table("people").unwrap();
+
+        let conditions = vec![
+            "id = 2",
+            "age >= 18 AND age < 65 AND score > 1.5 AND name <> 'Baam' AND id % 2 = 0",
+            "age > 18 OR name = 'Rachel'",
+            "age = NULL",
+            "-id < -2",
+            "name || ' the ' || 'great' = 'Baam the great'",
+            "(1 + 2) * 3 = id + 5",
+            "age::TEXT = '25'",
+            "id / score > 1",
+            "1 / 0 = id",
+            "missing = 1",
+            "id = 1 + missing",
+            "name + 1 = 2",
+            "(SELECT age) > 20",
+            "(SELECT age, id) > 20",
+            "name ~* '^[bk]' OR name !~ 'a'",
+            "name NOT SIMILAR TO '%(ae|el)%'",
+            "name ~ '('",
+        ];
+
+        for condition in conditions {
+            let expression = where_clause(&format!("SELECT id FROM people WHERE {};", condition));
+            let compiled = table.compile_expression(&expression);
+            for (row_index, row) in table.rows.iter().enumerate() {
+                let interpreted = table
+                    .evaluate_cell(row_index, &expression)
+                    .map(|(value, _, _)| value);
+                let compiled = compiled.evaluate(row).map(|value| value.into_owned());
+                assert_eq!(compiled, interpreted, "{} at row {}", condition, row_index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_folds_constants() {
+        let expression =
+            where_clause("SELECT id FROM people WHERE (1 + 2) * 3 = 9 AND id > -(4 - 2);");
+        let compiled = compile_expression(&["id".to_string()], &expression);
+        match compiled {
+            CompiledExpression::Binary { first, second, .. } => {
+                assert_eq!(*first, CompiledExpression::Const(SqlValue::Boolean(true)));
+                match *second {
+                    CompiledExpression::Binary { first, second, .. } => {
+                        assert_eq!(*first, CompiledExpression::Column(0));
+                        assert!(second.is_const());
+                    }
+                    other => panic!("Unexpected {:?}", other),
+                }
+            }
+            other => panic!("Unexpected {:?}", other),
+        }
+
+        // Errors only surface once a row gets evaluated
+        let compiled = compile_expression(
+            &[],
+            &where_clause("SELECT id FROM people WHERE 'a' - 1 = 1;"),
+        );
+        match &compiled {
+            CompiledExpression::Binary { first, .. } => {
+                assert!(matches!(**first, CompiledExpression::Error(_)))
+            }
+            other => panic!("Unexpected {:?}", other),
+        }
+        assert!(compiled.evaluate(&[]).is_err());
+    }
+}
@@ -0,0 +1,777 @@
+use super::nondeterminism::{Clock, FunctionSources, Timer, Timestamp, UuidGenerator};
+use super::timing::Stopwatch;
+use super::variables::SessionVariables;
+use super::{
+    CastMode, ConflictRetry, DivisionByZero, IdentifierMode, MemoryBackend, ResultLimits,
+    DEFAULT_MAX_IDENTIFIER_LENGTH, DEFAULT_STATEMENT_CACHE_CAPACITY,
+};
+use crate::ast::{ResetStatement, SetStatement, ShowStatement};
+use crate::backend::{BackendError, QueryResults, ResultColumn};
+use crate::lexer::{Token, TokenLocation};
+use crate::sql_types::{SqlText, SqlType, SqlValue};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Settings of a backend, given when creating it or a connection to it. The defaults are
+// what a backend created with `MemoryBackend::new` uses.
+//
+// The result limits, work_mem, parallel scans, timing, join reordering, hashed semi
+// joins, unknown settings, deterministic mode, division by zero, the cast mode and the
+// identifier mode belong to the session: `SET` changes them for the connection running it alone, `SHOW`
+// reports them and `RESET` goes back to what the session started with. Read-only mode
+// belongs to the session too, but can only be shown, as can the statement cache and the
+// longest identifier, which are shared by every session.
+// The hooks for the time, random numbers and UUIDs can't be set at all, nor can retrying
+// on conflicts, the directory for temporary files or timing statements at all, which
+// belong to the backend.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BackendConfig {
+    pub limits: ResultLimits,
+    // In kB, how much a sort or DISTINCT holds in memory before going on in temporary
+    // files, never if None. `work_mem` to SET, in kB unless a unit like MB is given.
+    pub work_mem: Option<usize>,
+    // Where those temporary files go, the directory of the system if None
+    pub temp_directory: Option<PathBuf>,
+    // Parsed queries kept for reuse, 0 turns the cache off
+    pub statement_cache_capacity: usize,
+    #[cfg(feature = "parallel")]
+    pub parallel_scans: bool,
+    // Whether statements are timed at all. Off, no clock is read for them and every
+    // duration they report is zero, though EXPLAIN ANALYZE still times what it runs.
+    pub time_statements: bool,
+    // What statements are timed by, the monotonic clock of the system if None
+    pub timer: Option<Timer>,
+    // Whether the operators of every SELECT are timed, see `MemoryBackend::last_timing`
+    pub collect_timing: bool,
+    // Whether inner joins of three tables or more may run in another order than written
+    pub reorder_joins: bool,
+    // Whether EXISTS, IN and NOT IN subqueries may run once into a hash table rather than
+    // for every row
+    pub hash_semi_joins: bool,
+    // Whether SET takes variables the backend doesn't know of, rather than only those
+    // with a dot in their name as Postgres does
+    pub allow_unknown_settings: bool,
+    // Where the timestamp functions read the time from, the system clock if None
+    pub clock: Option<Clock>,
+    // Seeds random() and gen_random_uuid(), so they give the same values on every run
+    pub random_seed: Option<u64>,
+    // What gen_random_uuid() gives in place of random UUIDs
+    pub uuid_generator: Option<UuidGenerator>,
+    // Whether every timestamp function gives the time the transaction started, and
+    // random numbers come from the seed even when none was given.
+    // `postgrustql.deterministic` to SET.
+    pub deterministic: bool,
+    // Whether statements that change tables or the schema fail, unless they only change
+    // temporary tables. `transaction_read_only` to SHOW.
+    pub read_only: bool,
+    // What dividing by zero gives. `postgrustql.division_by_zero` to SET.
+    pub division_by_zero: DivisionByZero,
+    // What becomes of text too long for its column. `postgrustql.cast_mode` to SET.
+    pub cast_mode: CastMode,
+    // The longest identifier in bytes, those longer are cut down or fail as
+    // `identifier_mode` says. `max_identifier_length` to SHOW.
+    pub max_identifier_length: usize,
+    // What becomes of identifiers too long. `postgrustql.identifier_mode` to SET.
+    pub identifier_mode: IdentifierMode,
+    // How writes outside of transactions are run again after a serialization conflict,
+    // never if None
+    pub retry_on_conflict: Option<ConflictRetry>,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig {
+            limits: ResultLimits::default(),
+            work_mem: None,
+            temp_directory: None,
+            statement_cache_capacity: DEFAULT_STATEMENT_CACHE_CAPACITY,
+            #[cfg(feature = "parallel")]
+            parallel_scans: true,
+            time_statements: true,
+            timer: None,
+            collect_timing: false,
+            reorder_joins: true,
+            hash_semi_joins: true,
+            allow_unknown_settings: false,
+            clock: None,
+            random_seed: None,
+            uuid_generator: None,
+            deterministic: false,
+            read_only: false,
+            division_by_zero: DivisionByZero::Error,
+            cast_mode: CastMode::Strict,
+            max_identifier_length: DEFAULT_MAX_IDENTIFIER_LENGTH,
+            identifier_mode: IdentifierMode::Truncate,
+            retry_on_conflict: None,
+        }
+    }
+}
+
+impl BackendConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_result_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.limits.max_result_rows = max_rows;
+        self
+    }
+
+    pub fn max_result_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.limits.max_result_bytes = max_bytes;
+        self
+    }
+
+    pub fn work_mem(mut self, kilobytes: Option<usize>) -> Self {
+        self.work_mem = kilobytes;
+        self
+    }
+
+    pub fn temp_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.temp_directory = Some(directory.into());
+        self
+    }
+
+    pub fn statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.statement_cache_capacity = capacity;
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn parallel_scans(mut self, enabled: bool) -> Self {
+        self.parallel_scans = enabled;
+        self
+    }
+
+    pub fn time_statements(mut self, enabled: bool) -> Self {
+        self.time_statements = enabled;
+        self
+    }
+
+    pub fn timer(mut self, timer: impl Fn() -> Duration + Send + Sync + 'static) -> Self {
+        self.timer = Some(Timer::new(timer));
+        self
+    }
+
+    pub fn collect_timing(mut self, enabled: bool) -> Self {
+        self.collect_timing = enabled;
+        self
+    }
+
+    pub fn reorder_joins(mut self, enabled: bool) -> Self {
+        self.reorder_joins = enabled;
+        self
+    }
+
+    pub fn hash_semi_joins(mut self, enabled: bool) -> Self {
+        self.hash_semi_joins = enabled;
+        self
+    }
+
+    pub fn allow_unknown_settings(mut self, enabled: bool) -> Self {
+        self.allow_unknown_settings = enabled;
+        self
+    }
+
+    pub fn clock(mut self, clock: impl Fn() -> Timestamp + Send + Sync + 'static) -> Self {
+        self.clock = Some(Clock::new(clock));
+        self
+    }
+
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    pub fn uuid_generator(
+        mut self,
+        generator: impl Fn() -> [u8; 16] + Send + Sync + 'static,
+    ) -> Self {
+        self.uuid_generator = Some(UuidGenerator::new(generator));
+        self
+    }
+
+    pub fn deterministic(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled;
+        self
+    }
+
+    pub fn division_by_zero(mut self, mode: DivisionByZero) -> Self {
+        self.division_by_zero = mode;
+        self
+    }
+
+    pub fn cast_mode(mut self, mode: CastMode) -> Self {
+        self.cast_mode = mode;
+        self
+    }
+
+    pub fn max_identifier_length(mut self, max_length: usize) -> Self {
+        self.max_identifier_length = max_length;
+        self
+    }
+
+    pub fn identifier_mode(mut self, mode: IdentifierMode) -> Self {
+        self.identifier_mode = mode;
+        self
+    }
+
+    // Runs a write outside of a transaction up to `max_attempts` times while it fails with
+    // a serialization conflict, waiting around `backoff` before the first retry and twice
+    // as long before every one after it
+    pub fn retry_on_conflict(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        self.retry_on_conflict = Some(ConflictRetry::new(max_attempts, backoff));
+        self
+    }
+}
+
+impl MemoryBackend {
+    pub fn with_config(config: BackendConfig) -> MemoryBackend {
+        let mut backend = MemoryBackend::new();
+        backend.limits = config.limits;
+        backend.work_mem = config.work_mem;
+        backend.temp_directory = config.temp_directory.clone();
+        backend.set_statement_cache_capacity(config.statement_cache_capacity);
+        #[cfg(feature = "parallel")]
+        backend.set_parallel_scans(config.parallel_scans);
+        backend.timing.enabled = config.time_statements;
+        backend.timing.stopwatch = Stopwatch::new(config.timer.clone());
+        backend.set_collect_timing(config.collect_timing);
+        backend.reorder_joins = config.reorder_joins;
+        backend.hash_semi_joins = config.hash_semi_joins;
+        backend.allow_unknown_settings = config.allow_unknown_settings;
+        backend.sources = FunctionSources::new(&config);
+        backend.read_only = config.read_only;
+        backend.division_by_zero = config.division_by_zero;
+        backend.cast_mode = config.cast_mode;
+        backend.max_identifier_length = config.max_identifier_length;
+        backend.identifier_mode = config.identifier_mode;
+        backend.conflict_retry = config.retry_on_conflict;
+        backend.variables = SessionVariables::new(config);
+        backend
+    }
+
+    // The settings in effect, including those changed since by SET or the setters
+    pub fn config(&self) -> BackendConfig {
+        let (clock, random_seed, uuid_generator) = self.sources.hooks();
+        BackendConfig {
+            limits: self.limits,
+            work_mem: self.work_mem,
+            temp_directory: self.temp_directory.clone(),
+            statement_cache_capacity: self.statement_cache_stats().capacity,
+            #[cfg(feature = "parallel")]
+            parallel_scans: self.parallel_scans,
+            time_statements: self.timing.enabled,
+            timer: self.timing.stopwatch.timer(),
+            collect_timing: self.timing.collect,
+            reorder_joins: self.reorder_joins,
+            hash_semi_joins: self.hash_semi_joins,
+            allow_unknown_settings: self.allow_unknown_settings,
+            clock,
+            random_seed,
+            uuid_generator,
+            deterministic: self.sources.deterministic,
+            read_only: self.read_only,
+            division_by_zero: self.division_by_zero,
+            cast_mode: self.cast_mode,
+            max_identifier_length: self.max_identifier_length,
+            identifier_mode: self.identifier_mode,
+            retry_on_conflict: self.conflict_retry,
+        }
+    }
+
+    // Exchanges the session settings of the backend with those in `config`, the other
+    // settings of both are left alone
+    pub(super) fn swap_session_config(&mut self, config: &mut BackendConfig) {
+        std::mem::swap(&mut self.limits, &mut config.limits);
+        std::mem::swap(&mut self.work_mem, &mut config.work_mem);
+        #[cfg(feature = "parallel")]
+        std::mem::swap(&mut self.parallel_scans, &mut config.parallel_scans);
+        std::mem::swap(&mut self.timing.collect, &mut config.collect_timing);
+        std::mem::swap(&mut self.reorder_joins, &mut config.reorder_joins);
+        std::mem::swap(&mut self.hash_semi_joins, &mut config.hash_semi_joins);
+        std::mem::swap(
+            &mut self.allow_unknown_settings,
+            &mut config.allow_unknown_settings,
+        );
+        std::mem::swap(&mut self.sources.deterministic, &mut config.deterministic);
+        std::mem::swap(&mut self.read_only, &mut config.read_only);
+        std::mem::swap(&mut self.division_by_zero, &mut config.division_by_zero);
+        std::mem::swap(&mut self.cast_mode, &mut config.cast_mode);
+        std::mem::swap(&mut self.identifier_mode, &mut config.identifier_mode);
+    }
+
+    pub(super) fn set_option(&mut self, statement: &SetStatement) -> Result<(), BackendError> {
+        // Postgres only warns that SET LOCAL has no effect outside of a transaction
+        if statement.local && !self.variables.in_transaction() {
+            return Ok(());
+        }
+        let name = statement.name.to_ascii_lowercase();
+        self.change_setting(&name, statement.local, |backend| match statement.value {
+            Token::Default => backend.reset_setting(&name, statement.name_loc),
+            _ => backend.assign_setting(&name, statement),
+        })
+    }
+
+    pub(super) fn reset_option(&mut self, statement: &ResetStatement) -> Result<(), BackendError> {
+        let names = match &statement.name {
+            Some(name) => vec![name.to_ascii_lowercase()],
+            None => {
+                let mut names: Vec<String> = SESSION_OPTIONS
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect();
+                names.extend(self.variables.names());
+                names
+            }
+        };
+        for name in names {
+            self.change_setting(&name, false, |backend| {
+                backend.reset_setting(&name, statement.name_loc)
+            })?;
+        }
+        Ok(())
+    }
+
+    // Current value of a setting as SHOW gives it, None for unknown ones
+    pub(super) fn setting(&self, name: &str) -> Option<String> {
+        config_setting(&self.config(), name).or_else(|| self.variables.get(name))
+    }
+
+    // Restores a setting to a value it had before, see `SessionVariables::end`
+    pub(super) fn restore_setting(&mut self, name: &str, value: Option<String>) {
+        match value {
+            Some(value) if SESSION_OPTIONS.contains(&name) => {
+                // Shown by the backend itself, so it is valid
+                let _ = self.assign_setting(name, &shown_value(name, value, Default::default()));
+            }
+            value => self.variables.restore(name, value),
+        }
+    }
+
+    // Runs `change` on a setting, keeping what it was before so that the transaction can
+    // undo it
+    fn change_setting(
+        &mut self,
+        name: &str,
+        local: bool,
+        change: impl FnOnce(&mut MemoryBackend) -> Result<(), BackendError>,
+    ) -> Result<(), BackendError> {
+        let previous = self.setting(name);
+        change(self)?;
+        let value = self.setting(name);
+        self.variables.changed(name, previous, value, local);
+        Ok(())
+    }
+
+    fn reset_setting(&mut self, name: &str, loc: TokenLocation) -> Result<(), BackendError> {
+        match config_setting(self.variables.reset_config(), name) {
+            Some(value) => self.assign_setting(name, &shown_value(name, value, loc)),
+            None => self.reset_variable(name, loc),
+        }
+    }
+
+    fn assign_setting(&mut self, name: &str, statement: &SetStatement) -> Result<(), BackendError> {
+        match name {
+            "max_result_rows" => self.limits.max_result_rows = limit_value(statement)?,
+            "max_result_bytes" => self.limits.max_result_bytes = limit_value(statement)?,
+            "work_mem" => self.work_mem = memory_value(statement)?,
+            #[cfg(feature = "parallel")]
+            "parallel_scans" => self.parallel_scans = bool_value(statement)?,
+            "collect_timing" => self.timing.collect = bool_value(statement)?,
+            "reorder_joins" => self.reorder_joins = bool_value(statement)?,
+            "hash_semi_joins" => self.hash_semi_joins = bool_value(statement)?,
+            "allow_unknown_settings" => self.allow_unknown_settings = bool_value(statement)?,
+            "postgrustql.deterministic" => self.sources.deterministic = bool_value(statement)?,
+            "postgrustql.division_by_zero" => {
+                self.division_by_zero = division_by_zero_value(statement)?
+            }
+            "postgrustql.cast_mode" => self.cast_mode = cast_mode_value(statement)?,
+            "postgrustql.identifier_mode" => {
+                self.identifier_mode = identifier_mode_value(statement)?
+            }
+            "statement_cache_capacity" | "transaction_read_only" | "max_identifier_length" => {
+                return Err(BackendError::CantChangeRuntimeParam(located(
+                    statement.name_loc,
+                    format!("Parameter \"{}\" cannot be changed now.", statement.name),
+                )))
+            }
+            _ => return self.set_variable(name, statement),
+        }
+        Ok(())
+    }
+
+    // The value of a setting as a single row, in a column named after it
+    pub(super) fn show_option(
+        &self,
+        statement: &ShowStatement,
+    ) -> Result<QueryResults<SqlValue>, BackendError> {
+        let name = statement.name.to_ascii_lowercase();
+        let value = match self.setting(&name) {
+            Some(value) => value,
+            None => return Err(unknown_option(&statement.name, statement.name_loc)),
+        };
+        let column = match SessionVariables::display_name(&name) {
+            Some(column) => column.to_string(),
+            None => statement.name.clone(),
+        };
+        Ok(QueryResults {
+            columns: vec![ResultColumn {
+                col_type: SqlType::Text,
+                name: column,
+                type_modifier: None,
+                nullable: true,
+                origin: None,
+            }],
+            rows: vec![vec![SqlValue::Text(SqlText::Text { value })]],
+        })
+    }
+}
+
+// Settings in `BackendConfig` that SET changes
+const SESSION_OPTIONS: &[&str] = &[
+    "max_result_rows",
+    "max_result_bytes",
+    "work_mem",
+    #[cfg(feature = "parallel")]
+    "parallel_scans",
+    "collect_timing",
+    "reorder_joins",
+    "hash_semi_joins",
+    "allow_unknown_settings",
+    "postgrustql.deterministic",
+    "postgrustql.division_by_zero",
+    "postgrustql.cast_mode",
+    "postgrustql.identifier_mode",
+];
+
+fn config_setting(config: &BackendConfig, name: &str) -> Option<String> {
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+    let value = match name {
+        "max_result_rows" => config.limits.max_result_rows.unwrap_or(0).to_string(),
+        "max_result_bytes" => config.limits.max_result_bytes.unwrap_or(0).to_string(),
+        "work_mem" => match config.work_mem {
+            Some(kilobytes) => format!("{}kB", kilobytes),
+            None => "0".to_string(),
+        },
+        #[cfg(feature = "parallel")]
+        "parallel_scans" => on_off(config.parallel_scans),
+        "collect_timing" => on_off(config.collect_timing),
+        "reorder_joins" => on_off(config.reorder_joins),
+        "hash_semi_joins" => on_off(config.hash_semi_joins),
+        "allow_unknown_settings" => on_off(config.allow_unknown_settings),
+        "postgrustql.deterministic" => on_off(config.deterministic),
+        "postgrustql.division_by_zero" => config.division_by_zero.name().to_string(),
+        "postgrustql.cast_mode" => config.cast_mode.name().to_string(),
+        "postgrustql.identifier_mode" => config.identifier_mode.name().to_string(),
+        "max_identifier_length" => config.max_identifier_length.to_string(),
+        "statement_cache_capacity" => config.statement_cache_capacity.to_string(),
+        "transaction_read_only" => on_off(config.read_only),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// A SET of the value SHOW gave for a setting
+fn shown_value(name: &str, value: String, loc: TokenLocation) -> SetStatement {
+    SetStatement {
+        name: name.to_string(),
+        value: Token::StringValue {
+            value: value.into(),
+        },
+        local: false,
+        name_loc: loc,
+        value_loc: loc,
+    }
+}
+
+// Prefixes `msg` with where in the query it is about, like parsing errors are
+pub(super) fn located(loc: TokenLocation, msg: String) -> String {
+    format!("[{}, {}]: {}", loc.line + 1, loc.col + 1, msg)
+}
+
+pub(super) fn unknown_option(name: &str, loc: TokenLocation) -> BackendError {
+    BackendError::UndefinedObject(located(
+        loc,
+        format!("Unrecognized configuration parameter \"{}\".", name),
+    ))
+}
+
+pub(super) fn invalid_value(statement: &SetStatement) -> BackendError {
+    let value = match &statement.value {
+        Token::IdentifierValue { value } => value.to_string(),
+        token => token.generate_code(),
+    };
+    BackendError::InvalidParameterValue(located(
+        statement.value_loc,
+        format!(
+            "Invalid value for parameter \"{}\": {}.",
+            statement.name, value
+        ),
+    ))
+}
+
+// A row or byte count, 0 standing for no limit at all
+fn limit_value(statement: &SetStatement) -> Result<Option<usize>, BackendError> {
+    let limit = match &statement.value {
+        Token::NumericValue { value } | Token::StringValue { value } => value.parse::<usize>(),
+        _ => return Err(invalid_value(statement)),
+    };
+    match limit {
+        Ok(0) => Ok(None),
+        Ok(limit) => Ok(Some(limit)),
+        Err(_) => Err(invalid_value(statement)),
+    }
+}
+
+// An amount of memory in kB, or in the unit following the number. 0 stands for no limit.
+fn memory_value(statement: &SetStatement) -> Result<Option<usize>, BackendError> {
+    let value = match &statement.value {
+        Token::NumericValue { value } | Token::StringValue { value } => value.trim(),
+        _ => return Err(invalid_value(statement)),
+    };
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let kilobytes_per_unit: usize = match value[digits..].trim_start() {
+        "" | "kB" => 1,
+        "MB" => 1024,
+        "GB" => 1024 * 1024,
+        "TB" => 1024 * 1024 * 1024,
+        _ => return Err(invalid_value(statement)),
+    };
+    let kilobytes = value[..digits]
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(kilobytes_per_unit));
+    match kilobytes {
+        Some(0) => Ok(None),
+        Some(kilobytes) => Ok(Some(kilobytes)),
+        None => Err(invalid_value(statement)),
+    }
+}
+
+fn bool_value(statement: &SetStatement) -> Result<bool, BackendError> {
+    match &statement.value {
+        Token::BoolValue { value } => Ok(*value),
+        Token::On => Ok(true),
+        Token::IdentifierValue { value } if value == "off" => Ok(false),
+        Token::StringValue { value } => match value.to_ascii_lowercase().as_str() {
+            "on" | "true" => Ok(true),
+            "off" | "false" => Ok(false),
+            _ => Err(invalid_value(statement)),
+        },
+        _ => Err(invalid_value(statement)),
+    }
+}
+
+fn division_by_zero_value(statement: &SetStatement) -> Result<DivisionByZero, BackendError> {
+    let mode = match &statement.value {
+        Token::Null => Some(DivisionByZero::Null),
+        Token::StringValue { value } | Token::IdentifierValue { value } => {
+            DivisionByZero::from_name(value)
+        }
+        _ => None,
+    };
+    mode.ok_or_else(|| invalid_value(statement))
+}
+
+fn cast_mode_value(statement: &SetStatement) -> Result<CastMode, BackendError> {
+    let mode = match &statement.value {
+        Token::StringValue { value } | Token::IdentifierValue { value } => {
+            CastMode::from_name(value)
+        }
+        _ => None,
+    };
+    mode.ok_or_else(|| invalid_value(statement))
+}
+
+fn identifier_mode_value(statement: &SetStatement) -> Result<IdentifierMode, BackendError> {
+    let mode = match &statement.value {
+        Token::StringValue { value } | Token::IdentifierValue { value } => {
+            IdentifierMode::from_name(value)
+        }
+        _ => None,
+    };
+    mode.ok_or_else(|| invalid_value(statement))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{backend_with, shared};
+    use crate::backend_memory::Connection;
+
+    const SETUP: &str = "CREATE TABLE t (id INT, name TEXT);
+        INSERT INTO t VALUES (0, 'name'), (1, 'name'), (2, 'name'), (3, 'name'), (4, 'name');";
+
+    fn show(mb: &mut MemoryBackend, name: &str) -> String {
+        match mb.eval_query(&format!("SHOW {};", name)).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.columns[0].name, name);
+                results.rows[0][0].to_string()
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_default_config() {
+        let mut mb = MemoryBackend::new();
+        assert_eq!(mb.config(), BackendConfig::default());
+        assert_eq!(show(&mut mb, "max_result_rows"), "0");
+        assert_eq!(show(&mut mb, "max_result_bytes"), "0");
+        assert_eq!(show(&mut mb, "statement_cache_capacity"), "256");
+    }
+
+    #[test]
+    fn test_max_result_rows() {
+        let mut mb = backend_with(BackendConfig::new().max_result_rows(Some(3)), SETUP);
+        assert_eq!(show(&mut mb, "max_result_rows"), "3");
+        assert!(mb.eval_query("SELECT * FROM t;").is_err());
+
+        mb.eval_query("SET max_result_rows = 5;").unwrap();
+        assert!(mb.eval_query("SELECT * FROM t;").is_ok());
+        mb.eval_query("SET max_result_rows TO 4;").unwrap();
+        assert_eq!(mb.result_limits().max_result_rows, Some(4));
+        assert!(mb.eval_query("SELECT * FROM t;").is_err());
+        mb.eval_query("SET max_result_rows = 0;").unwrap();
+        assert_eq!(mb.result_limits().max_result_rows, None);
+    }
+
+    #[test]
+    fn test_max_result_bytes() {
+        let mut mb = backend_with(BackendConfig::new().max_result_bytes(Some(10)), SETUP);
+        assert!(mb.eval_query("SELECT id FROM t;").is_err());
+
+        mb.eval_query("SET max_result_bytes = '1000';").unwrap();
+        assert_eq!(show(&mut mb, "max_result_bytes"), "1000");
+        assert!(mb.eval_query("SELECT id FROM t;").is_ok());
+    }
+
+    #[test]
+    fn test_work_mem() {
+        let mut mb = backend_with(BackendConfig::new().work_mem(Some(64)), SETUP);
+        assert_eq!(show(&mut mb, "work_mem"), "64kB");
+
+        mb.eval_query("SET work_mem = '4MB';").unwrap();
+        assert_eq!(mb.config().work_mem, Some(4096));
+        mb.eval_query("SET work_mem TO 100;").unwrap();
+        assert_eq!(show(&mut mb, "work_mem"), "100kB");
+        mb.eval_query("SET work_mem = '1 GB';").unwrap();
+        assert_eq!(mb.config().work_mem, Some(1024 * 1024));
+        mb.eval_query("RESET work_mem;").unwrap();
+        assert_eq!(mb.config().work_mem, Some(64));
+        mb.eval_query("SET work_mem = 0;").unwrap();
+        assert_eq!(show(&mut mb, "work_mem"), "0");
+
+        for value in ["'4 mb'", "'MB'", "'-1kB'", "on"] {
+            let err = mb
+                .eval_query(&format!("SET work_mem = {};", value))
+                .unwrap_err();
+            assert_eq!(err.code(), "22023", "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_statement_cache_capacity() {
+        let mut mb = backend_with(BackendConfig::new().statement_cache_capacity(0), SETUP);
+        assert_eq!(show(&mut mb, "statement_cache_capacity"), "0");
+        mb.eval_query("SELECT id FROM t WHERE id = 1;").unwrap();
+        mb.eval_query("SELECT id FROM t WHERE id = 2;").unwrap();
+        assert_eq!(mb.statement_cache_stats().hits, 0);
+
+        let mut mb = backend_with(BackendConfig::new().statement_cache_capacity(10), SETUP);
+        let before = mb.statement_cache_stats().hits;
+        mb.eval_query("SELECT id FROM t WHERE id = 1;").unwrap();
+        mb.eval_query("SELECT id FROM t WHERE id = 2;").unwrap();
+        assert_eq!(mb.statement_cache_stats().hits, before + 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_scans() {
+        let rows = super::super::PARALLEL_SCAN_MIN_ROWS;
+        let mut mb = backend_with(BackendConfig::new().parallel_scans(false), SETUP);
+        assert!(!mb.scans_in_parallel("t", rows));
+        assert_eq!(show(&mut mb, "parallel_scans"), "off");
+
+        mb.eval_query("SET parallel_scans = on;").unwrap();
+        assert!(mb.scans_in_parallel("t", rows));
+        mb.eval_query("SET parallel_scans TO 'off';").unwrap();
+        assert!(!mb.scans_in_parallel("t", rows));
+        mb.eval_query("SET parallel_scans = true;").unwrap();
+        assert_eq!(show(&mut mb, "parallel_scans"), "on");
+    }
+
+    #[test]
+    fn test_set_is_per_connection() {
+        let backend = shared(SETUP);
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open_with_config(
+            backend.clone(),
+            BackendConfig::new().max_result_rows(Some(2)),
+        );
+
+        assert!(first.eval_query("SELECT * FROM t;").is_ok());
+        assert!(second.eval_query("SELECT * FROM t;").is_err());
+        first.eval_query("SET max_result_rows = 1;").unwrap();
+        second.eval_query("SET max_result_rows = 10;").unwrap();
+        assert!(first.eval_query("SELECT * FROM t;").is_err());
+        assert!(second.eval_query("SELECT * FROM t;").is_ok());
+        assert_eq!(
+            backend.lock().unwrap().result_limits().max_result_rows,
+            None
+        );
+    }
+
+    #[test]
+    fn test_option_errors() {
+        let tests = vec![
+            (
+                "SET nope = 1;",
+                "42704",
+                "[1, 5]: Unrecognized configuration parameter \"nope\".",
+            ),
+            (
+                "SELECT 1;\nSHOW nope;",
+                "42704",
+                "Statement 2 (line 2, column 1 to line 2, column 9) failed: [2, 6]: \
+                Unrecognized configuration parameter \"nope\".\nStatement: SHOW nope",
+            ),
+            (
+                "SET max_result_rows = 'lots';",
+                "22023",
+                "[1, 23]: Invalid value for parameter \"max_result_rows\": 'lots'.",
+            ),
+            (
+                "SET max_result_bytes TO off;",
+                "22023",
+                "[1, 25]: Invalid value for parameter \"max_result_bytes\": off.",
+            ),
+            (
+                "SET max_result_rows = 1.5;",
+                "22023",
+                "[1, 23]: Invalid value for parameter \"max_result_rows\": 1.5.",
+            ),
+            (
+                "SET statement_cache_capacity = 1;",
+                "55P02",
+                "[1, 5]: Parameter \"statement_cache_capacity\" cannot be changed now.",
+            ),
+        ];
+
+        let mut mb = MemoryBackend::new();
+        for (query, code, msg) in tests {
+            let err = mb.eval_query(query).unwrap_err();
+            assert_eq!((err.code(), err.message()), (code, msg), "{}", query);
+        }
+        assert_eq!(mb.config(), BackendConfig::default());
+    }
+}
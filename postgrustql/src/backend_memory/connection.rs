@@ -0,0 +1,378 @@
+use super::cursor::Cursors;
+use super::resolver::Resolver;
+use super::retry::SessionRetry;
+use super::sequence::SequenceSession;
+use super::variables::SessionVariables;
+use super::{
+    AuthDecision, Authorizer, BackendConfig, ConflictRetry, Cursor, MemoryBackend, MemoryStorage,
+    Notification, StatementDescription, StatementInfo,
+};
+use crate::backend::{BackendError, EvalResult, TransactionStatus};
+use crate::sql_types::SqlValue;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// A session on a backend that other connections may share. Temporary tables created
+// through it are seen by it alone, shadow permanent tables of the same name and are
+// dropped along with it. So are the session settings and variables SET changes, the
+// channels it LISTENs to, the cursors it declares and what currval gives.
+pub struct Connection {
+    backend: Arc<Mutex<MemoryBackend>>,
+    temp_tables: MemoryStorage,
+    resolver: Resolver,
+    // Only the session settings are used, see `BackendConfig`
+    config: BackendConfig,
+    variables: SessionVariables,
+    cursors: Cursors,
+    sequences: SequenceSession,
+    authorizer: Option<Arc<Authorizer>>,
+    // Tells the notifications for this connection apart from those for others
+    session: u64,
+}
+
+impl Connection {
+    // The session starts out with the settings the backend has at the time
+    pub fn open(backend: Arc<Mutex<MemoryBackend>>) -> Self {
+        let config = match backend.lock() {
+            Ok(backend) => backend.config(),
+            Err(poisoned) => poisoned.into_inner().config(),
+        };
+        Connection::open_with_config(backend, config)
+    }
+
+    // A session that can read the tables of the backend but not change them, see
+    // `BackendConfig::read_only`
+    pub fn open_read_only(backend: Arc<Mutex<MemoryBackend>>) -> Self {
+        let config = match backend.lock() {
+            Ok(backend) => backend.config(),
+            Err(poisoned) => poisoned.into_inner().config(),
+        };
+        Connection::open_with_config(backend, config.read_only(true))
+    }
+
+    pub fn open_with_config(backend: Arc<Mutex<MemoryBackend>>, config: BackendConfig) -> Self {
+        let session = match backend.lock() {
+            Ok(mut backend) => backend.notifications.open_session(),
+            Err(poisoned) => poisoned.into_inner().notifications.open_session(),
+        };
+        Connection {
+            backend,
+            temp_tables: MemoryStorage::default(),
+            resolver: Resolver::default(),
+            variables: SessionVariables::new(config.clone()),
+            config,
+            cursors: Cursors::default(),
+            sequences: SequenceSession::default(),
+            authorizer: None,
+            session,
+        }
+    }
+
+    pub fn backend(&self) -> &Arc<Mutex<MemoryBackend>> {
+        &self.backend
+    }
+
+    // What `Notification::sender` holds for notifications sent through this connection
+    pub fn session_id(&self) -> u64 {
+        self.session
+    }
+
+    // Notifications on the channels this connection listens to, oldest first. Those of a
+    // transaction only arrive once it commits.
+    pub fn poll_notifications(&mut self) -> Vec<Notification> {
+        match self.backend.lock() {
+            Ok(mut backend) => backend.notifications.take(self.session),
+            Err(_) => vec![],
+        }
+    }
+
+    // With retries on, see `MemoryBackend::set_retry_on_conflict`, the waits between the
+    // runs of a write that conflicted leave the backend unlocked. So do those for the
+    // transaction of another connection, which the query is run again after as well.
+    pub fn eval_query(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, BackendError> {
+        let authorizer = self.authorizer.clone();
+        let mut eval_results = vec![];
+        let mut retrying = SessionRetry::default();
+        loop {
+            let result = self.with_session(|backend| {
+                backend.session_retry = Some(retrying);
+                let result = match &authorizer {
+                    Some(authorizer) => {
+                        backend.eval_authorized(query, &**authorizer, &mut eval_results)
+                    }
+                    None => backend.eval_query_on(query, &mut eval_results),
+                };
+                retrying = backend.session_retry.unwrap_or_default();
+                result
+            });
+            let delay = match result {
+                Ok(()) => return Ok(eval_results),
+                Err(err) => match (retrying.delay.take(), &err) {
+                    (Some(delay), _) => delay,
+                    (None, BackendError::LockNotAvailable(_)) => match self.conflict_retry() {
+                        Some(retry) if retrying.failed + 1 < retry.max_attempts => {
+                            retrying.failed += 1;
+                            retry.delay(retrying.failed)
+                        }
+                        _ => return Err(err),
+                    },
+                    (None, _) => return Err(err),
+                },
+            };
+            std::thread::sleep(delay);
+        }
+    }
+
+    // Runs `query`, a single SELECT, to hand out its rows a few at a time. The authorizer
+    // is asked about it as about `DECLARE ... CURSOR FOR query`.
+    pub fn open_cursor(&mut self, query: &str) -> Result<Cursor<'_>, BackendError> {
+        let authorizer = self.authorizer.clone();
+        let name = self
+            .with_session(|backend| backend.declare_unnamed_cursor(query, authorizer.as_deref()))?;
+        Ok(Cursor::new(self, name))
+    }
+
+    // The parameter types and result columns of `query`, which is not run. Temporary
+    // tables of this connection are seen like by `eval_query`.
+    pub fn describe(&mut self, query: &str) -> Result<StatementDescription, BackendError> {
+        self.with_session(|backend| backend.describe(query))
+    }
+
+    // Has every statement `eval_query` runs on this connection checked by `authorizer`
+    // once it is parsed, see `AuthDecision`. `with_session` is left unchecked.
+    pub fn set_authorizer(
+        &mut self,
+        authorizer: impl Fn(&StatementInfo) -> AuthDecision + Send + Sync + 'static,
+    ) {
+        self.authorizer = Some(Arc::new(authorizer));
+    }
+
+    pub fn clear_authorizer(&mut self) {
+        self.authorizer = None;
+    }
+
+    // Whether a transaction BEGIN started on this connection is running, and whether it
+    // failed, as the ReadyForQuery message of the protocol reports it
+    pub fn transaction_status(&self) -> TransactionStatus {
+        match self.backend.lock() {
+            Ok(backend) if backend.block_session == Some(self.session) => {
+                backend.transaction_status()
+            }
+            _ => TransactionStatus::Idle,
+        }
+    }
+
+    // Runs `f` on the backend with the temporary tables and session settings of this
+    // connection in place of those of whoever used it last. A transaction BEGIN started
+    // lasts over the calls until COMMIT or ROLLBACK ends it, as it would take in what the
+    // other connections do. They wait for it up to their lock_timeout, and without one
+    // fail right away rather than wait for a COMMIT that may never come. Conflicts of the
+    // writes `f` runs aren't retried, the wait would keep the others waiting too.
+    pub fn with_session<T>(
+        &mut self,
+        f: impl FnOnce(&mut MemoryBackend) -> Result<T, BackendError>,
+    ) -> Result<T, BackendError> {
+        let session = self.session;
+        let mut backend = lock_backend(&self.backend, session, self.lock_timeout())?;
+        std::mem::swap(&mut backend.temp_tables, &mut self.temp_tables);
+        std::mem::swap(&mut backend.resolver, &mut self.resolver);
+        backend.swap_session_config(&mut self.config);
+        std::mem::swap(&mut backend.variables, &mut self.variables);
+        std::mem::swap(&mut backend.cursors, &mut self.cursors);
+        backend.notifications.swap_session(&mut self.session);
+        backend.sequences.swap_session(&mut self.sequences);
+        backend.session_retry = Some(SessionRetry::default());
+        let result = f(&mut backend);
+        backend.session_retry = None;
+        let running = backend.transaction_status() != TransactionStatus::Idle;
+        if backend.block_session.is_some() && !running {
+            backend.block_ended.notify_all();
+        }
+        backend.block_session = if running { Some(session) } else { None };
+        backend.sequences.swap_session(&mut self.sequences);
+        backend.notifications.swap_session(&mut self.session);
+        std::mem::swap(&mut backend.cursors, &mut self.cursors);
+        std::mem::swap(&mut backend.variables, &mut self.variables);
+        backend.swap_session_config(&mut self.config);
+        std::mem::swap(&mut backend.resolver, &mut self.resolver);
+        std::mem::swap(&mut backend.temp_tables, &mut self.temp_tables);
+        result
+    }
+
+    fn conflict_retry(&self) -> Option<ConflictRetry> {
+        self.backend.lock().ok()?.conflict_retry
+    }
+
+    // How long to wait for the transaction of another connection, see lock_timeout. Not
+    // at all without it.
+    fn lock_timeout(&self) -> Option<Duration> {
+        match self.variables.value("lock_timeout")?.parse() {
+            Ok(0) | Err(_) => None,
+            Ok(milliseconds) => Some(Duration::from_millis(milliseconds)),
+        }
+    }
+}
+
+// The backend once no other connection than `session` is in the middle of a transaction
+fn lock_backend(
+    backend: &Mutex<MemoryBackend>,
+    session: u64,
+    timeout: Option<Duration>,
+) -> Result<MutexGuard<'_, MemoryBackend>, BackendError> {
+    // A panic in another session may have left its temporary tables behind
+    let unusable = || BackendError::Internal("Backend is unusable after a panic.".to_string());
+    let mut backend = backend.lock().map_err(|_| unusable())?;
+    let start = Instant::now();
+    while matches!(backend.block_session, Some(other) if other != session) {
+        let block_ended = backend.block_ended.clone();
+        backend = match timeout.and_then(|timeout| timeout.checked_sub(start.elapsed())) {
+            Some(left) => {
+                block_ended
+                    .wait_timeout(backend, left)
+                    .map_err(|_| unusable())?
+                    .0
+            }
+            None if timeout.is_some() => {
+                return Err(BackendError::LockNotAvailable(
+                    "Canceling statement due to lock timeout, another connection is in a \
+                    transaction."
+                        .to_string(),
+                ))
+            }
+            None => {
+                return Err(BackendError::LockNotAvailable(
+                    "Could not obtain lock, another connection is in a transaction.".to_string(),
+                ))
+            }
+        };
+    }
+    Ok(backend)
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // A transaction left running is rolled back, like when a client disconnects
+        if self.transaction_status() != TransactionStatus::Idle {
+            let _ = self.with_session(|backend| Ok(backend.abandon_transaction_block()));
+        }
+        if let Ok(mut backend) = self.backend.lock() {
+            backend.notifications.close_session(self.session);
+        }
+    }
+}
+
+#[cfg(test)]
+mod connection_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{shared, try_rows};
+
+    const SETUP: &str = "CREATE TABLE people (id INT, name TEXT);
+        INSERT INTO people VALUES (1, 'Baam');
+        INSERT INTO people VALUES (2, 'Rachel');";
+
+    #[test]
+    fn test_temp_tables_are_per_connection() {
+        let backend = shared(SETUP);
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+
+        first
+            .eval_query(
+                "CREATE TEMP TABLE notes (name TEXT);
+                INSERT INTO notes VALUES ('first');",
+            )
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut first, "SELECT name FROM notes;").unwrap(),
+            ["first"]
+        );
+        assert!(try_rows(&mut second, "SELECT name FROM notes;").is_err());
+
+        // The same name is free in every other connection
+        second
+            .eval_query(
+                "CREATE TEMPORARY TABLE notes (name TEXT);
+                INSERT INTO notes VALUES ('second');",
+            )
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut first, "SELECT name FROM notes;").unwrap(),
+            ["first"]
+        );
+        assert_eq!(
+            try_rows(&mut second, "SELECT name FROM notes;").unwrap(),
+            ["second"]
+        );
+        assert!(first
+            .eval_query("CREATE TEMP TABLE notes (name TEXT);")
+            .is_err());
+
+        second
+            .eval_query("SELECT name INTO TEMP TABLE found FROM people WHERE id = 2;")
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut second, "SELECT name FROM found;").unwrap(),
+            ["Rachel"]
+        );
+        assert!(try_rows(&mut first, "SELECT name FROM found;").is_err());
+
+        // Permanent tables are shared
+        first
+            .eval_query("SELECT name INTO kept FROM people WHERE id = 1;")
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut second, "SELECT name FROM kept;").unwrap(),
+            ["Baam"]
+        );
+
+        drop(first);
+        drop(second);
+        let mut backend = backend.lock().unwrap();
+        assert!(backend.table("notes").is_none());
+        assert!(backend.table("found").is_none());
+        assert!(backend.table("kept").is_some());
+        assert!(backend.eval_query("SELECT name FROM notes;").is_err());
+    }
+
+    #[test]
+    fn test_temp_tables_shadow_permanent_ones() {
+        let backend = shared(SETUP);
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend);
+
+        first
+            .eval_query(
+                "CREATE TEMP TABLE people (id INT, name TEXT);
+                INSERT INTO people VALUES (3, 'Khun');",
+            )
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut first, "SELECT name FROM people;").unwrap(),
+            ["Khun"]
+        );
+        assert_eq!(
+            try_rows(&mut second, "SELECT name FROM people;").unwrap(),
+            ["Baam", "Rachel"]
+        );
+
+        // Writes go to the temporary table as well
+        first
+            .eval_query("INSERT INTO people VALUES (4, 'Rak');")
+            .unwrap();
+        assert_eq!(
+            try_rows(&mut first, "SELECT name FROM people;").unwrap(),
+            ["Khun", "Rak"]
+        );
+        assert_eq!(
+            try_rows(&mut second, "SELECT name FROM people;").unwrap(),
+            ["Baam", "Rachel"]
+        );
+
+        // Dropping the temporary table uncovers the permanent one
+        first.eval_query("DROP TABLE people;").unwrap();
+        assert_eq!(
+            try_rows(&mut first, "SELECT name FROM people;").unwrap(),
+            ["Baam", "Rachel"]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use super::cache::select_tables;
+use super::{Authorizer, Connection, MemoryBackend};
+use crate::ast::{CloseStatement, DeclareCursorStatement, FetchStatement, Statement};
+use crate::backend::{BackendError, QueryResults, ResultColumns};
+use crate::sql_types::SqlValue;
+use std::collections::{HashMap, VecDeque};
+
+// Rows of a query handed out a few at a time by FETCH. The executor gives every row of a
+// query at once, so they are kept from DECLARE on and FETCH takes the next ones rather
+// than running the query again.
+#[derive(Debug, Clone, PartialEq)]
+struct OpenCursor {
+    columns: ResultColumns,
+    rows: VecDeque<Vec<SqlValue>>,
+    // Tables the query read, with their columns at the time
+    tables: Vec<(String, Vec<String>)>,
+}
+
+// Cursors of the session using the backend, by name. Those declared in a transaction
+// that fails are closed along with it. DECLARE only runs within a transaction, those
+// of `Connection::open_cursor` are closed by their `Cursor` instead.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct Cursors {
+    open: HashMap<String, OpenCursor>,
+    // Names declared by each running transaction, the innermost last
+    declared: Vec<Vec<String>>,
+    // Last number in the name of a cursor opened by `Connection::open_cursor`
+    last_unnamed: u64,
+}
+
+impl Cursors {
+    pub(super) fn begin(&mut self) {
+        self.declared.push(vec![]);
+    }
+
+    pub(super) fn end(&mut self, succeeded: bool) {
+        let declared = self.declared.pop().unwrap_or_default();
+        if !succeeded {
+            for name in declared {
+                self.open.remove(&name);
+            }
+        } else if let Some(outer) = self.declared.last_mut() {
+            outer.extend(declared);
+        }
+    }
+
+    // A name no cursor declared by SQL can have
+    fn unnamed(&mut self) -> String {
+        self.last_unnamed += 1;
+        format!("{}{}>", UNNAMED_PREFIX, self.last_unnamed)
+    }
+}
+
+const UNNAMED_PREFIX: &str = "<unnamed cursor ";
+
+fn undefined_cursor(name: &str) -> BackendError {
+    BackendError::InvalidCursorName(format!("Cursor \"{}\" does not exist.", name))
+}
+
+impl MemoryBackend {
+    pub(super) fn declare_cursor(
+        &mut self,
+        statement: DeclareCursorStatement,
+    ) -> Result<(), BackendError> {
+        // Nothing would close it, as Postgres does for cursors without WITH HOLD
+        if self.transaction_depth == 0 && !statement.name.starts_with(UNNAMED_PREFIX) {
+            return Err(BackendError::NoActiveSqlTransaction(
+                "DECLARE CURSOR can only be used in transaction blocks.".to_string(),
+            ));
+        }
+        if self.cursors.open.contains_key(&statement.name) {
+            return Err(BackendError::DuplicateCursor(format!(
+                "Cursor \"{}\" already exists.",
+                statement.name
+            )));
+        }
+
+        let mut table_names = vec![];
+        select_tables(&statement.query, &mut table_names);
+        let mut tables: Vec<(String, Vec<String>)> = vec![];
+        for table_name in table_names {
+            if tables.iter().any(|(known, _)| *known == table_name) {
+                continue;
+            }
+            if let Some(table) = self.table(&table_name) {
+                let columns = table.columns.clone();
+                tables.push((table_name, columns));
+            }
+        }
+
+        let results = self.select(statement.query)?;
+        if let Some(declared) = self.cursors.declared.last_mut() {
+            declared.push(statement.name.clone());
+        }
+        self.cursors.open.insert(
+            statement.name,
+            OpenCursor {
+                columns: results.columns,
+                rows: results.rows.into(),
+                tables,
+            },
+        );
+        Ok(())
+    }
+
+    // The next `count` rows of the cursor, every one left when it is None
+    pub(super) fn fetch(
+        &mut self,
+        statement: FetchStatement,
+    ) -> Result<QueryResults<SqlValue>, BackendError> {
+        let cursor = match self.cursors.open.get(&statement.name) {
+            Some(cursor) => cursor,
+            None => return Err(undefined_cursor(&statement.name)),
+        };
+        // Its rows were read from tables that may no longer be there to match them
+        for (table_name, columns) in &cursor.tables {
+            let change = match self.table(table_name) {
+                None => "dropped",
+                Some(table) if table.columns != *columns => "changed",
+                Some(_) => continue,
+            };
+            return Err(BackendError::InvalidCursorState(format!(
+                "Cursor \"{}\" can no longer be read, table \"{}\" was {} since it was declared.",
+                statement.name, table_name, change
+            )));
+        }
+
+        let cursor = match self.cursors.open.get_mut(&statement.name) {
+            Some(cursor) => cursor,
+            None => return Err(undefined_cursor(&statement.name)),
+        };
+        let count = statement
+            .count
+            .unwrap_or(cursor.rows.len())
+            .min(cursor.rows.len());
+        Ok(QueryResults {
+            columns: cursor.columns.clone(),
+            rows: cursor.rows.drain(..count).collect(),
+        })
+    }
+
+    pub(super) fn close_cursor(&mut self, statement: CloseStatement) -> Result<(), BackendError> {
+        match statement.name {
+            Some(name) => match self.cursors.open.remove(&name) {
+                Some(_) => Ok(()),
+                None => Err(undefined_cursor(&name)),
+            },
+            None => {
+                self.cursors.open.clear();
+                Ok(())
+            }
+        }
+    }
+
+    // Declares a cursor of its own for `query`, which must be a single SELECT, and gives
+    // its name
+    pub(super) fn declare_unnamed_cursor(
+        &mut self,
+        query: &str,
+        authorizer: Option<&Authorizer>,
+    ) -> Result<String, BackendError> {
+        let select = match self.parse_cached(query)?.as_slice() {
+            [Statement::SelectStatement(select)] => select.clone(),
+            _ => {
+                return Err(BackendError::SyntaxError(
+                    "A cursor can only be opened for a single SELECT query.".to_string(),
+                ))
+            }
+        };
+        let name = self.cursors.unnamed();
+        let mut statement = Statement::DeclareCursorStatement(DeclareCursorStatement {
+            name: name.clone(),
+            query: select,
+        });
+        if let Some(authorizer) = authorizer {
+            self.authorize(&mut statement, authorizer)?;
+        }
+        self.eval_statement(statement)?;
+        Ok(name)
+    }
+}
+
+// A cursor opened by `Connection::open_cursor`, which closes it when dropped
+pub struct Cursor<'a> {
+    connection: &'a mut Connection,
+    name: String,
+}
+
+impl<'a> Cursor<'a> {
+    pub(super) fn new(connection: &'a mut Connection, name: String) -> Self {
+        Cursor { connection, name }
+    }
+
+    // The next `count` rows of the query, fewer once it runs out of them
+    pub fn fetch(&mut self, count: usize) -> Result<QueryResults<SqlValue>, BackendError> {
+        let statement = FetchStatement {
+            name: self.name.clone(),
+            count: Some(count),
+        };
+        self.connection
+            .with_session(|backend| backend.fetch(statement))
+    }
+
+    // Every row of the query left
+    pub fn fetch_all(&mut self) -> Result<QueryResults<SqlValue>, BackendError> {
+        let statement = FetchStatement {
+            name: self.name.clone(),
+            count: None,
+        };
+        self.connection
+            .with_session(|backend| backend.fetch(statement))
+    }
+}
+
+impl Drop for Cursor<'_> {
+    fn drop(&mut self) {
+        let statement = CloseStatement {
+            name: Some(self.name.clone()),
+        };
+        // It is already gone after CLOSE ALL
+        let _ = self
+            .connection
+            .with_session(|backend| backend.close_cursor(statement));
+    }
+}
+
+#[cfg(test)]
+mod cursor_tests {
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::{backend, joined, rows, shared};
+    use crate::backend_memory::Connection;
+
+    const SETUP: &str = "CREATE TABLE items (id INT, name TEXT);
+        INSERT INTO items VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e');";
+
+    #[test]
+    fn test_fetch_pages() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("BEGIN; DECLARE cur CURSOR FOR SELECT id, name FROM items ORDER BY id DESC;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "FETCH 2 FROM cur;"), vec!["5|e", "4|d"]);
+        assert_eq!(rows(&mut mb, "FETCH cur;"), vec!["3|c"]);
+        // Rows inserted after DECLARE are not part of the query it ran
+        mb.eval_query("INSERT INTO items VALUES (6, 'f');").unwrap();
+        assert_eq!(
+            rows(&mut mb, "FETCH FORWARD ALL IN cur;"),
+            vec!["2|b", "1|a"]
+        );
+        assert!(rows(&mut mb, "FETCH NEXT FROM cur;").is_empty());
+
+        mb.eval_query("CLOSE cur;").unwrap();
+        match mb.eval_query("FETCH cur;") {
+            Err(BackendError::InvalidCursorName(msg)) => {
+                assert_eq!(msg, "Cursor \"cur\" does not exist.")
+            }
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        mb.eval_query("COMMIT;").unwrap();
+    }
+
+    #[test]
+    fn test_cursor_errors() {
+        let mut mb = backend(SETUP);
+        // Outside of a transaction nothing would close it
+        match mb.eval_query("DECLARE cur CURSOR FOR SELECT id FROM items;") {
+            Err(BackendError::NoActiveSqlTransaction(msg)) => {
+                assert_eq!(
+                    msg,
+                    "DECLARE CURSOR can only be used in transaction blocks."
+                )
+            }
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        assert!(matches!(
+            mb.eval_query("FETCH cur;"),
+            Err(BackendError::InvalidCursorName(_))
+        ));
+
+        mb.transaction(|mb| {
+            mb.eval_query("DECLARE cur CURSOR FOR SELECT id FROM items;")?;
+            match mb.eval_query("DECLARE cur CURSOR FOR SELECT name FROM items;") {
+                Err(BackendError::DuplicateCursor(msg)) => {
+                    assert_eq!(msg, "Cursor \"cur\" already exists.")
+                }
+                result => panic!("Expected an error, got {:?}", result),
+            }
+            assert!(matches!(
+                mb.eval_query("CLOSE other;"),
+                Err(BackendError::InvalidCursorName(_))
+            ));
+
+            mb.eval_query("DROP TABLE items;")?;
+            match mb.eval_query("FETCH 1 FROM cur;") {
+                Err(BackendError::InvalidCursorState(msg)) => assert_eq!(
+                    msg,
+                    "Cursor \"cur\" can no longer be read, table \"items\" was dropped since it was declared."
+                ),
+                result => panic!("Expected an error, got {:?}", result),
+            }
+            mb.eval_query("CLOSE ALL;")?;
+            mb.eval_query(
+                "CREATE TABLE items (id INT); DECLARE cur CURSOR FOR SELECT id FROM items;",
+            )
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cursors_of_failed_transactions_are_closed() {
+        let mut mb = backend(SETUP);
+        let result = mb.transaction(|mb| {
+            mb.eval_query(
+                "DECLARE kept CURSOR FOR SELECT id FROM items;
+                FETCH 1 FROM kept;",
+            )?;
+            let nested = mb.transaction(|mb| {
+                mb.eval_query("DECLARE lost CURSOR FOR SELECT id FROM items; SELECT * FROM nope;")
+            });
+            assert!(nested.is_err());
+            Ok::<_, BackendError>(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(rows(&mut mb, "FETCH 1 FROM kept;"), vec!["2"]);
+        assert!(matches!(
+            mb.eval_query("FETCH lost;"),
+            Err(BackendError::InvalidCursorName(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_cursor() {
+        let backend = shared(SETUP);
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend);
+        second
+            .eval_query("BEGIN; DECLARE cur CURSOR FOR SELECT name FROM items; COMMIT;")
+            .unwrap();
+
+        {
+            let mut cursor = first
+                .open_cursor("SELECT id FROM items WHERE id > 1 ORDER BY id;")
+                .unwrap();
+            assert_eq!(joined(&cursor.fetch(3).unwrap()), vec!["2", "3", "4"]);
+            assert_eq!(joined(&cursor.fetch(3).unwrap()), vec!["5"]);
+            assert!(cursor.fetch(3).unwrap().rows.is_empty());
+        }
+        // Cursors belong to the connection that declared them
+        assert!(matches!(
+            first.eval_query("FETCH cur;"),
+            Err(BackendError::InvalidCursorName(_))
+        ));
+        assert_eq!(second.eval_query("FETCH ALL cur;").unwrap().len(), 1);
+
+        match first.open_cursor("SELECT 1; SELECT 2;") {
+            Err(BackendError::SyntaxError(msg)) => assert_eq!(
+                msg,
+                "A cursor can only be opened for a single SELECT query."
+            ),
+            Err(err) => panic!("Expected a syntax error, got {:?}", err),
+            Ok(_) => panic!("Expected an error"),
+        };
+    }
+}
@@ -0,0 +1,395 @@
+use super::nondeterminism::{civil_from_days, days_from_civil, Timestamp};
+use crate::backend::BackendError;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+// What date_trunc can cut a timestamp down to
+const TRUNCATION_UNITS: &[&str] = &["year", "month", "day", "hour", "minute"];
+
+// What to_char replaces in its pattern, anything else that isn't a letter is kept as it
+// is, as is text in double quotes
+const FORMAT_PATTERNS: &[&str] = &["YYYY", "HH24", "MM", "DD", "MI", "SS"];
+
+// The fields of a timestamp in UTC, the way it is read from and written as text
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct DateTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    micros: i64,
+}
+
+impl DateTime {
+    fn from_timestamp(timestamp: Timestamp) -> Self {
+        let micros = timestamp.unix_micros();
+        let (year, month, day) = civil_from_days(micros.div_euclid(MICROS_PER_DAY));
+        let micros_of_day = micros.rem_euclid(MICROS_PER_DAY);
+        let secs_of_day = micros_of_day / MICROS_PER_SECOND;
+        DateTime {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day % 3600 / 60,
+            second: secs_of_day % 60,
+            micros: micros_of_day % MICROS_PER_SECOND,
+        }
+    }
+
+    fn to_timestamp(self) -> Timestamp {
+        let secs = days_from_civil(self.year, self.month, self.day) * 86_400
+            + self.hour * 3600
+            + self.minute * 60
+            + self.second;
+        Timestamp::from_unix_micros(secs * MICROS_PER_SECOND + self.micros)
+    }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)
+}
+
+// Reads the text of a date or a timestamp, the way now() writes it or as it is
+// usually written: `2024-03-05`, `2024-03-05 14:30` or `2024-03-05T14:30:00.25+02`.
+// An offset moves the time to UTC.
+pub(super) fn parse_timestamp(text: &str) -> Result<Timestamp, BackendError> {
+    let invalid = || {
+        BackendError::InvalidTextRepresentation(format!(
+            "Invalid input syntax for type timestamp: \"{}\".",
+            text
+        ))
+    };
+    let mut input = Input {
+        bytes: text.trim().as_bytes(),
+        pos: 0,
+    };
+
+    let year = input.number(4, 6).ok_or_else(invalid)?;
+    input.expect(b'-').ok_or_else(invalid)?;
+    let month = input.number(1, 2).ok_or_else(invalid)?;
+    input.expect(b'-').ok_or_else(invalid)?;
+    let day = input.number(1, 2).ok_or_else(invalid)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    let mut time = DateTime {
+        year,
+        month,
+        day,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        micros: 0,
+    };
+
+    if input.expect(b' ').or_else(|| input.expect(b'T')).is_some() {
+        time.hour = input
+            .number(1, 2)
+            .filter(|hour| *hour < 24)
+            .ok_or_else(invalid)?;
+        input.expect(b':').ok_or_else(invalid)?;
+        time.minute = input
+            .number(2, 2)
+            .filter(|minute| *minute < 60)
+            .ok_or_else(invalid)?;
+        if input.expect(b':').is_some() {
+            time.second = input
+                .number(2, 2)
+                .filter(|second| *second < 60)
+                .ok_or_else(invalid)?;
+            if input.expect(b'.').is_some() {
+                let start = input.pos;
+                let fraction = input.number(1, 6).ok_or_else(invalid)?;
+                time.micros = fraction * 10i64.pow(6 - (input.pos - start) as u32);
+            }
+        }
+    }
+
+    let mut timestamp = time.to_timestamp();
+    input.expect(b' ');
+    if input.expect(b'Z').is_none() {
+        let sign = match input.expect(b'+').or_else(|| input.expect(b'-')) {
+            Some(b'+') => -1,
+            Some(_) => 1,
+            None => 0,
+        };
+        if sign != 0 {
+            let hours = input.number(2, 2).ok_or_else(invalid)?;
+            input.expect(b':');
+            let minutes = input.number(2, 2).unwrap_or(0);
+            let offset = (hours * 3600 + minutes * 60) * MICROS_PER_SECOND;
+            timestamp = Timestamp::from_unix_micros(timestamp.unix_micros() + sign * offset);
+        }
+    }
+    if input.pos != input.bytes.len() {
+        return Err(invalid());
+    }
+    Ok(timestamp)
+}
+
+struct Input<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Input<'_> {
+    // Takes the next byte if it is `byte`
+    fn expect(&mut self, byte: u8) -> Option<u8> {
+        match self.bytes.get(self.pos) {
+            Some(next) if *next == byte => {
+                self.pos += 1;
+                Some(byte)
+            }
+            _ => None,
+        }
+    }
+
+    // A run of `min` to `max` digits, None without at least `min` of them
+    fn number(&mut self, min: usize, max: usize) -> Option<i64> {
+        let digits = self.bytes[self.pos..]
+            .iter()
+            .take(max)
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        if digits < min {
+            return None;
+        }
+        let value = self.bytes[self.pos..self.pos + digits]
+            .iter()
+            .fold(0, |value, digit| value * 10 + (digit - b'0') as i64);
+        self.pos += digits;
+        Some(value)
+    }
+}
+
+// date_trunc(unit, timestamp) zeroes every field of the timestamp below `unit`
+pub(super) fn date_trunc(unit: &str, text: &str) -> Result<String, BackendError> {
+    let mut time = DateTime::from_timestamp(parse_timestamp(text)?);
+    let unit = unit.to_lowercase();
+    let keep = match TRUNCATION_UNITS.iter().position(|known| *known == unit) {
+        Some(keep) => keep,
+        None => {
+            return Err(BackendError::InvalidParameterValue(format!(
+                "date_trunc: Unit \"{}\" not recognized, expected one of {}.",
+                unit,
+                TRUNCATION_UNITS.join(", ")
+            )))
+        }
+    };
+    time.micros = 0;
+    time.second = 0;
+    if keep < 4 {
+        time.minute = 0;
+    }
+    if keep < 3 {
+        time.hour = 0;
+    }
+    if keep < 2 {
+        time.day = 1;
+    }
+    if keep < 1 {
+        time.month = 1;
+    }
+    Ok(time.to_timestamp().to_string())
+}
+
+// to_char(timestamp, pattern) writes the fields of the timestamp in place of the
+// patterns in `pattern`
+pub(super) fn to_char(text: &str, pattern: &str) -> Result<String, BackendError> {
+    let time = DateTime::from_timestamp(parse_timestamp(text)?);
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut result = String::with_capacity(pattern.len());
+    let mut pos = 0;
+    while pos < chars.len() {
+        if chars[pos] == '"' {
+            let literal = chars[pos + 1..].iter().take_while(|c| **c != '"');
+            result.extend(literal.clone());
+            pos += literal.count() + 2;
+            continue;
+        }
+        if !chars[pos].is_ascii_alphabetic() {
+            result.push(chars[pos]);
+            pos += 1;
+            continue;
+        }
+        let rest: String = chars[pos..].iter().collect::<String>().to_uppercase();
+        let known = match FORMAT_PATTERNS
+            .iter()
+            .find(|known| rest.starts_with(*known))
+        {
+            Some(known) => *known,
+            None => {
+                let unknown: String = chars[pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .collect();
+                return Err(BackendError::InvalidParameterValue(format!(
+                    "to_char: Format pattern \"{}\" at position {} is not supported, expected one of {}.",
+                    unknown,
+                    pos + 1,
+                    FORMAT_PATTERNS.join(", ")
+                )));
+            }
+        };
+        match known {
+            "YYYY" => result.push_str(&format!("{:04}", time.year)),
+            "HH24" => result.push_str(&format!("{:02}", time.hour)),
+            "MM" => result.push_str(&format!("{:02}", time.month)),
+            "DD" => result.push_str(&format!("{:02}", time.day)),
+            "MI" => result.push_str(&format!("{:02}", time.minute)),
+            _ => result.push_str(&format!("{:02}", time.second)),
+        }
+        pos += known.len();
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod datetime_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::MemoryBackend;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let tests = vec![
+            ("1970-01-01", 0),
+            ("2024-02-29", 1_709_164_800_000_000),
+            ("2024-02-29 13:05", 1_709_211_900_000_000),
+            ("2024-02-29T13:05:09.25", 1_709_211_909_250_000),
+            ("2024-02-29 13:05:09.000001+00", 1_709_211_909_000_001),
+            ("2024-02-29 15:05:09+02", 1_709_211_909_000_000),
+            ("2024-02-29 12:35:09 -00:30", 1_709_211_909_000_000),
+            (" 1969-12-31 23:59:59Z ", -1_000_000),
+        ];
+        for (text, micros) in tests {
+            assert_eq!(
+                parse_timestamp(text).map(|timestamp| timestamp.unix_micros()),
+                Ok(micros),
+                "{}",
+                text
+            );
+        }
+        for text in &[
+            "",
+            "2024",
+            "24-01-01",
+            "2023-02-29",
+            "2024-13-01",
+            "2024-01-01 24:00",
+            "2024-01-01 10",
+            "2024-01-01 10:00:00.",
+            "2024-01-01 10:00 UTC",
+        ] {
+            match parse_timestamp(text) {
+                Err(BackendError::InvalidTextRepresentation(msg)) => assert_eq!(
+                    msg,
+                    format!("Invalid input syntax for type timestamp: \"{}\".", text)
+                ),
+                result => panic!("{}: expected an error, got {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_date_trunc_and_to_char() {
+        let mut mb = MemoryBackend::new();
+        let query = "SELECT
+            date_trunc('year', '2024-03-05 14:30:15.5'),
+            date_trunc('Month', '2024-03-05 14:30:15.5'),
+            date_trunc('day', '2024-03-05 14:30:15.5'),
+            date_trunc('hour', '2024-03-05 14:30:15.5'),
+            date_trunc('minute', '2024-03-05 14:30:15.5'),
+            to_char('2024-03-05 04:30:15', 'YYYY-MM-DD HH24:MI:SS'),
+            to_char('2024-03-05', 'dd/mm/yyyy \"at\" HH24\"h\"'),
+            to_char(NULL, 'YYYY'),
+            date_trunc('day', NULL);";
+        assert_eq!(
+            rows(&mut mb, query),
+            vec![[
+                "2024-01-01 00:00:00.000000+00",
+                "2024-03-01 00:00:00.000000+00",
+                "2024-03-05 00:00:00.000000+00",
+                "2024-03-05 14:00:00.000000+00",
+                "2024-03-05 14:30:00.000000+00",
+                "2024-03-05 04:30:15",
+                "05/03/2024 at 00h",
+                "NULL",
+                "NULL",
+            ]
+            .join("|")]
+        );
+    }
+
+    #[test]
+    fn test_daily_rollup() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE events (ts TEXT, amount INT);
+            INSERT INTO events VALUES
+                ('2024-03-05 09:15:00', 1),
+                ('2024-03-06 23:59:59', 2),
+                ('2024-03-05 18:40:00', 3),
+                ('2024-03-07 01:00:00+02', 4),
+                ('2024-03-06 00:00:00', 5);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT to_char(date_trunc('day', ts), 'YYYY-MM-DD'), count(*), sum(amount)
+                FROM events
+                GROUP BY date_trunc('day', ts)
+                ORDER BY date_trunc('day', ts);"
+            ),
+            vec!["2024-03-05|2|4", "2024-03-06|3|11"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_units_and_patterns() {
+        let tests = vec![
+            (
+                "SELECT date_trunc('week', '2024-03-05');",
+                "date_trunc: Unit \"week\" not recognized, expected one of year, month, day, hour, minute.",
+            ),
+            (
+                "SELECT to_char('2024-03-05', 'YYYY-Q-DD');",
+                "to_char: Format pattern \"Q\" at position 6 is not supported, expected one of YYYY, HH24, MM, DD, MI, SS.",
+            ),
+            (
+                "SELECT to_char('2024-03-05', 'HH:MI');",
+                "to_char: Format pattern \"HH\" at position 1 is not supported, expected one of YYYY, HH24, MM, DD, MI, SS.",
+            ),
+            (
+                "SELECT to_char('yesterday', 'YYYY');",
+                "Invalid input syntax for type timestamp: \"yesterday\".",
+            ),
+            (
+                "SELECT date_trunc('day');",
+                "date_trunc takes 2 arguments, got 1.",
+            ),
+            (
+                "SELECT to_char(1, 'YYYY');",
+                "to_char does not accept arguments of type Int, Text.",
+            ),
+        ];
+
+        let mut mb = MemoryBackend::new();
+        for (query, expected) in tests {
+            match mb.eval_query(query) {
+                Err(err) => assert_eq!(err.message(), expected, "{}", query),
+                Ok(_) => panic!("{}: expected an error", query),
+            }
+        }
+    }
+}
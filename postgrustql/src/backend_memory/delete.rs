@@ -0,0 +1,229 @@
+use super::update::check_source;
+use super::{MemoryBackend, RowWrites};
+use crate::ast::*;
+use crate::backend::{BackendError, ERR_TABLE_DOES_NOT_EXIST};
+use crate::sql_types::SqlValue;
+
+// A row a DELETE takes out: its position in the table and its values
+type RowDelete = (usize, Vec<SqlValue>);
+
+impl MemoryBackend {
+    // Takes out the rows WHERE holds for, every row without it, giving how many there
+    // were. Either every row goes or none does.
+    pub fn delete(&mut self, statement: DeleteStatement) -> Result<usize, BackendError> {
+        Ok(self.delete_rows(&statement)?.len())
+    }
+
+    // Like `delete`, giving the rows taken out
+    pub(super) fn delete_rows(
+        &mut self,
+        statement: &DeleteStatement,
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        let deletes = self.row_deletes(statement)?;
+
+        self.mark_written(&statement.table);
+        let writes = RowWrites::Delete(deletes);
+        self.write_rows_firing(&statement.table, &writes)?;
+
+        let rows = writes.into_rows();
+        if self.changes.hooked() {
+            self.changes.record_deletes(&statement.table, rows.clone());
+        }
+        Ok(rows)
+    }
+
+    fn row_deletes(&self, statement: &DeleteStatement) -> Result<Vec<RowDelete>, BackendError> {
+        let table = match self.engine(&statement.table).scan_table(&statement.table)? {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(
+                    ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                ))
+            }
+        };
+        // The alias hides the name of the table, as in Postgres
+        let source = statement.as_clause.as_ref().unwrap_or(&statement.table);
+        let sources = vec![source.clone(); table.columns.len()];
+
+        // Subqueries that don't read the row run once, before any row goes
+        check_source(&statement.where_clause, source)?;
+        let mut where_clause = statement.where_clause.clone();
+        self.run_subqueries(&mut where_clause, &table, &sources, None)?;
+
+        let rows = self.matching_rows(
+            &table,
+            &sources,
+            &where_clause,
+            &statement.order_by,
+            statement.limit,
+            "DELETE",
+        )?;
+        let deletes = rows
+            .into_iter()
+            .map(|row_idx| (row_idx, table.rows[row_idx].values()))
+            .collect();
+        Ok(deletes)
+    }
+}
+
+#[cfg(test)]
+mod delete_tests {
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{backend, rows};
+    use crate::backend_memory::{ChangeEvent, ChangeOperation, MemoryBackend};
+    use std::sync::{Arc, Mutex};
+
+    const SETUP: &str = "CREATE TABLE people (id INT PRIMARY KEY, name TEXT, age INT);
+        CREATE INDEX people_age ON people (age);
+        INSERT INTO people VALUES (1, 'Baam', 20), (2, 'Rachel', 21), (3, 'Khun', 22),
+            (4, 'Rak', 19);";
+
+    #[test]
+    fn test_delete() {
+        let mut mb = backend(SETUP);
+        match mb
+            .eval_query("DELETE FROM people WHERE age > 20;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Delete { rows_deleted, .. }) => assert_eq!(rows_deleted, 2),
+            _ => panic!("Expected delete results"),
+        }
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), vec!["1", "4"]);
+        // The indexes follow the rows that are left
+        assert_eq!(
+            rows(&mut mb, "SELECT name FROM people WHERE age = 20;"),
+            vec!["Baam"]
+        );
+        mb.eval_query("INSERT INTO people VALUES (2, 'Rachel', 21);")
+            .unwrap();
+
+        let deleted = "DELETE FROM people p WHERE p.name = 'Rak' \
+            OR EXISTS (SELECT 1 FROM people WHERE age = p.age + 1);";
+        mb.eval_query(deleted).unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), vec!["2"]);
+
+        mb.eval_query("DELETE FROM people;").unwrap();
+        assert!(rows(&mut mb, "SELECT id FROM people;").is_empty());
+
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            ("DELETE FROM missing;", "42P01"),
+            ("DELETE FROM people WHERE missing = 1;", "42703"),
+            ("DELETE FROM people p WHERE people.id = 1;", "42P01"),
+            ("DELETE people WHERE id = 1;", "42601"),
+        ];
+        for (query, code) in tests {
+            assert_eq!(mb.eval_query(query).unwrap_err().code(), code, "{}", query);
+        }
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM people;"), vec!["4"]);
+    }
+
+    #[test]
+    fn test_delete_returning() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "DELETE FROM people AS p WHERE age >= 21 RETURNING p.id, upper(name) AS name;"
+            ),
+            vec!["2|RACHEL", "3|KHUN"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "INSERT INTO people VALUES (5, 'Yuri', 30), (6, 'Evan', 31) RETURNING *;"
+            ),
+            vec!["5|Yuri|30", "6|Evan|31"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "UPDATE people SET age = age + 1 WHERE id > 4 RETURNING id, age;"
+            ),
+            vec!["5|31", "6|32"]
+        );
+        // Nothing changed gives no rows, with the columns all the same
+        match mb
+            .eval_query("DELETE FROM people WHERE id = 100 RETURNING id AS gone;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                assert!(results.rows.is_empty());
+                assert_eq!(results.columns[0].name, "gone");
+            }
+            _ => panic!("Expected select results"),
+        }
+        assert_eq!(
+            mb.eval_query("DELETE FROM people RETURNING missing;")
+                .unwrap_err()
+                .code(),
+            "42703"
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM people;"), vec!["4"]);
+    }
+
+    #[test]
+    fn test_delete_order_limit() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE jobs (id INT PRIMARY KEY, status TEXT, ts INT);
+            INSERT INTO jobs VALUES (1, 'done', 40), (2, 'new', 10), (3, 'done', 20),
+                (4, 'done', 30), (5, 'done', 10);",
+        )
+        .unwrap();
+        // The rows come back in the order they were picked in
+        assert_eq!(
+            rows(
+                &mut mb,
+                "DELETE FROM jobs WHERE status = 'done' ORDER BY ts LIMIT 2 RETURNING id, ts;"
+            ),
+            vec!["5|10", "3|20"]
+        );
+        assert_eq!(
+            rows(&mut mb, "DELETE FROM jobs ORDER BY ts DESC RETURNING id;"),
+            vec!["1", "4", "2"]
+        );
+
+        // Without ORDER BY the first rows of the table go
+        mb.eval_query("INSERT INTO jobs VALUES (6, 'new', 1), (7, 'new', 2), (8, 'new', 3);")
+            .unwrap();
+        mb.eval_query("DELETE FROM jobs LIMIT 2;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM jobs;"), vec!["8"]);
+        mb.eval_query("DELETE FROM jobs LIMIT 0;").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM jobs WHERE id = 8;"),
+            vec!["8"]
+        );
+
+        assert_eq!(
+            mb.eval_query("DELETE FROM jobs j ORDER BY jobs.ts LIMIT 1;")
+                .unwrap_err()
+                .code(),
+            "42P01"
+        );
+        assert_eq!(
+            mb.eval_query("DELETE FROM jobs LIMIT -1;")
+                .unwrap_err()
+                .code(),
+            "42601"
+        );
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut mb = backend(SETUP);
+        let events = Arc::new(Mutex::new(vec![]));
+        let seen = events.clone();
+        mb.set_change_hook(move |event: ChangeEvent| seen.lock().unwrap().push(event));
+        mb.eval_query("DELETE FROM people WHERE id = 3;").unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].operation, ChangeOperation::Delete);
+        assert_eq!(events[0].table, "people");
+        assert!(events[0].new_row.is_none());
+        assert_eq!(events[0].old_row.as_ref().unwrap()[1].to_string(), "Khun");
+    }
+}
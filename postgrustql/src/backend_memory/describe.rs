@@ -0,0 +1,822 @@
+use super::asterisk::{expand_asterisk, AsteriskColumn};
+use super::cache::statement_tables;
+use super::prepared::{value_to_token, visit_expression_literals, PreparedStatement};
+use super::resolver::{local_name, Resolution};
+use super::stored_row::StoredRow;
+use super::with_query::query_table;
+use super::{sample_value, MemoryBackend, StorageEngine, Table, ANONYMOUS_COL_NAME};
+use crate::ast::*;
+use crate::backend::{BackendError, QueryResults, ResultColumn, ERR_TABLE_DOES_NOT_EXIST};
+use crate::lexer::{Lexer, Token};
+use crate::sql_types::{SqlType, SqlValue};
+
+// What a statement takes and gives, found without running it: the type of each of its
+// parameters, `$1` first, and the columns of the rows it returns, none for statements
+// that return none
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatementDescription {
+    pub parameters: Vec<SqlType>,
+    pub columns: Vec<ResultColumn>,
+}
+
+impl MemoryBackend {
+    // A parameter takes its type from where it is used: the column it is compared to,
+    // inserted into or set to, the other side of an operator, a cast or a condition. Like
+    // in Postgres one that can't be typed that way is an error, as is one given two
+    // different types.
+    pub fn describe(&mut self, query: &str) -> Result<StatementDescription, BackendError> {
+        let mut prepared = self.prepare(query)?;
+        self.expand_views(prepared.statement_mut())?;
+        let guard = self.enter_catalog(prepared.statement())?;
+        let outer = self.with_tables.clone();
+        let result = self.describe_prepared(query, &prepared);
+        self.with_tables = outer;
+        self.leave_catalog(guard)?;
+        result
+    }
+
+    fn describe_prepared(
+        &mut self,
+        query: &str,
+        prepared: &PreparedStatement,
+    ) -> Result<StatementDescription, BackendError> {
+        let mut inference = Inference {
+            backend: self,
+            types: vec![None; prepared.param_count()],
+            queries: vec![],
+        };
+        // A parameter typed late in the statement can type one before it, each pass types
+        // at least one more until none is left that can be
+        loop {
+            let known = inference.types.clone();
+            inference.statement(prepared.statement())?;
+            if inference.types == known {
+                break;
+            }
+        }
+        let types = inference.types;
+        let unknown: Vec<usize> = (1..=types.len())
+            .filter(|index| types[index - 1].is_none())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(BackendError::IndeterminateDatatype(unknown_message(
+                query, &unknown,
+            )));
+        }
+        let parameters: Vec<SqlType> = types.into_iter().flatten().collect();
+
+        // The columns are those the statement gives when run on tables without rows, in
+        // place of those it reads, with sample values for the parameters
+        let samples: Vec<SqlValue> = parameters.iter().map(|typ| sample_value(*typ)).collect();
+        let statement = prepared.bind(&samples)?;
+        let mut read = vec![];
+        statement_tables(&statement, &mut read);
+        for name in read {
+            // A table a view reads under its qualified name is read as it is, a copy would be
+            // taken for the table its plain name finds
+            if self.resolve(&name) == Some(Resolution::WithQuery) || local_name(&name) != name {
+                continue;
+            }
+            if let Some(table) = self.table(&name) {
+                let empty = without_rows(table);
+                self.with_tables.create_table(empty)?;
+            }
+        }
+        let columns = self.result_columns(&statement)?;
+        Ok(StatementDescription {
+            parameters,
+            columns,
+        })
+    }
+
+    fn result_columns(&mut self, statement: &Statement) -> Result<Vec<ResultColumn>, BackendError> {
+        let (table_name, as_clause) = match statement {
+            Statement::SelectStatement(select) => {
+                let mut select = select.clone();
+                // Without FROM the items are worked out even when no row is asked for
+                if select.from.is_empty() {
+                    select.where_clause = Expression::Literal(LiteralExpression {
+                        literal: Token::BoolValue { value: false },
+                    });
+                }
+                return Ok(self.select(select)?.columns);
+            }
+            Statement::WithStatement(with) => {
+                for query in &with.queries {
+                    let results = QueryResults {
+                        columns: self.result_columns(&query.statement)?,
+                        rows: vec![],
+                    };
+                    let table = query_table(&query.name, &query.columns, results)?;
+                    self.with_tables.create_table(table)?;
+                }
+                return self.result_columns(&with.statement);
+            }
+            _ if statement.returning().is_empty() => return Ok(vec![]),
+            Statement::InsertStatement(insert) => (&insert.table, None),
+            Statement::UpdateStatement(update) => (&update.table, update.as_clause.as_ref()),
+            Statement::DeleteStatement(delete) => (&delete.table, delete.as_clause.as_ref()),
+            _ => return Ok(vec![]),
+        };
+        let mut select = SelectStatement::new();
+        select.items = statement.returning().to_vec();
+        select.from.push(RowDataSource::Table {
+            table_name: table_name.clone(),
+            as_clause: as_clause.cloned(),
+            sample: None,
+            joins: vec![],
+        });
+        Ok(self.select(select)?.columns)
+    }
+}
+
+// The table with its columns and none of its rows
+fn without_rows(table: &Table) -> Table {
+    Table {
+        name: table.name.clone(),
+        columns: table.columns.clone(),
+        column_types: table.column_types.clone(),
+        rows: vec![],
+        indexes: vec![],
+        column_constraints: table.column_constraints.clone(),
+        checks: table.checks.clone(),
+        schema_version: table.schema_version,
+        heap_bytes: 0,
+        dead_rows: 0,
+    }
+}
+
+// Names each parameter with where it first appears in the query
+fn unknown_message(query: &str, unknown: &[usize]) -> String {
+    let tokens = Lexer::new().lex(query).unwrap_or_default();
+    let places: Vec<String> = unknown
+        .iter()
+        .map(|index| {
+            let parameter = Token::Parameter { index: *index };
+            match tokens.iter().find(|token| token.token == parameter) {
+                Some(token) => {
+                    format!("${} at {}:{}", index, token.loc.line + 1, token.loc.col + 1)
+                }
+                None => format!("${}", index),
+            }
+        })
+        .collect();
+    format!(
+        "Could not determine data type of parameter{} {}.",
+        if places.len() == 1 { "" } else { "s" },
+        places.join(", ")
+    )
+}
+
+// A FROM item or WITH query under the name it is read by, with the type of each of its
+// columns where it is known
+#[derive(Clone, Default)]
+struct Source {
+    name: String,
+    columns: Vec<String>,
+    types: Vec<Option<SqlType>>,
+}
+
+// The columns a part of a statement can read: those of its own FROM items, then those of
+// the queries it is in
+struct Scope<'a> {
+    sources: Vec<Source>,
+    outer: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(sources: Vec<Source>, outer: Option<&'a Scope<'a>>) -> Self {
+        Scope { sources, outer }
+    }
+
+    fn column_type(&self, table: Option<&str>, column: &str) -> Option<SqlType> {
+        let own = self
+            .sources
+            .iter()
+            .filter(|source| table.is_none_or(|table| source.name == table))
+            .find_map(|source| {
+                let position = source.columns.iter().position(|name| name == column)?;
+                Some(source.types[position])
+            });
+        match own {
+            Some(typ) => typ,
+            None => self.outer?.column_type(table, column),
+        }
+    }
+
+    // A row of sample values of the columns of known type, to find the type of an
+    // expression by evaluating it
+    fn sample_table(&self) -> Table {
+        let mut columns = vec![];
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            for source in &current.sources {
+                for (column, typ) in source.columns.iter().zip(&source.types) {
+                    if let Some(typ) = typ {
+                        columns.push((column.clone(), *typ));
+                    }
+                }
+            }
+            scope = current.outer;
+        }
+        let mut table = Table::new("", columns);
+        let row: Vec<SqlValue> = table
+            .column_types
+            .iter()
+            .map(|typ| sample_value(*typ))
+            .collect();
+        table.rows.push(StoredRow::new(&row));
+        table
+    }
+}
+
+struct Inference<'a> {
+    backend: &'a MemoryBackend,
+    // The type found for each parameter so far, `$1` first
+    types: Vec<Option<SqlType>>,
+    // The WITH queries that can be read, the last of a name hiding any before it
+    queries: Vec<Source>,
+}
+
+impl Inference<'_> {
+    // Types what parameters of the statement it can, giving the columns of its rows
+    fn statement(&mut self, statement: &Statement) -> Result<Source, BackendError> {
+        match statement {
+            Statement::SelectStatement(select) => self.select(select, None, &[]),
+            Statement::InsertStatement(insert) => {
+                let target = self.table_source(&insert.table, None)?;
+                for row in &insert.rows {
+                    for (value, typ) in row.iter().zip(&target.types) {
+                        self.expression(value, *typ, &Scope::new(vec![], None))?;
+                    }
+                }
+                if let Some(query) = &insert.query {
+                    self.select(query, None, &target.types)?;
+                }
+                self.items(&insert.returning, &[], &Scope::new(vec![target], None))
+            }
+            Statement::UpdateStatement(update) => {
+                let target = self.table_source(&update.table, update.as_clause.as_deref())?;
+                let scope = Scope::new(vec![target], None);
+                for assignment in &update.assignments {
+                    let typ = scope.column_type(None, &assignment.column);
+                    self.expression(&assignment.value, typ, &scope)?;
+                }
+                self.expression(&update.where_clause, Some(SqlType::Boolean), &scope)?;
+                for order_by in &update.order_by {
+                    self.expression(&order_by.exp, None, &scope)?;
+                }
+                self.items(&update.returning, &[], &scope)
+            }
+            Statement::DeleteStatement(delete) => {
+                let target = self.table_source(&delete.table, delete.as_clause.as_deref())?;
+                let scope = Scope::new(vec![target], None);
+                self.expression(&delete.where_clause, Some(SqlType::Boolean), &scope)?;
+                for order_by in &delete.order_by {
+                    self.expression(&order_by.exp, None, &scope)?;
+                }
+                self.items(&delete.returning, &[], &scope)
+            }
+            Statement::WithStatement(with) => {
+                let known = self.queries.len();
+                for query in &with.queries {
+                    let mut source = self.statement(&query.statement)?;
+                    source.name = query.name.clone();
+                    for (column, alias) in source.columns.iter_mut().zip(&query.columns) {
+                        *column = alias.clone();
+                    }
+                    self.queries.push(source);
+                }
+                let source = self.statement(&with.statement);
+                self.queries.truncate(known);
+                source
+            }
+            Statement::CreateTableAsStatement(create_table_as) => {
+                self.select(&create_table_as.query, None, &[])
+            }
+            Statement::ExplainStatement(explain) => self.select(&explain.select, None, &[]),
+            Statement::DeclareCursorStatement(declare) => self.select(&declare.query, None, &[]),
+            _ => Ok(Source::default()),
+        }
+    }
+
+    // `expected` has the types the items are to have, like the columns of an INSERT
+    fn select(
+        &mut self,
+        select: &SelectStatement,
+        outer: Option<&Scope>,
+        expected: &[Option<SqlType>],
+    ) -> Result<Source, BackendError> {
+        let mut scope = Scope::new(vec![], outer);
+        for item in &select.from {
+            self.row_source(item, &mut scope)?;
+        }
+        self.expression(&select.where_clause, Some(SqlType::Boolean), &scope)?;
+        for expression in &select.group_by {
+            self.expression(expression, None, &scope)?;
+        }
+        self.expression(&select.having, Some(SqlType::Boolean), &scope)?;
+        for order_by in &select.order_by {
+            self.expression(&order_by.exp, None, &scope)?;
+        }
+        self.items(&select.items, expected, &scope)
+    }
+
+    fn row_source(&mut self, item: &RowDataSource, scope: &mut Scope) -> Result<(), BackendError> {
+        let outer = scope.outer;
+        let (source, joins) = match item {
+            RowDataSource::Table {
+                table_name,
+                as_clause,
+                joins,
+                ..
+            } => (self.table_source(table_name, as_clause.as_deref())?, joins),
+            RowDataSource::SubSelect {
+                select,
+                as_clause,
+                lateral,
+                joins,
+            } => {
+                let mut source = match lateral {
+                    true => {
+                        let visible = Scope::new(scope.sources.clone(), outer);
+                        self.select(select, Some(&visible), &[])?
+                    }
+                    false => self.select(select, outer, &[])?,
+                };
+                source.name = as_clause.clone();
+                (source, joins)
+            }
+            RowDataSource::Values {
+                rows,
+                as_clause,
+                column_aliases,
+                joins,
+            } => {
+                let values_scope = Scope::new(vec![], outer);
+                let mut types = vec![None; rows.first().map_or(0, Vec::len)];
+                for row in rows {
+                    for (value, typ) in row.iter().zip(types.iter_mut()) {
+                        self.expression(value, *typ, &values_scope)?;
+                        *typ = typ.or_else(|| self.expression_type(value, &values_scope));
+                    }
+                }
+                let columns = (0..types.len())
+                    .map(|idx| match column_aliases.get(idx) {
+                        Some(alias) => alias.clone(),
+                        None => format!("column{}", idx + 1),
+                    })
+                    .collect();
+                let source = Source {
+                    name: as_clause.clone(),
+                    columns,
+                    types,
+                };
+                (source, joins)
+            }
+            RowDataSource::Function {
+                call,
+                as_clause,
+                column_aliases,
+                joins,
+                ..
+            } => {
+                for arg in &call.args {
+                    self.expression(arg, None, scope)?;
+                }
+                let source = Source {
+                    name: as_clause.clone().unwrap_or_else(|| call.name.clone()),
+                    columns: column_aliases.clone(),
+                    types: vec![None; column_aliases.len()],
+                };
+                (source, joins)
+            }
+        };
+        scope.sources.push(source);
+        for join in joins {
+            self.row_source(&join.source, scope)?;
+            self.expression(&join.on, Some(SqlType::Boolean), scope)?;
+        }
+        Ok(())
+    }
+
+    fn items(
+        &mut self,
+        items: &[SelectItem],
+        expected: &[Option<SqlType>],
+        scope: &Scope,
+    ) -> Result<Source, BackendError> {
+        let mut source = Source::default();
+        for item in items {
+            if item.asterisk {
+                // Those of the FROM item of `name.*` only
+                let sources: Vec<&Source> = scope
+                    .sources
+                    .iter()
+                    .filter(|from| {
+                        item.asterisk_source
+                            .as_ref()
+                            .is_none_or(|source| from.name == *source)
+                    })
+                    .collect();
+                let columns: Vec<(&str, &str)> = sources
+                    .iter()
+                    .flat_map(|from| {
+                        let name = from.name.as_str();
+                        from.columns
+                            .iter()
+                            .map(move |column| (name, column.as_str()))
+                    })
+                    .collect();
+                let types = sources.iter().flat_map(|from| from.types.iter());
+                let no_modifiers = AsteriskModifiers::default();
+                let modifiers = item.asterisk_modifiers.as_ref().unwrap_or(&no_modifiers);
+                let expanded = expand_asterisk(modifiers, &columns)?;
+                for (((_, column), typ), expanded) in columns.iter().zip(types).zip(expanded) {
+                    match expanded {
+                        AsteriskColumn::Kept => {
+                            source.columns.push(column.to_string());
+                            source.types.push(*typ);
+                        }
+                        AsteriskColumn::Excluded => {}
+                        AsteriskColumn::Replaced(replacement) => {
+                            let typ = expected.get(source.columns.len()).copied().flatten();
+                            self.expression(&replacement.expression, typ, scope)?;
+                            source.columns.push(item_name(replacement));
+                            source
+                                .types
+                                .push(self.expression_type(&replacement.expression, scope));
+                        }
+                    }
+                }
+                continue;
+            }
+            let typ = expected.get(source.columns.len()).copied().flatten();
+            self.expression(&item.expression, typ, scope)?;
+            source.columns.push(item_name(item));
+            source
+                .types
+                .push(self.expression_type(&item.expression, scope));
+        }
+        Ok(source)
+    }
+
+    fn table_source(&self, name: &str, as_clause: Option<&str>) -> Result<Source, BackendError> {
+        let mut source = match self.queries.iter().rev().find(|query| query.name == name) {
+            Some(query) => query.clone(),
+            None => match self.backend.table(name) {
+                Some(table) => Source {
+                    name: String::new(),
+                    columns: table.columns.clone(),
+                    types: table.column_types.iter().copied().map(Some).collect(),
+                },
+                None => {
+                    return Err(BackendError::UndefinedTable(
+                        ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                    ))
+                }
+            },
+        };
+        source.name = as_clause.unwrap_or(name).to_string();
+        Ok(source)
+    }
+
+    // Types the parameters of the expression it can, `expected` being the type the
+    // expression is to have where that is known
+    fn expression(
+        &mut self,
+        expression: &Expression,
+        expected: Option<SqlType>,
+        scope: &Scope,
+    ) -> Result<(), BackendError> {
+        match expression {
+            Expression::Literal(literal) => {
+                if let (Token::Parameter { index }, Some(typ)) = (&literal.literal, expected) {
+                    self.assign(*index, typ)?;
+                }
+            }
+            Expression::Binary(binary) => {
+                let (first, second) = match binary.operand {
+                    Token::And | Token::Or => (Some(SqlType::Boolean), Some(SqlType::Boolean)),
+                    Token::Concat
+                    | Token::RegexMatch
+                    | Token::RegexMatchInsensitive
+                    | Token::RegexNotMatch
+                    | Token::RegexNotMatchInsensitive
+                    | Token::Similar => (Some(SqlType::Text), Some(SqlType::Text)),
+                    Token::Equal
+                    | Token::NotEqual
+                    | Token::LessThan
+                    | Token::LessThanOrEqual
+                    | Token::GreaterThan
+                    | Token::GreaterThanOrEqual => (
+                        self.expression_type(&binary.second, scope),
+                        self.expression_type(&binary.first, scope),
+                    ),
+                    // Arithmetic gives the type of its operands
+                    _ => (
+                        self.expression_type(&binary.second, scope).or(expected),
+                        self.expression_type(&binary.first, scope).or(expected),
+                    ),
+                };
+                self.expression(&binary.first, first, scope)?;
+                self.expression(&binary.second, second, scope)?;
+            }
+            Expression::Unary(unary) => {
+                let typ = match unary.operand {
+                    Token::Not => Some(SqlType::Boolean),
+                    _ => expected,
+                };
+                self.expression(&unary.first, typ, scope)?;
+            }
+            Expression::Cast { data, typ } => self.expression(data, Some(*typ), scope)?,
+            Expression::Collate { data, .. } => {
+                self.expression(data, Some(SqlType::Text), scope)?
+            }
+            Expression::SubSelect(select) => {
+                self.select(select, Some(scope), &[expected])?;
+            }
+            Expression::Exists(select) => {
+                self.select(select, Some(scope), &[])?;
+            }
+            Expression::Quantified(quantified) => match &quantified.set {
+                QuantifiedSet::SubSelect(select) => {
+                    let first = self.expression_type(&quantified.first, scope);
+                    let set = self.select(select, Some(scope), &[first])?;
+                    let typ = set.types.first().copied().flatten();
+                    self.expression(&quantified.first, typ, scope)?;
+                }
+                QuantifiedSet::Values(values) => {
+                    let typ = values.first().map(SqlValue::get_type);
+                    self.expression(&quantified.first, typ, scope)?;
+                }
+            },
+            Expression::FunctionCall(call) => {
+                for arg in &call.args {
+                    self.expression(arg, None, scope)?;
+                }
+                if let Some(filter) = &call.filter {
+                    self.expression(filter, Some(SqlType::Boolean), scope)?;
+                }
+                if let Some(over) = &call.over {
+                    for expression in &over.partition_by {
+                        self.expression(expression, None, scope)?;
+                    }
+                    for order_by in &over.order_by {
+                        self.expression(&order_by.exp, None, scope)?;
+                    }
+                }
+            }
+            Expression::TableColumn(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::Empty => {}
+        }
+        Ok(())
+    }
+
+    // None while the expression reads a parameter not typed yet, or its type can't be
+    // told without rows
+    fn expression_type(&self, expression: &Expression, scope: &Scope) -> Option<SqlType> {
+        match expression {
+            Expression::Literal(literal) => match &literal.literal {
+                Token::Parameter { index } => return self.types[index - 1],
+                Token::IdentifierValue { value } => return scope.column_type(None, value),
+                _ => {}
+            },
+            Expression::TableColumn(column) => {
+                return scope.column_type(column.table_name.as_deref(), &column.col_name)
+            }
+            Expression::Cast { typ, .. } => return Some(*typ),
+            _ => {}
+        }
+
+        let mut sample = expression.clone();
+        let mut known = true;
+        visit_expression_literals(&mut sample, &mut |literal| {
+            if let Token::Parameter { index } = literal {
+                let token = self.types[*index - 1].map(|typ| value_to_token(&sample_value(typ)));
+                match token {
+                    Some(Ok(token)) => *literal = token,
+                    _ => known = false,
+                }
+            }
+        });
+        if !known {
+            return None;
+        }
+        match scope.sample_table().evaluate_cell(0, &sample) {
+            Ok((_, _, SqlType::Null)) | Err(_) => None,
+            Ok((_, _, typ)) => Some(typ),
+        }
+    }
+
+    fn assign(&mut self, index: usize, typ: SqlType) -> Result<(), BackendError> {
+        match self.types[index - 1] {
+            // NULL says nothing of the type
+            _ if typ == SqlType::Null => Ok(()),
+            None => {
+                self.types[index - 1] = Some(typ);
+                Ok(())
+            }
+            Some(known) if known == typ => Ok(()),
+            Some(known) => Err(BackendError::DatatypeMismatch(format!(
+                "Inconsistent types deduced for parameter ${}: {} versus {}.",
+                index, known, typ
+            ))),
+        }
+    }
+}
+
+// The name the item's column gets, as the engine names it
+fn item_name(item: &SelectItem) -> String {
+    if let Some(as_clause) = &item.as_clause {
+        return as_clause.clone();
+    }
+    match &item.expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => value.to_string(),
+        Expression::TableColumn(column) => column.col_name.clone(),
+        Expression::FunctionCall(call) => call.name.clone(),
+        _ => ANONYMOUS_COL_NAME.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod describe_tests {
+    use crate::backend::ResultColumn;
+    use crate::backend_memory::test_support::{backend, shared};
+    use crate::backend_memory::{Connection, MemoryBackend};
+    use crate::sql_types::SqlType;
+
+    const SETUP: &str = "CREATE TABLE people (id INT PRIMARY KEY, name TEXT, age SMALLINT);
+        CREATE TABLE pets (id BIGINT, owner INT, kind VARCHAR(10));
+        INSERT INTO people VALUES (1, 'Ann', 30);";
+
+    fn parameters(mb: &mut MemoryBackend, query: &str) -> Vec<SqlType> {
+        match mb.describe(query) {
+            Ok(description) => description.parameters,
+            Err(err) => panic!("{}: {}", query, err),
+        }
+    }
+
+    fn columns(mb: &mut MemoryBackend, query: &str) -> Vec<(String, SqlType)> {
+        let described = mb.describe(query).unwrap().columns;
+        let columns = described
+            .into_iter()
+            .map(|c: ResultColumn| (c.name, c.col_type));
+        columns.collect()
+    }
+
+    #[test]
+    fn test_parameter_types() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            ("SELECT * FROM people WHERE id = $1;", vec![SqlType::Int]),
+            (
+                "SELECT * FROM people WHERE $2 < age AND name = $1;",
+                vec![SqlType::Text, SqlType::SmallInt],
+            ),
+            (
+                "INSERT INTO pets VALUES ($1, $2, $3);",
+                vec![SqlType::BigInt, SqlType::Int, SqlType::VarChar],
+            ),
+            (
+                "INSERT INTO people SELECT $1, $2, age FROM people;",
+                vec![SqlType::Int, SqlType::Text],
+            ),
+            (
+                "UPDATE people SET name = $2 WHERE id = $1;",
+                vec![SqlType::Int, SqlType::Text],
+            ),
+            (
+                "DELETE FROM pets WHERE kind = $1 LIMIT 1;",
+                vec![SqlType::VarChar],
+            ),
+            (
+                "SELECT $1::BIGINT, $2 || 'x';",
+                vec![SqlType::BigInt, SqlType::Text],
+            ),
+            ("SELECT id + $1 FROM people;", vec![SqlType::Int]),
+            ("SELECT 1 WHERE $1;", vec![SqlType::Boolean]),
+            // Typed from a parameter typed further on
+            (
+                "SELECT $1 FROM pets WHERE $1 = $2 AND $2 = id;",
+                vec![SqlType::BigInt, SqlType::BigInt],
+            ),
+            (
+                "SELECT p.name FROM people p JOIN pets ON pets.owner = p.id \
+                WHERE pets.id = $1;",
+                vec![SqlType::BigInt],
+            ),
+            (
+                "SELECT name FROM people WHERE id IN (SELECT owner FROM pets WHERE kind = $1) \
+                AND EXISTS (SELECT 1 FROM pets WHERE pets.owner = people.id AND age > $2);",
+                vec![SqlType::VarChar, SqlType::SmallInt],
+            ),
+            (
+                "WITH old AS (SELECT id AS key, age FROM people WHERE age > $1) \
+                SELECT key FROM old WHERE key = $2;",
+                vec![SqlType::SmallInt, SqlType::Int],
+            ),
+            ("SELECT * FROM people;", vec![]),
+        ];
+        for (query, types) in tests {
+            assert_eq!(parameters(&mut mb, query), types, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_result_columns() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            columns(
+                &mut mb,
+                "SELECT id, name AS who, age + 1 FROM people WHERE id = $1;"
+            ),
+            vec![
+                ("id".to_string(), SqlType::Int),
+                ("who".to_string(), SqlType::Text),
+                ("?column?".to_string(), SqlType::Int),
+            ]
+        );
+        assert_eq!(
+            columns(&mut mb, "SELECT count(*), $1::TEXT;"),
+            vec![
+                ("count".to_string(), SqlType::BigInt),
+                ("?column?".to_string(), SqlType::Text),
+            ]
+        );
+        assert_eq!(
+            columns(
+                &mut mb,
+                "DELETE FROM pets p WHERE owner = $1 RETURNING p.kind;"
+            ),
+            vec![("kind".to_string(), SqlType::VarChar)]
+        );
+        assert_eq!(
+            columns(
+                &mut mb,
+                "WITH gone AS (DELETE FROM people RETURNING id) SELECT id FROM gone;"
+            ),
+            vec![("id".to_string(), SqlType::Int)]
+        );
+        assert_eq!(columns(&mut mb, "UPDATE people SET age = $1;"), vec![]);
+        assert_eq!(columns(&mut mb, "CREATE TABLE other (id INT);"), vec![]);
+
+        // Nothing is run
+        assert!(mb.table("other").is_none());
+        match mb.eval_query("SELECT count(*) FROM people;").unwrap().pop() {
+            Some(crate::backend::EvalResult::Select { results, .. }) => {
+                assert_eq!(results.rows[0][0].to_string(), "1")
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_describe_errors() {
+        let mut mb = backend(SETUP);
+        let err = mb
+            .describe("SELECT $1 FROM people WHERE id = $2 OR $3 = $3;")
+            .unwrap_err();
+        assert_eq!(err.code(), "42P18");
+        assert_eq!(
+            err.message(),
+            "Could not determine data type of parameters $1 at 1:8, $3 at 1:40."
+        );
+        let err = mb
+            .describe("SELECT *\nFROM people WHERE lower($1) = 'x';")
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Could not determine data type of parameter $1 at 2:25."
+        );
+
+        let tests = vec![
+            ("SELECT * FROM people WHERE id = $1 AND name = $1;", "42804"),
+            ("SELECT * FROM missing WHERE id = $1;", "42P01"),
+            ("SELECT 1; SELECT 2;", "42601"),
+        ];
+        for (query, code) in tests {
+            assert_eq!(mb.describe(query).unwrap_err().code(), code, "{}", query);
+        }
+    }
+    #[test]
+    fn test_describe_temp_tables() {
+        let backend = shared(SETUP);
+        let mut connection = Connection::open(backend.clone());
+        connection
+            .eval_query("CREATE TEMP TABLE people (note TEXT);")
+            .unwrap();
+        let description = connection
+            .describe("SELECT * FROM people WHERE note = $1;")
+            .unwrap();
+        assert_eq!(description.parameters, vec![SqlType::Text]);
+        assert_eq!(description.columns.len(), 1);
+        // Other connections see the permanent one
+        let mut other = Connection::open(backend);
+        let description = other.describe("SELECT * FROM people;").unwrap();
+        assert_eq!(description.columns.len(), 3);
+    }
+}
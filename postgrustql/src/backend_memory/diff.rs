@@ -0,0 +1,306 @@
+use super::semi_join::encode_keys;
+use super::MemoryBackend;
+use crate::backend::{BackendError, EvalResult, QueryResults, ResultColumn};
+use crate::quote::quote_identifier;
+use crate::sql_types::{SqlText, SqlType, SqlValue};
+use std::collections::{HashMap, HashSet};
+
+// How the rows of two tables of the same columns differ, see `MemoryBackend::diff_tables`.
+// Rows are matched by their key, and NULLs in the values compared equal one another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiff {
+    pub columns: Vec<ResultColumn>,
+    // The rows of the first table with a key no row of the second has, in table order
+    pub only_left: Vec<Vec<SqlValue>>,
+    // The rows of the second table with a key no row of the first has, in table order
+    pub only_right: Vec<Vec<SqlValue>>,
+    // The rows of both tables with the same key but other values, first table first and
+    // in its order
+    pub changed: Vec<(Vec<SqlValue>, Vec<SqlValue>)>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.changed.is_empty()
+    }
+
+    // The rows that differ, with a `side` column before the others like the lines of a
+    // unified diff: `-` for a row of the first table, `+` for one of the second. The
+    // changed rows come first, each as both of its rows, then those only in the first
+    // table and those only in the second.
+    pub fn to_results(&self) -> QueryResults<SqlValue> {
+        let row = |side: &str, row: &Vec<SqlValue>| {
+            let side = SqlValue::Text(SqlText::Text {
+                value: side.to_string(),
+            });
+            std::iter::once(side)
+                .chain(row.iter().cloned())
+                .collect::<Vec<_>>()
+        };
+        let mut rows = vec![];
+        for (left, right) in &self.changed {
+            rows.push(row("-", left));
+            rows.push(row("+", right));
+        }
+        rows.extend(self.only_left.iter().map(|left| row("-", left)));
+        rows.extend(self.only_right.iter().map(|right| row("+", right)));
+
+        let mut columns = vec![ResultColumn {
+            col_type: SqlType::Text,
+            name: "side".to_string(),
+            type_modifier: None,
+            nullable: false,
+            origin: None,
+        }];
+        columns.extend(self.columns.iter().cloned());
+        QueryResults { columns, rows }
+    }
+}
+
+impl MemoryBackend {
+    // The rows the tables named differ in, matched by the columns of `key`, or by all of
+    // their columns when it is empty. Rows are hashed by the sortable encoding of their
+    // key like in hashed semi joins, so a diff takes time linear in the rows. Tables with
+    // other columns, keys that are NULL and keys found in more than one row of a table
+    // are errors, as the rows can't be matched up then.
+    pub fn diff_tables(
+        &mut self,
+        left: &str,
+        right: &str,
+        key: &[&str],
+    ) -> Result<TableDiff, BackendError> {
+        let left_rows = self.all_rows(left)?;
+        let right_rows = self.all_rows(right)?;
+        check_same_columns(left, &left_rows.columns, right, &right_rows.columns)?;
+        let whole_row = key.is_empty();
+        let key_positions = match whole_row {
+            true => (0..left_rows.columns.len()).collect(),
+            false => key_positions(left, &left_rows.columns, key)?,
+        };
+        // The encoded key of every row, in table order
+        let keys_of = |table_name: &str, rows: &[Vec<SqlValue>]| {
+            let mut encoded = Vec::with_capacity(rows.len());
+            let mut seen = HashSet::with_capacity(rows.len());
+            for row in rows {
+                let keys = row_keys(
+                    table_name,
+                    &left_rows.columns,
+                    row,
+                    &key_positions,
+                    whole_row,
+                )?;
+                let keys_encoded = encode_keys(&keys);
+                if !seen.insert(keys_encoded.clone()) {
+                    return Err(BackendError::CardinalityViolation(format!(
+                        "Key ({})=({}) is found in more than one row of table \"{}\".",
+                        key_names(&left_rows.columns, &key_positions),
+                        keys.iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        table_name
+                    )));
+                }
+                encoded.push(keys_encoded);
+            }
+            Ok(encoded)
+        };
+        let left_keys = keys_of(left, &left_rows.rows)?;
+        let mut right_keys: HashMap<Vec<u8>, usize> = keys_of(right, &right_rows.rows)?
+            .into_iter()
+            .enumerate()
+            .map(|(position, keys)| (keys, position))
+            .collect();
+
+        let mut diff = TableDiff {
+            columns: left_rows.columns.clone(),
+            only_left: vec![],
+            only_right: vec![],
+            changed: vec![],
+        };
+        for (left_row, keys) in left_rows.rows.iter().zip(&left_keys) {
+            match right_keys.remove(keys) {
+                Some(position) => {
+                    let right_row = &right_rows.rows[position];
+                    if left_row != right_row {
+                        diff.changed.push((left_row.clone(), right_row.clone()));
+                    }
+                }
+                None => diff.only_left.push(left_row.clone()),
+            }
+        }
+        let mut only_right: Vec<usize> = right_keys.into_values().collect();
+        only_right.sort_unstable();
+        diff.only_right = only_right
+            .into_iter()
+            .map(|position| right_rows.rows[position].clone())
+            .collect();
+        Ok(diff)
+    }
+
+    fn all_rows(&mut self, table_name: &str) -> Result<QueryResults<SqlValue>, BackendError> {
+        let query = format!("SELECT * FROM {};", quote_identifier(table_name));
+        match self.eval_query(&query)?.pop() {
+            Some(EvalResult::Select { results, .. }) => Ok(results),
+            _ => Err(BackendError::Internal(format!(
+                "No rows were read from \"{}\".",
+                table_name
+            ))),
+        }
+    }
+}
+
+fn check_same_columns(
+    left: &str,
+    left_columns: &[ResultColumn],
+    right: &str,
+    right_columns: &[ResultColumn],
+) -> Result<(), BackendError> {
+    if left_columns.len() != right_columns.len() {
+        return Err(BackendError::SyntaxError(format!(
+            "Table \"{}\" has {} columns and table \"{}\" has {}, they can't be compared.",
+            left,
+            left_columns.len(),
+            right,
+            right_columns.len()
+        )));
+    }
+    for (left_column, right_column) in left_columns.iter().zip(right_columns) {
+        if left_column.name != right_column.name {
+            return Err(BackendError::InvalidColumnReference(format!(
+                "Column \"{}\" of table \"{}\" is column \"{}\" in table \"{}\".",
+                left_column.name, left, right_column.name, right
+            )));
+        }
+        if left_column.col_type != right_column.col_type {
+            return Err(BackendError::DatatypeMismatch(format!(
+                "Column \"{}\" is of type {} in table \"{}\" and {} in table \"{}\".",
+                left_column.name, left_column.col_type, left, right_column.col_type, right
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn key_positions(
+    table_name: &str,
+    columns: &[ResultColumn],
+    key: &[&str],
+) -> Result<Vec<usize>, BackendError> {
+    key.iter()
+        .map(|name| {
+            columns
+                .iter()
+                .position(|column| column.name == *name)
+                .ok_or_else(|| {
+                    BackendError::UndefinedColumn(format!(
+                        "Column \"{}\" of table \"{}\" doesn't exist.",
+                        name, table_name
+                    ))
+                })
+        })
+        .collect()
+}
+
+// The values of the key of a row, which only matches other rows when none of them is
+// NULL. Rows matched by all of their columns may hold NULLs, which match each other.
+fn row_keys(
+    table_name: &str,
+    columns: &[ResultColumn],
+    row: &[SqlValue],
+    key_positions: &[usize],
+    whole_row: bool,
+) -> Result<Vec<SqlValue>, BackendError> {
+    let mut keys = Vec::with_capacity(key_positions.len());
+    for position in key_positions {
+        let value = &row[*position];
+        if value.is_null() && !whole_row {
+            return Err(BackendError::NotNullViolation(format!(
+                "Key column \"{}\" is NULL in a row of table \"{}\".",
+                columns[*position].name, table_name
+            )));
+        }
+        keys.push(value.clone());
+    }
+    Ok(keys)
+}
+
+fn key_names(columns: &[ResultColumn], key_positions: &[usize]) -> String {
+    key_positions
+        .iter()
+        .map(|position| columns[*position].name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, joined};
+
+    const SETUP: &str = "CREATE TABLE expected (id INT, name TEXT, qty INT);
+        INSERT INTO expected VALUES (1, 'a', 5), (2, 'b', 10), (3, 'c', NULL), (4, 'd', 1);
+        CREATE TABLE actual (id INT, name TEXT, qty INT);
+        INSERT INTO actual VALUES (5, 'e', 2), (4, 'd', 1), (3, 'c', NULL), (2, 'b', 11);";
+
+    #[test]
+    fn test_diff_tables() {
+        let mut mb = backend(SETUP);
+        let diff = mb.diff_tables("expected", "actual", &["id"]).unwrap();
+        assert!(!diff.is_empty());
+        let results = diff.to_results();
+        let names: Vec<&str> = results.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["side", "id", "name", "qty"]);
+        assert_eq!(
+            joined(&results),
+            ["-|2|b|10", "+|2|b|11", "-|1|a|5", "+|5|e|2"]
+        );
+
+        // Without a key whole rows are compared, NULLs equal to each other
+        let diff = mb.diff_tables("expected", "actual", &[]).unwrap();
+        assert!(diff.changed.is_empty());
+        assert_eq!(
+            joined(&diff.to_results()),
+            ["-|1|a|5", "-|2|b|10", "+|5|e|2", "+|2|b|11"]
+        );
+        assert!(mb
+            .diff_tables("expected", "expected", &["id", "name"])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_diff_tables_errors() {
+        let mut mb = backend(SETUP);
+        let code = |mb: &mut MemoryBackend, left, right, key: &[&str]| {
+            mb.diff_tables(left, right, key).unwrap_err().code()
+        };
+        assert_eq!(code(&mut mb, "expected", "nope", &["id"]), "42P01");
+        assert_eq!(code(&mut mb, "expected", "actual", &["nope"]), "42703");
+
+        // Rows can't be matched by a NULL key or one that more than a row has
+        mb.eval_query("INSERT INTO actual VALUES (NULL, 'e', 3);")
+            .unwrap();
+        assert_eq!(code(&mut mb, "expected", "actual", &["id"]), "23502");
+        assert_eq!(code(&mut mb, "expected", "actual", &["name"]), "21000");
+        mb.eval_query("INSERT INTO expected VALUES (1, 'a', 5);")
+            .unwrap();
+        let err = mb.diff_tables("expected", "actual", &[]).unwrap_err();
+        assert_eq!(err.code(), "21000");
+        assert_eq!(
+            err.message(),
+            "Key (id, name, qty)=(1, a, 5) is found in more than one row of table \"expected\"."
+        );
+
+        // Nor can the rows of tables of other columns
+        mb.eval_query(
+            "CREATE TABLE fewer (id INT, name TEXT);
+            CREATE TABLE renamed (id INT, label TEXT, qty INT);
+            CREATE TABLE retyped (id INT, name TEXT, qty BIGINT);",
+        )
+        .unwrap();
+        assert_eq!(code(&mut mb, "expected", "fewer", &["id"]), "42601");
+        assert_eq!(code(&mut mb, "expected", "renamed", &["id"]), "42P10");
+        assert_eq!(code(&mut mb, "expected", "retyped", &["id"]), "42804");
+    }
+}
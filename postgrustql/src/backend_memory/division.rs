@@ -0,0 +1,189 @@
+use super::MemoryBackend;
+use crate::sql_types::{SqlTypeError, SqlValue};
+use std::cell::Cell;
+
+// What dividing by zero with `/`, `%`, div() or mod() gives. Postgres fails, which stays
+// the default, NULL lets queries over data with zeros in it go on.
+// `postgrustql.division_by_zero` to SET, as 'error' or 'null'.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DivisionByZero {
+    #[default]
+    Error,
+    Null,
+}
+
+impl DivisionByZero {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(DivisionByZero::Error),
+            "null" => Some(DivisionByZero::Null),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DivisionByZero::Error => "error",
+            DivisionByZero::Null => "null",
+        }
+    }
+}
+
+thread_local! {
+    static DIVISION_BY_ZERO: Cell<DivisionByZero> = const { Cell::new(DivisionByZero::Error) };
+}
+
+// Puts back the mode of the statement that was running before when dropped
+pub(super) struct DivisionByZeroGuard {
+    outer: DivisionByZero,
+}
+
+impl Drop for DivisionByZeroGuard {
+    fn drop(&mut self) {
+        DIVISION_BY_ZERO.with(|mode| mode.set(self.outer));
+    }
+}
+
+// Makes `mode` that of the operators evaluated on this thread until the guard is dropped.
+// Constants are folded by the same operators, so they follow it too.
+pub(super) fn enter_division_by_zero(mode: DivisionByZero) -> DivisionByZeroGuard {
+    DivisionByZeroGuard {
+        outer: DIVISION_BY_ZERO.with(|current| current.replace(mode)),
+    }
+}
+
+// The result of a division, NULL in place of a division by zero in 'null' mode
+pub(super) fn null_on_division_by_zero(
+    result: Result<SqlValue, SqlTypeError>,
+) -> Result<SqlValue, SqlTypeError> {
+    match result {
+        Err(SqlTypeError::DivisionByZero)
+            if DIVISION_BY_ZERO.with(Cell::get) == DivisionByZero::Null =>
+        {
+            Ok(SqlValue::Null)
+        }
+        result => result,
+    }
+}
+
+impl MemoryBackend {
+    // The mode of the session, for the statement about to run on this thread
+    pub(super) fn enter_division_mode(&self) -> DivisionByZeroGuard {
+        enter_division_by_zero(self.division_by_zero)
+    }
+}
+
+#[cfg(test)]
+mod division_tests {
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::{backend, backend_with, rows};
+    use crate::backend_memory::{BackendConfig, DivisionByZero, MemoryBackend};
+
+    const SETUP: &str = "CREATE TABLE stats (id INT, hits INT, total DOUBLE PRECISION);
+        INSERT INTO stats VALUES (1, 4, 10.0), (2, 0, 7.5), (3, NULL, 3.0);";
+
+    fn division_error(mb: &mut MemoryBackend, query: &str) {
+        match mb.eval_query(query) {
+            Err(BackendError::DivisionByZero(_)) => {}
+            result => panic!("{}: expected an error, got {:?}", query, result),
+        }
+    }
+
+    #[test]
+    fn test_div_and_mod() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT div(7, 2), div(-7, 2), div(7.5, 2), mod(7, 3), mod(-7, 3), div(7, NULL);"
+            ),
+            vec!["3|-3|3|1|-1|NULL"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT div(total, 4), mod(id, 2) FROM stats;"),
+            vec!["2|1", "1|0", "0|1"]
+        );
+        division_error(&mut mb, "SELECT div(1, 0);");
+        division_error(&mut mb, "SELECT mod(1, 0);");
+        assert_eq!(
+            mb.eval_query("SELECT div('a', 1);").unwrap_err().code(),
+            "42883"
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero_setting() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(&mut mb, "SHOW postgrustql.division_by_zero;"),
+            vec!["error"]
+        );
+        division_error(&mut mb, "SELECT total / hits FROM stats;");
+        division_error(&mut mb, "SELECT id FROM stats WHERE id % hits = 0;");
+
+        mb.eval_query("SET postgrustql.division_by_zero = 'null';")
+            .unwrap();
+        assert_eq!(mb.config().division_by_zero, DivisionByZero::Null);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT id, total / hits, id % hits, div(total, hits), mod(id, hits) FROM stats;"
+            ),
+            vec![
+                "1|2.5|1|2|1",
+                "2|NULL|NULL|NULL|NULL",
+                "3|NULL|NULL|NULL|NULL"
+            ]
+        );
+        // Constants are folded to NULL as well
+        assert_eq!(
+            rows(&mut mb, "SELECT 1 / 0, 5 % 0, 1.5 / 0.0;"),
+            vec!["NULL|NULL|NULL"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT id FROM stats WHERE total / hits > 2 OR 1 / 0 = 1;"
+            ),
+            vec!["1"]
+        );
+        mb.eval_query("INSERT INTO stats VALUES (4, 1 / 0, 1);")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT hits FROM stats WHERE id = 4;"),
+            vec!["NULL"]
+        );
+        // Looking up an index by a key that divides by zero finds nothing
+        mb.eval_query("CREATE INDEX stats_id ON stats (id);")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "EXPLAIN SELECT * FROM stats WHERE id = 1 / 0;"),
+            vec!["Result", "  ->  Seq Scan on stats"]
+        );
+        assert!(rows(&mut mb, "SELECT * FROM stats WHERE id = 1 / 0;").is_empty());
+
+        mb.eval_query("RESET postgrustql.division_by_zero;")
+            .unwrap();
+        division_error(&mut mb, "SELECT 1 / 0;");
+        // Plans are made without evaluating anything, the division only fails as it runs
+        mb.eval_query("EXPLAIN SELECT * FROM stats WHERE id = 1 / 0;")
+            .unwrap();
+        division_error(&mut mb, "SELECT * FROM stats WHERE id = 1 / 0;");
+        assert_eq!(
+            mb.eval_query("SET postgrustql.division_by_zero = 'ignore';")
+                .unwrap_err()
+                .code(),
+            "22023"
+        );
+
+        let mut mb = backend_with(
+            BackendConfig::new().division_by_zero(DivisionByZero::Null),
+            SETUP,
+        );
+        assert_eq!(
+            rows(&mut mb, "SHOW postgrustql.division_by_zero;"),
+            vec!["null"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT 1 / 0;"), vec!["NULL"]);
+    }
+}
@@ -0,0 +1,596 @@
+use super::apply_binary_operator;
+use super::catalog::{is_visible, server_version, PUBLIC_SCHEMA};
+use super::datetime::{date_trunc, to_char};
+use super::digest::{hashtext, md5, sha256, to_hex};
+use super::nondeterminism::{
+    clock_timestamp, random_u64, random_uuid, statement_timestamp, transaction_timestamp,
+};
+use super::progress::sleep;
+use super::sequence::{currval, nextval, setval};
+use super::table_size::relation_size;
+use crate::ast::FunctionCall;
+use crate::backend::{BackendError, ERR_FUNCTION_DOES_NOT_EXIST};
+use crate::lexer::Token;
+use crate::quote::{quote_identifier, quote_text};
+use crate::sql_types::{SqlNumeric, SqlText, SqlType, SqlValue};
+
+// Functions that work on one value at a time. Every one of them but format and pg_typeof
+// returns NULL when any of its arguments is NULL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScalarFunction {
+    Abs,
+    ClockTimestamp,
+    CurrentSchema,
+    Currval,
+    DateTrunc,
+    Div,
+    Format,
+    GenRandomUuid,
+    Hashtext,
+    IndexBytes,
+    Length,
+    Lower,
+    Md5,
+    Mod,
+    Nextval,
+    Now,
+    PgSleep,
+    PgTableIsVisible,
+    PgTypeof,
+    Random,
+    Setval,
+    Sha256,
+    StatementTimestamp,
+    TableBytes,
+    TableRows,
+    ToChar,
+    TransactionTimestamp,
+    Upper,
+    Version,
+}
+
+// Functions that take a whole set of rows, only valid where rows are grouped
+pub const AGGREGATE_FUNCTIONS: &[&str] = &["avg", "count", "max", "min", "sum"];
+
+impl ScalarFunction {
+    // The function of a call that is evaluated for each row on its own
+    pub fn from_call(call: &FunctionCall) -> Result<Self, BackendError> {
+        if AGGREGATE_FUNCTIONS.contains(&call.name.as_str()) {
+            return Err(BackendError::GroupingError(format!(
+                "{}: Aggregate functions are not allowed here.",
+                call.name
+            )));
+        }
+        let function = ScalarFunction::from_name(&call.name)?;
+        if call.distinct {
+            return Err(BackendError::WrongObjectType(format!(
+                "{}: DISTINCT specified, but it is not an aggregate function.",
+                call.name
+            )));
+        }
+        if call.filter.is_some() {
+            return Err(BackendError::WrongObjectType(format!(
+                "{}: FILTER specified, but it is not an aggregate function.",
+                call.name
+            )));
+        }
+        Ok(function)
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, BackendError> {
+        match name {
+            "abs" => Ok(ScalarFunction::Abs),
+            "clock_timestamp" => Ok(ScalarFunction::ClockTimestamp),
+            "current_schema" => Ok(ScalarFunction::CurrentSchema),
+            "currval" => Ok(ScalarFunction::Currval),
+            "date_trunc" => Ok(ScalarFunction::DateTrunc),
+            "div" => Ok(ScalarFunction::Div),
+            "format" => Ok(ScalarFunction::Format),
+            "gen_random_uuid" => Ok(ScalarFunction::GenRandomUuid),
+            "hashtext" => Ok(ScalarFunction::Hashtext),
+            "index_bytes" => Ok(ScalarFunction::IndexBytes),
+            "length" => Ok(ScalarFunction::Length),
+            "lower" => Ok(ScalarFunction::Lower),
+            "md5" => Ok(ScalarFunction::Md5),
+            "mod" => Ok(ScalarFunction::Mod),
+            "nextval" => Ok(ScalarFunction::Nextval),
+            "now" => Ok(ScalarFunction::Now),
+            "pg_sleep" => Ok(ScalarFunction::PgSleep),
+            "pg_table_is_visible" => Ok(ScalarFunction::PgTableIsVisible),
+            "pg_typeof" => Ok(ScalarFunction::PgTypeof),
+            "random" => Ok(ScalarFunction::Random),
+            "setval" => Ok(ScalarFunction::Setval),
+            "sha256" => Ok(ScalarFunction::Sha256),
+            "statement_timestamp" => Ok(ScalarFunction::StatementTimestamp),
+            "table_bytes" => Ok(ScalarFunction::TableBytes),
+            "table_rows" => Ok(ScalarFunction::TableRows),
+            "to_char" => Ok(ScalarFunction::ToChar),
+            "transaction_timestamp" => Ok(ScalarFunction::TransactionTimestamp),
+            "upper" => Ok(ScalarFunction::Upper),
+            "version" => Ok(ScalarFunction::Version),
+            _ => Err(BackendError::UndefinedFunction(format!(
+                "{}: {}",
+                name, ERR_FUNCTION_DOES_NOT_EXIST
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScalarFunction::Abs => "abs",
+            ScalarFunction::ClockTimestamp => "clock_timestamp",
+            ScalarFunction::CurrentSchema => "current_schema",
+            ScalarFunction::Currval => "currval",
+            ScalarFunction::DateTrunc => "date_trunc",
+            ScalarFunction::Div => "div",
+            ScalarFunction::Format => "format",
+            ScalarFunction::GenRandomUuid => "gen_random_uuid",
+            ScalarFunction::Hashtext => "hashtext",
+            ScalarFunction::IndexBytes => "index_bytes",
+            ScalarFunction::Length => "length",
+            ScalarFunction::Lower => "lower",
+            ScalarFunction::Md5 => "md5",
+            ScalarFunction::Mod => "mod",
+            ScalarFunction::Nextval => "nextval",
+            ScalarFunction::Now => "now",
+            ScalarFunction::PgSleep => "pg_sleep",
+            ScalarFunction::PgTableIsVisible => "pg_table_is_visible",
+            ScalarFunction::PgTypeof => "pg_typeof",
+            ScalarFunction::Random => "random",
+            ScalarFunction::Setval => "setval",
+            ScalarFunction::Sha256 => "sha256",
+            ScalarFunction::StatementTimestamp => "statement_timestamp",
+            ScalarFunction::TableBytes => "table_bytes",
+            ScalarFunction::TableRows => "table_rows",
+            ScalarFunction::ToChar => "to_char",
+            ScalarFunction::TransactionTimestamp => "transaction_timestamp",
+            ScalarFunction::Upper => "upper",
+            ScalarFunction::Version => "version",
+        }
+    }
+
+    // Whether two calls with the same arguments can give different results within a
+    // statement, those are never worked out ahead of the rows
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            ScalarFunction::ClockTimestamp
+                | ScalarFunction::Currval
+                | ScalarFunction::GenRandomUuid
+                | ScalarFunction::Nextval
+                | ScalarFunction::PgSleep
+                | ScalarFunction::Random
+                | ScalarFunction::Setval
+        )
+    }
+
+    // Whether the result only ever depends on the arguments, even across statements. Those
+    // telling about the server and its tables are stable in Postgres, so they aren't either.
+    pub fn is_immutable(&self) -> bool {
+        !self.is_volatile()
+            && !matches!(
+                self,
+                ScalarFunction::CurrentSchema
+                    | ScalarFunction::Now
+                    | ScalarFunction::IndexBytes
+                    | ScalarFunction::PgTableIsVisible
+                    | ScalarFunction::StatementTimestamp
+                    | ScalarFunction::TableBytes
+                    | ScalarFunction::TableRows
+                    | ScalarFunction::TransactionTimestamp
+                    | ScalarFunction::Version
+            )
+    }
+
+    pub fn call(&self, args: &[SqlValue]) -> Result<SqlValue, BackendError> {
+        if *self == ScalarFunction::Format {
+            return format(args);
+        }
+        let arity = match self {
+            ScalarFunction::ClockTimestamp
+            | ScalarFunction::CurrentSchema
+            | ScalarFunction::GenRandomUuid
+            | ScalarFunction::Now
+            | ScalarFunction::Random
+            | ScalarFunction::StatementTimestamp
+            | ScalarFunction::TransactionTimestamp
+            | ScalarFunction::Version => 0,
+            ScalarFunction::DateTrunc
+            | ScalarFunction::Div
+            | ScalarFunction::Mod
+            | ScalarFunction::Setval
+            | ScalarFunction::ToChar => 2,
+            _ => 1,
+        };
+        if args.len() != arity {
+            return Err(BackendError::UndefinedFunction(format!(
+                "{} takes {} argument{}, got {}.",
+                self.name(),
+                arity,
+                if arity == 1 { "" } else { "s" },
+                args.len()
+            )));
+        }
+        if args.is_empty() {
+            return self.call_without_args();
+        }
+        // Going by the value alone, NULL has a type of its own. Where the type the argument
+        // was worked out to have is known, `call_typed` gives that instead.
+        if let (ScalarFunction::PgTypeof, [arg]) = (self, args) {
+            return Ok(SqlValue::Type(arg.get_type()));
+        }
+        if args.iter().any(SqlValue::is_null) {
+            return Ok(SqlValue::Null);
+        }
+
+        match (self, args) {
+            (ScalarFunction::Abs, [SqlValue::Numeric(num)]) => Ok(SqlValue::Numeric(abs(num)?)),
+            // The quotient rounded towards zero and the remainder, for any numeric type
+            (ScalarFunction::Div, [a @ SqlValue::Numeric(_), b @ SqlValue::Numeric(_)]) => {
+                match apply_binary_operator(&Token::Slash, a, b)? {
+                    SqlValue::Numeric(SqlNumeric::Real { value }) => {
+                        Ok(SqlValue::Numeric(SqlNumeric::Real {
+                            value: value.trunc(),
+                        }))
+                    }
+                    SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => {
+                        Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                            value: value.trunc(),
+                        }))
+                    }
+                    quotient => Ok(quotient),
+                }
+            }
+            (ScalarFunction::Mod, [a @ SqlValue::Numeric(_), b @ SqlValue::Numeric(_)]) => {
+                apply_binary_operator(&Token::Modulo, a, b)
+            }
+            // Gives NULL in place of Postgres' void
+            (ScalarFunction::PgSleep, [SqlValue::Numeric(seconds)]) => {
+                sleep(match *seconds {
+                    SqlNumeric::SmallInt { value } => f64::from(value),
+                    SqlNumeric::Int { value } => f64::from(value),
+                    SqlNumeric::BigInt { value } => value as f64,
+                    SqlNumeric::Real { value } => f64::from(value),
+                    SqlNumeric::DoublePrecision { value } => value,
+                })?;
+                Ok(SqlValue::Null)
+            }
+            (ScalarFunction::Length, [SqlValue::Text(text)]) => {
+                Ok(SqlValue::Numeric(SqlNumeric::Int {
+                    value: text.to_string().chars().count() as i32,
+                }))
+            }
+            (ScalarFunction::Lower, [SqlValue::Text(text)]) => Ok(SqlValue::Text(SqlText::Text {
+                value: text.to_string().to_lowercase(),
+            })),
+            (ScalarFunction::Upper, [SqlValue::Text(text)]) => Ok(SqlValue::Text(SqlText::Text {
+                value: text.to_string().to_uppercase(),
+            })),
+            // NULL for OIDs of no relation, as in Postgres
+            // Other values are hashed as the text they cast to, as if cast implicitly.
+            // There is no bytea, the digests are given in hex.
+            (ScalarFunction::Md5 | ScalarFunction::Sha256 | ScalarFunction::Hashtext, [arg]) => {
+                let text = match arg.to_type(SqlType::Text) {
+                    Ok(SqlValue::Text(text)) => text.to_string(),
+                    _ => arg.to_string(),
+                };
+                Ok(match self {
+                    ScalarFunction::Md5 => SqlValue::Text(SqlText::Text {
+                        value: to_hex(&md5(text.as_bytes())),
+                    }),
+                    ScalarFunction::Sha256 => SqlValue::Text(SqlText::Text {
+                        value: to_hex(&sha256(text.as_bytes())),
+                    }),
+                    _ => SqlValue::Numeric(SqlNumeric::Int {
+                        value: hashtext(text.as_bytes()),
+                    }),
+                })
+            }
+            (ScalarFunction::PgTableIsVisible, [SqlValue::Numeric(SqlNumeric::Int { value })]) => {
+                Ok(is_visible(i64::from(*value)).map_or(SqlValue::Null, SqlValue::Boolean))
+            }
+            (
+                ScalarFunction::PgTableIsVisible,
+                [SqlValue::Numeric(SqlNumeric::BigInt { value })],
+            ) => Ok(is_visible(*value).map_or(SqlValue::Null, SqlValue::Boolean)),
+            // Relations are named by text, like a regclass is given in Postgres
+            (
+                ScalarFunction::IndexBytes | ScalarFunction::TableBytes | ScalarFunction::TableRows,
+                [SqlValue::Text(name)],
+            ) => relation_size(self.name(), &name.to_string()),
+            // Sequences are named by text, like a regclass is given in Postgres
+            (ScalarFunction::Nextval, [SqlValue::Text(name)]) => nextval(&name.to_string()),
+            (ScalarFunction::Currval, [SqlValue::Text(name)]) => currval(&name.to_string()),
+            (ScalarFunction::Setval, [SqlValue::Text(name), value]) => match whole_number(value) {
+                Some(value) => setval(&name.to_string(), value),
+                None => Err(BackendError::UndefinedFunction(format!(
+                    "setval does not accept a value of type {}.",
+                    value.get_type()
+                ))),
+            },
+            // Timestamps are text, the way now() gives them
+            (ScalarFunction::DateTrunc, [SqlValue::Text(unit), SqlValue::Text(time)]) => {
+                Ok(SqlValue::Text(SqlText::Text {
+                    value: date_trunc(&unit.to_string(), &time.to_string())?,
+                }))
+            }
+            (ScalarFunction::ToChar, [SqlValue::Text(time), SqlValue::Text(pattern)]) => {
+                Ok(SqlValue::Text(SqlText::Text {
+                    value: to_char(&time.to_string(), &pattern.to_string())?,
+                }))
+            }
+            (_, [arg]) => Err(BackendError::UndefinedFunction(format!(
+                "{} does not accept an argument of type {}.",
+                self.name(),
+                arg.get_type()
+            ))),
+            (_, args) => Err(BackendError::UndefinedFunction(format!(
+                "{} does not accept arguments of type {}.",
+                self.name(),
+                args.iter()
+                    .map(|arg| arg.get_type().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    // Like `call`, for arguments along with the types they were worked out to have
+    pub fn call_typed(&self, args: &[(SqlValue, SqlType)]) -> Result<SqlValue, BackendError> {
+        match (self, args) {
+            (ScalarFunction::PgTypeof, [(_, typ)]) => Ok(SqlValue::Type(*typ)),
+            _ => {
+                let args: Vec<SqlValue> = args.iter().map(|(value, _)| value.clone()).collect();
+                self.call(&args)
+            }
+        }
+    }
+
+    // The time, a random number or a UUID, from the sources of the running statement, or
+    // what the server is
+    fn call_without_args(&self) -> Result<SqlValue, BackendError> {
+        let timestamp = match self {
+            ScalarFunction::CurrentSchema => {
+                return Ok(SqlValue::Text(SqlText::Text {
+                    value: PUBLIC_SCHEMA.to_string(),
+                }))
+            }
+            ScalarFunction::Version => {
+                return Ok(SqlValue::Text(SqlText::Text {
+                    value: server_version(),
+                }))
+            }
+            ScalarFunction::GenRandomUuid => {
+                return Ok(SqlValue::Text(SqlText::Text {
+                    value: format_uuid(random_uuid()),
+                }))
+            }
+            // The top 53 bits, as many as a double holds, scaled into [0, 1)
+            ScalarFunction::Random => {
+                return Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                    value: (random_u64() >> 11) as f64 / (1u64 << 53) as f64,
+                }))
+            }
+            ScalarFunction::ClockTimestamp => clock_timestamp(),
+            ScalarFunction::StatementTimestamp => statement_timestamp(),
+            _ => transaction_timestamp(),
+        };
+        match timestamp {
+            Some(timestamp) => Ok(SqlValue::Text(SqlText::Text {
+                value: timestamp.to_string(),
+            })),
+            None => Err(BackendError::FeatureNotSupported(format!(
+                "{}: The current time is not available on this platform.",
+                self.name()
+            ))),
+        }
+    }
+}
+
+// format(pattern, args...) puts the arguments in place of the %s, %I and %L of the
+// pattern, in order. %s gives the text of a value, nothing for NULL, %I quotes it as an
+// identifier where it needs to be and %L as a literal, NULL staying unquoted. %% gives %.
+fn format(args: &[SqlValue]) -> Result<SqlValue, BackendError> {
+    let pattern = match args.first() {
+        Some(SqlValue::Null) => return Ok(SqlValue::Null),
+        Some(SqlValue::Text(pattern)) => pattern.to_string(),
+        Some(arg) => {
+            return Err(BackendError::UndefinedFunction(format!(
+                "format does not accept a pattern of type {}.",
+                arg.get_type()
+            )))
+        }
+        None => {
+            return Err(BackendError::UndefinedFunction(
+                "format takes at least 1 argument, got 0.".to_string(),
+            ))
+        }
+    };
+
+    let mut values = args[1..].iter();
+    let mut formatted = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().enumerate();
+    while let Some((_, c)) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        // Errors give the position of the % in the pattern, counted from 1
+        let (position, specifier) = match chars.next() {
+            Some((_, '%')) => {
+                formatted.push('%');
+                continue;
+            }
+            Some((idx, specifier)) => (idx, specifier),
+            None => {
+                return Err(BackendError::InvalidParameterValue(
+                    "Unterminated format() type specifier.".to_string(),
+                ))
+            }
+        };
+        if !matches!(specifier, 's' | 'I' | 'L') {
+            return Err(BackendError::InvalidParameterValue(format!(
+                "Unrecognized format() type specifier \"{}\", at position {}.",
+                specifier, position
+            )));
+        }
+        let value = match values.next() {
+            Some(value) => value,
+            None => {
+                return Err(BackendError::InvalidParameterValue(format!(
+                    "Too few arguments for format(), at position {}.",
+                    position
+                )))
+            }
+        };
+        match (specifier, value) {
+            ('s', SqlValue::Null) => {}
+            ('I', SqlValue::Null) => {
+                return Err(BackendError::InvalidParameterValue(
+                    "Null values cannot be formatted as an SQL identifier.".to_string(),
+                ))
+            }
+            ('L', SqlValue::Null) => formatted.push_str("NULL"),
+            ('I', value) => formatted.push_str(&quote_identifier(&value.to_string())),
+            ('L', value) => formatted.push_str(&quote_text(&value.to_string())),
+            (_, value) => formatted.push_str(&value.to_string()),
+        }
+    }
+    Ok(SqlValue::Text(SqlText::Text { value: formatted }))
+}
+
+// A UUID as text, in its usual groups of hex digits
+fn format_uuid(bytes: [u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// Functions that give a set of rows, they can only be used in FROM where they stand in
+// for a table
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TableFunction {
+    GenerateSeries,
+}
+
+impl TableFunction {
+    pub fn from_name(name: &str) -> Result<Self, BackendError> {
+        match name {
+            "generate_series" => Ok(TableFunction::GenerateSeries),
+            _ => Err(BackendError::UndefinedFunction(format!(
+                "{}: {}",
+                name, ERR_FUNCTION_DOES_NOT_EXIST
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TableFunction::GenerateSeries => "generate_series",
+        }
+    }
+
+    // The type of the single column the function gives and its values, which are only
+    // worked out as they are read
+    pub fn call(
+        &self,
+        args: &[SqlValue],
+    ) -> Result<(SqlType, Box<dyn Iterator<Item = SqlValue>>), BackendError> {
+        match self {
+            TableFunction::GenerateSeries => generate_series(args),
+        }
+    }
+}
+
+// generate_series(start, stop [, step]) counts from start to stop, down when the step
+// is negative. Any NULL argument gives no rows.
+fn generate_series(
+    args: &[SqlValue],
+) -> Result<(SqlType, Box<dyn Iterator<Item = SqlValue>>), BackendError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(BackendError::UndefinedFunction(format!(
+            "generate_series takes 2 or 3 arguments, got {}.",
+            args.len()
+        )));
+    }
+    let mut typ = SqlType::Int;
+    let mut values = Vec::with_capacity(3);
+    for arg in args {
+        match arg {
+            SqlValue::Null => return Ok((typ, Box::new(std::iter::empty()))),
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => values.push(*value as i64),
+            SqlValue::Numeric(SqlNumeric::Int { value }) => values.push(*value as i64),
+            SqlValue::Numeric(SqlNumeric::BigInt { value }) => {
+                typ = SqlType::BigInt;
+                values.push(*value);
+            }
+            _ => {
+                return Err(BackendError::UndefinedFunction(format!(
+                    "generate_series does not accept an argument of type {}.",
+                    arg.get_type()
+                )))
+            }
+        }
+    }
+    let (start, stop, step) = (values[0], values[1], values.get(2).copied().unwrap_or(1));
+    if step == 0 {
+        return Err(BackendError::InvalidParameterValue(
+            "generate_series step size cannot equal zero.".to_string(),
+        ));
+    }
+
+    let mut next = Some(start);
+    let series = std::iter::from_fn(move || {
+        let value = next.filter(|value| {
+            if step > 0 {
+                *value <= stop
+            } else {
+                *value >= stop
+            }
+        })?;
+        // Stops short of overflowing
+        next = value.checked_add(step);
+        Some(value)
+    });
+    let series = series.map(move |value| match typ {
+        SqlType::Int => SqlValue::Numeric(SqlNumeric::Int {
+            value: value as i32,
+        }),
+        _ => SqlValue::Numeric(SqlNumeric::BigInt { value }),
+    });
+    Ok((typ, Box::new(series)))
+}
+
+// The value of an integer of any size
+fn whole_number(value: &SqlValue) -> Option<i64> {
+    match value {
+        SqlValue::Numeric(SqlNumeric::SmallInt { value }) => Some(i64::from(*value)),
+        SqlValue::Numeric(SqlNumeric::Int { value }) => Some(i64::from(*value)),
+        SqlValue::Numeric(SqlNumeric::BigInt { value }) => Some(*value),
+        _ => None,
+    }
+}
+
+fn abs(num: &SqlNumeric) -> Result<SqlNumeric, BackendError> {
+    let overflow =
+        || BackendError::NumericValueOutOfRange("Numeric value out of range.".to_string());
+    Ok(match *num {
+        SqlNumeric::SmallInt { value } => SqlNumeric::SmallInt {
+            value: value.checked_abs().ok_or_else(overflow)?,
+        },
+        SqlNumeric::Int { value } => SqlNumeric::Int {
+            value: value.checked_abs().ok_or_else(overflow)?,
+        },
+        SqlNumeric::BigInt { value } => SqlNumeric::BigInt {
+            value: value.checked_abs().ok_or_else(overflow)?,
+        },
+        SqlNumeric::Real { value } => SqlNumeric::Real { value: value.abs() },
+        SqlNumeric::DoublePrecision { value } => SqlNumeric::DoublePrecision { value: value.abs() },
+    })
+}
@@ -0,0 +1,244 @@
+use super::check::RowExpression;
+use super::{CompiledExpression, Table};
+use crate::backend::BackendError;
+use crate::sql_types::SqlValue;
+
+// Columns declared GENERATED ALWAYS AS (expression) STORED. Their values are computed
+// from the rest of the row whenever one goes in and stored with it. Writing them directly
+// is an error, an INSERT can only give DEFAULT for them.
+impl Table {
+    pub(super) fn is_generated(&self, column: usize) -> bool {
+        matches!(
+            self.column_constraints.get(column),
+            Some(constraints) if constraints.generated.is_some()
+        )
+    }
+
+    // Makes sure the generation expressions of a table being created only read columns
+    // of the row that aren't generated themselves, and only call immutable functions
+    pub(super) fn check_generated_columns(&self) -> Result<(), BackendError> {
+        let columns = RowExpression::Generated.columns(&self.name, &self.columns);
+        for (idx, constraints) in self.column_constraints.iter().enumerate() {
+            let expression = match &constraints.generated {
+                Some(expression) => expression,
+                None => continue,
+            };
+            let mut used = vec![];
+            columns.collect(expression, &mut used)?;
+            if let Some(other) = used.iter().find(|column| self.is_generated(**column)) {
+                return Err(BackendError::InvalidTableDefinition(format!(
+                    "Cannot use generated column \"{}\" in the generation expression of \"{}\".",
+                    self.columns[*other], self.columns[idx]
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // The generated columns whose expressions read the column at `column`
+    pub(super) fn generated_using(&self, column: usize) -> Vec<usize> {
+        let columns = RowExpression::Generated.columns(&self.name, &self.columns);
+        (0..self.columns.len())
+            .filter(|idx| match &self.column_constraints.get(*idx) {
+                Some(constraints) => match &constraints.generated {
+                    Some(expression) => {
+                        let mut used = vec![];
+                        let _ = columns.collect(expression, &mut used);
+                        used.contains(&column)
+                    }
+                    None => false,
+                },
+                None => false,
+            })
+            .collect()
+    }
+
+    // The generation expressions resolved against the columns, to be given to
+    // `fill_generated`
+    pub(super) fn compile_generated(&self) -> Vec<(usize, CompiledExpression)> {
+        self.column_constraints
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, constraints)| {
+                let expression = constraints.generated.as_ref()?;
+                Some((idx, self.compile_expression(expression)))
+            })
+            .collect()
+    }
+
+    // Computes the generated columns of a row from the others, which have their values
+    pub(super) fn fill_generated(
+        &self,
+        generated: &[(usize, CompiledExpression)],
+        row: &mut [SqlValue],
+    ) -> Result<(), BackendError> {
+        for (idx, expression) in generated {
+            let value = expression.evaluate(row)?.into_owned();
+            row[*idx] = value.to_type(self.column_types[*idx])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod generated_tests {
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::MemoryBackend;
+    use crate::sql_types::{SqlNumeric, SqlValue};
+
+    #[test]
+    fn test_generated_columns() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE items (
+                price INT,
+                qty INT NOT NULL,
+                total BIGINT GENERATED ALWAYS AS (price * qty) STORED,
+                label TEXT GENERATED ALWAYS AS ('x' || upper(name)) STORED NOT NULL,
+                name TEXT
+            );
+            INSERT INTO items VALUES (3, 4, DEFAULT, DEFAULT, 'a'), (NULL, 2, DEFAULT, DEFAULT, 'b');",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM items;"),
+            vec!["3|4|12|xA|a", "NULL|2|NULL|xB|b"]
+        );
+        // They are stored like any other column
+        assert_eq!(
+            rows(&mut mb, "SELECT name FROM items WHERE total > 10;"),
+            vec!["a"]
+        );
+
+        // The computed value has to pass the constraints of the column
+        match mb.eval_query("INSERT INTO items VALUES (1, 1, DEFAULT, DEFAULT, NULL);") {
+            Err(BackendError::NotNullViolation(_)) => {}
+            result => panic!("Expected a NOT NULL violation, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_generated_column_writes() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (a INT, b INT GENERATED ALWAYS AS (a + 1) STORED);")
+            .unwrap();
+        for query in &[
+            "INSERT INTO t VALUES (1, 2);",
+            "INSERT INTO t VALUES (1, NULL);",
+            "INSERT INTO t VALUES (1, DEFAULT), (2, 3);",
+        ] {
+            match mb.eval_query(query) {
+                Err(BackendError::GeneratedAlways(msg)) => assert_eq!(
+                    msg, "Cannot insert a non-DEFAULT value into column \"b\".",
+                    "{}",
+                    query
+                ),
+                result => panic!("{}: expected an error, got {:?}", query, result),
+            }
+        }
+        assert!(rows(&mut mb, "SELECT * FROM t;").is_empty());
+    }
+
+    #[test]
+    fn test_generated_columns_in_batches() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (price INT, doubled INT GENERATED ALWAYS AS (price * 2) STORED);",
+        )
+        .unwrap();
+        let int = |value| SqlValue::Numeric(SqlNumeric::Int { value });
+
+        let insert = mb.prepare("INSERT INTO t VALUES ($1, $2);").unwrap();
+        match insert.execute_many(&mut mb, vec![vec![int(6), int(999)]]) {
+            Err(BackendError::GeneratedAlways(msg)) => assert_eq!(
+                msg,
+                "Parameter set 1 failed: \
+                Cannot insert a non-DEFAULT value into column \"doubled\"."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        assert!(rows(&mut mb, "SELECT * FROM t;").is_empty());
+
+        let insert = mb.prepare("INSERT INTO t VALUES ($1, DEFAULT);").unwrap();
+        insert
+            .execute_many(&mut mb, vec![vec![int(6)], vec![int(7)]])
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), vec!["6|12", "7|14"]);
+    }
+
+    #[test]
+    fn test_invalid_generated_columns() {
+        let tests = vec![
+            (
+                "CREATE TABLE u (a INT, b INT GENERATED ALWAYS AS (a + (SELECT 1)) STORED);",
+                "Cannot use subquery in column generation expression.",
+            ),
+            (
+                "CREATE TABLE u (a INT, b INT GENERATED ALWAYS AS (sum(a)) STORED);",
+                "Aggregate functions are not allowed in column generation expressions.",
+            ),
+            (
+                "CREATE TABLE u (a INT, b INT GENERATED ALWAYS AS (c) STORED);",
+                "c: Column does not exist.",
+            ),
+            (
+                "CREATE TABLE u (a INT, b INT GENERATED ALWAYS AS (t.a) STORED);",
+                "t: Column generation expressions can only use columns of their own table.",
+            ),
+            (
+                "CREATE TABLE u (a TEXT GENERATED ALWAYS AS (now()) STORED);",
+                "now: Generation expression is not immutable.",
+            ),
+            (
+                "CREATE TABLE u (a INT, b INT GENERATED ALWAYS AS (a) STORED, c INT GENERATED ALWAYS AS (b) STORED);",
+                "Cannot use generated column \"b\" in the generation expression of \"c\".",
+            ),
+            (
+                "CREATE TABLE u (a INT, b INT DEFAULT 1 GENERATED ALWAYS AS (a) STORED);",
+                "Both default and generation expression specified for column \"b\" of table \"u\".",
+            ),
+        ];
+
+        let mut mb = MemoryBackend::new();
+        for (query, expected) in tests {
+            match mb.eval_query(query) {
+                Err(err) => assert_eq!(err.message(), expected, "{}", query),
+                Ok(_) => panic!("{}: expected an error", query),
+            }
+        }
+        assert!(mb.table("u").is_none());
+    }
+
+    #[test]
+    fn test_drop_generated_dependency() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (a INT, b INT GENERATED ALWAYS AS (a * 2) STORED, c INT);
+            CREATE INDEX t_b ON t (b);
+            INSERT INTO t VALUES (1, DEFAULT, 5);",
+        )
+        .unwrap();
+
+        match mb.eval_query("ALTER TABLE t DROP COLUMN a;") {
+            Err(BackendError::DependentObjectsStillExist(msg)) => assert_eq!(
+                msg,
+                "Cannot drop column \"a\" of table \"t\" because column \"b\" depends on it."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        // CASCADE drops the generated column and its index along with it
+        mb.eval_query("ALTER TABLE t DROP COLUMN a CASCADE;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), vec!["5"]);
+        assert!(mb.index_table("t_b").is_none());
+        // Dropping the generated column itself leaves the rest alone
+        mb.eval_query(
+            "CREATE TABLE g (a INT, b INT GENERATED ALWAYS AS (a * 2) STORED);
+            ALTER TABLE g DROP COLUMN b;
+            INSERT INTO g VALUES (4);",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM g;"), vec!["4"]);
+    }
+}
@@ -0,0 +1,554 @@
+use super::warnings::{enter_warnings, warn, NAME_TOO_LONG};
+use super::MemoryBackend;
+use crate::ast::*;
+use crate::backend::{BackendError, Warning};
+use crate::lexer::Token;
+
+// The longest identifier in bytes, NAMEDATALEN - 1 as Postgres is usually built
+pub const DEFAULT_MAX_IDENTIFIER_LENGTH: usize = 63;
+
+// What becomes of an identifier longer than `BackendConfig::max_identifier_length`.
+// Postgres cuts it down with a notice, which stays the default, 'strict' fails instead.
+// `postgrustql.identifier_mode` to SET, as 'truncate' or 'strict'.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum IdentifierMode {
+    #[default]
+    Truncate,
+    Strict,
+}
+
+impl IdentifierMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "truncate" => Some(IdentifierMode::Truncate),
+            "strict" => Some(IdentifierMode::Strict),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdentifierMode::Truncate => "truncate",
+            IdentifierMode::Strict => "strict",
+        }
+    }
+}
+
+// The first `max_length` bytes of a name, fewer when that would split a character
+pub(super) fn truncate_identifier(name: &str, max_length: usize) -> &str {
+    if name.len() <= max_length {
+        return name;
+    }
+    let mut end = max_length;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+impl MemoryBackend {
+    // Cuts the names of tables, columns, indexes and constraints and the aliases of a
+    // statement down to the longest identifier, before anything is looked up by them.
+    // Two names the same up to there are the same name, as they are in Postgres.
+    // Statements the recovering parser had to make names or expressions up in are
+    // refused here, before they can run.
+    pub(super) fn bind_identifiers(&self, statement: &mut Statement) -> Result<(), BackendError> {
+        let (max_length, mode) = (self.max_identifier_length, self.identifier_mode);
+        if untouched_name(statement) == Some(PLACEHOLDER_NAME) {
+            return Err(placeholder_error());
+        }
+        visit_statement_names(statement, &mut |name: &mut String| {
+            if name == PLACEHOLDER_NAME {
+                return Err(placeholder_error());
+            }
+            if name.len() <= max_length {
+                return Ok(());
+            }
+            let truncated = truncate_identifier(name, max_length).to_string();
+            if mode == IdentifierMode::Strict {
+                return Err(BackendError::NameTooLong(format!(
+                    "Identifier \"{}\" is longer than {} bytes.",
+                    name, max_length
+                )));
+            }
+            warn(Warning::new(
+                NAME_TOO_LONG,
+                format!(
+                    "Identifier \"{}\" will be truncated to \"{}\".",
+                    name, truncated
+                ),
+            ));
+            *name = truncated;
+            Ok(())
+        })
+    }
+
+    // Like `bind_identifiers` for a statement that isn't run yet, nothing would carry
+    // the warnings
+    pub(super) fn bind_identifiers_quietly(
+        &self,
+        statement: &mut Statement,
+    ) -> Result<(), BackendError> {
+        let _warnings = enter_warnings();
+        self.bind_identifiers(statement)
+    }
+}
+
+fn placeholder_error() -> BackendError {
+    BackendError::SyntaxError(
+        "Statement has parts missing, it was parsed with recovery.".to_string(),
+    )
+}
+
+// The name of a setting, channel or cursor, which is kept whole however long it is
+fn untouched_name(statement: &Statement) -> Option<&str> {
+    let name = match statement {
+        Statement::SetStatement(set) => Some(&set.name),
+        Statement::ShowStatement(show) => Some(&show.name),
+        Statement::ResetStatement(reset) => reset.name.as_ref(),
+        Statement::ListenStatement(listen) => Some(&listen.channel),
+        Statement::NotifyStatement(notify) => Some(&notify.channel),
+        Statement::UnlistenStatement(unlisten) => unlisten.channel.as_ref(),
+        Statement::DeclareCursorStatement(declare) => Some(&declare.name),
+        Statement::FetchStatement(fetch) => Some(&fetch.name),
+        Statement::CloseStatement(close) => close.name.as_ref(),
+        _ => None,
+    };
+    name.map(String::as_str)
+}
+
+type NameVisitor<'a> = dyn FnMut(&mut String) -> Result<(), BackendError> + 'a;
+
+fn visit_names(names: &mut [String], f: &mut NameVisitor) -> Result<(), BackendError> {
+    for name in names.iter_mut() {
+        f(name)?;
+    }
+    Ok(())
+}
+
+fn visit_option_name(name: &mut Option<String>, f: &mut NameVisitor) -> Result<(), BackendError> {
+    match name {
+        Some(name) => f(name),
+        None => Ok(()),
+    }
+}
+
+fn visit_statement_names(
+    statement: &mut Statement,
+    f: &mut NameVisitor,
+) -> Result<(), BackendError> {
+    match statement {
+        Statement::SelectStatement(select) => visit_select_names(select, f),
+        Statement::CreateTableStatement(create_table) => {
+            f(&mut create_table.name)?;
+            for column in create_table.cols.iter_mut() {
+                f(&mut column.name)?;
+                if let Some(default) = column.default.as_mut() {
+                    visit_expression_names(default, f)?;
+                }
+                if let Some(generated) = column.generated.as_mut() {
+                    visit_expression_names(generated, f)?;
+                }
+            }
+            for check in create_table.checks.iter_mut() {
+                visit_option_name(&mut check.name, f)?;
+                visit_expression_names(&mut check.expression, f)?;
+            }
+            Ok(())
+        }
+        Statement::CreateTableAsStatement(create_table_as) => {
+            f(&mut create_table_as.name)?;
+            visit_names(&mut create_table_as.columns, f)?;
+            visit_select_names(&mut create_table_as.query, f)
+        }
+        Statement::CreateIndexStatement(create_index) => {
+            f(&mut create_index.name)?;
+            f(&mut create_index.table)?;
+            visit_expression_names(&mut create_index.expression, f)
+        }
+        Statement::DropTableStatement(drop_table) => f(&mut drop_table.name),
+        Statement::DropIndexStatement(drop_index) => f(&mut drop_index.name),
+        Statement::CreateSequenceStatement(create_sequence) => f(&mut create_sequence.name),
+        Statement::DropSequenceStatement(drop_sequence) => f(&mut drop_sequence.name),
+        Statement::CreateViewStatement(create_view) => {
+            f(&mut create_view.name)?;
+            visit_select_names(&mut create_view.query, f)
+        }
+        Statement::DropViewStatement(drop_view) => f(&mut drop_view.name),
+        Statement::CreateTriggerStatement(create_trigger) => {
+            f(&mut create_trigger.name)?;
+            f(&mut create_trigger.table)?;
+            visit_statement_names(&mut create_trigger.action, f)
+        }
+        Statement::DropTriggerStatement(drop_trigger) => {
+            f(&mut drop_trigger.name)?;
+            f(&mut drop_trigger.table)
+        }
+        Statement::AlterTableStatement(alter_table) => {
+            f(&mut alter_table.name)?;
+            match &mut alter_table.action {
+                AlterTableAction::DropColumn { name, .. } => f(name),
+            }
+        }
+        Statement::ReindexStatement(reindex) => f(&mut reindex.table),
+        Statement::VacuumStatement(vacuum) => visit_option_name(&mut vacuum.table, f),
+        Statement::ExplainStatement(explain) => visit_select_names(&mut explain.select, f),
+        Statement::InsertStatement(insert) => {
+            f(&mut insert.table)?;
+            for value in insert.rows.iter_mut().flatten() {
+                visit_expression_names(value, f)?;
+            }
+            if let Some(query) = insert.query.as_mut() {
+                visit_select_names(query, f)?;
+            }
+            visit_item_names(&mut insert.returning, f)
+        }
+        Statement::UpdateStatement(update) => {
+            f(&mut update.table)?;
+            visit_option_name(&mut update.as_clause, f)?;
+            for assignment in update.assignments.iter_mut() {
+                f(&mut assignment.column)?;
+                visit_expression_names(&mut assignment.value, f)?;
+            }
+            visit_expression_names(&mut update.where_clause, f)?;
+            for order_by in update.order_by.iter_mut() {
+                visit_expression_names(&mut order_by.exp, f)?;
+            }
+            visit_item_names(&mut update.returning, f)
+        }
+        Statement::DeleteStatement(delete) => {
+            f(&mut delete.table)?;
+            visit_option_name(&mut delete.as_clause, f)?;
+            visit_expression_names(&mut delete.where_clause, f)?;
+            for order_by in delete.order_by.iter_mut() {
+                visit_expression_names(&mut order_by.exp, f)?;
+            }
+            visit_item_names(&mut delete.returning, f)
+        }
+        Statement::WithStatement(with) => {
+            for query in with.queries.iter_mut() {
+                f(&mut query.name)?;
+                visit_names(&mut query.columns, f)?;
+                visit_statement_names(&mut query.statement, f)?;
+            }
+            visit_statement_names(&mut with.statement, f)
+        }
+        Statement::DeclareCursorStatement(declare) => visit_select_names(&mut declare.query, f),
+        Statement::SetStatement(_)
+        | Statement::ShowStatement(_)
+        | Statement::ResetStatement(_)
+        | Statement::ListenStatement(_)
+        | Statement::NotifyStatement(_)
+        | Statement::UnlistenStatement(_)
+        | Statement::FetchStatement(_)
+        | Statement::CloseStatement(_)
+        | Statement::TransactionStatement(_) => Ok(()),
+    }
+}
+
+fn visit_item_names(items: &mut [SelectItem], f: &mut NameVisitor) -> Result<(), BackendError> {
+    for item in items.iter_mut() {
+        visit_expression_names(&mut item.expression, f)?;
+        visit_option_name(&mut item.as_clause, f)?;
+    }
+    Ok(())
+}
+
+fn visit_select_names(
+    select: &mut SelectStatement,
+    f: &mut NameVisitor,
+) -> Result<(), BackendError> {
+    visit_item_names(&mut select.items, f)?;
+    for source in select.from.iter_mut() {
+        let joins = visit_source_names(source, f)?;
+        for join in joins.iter_mut() {
+            visit_source_names(&mut join.source, f)?;
+            visit_expression_names(&mut join.on, f)?;
+        }
+    }
+    visit_expression_names(&mut select.where_clause, f)?;
+    for exp in select.group_by.iter_mut() {
+        visit_expression_names(exp, f)?;
+    }
+    visit_expression_names(&mut select.having, f)?;
+    for order_by in select.order_by.iter_mut() {
+        visit_expression_names(&mut order_by.exp, f)?;
+    }
+    Ok(())
+}
+
+// Visits the names of a FROM item, giving its joins to visit next
+fn visit_source_names<'a>(
+    source: &'a mut RowDataSource,
+    f: &mut NameVisitor,
+) -> Result<&'a mut Vec<JoinClause>, BackendError> {
+    match source {
+        RowDataSource::SubSelect {
+            select,
+            as_clause,
+            joins,
+            ..
+        } => {
+            visit_select_names(select, f)?;
+            f(as_clause)?;
+            Ok(joins)
+        }
+        RowDataSource::Table {
+            table_name,
+            as_clause,
+            sample,
+            joins,
+        } => {
+            f(table_name)?;
+            visit_option_name(as_clause, f)?;
+            if let Some(sample) = sample {
+                visit_expression_names(&mut sample.percentage, f)?;
+                if let Some(repeatable) = sample.repeatable.as_mut() {
+                    visit_expression_names(repeatable, f)?;
+                }
+            }
+            Ok(joins)
+        }
+        RowDataSource::Values {
+            rows,
+            as_clause,
+            column_aliases,
+            joins,
+        } => {
+            for exp in rows.iter_mut().flatten() {
+                visit_expression_names(exp, f)?;
+            }
+            f(as_clause)?;
+            visit_names(column_aliases, f)?;
+            Ok(joins)
+        }
+        RowDataSource::Function {
+            call,
+            as_clause,
+            column_aliases,
+            joins,
+            ..
+        } => {
+            for arg in call.args.iter_mut() {
+                visit_expression_names(arg, f)?;
+            }
+            visit_option_name(as_clause, f)?;
+            visit_names(column_aliases, f)?;
+            Ok(joins)
+        }
+    }
+}
+
+fn visit_expression_names(
+    expression: &mut Expression,
+    f: &mut NameVisitor,
+) -> Result<(), BackendError> {
+    match expression {
+        // A column named on its own
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => {
+            let mut name = value.to_string();
+            f(&mut name)?;
+            if name.len() != value.len() {
+                *value = name.into();
+            }
+            Ok(())
+        }
+        Expression::TableColumn(column) => {
+            f(&mut column.col_name)?;
+            visit_option_name(&mut column.table_name, f)
+        }
+        Expression::ProcessedTableColumn(column) => visit_option_name(&mut column.col_name, f),
+        Expression::Binary(binary) => {
+            visit_expression_names(&mut binary.first, f)?;
+            visit_expression_names(&mut binary.second, f)
+        }
+        Expression::Unary(unary) => visit_expression_names(&mut unary.first, f),
+        Expression::SubSelect(select) | Expression::Exists(select) => visit_select_names(select, f),
+        Expression::Quantified(quantified) => {
+            visit_expression_names(&mut quantified.first, f)?;
+            match &mut quantified.set {
+                QuantifiedSet::SubSelect(select) => visit_select_names(select, f),
+                QuantifiedSet::Values(_) => Ok(()),
+            }
+        }
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            visit_expression_names(data, f)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                visit_expression_names(arg, f)?;
+            }
+            if let Some(filter) = call.filter.as_mut() {
+                visit_expression_names(filter, f)?;
+            }
+            if let Some(over) = &mut call.over {
+                for exp in over.partition_by.iter_mut() {
+                    visit_expression_names(exp, f)?;
+                }
+                for order_by in over.order_by.iter_mut() {
+                    visit_expression_names(&mut order_by.exp, f)?;
+                }
+            }
+            Ok(())
+        }
+        Expression::Literal(_) | Expression::Empty => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod identifiers_tests {
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::{BackendConfig, IdentifierMode, MemoryBackend};
+    use crate::parser::parse_recovering;
+    use crate::sql_types::{SqlNumeric, SqlValue};
+
+    fn warnings(mb: &mut MemoryBackend, query: &str) -> Vec<String> {
+        mb.eval_query(query)
+            .unwrap()
+            .iter()
+            .flat_map(EvalResult::warnings)
+            .map(|warning| format!("{} {}", warning.code, warning))
+            .collect()
+    }
+
+    #[test]
+    fn test_truncated_identifiers() {
+        let mut mb = MemoryBackend::new();
+        let table = format!("t{}", "a".repeat(70));
+        let column = format!("c{}", "b".repeat(70));
+        assert_eq!(
+            warnings(
+                &mut mb,
+                &format!("CREATE TABLE {} (id INT, {} TEXT);", table, column)
+            ),
+            vec![
+                format!(
+                    "42622 Identifier \"{}\" will be truncated to \"{}\".",
+                    table,
+                    &table[..63]
+                ),
+                format!(
+                    "42622 Identifier \"{}\" will be truncated to \"{}\".",
+                    column,
+                    &column[..63]
+                ),
+            ]
+        );
+        match mb.table(&table[..63]) {
+            Some(table) => assert_eq!(table.columns[1], column[..63]),
+            None => panic!("Expected the table under its truncated name"),
+        }
+
+        // Names the same in their first 63 bytes are the same name
+        let other_table = format!("{}zzz", &table[..63]);
+        let other_column = format!("{}zzz", &column[..63]);
+        mb.eval_query(&format!(
+            "INSERT INTO {} VALUES (1, 'Baam'); \
+            CREATE INDEX {} ON {} (id);",
+            other_table,
+            "i".repeat(64),
+            table
+        ))
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                &format!(
+                    "SELECT {}.{} AS {} FROM {} AS {} WHERE id = 1;",
+                    "x".repeat(64),
+                    other_column,
+                    "y".repeat(64),
+                    table,
+                    "x".repeat(65)
+                )
+            ),
+            vec!["Baam"]
+        );
+        assert_eq!(
+            mb.eval_query(&format!("CREATE TABLE {} (id INT);", other_table))
+                .unwrap_err()
+                .code(),
+            "42P07"
+        );
+        mb.eval_query(&format!("DROP INDEX {};", "i".repeat(63)))
+            .unwrap();
+
+        // Never in the middle of a character
+        let name = format!("{}é", "a".repeat(62));
+        mb.eval_query(&format!("CREATE TABLE \"{}\" (id INT);", name))
+            .unwrap();
+        assert!(mb.table(&"a".repeat(62)).is_some());
+    }
+
+    #[test]
+    fn test_strict_identifiers() {
+        let config = BackendConfig::new()
+            .max_identifier_length(8)
+            .identifier_mode(IdentifierMode::Strict);
+        let mut mb = MemoryBackend::with_config(config);
+        mb.eval_query("CREATE TABLE people (id INT);").unwrap();
+        let err = mb
+            .eval_query("CREATE TABLE employees (id INT);")
+            .unwrap_err();
+        assert_eq!(err.code(), "42622");
+        assert_eq!(
+            err.to_string(),
+            "Identifier \"employees\" is longer than 8 bytes."
+        );
+        assert!(mb.table("employee").is_none());
+        let tests = vec![
+            "SELECT id AS identifier FROM people;",
+            "CREATE TABLE p (id INT, CONSTRAINT positive_id CHECK (id > 0));",
+            "CREATE INDEX people_id ON people (id);",
+            "WITH long_name AS (SELECT 1) SELECT 1;",
+        ];
+        for query in tests {
+            match mb.eval_query(query) {
+                Err(err) => assert_eq!(err.code(), "42622", "{}", query),
+                Ok(_) => panic!("Expected {} to fail", query),
+            }
+        }
+        assert!(mb.prepare("SELECT * FROM employees;").is_err());
+
+        mb.eval_query("SET postgrustql.identifier_mode = 'truncate';")
+            .unwrap();
+        mb.eval_query("CREATE TABLE employees (id INT);").unwrap();
+        let prepared = mb.prepare("INSERT INTO employeez VALUES ($1);").unwrap();
+        prepared
+            .execute(&mut mb, &[SqlValue::Numeric(SqlNumeric::Int { value: 1 })])
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM employees;"), vec!["1"]);
+
+        assert_eq!(rows(&mut mb, "SHOW max_identifier_length;"), vec!["8"]);
+        assert_eq!(
+            mb.eval_query("SET max_identifier_length = 100;")
+                .unwrap_err()
+                .code(),
+            "55P02"
+        );
+    }
+
+    #[test]
+    fn test_placeholders_dont_run() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE people (id INT);").unwrap();
+        let (statements, errors) =
+            parse_recovering("DELETE FROM people WHERE; LISTEN; INSERT INTO people VALUES (1);");
+        assert_eq!((statements.len(), errors.len()), (3, 2));
+        let mut statements = statements.into_iter();
+        for statement in statements.by_ref().take(2) {
+            let err = mb.eval_statement(statement).unwrap_err();
+            assert_eq!(err.code(), "42601");
+            assert_eq!(
+                err.message(),
+                "Statement has parts missing, it was parsed with recovery."
+            );
+        }
+        // What was parsed whole runs as usual
+        mb.eval_statement(statements.next().unwrap()).unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), vec!["1"]);
+    }
+}
@@ -0,0 +1,456 @@
+use super::{source_joins, MemoryBackend, Table};
+use crate::ast::*;
+use crate::lexer::{Token, TRUE_KEYWORD};
+
+// Postgres' guesses for conditions it knows nothing about but the operator
+const EQUALITY_SELECTIVITY: f64 = 0.005;
+const INEQUALITY_SELECTIVITY: f64 = 1.0 / 3.0;
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+
+// The order a FROM item and the sources of its joins are joined in. Relation 0 is the FROM
+// item, relation `i` the source of its `i - 1`th join.
+#[derive(Debug, PartialEq)]
+pub(super) struct JoinPlan<'a> {
+    pub(super) order: Vec<usize>,
+    // How each relation after the first is joined to those before it
+    pub(super) steps: Vec<JoinStep<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct JoinStep<'a> {
+    pub(super) kind: JoinKind,
+    // Conditions to join on, all of them have to hold. No conditions join every pair of rows.
+    pub(super) on: Vec<&'a Expression>,
+}
+
+impl<'a> JoinPlan<'a> {
+    // The joins as written
+    fn syntactic(joins: &'a [JoinClause]) -> Self {
+        JoinPlan {
+            order: (0..=joins.len()).collect(),
+            steps: joins
+                .iter()
+                .map(|join| JoinStep {
+                    kind: join.kind.clone(),
+                    on: vec![&join.on],
+                })
+                .collect(),
+        }
+    }
+
+    pub(super) fn is_reordered(&self) -> bool {
+        self.order
+            .iter()
+            .enumerate()
+            .any(|(i, relation)| i != *relation)
+    }
+}
+
+impl JoinStep<'_> {
+    // The conditions as one expression
+    pub(super) fn condition(&self) -> Expression {
+        let mut conditions = self.on.iter().map(|on| (*on).clone());
+        let first = match conditions.next() {
+            Some(first) => first,
+            None => return Expression::new_literal_bool(TRUE_KEYWORD.to_string()),
+        };
+        conditions.fold(first, |all, condition| {
+            Expression::Binary(BinaryExpression {
+                first: Box::new(all),
+                second: Box::new(condition),
+                operand: Token::And,
+            })
+        })
+    }
+}
+
+// The source of relation `relation` of a FROM item
+pub(super) fn join_relation(from: &RowDataSource, relation: usize) -> &RowDataSource {
+    match relation {
+        0 => from,
+        relation => &source_joins(from)[relation - 1].source,
+    }
+}
+
+// A join condition or WHERE condition along with the relations whose columns it reads
+struct Condition<'a> {
+    expression: &'a Expression,
+    relations: Vec<usize>,
+    selectivity: f64,
+}
+
+impl MemoryBackend {
+    // Inner joins of three tables or more are joined smallest estimated result first,
+    // starting with the pair that gives the fewest rows. Only row counts are known, so the
+    // rows a condition keeps are guessed from its operator alone, and an equality of two
+    // columns is taken to match each row of the larger table once. Outer joins, and
+    // joins of anything but tables, keep the order they are written in, as do joins on
+    // columns whose name more than one of the tables has, since columns are found by
+    // name alone.
+    pub(super) fn plan_joins<'a>(
+        &self,
+        from: &'a RowDataSource,
+        where_clause: &Expression,
+    ) -> JoinPlan<'a> {
+        let joins = source_joins(from);
+        let syntactic = JoinPlan::syntactic(joins);
+        if !self.reorder_joins
+            || joins.len() < 2
+            || joins.iter().any(|join| join.kind != JoinKind::Inner)
+        {
+            return syntactic;
+        }
+
+        let mut tables: Vec<&Table> = Vec::with_capacity(joins.len() + 1);
+        let mut row_counts: Vec<f64> = Vec::with_capacity(joins.len() + 1);
+        for relation in 0..=joins.len() {
+            match join_relation(from, relation) {
+                RowDataSource::Table {
+                    table_name, sample, ..
+                } => match self.table(table_name) {
+                    // A sampled table gives about its share of the rows
+                    Some(table) => {
+                        let fraction = self.sampled_fraction(sample.as_ref());
+                        tables.push(table);
+                        row_counts.push(table.rows.len() as f64 * fraction);
+                    }
+                    None => return syntactic,
+                },
+                _ => return syntactic,
+            }
+        }
+        let relations_of = |expression: &Expression| -> Option<Vec<usize>> {
+            let mut names = vec![];
+            column_names(expression, &mut names)?;
+            let mut relations = vec![];
+            for name in names {
+                let mut owners =
+                    (0..tables.len()).filter(|relation| tables[*relation].columns.contains(&name));
+                let owner = owners.next()?;
+                if owners.next().is_some() {
+                    return None;
+                }
+                if !relations.contains(&owner) {
+                    relations.push(owner);
+                }
+            }
+            Some(relations)
+        };
+
+        let mut join_conditions = vec![];
+        for join in joins {
+            let relations = match relations_of(&join.on) {
+                Some(relations) => relations,
+                None => return syntactic,
+            };
+            let selectivity = match (&join.on, relations.as_slice()) {
+                (Expression::Binary(binary), [first, second]) if binary.operand == Token::Equal => {
+                    1.0 / row_counts[*first].max(row_counts[*second]).max(1.0)
+                }
+                (expression, _) => selectivity(expression),
+            };
+            join_conditions.push(Condition {
+                expression: &join.on,
+                relations,
+                selectivity,
+            });
+        }
+
+        // Rows each table is left with once the WHERE conditions on it alone are applied
+        let mut estimates = row_counts.clone();
+        let mut conjuncts = vec![];
+        split_conjuncts(where_clause, &mut conjuncts);
+        for conjunct in conjuncts {
+            if let Some([relation]) = relations_of(conjunct).as_deref() {
+                estimates[*relation] *= selectivity(conjunct);
+            }
+        }
+
+        // Rows of joining `next` to the relations in `joined`, and whether a condition
+        // links them
+        let join_estimate = |joined: &[usize], rows: f64, next: usize| -> (bool, f64) {
+            let mut rows = rows * estimates[next];
+            let mut linked = false;
+            for condition in &join_conditions {
+                if condition.relations.contains(&next)
+                    && condition
+                        .relations
+                        .iter()
+                        .all(|relation| *relation == next || joined.contains(relation))
+                {
+                    rows *= condition.selectivity;
+                    linked |= condition.relations.len() > 1;
+                }
+            }
+            (linked, rows.max(1.0))
+        };
+
+        // Pairs and then relations joined to them come first when a condition links
+        // them, then by the rows they are expected to give, then in the order written
+        let mut order: Vec<usize> = vec![];
+        let mut rows = 0.0;
+        let mut best = (true, f64::INFINITY);
+        for first in 0..tables.len() {
+            for second in first + 1..tables.len() {
+                let (linked, estimate) = join_estimate(&[first], estimates[first], second);
+                if (!linked, estimate) < best {
+                    best = (!linked, estimate);
+                    rows = estimate;
+                    order = if estimates[second] < estimates[first] {
+                        vec![second, first]
+                    } else {
+                        vec![first, second]
+                    };
+                }
+            }
+        }
+        while order.len() < tables.len() {
+            let mut best = (true, f64::INFINITY);
+            let mut chosen = None;
+            for next in (0..tables.len()).filter(|relation| !order.contains(relation)) {
+                let (linked, estimate) = join_estimate(&order, rows, next);
+                if (!linked, estimate) < best {
+                    best = (!linked, estimate);
+                    chosen = Some(next);
+                }
+            }
+            match chosen {
+                Some(next) => {
+                    rows = best.1;
+                    order.push(next);
+                }
+                None => break,
+            }
+        }
+
+        // Each condition goes to the first join that has all the relations it reads
+        let step_of = |condition: &Condition| {
+            (1..order.len()).find(|step| {
+                condition
+                    .relations
+                    .iter()
+                    .all(|relation| order[..=*step].contains(relation))
+            })
+        };
+        let plan = JoinPlan {
+            steps: (1..order.len())
+                .map(|step| JoinStep {
+                    kind: JoinKind::Inner,
+                    on: join_conditions
+                        .iter()
+                        .filter(|condition| step_of(condition) == Some(step))
+                        .map(|condition| condition.expression)
+                        .collect(),
+                })
+                .collect(),
+            order,
+        };
+        if plan.is_reordered() {
+            plan
+        } else {
+            syntactic
+        }
+    }
+}
+
+fn selectivity(expression: &Expression) -> f64 {
+    match expression {
+        Expression::Binary(binary) => match binary.operand {
+            Token::Equal => EQUALITY_SELECTIVITY,
+            Token::LessThan
+            | Token::LessThanOrEqual
+            | Token::GreaterThan
+            | Token::GreaterThanOrEqual => INEQUALITY_SELECTIVITY,
+            _ => DEFAULT_SELECTIVITY,
+        },
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+pub(super) fn split_conjuncts<'a>(expression: &'a Expression, conjuncts: &mut Vec<&'a Expression>) {
+    match expression {
+        Expression::Binary(binary) if binary.operand == Token::And => {
+            split_conjuncts(&binary.first, conjuncts);
+            split_conjuncts(&binary.second, conjuncts);
+        }
+        Expression::Empty => {}
+        expression => conjuncts.push(expression),
+    }
+}
+
+// The names of the columns an expression reads, None if it has a subquery, whose names
+// could be those of its own tables
+fn column_names(expression: &Expression, names: &mut Vec<String>) -> Option<()> {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => names.push(value.to_string()),
+        Expression::TableColumn(table_column) => names.push(table_column.col_name.clone()),
+        Expression::Binary(binary) => {
+            column_names(&binary.first, names)?;
+            column_names(&binary.second, names)?;
+        }
+        Expression::Unary(unary) => column_names(&unary.first, names)?,
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            column_names(data, names)?
+        }
+        Expression::FunctionCall(call) => {
+            for arg in &call.args {
+                column_names(arg, names)?;
+            }
+        }
+        Expression::Quantified(quantified) => match quantified.set {
+            QuantifiedSet::Values(_) => column_names(&quantified.first, names)?,
+            QuantifiedSet::SubSelect(_) => return None,
+        },
+        Expression::SubSelect(_) | Expression::Exists(_) | Expression::ProcessedTableColumn(_) => {
+            return None
+        }
+        Expression::Literal(_) | Expression::Empty => {}
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod join_order_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE big AS SELECT n AS b_id, n * 10 AS b_val FROM generate_series(1, 200) AS g(n);
+        CREATE TABLE mid AS SELECT n AS m_id, n * 3 AS m_b FROM generate_series(1, 50) AS g(n);
+        CREATE TABLE small AS SELECT n AS s_id, n * 2 AS s_m, n / 5 AS s_flag FROM generate_series(1, 20) AS g(n);";
+
+    const SKEWED_JOIN: &str = "SELECT b_val, s_id FROM big \
+        INNER JOIN mid ON m_b = b_id \
+        INNER JOIN small ON s_m = m_id \
+        WHERE s_flag = 1;";
+
+    #[test]
+    fn test_join_order() {
+        let mb = backend(SETUP);
+        let order = |query: &str| match crate::parser::parse(query).unwrap().statements.pop() {
+            Some(Statement::SelectStatement(select)) => {
+                mb.plan_joins(&select.from[0], &select.where_clause).order
+            }
+            _ => panic!("Expected a select statement"),
+        };
+        let tests =
+            vec![
+            (SKEWED_JOIN, vec![2, 1, 0]),
+            (
+                "SELECT * FROM small INNER JOIN mid ON s_m = m_id INNER JOIN big ON m_b = b_id;",
+                vec![0, 1, 2],
+            ),
+            (
+                "SELECT * FROM big INNER JOIN mid ON m_b = b_id INNER JOIN small ON s_m = m_id;",
+                vec![2, 1, 0],
+            ),
+            // Outer joins, joins of two tables, of subqueries and on names more than one
+            // table has keep the order they are written in
+            (
+                "SELECT * FROM big INNER JOIN mid ON m_b = b_id LEFT JOIN small ON s_m = m_id;",
+                vec![0, 1, 2],
+            ),
+            ("SELECT * FROM big INNER JOIN small ON s_m = b_id;", vec![0, 1]),
+            (
+                "SELECT * FROM big INNER JOIN mid ON m_b = b_id \
+                INNER JOIN (SELECT * FROM small) AS s ON s_m = m_id;",
+                vec![0, 1, 2],
+            ),
+            (
+                "SELECT * FROM big INNER JOIN mid ON m_b = b_id \
+                INNER JOIN big AS other ON b_val = m_id;",
+                vec![0, 1, 2],
+            ),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(order(query), expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_explain_join_order() {
+        let mut mb = backend(SETUP);
+        let plan = |mb: &mut MemoryBackend| rows(mb, &format!("EXPLAIN {}", SKEWED_JOIN));
+        assert_eq!(
+            plan(&mut mb),
+            [
+                "Result",
+                "  ->  Nested Loop",
+                "        ->  Nested Loop",
+                "              ->  Seq Scan on small",
+                "              ->  Seq Scan on mid",
+                "        ->  Seq Scan on big",
+            ]
+        );
+
+        // The order a query runs in is the one EXPLAIN shows
+        let analyzed = rows(&mut mb, &format!("EXPLAIN ANALYZE {}", SKEWED_JOIN));
+        let scans: Vec<&str> = analyzed
+            .iter()
+            .filter_map(|line| line.trim_start().strip_prefix("->  Seq Scan on "))
+            .map(|scan| scan.split(' ').next().unwrap_or(""))
+            .collect();
+        assert_eq!(scans, ["small", "mid", "big"]);
+
+        mb.eval_query("SET reorder_joins = off;").unwrap();
+        assert_eq!(
+            plan(&mut mb),
+            [
+                "Result",
+                "  ->  Nested Loop",
+                "        ->  Nested Loop",
+                "              ->  Seq Scan on big",
+                "              ->  Seq Scan on mid",
+                "        ->  Seq Scan on small",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reordered_results() {
+        let queries = vec![
+            SKEWED_JOIN,
+            "SELECT * FROM big INNER JOIN mid ON m_b = b_id INNER JOIN small ON s_m = m_id;",
+            "SELECT small.s_id, big.b_val FROM big INNER JOIN small ON s_id = b_id \
+            INNER JOIN mid ON m_id = s_id WHERE b_val > 50 ORDER BY s_id DESC;",
+            "SELECT s_flag, count(*) AS total FROM big INNER JOIN mid ON m_b = b_id \
+            INNER JOIN small ON s_m = m_id GROUP BY s_flag ORDER BY s_flag;",
+        ];
+        for query in queries {
+            let mut mb = backend(SETUP);
+            let reordered = rows(&mut mb, query);
+            mb.eval_query("SET reorder_joins = off;").unwrap();
+            let written = rows(&mut mb, query);
+            assert!(!written.is_empty(), "{}", query);
+            assert_eq!(reordered, written, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_shared_column_names() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE owners (id INT, name TEXT);
+            CREATE TABLE pets (id INT, owner INT, name TEXT);
+            INSERT INTO owners VALUES (1, 'Baam'), (2, 'Rachel');
+            INSERT INTO pets VALUES (1, 2, 'Rex'), (2, 1, 'Tom'), (3, 1, 'Kit');",
+        )
+        .unwrap();
+        // Qualified columns read from the FROM item they name, whichever comes first
+        let tests = vec![
+            (
+                "SELECT o.name, p.name FROM owners o JOIN pets p ON o.id = p.owner \
+                WHERE p.id > 1;",
+                vec!["Baam|Tom", "Baam|Kit"],
+            ),
+            (
+                "SELECT p.name FROM pets p, owners o WHERE p.owner = o.id AND o.name = 'Rachel';",
+                vec!["Rex"],
+            ),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(rows(&mut mb, query), expected, "{}", query);
+        }
+    }
+}
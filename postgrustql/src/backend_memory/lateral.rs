@@ -0,0 +1,306 @@
+use super::semi_join::{
+    column_references, select_expressions, select_expressions_mut, substitute, value_expression,
+};
+use super::stored_row::StoredRow;
+use super::timing::{scan_name, source_joins, PlanBuilder};
+use super::{MemoryBackend, Table};
+use crate::ast::*;
+use crate::backend::BackendError;
+use crate::lexer::Token;
+use crate::sql_types::SqlValue;
+
+// The FROM items of a subquery, which tell the columns it reads of its own from those of
+// the FROM items before it. The columns are only known when every item is a table.
+struct InnerScope {
+    sources: Vec<String>,
+    columns: Option<Vec<String>>,
+}
+
+pub(super) fn is_lateral(source: &RowDataSource) -> bool {
+    matches!(
+        source,
+        RowDataSource::SubSelect { lateral: true, .. }
+            | RowDataSource::Function { lateral: true, .. }
+    )
+}
+
+impl MemoryBackend {
+    fn inner_scope(&self, select: &SelectStatement) -> InnerScope {
+        let mut scope = InnerScope {
+            sources: vec![],
+            columns: Some(vec![]),
+        };
+        for from in &select.from {
+            let joined = source_joins(from).iter().map(|join| &join.source);
+            for source in std::iter::once(from).chain(joined) {
+                match source {
+                    RowDataSource::Table {
+                        table_name,
+                        as_clause,
+                        ..
+                    } => {
+                        // An alias hides the name of the table, which then is the outer one
+                        scope
+                            .sources
+                            .push(as_clause.clone().unwrap_or_else(|| table_name.clone()));
+                        match (self.table(table_name), &mut scope.columns) {
+                            (Some(table), Some(columns)) => {
+                                columns.extend_from_slice(&table.columns)
+                            }
+                            _ => scope.columns = None,
+                        }
+                    }
+                    RowDataSource::SubSelect { as_clause, .. }
+                    | RowDataSource::Values { as_clause, .. } => {
+                        scope.sources.push(as_clause.clone());
+                        scope.columns = None;
+                    }
+                    RowDataSource::Function {
+                        call, as_clause, ..
+                    } => {
+                        scope
+                            .sources
+                            .push(as_clause.clone().unwrap_or_else(|| call.name.clone()));
+                        scope.columns = None;
+                    }
+                }
+            }
+        }
+        scope
+    }
+
+    // The column references of a subquery or function in FROM that read the FROM items
+    // before it, along with the position of the column they read among `columns`, whose
+    // FROM items are `sources`
+    pub(super) fn outer_references(
+        &self,
+        source: &RowDataSource,
+        sources: &[String],
+        columns: &[String],
+    ) -> Vec<(Expression, usize)> {
+        let mut references = vec![];
+        let scope = match source {
+            RowDataSource::SubSelect { select, .. } => {
+                for expression in select_expressions(select) {
+                    column_references(expression, &mut references);
+                }
+                self.inner_scope(select)
+            }
+            // The arguments of a function have nothing else to read
+            RowDataSource::Function { call, .. } => {
+                for arg in &call.args {
+                    column_references(arg, &mut references);
+                }
+                InnerScope {
+                    sources: vec![],
+                    columns: Some(vec![]),
+                }
+            }
+            RowDataSource::Table { .. } | RowDataSource::Values { .. } => return vec![],
+        };
+
+        let mut outer: Vec<(Expression, usize)> = vec![];
+        for reference in references {
+            let (source, column): (Option<&String>, &str) = match reference {
+                Expression::TableColumn(TableColumn {
+                    table_name: Some(source),
+                    col_name,
+                }) if !scope.sources.contains(source) => (Some(source), col_name),
+                Expression::TableColumn(TableColumn {
+                    table_name: None,
+                    col_name,
+                }) => (None, col_name),
+                Expression::Literal(LiteralExpression {
+                    literal: Token::IdentifierValue { value },
+                }) => (None, value),
+                _ => continue,
+            };
+            let is_inner = match &scope.columns {
+                Some(inner) => inner.iter().any(|inner| inner == column),
+                None => true,
+            };
+            if source.is_none() && is_inner {
+                continue;
+            }
+            let position = (0..columns.len()).find(|idx| {
+                columns[*idx] == *column
+                    && source.is_none_or(|source| sources.get(*idx) == Some(source))
+            });
+            if let Some(position) = position {
+                if !outer.iter().any(|(known, _)| known == reference) {
+                    outer.push((reference.clone(), position));
+                }
+            }
+        }
+        outer
+    }
+
+    // Runs a LATERAL subquery or function for each of `rows`, with the values of the row
+    // in place of the columns it reads of them. Gives its name, all the rows it gave and
+    // the position of the row each of them was given for.
+    pub(super) fn lateral_rows(
+        &self,
+        source: &RowDataSource,
+        references: &[(Expression, usize)],
+        rows: &[StoredRow],
+        plan: &mut PlanBuilder,
+    ) -> Result<(String, Table, Vec<usize>), BackendError> {
+        let mut lateral: Option<(String, Table)> = None;
+        let mut origins = vec![];
+        // Without rows it still runs once, with NULLs, for its columns
+        let width = references.iter().map(|(_, idx)| idx + 1).max().unwrap_or(0);
+        let null_row = StoredRow::new(&vec![SqlValue::Null; width]);
+        let runs: Vec<&StoredRow> = match rows {
+            [] => vec![&null_row],
+            rows => rows.iter().collect(),
+        };
+        for (origin, row) in runs.into_iter().enumerate() {
+            let values = references
+                .iter()
+                .map(|(_, idx)| value_expression(&row.value(*idx)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let replace = |expression: &Expression| {
+                let idx = references
+                    .iter()
+                    .position(|(reference, _)| reference == expression)?;
+                Some(values[idx].clone())
+            };
+            let (name, table) = match source {
+                RowDataSource::SubSelect {
+                    select, as_clause, ..
+                } => {
+                    let mut select = select.as_ref().clone();
+                    for expression in select_expressions_mut(&mut select) {
+                        substitute(expression, &replace);
+                    }
+                    (as_clause.clone(), Table::from(self.select(select)?))
+                }
+                RowDataSource::Function {
+                    call,
+                    as_clause,
+                    column_aliases,
+                    ..
+                } => {
+                    let mut call = call.clone();
+                    for arg in call.args.iter_mut() {
+                        substitute(arg, &replace);
+                    }
+                    self.function_table(&call, as_clause, column_aliases)?
+                }
+                RowDataSource::Table { .. } | RowDataSource::Values { .. } => {
+                    return Err(BackendError::Internal(
+                        "Only subqueries and functions can be LATERAL".to_string(),
+                    ))
+                }
+            };
+            origins.extend(std::iter::repeat_n(origin, table.rows.len()));
+            match &mut lateral {
+                Some((_, all)) => all.rows.extend(table.rows),
+                None => lateral = Some((name, table)),
+            }
+        }
+        let (name, mut table) = match lateral {
+            Some(lateral) => lateral,
+            None => return Err(BackendError::Internal("LATERAL never ran".to_string())),
+        };
+        if rows.is_empty() {
+            table.rows.clear();
+            origins.clear();
+        }
+        plan.take_correlated_subplan(&self.timing);
+        plan.add(|| scan_name(source, None), table.rows.len(), 0);
+        Ok((name, table, origins))
+    }
+}
+
+#[cfg(test)]
+mod lateral_tests {
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE users (id INT, name TEXT);
+        CREATE TABLE orders (id INT, user_id INT, ts INT);
+        INSERT INTO users VALUES (1, 'ann'), (2, 'bob'), (3, 'cy');
+        INSERT INTO orders VALUES
+            (10, 1, 5), (11, 1, 9), (12, 2, 1), (13, 1, 7), (14, 1, 2), (15, 2, 4);";
+
+    #[test]
+    fn test_lateral_top_n_per_group() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT u.id, o.* FROM users u, LATERAL (
+                    SELECT * FROM orders WHERE orders.user_id = u.id ORDER BY ts DESC LIMIT 3
+                ) o;"
+            ),
+            vec!["1|11|1|9", "1|13|1|7", "1|10|1|5", "2|15|2|4", "2|12|2|1"]
+        );
+        // A LEFT JOIN keeps the users without orders
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT u.name, o.ts FROM users u LEFT JOIN LATERAL (
+                    SELECT ts, user_id FROM orders WHERE user_id = u.id ORDER BY ts LIMIT 1
+                ) o ON o.user_id = u.id;"
+            ),
+            vec!["ann|2", "bob|1", "cy|NULL"]
+        );
+    }
+
+    #[test]
+    fn test_lateral_function() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT u.name, g.n FROM users u, LATERAL generate_series(1, u.id) AS g(n)
+                WHERE u.id > 1;"
+            ),
+            vec!["bob|1", "bob|2", "cy|1", "cy|2", "cy|3"]
+        );
+        // Nothing to run for
+        assert!(rows(
+            &mut mb,
+            "SELECT * FROM users u, LATERAL generate_series(1, u.id) g WHERE u.id > 5;"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_reference_without_lateral() {
+        let mut mb = backend(SETUP);
+        for query in &[
+            "SELECT * FROM users u, (SELECT * FROM orders WHERE orders.user_id = u.id) o;",
+            "SELECT * FROM users u, generate_series(1, u.id) g;",
+        ] {
+            match mb.eval_query(query) {
+                Err(BackendError::UndefinedTable(msg)) => assert_eq!(
+                    msg,
+                    "Invalid reference to FROM-clause entry for table \"u\", \
+                    only a LATERAL item can read the FROM items before it.",
+                    "{}",
+                    query
+                ),
+                result => panic!("{}: expected an error, got {:?}", query, result),
+            }
+        }
+        match mb.eval_query(
+            "SELECT * FROM users u RIGHT JOIN LATERAL (SELECT * FROM orders WHERE user_id = u.id) o ON o.id = u.id;",
+        ) {
+            Err(BackendError::InvalidColumnReference(msg)) => assert_eq!(
+                msg,
+                "The combining JOIN type must be INNER or LEFT for a LATERAL reference."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        // A subquery of its own reads nothing of the rest
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT u.id, o.n FROM users u, (SELECT count(*) AS n FROM orders) o;"
+            ),
+            vec!["1|6", "2|6", "3|6"]
+        );
+    }
+}
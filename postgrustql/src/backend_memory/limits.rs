@@ -0,0 +1,185 @@
+use super::MemoryBackend;
+use crate::backend::{BackendError, ERR_RESULT_LIMIT_EXCEEDED};
+use crate::sql_types::SqlValue;
+
+// Caps on what a single query may hold in memory at once. They apply to the final
+// results as well as to the rows buffered by joins, sorts and DISTINCT.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ResultLimits {
+    pub max_result_rows: Option<usize>,
+    // Estimated from the length of each value's MemoryCell
+    pub max_result_bytes: Option<usize>,
+}
+
+impl MemoryBackend {
+    pub fn result_limits(&self) -> ResultLimits {
+        self.limits
+    }
+
+    pub fn set_result_limits(&mut self, limits: ResultLimits) {
+        self.limits = limits;
+    }
+}
+
+// Keeps count of the rows one operator materialized, failing once they exceed the limits
+pub(super) struct MaterializedRows {
+    limits: ResultLimits,
+    operator: &'static str,
+    rows: usize,
+    bytes: usize,
+}
+
+impl MaterializedRows {
+    pub fn new(limits: ResultLimits, operator: &'static str) -> Self {
+        MaterializedRows {
+            limits,
+            operator,
+            rows: 0,
+            bytes: 0,
+        }
+    }
+
+    #[inline]
+    pub fn add(&mut self, row: &[SqlValue]) -> Result<(), BackendError> {
+        if let Some(max_rows) = self.limits.max_result_rows {
+            self.rows += 1;
+            if self.rows > max_rows {
+                return Err(BackendError::ProgramLimitExceeded(format!(
+                    "{} {} materialized more than {} rows.",
+                    ERR_RESULT_LIMIT_EXCEEDED, self.operator, max_rows
+                )));
+            }
+        }
+        if let Some(max_bytes) = self.limits.max_result_bytes {
+            self.bytes += row.iter().map(SqlValue::encoded_len).sum::<usize>();
+            if self.bytes > max_bytes {
+                return Err(BackendError::ProgramLimitExceeded(format!(
+                    "{} {} materialized more than {} bytes.",
+                    ERR_RESULT_LIMIT_EXCEEDED, self.operator, max_bytes
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::*;
+
+    struct LimitTest {
+        query: &'static str,
+        limits: ResultLimits,
+        result: Result<usize, &'static str>,
+    }
+
+    fn rows(limit: usize) -> ResultLimits {
+        ResultLimits {
+            max_result_rows: Some(limit),
+            max_result_bytes: None,
+        }
+    }
+
+    fn bytes(limit: usize) -> ResultLimits {
+        ResultLimits {
+            max_result_rows: None,
+            max_result_bytes: Some(limit),
+        }
+    }
+
+    #[test]
+    fn test_result_limits() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT, name TEXT);
+            INSERT INTO people VALUES (1, 'Baam');
+            INSERT INTO people VALUES (2, 'Rachel');
+            INSERT INTO people VALUES (3, 'Khun');
+            INSERT INTO people VALUES (4, 'Baam');
+            CREATE TABLE pets (owner INT, pet TEXT);
+            INSERT INTO pets VALUES (1, 'Cat');
+            INSERT INTO pets VALUES (1, 'Dog');
+            INSERT INTO pets VALUES (3, 'Eel');",
+        )
+        .unwrap();
+
+        let tests = vec![
+            LimitTest {
+                query: "SELECT * FROM people;",
+                limits: ResultLimits::default(),
+                result: Ok(4),
+            },
+            LimitTest {
+                query: "SELECT * FROM people;",
+                limits: rows(4),
+                result: Ok(4),
+            },
+            LimitTest {
+                query: "SELECT * FROM people;",
+                limits: rows(3),
+                result: Err("Result limit exceeded. SELECT materialized more than 3 rows."),
+            },
+            LimitTest {
+                query: "SELECT * FROM people LIMIT 3;",
+                limits: rows(3),
+                result: Ok(3),
+            },
+            LimitTest {
+                query: "SELECT * FROM people WHERE id > 1;",
+                limits: rows(3),
+                result: Ok(3),
+            },
+            LimitTest {
+                query: "SELECT id FROM people ORDER BY name;",
+                limits: rows(2),
+                result: Err("Result limit exceeded. ORDER BY materialized more than 2 rows."),
+            },
+            LimitTest {
+                query: "SELECT DISTINCT name FROM people;",
+                limits: rows(3),
+                result: Ok(3),
+            },
+            LimitTest {
+                query: "SELECT DISTINCT name FROM people;",
+                limits: rows(2),
+                result: Err("Result limit exceeded. DISTINCT materialized more than 2 rows."),
+            },
+            LimitTest {
+                query: "SELECT name FROM people INNER JOIN pets ON id = owner LIMIT 1;",
+                limits: rows(2),
+                result: Err("Result limit exceeded. JOIN materialized more than 2 rows."),
+            },
+            // 4 bytes per INT, plus 8 bytes of length and the bytes of each name
+            LimitTest {
+                query: "SELECT * FROM people;",
+                limits: bytes(66),
+                result: Ok(4),
+            },
+            LimitTest {
+                query: "SELECT * FROM people;",
+                limits: bytes(65),
+                result: Err("Result limit exceeded. SELECT materialized more than 65 bytes."),
+            },
+        ];
+
+        for test in tests {
+            mb.set_result_limits(test.limits);
+            let result = match mb.eval_query(test.query) {
+                Ok(mut results) => match results.pop() {
+                    Some(crate::backend::EvalResult::Select { results, .. }) => {
+                        Ok(results.rows.len())
+                    }
+                    _ => panic!("Expected select results"),
+                },
+                Err(err) => Err(err.to_string()),
+            };
+            assert_eq!(
+                result,
+                test.result.map_err(|err| err.to_string()),
+                "{} with {:?}",
+                test.query,
+                test.limits
+            );
+        }
+    }
+}
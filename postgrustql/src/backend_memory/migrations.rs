@@ -0,0 +1,383 @@
+use super::values::text;
+use super::{Connection, MemoryBackend};
+use crate::backend::{BackendError, EvalResult};
+use crate::lexer::{fnv1a, FNV_OFFSET_BASIS};
+use crate::quote_literal;
+use crate::sql_types::{SqlNumeric, SqlValue};
+use std::sync::{Arc, Mutex};
+
+// The table keeping which migrations were applied to a backend, it has to be quoted
+pub const MIGRATIONS_TABLE: &str = "__postgrustql_migrations";
+
+// The version the init SQL of `Connection::open_with_init` is kept under, those of the
+// migrations given to `run_migrations` start from 1
+pub const INIT_VERSION: i64 = 0;
+
+// A change to the schema, applied once to a backend along with those of lower versions
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Migration<'a> {
+    pub version: i64,
+    pub name: &'a str,
+    pub sql: &'a str,
+}
+
+impl Migration<'_> {
+    // What tells whether the SQL of the migration changed since it was applied, as it
+    // is written down to the byte
+    pub fn checksum(&self) -> String {
+        format!("{:016x}", fnv1a(FNV_OFFSET_BASIS, self.sql.as_bytes()))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MigrationError {
+    // The migration failed and was rolled back, those before it stay applied
+    Failed {
+        version: i64,
+        name: String,
+        error: BackendError,
+    },
+    // The SQL of a migration applied before is not what it was applied with
+    ChecksumMismatch {
+        version: i64,
+        name: String,
+        applied: String,
+        current: String,
+    },
+    // The migrations given or the table keeping them can't be used
+    Backend(BackendError),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Failed {
+                version,
+                name,
+                error,
+            } => write!(f, "Migration {} ({}) failed: {}", version, name, error),
+            MigrationError::ChecksumMismatch {
+                version,
+                name,
+                applied,
+                current,
+            } => write!(
+                f,
+                "Migration {} ({}) changed since it was applied, its checksum was {} and is {}",
+                version, name, applied, current
+            ),
+            MigrationError::Backend(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<BackendError> for MigrationError {
+    fn from(error: BackendError) -> Self {
+        MigrationError::Backend(error)
+    }
+}
+
+// A migration as the table keeps it
+struct AppliedMigration {
+    version: i64,
+    checksum: String,
+}
+
+impl Connection {
+    // Applies the migrations not applied to the backend yet, in the order of their
+    // versions, each in a transaction of its own. Every migration applied before is
+    // checked first to still be what it was, none is applied otherwise. The backend is
+    // held throughout, other connections can't apply the same migrations meanwhile.
+    // Versions have to be 1 or more, `INIT_VERSION` is kept for `open_with_init`.
+    // Returns the number of migrations applied.
+    pub fn run_migrations(&mut self, migrations: &[Migration]) -> Result<usize, MigrationError> {
+        if let Some(migration) = migrations
+            .iter()
+            .find(|migration| migration.version <= INIT_VERSION)
+        {
+            return Err(MigrationError::Backend(
+                BackendError::InvalidParameterValue(format!(
+                    "Migration {} has version {}, versions start from {}.",
+                    migration.name,
+                    migration.version,
+                    INIT_VERSION + 1
+                )),
+            ));
+        }
+        self.apply_migrations(migrations)
+    }
+
+    // Opens a connection after running `init_sql` on the backend, unless it already was.
+    // It is kept as the migration of version `INIT_VERSION`, and checked like the others.
+    pub fn open_with_init(
+        backend: Arc<Mutex<MemoryBackend>>,
+        init_sql: &str,
+    ) -> Result<Self, MigrationError> {
+        let mut connection = Connection::open(backend);
+        connection.apply_migrations(&[Migration {
+            version: INIT_VERSION,
+            name: "init",
+            sql: init_sql,
+        }])?;
+        Ok(connection)
+    }
+
+    fn apply_migrations(&mut self, migrations: &[Migration]) -> Result<usize, MigrationError> {
+        let mut migrations = migrations.to_vec();
+        migrations.sort_by_key(|migration| migration.version);
+        if let Some(pair) = migrations
+            .windows(2)
+            .find(|pair| pair[0].version == pair[1].version)
+        {
+            return Err(MigrationError::Backend(
+                BackendError::InvalidParameterValue(format!(
+                    "Migrations {} and {} have the same version {}.",
+                    pair[0].name, pair[1].name, pair[0].version
+                )),
+            ));
+        }
+        self.with_session(|backend| Ok(backend.run_migrations(&migrations)))?
+    }
+}
+
+impl MemoryBackend {
+    // Migrations sorted by version, none twice
+    fn run_migrations(&mut self, migrations: &[Migration]) -> Result<usize, MigrationError> {
+        if self.table(MIGRATIONS_TABLE).is_none() {
+            self.eval_query(&format!(
+                "CREATE TABLE \"{}\" (version BIGINT PRIMARY KEY, name TEXT NOT NULL, \
+                checksum TEXT NOT NULL);",
+                MIGRATIONS_TABLE
+            ))?;
+        }
+        let applied = self.applied_migrations()?;
+        for migration in migrations {
+            let found = applied
+                .iter()
+                .find(|applied| applied.version == migration.version);
+            match found {
+                Some(applied) if applied.checksum != migration.checksum() => {
+                    return Err(MigrationError::ChecksumMismatch {
+                        version: migration.version,
+                        name: migration.name.to_string(),
+                        applied: applied.checksum.clone(),
+                        current: migration.checksum(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let mut count = 0;
+        let pending = migrations.iter().filter(|migration| {
+            !applied
+                .iter()
+                .any(|applied| applied.version == migration.version)
+        });
+        for migration in pending {
+            let record = format!(
+                "INSERT INTO \"{}\" VALUES ({}, {}, {});",
+                MIGRATIONS_TABLE,
+                quote_literal(&SqlValue::Numeric(SqlNumeric::BigInt {
+                    value: migration.version
+                })),
+                quote_literal(&text(migration.name)),
+                quote_literal(&text(&migration.checksum())),
+            );
+            self.transaction(|backend| {
+                backend.eval_query(migration.sql)?;
+                backend.eval_query(&record)
+            })
+            .map_err(|error| MigrationError::Failed {
+                version: migration.version,
+                name: migration.name.to_string(),
+                error,
+            })?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>, BackendError> {
+        let query = format!("SELECT version, checksum FROM \"{}\";", MIGRATIONS_TABLE);
+        let results = match self.eval_query(&query)?.pop() {
+            Some(EvalResult::Select { results, .. }) => results,
+            _ => {
+                return Err(BackendError::Internal(
+                    "Expected select results.".to_string(),
+                ))
+            }
+        };
+        results
+            .rows
+            .iter()
+            .map(|row| match (&row[0], &row[1]) {
+                (SqlValue::Numeric(SqlNumeric::BigInt { value }), SqlValue::Text(checksum)) => {
+                    Ok(AppliedMigration {
+                        version: *value,
+                        checksum: checksum.to_string(),
+                    })
+                }
+                _ => Err(BackendError::DatatypeMismatch(format!(
+                    "Table {} doesn't keep migrations.",
+                    MIGRATIONS_TABLE
+                ))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod migrations_tests {
+    use super::*;
+
+    const MIGRATIONS: [Migration; 3] = [
+        Migration {
+            version: 1,
+            name: "people",
+            sql: "CREATE TABLE people (id INT, name TEXT);",
+        },
+        Migration {
+            version: 2,
+            name: "seed",
+            sql: "INSERT INTO people VALUES (1, 'Baam'); INSERT INTO people VALUES (2, nope);",
+        },
+        Migration {
+            version: 3,
+            name: "pets",
+            sql: "CREATE TABLE pets (owner INT);",
+        },
+    ];
+
+    fn versions(connection: &mut Connection) -> Vec<String> {
+        let query = format!(
+            "SELECT version FROM \"{}\" ORDER BY version;",
+            MIGRATIONS_TABLE
+        );
+        match connection.eval_query(&query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                results.rows.iter().map(|row| row[0].to_string()).collect()
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    fn people(connection: &mut Connection) -> usize {
+        match connection
+            .eval_query("SELECT id FROM people;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => results.rows.len(),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_migrations_resume_after_a_failure() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut connection = Connection::open(backend.clone());
+        // Given out of order, the second fails halfway and leaves nothing of itself behind
+        let mut migrations = MIGRATIONS;
+        migrations.reverse();
+        match connection.run_migrations(&migrations).unwrap_err() {
+            MigrationError::Failed {
+                version,
+                name,
+                error,
+            } => {
+                assert_eq!((version, name.as_str()), (2, "seed"));
+                assert_eq!(error.code(), "42703");
+            }
+            err => panic!("Expected the seed migration to fail, got {:?}", err),
+        }
+        assert_eq!(versions(&mut connection), ["1"]);
+        assert_eq!(people(&mut connection), 0);
+        assert!(connection.eval_query("SELECT owner FROM pets;").is_err());
+
+        // Fixed, it is applied on the next run along with those after it
+        migrations[1].sql = "INSERT INTO people VALUES (1, 'Baam'); INSERT INTO people \
+            VALUES (2, 'Rachel');";
+        let mut connection = Connection::open(backend);
+        assert_eq!(connection.run_migrations(&migrations), Ok(2));
+        assert_eq!(versions(&mut connection), ["1", "2", "3"]);
+        assert_eq!(people(&mut connection), 2);
+        assert_eq!(connection.run_migrations(&migrations), Ok(0));
+    }
+
+    #[test]
+    fn test_changed_migration_is_refused() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut connection = Connection::open(backend);
+        assert_eq!(connection.run_migrations(&MIGRATIONS[..1]), Ok(1));
+
+        let mut migrations = MIGRATIONS;
+        migrations[0].sql = "CREATE TABLE people (id BIGINT, name TEXT);";
+        let err = connection.run_migrations(&migrations).unwrap_err();
+        assert_eq!(
+            err,
+            MigrationError::ChecksumMismatch {
+                version: 1,
+                name: "people".to_string(),
+                applied: MIGRATIONS[0].checksum(),
+                current: migrations[0].checksum(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Migration 1 (people) changed since it was applied, its checksum was {} and is {}",
+                MIGRATIONS[0].checksum(),
+                migrations[0].checksum()
+            )
+        );
+        // Nothing after it was applied
+        assert_eq!(versions(&mut connection), ["1"]);
+
+        migrations[2].version = 1;
+        let err = connection.run_migrations(&migrations).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Migrations people and pets have the same version 1."
+        );
+    }
+
+    #[test]
+    fn test_open_with_init() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let init = "CREATE TABLE people (id INT, name TEXT); \
+            INSERT INTO people VALUES (1, 'Baam');";
+        let mut connection = Connection::open_with_init(backend.clone(), init).unwrap();
+        assert_eq!(people(&mut connection), 1);
+
+        // Another connection to the same backend doesn't run it again
+        let mut other = Connection::open_with_init(backend.clone(), init).unwrap();
+        assert_eq!(people(&mut other), 1);
+        assert_eq!(versions(&mut other), ["0"]);
+        let err = Connection::open_with_init(backend, "CREATE TABLE people (id INT);");
+        assert!(matches!(
+            err,
+            Err(MigrationError::ChecksumMismatch { version: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_init_version_is_reserved() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let init = "CREATE TABLE people (id INT, name TEXT);";
+        let mut connection = Connection::open_with_init(backend, init).unwrap();
+
+        // A migration of version 0 would be taken for the init SQL and never run
+        let mut migrations = MIGRATIONS;
+        migrations[0].version = INIT_VERSION;
+        let err = connection.run_migrations(&migrations).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Migration people has version 0, versions start from 1."
+        );
+        migrations[0].version = -1;
+        assert!(connection.run_migrations(&migrations).is_err());
+        // Nothing was applied
+        assert_eq!(versions(&mut connection), ["0"]);
+    }
+}
@@ -1,16 +1,140 @@
 extern crate byteorder;
 
+mod aggregate;
+mod alter;
+mod assign;
+mod asterisk;
+mod authorize;
+mod cache;
+mod catalog;
+mod changes;
+mod check;
+mod collation;
+mod column_origin;
+mod compiled;
+mod config;
+mod connection;
+mod cursor;
+mod datetime;
+mod delete;
+mod describe;
+mod diff;
+mod digest;
+mod division;
+mod functions;
+mod generated;
+mod identifiers;
+mod join_order;
+mod lateral;
+mod limits;
+mod migrations;
+mod nondeterminism;
+mod notify;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod pattern;
+mod prepared;
+mod progress;
+mod projection;
+mod read_only;
+mod reindex;
+mod resolver;
+mod retry;
+mod sample;
+mod script;
+mod semi_join;
+mod sequence;
+mod spill;
+mod storage;
+mod stored_row;
+mod table_size;
+#[cfg(test)]
+mod test_support;
+mod timing;
+mod transaction_block;
+mod triggers;
+mod update;
+mod vacuum;
+mod values;
+mod variables;
+mod views;
+mod warnings;
+mod window;
+mod with_query;
+
+pub use aggregate::*;
+pub use assign::CastMode;
+pub use authorize::*;
+pub use cache::*;
+pub use changes::*;
+pub use check::*;
+pub use collation::Collation;
+pub use compiled::*;
+pub use config::*;
+pub use connection::*;
+pub use cursor::Cursor;
+pub use describe::StatementDescription;
+pub use diff::TableDiff;
+pub use division::DivisionByZero;
+pub use functions::*;
+pub use identifiers::{IdentifierMode, DEFAULT_MAX_IDENTIFIER_LENGTH};
+pub use limits::*;
+pub use migrations::*;
+pub use nondeterminism::{Clock, Timer, Timestamp, UuidGenerator};
+pub use notify::*;
+#[cfg(feature = "parallel")]
+pub use parallel::*;
+pub use prepared::*;
+pub use progress::{StatementMonitor, StatementProgress};
+pub use reindex::*;
+pub use retry::ConflictRetry;
+use retry::SessionRetry;
+pub use script::*;
+pub use storage::*;
+pub use table_size::TableSize;
+pub use timing::*;
+pub use triggers::MAX_TRIGGER_DEPTH;
+pub use vacuum::VacuumStats;
+pub use window::*;
+
 use super::ast::*;
 use super::backend::*;
 use super::lexer::*;
-use super::parser::parse;
+use super::row::FromRow;
+use alter::index_columns;
+use asterisk::{expand_asterisk, AsteriskColumn};
+use collation::{
+    apply_collated_operator, apply_collated_quantified_operator, check_collatable, collation_keys,
+    given_collation, operand_collation,
+};
+use column_origin::DescribedColumn;
+use cursor::Cursors;
+use join_order::join_relation;
+use lateral::is_lateral;
+use nondeterminism::FunctionSources;
+use progress::leave_monitor;
+use projection::{ColumnMapping, ReferencedColumns};
+use resolver::{Resolution, Resolver};
+use sequence::{enter_sampling, SequenceOwner, Sequences};
+use spill::{Distinct, DistinctRows, ResultRows, SortedRows};
+use stored_row::StoredRow;
+use transaction_block::TransactionBlock;
+use triggers::Triggers;
+use variables::SessionVariables;
+use views::Views;
+use warnings::{enter_warnings, explicit_cast, nonstandard_escapes};
 
+use crate::parser::ParsingError;
+use crate::quote::quote_identifier;
 use crate::{
     backend::MemoryCell,
-    sql_types::{SqlType, SqlValue},
+    sql_types::{SqlText, SqlType, SqlValue},
 };
-use instant::Instant;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar};
+use std::time::Duration;
 
 const ERR_INVALID_CELL: &str = "Invalid Cell";
 const ERR_INVALID_OPERANDS: &str = "Invalid Operands";
@@ -26,165 +150,292 @@ pub enum TableContainer<'a> {
 pub struct Index {
     name: String,
     expression: Expression,
+    // The columns the expression reads
+    columns: Vec<String>,
     unique: bool,
     primary_key: bool,
     typ: String,
-    tree: std::collections::btree_map::BTreeMap<SqlValue, Vec<usize>>,
+    // Rows by the sortable encoding of their value
+    tree: std::collections::btree_map::BTreeMap<MemoryCellData, Vec<usize>>,
+    // What the entries take, see `TableSize`
+    bytes: usize,
+}
+
+// What a column falls back to when a row leaves it out, whether it may hold NULL, the
+// length it was declared with, like the 20 of VARCHAR(20), the expression it is computed
+// from if it is a generated column and the collation its text goes by
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ColumnConstraints {
+    default: Option<Expression>,
+    not_null: bool,
+    type_modifier: Option<usize>,
+    generated: Option<Expression>,
+    collation: Option<Collation>,
 }
 
 impl Index {
-    pub fn add_row(&mut self, table: &Table, row_index: usize) -> Result<(), String> {
-        let (index_value, _, _) = table.evaluate_cell(row_index, &self.expression)?;
+    pub fn add_row(&mut self, table: &Table, row_index: usize) -> Result<(), BackendError> {
+        let index_value = table.index_value(self, row_index)?;
 
         if index_value.is_null() {
-            return Err("Violates NOT NULL Constraint".to_string());
+            if self.primary_key {
+                return Err(BackendError::NotNullViolation(
+                    "Violates NOT NULL Constraint".to_string(),
+                ));
+            }
+            return Ok(());
         }
+        let index_value = index_value.encode_sortable().bytes;
 
         if self.unique {
             if let Some(_) = self.tree.get(&index_value) {
-                return Err("Duplicate Value violates UNIQUE Constraint".to_string());
-            }
-        }
-
-        match self.tree.get_mut(&index_value) {
-            Some(row_indexes) => {
-                row_indexes.push(row_index);
-            }
-            None => {
-                self.tree.insert(index_value, vec![row_index]);
+                return Err(BackendError::UniqueViolation(
+                    "Duplicate Value violates UNIQUE Constraint".to_string(),
+                ));
             }
         }
 
+        self.insert_entry(index_value, row_index);
         Ok(())
     }
 
-    pub fn applicable_value(&self, exp: &Expression) -> Result<Option<Expression>, String> {
-        if let Expression::Binary(bin_exp) = exp {
-            // Find the column and the value in the boolean expression
-            let mut column_exp = *bin_exp.first.clone();
-            let mut value_exp = *bin_exp.second.clone();
-            if column_exp != self.expression {
-                std::mem::swap(&mut column_exp, &mut value_exp);
-            }
-
-            // Neither side is applicable, return None
-            if column_exp != self.expression {
-                return Ok(None);
-            }
+    // The comparison of a condition of the WHERE clause the index can look rows up by,
+    // `expression operand value` with the value cast to the type the index keeps. The
+    // indexed expression is never cast, so a value that can't be cast to its type
+    // without changing, like 2.5 for integers, isn't looked up. Parameters are literals
+    // once bound.
+    pub fn lookup_key(&self, table: &Table, exp: &Expression) -> Option<(Token, SqlValue)> {
+        let bin_exp = match exp {
+            Expression::Binary(bin_exp) => bin_exp,
+            _ => return None,
+        };
+        let (operand, value_exp) = if *bin_exp.first == self.expression {
+            (bin_exp.operand.clone(), bin_exp.second.as_ref())
+        } else if *bin_exp.second == self.expression {
+            // `5 < col` is `col > 5`
+            let operand = match bin_exp.operand {
+                Token::LessThan => Token::GreaterThan,
+                Token::LessThanOrEqual => Token::GreaterThanOrEqual,
+                Token::GreaterThan => Token::LessThan,
+                Token::GreaterThanOrEqual => Token::LessThanOrEqual,
+                ref operand => operand.clone(),
+            };
+            (operand, bin_exp.first.as_ref())
+        } else {
+            return None;
+        };
 
-            let supported_checks = vec![
-                Token::Equal,
-                Token::NotEqual,
-                Token::GreaterThan,
-                Token::GreaterThanOrEqual,
-                Token::LessThan,
-                Token::LessThanOrEqual,
-            ];
-            let is_supported = supported_checks.contains(&bin_exp.operand);
+        let supported_checks = [
+            Token::Equal,
+            Token::NotEqual,
+            Token::GreaterThan,
+            Token::GreaterThanOrEqual,
+            Token::LessThan,
+            Token::LessThanOrEqual,
+        ];
+        if !supported_checks.contains(&operand) {
+            return None;
+        }
+        match value_exp {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { .. } | Token::Parameter { .. },
+            }) => return None,
+            Expression::Literal(_) => {}
+            _ => return None,
+        }
+        let (value, _, _) = table.evaluate_cell(0, value_exp).ok()?;
+        let value = value.lossless_cast_to_type(self.indexed_type(table))?;
+        Some((operand, value))
+    }
 
-            if is_supported == false {
-                return Ok(None);
-            }
-            if let Expression::Literal(lit_exp) = value_exp {
-                return Ok(Some(Expression::Literal(lit_exp)));
-            } else {
-                eprintln!("Only index checks on literals supported");
-                return Ok(None);
-            }
-        } else {
-            return Ok(None);
+    // The type of the values of the indexed expression
+    fn indexed_type(&self, table: &Table) -> SqlType {
+        let column = match &self.expression {
+            Expression::TableColumn(table_column) => Some(table_column.col_name.as_str()),
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => Some(value.as_str()),
+            _ => None,
+        };
+        let position = column.and_then(|column| table.columns.iter().position(|c| c == column));
+        match position {
+            Some(position) => table.column_types[position],
+            None => table.sample_types(&[SelectItem {
+                asterisk: false,
+                asterisk_source: None,
+                asterisk_modifiers: None,
+                as_clause: None,
+                expression: self.expression.clone(),
+            }])[0],
         }
     }
 
+    // The rows of the table the condition holds for by the index, in the order of the table
     pub fn new_table_from_subset(
         &self,
         table: &Table,
-        bin_exp: BinaryExpression,
-    ) -> Result<Table, String> {
-        let value_exp = match self.applicable_value(&Expression::Binary(bin_exp.clone()))? {
-            Some(value) => value,
-            None => {
-                return Ok(table.clone());
-            }
+        exp: &Expression,
+        storage: &dyn StorageEngine,
+    ) -> Result<Table, BackendError> {
+        let (operand, value) = match self.lookup_key(table, exp) {
+            Some(key) => key,
+            None => return Ok(table.clone()),
         };
+        // Collated indexes are looked up by the key of the value
+        let key = match table.collation_of(&self.expression) {
+            Some(collation) => collation.key(&value).encode_sortable().bytes,
+            None => value.encode_sortable().bytes,
+        };
+        let mut row_indexes = storage.index_lookup(&table.name, &self.name, &operand, &key)?;
+        row_indexes.sort_unstable();
 
-        let mut new_table = Table {
+        Ok(Table {
             column_types: table.column_types.clone(),
             columns: table.columns.clone(),
             indexes: vec![],
             name: table.name.clone(),
-            rows: Vec::with_capacity(100),
-        };
+            rows: row_indexes
+                .into_iter()
+                .filter_map(|index| table.rows.get(index).cloned())
+                .collect(),
+            column_constraints: table.column_constraints.clone(),
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        })
+    }
+}
 
-        let (value, _, _) = match new_table.evaluate_cell(0, &value_exp) {
-            Ok(value) => value,
-            Err(err) => {
-                eprintln!("{}", err);
-                return Ok(table.clone());
-            }
-        };
+// The name Postgres gives the result column of a select item without AS: the column it
+// reads, the function it calls, the item of a subquery, or `?column?` for the rest. A
+// cast keeps the name of what it casts.
+fn output_name(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => value.to_string(),
+        Expression::TableColumn(table_column) => table_column.col_name.clone(),
+        Expression::ProcessedTableColumn(ProcessedTableColumn {
+            col_name: Some(name),
+            ..
+        }) => name.clone(),
+        Expression::FunctionCall(call) => call.name.clone(),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => output_name(data),
+        Expression::SubSelect(select) => match select.items.as_slice() {
+            [item] => match &item.as_clause {
+                Some(as_clause) => as_clause.clone(),
+                None => output_name(&item.expression),
+            },
+            _ => ANONYMOUS_COL_NAME.to_string(),
+        },
+        _ => ANONYMOUS_COL_NAME.to_string(),
+    }
+}
 
-        let mut row_indexes: Vec<usize> = Vec::with_capacity(100);
+// The result column ORDER BY `exp` sorts by, when it is the position of one or a name one of
+// them has. Positions start at 1.
+fn order_by_output(exp: &Expression, names: &[String]) -> Result<Option<usize>, BackendError> {
+    let name = match exp {
+        Expression::Literal(LiteralExpression {
+            literal: Token::NumericValue { value },
+        }) => {
+            return match value.parse::<usize>() {
+                Ok(position) if (1..=names.len()).contains(&position) => Ok(Some(position - 1)),
+                Ok(position) => Err(BackendError::InvalidColumnReference(format!(
+                    "ORDER BY position {} is not in select list.",
+                    position
+                ))),
+                Err(_) => Ok(None),
+            };
+        }
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) => value.as_ref(),
+        Expression::TableColumn(TableColumn {
+            col_name,
+            table_name: None,
+        }) => col_name.as_str(),
+        _ => return Ok(None),
+    };
+    Ok(names.iter().position(|output| output == name))
+}
 
-        match bin_exp.operand {
-            Token::Equal => {
-                match self.tree.get(&value) {
-                    Some(indexes) => {
-                        row_indexes.append(&mut indexes.clone());
-                    }
-                    None => {}
-                };
-            }
-            Token::NotEqual => {
-                for (key, indexes) in &self.tree {
-                    if *key == value {
-                        continue;
-                    }
-                    row_indexes.append(&mut indexes.clone());
-                }
-            }
-            Token::LessThan => {
-                for (key, indexes) in &self.tree {
-                    if key >= &value {
-                        break;
-                    }
-                    row_indexes.append(&mut indexes.clone());
-                }
-            }
-            Token::LessThanOrEqual => {
-                for (key, indexes) in &self.tree {
-                    if key > &value {
-                        break;
-                    }
-                    row_indexes.append(&mut indexes.clone());
-                }
+// Points the columns an expression qualifies by their FROM item at the one they read, where
+// more than one FROM item has a column of the name. The others are found by name alone.
+// Subqueries are left as they are, their columns may be those of their own tables.
+fn resolve_qualified_columns(expression: &mut Expression, columns: &[String], sources: &[String]) {
+    match expression {
+        Expression::TableColumn(TableColumn {
+            col_name,
+            table_name: Some(source),
+        }) => {
+            if columns.iter().filter(|column| *column == col_name).count() < 2 {
+                return;
             }
-            Token::GreaterThan => {
-                for (_, ref mut indexes) in self.tree.clone().split_off(&value) {
-                    row_indexes.append(indexes);
-                }
+            let idx = (0..columns.len())
+                .find(|idx| sources.get(*idx) == Some(source) && &columns[*idx] == col_name);
+            if let Some(idx) = idx {
+                let col_name = col_name.clone();
+                *expression = Expression::ProcessedTableColumn(ProcessedTableColumn {
+                    col_name: Some(col_name),
+                    col_idx: idx,
+                });
             }
-            Token::GreaterThanOrEqual => {
-                match self.tree.get(&value) {
-                    Some(indexes) => {
-                        row_indexes.append(&mut indexes.clone());
-                    }
-                    None => {}
-                };
-                for (_, ref mut indexes) in self.tree.clone().split_off(&value) {
-                    row_indexes.append(indexes);
-                }
+        }
+        Expression::Binary(binary) => {
+            resolve_qualified_columns(&mut binary.first, columns, sources);
+            resolve_qualified_columns(&mut binary.second, columns, sources);
+        }
+        Expression::Unary(unary) => resolve_qualified_columns(&mut unary.first, columns, sources),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            resolve_qualified_columns(data, columns, sources)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                resolve_qualified_columns(arg, columns, sources);
             }
-            _ => {}
         }
+        Expression::Quantified(quantified) => {
+            resolve_qualified_columns(&mut quantified.first, columns, sources)
+        }
+        _ => {}
+    }
+}
 
-        for index in row_indexes {
-            if let Some(row) = table.rows.get(index) {
-                new_table.rows.push(row.clone());
+// Fails for a column qualified by a name no FROM item goes by. As in Postgres a table
+// given an alias only goes by the alias, its name then being left for the outer query.
+fn check_qualifiers(expression: &Expression, sources: &[String]) -> Result<(), BackendError> {
+    let mut columns = vec![];
+    semi_join::column_references(expression, &mut columns);
+    for column in columns {
+        if let Expression::TableColumn(TableColumn {
+            table_name: Some(source),
+            ..
+        }) = column
+        {
+            if !sources.contains(source) {
+                return Err(BackendError::UndefinedTable(format!(
+                    "{}: {}",
+                    source, ERR_TABLE_DOES_NOT_EXIST
+                )));
             }
         }
+    }
+    Ok(())
+}
 
-        return Ok(table.clone());
+// A value of the type that operators accept in most places, used where a query needs the
+// type of an expression but has no row to evaluate it on
+fn sample_value(typ: SqlType) -> SqlValue {
+    match typ {
+        SqlType::Boolean => SqlValue::Boolean(true),
+        SqlType::Null | SqlType::Type => SqlValue::Null,
+        typ => SqlValue::Text(SqlText::Text {
+            value: "1".to_string(),
+        })
+        .explicit_cast_to_type(typ)
+        .unwrap_or(SqlValue::Null),
     }
 }
 
@@ -193,8 +444,17 @@ pub struct Table {
     name: String,
     columns: Vec<String>,
     column_types: Vec<SqlType>,
-    rows: Vec<Vec<SqlValue>>,
+    rows: Vec<StoredRow>,
     indexes: Vec<Index>,
+    // DEFAULT and NOT NULL of each column, empty for tables made up by a query
+    column_constraints: Vec<ColumnConstraints>,
+    checks: Vec<Check>,
+    // Changes whenever the columns or indexes change, 0 for tables made up by a query
+    schema_version: u64,
+    // What the rows take, kept up to date once the table is stored, see `TableSize`
+    heap_bytes: usize,
+    // Rows taken out since the table was last vacuumed, see `VacuumStats`
+    dead_rows: usize,
 }
 
 impl From<QueryResults<SqlValue>> for Table {
@@ -204,18 +464,84 @@ impl From<QueryResults<SqlValue>> for Table {
             name: String::from(""),
             columns: results.columns.iter().map(|c| c.name.clone()).collect(),
             indexes: vec![],
-            rows: results.rows,
+            rows: results.rows.into_iter().map(StoredRow::from).collect(),
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
         }
     }
 }
 
 impl Table {
+    fn check_not_null(&self, row: &[SqlValue]) -> Result<(), BackendError> {
+        let columns = self.columns.iter().zip(&self.column_constraints);
+        for (value, (column, constraints)) in row.iter().zip(columns) {
+            if constraints.not_null && value.is_null() {
+                return Err(BackendError::NotNullViolation(format!(
+                    "Null value in column \"{}\" violates NOT NULL constraint.",
+                    column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Adds a row and its index entries, or nothing at all if it breaks an index constraint
+    fn push_row(&mut self, row: Vec<SqlValue>) -> Result<(), BackendError> {
+        self.push_stored_row(StoredRow::new(&row));
+        let result = self.add_index_entries(self.rows.len() - 1);
+        if result.is_err() {
+            self.pop_stored_row();
+        }
+        result
+    }
+
+    // Adds the index entries of the row at `row_index`, or none at all if it breaks an
+    // index constraint
+    fn add_index_entries(&mut self, row_index: usize) -> Result<(), BackendError> {
+        let mut index_values = Vec::with_capacity(self.indexes.len());
+        for index in &self.indexes {
+            let error = match self.index_value(index, row_index) {
+                Err(err) => err,
+                Ok(value) if value.is_null() && index.primary_key => {
+                    BackendError::NotNullViolation("Violates NOT NULL Constraint".to_string())
+                }
+                // Only primary keys need a value, other indexes keep the row out
+                Ok(value) if value.is_null() => {
+                    index_values.push(None);
+                    continue;
+                }
+                Ok(value) => {
+                    let key = value.encode_sortable().bytes;
+                    if index.unique && index.tree.contains_key(&key) {
+                        BackendError::UniqueViolation(
+                            "Duplicate Value violates UNIQUE Constraint".to_string(),
+                        )
+                    } else {
+                        index_values.push(Some(key));
+                        continue;
+                    }
+                }
+            };
+            return Err(error);
+        }
+
+        for (index, value) in self.indexes.iter_mut().zip(index_values) {
+            if let Some(value) = value {
+                index.insert_entry(value, row_index);
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn evaluate_literal_cell(
         &self,
         row_index: usize,
         expression: &Expression,
-    ) -> Result<(SqlValue, &str, SqlType), String> {
+    ) -> Result<(SqlValue, &str, SqlType), BackendError> {
         match expression {
             Expression::Literal(literal_expression) => {
                 let literal = &literal_expression.literal;
@@ -224,19 +550,28 @@ impl Table {
                     Token::IdentifierValue { value } => {
                         for (i, table_col) in self.columns.iter().enumerate() {
                             if table_col == value.as_str() {
-                                let typ =
-                                    self.column_types.get(i).ok_or("Error accesing column")?;
+                                let typ = self.column_types.get(i).ok_or_else(|| {
+                                    BackendError::Internal("Error accesing column".to_string())
+                                })?;
                                 let val = self
                                     .rows
                                     .get(row_index as usize)
-                                    .ok_or("Error accesing row")?
+                                    .ok_or_else(|| {
+                                        BackendError::Internal("Error accesing row".to_string())
+                                    })?
                                     .get(i as usize)
-                                    .ok_or("Error accesing row's column")?;
-                                return Ok((val.clone(), table_col, *typ));
+                                    .ok_or_else(|| {
+                                        BackendError::Internal(
+                                            "Error accesing row's column".to_string(),
+                                        )
+                                    })?;
+                                return Ok((val, table_col, *typ));
                             }
                         }
 
-                        return Err(format!("{}: {}", value, ERR_COLUMN_DOES_NOT_EXIST).to_string());
+                        Err(BackendError::UndefinedColumn(
+                            format!("{}: {}", value, ERR_COLUMN_DOES_NOT_EXIST).to_string(),
+                        ))
                     }
                     _ => {
                         let val = SqlValue::from_token(&literal)?;
@@ -248,39 +583,45 @@ impl Table {
             Expression::TableColumn(table_column) => {
                 for (i, table_col) in self.columns.iter().enumerate() {
                     if table_col == &table_column.col_name {
-                        let typ = self.column_types.get(i).ok_or("Error accesing column")?;
+                        let typ = self.column_types.get(i).ok_or_else(|| {
+                            BackendError::Internal("Error accesing column".to_string())
+                        })?;
                         let val = self
                             .rows
                             .get(row_index)
-                            .ok_or("Error accesing row")?
+                            .ok_or_else(|| {
+                                BackendError::Internal("Error accesing row".to_string())
+                            })?
                             .get(i)
-                            .ok_or("Error accesing row's column")?;
-                        return Ok((val.clone(), table_col, *typ));
+                            .ok_or_else(|| {
+                                BackendError::Internal("Error accesing row's column".to_string())
+                            })?;
+                        return Ok((val, table_col, *typ));
                     }
                 }
 
-                return Err(
+                Err(BackendError::UndefinedColumn(
                     format!("{}: {}", table_column.col_name, ERR_COLUMN_DOES_NOT_EXIST).to_string(),
-                );
+                ))
             }
             Expression::ProcessedTableColumn(table_column) => {
-                let table_col = self
-                    .columns
-                    .get(table_column.col_idx)
-                    .ok_or(ERR_COLUMN_DOES_NOT_EXIST)?;
+                let table_col = self.columns.get(table_column.col_idx).ok_or_else(|| {
+                    BackendError::UndefinedColumn(ERR_COLUMN_DOES_NOT_EXIST.to_string())
+                })?;
                 let val = self
                     .rows
                     .get(row_index)
-                    .ok_or("Error accesing row")?
+                    .ok_or_else(|| BackendError::Internal("Error accesing row".to_string()))?
                     .get(table_column.col_idx)
-                    .ok_or("Error accesing row's column")?;
-                let typ = self
-                    .column_types
-                    .get(table_column.col_idx)
-                    .ok_or(ERR_COLUMN_DOES_NOT_EXIST)?;
-                return Ok((val.clone(), table_col, *typ));
+                    .ok_or_else(|| {
+                        BackendError::Internal("Error accesing row's column".to_string())
+                    })?;
+                let typ = self.column_types.get(table_column.col_idx).ok_or_else(|| {
+                    BackendError::UndefinedColumn(ERR_COLUMN_DOES_NOT_EXIST.to_string())
+                })?;
+                Ok((val, table_col, *typ))
             }
-            _ => return Err(ERR_INVALID_CELL.to_string()),
+            _ => Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
         }
     }
 
@@ -289,7 +630,7 @@ impl Table {
         &self,
         row_index: usize,
         expression: &Expression,
-    ) -> Result<(SqlValue, &str, SqlType), String> {
+    ) -> Result<(SqlValue, &str, SqlType), BackendError> {
         match expression {
             Expression::Binary(binary_expression) => {
                 let (first_val, _, _) = self.evaluate_cell(row_index, &binary_expression.first)?;
@@ -297,159 +638,49 @@ impl Table {
                 let (second_val, _, _) =
                     self.evaluate_cell(row_index, &binary_expression.second)?;
 
-                match binary_expression.operand {
-                    Token::Equal => {
-                        let result = SqlValue::equals(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::NotEqual => {
-                        let result = SqlValue::not_equal(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::GreaterThan => {
-                        let result = SqlValue::greater_than(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::GreaterThanOrEqual => {
-                        let result = SqlValue::greater_than_or_equals(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::LessThan => {
-                        let result = SqlValue::less_than(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::LessThanOrEqual => {
-                        let result = SqlValue::less_than_or_equals(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Concat => {
-                        let result = SqlValue::concat(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Plus => {
-                        let result = SqlValue::add(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Minus => {
-                        let result = SqlValue::subtract(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Asterisk => {
-                        let result = SqlValue::multiply(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Slash => {
-                        let result = SqlValue::divide(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Modulo => {
-                        let result = SqlValue::modulo(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::And => {
-                        let result = SqlValue::and(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Or => {
-                        let result = SqlValue::or(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Exponentiation => {
-                        let result = SqlValue::exponentiation(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseAnd => {
-                        let result = SqlValue::bitwise_and(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseOr => {
-                        let result = SqlValue::bitwise_or(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseXor => {
-                        let result = SqlValue::bitwise_xor(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseShiftLeft => {
-                        let result = SqlValue::bitwise_shift_left(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseShiftRight => {
-                        let result = SqlValue::bitwise_shift_right(&first_val, &second_val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    _ => {
-                        return Err(ERR_INVALID_CELL.to_string());
-                    }
-                }
+                let result = apply_collated_operator(
+                    &binary_expression.operand,
+                    operand_collation(&binary_expression.first, &binary_expression.second),
+                    &first_val,
+                    &second_val,
+                )?;
+                let typ = result.get_type();
+                Ok((result, ANONYMOUS_COL_NAME, typ))
             }
             Expression::Unary(unary_expression) => {
-                let (val, _, _) = self.evaluate_cell(row_index, &unary_expression.first)?;
-                match unary_expression.operand {
-                    Token::Minus => {
-                        let result = SqlValue::minus(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::SquareRoot => {
-                        let result = SqlValue::square_root(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::CubeRoot => {
-                        let result = SqlValue::cube_root(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Factorial | Token::FactorialPrefix => {
-                        let result = SqlValue::factorial(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::Not => {
-                        let result = SqlValue::not(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::AbsoluteValue => {
-                        let result = SqlValue::abs(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
-                    }
-                    Token::BitwiseNot => {
-                        let result = SqlValue::bitwise_not(&val)?;
-                        let typ = result.get_type();
-                        return Ok((result, ANONYMOUS_COL_NAME, typ));
+                let result = match unary_expression.negated_literal() {
+                    Some(literal) => literal_to_memory_cell(&literal)?,
+                    None => {
+                        let (val, _, _) = self.evaluate_cell(row_index, &unary_expression.first)?;
+                        apply_unary_operator(&unary_expression.operand, &val)?
                     }
-                    _ => return Err(ERR_INVALID_CELL.to_string()),
-                }
+                };
+                let typ = result.get_type();
+                Ok((result, ANONYMOUS_COL_NAME, typ))
             }
             Expression::Cast { data, typ } => {
                 let (val, _, _) = self.evaluate_cell(row_index, &data)?;
-                let result = val.explicit_cast_to_type(*typ)?;
+                let result = explicit_cast(&val, *typ)?;
                 return Ok((result, ANONYMOUS_COL_NAME, *typ));
             }
-            _ => return Err(ERR_INVALID_CELL.to_string()),
+            Expression::Collate { data, collation } => {
+                Collation::from_name(collation)?;
+                self.evaluate_cell(row_index, data)
+            }
+            Expression::Quantified(QuantifiedExpression {
+                first,
+                operand,
+                all,
+                set: QuantifiedSet::Values(values),
+            }) => {
+                let (first_val, _, _) = self.evaluate_cell(row_index, first)?;
+                let collation = given_collation(first);
+                let result = apply_collated_quantified_operator(
+                    operand, *all, collation, &first_val, values,
+                )?;
+                Ok((result, ANONYMOUS_COL_NAME, SqlType::Boolean))
+            }
+            _ => Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
         }
     }
 
@@ -458,19 +689,40 @@ impl Table {
         &self,
         row_index: usize,
         expression: &Expression,
-    ) -> Result<(SqlValue, &str, SqlType), String> {
+    ) -> Result<(SqlValue, &str, SqlType), BackendError> {
         match expression {
             Expression::Literal(_)
             | Expression::TableColumn(_)
             | Expression::ProcessedTableColumn(_) => {
                 self.evaluate_literal_cell(row_index, expression)
             }
-            Expression::Binary(_) | Expression::Unary(_) | Expression::Cast { data: _, typ: _ } => {
-                self.evaluate_binary_cell(row_index, expression)
+            Expression::Binary(_)
+            | Expression::Unary(_)
+            | Expression::Cast { data: _, typ: _ }
+            | Expression::Collate { .. }
+            | Expression::Quantified(_) => self.evaluate_binary_cell(row_index, expression),
+            Expression::FunctionCall(call) => {
+                // Window functions have been computed into columns before this point
+                if call.over.is_some() {
+                    return Err(BackendError::WindowingError(
+                        ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string(),
+                    ));
+                }
+                let function = ScalarFunction::from_call(call)?;
+                let mut args = Vec::with_capacity(call.args.len());
+                for arg in &call.args {
+                    let (val, _, typ) = self.evaluate_cell(row_index, arg)?;
+                    args.push((val, typ));
+                }
+                let result = function.call_typed(&args)?;
+                let typ = result.get_type();
+                Ok((result, ANONYMOUS_COL_NAME, typ))
             }
             Expression::SubSelect(select_statement) => {
                 if select_statement.items.len() != 1 {
-                    return Err("Subquery must return only one column".to_string());
+                    return Err(BackendError::SyntaxError(
+                        "Subquery must return only one column".to_string(),
+                    ));
                 }
                 if Expression::Empty != select_statement.where_clause {
                     if let (SqlValue::Boolean(false), _, SqlType::Boolean) =
@@ -483,23 +735,56 @@ impl Table {
                     let (result, _, typ) = self.evaluate_cell(row_index, &item.expression)?;
                     return Ok((result, ANONYMOUS_COL_NAME, typ));
                 } else {
-                    Err("Subquery must return only one column".to_string())
+                    Err(BackendError::SyntaxError(
+                        "Subquery must return only one column".to_string(),
+                    ))
                 }
             }
-            _ => Err(ERR_INVALID_CELL.to_string()),
+            _ => Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
         }
     }
 
+    // Types of the select items for a query that gave no rows to take them from, found
+    // by evaluating the items on a made up row of sample values
+    fn sample_types(&self, items: &[SelectItem]) -> Vec<SqlType> {
+        let _sequences = enter_sampling();
+        let _monitor = leave_monitor();
+        let sample_row: Vec<SqlValue> = self
+            .column_types
+            .iter()
+            .map(|typ| sample_value(*typ))
+            .collect();
+        let sample = Table {
+            name: self.name.clone(),
+            columns: self.columns.clone(),
+            column_types: self.column_types.clone(),
+            rows: vec![StoredRow::new(&sample_row)],
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        };
+        items
+            .iter()
+            .map(|item| match sample.evaluate_cell(0, &item.expression) {
+                Ok((_, _, col_type)) => col_type,
+                Err(_) => SqlType::Null,
+            })
+            .collect()
+    }
+
     pub fn get_applicable_indexes(
         &self,
         where_clause: Option<&Expression>,
-    ) -> Result<Vec<(&Index, Expression)>, String> {
+    ) -> Result<Vec<(&Index, Expression)>, BackendError> {
         let exps = linearize_expressions(where_clause.map(|v| v.clone()), vec![]);
 
         let mut indexes_and_expressions = vec![];
         for exp in &exps {
             for index in &self.indexes {
-                if let Some(_) = index.applicable_value(exp)? {
+                if index.lookup_key(self, exp).is_some() {
                     let index_and_expression = (index, exp.clone());
                     indexes_and_expressions.push(index_and_expression);
                 }
@@ -510,572 +795,1573 @@ impl Table {
     }
 }
 
-#[derive(PartialEq)]
+// What a transaction began with, which ending it needs
+struct TransactionStart {
+    outer_writes: Option<HashSet<String>>,
+    changes: usize,
+    notifications: usize,
+    variables: usize,
+    // Whether it fixed the time functions like now() give, see `begin_transaction_time`
+    timed: bool,
+}
+
+// A value an INSERT gives a column, in the statement or in a row of its query, or bound to
+// a parameter of a prepared one
+enum GivenValue<'a> {
+    Expression(&'a Expression),
+    Selected(&'a SqlValue),
+}
+
 pub struct MemoryBackend {
-    tables: HashMap<String, Table>,
+    storage: Box<dyn StorageEngine>,
+    // Temporary tables of the session using the backend, they shadow those of `storage`
+    temp_tables: MemoryStorage,
+    // What the queries of the running WITH gave and the tables they change as they were
+    // before it, read in place of any table of the same name
+    with_tables: MemoryStorage,
+    // Tables written to by the running transaction, None outside of transactions
+    pending_writes: Option<HashSet<String>>,
+    // Transactions running, one inside the other
+    transaction_depth: usize,
+    // The transaction BEGIN started, if it is still running
+    transaction_block: Option<TransactionBlock>,
+    // The connection the transaction BEGIN started belongs to, which the others wait to
+    // end, see `Connection::with_session`
+    block_session: Option<u64>,
+    block_ended: Arc<Condvar>,
+    limits: ResultLimits,
+    // In kB, see `BackendConfig::work_mem`
+    work_mem: Option<usize>,
+    temp_directory: Option<PathBuf>,
+    statement_cache: StatementCache,
+    // Last version handed to a table schema, never goes back, even on rollback
+    schema_version: u64,
+    // Bumped whenever the tables or views names can refer to change, see `Resolver`
+    catalog_version: u64,
+    // What names refer to for the session using the backend
+    resolver: Resolver,
+    #[cfg(feature = "parallel")]
+    parallel_scans: bool,
+    timing: TimingRecorder,
+    reorder_joins: bool,
+    hash_semi_joins: bool,
+    allow_unknown_settings: bool,
+    variables: SessionVariables,
+    sources: FunctionSources,
+    changes: ChangeNotifier,
+    notifications: Notifications,
+    cursors: Cursors,
+    sequences: Sequences,
+    views: Views,
+    triggers: Triggers,
+    read_only: bool,
+    division_by_zero: DivisionByZero,
+    cast_mode: CastMode,
+    max_identifier_length: usize,
+    identifier_mode: IdentifierMode,
+    conflict_retry: Option<ConflictRetry>,
+    // Set while a `Connection` runs a query, see `SessionRetry`
+    session_retry: Option<SessionRetry>,
+    monitor: StatementMonitor,
 }
 
 pub fn get_true_mem_cell() -> MemoryCell {
-    MemoryCell { bytes: vec![1] }
+    SqlValue::Boolean(true).encode()
 }
 pub fn get_false_mem_cell() -> MemoryCell {
-    MemoryCell { bytes: vec![0] }
+    SqlValue::Boolean(false).encode()
 }
 pub fn get_true_lex_token() -> TokenContainer {
     TokenContainer {
         token: Token::BoolValue { value: true },
         loc: TokenLocation { line: 0, col: 0 },
+        span: Span::default(),
     }
 }
 pub fn get_false_lex_token() -> TokenContainer {
     TokenContainer {
         token: Token::BoolValue { value: false },
         loc: TokenLocation { line: 0, col: 0 },
+        span: Span::default(),
     }
 }
 
 impl MemoryBackend {
     pub fn new() -> MemoryBackend {
         return Self {
-            tables: HashMap::new(),
+            storage: Box::new(MemoryStorage::default()),
+            temp_tables: MemoryStorage::default(),
+            with_tables: MemoryStorage::default(),
+            pending_writes: None,
+            transaction_depth: 0,
+            transaction_block: None,
+            block_session: None,
+            block_ended: Arc::new(Condvar::new()),
+            limits: ResultLimits::default(),
+            work_mem: None,
+            temp_directory: None,
+            statement_cache: StatementCache::new(DEFAULT_STATEMENT_CACHE_CAPACITY),
+            schema_version: 0,
+            catalog_version: 0,
+            resolver: Resolver::default(),
+            #[cfg(feature = "parallel")]
+            parallel_scans: true,
+            timing: TimingRecorder::default(),
+            reorder_joins: true,
+            hash_semi_joins: true,
+            allow_unknown_settings: false,
+            variables: SessionVariables::new(BackendConfig::default()),
+            sources: FunctionSources::new(&BackendConfig::default()),
+            changes: ChangeNotifier::default(),
+            notifications: Notifications::default(),
+            cursors: Cursors::default(),
+            sequences: Sequences::default(),
+            views: Views::default(),
+            triggers: Triggers::default(),
+            read_only: false,
+            division_by_zero: DivisionByZero::default(),
+            cast_mode: CastMode::default(),
+            max_identifier_length: DEFAULT_MAX_IDENTIFIER_LENGTH,
+            identifier_mode: IdentifierMode::default(),
+            conflict_retry: None,
+            session_retry: None,
+            monitor: StatementMonitor::default(),
         };
     }
 
-    pub fn create_table(&mut self, create_statement: CreateTableStatement) -> Result<bool, String> {
+    pub fn create_table(
+        &mut self,
+        create_statement: CreateTableStatement,
+    ) -> Result<bool, BackendError> {
         let mut new_table = Table {
             name: create_statement.name.clone(),
             columns: vec![],
             column_types: vec![],
             rows: vec![],
             indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
         };
 
         if create_statement.cols.len() == 0 {
-            return Err("No Table Columns.".to_owned());
+            return Err(BackendError::SyntaxError("No Table Columns.".to_owned()));
         }
 
-        match self.tables.get(&create_statement.name) {
-            Some(_) => {
-                return Err(format!(
-                    "Table \"{}\" already exists.",
-                    create_statement.name.clone()
-                ));
-            }
-            _ => {}
+        let namespace = self.namespace(create_statement.is_temporary);
+        if namespace.table(&create_statement.name).is_some() {
+            return Err(BackendError::DuplicateTable(format!(
+                "Table \"{}\" already exists.",
+                create_statement.name.clone()
+            )));
+        }
+        if !create_statement.is_temporary
+            && (self.sequences.contains(&create_statement.name)
+                || self.views.contains(&create_statement.name))
+        {
+            return Err(BackendError::DuplicateTable(format!(
+                "Relation \"{}\" already exists.",
+                create_statement.name
+            )));
         }
 
-        let mut primary_key: Option<Expression> = None;
-
-        for col in create_statement.cols {
+        // The sequences of the SERIAL columns, made once the table is
+        let mut serial_sequences = vec![];
+        for mut col in create_statement.cols {
             new_table.columns.push(col.name.clone());
 
             let data_type;
             if let Ok(typ) = SqlType::from_token(col.data_type.token) {
                 data_type = typ;
             } else {
-                self.tables.remove(&new_table.name);
-                return Err(ERR_INVALID_DATA_TYPE.to_string());
+                return Err(BackendError::SyntaxError(ERR_INVALID_DATA_TYPE.to_string()));
+            }
+
+            if col.default.is_some() && col.generated.is_some() {
+                return Err(BackendError::SyntaxError(format!(
+                    "Both default and generation expression specified for column \"{}\" of table \"{}\".",
+                    col.name, create_statement.name
+                )));
+            }
+
+            if col.serial {
+                if col.default.is_some() || col.generated.is_some() {
+                    return Err(BackendError::SyntaxError(format!(
+                        "Multiple default values specified for column \"{}\" of table \"{}\".",
+                        col.name, create_statement.name
+                    )));
+                }
+                let sequence = self.serial_sequence_name(&create_statement.name, &col.name);
+                col.default = Some(Expression::FunctionCall(FunctionCall {
+                    name: "nextval".to_string(),
+                    args: vec![Expression::new_literal_string(quote_identifier(&sequence))],
+                    asterisk: false,
+                    distinct: false,
+                    filter: None,
+                    over: None,
+                }));
+                col.not_null = true;
+                let max = match data_type {
+                    SqlType::SmallInt => i64::from(i16::MAX),
+                    SqlType::Int => i64::from(i32::MAX),
+                    _ => i64::MAX,
+                };
+                serial_sequences.push((sequence, col.name.clone(), max));
             }
 
             if col.is_primary_key {
-                if primary_key != None {
-                    self.tables.remove(&new_table.name);
-                    return Err("Primary Key already exists".to_string());
+                if !new_table.indexes.is_empty() {
+                    return Err(BackendError::InvalidTableDefinition(
+                        "Primary Key already exists".to_string(),
+                    ));
                 }
-                primary_key = Some(Expression::new_literal_id(col.name));
+                // The table is empty, so the index starts out empty too
+                new_table.indexes.push(Index {
+                    name: format!("{}_pkey", create_statement.name),
+                    columns: vec![col.name.clone()],
+                    expression: Expression::new_literal_id(col.name),
+                    unique: true,
+                    primary_key: true,
+                    typ: "btreemap".to_string(),
+                    tree: std::collections::btree_map::BTreeMap::new(),
+                    bytes: 0,
+                });
             }
 
+            let collation = match &col.collation {
+                Some(name) => {
+                    check_collatable(data_type)?;
+                    Some(Collation::from_name(name)?)
+                }
+                None => None,
+            };
+
             new_table.column_types.push(data_type);
+            new_table.column_constraints.push(ColumnConstraints {
+                default: col.default,
+                not_null: col.not_null,
+                type_modifier: col.type_modifier,
+                generated: col.generated,
+                collation,
+            });
         }
+        new_table.add_checks(create_statement.checks)?;
+        new_table.check_generated_columns()?;
 
-        self.tables.insert(create_statement.name.clone(), new_table);
-
-        if let Some(primary_key) = primary_key {
-            match self.create_index(CreateIndexStatement {
+        self.namespace_mut(create_statement.is_temporary)
+            .create_table(new_table)?;
+        for (sequence, column, max) in serial_sequences {
+            let owner = SequenceOwner {
                 table: create_statement.name.clone(),
-                name: format!("{}_pkey", create_statement.name),
-                is_unique: true,
-                is_primary_key: true,
-                expression: primary_key,
-            }) {
-                Err(err) => {
-                    self.tables.remove(&create_statement.name);
-                    return Err(err);
-                }
-                _ => {}
-            }
+                column,
+                is_temporary: create_statement.is_temporary,
+            };
+            self.create_serial_sequence(sequence, owner, max);
         }
+        self.mark_written(&create_statement.name);
+        self.schema_changed(&create_statement.name);
 
         return Ok(true);
     }
 
-    pub fn insert(&mut self, insert_statement: InsertStatement) -> Result<bool, String> {
-        let &table = &match self.tables.get(&insert_statement.table) {
-            Some(value) => value,
-            None => {
-                return Err(ERR_TABLE_DOES_NOT_EXIST.to_owned());
-            }
-        };
+    // Creates a table shaped like the results of the query and, unless WITH NO DATA was
+    // given, fills it with them. Returns how many rows went in.
+    pub fn create_table_as(
+        &mut self,
+        statement: CreateTableAsStatement,
+    ) -> Result<usize, BackendError> {
+        if self
+            .namespace(statement.is_temporary)
+            .table(&statement.name)
+            .is_some()
+        {
+            return Err(BackendError::DuplicateTable(format!(
+                "Table \"{}\" already exists.",
+                statement.name
+            )));
+        }
 
-        if insert_statement.values.len() != table.columns.len() {
-            return Err(ERR_MISSING_VALUES.to_owned());
+        let mut query = statement.query;
+        if !statement.with_data {
+            query.limit = Some(0);
         }
+        let results = self.select(query)?;
 
-        let mut row: Vec<SqlValue> = vec![];
+        if results.columns.is_empty() {
+            return Err(BackendError::SyntaxError("No Table Columns.".to_owned()));
+        }
+        if statement.columns.len() > results.columns.len() {
+            return Err(BackendError::SyntaxError(
+                "Too many column names were specified.".to_owned(),
+            ));
+        }
+        let mut columns: Vec<String> = results.columns.iter().map(|c| c.name.clone()).collect();
+        for (column, name) in columns.iter_mut().zip(statement.columns) {
+            *column = name;
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].contains(column) {
+                return Err(BackendError::DuplicateColumn(format!(
+                    "Column \"{}\" specified more than once.",
+                    column
+                )));
+            }
+        }
 
-        for (i, value) in insert_statement.values.iter().enumerate() {
-            match value {
-                Expression::Literal(value) => {
-                    let typ = match table.column_types.get(i) {
-                        Some(val) => val,
-                        None => {
-                            return Err("Column type not found".to_owned());
-                        }
-                    };
+        // The first row decides the types, a column it had NULL in takes the type of the
+        // first value that isn't, or TEXT if there is none
+        let mut column_types = Vec::with_capacity(columns.len());
+        for (i, column) in results.columns.iter().enumerate() {
+            let typ = match column.col_type {
+                SqlType::Null => results
+                    .rows
+                    .iter()
+                    .map(|row| row[i].get_type())
+                    .find(|typ| *typ != SqlType::Null)
+                    .unwrap_or(SqlType::Text),
+                typ => typ,
+            };
+            if typ == SqlType::Type {
+                return Err(BackendError::SyntaxError(ERR_INVALID_DATA_TYPE.to_string()));
+            }
+            column_types.push(typ);
+        }
+        // Lengths carry over, NOT NULL doesn't
+        let column_constraints = results
+            .columns
+            .iter()
+            .map(|column| ColumnConstraints {
+                type_modifier: column.type_modifier,
+                ..ColumnConstraints::default()
+            })
+            .collect();
 
-                    let cell = literal_to_memory_cell(&value.literal)?.to_type(*typ)?;
-                    row.push(cell);
-                }
-                _ => {
-                    println!("Skipping non-literal.");
-                    continue;
-                }
+        let mut rows = Vec::with_capacity(results.rows.len());
+        for row in results.rows {
+            let mut new_row = Vec::with_capacity(row.len());
+            for (value, typ) in row.iter().zip(&column_types) {
+                new_row.push(value.to_type(*typ)?);
             }
+            rows.push(new_row);
+        }
+
+        let inserted = rows.len();
+        if self.changes.hooked() {
+            self.changes.record_inserts(&statement.name, rows.clone());
         }
+        self.namespace_mut(statement.is_temporary)
+            .create_table(Table {
+                name: statement.name.clone(),
+                columns,
+                column_types,
+                rows: rows.into_iter().map(StoredRow::from).collect(),
+                indexes: vec![],
+                column_constraints,
+                checks: vec![],
+                schema_version: 0,
+                heap_bytes: 0,
+                dead_rows: 0,
+            })?;
+        self.mark_written(&statement.name);
+        self.schema_changed(&statement.name);
 
-        let table = match self.tables.get_mut(&insert_statement.table) {
+        Ok(inserted)
+    }
+
+    pub fn insert(&mut self, insert_statement: InsertStatement) -> Result<bool, BackendError> {
+        self.insert_rows(&insert_statement)?;
+        Ok(true)
+    }
+
+    // Like `insert`, giving the rows as they went in
+    pub(super) fn insert_rows(
+        &mut self,
+        insert_statement: &InsertStatement,
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        // The rows of a query are all there before any goes in, even into the table it reads
+        let selected = match &insert_statement.query {
+            Some(query) => Some(self.select(query.clone())?.rows),
+            None => None,
+        };
+        let &table = &match self.table(&insert_statement.table) {
             Some(value) => value,
             None => {
-                return Err(ERR_TABLE_DOES_NOT_EXIST.to_owned());
+                return Err(BackendError::UndefinedTable(
+                    ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                ));
             }
         };
 
-        table.rows.push(row);
-        let row_index = table.rows.len() - 1;
-        for i in 0..table.indexes.len() {
-            let index = match table.indexes.get(i) {
-                None => {
-                    return Err("Index not found".to_string());
-                }
-                Some(value) => value,
+        let checks = table.compile_checks();
+        let generated = table.compile_generated();
+        let row_count = match &selected {
+            Some(selected) => selected.len(),
+            None => insert_statement.rows.len(),
+        };
+        let mut rows = Vec::with_capacity(row_count);
+        for row_idx in 0..row_count {
+            let values: Vec<GivenValue> = match &selected {
+                Some(selected) => selected[row_idx].iter().map(GivenValue::Selected).collect(),
+                None => insert_statement.rows[row_idx]
+                    .iter()
+                    .map(GivenValue::Expression)
+                    .collect(),
             };
-            let (index_value, _, _) = table.evaluate_cell(row_index, &index.expression)?;
+            rows.push(self.insert_row(table, &checks, &generated, values, row_idx + 1)?);
+        }
+        self.write_inserts(&insert_statement.table, rows)
+    }
 
-            if index_value.is_null() {
-                table.rows.remove(row_index);
-                return Err("Violates NOT NULL Constraint".to_string());
-            }
+    // The row an INSERT puts in a table for the values it gives, `row` counting the rows of
+    // the statement for errors. Values are worked out first, defaults afresh for every row,
+    // and only then converted to the column types, with the casts allowed on assignment.
+    // Generated columns are computed last, from the rest, and the constraints of the table
+    // checked on the whole row.
+    fn insert_row(
+        &self,
+        table: &Table,
+        checks: &[CompiledExpression],
+        generated: &[(usize, CompiledExpression)],
+        values: Vec<GivenValue>,
+        row: usize,
+    ) -> Result<Vec<SqlValue>, BackendError> {
+        if values.len() != table.columns.len() {
+            return Err(BackendError::SyntaxError(ERR_MISSING_VALUES.to_owned()));
+        }
 
-            if index.unique {
-                if let Some(_) = index.tree.get(&index_value) {
-                    table.rows.remove(row_index);
-                    return Err("Duplicate Value violates UNIQUE Constraint".to_string());
+        let mut new_row: Vec<SqlValue> = Vec::with_capacity(table.columns.len());
+        for (i, value) in values.into_iter().enumerate() {
+            let value = match value {
+                GivenValue::Expression(Expression::Literal(LiteralExpression {
+                    literal: Token::Default,
+                })) if table.is_generated(i) => SqlValue::Null,
+                _ if table.is_generated(i) => {
+                    return Err(BackendError::GeneratedAlways(format!(
+                        "Cannot insert a non-DEFAULT value into column \"{}\".",
+                        table.columns[i]
+                    )))
                 }
-            }
-            let index = match table.indexes.get_mut(i) {
-                None => {
-                    return Err("Index not found".to_string());
-                }
-                Some(value) => value,
+                GivenValue::Expression(Expression::Literal(LiteralExpression {
+                    literal: Token::Default,
+                })) => match table
+                    .column_constraints
+                    .get(i)
+                    .and_then(|c| c.default.as_ref())
+                {
+                    Some(default) => self.evaluate_constant(default)?,
+                    None => SqlValue::Null,
+                },
+                GivenValue::Expression(value) => self.evaluate_constant(value)?,
+                GivenValue::Selected(value) => value.clone(),
             };
+            new_row.push(table.assign(i, &value, row, self.cast_mode)?);
+        }
+        table.fill_generated(generated, &mut new_row)?;
+        table.check_not_null(&new_row)?;
+        table.check_row(checks, &new_row)?;
+        Ok(new_row)
+    }
 
-            match index.tree.get_mut(&index_value) {
-                Some(row_indexes) => {
-                    row_indexes.push(row_index);
-                }
+    // Writes the rows of an INSERT, all of them or none, firing the triggers of the table
+    // and telling the change hook about them. Gives the rows as they went in.
+    fn write_inserts(
+        &mut self,
+        table: &str,
+        rows: Vec<Vec<SqlValue>>,
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        self.mark_written(table);
+        let writes = RowWrites::Insert(rows);
+        self.write_rows_firing(table, &writes)?;
+        let rows = writes.into_rows();
+        if self.changes.hooked() {
+            self.changes.record_inserts(table, rows.clone());
+        }
+        Ok(rows)
+    }
+
+    // Evaluates an expression that has no row to read columns from, like the values of
+    // an INSERT. Subqueries in it run in full and must give at most one value.
+    fn evaluate_constant(&self, expression: &Expression) -> Result<SqlValue, BackendError> {
+        match expression {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => Err(BackendError::UndefinedColumn(format!(
+                "{}: {}",
+                value, ERR_COLUMN_NOT_IN_CONTEXT
+            ))),
+            Expression::Literal(literal) => literal_to_memory_cell(&literal.literal),
+            Expression::TableColumn(table_column) => Err(BackendError::UndefinedColumn(format!(
+                "{}: {}",
+                table_column.col_name, ERR_COLUMN_NOT_IN_CONTEXT
+            ))),
+            Expression::ProcessedTableColumn(_) => Err(BackendError::UndefinedColumn(
+                ERR_COLUMN_NOT_IN_CONTEXT.to_string(),
+            )),
+            Expression::Binary(binary) => apply_collated_operator(
+                &binary.operand,
+                operand_collation(&binary.first, &binary.second),
+                &self.evaluate_constant(&binary.first)?,
+                &self.evaluate_constant(&binary.second)?,
+            ),
+            Expression::Unary(unary) => match unary.negated_literal() {
+                Some(literal) => literal_to_memory_cell(&literal),
                 None => {
-                    table.indexes[i].tree.insert(index_value, vec![row_index]);
+                    apply_unary_operator(&unary.operand, &self.evaluate_constant(&unary.first)?)
+                }
+            },
+            Expression::Cast { data, typ } => {
+                Ok(explicit_cast(&self.evaluate_constant(data)?, *typ)?)
+            }
+            Expression::Collate { data, collation } => {
+                Collation::from_name(collation)?;
+                self.evaluate_constant(data)
+            }
+            Expression::Quantified(quantified) => {
+                let first_val = self.evaluate_constant(&quantified.first)?;
+                let values = match &quantified.set {
+                    QuantifiedSet::SubSelect(select_statement) => {
+                        self.subquery_values(select_statement)?
+                    }
+                    QuantifiedSet::Values(values) => values.clone(),
+                };
+                apply_collated_quantified_operator(
+                    &quantified.operand,
+                    quantified.all,
+                    given_collation(&quantified.first),
+                    &first_val,
+                    &values,
+                )
+            }
+            Expression::FunctionCall(call) => {
+                if call.over.is_some() {
+                    return Err(BackendError::WindowingError(
+                        ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string(),
+                    ));
                 }
+                let function = ScalarFunction::from_call(call)?;
+                let args = call
+                    .args
+                    .iter()
+                    .map(|arg| self.evaluate_constant(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(&args)
             }
+            Expression::SubSelect(select_statement) => {
+                Ok(self.scalar_subquery(select_statement)?.0)
+            }
+            Expression::Exists(select_statement) => {
+                Ok(SqlValue::Boolean(self.exists(select_statement)?))
+            }
+            Expression::Empty => Err(BackendError::Internal(ERR_INVALID_CELL.to_string())),
         }
-
-        return Ok(true);
     }
 
-    pub fn select(
+    // The value a subquery used as an expression gives, NULL for no row, along with the
+    // type of its column
+    fn scalar_subquery(
         &self,
-        select_statement: SelectStatement,
-    ) -> Result<QueryResults<SqlValue>, String> {
-        let mut tables: HashMap<String, TableContainer> = HashMap::new();
-
-        let mut results: Vec<Vec<SqlValue>> = Vec::with_capacity(100);
-        let mut results_order: Vec<SqlValue> = Vec::with_capacity(100);
-        let mut offset = 0;
-
-        let mut columns: ResultColumns = Vec::with_capacity(10);
+        select_statement: &SelectStatement,
+    ) -> Result<(SqlValue, SqlType), BackendError> {
+        let mut results = self.select(select_statement.clone())?;
+        if select_statement.items.len() != 1 || results.columns.len() > 1 {
+            return Err(BackendError::SyntaxError(
+                "Subquery must return only one column".to_string(),
+            ));
+        }
+        let typ = results
+            .columns
+            .first()
+            .map_or(SqlType::Null, |column| column.col_type);
+        match results.rows.as_mut_slice() {
+            [] => Ok((SqlValue::Null, typ)),
+            [row] => Ok((row.remove(0), typ)),
+            _ => Err(BackendError::CardinalityViolation(
+                "Subquery used as an expression returned more than one row".to_string(),
+            )),
+        }
+    }
 
-        if select_statement.items.is_empty() {
-            return Ok(QueryResults {
-                columns,
-                rows: results,
-            });
+    // The values of the single column a subquery gives
+    fn subquery_values(
+        &self,
+        select_statement: &SelectStatement,
+    ) -> Result<Vec<SqlValue>, BackendError> {
+        let results = self.select(select_statement.clone())?;
+        if select_statement.items.len() != 1 || results.columns.len() > 1 {
+            return Err(BackendError::SyntaxError(
+                "Subquery must return only one column".to_string(),
+            ));
         }
+        Ok(results
+            .rows
+            .into_iter()
+            .map(|mut row| row.remove(0))
+            .collect())
+    }
 
-        let mut table_joins = &vec![];
-        let (table_name, mut table) = match select_statement.from.get(0) {
-            Some(RowDataSource::Table {
-                as_clause,
-                table_name: ref from_name,
-                joins,
-            }) => match self.tables.get(from_name) {
-                // TODO
-                None => {
-                    return Err(ERR_TABLE_DOES_NOT_EXIST.to_string());
+    // Runs the subqueries of the ANY and ALL comparisons and of EXISTS in `expression`,
+    // keeping the values they gave in their place
+    fn run_quantified_subqueries(&self, expression: &mut Expression) -> Result<(), BackendError> {
+        match expression {
+            Expression::Quantified(quantified) => {
+                self.run_quantified_subqueries(&mut quantified.first)?;
+                if let QuantifiedSet::SubSelect(select_statement) = &quantified.set {
+                    quantified.set = QuantifiedSet::Values(self.subquery_values(select_statement)?);
                 }
-                Some(table) => {
-                    let mut new_table;
-                    let from_name = if let Some(from_name) = as_clause {
-                        from_name.clone()
-                    } else {
-                        from_name.clone()
-                    };
-
-                    new_table = TableContainer::Concrete(&table);
-                    for (index, exp) in
-                        table.get_applicable_indexes(Some(&select_statement.where_clause))?
-                    {
-                        if let Expression::Binary(bin_exp) = exp {
-                            new_table = TableContainer::Temp(Box::new(
-                                index.new_table_from_subset(table, bin_exp)?,
-                            ));
-                        }
-                    }
-                    table_joins = joins;
-                    (from_name.clone(), new_table)
+            }
+            Expression::Binary(binary) => {
+                self.run_quantified_subqueries(&mut binary.first)?;
+                self.run_quantified_subqueries(&mut binary.second)?;
+            }
+            Expression::Exists(select_statement) => {
+                *expression = Expression::Literal(LiteralExpression {
+                    literal: Token::BoolValue {
+                        value: self.exists(select_statement)?,
+                    },
+                });
+            }
+            Expression::Unary(unary) => self.run_quantified_subqueries(&mut unary.first)?,
+            Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+                self.run_quantified_subqueries(data)?
+            }
+            Expression::FunctionCall(call) => {
+                for arg in call.args.iter_mut() {
+                    self.run_quantified_subqueries(arg)?;
                 }
-            },
-            Some(RowDataSource::SubSelect {
+                if let Some(filter) = call.filter.as_mut() {
+                    self.run_quantified_subqueries(filter)?;
+                }
+            }
+            Expression::SubSelect(_)
+            | Expression::Literal(_)
+            | Expression::TableColumn(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::Empty => {}
+        }
+        Ok(())
+    }
+
+    // Runs a function of FROM. Its rows make up a table named after the alias or else the
+    // function, with a column named after the column alias, or the table.
+    fn function_table(
+        &self,
+        call: &FunctionCall,
+        as_clause: &Option<String>,
+        column_aliases: &[String],
+    ) -> Result<(String, Table), BackendError> {
+        if call.over.is_some() {
+            return Err(BackendError::WindowingError(
+                ERR_WINDOW_FUNCTION_NOT_ALLOWED.to_string(),
+            ));
+        }
+        let function = TableFunction::from_name(&call.name)?;
+        let table_name = as_clause.clone().unwrap_or_else(|| call.name.clone());
+        if column_aliases.len() > 1 {
+            return Err(BackendError::InvalidColumnReference(format!(
+                "Table \"{}\" has 1 column available but {} columns were specified.",
+                table_name,
+                column_aliases.len()
+            )));
+        }
+        let column = column_aliases.first().unwrap_or(&table_name).clone();
+
+        let args = call
+            .args
+            .iter()
+            .map(|arg| self.evaluate_constant(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (typ, values) = function.call(&args)?;
+        let mut materialized = MaterializedRows::new(self.limits, function.name());
+        let mut rows = vec![];
+        for value in values {
+            let row = vec![value];
+            materialized.add(&row)?;
+            rows.push(StoredRow::new(&row));
+        }
+
+        let table = Table {
+            name: table_name.clone(),
+            columns: vec![column],
+            column_types: vec![typ],
+            rows,
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        };
+        Ok((table_name, table))
+    }
+
+    // Evaluates a VALUES list into a table. A column takes the type of its first value
+    // that is not NULL, widened to hold the numbers or text of later rows, and every value
+    // is converted to it.
+    fn values_table(
+        &self,
+        rows: &[Vec<Expression>],
+        as_clause: &str,
+        column_aliases: &[String],
+    ) -> Result<(String, Table), BackendError> {
+        let width = rows.first().map_or(0, Vec::len);
+        if column_aliases.len() > width {
+            return Err(BackendError::InvalidColumnReference(format!(
+                "Table \"{}\" has {} columns available but {} columns were specified.",
                 as_clause,
-                select,
-                joins,
-            }) => {
-                // TODO
-                let result = self.select(select.clone())?;
-                let new_table = Table::from(result);
-                table_joins = joins;
-                (as_clause.clone(), TableContainer::Temp(Box::new(new_table)))
+                width,
+                column_aliases.len()
+            )));
+        }
+
+        let mut materialized = MaterializedRows::new(self.limits, "VALUES");
+        let mut values = Vec::with_capacity(rows.len());
+        for row in rows {
+            let row = row
+                .iter()
+                .map(|exp| self.evaluate_constant(exp))
+                .collect::<Result<Vec<_>, _>>()?;
+            materialized.add(&row)?;
+            values.push(row);
+        }
+
+        let is_number = |typ: SqlType| {
+            matches!(
+                typ,
+                SqlType::SmallInt
+                    | SqlType::Int
+                    | SqlType::BigInt
+                    | SqlType::Real
+                    | SqlType::DoublePrecision
+            )
+        };
+        let is_text =
+            |typ: SqlType| matches!(typ, SqlType::Char | SqlType::VarChar | SqlType::Text);
+        let mut column_types = vec![SqlType::Null; width];
+        for row in &values {
+            for (typ, value) in column_types.iter_mut().zip(row) {
+                *typ = match (*typ, value.get_type()) {
+                    (typ, SqlType::Null) => typ,
+                    (SqlType::Null, value_type) => value_type,
+                    (typ, value_type) if typ == value_type => typ,
+                    (typ, value_type)
+                        if is_number(typ) && is_number(value_type)
+                            || is_text(typ) && is_text(value_type) =>
+                    {
+                        std::cmp::max_by_key(typ, value_type, SqlType::order)
+                    }
+                    (typ, value_type) => {
+                        return Err(BackendError::DatatypeMismatch(format!(
+                            "VALUES types {} and {} cannot be matched.",
+                            typ, value_type
+                        )));
+                    }
+                };
             }
-            None => {
+        }
+        for typ in column_types.iter_mut() {
+            if *typ == SqlType::Null {
+                *typ = SqlType::Text;
+            }
+        }
+        for row in values.iter_mut() {
+            for (value, typ) in row.iter_mut().zip(&column_types) {
+                if !value.is_null() {
+                    *value = value.to_type(*typ)?;
+                }
+            }
+        }
+
+        let columns = (0..width)
+            .map(|idx| match column_aliases.get(idx) {
+                Some(alias) => alias.clone(),
+                None => format!("column{}", idx + 1),
+            })
+            .collect();
+        let table = Table {
+            name: as_clause.to_string(),
+            columns,
+            column_types,
+            rows: values.into_iter().map(StoredRow::from).collect(),
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        };
+        Ok((as_clause.to_string(), table))
+    }
+
+    pub fn select(
+        &self,
+        mut select_statement: SelectStatement,
+    ) -> Result<QueryResults<SqlValue>, BackendError> {
+        // Operators are only timed as a whole, the rows they go through never are
+        let mut plan = PlanBuilder::new(&self.timing);
+
+        // Without a FROM the select items are worked out on a single row of no columns,
+        // which `*` would have nothing to list of
+        if select_statement.from.is_empty()
+            && select_statement.items.iter().any(|item| item.asterisk)
+        {
+            return Err(BackendError::SyntaxError(
+                ERR_ASTERISK_WITHOUT_FROM.to_string(),
+            ));
+        }
+
+        // Comparisons go by the collation of what they compare, and sorting, grouping and
+        // DISTINCT by that of the expressions they go by
+        let collations = self
+            .column_origins(select_statement.from.first())
+            .collations();
+        let mut item_derivations = Vec::with_capacity(select_statement.items.len());
+        for item in select_statement.items.iter_mut() {
+            item_derivations.push(collations.resolve(&mut item.expression)?);
+        }
+        collations.resolve(&mut select_statement.where_clause)?;
+        collations.resolve(&mut select_statement.having)?;
+        let mut group_collations = Vec::with_capacity(select_statement.group_by.len());
+        for key in select_statement.group_by.iter_mut() {
+            group_collations.push(collations.resolve(key)?.collation()?);
+        }
+        let mut order_collations = Vec::with_capacity(select_statement.order_by.len());
+        for order_by in select_statement.order_by.iter_mut() {
+            order_collations.push(collations.resolve(&mut order_by.exp)?.collation()?);
+        }
+        // EXISTS, IN and NOT IN conditions of WHERE that read the outer row, or that are
+        // hashed, run as semi joins on the joined rows
+        let planning = self.timing.now();
+        let (semi_joins, where_clause) = self.plan_semi_joins(&select_statement.where_clause);
+        select_statement.where_clause = where_clause;
+        self.timing.add_planning(self.timing.since(planning));
+
+        // The subqueries of ANY, ALL and EXISTS give the same values for every row
+        for item in select_statement.items.iter_mut() {
+            self.run_quantified_subqueries(&mut item.expression)?;
+        }
+        self.run_quantified_subqueries(&mut select_statement.where_clause)?;
+        for exp in select_statement.group_by.iter_mut() {
+            self.run_quantified_subqueries(exp)?;
+        }
+        self.run_quantified_subqueries(&mut select_statement.having)?;
+        for order_by in select_statement.order_by.iter_mut() {
+            self.run_quantified_subqueries(&mut order_by.exp)?;
+        }
+        for source in select_statement.from.iter_mut() {
+            let joins = match source {
+                RowDataSource::Table { joins, .. }
+                | RowDataSource::SubSelect { joins, .. }
+                | RowDataSource::Function { joins, .. }
+                | RowDataSource::Values { joins, .. } => joins,
+            };
+            for join in joins.iter_mut() {
+                self.run_quantified_subqueries(&mut join.on)?;
+                collations.resolve(&mut join.on)?;
+            }
+        }
+        plan.take_subplans(&self.timing);
+        // Scans that copy rows only carry the columns the query reads
+        let mut referenced = ReferencedColumns::of_select(&select_statement);
+        for semi_join in &semi_joins {
+            for expression in semi_join.reads() {
+                referenced = referenced.and_then(|mut referenced| {
+                    referenced.add(expression)?;
+                    Some(referenced)
+                });
+            }
+        }
+
+        let mut tables: HashMap<String, TableContainer> = HashMap::new();
+
+        let mut columns: ResultColumns = Vec::with_capacity(10);
+
+        if select_statement.items.is_empty() {
+            return Ok(QueryResults {
+                columns,
+                rows: vec![],
+            });
+        }
+
+        let from = select_statement.from.first();
+        let planning = self.timing.now();
+        let join_plan = from.map(|from| self.plan_joins(from, &select_statement.where_clause));
+        self.timing.add_planning(self.timing.since(planning));
+        let (table_name, mut table) = match (from, &join_plan) {
+            (Some(from), Some(join_plan)) => self.scan_source(
+                join_relation(from, join_plan.order[0]),
+                &select_statement.where_clause,
+                &mut plan,
+            )?,
+            _ => {
                 let mut new_table = Table {
                     column_types: Vec::with_capacity(10),
                     columns: Vec::with_capacity(10),
                     indexes: Vec::with_capacity(10),
                     name: "".to_string(),
                     rows: Vec::with_capacity(1),
+                    column_constraints: vec![],
+                    checks: vec![],
+                    schema_version: 0,
+                    heap_bytes: 0,
+                    dead_rows: 0,
                 };
-                new_table.rows.push(vec![]);
+                new_table.rows.push(StoredRow::new(&[]));
                 ("".to_string(), TableContainer::Temp(Box::new(new_table)))
             }
         };
-        if let Some(JoinClause { on, source, kind }) = table_joins.get(0) {
-            let (source_table_name, source_table) = match source {
-                RowDataSource::Table {
-                    as_clause,
-                    table_name: ref from_name,
-                    joins,
-                } => match self.tables.get(from_name) {
-                    // TODO
-                    None => {
-                        return Err(ERR_TABLE_DOES_NOT_EXIST.to_string());
+        // The FROM item each column comes from, by its alias or else its name, and the
+        // relation of the join plan it is a column of
+        let mut column_sources = match &table {
+            TableContainer::Concrete(table) => vec![table_name.clone(); table.columns.len()],
+            TableContainer::Temp(table) => vec![table_name.clone(); table.columns.len()],
+        };
+        let mut column_relations = vec![0; column_sources.len()];
+        if let (Some(from), Some(join_plan)) = (from, &join_plan) {
+            column_relations = vec![join_plan.order[0]; column_sources.len()];
+            for (step_idx, step) in join_plan.steps.iter().enumerate() {
+                let relation = join_plan.order[step_idx + 1];
+                let source = join_relation(from, relation);
+
+                let &mut rows;
+                let temp;
+                let (columns, column_types) = match table {
+                    TableContainer::Concrete(table) => {
+                        rows = &table.rows;
+                        (table.columns.clone(), table.column_types.clone())
                     }
-                    Some(table) => {
-                        let mut new_table;
-                        let from_name = if let Some(from_name) = as_clause {
-                            from_name.clone()
-                        } else {
-                            from_name.clone()
-                        };
-
-                        new_table = TableContainer::Concrete(&table);
-                        for (index, exp) in
-                            table.get_applicable_indexes(Some(&select_statement.where_clause))?
-                        {
-                            if let Expression::Binary(bin_exp) = exp {
-                                new_table = TableContainer::Temp(Box::new(
-                                    index.new_table_from_subset(table, bin_exp)?,
-                                ));
+                    TableContainer::Temp(table) => {
+                        temp = table.rows;
+                        rows = &temp;
+                        (table.columns.clone(), table.column_types.clone())
+                    }
+                };
+
+                // A LATERAL item runs for every row joined so far, and its rows only join
+                // the row they were given for
+                let references = self.outer_references(source, &column_sources, &columns);
+                let mut origins = None;
+                let (source_table_name, source_table) = match references.first() {
+                    None => self.scan_source(source, &select_statement.where_clause, &mut plan)?,
+                    Some((_, idx)) if !is_lateral(source) => {
+                        return Err(BackendError::UndefinedTable(format!(
+                            "Invalid reference to FROM-clause entry for table \"{}\", only a \
+                            LATERAL item can read the FROM items before it.",
+                            column_sources[*idx]
+                        )));
+                    }
+                    Some(_) if matches!(step.kind, JoinKind::RightOuter | JoinKind::FullOuter) => {
+                        return Err(BackendError::InvalidColumnReference(
+                            "The combining JOIN type must be INNER or LEFT for a LATERAL reference."
+                                .to_string(),
+                        ));
+                    }
+                    Some(_) => {
+                        let (name, lateral, row_origins) =
+                            self.lateral_rows(source, &references, rows, &mut plan)?;
+                        origins = Some(row_origins);
+                        (name, TableContainer::Temp(Box::new(lateral)))
+                    }
+                };
+
+                let &mut source_rows;
+                let temp;
+                let (source_columns, source_column_types) = match source_table {
+                    TableContainer::Concrete(table) => {
+                        source_rows = &table.rows;
+                        (table.columns.clone(), table.column_types.clone())
+                    }
+                    TableContainer::Temp(table) => {
+                        temp = table.rows;
+                        source_rows = &temp;
+                        (table.columns.clone(), table.column_types.clone())
+                    }
+                };
+                // The rows of earlier joins already carry just the columns read
+                let left_referenced = match step_idx {
+                    0 => referenced.as_ref(),
+                    _ => None,
+                };
+                let mapping = ColumnMapping::new(left_referenced, &table_name, &columns);
+                let rows = mapping.project_rows(rows);
+                let columns = mapping.project(&columns);
+                let column_types = mapping.project(&column_types);
+                column_sources.truncate(columns.len());
+                column_relations.truncate(columns.len());
+                let source_mapping =
+                    ColumnMapping::new(referenced.as_ref(), &source_table_name, &source_columns);
+                let source_rows = source_mapping.project_rows(source_rows);
+                let mut source_columns = source_mapping.project(&source_columns);
+                let mut source_column_types = source_mapping.project(&source_column_types);
+
+                let source_columns_num = source_columns.len();
+                column_sources.extend(vec![source_table_name; source_columns_num]);
+                column_relations.extend(vec![relation; source_columns_num]);
+                let on_columns_num = columns.len();
+                let mut full_derp_table = Table {
+                    column_types,
+                    columns,
+                    indexes: vec![],
+                    name: "".to_string(),
+                    rows: Vec::with_capacity(100),
+                    column_constraints: vec![],
+                    checks: vec![],
+                    schema_version: 0,
+                    heap_bytes: 0,
+                    dead_rows: 0,
+                };
+                full_derp_table.columns.append(&mut source_columns);
+                full_derp_table
+                    .column_types
+                    .append(&mut source_column_types);
+                // TODO nested loop through tables, temp table with only the current row for each loop, run expression, rename cols if needed
+
+                let mut condition = step.condition();
+                check_qualifiers(&condition, &column_sources)?;
+                resolve_qualified_columns(
+                    &mut condition,
+                    &full_derp_table.columns,
+                    &column_sources,
+                );
+                let on = full_derp_table.compile_expression(&condition);
+                let kind = &step.kind;
+                let mut joined = MaterializedRows::new(self.limits, "JOIN");
+
+                let mut used_source_indices = vec![];
+                let mut used_on_indices = vec![];
+                for (source_index, source_row) in source_rows.iter().enumerate() {
+                    for (on_index, row) in rows.iter().enumerate() {
+                        if let Some(origins) = &origins {
+                            if origins[source_index] != on_index {
+                                continue;
                             }
                         }
-                        // table_joins = joins;
-                        (from_name.clone(), new_table)
-                    }
-                },
-                RowDataSource::SubSelect {
-                    as_clause,
-                    select,
-                    joins,
-                } => {
-                    // TODO
-                    let result = self.select(select.clone())?;
-                    let new_table = Table::from(result);
-                    // table_joins = joins;
-                    (as_clause.clone(), TableContainer::Temp(Box::new(new_table)))
-                }
-            };
-            let &mut rows;
-            let temp;
-            let (mut columns, mut column_types) = match table {
-                TableContainer::Concrete(table) => {
-                    rows = &table.rows;
-                    (table.columns.clone(), table.column_types.clone())
-                }
-                TableContainer::Temp(table) => {
-                    temp = table.rows;
-                    rows = &temp;
-                    (table.columns.clone(), table.column_types.clone())
-                }
-            };
+                        let mut new_row = row.clone();
+                        new_row.extend_from_slice(source_row);
+                        let result = on.evaluate(&new_row)?;
 
-            let &mut source_rows;
-            let temp;
-            let (mut source_columns, mut source_column_types) = match source_table {
-                TableContainer::Concrete(table) => {
-                    source_rows = &table.rows;
-                    (table.columns.clone(), table.column_types.clone())
-                }
-                TableContainer::Temp(table) => {
-                    temp = table.rows;
-                    source_rows = &temp;
-                    (table.columns.clone(), table.column_types.clone())
-                }
-            };
-            let source_columns_num = source_columns.len();
-            let on_columns_num = columns.len();
-            let mut full_derp_table = Table {
-                column_types,
-                columns,
-                indexes: vec![],
-                name: "".to_string(),
-                rows: Vec::with_capacity(100),
-            };
-            full_derp_table.columns.append(&mut source_columns);
-            full_derp_table
-                .column_types
-                .append(&mut source_column_types);
-            let mut temp_table = full_derp_table.clone();
-            // TODO nested loop through tables, temp table with only the current row for each loop, run expression, rename cols if needed
-
-            let mut used_source_indices = vec![];
-            let mut used_on_indices = vec![];
-            for (source_index, source_row) in source_rows.iter().enumerate() {
-                for (on_index, row) in rows.iter().enumerate() {
-                    let mut new_row = row.clone();
-                    new_row.append(&mut source_row.clone());
-                    temp_table.rows = vec![new_row.clone()];
-                    let (result, _, typ) = temp_table.evaluate_cell(0, on)?;
-
-                    if let SqlValue::Boolean(true) = result {
-                        used_source_indices.push(source_index);
-                        used_on_indices.push(on_index);
-                        full_derp_table.rows.push(new_row);
-                    } else if let SqlValue::Boolean(false) = result {
-                        continue;
-                    } else {
-                        return Err("Invalid Join Expression".to_string());
+                        if let SqlValue::Boolean(true) = *result {
+                            used_source_indices.push(source_index);
+                            used_on_indices.push(on_index);
+                            joined.add(&new_row)?;
+                            full_derp_table.rows.push(StoredRow::new(&new_row));
+                        } else if let SqlValue::Boolean(false) = *result {
+                            continue;
+                        } else {
+                            return Err(BackendError::DatatypeMismatch(
+                                "Invalid Join Expression".to_string(),
+                            ));
+                        }
                     }
                 }
-            }
 
-            if kind == &JoinKind::RightOuter || kind == &JoinKind::FullOuter {
-                let start = 0;
-                let end = source_columns_num;
-                for (source_index, source_row) in source_rows.iter().enumerate() {
-                    if used_source_indices.contains(&source_index) == false {
-                        let mut new_row = vec![];
-                        for _ in start..end {
-                            new_row.push(SqlValue::Null);
+                // Rows without a match get NULLs in place of the other side's columns
+                if kind == &JoinKind::RightOuter || kind == &JoinKind::FullOuter {
+                    for (source_index, source_row) in source_rows.iter().enumerate() {
+                        if !used_source_indices.contains(&source_index) {
+                            let mut new_row = vec![SqlValue::Null; on_columns_num];
+                            new_row.append(&mut source_row.clone());
+                            joined.add(&new_row)?;
+                            full_derp_table.rows.push(StoredRow::new(&new_row));
                         }
-                        new_row.append(&mut source_row.clone());
-                        full_derp_table.rows.push(new_row);
                     }
                 }
-            }
-            if kind == &JoinKind::LeftOuter || kind == &JoinKind::FullOuter {
-                let start = source_columns_num;
-                let end = source_columns_num + on_columns_num;
-                for (on_index, on_row) in rows.iter().enumerate() {
-                    if used_on_indices.contains(&on_index) == false {
-                        let mut new_row = on_row.clone();
-                        for _ in start..end {
-                            new_row.push(SqlValue::Null);
+                if kind == &JoinKind::LeftOuter || kind == &JoinKind::FullOuter {
+                    for (on_index, on_row) in rows.iter().enumerate() {
+                        if !used_on_indices.contains(&on_index) {
+                            let mut new_row = on_row.clone();
+                            new_row.resize(on_columns_num + source_columns_num, SqlValue::Null);
+                            joined.add(&new_row)?;
+                            full_derp_table.rows.push(StoredRow::new(&new_row));
                         }
-                        full_derp_table.rows.push(new_row);
                     }
                 }
+
+                plan.add(|| join_name(kind), full_derp_table.rows.len(), 2);
+                table = TableContainer::Temp(Box::new(full_derp_table));
             }
 
-            table = TableContainer::Temp(Box::new(full_derp_table));
+            // Joined in another order, the columns go back to the order they are written in
+            if join_plan.is_reordered() {
+                let mut positions: Vec<usize> = (0..column_relations.len()).collect();
+                positions.sort_by_key(|idx| column_relations[*idx]);
+                let reorder = |values: &[String]| -> Vec<String> {
+                    positions.iter().map(|idx| values[*idx].clone()).collect()
+                };
+                column_sources = reorder(&column_sources);
+                if let TableContainer::Temp(joined) = &mut table {
+                    joined.columns = reorder(&joined.columns);
+                    joined.column_types = positions
+                        .iter()
+                        .map(|idx| joined.column_types[*idx])
+                        .collect();
+                    for row in joined.rows.iter_mut() {
+                        *row = StoredRow::new(&row.project(&positions));
+                    }
+                }
+            }
+        }
+        if join_plan.iter().all(|join_plan| join_plan.steps.is_empty())
+            && select_statement
+                .items
+                .iter()
+                .any(|item| contains_window_function(&item.expression))
+        {
+            // Window functions work on a copy of the table, of the columns read alone
+            let full_table = match &table {
+                TableContainer::Concrete(table) => *table,
+                TableContainer::Temp(table) => table.as_ref(),
+            };
+            let mapping = ColumnMapping::new(referenced.as_ref(), &table_name, &full_table.columns);
+            if let Some(projected) = mapping.project_table(full_table) {
+                column_sources.truncate(projected.columns.len());
+                table = TableContainer::Temp(Box::new(projected));
+            }
+        }
+        if !semi_joins.is_empty() {
+            let full_table = match &table {
+                TableContainer::Concrete(table) => *table,
+                TableContainer::Temp(table) => table.as_ref(),
+            };
+            let semi_joined = self.semi_join(&semi_joins, full_table, &mut plan)?;
+            table = TableContainer::Temp(Box::new(semi_joined));
         }
-        tables.insert(table_name, table);
+        tables.insert(table_name.clone(), table);
+        let table = match &tables.get(&table_name) {
+            Some(TableContainer::Concrete(table)) => table,
+            Some(TableContainer::Temp(table)) => table.as_ref(),
+            None => {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Table {} not found",
+                    table_name
+                )))
+            }
+        };
 
+        // Column references are resolved to the column they read, and every item gets
+        // the name of its result column along with, for columns, the FROM item of theirs
+        let planning = self.timing.now();
         let mut final_select_items: Vec<SelectItem> = Vec::with_capacity(10);
-        for item in select_statement.items {
-            // TODO when multiple tables in join have same column name, rename them to table.column
+        let mut names: Vec<(String, Option<&str>)> = Vec::with_capacity(10);
+        // The type modifier, nullability and origin of each, from the column they read
+        let origins = self.column_origins(from);
+        let mut described: Vec<DescribedColumn> = Vec::with_capacity(10);
+        // The collation of each, that DISTINCT and sorting by the result columns go by
+        let mut output_collations: Vec<Option<Collation>> = Vec::with_capacity(10);
+        // A `name.*`, or a `*` with EXCEPT or REPLACE, goes in as the columns it keeps,
+        // qualified with their FROM item, and the replacements, which are named after
+        // their columns
+        let mut items = Vec::with_capacity(select_statement.items.len());
+        let no_modifiers = AsteriskModifiers::default();
+        for (item, derivation) in select_statement.items.into_iter().zip(item_derivations) {
+            if !item.asterisk
+                || (item.asterisk_source.is_none() && item.asterisk_modifiers.is_none())
+            {
+                items.push((item, derivation));
+                continue;
+            }
+            if let Some(source) = &item.asterisk_source {
+                if !column_sources.contains(source) {
+                    return Err(BackendError::UndefinedTable(format!(
+                        "{}: {}",
+                        source, ERR_TABLE_DOES_NOT_EXIST
+                    )));
+                }
+            }
+            // The columns the `*` goes over, only those of its FROM item when qualified
+            let covered: Vec<usize> = (0..table.columns.len())
+                .filter(|idx| {
+                    item.asterisk_source
+                        .as_ref()
+                        .is_none_or(|source| column_sources[*idx] == *source)
+                })
+                .collect();
+            let columns: Vec<(&str, &str)> = covered
+                .iter()
+                .map(|idx| (column_sources[*idx].as_str(), table.columns[*idx].as_str()))
+                .collect();
+            let modifiers = item.asterisk_modifiers.as_ref().unwrap_or(&no_modifiers);
+            for (idx, column) in covered
+                .into_iter()
+                .zip(expand_asterisk(modifiers, &columns)?)
+            {
+                let mut item = match column {
+                    AsteriskColumn::Kept => SelectItem {
+                        expression: Expression::TableColumn(TableColumn {
+                            col_name: table.columns[idx].clone(),
+                            table_name: Some(column_sources[idx].clone()),
+                        }),
+                        as_clause: None,
+                        asterisk: false,
+                        asterisk_source: None,
+                        asterisk_modifiers: None,
+                    },
+                    AsteriskColumn::Excluded => continue,
+                    AsteriskColumn::Replaced(replacement) => replacement.clone(),
+                };
+                self.run_quantified_subqueries(&mut item.expression)?;
+                let derivation = collations.resolve(&mut item.expression)?;
+                items.push((item, derivation));
+            }
+        }
+        for (item, derivation) in items {
             if item.asterisk {
-                for table in tables.values() {
-                    let table = match table {
-                        TableContainer::Concrete(table) => table,
-                        TableContainer::Temp(table) => table.as_ref(),
-                    };
-                    let mut new_select_items: Vec<SelectItem> = Vec::with_capacity(10);
-                    for (idx, column) in table.columns.iter().enumerate() {
-                        let new_select_item = SelectItem {
-                            expression: Expression::ProcessedTableColumn(ProcessedTableColumn {
-                                col_name: Some(column.to_string()),
-                                col_idx: idx,
-                            }),
-                            as_clause: None,
-                            asterisk: false,
-                        };
-                        new_select_items.push(new_select_item);
+                for (idx, column) in table.columns.iter().enumerate() {
+                    final_select_items.push(SelectItem {
+                        expression: Expression::ProcessedTableColumn(ProcessedTableColumn {
+                            col_name: Some(column.to_string()),
+                            col_idx: idx,
+                        }),
+                        as_clause: None,
+                        asterisk: false,
+                        asterisk_source: None,
+                        asterisk_modifiers: None,
+                    });
+                    names.push((column.clone(), Some(&column_sources[idx])));
+                    described.push(origins.describe(&column_sources[idx], column));
+                    output_collations.push(collations.lookup(Some(&column_sources[idx]), column));
+                }
+                continue;
+            }
+            if select_statement.is_distinct || !select_statement.order_by.is_empty() {
+                output_collations.push(derivation.collation()?);
+            }
+
+            match &item.expression {
+                // Names are compared exactly, unquoted ones were already folded to
+                // lowercase by the lexer
+                Expression::TableColumn(TableColumn {
+                    col_name,
+                    table_name: Some(source),
+                }) => {
+                    if !column_sources.contains(source) {
+                        return Err(BackendError::UndefinedTable(format!(
+                            "{}: {}",
+                            source, ERR_TABLE_DOES_NOT_EXIST
+                        )));
                     }
-                    final_select_items.append(&mut new_select_items);
+                    let idx = (0..table.columns.len())
+                        .find(|idx| {
+                            &column_sources[*idx] == source && &table.columns[*idx] == col_name
+                        })
+                        .ok_or_else(|| {
+                            BackendError::UndefinedColumn(format!(
+                                "{}: {}",
+                                col_name, ERR_COLUMN_DOES_NOT_EXIST
+                            ))
+                        })?;
+                    names.push(match &item.as_clause {
+                        Some(as_clause) => (as_clause.clone(), None),
+                        None => (col_name.clone(), Some(&column_sources[idx])),
+                    });
+                    described.push(origins.describe(&column_sources[idx], col_name));
+                    final_select_items.push(SelectItem {
+                        expression: Expression::ProcessedTableColumn(ProcessedTableColumn {
+                            col_name: Some(col_name.to_string()),
+                            col_idx: idx,
+                        }),
+                        as_clause: item.as_clause,
+                        asterisk: false,
+                        asterisk_source: None,
+                        asterisk_modifiers: None,
+                    });
                 }
-            } else {
-                for table in tables.values() {
-                    let table = match table {
-                        TableContainer::Concrete(table) => table,
-                        TableContainer::Temp(table) => table.as_ref(),
-                    };
-                    if let Expression::TableColumn(TableColumn {
-                        ref col_name,
-                        table_name: Some(ref table_name),
-                    }) = item.expression
-                    {
-                        if table_name != &table.name {
-                            continue;
-                        }
-                        for (idx, column) in table.columns.iter().enumerate() {
-                            if column != col_name {
-                                continue;
+                expression => {
+                    names.push(match &item.as_clause {
+                        Some(as_clause) => (as_clause.clone(), None),
+                        None => {
+                            let name = output_name(expression);
+                            let idx = table.columns.iter().position(|column| *column == name);
+                            match expression {
+                                Expression::TableColumn(_) | Expression::Literal(_) => {
+                                    (name, idx.map(|idx| column_sources[idx].as_str()))
+                                }
+                                _ => (name, None),
                             }
-                            let new_select_item = SelectItem {
-                                expression: Expression::ProcessedTableColumn(
-                                    ProcessedTableColumn {
-                                        col_name: Some(column.to_string()),
-                                        col_idx: idx,
-                                    },
-                                ),
-                                as_clause: None,
-                                asterisk: false,
-                            };
-                            final_select_items.push(new_select_item.clone());
-                            break;
                         }
-                        break;
-                    } else {
-                        final_select_items.push(item.clone());
-                    }
+                    });
+                    described.push(match expression {
+                        Expression::TableColumn(TableColumn { col_name, .. }) => table
+                            .columns
+                            .iter()
+                            .position(|column| column == col_name)
+                            .map(|idx| origins.describe(&column_sources[idx], col_name))
+                            .unwrap_or_else(DescribedColumn::computed),
+                        _ => DescribedColumn::computed(),
+                    });
+                    final_select_items.push(item);
                 }
             }
         }
 
-        let table_name = if let Some(RowDataSource::Table {
-            as_clause,
-            table_name,
-            joins,
-        }) = select_statement.from.get(0)
-        {
-            // TODO
-            table_name.clone()
-        } else if let Some(RowDataSource::SubSelect {
-            as_clause,
-            select,
-            joins,
-        }) = select_statement.from.get(0)
-        {
-            // TODO
-            as_clause.clone()
-        // return Err("Not implemented subquery for FROM, yet..".to_string());
-        } else {
-            "".to_string()
+        // A column is qualified with its FROM item when another result column of the
+        // same name comes from somewhere else, like `a.id` and `b.id` after a join.
+        // AS names are kept as they are, and so are repeats like `?column?`.
+        let names: Vec<String> = names
+            .iter()
+            .map(|(name, source)| match source {
+                Some(source)
+                    if !source.is_empty()
+                        && names.iter().any(|(other, other_source)| {
+                            other == name && other_source != &Some(*source)
+                        }) =>
+                {
+                    format!("{}.{}", source, name)
+                }
+                _ => name.clone(),
+            })
+            .collect();
+
+        // ORDER BY a position or the name of a result column sorts by that column, taking
+        // its value from the result row. Other names are those of the FROM items' columns.
+        let mut order_outputs = Vec::with_capacity(select_statement.order_by.len());
+        let clauses = select_statement
+            .order_by
+            .iter_mut()
+            .zip(&mut order_collations);
+        for (order_by, collation) in clauses {
+            let output = order_by_output(&order_by.exp, &names)?;
+            if let Some(idx) = output {
+                order_by.exp = Expression::Empty;
+                *collation = output_collations[idx];
+            }
+            order_outputs.push(output);
+        }
+
+        if contains_window_function(&select_statement.where_clause) {
+            return Err(BackendError::WindowingError(
+                ERR_WINDOW_FUNCTION_IN_WHERE.to_string(),
+            ));
+        }
+        if contains_aggregate(&select_statement.where_clause) {
+            return Err(BackendError::GroupingError(
+                ERR_AGGREGATE_FUNCTION_IN_WHERE.to_string(),
+            ));
+        }
+        // Scalar subqueries reading no column of the outer row run once, the rest run for
+        // every row with its values in place of the columns they read
+        for item in final_select_items.iter_mut() {
+            self.run_subqueries(&mut item.expression, table, &column_sources, None)?;
+        }
+        self.run_subqueries(
+            &mut select_statement.where_clause,
+            table,
+            &column_sources,
+            None,
+        )?;
+        for order_by in select_statement.order_by.iter_mut() {
+            self.run_subqueries(&mut order_by.exp, table, &column_sources, None)?;
+        }
+        let filtered;
+        let (table, where_clause) = match &select_statement.where_clause {
+            Expression::Empty => (table, None),
+            where_clause => {
+                check_qualifiers(where_clause, &column_sources)?;
+                let mut where_clause = where_clause.clone();
+                resolve_qualified_columns(&mut where_clause, &table.columns, &column_sources);
+                if semi_join::has_subquery(&where_clause) {
+                    filtered = self.filter_rows(table, &column_sources, &where_clause)?;
+                    (&filtered, None)
+                } else {
+                    (table, Some(table.compile_expression(&where_clause)))
+                }
+            }
         };
-        let table = match &tables.get(&table_name) {
-            Some(TableContainer::Concrete(table)) => table,
-            Some(TableContainer::Temp(table)) => table.as_ref(),
-            None => return Err(format!("Table {} not found", table_name)),
+        self.timing.add_planning(self.timing.since(planning));
+        plan.restart();
+        // Columns qualified with their FROM item go by their position from here on, so
+        // that GROUP BY keys like `a.id` and `b.id` after a join are told apart, and the
+        // select items, HAVING and ORDER BY find their own
+        for key in select_statement.group_by.iter_mut() {
+            check_qualifiers(key, &column_sources)?;
+            resolve_qualified_columns(key, &table.columns, &column_sources);
+        }
+        for item in final_select_items.iter_mut() {
+            resolve_qualified_columns(&mut item.expression, &table.columns, &column_sources);
+        }
+        resolve_qualified_columns(
+            &mut select_statement.having,
+            &table.columns,
+            &column_sources,
+        );
+        for order_by in select_statement.order_by.iter_mut() {
+            resolve_qualified_columns(&mut order_by.exp, &table.columns, &column_sources);
+        }
+        // Grouping takes the rows WHERE keeps, HAVING then takes its place as the
+        // condition the rows of the grouped table have to pass
+        let grouped;
+        let (table, where_clause) = match table.grouped(
+            &select_statement.group_by,
+            &group_collations,
+            &mut final_select_items,
+            &mut select_statement.having,
+            &mut select_statement.order_by,
+            |aggregation| self.group_rows(&table_name, aggregation, &table.rows, &where_clause),
+        )? {
+            Some(table) => {
+                grouped = table;
+                plan.add(|| "Aggregate".to_string(), grouped.rows.len(), 1);
+                let having = match &select_statement.having {
+                    Expression::Empty => None,
+                    having => Some(grouped.compile_expression(having)),
+                };
+                (&grouped, having)
+            }
+            None => (table, where_clause),
+        };
+        // Window functions are computed over the rows WHERE keeps, the select items and
+        // ORDER BY then read their results from columns added to the table
+        let windowed;
+        let table = match table.with_window_columns(
+            &where_clause,
+            &mut final_select_items,
+            &mut select_statement.order_by,
+        )? {
+            Some(table) => {
+                windowed = table;
+                plan.add(|| "WindowAgg".to_string(), windowed.rows.len(), 1);
+                &windowed
+            }
+            None => table,
+        };
+        // Sorting and DISTINCT need every result row buffered, the limits apply to them
+        let materialized = MaterializedRows::new(
+            self.limits,
+            if !select_statement.order_by.is_empty() {
+                "ORDER BY"
+            } else if select_statement.is_distinct {
+                "DISTINCT"
+            } else {
+                "SELECT"
+            },
+        );
+        // Big tables get their conditions evaluated up front, across threads
+        let verdicts = match &where_clause {
+            Some(where_clause) => self.filter_in_parallel(&table_name, &table.rows, where_clause),
+            None => None,
         };
 
+        // Clauses apply in the order join, where, select, distinct, order, offset, limit.
+        // Past work_mem the sort and DISTINCT go on in temporary files.
+        let spill = self.spill_settings();
+        let mut distinct = DistinctRows::new(spill.clone());
+        let sorted = (!select_statement.order_by.is_empty()).then(|| SortedRows::new(spill));
+        let mut output = ResultRows::new(
+            materialized,
+            sorted,
+            select_statement.offset,
+            select_statement.limit,
+        );
+        let mut removed = 0;
         for row_index in 0..table.rows.len() {
-            if let Some(limit) = select_statement.limit {
-                if results.len() >= limit {
-                    break;
-                }
+            if output.is_full() {
+                break;
             }
             let mut result: Vec<SqlValue> = vec![];
-            let is_first_row = results.len() == 0;
-
-            match &select_statement.where_clause {
-                Expression::Empty => {}
-                _ => {
-                    let (cell_val, _, _) =
-                        table.evaluate_cell(row_index, &select_statement.where_clause)?;
+            let is_first_row = columns.is_empty();
 
-                    if let SqlValue::Boolean(true) = cell_val {
-                    } else {
-                        continue;
-                    }
-                }
-            }
-            if let Some(target_offset) = select_statement.offset {
-                offset += 1;
-                if offset <= target_offset {
+            if let Some(where_clause) = &where_clause {
+                let is_true = match &verdicts {
+                    Some(verdicts) => verdicts[row_index].clone()?,
+                    None => where_clause.is_true(&table.rows[row_index])?,
+                };
+                if !is_true {
+                    removed += 1;
                     continue;
                 }
             }
 
-            for select_item in &final_select_items {
-                let (cell_val, col_name, col_type) =
-                    table.evaluate_cell(row_index, &select_item.expression)?;
+            let items = final_select_items.iter().zip(&names).zip(&described);
+            for ((select_item, name), described) in items {
+                let (cell_val, col_type) =
+                    self.row_value(table, &column_sources, row_index, &select_item.expression)?;
 
                 if is_first_row {
-                    match &select_item.as_clause {
-                        Some(as_name) => {
-                            columns.push(ResultColumn {
-                                col_type: col_type.clone(),
-                                name: as_name.clone(),
-                            });
-                        }
-                        None => {
-                            columns.push(ResultColumn {
-                                col_type: col_type.clone(),
-                                name: col_name.to_string(),
-                            });
-                        }
-                    }
+                    columns.push(ResultColumn {
+                        col_type,
+                        name: name.clone(),
+                        type_modifier: described.type_modifier,
+                        nullable: described.nullable,
+                        origin: described.origin.clone(),
+                    });
                 }
 
                 result.push(cell_val);
             }
 
-            if select_statement.is_distinct && results.contains(&result) {
-                continue;
+            // Rows are compared under the collations of the items
+            let mut deferred = None;
+            if select_statement.is_distinct {
+                let keys = collation_keys(&output_collations, &result);
+                match distinct.check(&keys)? {
+                    Distinct::New => {}
+                    Distinct::Seen => continue,
+                    Distinct::Deferred => deferred = Some(keys),
+                }
             }
 
-            if let Some(ref order_by) = select_statement.order_by {
-                let (new_ord_val, _, _) = table.evaluate_cell(row_index, &order_by.exp)?;
-
-                let mut index = if order_by.asc {
-                    results_order.len()
-                } else {
-                    results_order.len()
-                };
-                for (i, val) in results_order.iter().enumerate() {
-                    if order_by.asc && new_ord_val < *val {
-                        index = i;
-                        break;
-                    } else if order_by.asc == false && new_ord_val > *val {
-                        index = i;
-                        break;
-                    }
+            // The ORDER BY values are worked out once for each row, the sort only comparing
+            // the bytes of their encodings
+            let mut order_key = None;
+            if !select_statement.order_by.is_empty() {
+                let mut key = vec![];
+                let clauses = select_statement
+                    .order_by
+                    .iter()
+                    .zip(&order_collations)
+                    .zip(&order_outputs);
+                for ((order_by, collation), output) in clauses {
+                    let value = match output {
+                        Some(idx) => result[*idx].clone(),
+                        None => {
+                            let exp = &order_by.exp;
+                            self.row_value(table, &column_sources, row_index, exp)?.0
+                        }
+                    };
+                    let value = match collation {
+                        Some(collation) => collation.key(&value).into_owned(),
+                        None => value,
+                    };
+                    push_order_key(&mut key, &value, order_by.asc);
                 }
-                results_order.insert(index, new_ord_val);
-                results.insert(index, result);
-                continue;
+                order_key = Some(key);
+            }
+
+            match deferred {
+                Some(keys) => distinct.defer(&keys, &result, order_key.as_deref())?,
+                None => output.add(result, order_key)?,
             }
+        }
+        distinct.finish(|row, order_key| output.add(row, order_key))?;
+        let count = output.len();
+        plan.add_result(count, where_clause.as_ref().map(|_| removed));
+
+        let results = output.finish(|rows| self.sort_rows(&table_name, rows))?;
+        if !select_statement.order_by.is_empty() {
+            plan.add(|| "Sort".to_string(), count, 1);
+        }
+
+        if columns.is_empty() {
+            let types = table.sample_types(&final_select_items);
+            columns = types
+                .into_iter()
+                .zip(names)
+                .zip(described)
+                .map(|((col_type, name), described)| ResultColumn {
+                    col_type,
+                    name,
+                    type_modifier: described.type_modifier,
+                    nullable: described.nullable,
+                    origin: described.origin,
+                })
+                .collect();
+        }
 
-            results.push(result);
+        if select_statement.limit.is_some() || select_statement.offset.is_some() {
+            plan.add(|| "Limit".to_string(), results.len(), 1);
         }
+        plan.finish(&self.timing);
 
         return Ok(QueryResults {
             columns,
@@ -1083,108 +2369,797 @@ impl MemoryBackend {
         });
     }
 
-    pub fn drop_table(&mut self, drop_table_statement: DropTableStatement) -> Result<bool, String> {
-        match self.tables.get(&drop_table_statement.name) {
-            None => {
-                return Err(format!(
-                    "Table \"{}\" doesn't exist.",
-                    drop_table_statement.name.clone()
-                ));
+    // Reads a FROM item or the source of a join, through an index where the WHERE clause
+    // allows it
+    fn scan_source<'a>(
+        &'a self,
+        source: &'a RowDataSource,
+        where_clause: &Expression,
+        plan: &mut PlanBuilder,
+    ) -> Result<(String, TableContainer<'a>), BackendError> {
+        let mut scan_index = None;
+        let mut sampling_detail = None;
+        let (name, table) = match source {
+            RowDataSource::Table {
+                as_clause,
+                table_name,
+                sample,
+                ..
+            } => match self
+                .engine(table_name)
+                .scan_table(self.kept_name(table_name))?
+            {
+                None => {
+                    return Err(BackendError::UndefinedTable(
+                        ERR_TABLE_DOES_NOT_EXIST.to_string(),
+                    ));
+                }
+                // Rows are sampled before WHERE filters them, so no index is looked up
+                Some(scanned) if sample.is_some() => {
+                    let sampling = self.sampling(sample.as_ref().unwrap())?;
+                    let new_table = sampling.sample(&scanned);
+                    sampling_detail = Some(sampling.detail());
+                    let name = as_clause.as_ref().unwrap_or(table_name);
+                    (name.clone(), TableContainer::Temp(Box::new(new_table)))
+                }
+                Some(scanned) => {
+                    let engine = self.engine(table_name);
+                    let mut subset = None;
+                    for (index, exp) in scanned.get_applicable_indexes(Some(where_clause))? {
+                        if let Expression::Binary(_) = exp {
+                            subset = Some(index.new_table_from_subset(&scanned, &exp, engine)?);
+                            scan_index = Some(index.name.clone());
+                        }
+                    }
+                    // Engines that don't keep their rows in the table give a copy with them
+                    let new_table = match (subset, scanned) {
+                        (Some(subset), _) => TableContainer::Temp(Box::new(subset)),
+                        (None, Cow::Borrowed(table)) => TableContainer::Concrete(table),
+                        (None, Cow::Owned(table)) => TableContainer::Temp(Box::new(table)),
+                    };
+                    let name = as_clause.as_ref().unwrap_or(table_name);
+                    (name.clone(), new_table)
+                }
+            },
+            RowDataSource::SubSelect {
+                as_clause, select, ..
+            } => {
+                let result = self.select(select.as_ref().clone())?;
+                plan.take_subquery(&self.timing);
+                let new_table = Table::from(result);
+                (as_clause.clone(), TableContainer::Temp(Box::new(new_table)))
+            }
+            RowDataSource::Function {
+                call,
+                as_clause,
+                column_aliases,
+                ..
+            } => {
+                let (name, new_table) = self.function_table(call, as_clause, column_aliases)?;
+                (name, TableContainer::Temp(Box::new(new_table)))
+            }
+            RowDataSource::Values {
+                rows,
+                as_clause,
+                column_aliases,
+                ..
+            } => {
+                let (name, new_table) = self.values_table(rows, as_clause, column_aliases)?;
+                (name, TableContainer::Temp(Box::new(new_table)))
+            }
+        };
+        let rows = match &table {
+            TableContainer::Concrete(table) => table.rows.len(),
+            TableContainer::Temp(table) => table.rows.len(),
+        };
+        plan.add(
+            || scan_name(source, scan_index.as_deref()),
+            rows,
+            scan_inputs(source),
+        );
+        if let Some(detail) = sampling_detail {
+            plan.add_detail(|| detail);
+        }
+        Ok((name, table))
+    }
+
+    pub fn drop_table(
+        &mut self,
+        drop_table_statement: DropTableStatement,
+    ) -> Result<bool, BackendError> {
+        // The table the name finds goes, a temporary one uncovering the table it shadowed.
+        // Views only read the permanent one.
+        let name = &drop_table_statement.name;
+        let is_temporary = self.is_temporary(name);
+        if !is_temporary {
+            if self.views.contains(name) {
+                return Err(BackendError::WrongObjectType(format!(
+                    "\"{}\" is not a table, use DROP VIEW to drop it.",
+                    name
+                )));
             }
+            self.check_dependent_views("table", name, drop_table_statement.cascade)?;
+            self.check_dependent_triggers(name, drop_table_statement.cascade)?;
+            self.drop_dependent_views(name);
+            self.drop_table_triggers(name);
+        }
+        let removed = self
+            .namespace_mut(is_temporary)
+            .drop_table(&drop_table_statement.name)?;
+        match removed {
+            None => Err(BackendError::UndefinedTable(format!(
+                "Table \"{}\" doesn't exist.",
+                drop_table_statement.name.clone()
+            ))),
             Some(_) => {
-                self.tables.remove(&drop_table_statement.name);
+                self.drop_owned_sequences(&drop_table_statement.name, None, is_temporary);
+                self.mark_written(&drop_table_statement.name);
+                self.changes
+                    .record(ChangeOperation::DropTable, &drop_table_statement.name);
+                self.schema_changed(&drop_table_statement.name);
                 Ok(true)
             }
         }
     }
 
-    pub fn eval_query(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, String> {
-        let mut before = Instant::now();
-        let ast = match parse(query) {
-            Ok(val) => val,
-            Err(err) => return Err(err.to_string()),
-        };
-
+    pub fn eval_query(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, BackendError> {
         let mut eval_results = vec![];
+        self.eval_query_on(query, &mut eval_results)?;
+        Ok(eval_results)
+    }
 
-        for statement in ast.statements {
-            match statement {
-                Statement::CreateTableStatement(create_table_statement) => {
-                    let result = self.create_table(create_table_statement)?;
+    // Runs the statements of `query` after those `eval_results` already has the results
+    // of, so a `Connection` can run it on from a write that conflicted
+    pub(super) fn eval_query_on(
+        &mut self,
+        query: &str,
+        eval_results: &mut Vec<EvalResult<SqlValue>>,
+    ) -> Result<(), BackendError> {
+        // The whole query lexes as the session is before it runs, as in Postgres
+        let options = self.lex_options();
+        let statements = self.parse_cached(query)?;
+        let count = statements.len();
+        let mut escapes = nonstandard_escapes(query, options);
+        let spans = match escapes.is_empty() {
+            true => vec![],
+            false => statement_spans(query, options),
+        };
 
-                    eval_results.push(EvalResult::CreateTable {
-                        success: result,
-                        time: before.elapsed(),
-                    });
+        let done = eval_results.len();
+        for (index, statement) in statements.into_iter().enumerate().skip(done) {
+            match self.eval_statement(statement) {
+                Ok(mut result) => {
+                    // Lexing warned first, of the strings within the statement
+                    if let Some(span) = spans.get(index) {
+                        let (lexed, rest) = escapes.into_iter().partition(|warning: &Warning| {
+                            warning
+                                .location
+                                .is_some_and(|loc| span.start <= loc && loc < span.end)
+                        });
+                        escapes = rest;
+                        result.warnings_mut().splice(0..0, lexed);
+                    }
+                    eval_results.push(result)
                 }
-                Statement::CreateIndexStatement(create_index_statement) => {
-                    self.create_index(create_index_statement)?;
+                // With more than one statement, the error says which of them failed
+                Err(err) if count > 1 => return Err(statement_error(err, query, options, index)),
+                Err(err) => return Err(err),
+            }
+        }
 
-                    eval_results.push(EvalResult::CreateTable {
-                        success: true,
-                        time: before.elapsed(),
-                    });
-                }
-                Statement::InsertStatement(insert_statement) => {
-                    let result = self.insert(insert_statement)?;
-                    eval_results.push(EvalResult::Insert {
-                        success: result,
-                        time: before.elapsed(),
-                    });
-                    before = Instant::now();
-                }
-                Statement::SelectStatement(select_statement) => {
-                    let results = self.select(select_statement)?;
-                    eval_results.push(EvalResult::Select {
-                        results,
-                        time: before.elapsed(),
-                    });
-                    before = Instant::now();
-                }
-                Statement::DropTableStatement(drop_table_statement) => {
-                    let result = self.drop_table(drop_table_statement)?;
-                    eval_results.push(EvalResult::DropTable {
-                        success: result,
-                        time: before.elapsed(),
-                    });
-                    before = Instant::now();
+        Ok(())
+    }
+
+    pub fn eval_statement(
+        &mut self,
+        statement: Statement,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        self.check_transaction_block(&statement)?;
+        let result = match statement {
+            Statement::TransactionStatement(transaction) => {
+                self.finish_runs(1);
+                self.eval_transaction_statement(transaction)
+            }
+            statement => match self.retries_conflicts(&statement) {
+                Some(retry) => self.eval_retrying(statement, retry),
+                None => {
+                    self.finish_runs(1);
+                    self.eval_attempt(statement)
                 }
+            },
+        };
+        if result.is_err() {
+            self.fail_transaction_block();
+        }
+        result
+    }
+
+    fn eval_attempt(
+        &mut self,
+        mut statement: Statement,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        let start = self.timing.now();
+        let warnings = enter_warnings();
+        self.bind_identifiers(&mut statement)?;
+        self.expand_views(&mut statement)?;
+        self.check_read_only(&statement)?;
+        self.timing.start_statement();
+        let changes = self.changes.begin();
+        let _sources = self.enter_statement();
+        let _monitor = self.enter_monitor();
+        let _division = self.enter_division_mode();
+        let _sequences = self.enter_sequences();
+        let catalog = self.enter_catalog(&statement)?;
+        let result = self.run_statement(statement);
+        self.leave_catalog(catalog)?;
+        self.changes.end(changes, result.is_ok());
+        let elapsed = self.timing.since(start);
+        self.timing.finish_statement(elapsed);
+        result.map(|mut result| {
+            *result.warnings_mut() = warnings.finish();
+            *result.time_mut() = elapsed;
+            result
+        })
+    }
+
+    // Runs the statement, leaving the time of its result for the caller to fill in
+    fn run_statement(
+        &mut self,
+        statement: Statement,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        // Changes with RETURNING give rows like a query. The change is undone when they
+        // can't be worked out.
+        if !statement.returning().is_empty() {
+            let results = self.transaction(|backend| backend.run_returning(&statement))?;
+            return Ok(EvalResult::Select {
+                results,
+                time: Duration::default(),
+                warnings: vec![],
+            });
+        }
+        match statement {
+            Statement::CreateTableStatement(create_table_statement) => {
+                let result = self.create_table(create_table_statement)?;
+                Ok(EvalResult::CreateTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CreateTableAsStatement(create_table_as_statement) => {
+                let rows_inserted = self.create_table_as(create_table_as_statement)?;
+                Ok(EvalResult::CreateTableAs {
+                    success: true,
+                    rows_inserted,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CreateIndexStatement(create_index_statement) => {
+                self.create_index(create_index_statement)?;
+                Ok(EvalResult::CreateTable {
+                    success: true,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::InsertStatement(insert_statement) => {
+                let result = self.insert(insert_statement)?;
+                Ok(EvalResult::Insert {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::UpdateStatement(update_statement) => {
+                let rows_updated = self.update(update_statement)?;
+                Ok(EvalResult::Update {
+                    rows_updated,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DeleteStatement(delete_statement) => {
+                let rows_deleted = self.delete(delete_statement)?;
+                Ok(EvalResult::Delete {
+                    rows_deleted,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::WithStatement(with_statement) => self.with_query(with_statement),
+            Statement::SelectStatement(select_statement) => {
+                let results = self.select(select_statement)?;
+                Ok(EvalResult::Select {
+                    results,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DropTableStatement(drop_table_statement) => {
+                let result = self.drop_table(drop_table_statement)?;
+                Ok(EvalResult::DropTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DropIndexStatement(drop_index_statement) => {
+                let result = self.drop_index(drop_index_statement)?;
+                Ok(EvalResult::DropIndex {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CreateSequenceStatement(create_sequence_statement) => {
+                let result = self.create_sequence(create_sequence_statement)?;
+                Ok(EvalResult::CreateTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DropSequenceStatement(drop_sequence_statement) => {
+                let result = self.drop_sequence(drop_sequence_statement)?;
+                Ok(EvalResult::DropTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CreateViewStatement(create_view_statement) => {
+                let result = self.create_view(create_view_statement)?;
+                Ok(EvalResult::CreateTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DropViewStatement(drop_view_statement) => {
+                let result = self.drop_view(drop_view_statement)?;
+                Ok(EvalResult::DropTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CreateTriggerStatement(create_trigger_statement) => {
+                let result = self.create_trigger(create_trigger_statement)?;
+                Ok(EvalResult::CreateTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DropTriggerStatement(drop_trigger_statement) => {
+                let result = self.drop_trigger(drop_trigger_statement)?;
+                Ok(EvalResult::DropTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::AlterTableStatement(alter_table_statement) => {
+                let result = self.alter_table(alter_table_statement)?;
+                Ok(EvalResult::AlterTable {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::ReindexStatement(reindex_statement) => {
+                let result = self.reindex(reindex_statement)?;
+                Ok(EvalResult::Reindex {
+                    success: result,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::VacuumStatement(vacuum_statement) => {
+                let stats = self.vacuum(vacuum_statement.table.as_deref())?;
+                Ok(EvalResult::Vacuum {
+                    rows_reclaimed: stats.rows_reclaimed,
+                    bytes_reclaimed: stats.bytes_reclaimed,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::SetStatement(set_statement) => {
+                self.set_option(&set_statement)?;
+                Ok(EvalResult::Set {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::ShowStatement(show_statement) => {
+                let results = self.show_option(&show_statement)?;
+                Ok(EvalResult::Select {
+                    results,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::ResetStatement(reset_statement) => {
+                self.reset_option(&reset_statement)?;
+                Ok(EvalResult::Set {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::ExplainStatement(explain_statement) => {
+                let results = self.explain(explain_statement)?;
+                Ok(EvalResult::Select {
+                    results,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::ListenStatement(listen_statement) => {
+                self.listen(listen_statement);
+                Ok(EvalResult::Listen {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::NotifyStatement(notify_statement) => {
+                self.notify(notify_statement)?;
+                Ok(EvalResult::Notify {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::UnlistenStatement(unlisten_statement) => {
+                self.unlisten(unlisten_statement);
+                Ok(EvalResult::Unlisten {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::DeclareCursorStatement(declare_statement) => {
+                self.declare_cursor(declare_statement)?;
+                Ok(EvalResult::DeclareCursor {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::FetchStatement(fetch_statement) => {
+                let results = self.fetch(fetch_statement)?;
+                Ok(EvalResult::Select {
+                    results,
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            Statement::CloseStatement(close_statement) => {
+                self.close_cursor(close_statement)?;
+                Ok(EvalResult::CloseCursor {
+                    time: Duration::default(),
+                    warnings: vec![],
+                })
+            }
+            // Run by `eval_statement` itself, as they outlast the statement
+            Statement::TransactionStatement(transaction) => {
+                self.eval_transaction_statement(transaction)
             }
         }
+    }
 
-        Ok(eval_results)
+    // Runs `f`, restoring every table to its prior state if it fails
+    pub fn transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut MemoryBackend) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let start = self.begin_transaction();
+        let result = f(self);
+        self.end_transaction(start, result.is_ok());
+        result
+    }
+
+    fn begin_transaction(&mut self) -> TransactionStart {
+        self.storage.begin();
+        self.temp_tables.begin();
+        self.transaction_depth += 1;
+        let outer_writes = self.pending_writes.replace(HashSet::new());
+        let changes = self.changes.begin();
+        let notifications = self.notifications.begin();
+        let variables = self.variables.begin();
+        self.cursors.begin();
+        self.sequences.begin();
+        self.views.begin();
+        self.triggers.begin();
+        TransactionStart {
+            outer_writes,
+            changes,
+            notifications,
+            variables,
+            timed: self.begin_transaction_time(),
+        }
+    }
+
+    // Ends the transaction `start` began, keeping what it did or undoing all of it
+    fn end_transaction(&mut self, start: TransactionStart, commit: bool) {
+        if start.timed {
+            self.end_transaction_time();
+        }
+        self.transaction_depth -= 1;
+        let writes = std::mem::replace(&mut self.pending_writes, start.outer_writes);
+        self.storage.end(commit);
+        self.temp_tables.end(commit);
+        if !commit {
+            self.catalog_changed();
+        }
+        if let (Some(outer_writes), Some(writes)) = (&mut self.pending_writes, writes) {
+            // Writes of a nested transaction are pending until the outer one ends too
+            outer_writes.extend(writes);
+        }
+        self.changes.end(start.changes, commit);
+        self.notifications.end(start.notifications, commit);
+        self.cursors.end(commit);
+        self.sequences.end(commit);
+        self.views.end(commit);
+        self.triggers.end(commit);
+        for (name, value) in self.variables.end(start.variables, commit) {
+            self.restore_setting(&name, value);
+        }
+    }
+
+    // The table a name refers to, see `resolve`
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.engine(name).table(self.kept_name(name))
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Result<Option<&mut Table>, BackendError> {
+        self.engine_mut(name).table_mut(name)
+    }
+
+    // The engine keeping the table a name refers to. Only reads go to the tables of a
+    // WITH, rows are written where the table is kept. The catalog tables a statement
+    // reads are kept along with those of a WITH.
+    fn engine(&self, name: &str) -> &dyn StorageEngine {
+        match self.resolve(name) {
+            Some(Resolution::WithQuery) | Some(Resolution::Catalog) => &self.with_tables,
+            resolution => self.namespace(resolution == Some(Resolution::Temporary)),
+        }
+    }
+
+    fn engine_mut(&mut self, name: &str) -> &mut dyn StorageEngine {
+        self.namespace_mut(self.is_temporary(name))
     }
 
+    fn namespace(&self, is_temporary: bool) -> &dyn StorageEngine {
+        if is_temporary {
+            &self.temp_tables
+        } else {
+            self.storage.as_ref()
+        }
+    }
+
+    fn namespace_mut(&mut self, is_temporary: bool) -> &mut dyn StorageEngine {
+        if is_temporary {
+            &mut self.temp_tables
+        } else {
+            self.storage.as_mut()
+        }
+    }
+
+    fn mark_written(&mut self, table_name: &str) {
+        if let Some(pending_writes) = &mut self.pending_writes {
+            pending_writes.insert(table_name.to_string());
+        }
+    }
+
+    // Whether the running transaction wrote to the table
+    pub fn has_pending_writes(&self, table_name: &str) -> bool {
+        match &self.pending_writes {
+            Some(pending_writes) => pending_writes.contains(table_name),
+            None => false,
+        }
+    }
+
+    /// Runs `query` and maps the rows of its last statement to `T`.
+    pub fn query_as<T: FromRow>(&mut self, query: &str) -> Result<Vec<T>, BackendError> {
+        match self.eval_query(query)?.pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                results.map_rows().map_err(BackendError::from)
+            }
+            _ => Err(BackendError::NoData(
+                "Query did not return any rows.".to_string(),
+            )),
+        }
+    }
+
+    // Creates the index, built from the rows the table has at once, see `Index::build`.
+    // The backend is held by the statement until it ends like by any other, so writes to
+    // the table through other connections wait for the index to be built and none of
+    // their rows is left out of it.
     pub fn create_index(
         &mut self,
         create_index_statement: CreateIndexStatement,
-    ) -> Result<(), String> {
-        let table = match self.tables.get_mut(&create_index_statement.table) {
+    ) -> Result<(), BackendError> {
+        let table = match self.table(&create_index_statement.table) {
             Some(value) => value,
-            None => return Err("Table not found".to_string()),
+            None => return Err(BackendError::UndefinedTable("Table not found".to_string())),
         };
 
         for index in &table.indexes {
             if index.name == create_index_statement.name {
-                return Err("Index name already exists".to_string());
+                return Err(BackendError::DuplicateTable(
+                    "Index name already exists".to_string(),
+                ));
             }
         }
 
-        let index = Index {
+        let mut columns = vec![];
+        index_columns(&create_index_statement.expression, &mut columns);
+        let mut index = Index {
+            columns,
             expression: create_index_statement.expression,
             unique: create_index_statement.is_unique,
             primary_key: create_index_statement.is_primary_key,
             name: create_index_statement.name,
             tree: std::collections::btree_map::BTreeMap::new(),
+            bytes: 0,
             typ: "btreemap".to_string(),
         };
+        // The rows already there are looked up by it as well
+        index.build(table)?;
 
-        table.indexes.push(index);
+        self.mark_written(&create_index_statement.table);
+        self.engine_mut(&create_index_statement.table)
+            .create_index(&create_index_statement.table, index)?;
+        self.schema_changed(&create_index_statement.table);
 
         Ok(())
     }
 }
 
+#[cfg(not(feature = "parallel"))]
+impl MemoryBackend {
+    fn filter_in_parallel(
+        &self,
+        _table_name: &str,
+        _rows: &[StoredRow],
+        _condition: &CompiledExpression,
+    ) -> Option<Vec<Result<bool, BackendError>>> {
+        None
+    }
+
+    fn group_rows(
+        &self,
+        _table_name: &str,
+        aggregation: &Aggregation,
+        rows: &[StoredRow],
+        where_clause: &Option<CompiledExpression>,
+    ) -> Result<Groups, BackendError> {
+        aggregation.groups(rows, where_clause)
+    }
+
+    fn sort_rows(&self, _table_name: &str, rows: &mut [(MemoryCellData, Vec<SqlValue>)]) {
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+}
+
+// Where a statement of a query starts and ends, the end being right after its last
+// character, and the bytes of the query it takes
+#[derive(Clone, Copy)]
+struct StatementSpan {
+    start: TokenLocation,
+    end: TokenLocation,
+    first_byte: usize,
+    end_byte: usize,
+}
+
+// The spans of the statements of a query. Empty statements are left out, as the parser
+// skips them.
+fn statement_spans(query: &str, options: LexOptions) -> Vec<StatementSpan> {
+    let tokens = match Lexer::with_options(options).lex(query) {
+        Ok(tokens) => tokens,
+        Err(_) => return vec![],
+    };
+    let mut spans = vec![];
+    let mut statement: Option<StatementSpan> = None;
+    let mut depth = 0usize;
+    for token in &tokens {
+        match token.token {
+            Token::LeftParenthesis => depth += 1,
+            Token::RightParenthesis => depth = depth.saturating_sub(1),
+            Token::Semicolon if depth == 0 => {
+                spans.extend(statement.take());
+                continue;
+            }
+            _ => {}
+        }
+        let (start, first_byte) = statement.map_or((token.loc, token.span.start), |span| {
+            (span.start, span.first_byte)
+        });
+        statement = Some(StatementSpan {
+            start,
+            end: token.span.end_loc,
+            first_byte,
+            end_byte: token.span.end,
+        });
+    }
+    spans.extend(statement);
+    spans
+}
+
+// Prefixes the error of the statement at `index` of `query` with its ordinal and span,
+// and follows it with the start of the statement. Locations within the message already
+// count from the start of the query, which is lexed as a whole.
+fn statement_error(
+    err: BackendError,
+    query: &str,
+    options: LexOptions,
+    index: usize,
+) -> BackendError {
+    match statement_spans(query, options).get(index) {
+        Some(span) => err.map_message(|msg| {
+            format!(
+                "Statement {} (line {}, column {} to line {}, column {}) failed: {}\n\
+                Statement: {}",
+                index + 1,
+                span.start.line + 1,
+                span.start.col + 1,
+                span.end.line + 1,
+                span.end.col,
+                msg,
+                statement_excerpt(&query[span.first_byte..span.end_byte])
+            )
+        }),
+        None => err.map_message(|msg| format!("Statement {} failed: {}", index + 1, msg)),
+    }
+}
+
+// A query's lex or parse error, which like statement_error says which of its statements
+// failed when it has more than one
+fn syntax_error(err: ParsingError, query: &str, options: LexOptions) -> BackendError {
+    let index = match &err {
+        // A query that doesn't lex is told apart into statements by its semicolons alone
+        ParsingError::Lexing { loc, .. } => {
+            let statements = split_statements(query).unwrap_or_default();
+            let started = statements.iter().filter(|statement| statement.loc <= *loc);
+            (statements.len() > 1).then(|| started.count().saturating_sub(1))
+        }
+        ParsingError::General { cursor, .. } | ParsingError::Delimiter { cursor, .. } => {
+            let tokens = Lexer::with_options(options).lex(query).unwrap_or_default();
+            let spans = statement_spans(query, options);
+            let loc = match tokens.get(*cursor).or_else(|| tokens.last()) {
+                Some(token) => token.loc,
+                None => return err.into(),
+            };
+            let started = spans.iter().filter(|span| span.start <= loc);
+            (spans.len() > 1).then(|| started.count().saturating_sub(1))
+        }
+    };
+    match index {
+        Some(index) => statement_error(err.into(), query, options, index),
+        None => err.into(),
+    }
+}
+
+// Adds the sortable encoding of an ORDER BY value to the key of its row. Encodings end
+// where their values do, so keys made of several compare value by value. Those of DESC
+// values are complemented to order the other way round, NULLs then coming first.
+fn push_order_key(key: &mut Vec<u8>, value: &SqlValue, asc: bool) {
+    let encoded = value.encode_sortable().bytes;
+    if asc {
+        key.extend_from_slice(&encoded);
+    } else {
+        key.extend(encoded.iter().map(|byte| !byte));
+    }
+}
+
+// Ordering of rows by the sortable encoding of a value they are sorted by, rows with
+// equal values keep their order. NULLs come last, or first when descending.
+fn compare_order_keys(a: &[u8], b: &[u8], asc: bool) -> std::cmp::Ordering {
+    if asc {
+        a.cmp(b)
+    } else {
+        b.cmp(a)
+    }
+}
+
 pub fn linearize_expressions(
     where_clause: Option<Expression>,
     expressions: Vec<Expression>,
@@ -1212,9 +3187,1463 @@ pub fn linearize_expressions(
 }
 
 #[inline]
-pub fn literal_to_memory_cell(token: &Token) -> Result<SqlValue, String> {
+pub fn literal_to_memory_cell(token: &Token) -> Result<SqlValue, BackendError> {
     match SqlValue::from_token(&token) {
         Ok(value) => Ok(value),
-        Err(err) => Err(err.to_string()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod identifier_tests {
+    use super::*;
+    use crate::sql_types::SqlText;
+
+    struct IdentifierTest {
+        query: &'static str,
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    // Runs a query selecting a single text column
+    fn select_texts(mb: &mut MemoryBackend, query: &str) -> Result<Vec<String>, BackendError> {
+        match mb.eval_query(query)?.pop() {
+            Some(EvalResult::Select { results, .. }) => Ok(results
+                .rows
+                .into_iter()
+                .map(|row| match &row[..] {
+                    [SqlValue::Text(SqlText::Text { value })] => value.clone(),
+                    row => panic!("Unexpected row {:?}", row),
+                })
+                .collect()),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_quoted_identifiers() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE users (username TEXT);
+            CREATE TABLE \"Users\" (\"UserName\" TEXT);
+            INSERT INTO users VALUES ('lower');
+            INSERT INTO \"Users\" VALUES ('quoted');",
+        )
+        .unwrap();
+
+        let tests = vec![
+            IdentifierTest {
+                query: "SELECT USERNAME FROM Users;",
+                result: Ok(vec!["lower"]),
+            },
+            IdentifierTest {
+                query: "SELECT \"username\" FROM \"users\";",
+                result: Ok(vec!["lower"]),
+            },
+            IdentifierTest {
+                query: "SELECT users.UserName FROM USERS;",
+                result: Ok(vec!["lower"]),
+            },
+            IdentifierTest {
+                query: "SELECT \"UserName\" FROM \"Users\";",
+                result: Ok(vec!["quoted"]),
+            },
+            IdentifierTest {
+                query: "SELECT \"Users\".\"UserName\" FROM \"Users\";",
+                result: Ok(vec!["quoted"]),
+            },
+            IdentifierTest {
+                query: "SELECT UserName FROM \"Users\";",
+                result: Err("username: Column does not exist."),
+            },
+            IdentifierTest {
+                query: "SELECT \"USERNAME\" FROM users;",
+                result: Err("USERNAME: Column does not exist."),
+            },
+            IdentifierTest {
+                query: "SELECT users.\"UserName\" FROM \"Users\";",
+                result: Err("users: Table does not exist."),
+            },
+            IdentifierTest {
+                query: "SELECT \"Users\".username FROM \"Users\";",
+                result: Err("username: Column does not exist."),
+            },
+            IdentifierTest {
+                query: "SELECT * FROM \"USERS\";",
+                result: Err("Table does not exist."),
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                select_texts(&mut mb, test.query).map_err(|err| err.to_string()),
+                test.result
+                    .map(|rows| rows.into_iter().map(String::from).collect())
+                    .map_err(String::from),
+                "{}",
+                test.query
+            );
+        }
+
+        // Quoted names can reuse keywords and keep any quotes inside them
+        mb.eval_query("CREATE TABLE \"select\" (\"odd\"\"name\" INT);")
+            .unwrap();
+        assert!(mb
+            .eval_query("SELECT \"odd\"\"name\" FROM \"select\";")
+            .is_ok());
+        assert!(mb.eval_query("CREATE TABLE \"Users\" (id INT);").is_err());
+        assert!(mb.eval_query("CREATE TABLE \"USERS\" (id INT);").is_ok());
+    }
+
+    #[test]
+    fn test_keyword_identifiers() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE index (key INT, \"order\" TEXT, \"limit\" INT, text TEXT);
+            INSERT INTO index VALUES (1, 'first', 10, 'a');
+            INSERT INTO index VALUES (2, 'second', 20, 'b');
+            CREATE INDEX key ON index (key);",
+        )
+        .unwrap();
+
+        let tests = vec![
+            IdentifierTest {
+                query: "SELECT \"order\" FROM index WHERE key = 2;",
+                result: Ok(vec!["second"]),
+            },
+            IdentifierTest {
+                query: "SELECT text FROM index WHERE \"limit\" < 15;",
+                result: Ok(vec!["a"]),
+            },
+            IdentifierTest {
+                query: "SELECT index.text AS key FROM index ORDER BY \"order\" DESC;",
+                result: Ok(vec!["b", "a"]),
+            },
+            IdentifierTest {
+                query: "SELECT \"order\" FROM \"index\" WHERE \"key\" = 1;",
+                result: Ok(vec!["first"]),
+            },
+        ];
+        for test in tests {
+            assert_eq!(
+                select_texts(&mut mb, test.query).map_err(|err| err.to_string()),
+                test.result
+                    .map(|rows| rows.into_iter().map(String::from).collect())
+                    .map_err(String::from),
+                "{}",
+                test.query
+            );
+        }
+
+        // Reserved keywords still have to be quoted
+        assert!(mb.eval_query("CREATE TABLE t (order INT);").is_err());
+        assert!(mb.eval_query("SELECT limit FROM index;").is_err());
+    }
+}
+
+#[cfg(test)]
+mod insert_tests {
+    use super::*;
+    use crate::sql_types::{SqlNumeric, SqlText};
+
+    struct InsertTest {
+        query: &'static str,
+        result: Result<Vec<SqlValue>, &'static str>,
+    }
+
+    fn int(value: i32) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::Int { value })
+    }
+
+    fn bigint(value: i64) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::BigInt { value })
+    }
+
+    fn text(value: &str) -> SqlValue {
+        SqlValue::Text(SqlText::Text {
+            value: value.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_insert_expressions() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE other (id INT, name TEXT);
+            INSERT INTO other VALUES (7, 'seven');
+            INSERT INTO other VALUES (8, 'eight');
+            CREATE TABLE t (a INT, b TEXT, c BIGINT);",
+        )
+        .unwrap();
+
+        let tests = vec![
+            InsertTest {
+                query: "INSERT INTO t
+                    VALUES (1 + 2, upper('a'), (SELECT id FROM other WHERE name = 'seven'));",
+                result: Ok(vec![int(3), text("A"), bigint(7)]),
+            },
+            InsertTest {
+                query: "INSERT INTO t
+                    VALUES (abs(2 - 10) * 2, lower('AbC') || '!', length('four'));",
+                result: Ok(vec![int(16), text("abc!"), bigint(4)]),
+            },
+            // Values are converted to the column type once evaluated
+            InsertTest {
+                query: "INSERT INTO t
+                    VALUES ('4'::INT, 5::TEXT, (SELECT id FROM other WHERE id > 100));",
+                result: Ok(vec![int(4), text("5"), SqlValue::Null]),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES (id, 'x', 1);",
+                result: Err("id: Column does not exist in this context."),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES (1, upper(name), 1);",
+                result: Err("name: Column does not exist in this context."),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES (max(1), 'x', 1);",
+                result: Err("max: Aggregate functions are not allowed here."),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES (1, nope('x'), 1);",
+                result: Err("nope: Function does not exist."),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES ((SELECT id FROM other), 'x', 1);",
+                result: Err("Subquery used as an expression returned more than one row"),
+            },
+            InsertTest {
+                query: "INSERT INTO t VALUES (upper('x'), 'x', 1);",
+                result: Err(
+                    "Cannot assign Text value in row 1 to column \"a\" of type Int without an explicit cast.",
+                ),
+            },
+        ];
+
+        for test in tests {
+            let result = mb.eval_query(test.query).map(|_| {
+                match mb.eval_query("SELECT * FROM t;").unwrap().pop() {
+                    Some(EvalResult::Select { mut results, .. }) => results.rows.pop().unwrap(),
+                    _ => panic!("Expected select results"),
+                }
+            });
+            assert_eq!(
+                result.map_err(|err| err.to_string()),
+                test.result.map_err(String::from),
+                "{}",
+                test.query
+            );
+        }
+
+        // The same functions work on the rows of a query
+        match mb
+            .eval_query("SELECT upper(name) FROM other WHERE length(name) > 4 AND id = 8;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.rows, vec![vec![text("EIGHT")]])
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod create_table_as_tests {
+    use super::*;
+
+    struct CreateTableAsTest {
+        query: &'static str,
+        // The rows inserted and the new table's columns, or the error
+        result: Result<(usize, Vec<(&'static str, SqlType)>), &'static str>,
+        rows: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_create_table_as() {
+        let tests = vec![
+            CreateTableAsTest {
+                query: "CREATE TABLE t1 AS SELECT dept, pay * 2 AS double_pay, NULL AS nothing
+                    FROM emp WHERE pay > 10 ORDER BY pay;",
+                result: Ok((
+                    2,
+                    vec![
+                        ("dept", SqlType::Text),
+                        ("double_pay", SqlType::BigInt),
+                        ("nothing", SqlType::Text),
+                    ],
+                )),
+                rows: vec!["ops,40,NULL", "dev,60,NULL"],
+            },
+            CreateTableAsTest {
+                query: "CREATE TABLE t2 (department, n) AS SELECT dept, id FROM emp WITH DATA;",
+                result: Ok((3, vec![("department", SqlType::Text), ("n", SqlType::Int)])),
+                rows: vec!["dev,1", "ops,2", "dev,3"],
+            },
+            // Fewer names than columns rename only the first ones
+            CreateTableAsTest {
+                query: "CREATE TABLE t3 (department) AS SELECT dept, id = 1 FROM emp
+                    WITH NO DATA;",
+                result: Ok((
+                    0,
+                    vec![
+                        ("department", SqlType::Text),
+                        ("?column?", SqlType::Boolean),
+                    ],
+                )),
+                rows: vec![],
+            },
+            // A query without rows still gives the columns their types
+            CreateTableAsTest {
+                query: "CREATE TABLE t4 AS SELECT id + 1 AS next, upper(dept) AS d FROM emp
+                    WHERE id > 10;",
+                result: Ok((0, vec![("next", SqlType::Int), ("d", SqlType::Text)])),
+                rows: vec![],
+            },
+            CreateTableAsTest {
+                query: "CREATE TABLE t5 AS SELECT 1, 2;",
+                result: Err("Column \"?column?\" specified more than once."),
+                rows: vec![],
+            },
+            CreateTableAsTest {
+                query: "CREATE TABLE t6 (a, b, c) AS SELECT id, dept FROM emp;",
+                result: Err("Too many column names were specified."),
+                rows: vec![],
+            },
+            CreateTableAsTest {
+                query: "CREATE TABLE emp AS SELECT id FROM emp;",
+                result: Err("Table \"emp\" already exists."),
+                rows: vec![],
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE emp (id INT, dept TEXT, pay BIGINT);
+            INSERT INTO emp VALUES (1, 'dev', 30);
+            INSERT INTO emp VALUES (2, 'ops', 20);
+            INSERT INTO emp VALUES (3, 'dev', 10);",
+        )
+        .unwrap();
+
+        for test in tests {
+            let (rows_inserted, columns) = match (mb.eval_query(test.query), test.result) {
+                (Ok(mut results), Ok(expected)) => match results.pop() {
+                    Some(EvalResult::CreateTableAs { rows_inserted, .. }) => {
+                        assert_eq!(rows_inserted, expected.0, "{}", test.query);
+                        expected
+                    }
+                    _ => panic!("Expected create table results for {}", test.query),
+                },
+                (Err(err), Err(expected)) => {
+                    assert_eq!(err.to_string(), expected, "{}", test.query);
+                    continue;
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            };
+
+            let name = test.query.split_whitespace().nth(2).unwrap();
+            let table = mb.table(name).unwrap();
+            let table_columns: Vec<(&str, SqlType)> = table
+                .columns
+                .iter()
+                .map(String::as_str)
+                .zip(table.column_types.iter().copied())
+                .collect();
+            assert_eq!(table_columns, columns, "{}", test.query);
+            assert_eq!(table.rows.len(), rows_inserted, "{}", test.query);
+            let rows: Vec<String> = table
+                .rows
+                .iter()
+                .map(|row| {
+                    row.values()
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect();
+            assert_eq!(rows, test.rows, "{}", test.query);
+        }
+    }
+}
+
+#[cfg(test)]
+mod quantified_tests {
+    use super::*;
+
+    struct QuantifiedTest {
+        query: &'static str,
+        // The result rows joined by commas, or the error
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    #[test]
+    fn test_quantified_comparisons() {
+        let tests = vec![
+            QuantifiedTest {
+                query: "SELECT name FROM users WHERE id = ANY (SELECT user_id FROM orders);",
+                result: Ok(vec!["Baam", "Khun"]),
+            },
+            QuantifiedTest {
+                query: "SELECT name FROM users WHERE id = SOME (SELECT user_id FROM orders
+                    WHERE price > 20);",
+                result: Ok(vec!["Khun"]),
+            },
+            // The NULL user_id makes the comparisons that find no match unknown
+            QuantifiedTest {
+                query: "SELECT name, id <> ALL (SELECT user_id FROM orders),
+                    id = ANY (SELECT user_id FROM orders) FROM users;",
+                result: Ok(vec![
+                    "Baam,false,true",
+                    "Rachel,NULL,NULL",
+                    "Khun,false,true",
+                ]),
+            },
+            QuantifiedTest {
+                query: "SELECT name FROM users WHERE id * 10 >= ALL (SELECT price FROM orders);",
+                result: Ok(vec!["Khun"]),
+            },
+            // ALL holds and ANY fails for a subquery without rows, even for NULL
+            QuantifiedTest {
+                query: "SELECT id > ALL (SELECT price FROM orders WHERE price > 100),
+                    id = ANY (SELECT price FROM orders WHERE price > 100),
+                    NULL > ALL (SELECT price FROM orders WHERE price > 100),
+                    NULL = ANY (SELECT price FROM orders) FROM users WHERE id = 1;",
+                result: Ok(vec!["true,false,true,NULL"]),
+            },
+            QuantifiedTest {
+                query: "SELECT name FROM users WHERE id > 1 AND 25 < ANY (SELECT price FROM orders)
+                    ORDER BY name;",
+                result: Ok(vec!["Khun", "Rachel"]),
+            },
+            QuantifiedTest {
+                query: "SELECT id FROM users WHERE id = ANY (SELECT user_id, price FROM orders);",
+                result: Err("Subquery must return only one column"),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE users (id INT, name TEXT);
+            INSERT INTO users VALUES (1, 'Baam');
+            INSERT INTO users VALUES (2, 'Rachel');
+            INSERT INTO users VALUES (3, 'Khun');
+            CREATE TABLE orders (user_id INT, price INT);
+            INSERT INTO orders VALUES (1, 10);
+            INSERT INTO orders VALUES (3, 30);
+            INSERT INTO orders VALUES (NULL, 5);",
+        )
+        .unwrap();
+
+        for test in tests {
+            let rows = match mb.eval_query(test.query).map(|mut results| results.pop()) {
+                Ok(Some(EvalResult::Select { results, .. })) => Ok(results
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    })
+                    .collect::<Vec<_>>()),
+                Ok(_) => panic!("Expected select results for {}", test.query),
+                Err(err) => Err(err),
+            };
+            match (rows, test.result) {
+                (Ok(rows), Ok(expected)) => assert_eq!(rows, expected, "{}", test.query),
+                (Err(err), Err(expected)) => {
+                    assert_eq!(err.to_string(), expected, "{}", test.query)
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+
+        // Values of an INSERT run their subqueries too
+        mb.eval_query(
+            "CREATE TABLE flags (ok BOOLEAN);
+            INSERT INTO flags VALUES (30 = ANY (SELECT price FROM orders));
+            INSERT INTO flags VALUES (30 > ALL (SELECT price FROM orders));",
+        )
+        .unwrap();
+        let flags: Vec<String> = mb
+            .table("flags")
+            .unwrap()
+            .rows
+            .iter()
+            .map(|row| row.value(0).to_string())
+            .collect();
+        assert_eq!(flags, ["true", "false"]);
+
+        assert!(mb
+            .eval_query("SELECT id FROM users WHERE id + ANY (SELECT 1);")
+            .is_err());
+        assert!(mb
+            .eval_query("SELECT id FROM users WHERE id = ANY (1);")
+            .is_err());
+    }
+}
+
+#[cfg(test)]
+mod table_function_tests {
+    use super::*;
+
+    struct TableFunctionTest {
+        query: &'static str,
+        // The result columns, then the rows joined by commas, or the error
+        result: Result<(Vec<&'static str>, Vec<&'static str>), &'static str>,
+    }
+
+    #[test]
+    fn test_generate_series() {
+        let tests = vec![
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(1, 3);",
+                result: Ok((vec!["generate_series"], vec!["1", "2", "3"])),
+            },
+            TableFunctionTest {
+                query: "SELECT n * 2 FROM generate_series(1, 5) AS g(n) WHERE n > 3;",
+                result: Ok((vec!["?column?"], vec!["8", "10"])),
+            },
+            TableFunctionTest {
+                query: "SELECT g FROM generate_series(10, 1, -4) g;",
+                result: Ok((vec!["g"], vec!["10", "6", "2"])),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(3, 1);",
+                result: Ok((vec!["generate_series"], vec![])),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(1, NULL);",
+                result: Ok((vec!["generate_series"], vec![])),
+            },
+            // The series stops at the end of the range rather than overflowing
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(9223372036854775806, 9223372036854775807)
+                    AS g(n);",
+                result: Ok((
+                    vec!["n"],
+                    vec!["9223372036854775806", "9223372036854775807"],
+                )),
+            },
+            TableFunctionTest {
+                query: "SELECT name, n FROM people JOIN generate_series(1, 2) AS g(n) ON id = n;",
+                result: Ok((vec!["name", "n"], vec!["Baam,1"])),
+            },
+            TableFunctionTest {
+                query: "SELECT n, name FROM generate_series(1, 4) AS g(n)
+                    LEFT JOIN people ON n = id;",
+                result: Ok((
+                    vec!["n", "name"],
+                    vec!["1,Baam", "3,Khun", "2,NULL", "4,NULL"],
+                )),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(1, 10, 0);",
+                result: Err("generate_series step size cannot equal zero."),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(1, 3) AS g(a, b);",
+                result: Err("Table \"g\" has 1 column available but 2 columns were specified."),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM generate_series(1);",
+                result: Err("generate_series takes 2 or 3 arguments, got 1."),
+            },
+            TableFunctionTest {
+                query: "SELECT * FROM upper('a');",
+                result: Err("upper: Function does not exist."),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT, name TEXT);
+            INSERT INTO people VALUES (1, 'Baam');
+            INSERT INTO people VALUES (3, 'Khun');",
+        )
+        .unwrap();
+
+        for test in tests {
+            let results = match mb.eval_query(test.query).map(|mut results| results.pop()) {
+                Ok(Some(EvalResult::Select { results, .. })) => Ok(results),
+                Ok(_) => panic!("Expected select results for {}", test.query),
+                Err(err) => Err(err),
+            };
+            match (results, test.result) {
+                (Ok(results), Ok((columns, rows))) => {
+                    let names: Vec<&str> =
+                        results.columns.iter().map(|c| c.name.as_str()).collect();
+                    assert_eq!(names, columns, "{}", test.query);
+                    let result_rows: Vec<String> = results
+                        .rows
+                        .iter()
+                        .map(|row| {
+                            row.iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(",")
+                        })
+                        .collect();
+                    assert_eq!(result_rows, rows, "{}", test.query);
+                }
+                (Err(err), Err(expected)) => {
+                    assert_eq!(err.to_string(), expected, "{}", test.query)
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+
+        // The series counts against the result limits as it is generated
+        mb.set_result_limits(ResultLimits {
+            max_result_rows: Some(100),
+            max_result_bytes: None,
+        });
+        assert!(mb
+            .eval_query("SELECT * FROM generate_series(1, 1000000000000) LIMIT 1;")
+            .unwrap_err()
+            .message()
+            .starts_with(ERR_RESULT_LIMIT_EXCEEDED));
+    }
+}
+
+#[cfg(test)]
+mod values_tests {
+    use super::*;
+
+    struct ValuesTest {
+        query: &'static str,
+        // The result columns, then the rows joined by commas, or the start of the error
+        result: Result<(Vec<&'static str>, Vec<&'static str>), &'static str>,
+    }
+
+    #[test]
+    fn test_values_lists() {
+        let tests = vec![
+            ValuesTest {
+                query: "SELECT * FROM (VALUES (1, 'a'), (2, 'b')) AS t(id, name);",
+                result: Ok((vec!["id", "name"], vec!["1,a", "2,b"])),
+            },
+            ValuesTest {
+                query:
+                    "SELECT column2, column1 FROM (VALUES (1, 'a'), (2, 'b')) t WHERE column1 > 1;",
+                result: Ok((vec!["column2", "column1"], vec!["b,2"])),
+            },
+            // Later rows widen the column to fit their numbers
+            ValuesTest {
+                query: "SELECT n FROM (VALUES (1), (NULL), (2.5)) AS v(n);",
+                result: Ok((vec!["n"], vec!["1", "NULL", "2.5"])),
+            },
+            ValuesTest {
+                query: "SELECT * FROM (VALUES (1), ('a')) AS v;",
+                result: Err("VALUES types Int and Text cannot be matched."),
+            },
+            ValuesTest {
+                query: "SELECT * FROM (VALUES (1)) AS v(a, b);",
+                result: Err("Table \"v\" has 1 columns available but 2 columns were specified."),
+            },
+            ValuesTest {
+                query: "SELECT * FROM (VALUES (1, 2), (3)) AS v;",
+                result: Err("[1, 31]: VALUES lists must all be the same length"),
+            },
+            ValuesTest {
+                query: "SELECT * FROM (VALUES (1), (2));",
+                result: Err("[1, 31]: VALUES in FROM must have an alias"),
+            },
+            ValuesTest {
+                query: "VALUES (1 + 2, upper('x')), (4, 'y');",
+                result: Ok((vec!["column1", "column2"], vec!["3,X", "4,y"])),
+            },
+            ValuesTest {
+                query: "SELECT name, word FROM people JOIN (VALUES (3, 'three')) AS v(n, word)
+                    ON id = n;",
+                result: Ok((vec!["name", "word"], vec!["Khun,three"])),
+            },
+            ValuesTest {
+                query: "SELECT n, name FROM (VALUES (1), (2)) AS v(n) LEFT JOIN people ON n = id;",
+                result: Ok((vec!["n", "name"], vec!["1,Baam", "2,NULL"])),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT, name TEXT);
+            INSERT INTO people VALUES (1, 'Baam');
+            INSERT INTO people VALUES (3, 'Khun');",
+        )
+        .unwrap();
+
+        for test in tests {
+            let results = match mb.eval_query(test.query).map(|mut results| results.pop()) {
+                Ok(Some(EvalResult::Select { results, .. })) => Ok(results),
+                Ok(_) => panic!("Expected select results for {}", test.query),
+                Err(err) => Err(err),
+            };
+            match (results, test.result) {
+                (Ok(results), Ok((columns, rows))) => {
+                    let names: Vec<&str> =
+                        results.columns.iter().map(|c| c.name.as_str()).collect();
+                    assert_eq!(names, columns, "{}", test.query);
+                    let result_rows: Vec<String> = results
+                        .rows
+                        .iter()
+                        .map(|row| {
+                            row.iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(",")
+                        })
+                        .collect();
+                    assert_eq!(result_rows, rows, "{}", test.query);
+                }
+                (Err(err), Err(expected)) => {
+                    assert!(
+                        err.message().starts_with(expected),
+                        "{}: {}",
+                        test.query,
+                        err
+                    )
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod default_tests {
+    use super::*;
+
+    struct DefaultTest {
+        query: &'static str,
+        // The rows of the table afterwards joined by commas, or the start of the error
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    fn table_rows(mb: &mut MemoryBackend, query: &str) -> Vec<String> {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results
+                .rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect(),
+            _ => panic!("Expected select results for {}", query),
+        }
+    }
+
+    #[test]
+    fn test_column_defaults() {
+        let tests = vec![
+            DefaultTest {
+                query: "INSERT INTO items VALUES (1, DEFAULT, DEFAULT, DEFAULT);",
+                result: Ok(vec!["1,item,3,NULL"]),
+            },
+            // Every row of a multi-row insert gets its own defaults
+            DefaultTest {
+                query: "INSERT INTO items VALUES (2, 'b', DEFAULT, 'x'), (3, DEFAULT, 7, NULL);",
+                result: Ok(vec!["1,item,3,NULL", "2,b,3,x", "3,item,7,NULL"]),
+            },
+            DefaultTest {
+                query: "INSERT INTO items VALUES (4, NULL, DEFAULT, DEFAULT);",
+                result: Err("Null value in column \"name\" violates NOT NULL constraint."),
+            },
+            // A failing row keeps the rows before it out as well
+            DefaultTest {
+                query: "INSERT INTO items VALUES (4, 'd', DEFAULT, DEFAULT), (1, 'e', 1, 'y');",
+                result: Err("Duplicate Value violates UNIQUE Constraint"),
+            },
+            DefaultTest {
+                query: "INSERT INTO items VALUES (4, 'd', DEFAULT, DEFAULT);",
+                result: Ok(vec![
+                    "1,item,3,NULL",
+                    "2,b,3,x",
+                    "3,item,7,NULL",
+                    "4,d,3,NULL",
+                ]),
+            },
+            DefaultTest {
+                query: "INSERT INTO items VALUES (5, 'e', 1);",
+                result: Err(ERR_MISSING_VALUES),
+            },
+            DefaultTest {
+                query: "SELECT DEFAULT;",
+                result: Err("DEFAULT is not allowed in this context."),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE items (id INT PRIMARY KEY, name TEXT NOT NULL DEFAULT 'item',
+                quantity INT DEFAULT 1 + 2 NOT NULL, note TEXT);",
+        )
+        .unwrap();
+
+        for test in tests {
+            match (mb.eval_query(test.query), test.result) {
+                (Ok(_), Ok(rows)) => {
+                    let result_rows = table_rows(&mut mb, "SELECT * FROM items;");
+                    assert_eq!(result_rows, rows, "{}", test.query);
+                }
+                (Err(err), Err(expected)) => {
+                    assert!(
+                        err.message().starts_with(expected),
+                        "{}: {}",
+                        test.query,
+                        err
+                    )
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_function_defaults() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE events (id TEXT DEFAULT gen_random_uuid(), created_at TEXT DEFAULT now());
+            INSERT INTO events VALUES (DEFAULT, DEFAULT), (DEFAULT, DEFAULT);
+            INSERT INTO events VALUES (DEFAULT, DEFAULT);",
+        )
+        .unwrap();
+
+        let ids = table_rows(&mut mb, "SELECT id FROM events;");
+        assert_eq!(ids.len(), 3);
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id.len(), 36, "{}", id);
+            assert_eq!(&id[14..15], "4", "{}", id);
+            assert!(!ids[..i].contains(id), "{:?}", ids);
+        }
+
+        for created_at in table_rows(&mut mb, "SELECT created_at FROM events;") {
+            assert_eq!(created_at.len(), 29, "{}", created_at);
+            assert!(created_at.ends_with("+00"), "{}", created_at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod check_tests {
+    use super::*;
+
+    struct CheckTest {
+        query: &'static str,
+        // The rows of the table afterwards joined by commas, or the start of the error
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    #[test]
+    fn test_check_constraints() {
+        let tests = vec![
+            // NULL passes a check just like TRUE does
+            CheckTest {
+                query: "INSERT INTO t VALUES (1, 2.5, 4), (NULL, NULL, NULL);",
+                result: Ok(vec!["1,2.5,4", "NULL,NULL,NULL"]),
+            },
+            CheckTest {
+                query: "INSERT INTO t VALUES (-1, 1, 1);",
+                result: Err(
+                    "New row for relation \"t\" violates check constraint \"t_age_check\".",
+                ),
+            },
+            CheckTest {
+                query: "INSERT INTO t VALUES (1, 100, 100);",
+                result: Err("New row for relation \"t\" violates check constraint \"t_check\"."),
+            },
+            CheckTest {
+                query: "INSERT INTO t VALUES (1, 1, 13);",
+                result: Err(
+                    "New row for relation \"t\" violates check constraint \"no_thirteen\".",
+                ),
+            },
+            // The second check on qty gets a number to tell it apart
+            CheckTest {
+                query: "INSERT INTO t VALUES (5, 1, 2), (1, 1, -2);",
+                result: Err(
+                    "New row for relation \"t\" violates check constraint \"t_qty_check1\".",
+                ),
+            },
+            CheckTest {
+                query: "INSERT INTO t VALUES (5, 1, 2);",
+                result: Ok(vec!["1,2.5,4", "NULL,NULL,NULL", "5,1,2"]),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (age INT CHECK (age >= 0), price REAL, qty INT CHECK (qty <> 0),
+                CHECK (price * qty < 100), CONSTRAINT no_thirteen CHECK (qty <> 13),
+                CHECK (t.qty > 0));",
+        )
+        .unwrap();
+
+        for test in tests {
+            match (mb.eval_query(test.query), test.result) {
+                (Ok(_), Ok(rows)) => {
+                    let results = match mb.eval_query("SELECT * FROM t;").unwrap().pop() {
+                        Some(EvalResult::Select { results, .. }) => results,
+                        _ => panic!("Expected select results"),
+                    };
+                    let result_rows: Vec<String> = results
+                        .rows
+                        .iter()
+                        .map(|row| {
+                            row.iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(",")
+                        })
+                        .collect();
+                    assert_eq!(result_rows, rows, "{}", test.query);
+                }
+                (Err(err), Err(expected)) => {
+                    assert!(
+                        err.message().starts_with(expected),
+                        "{}: {}",
+                        test.query,
+                        err
+                    )
+                }
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_check_constraints() {
+        let tests =
+            vec![
+            (
+                "CREATE TABLE u (a INT CHECK (a > (SELECT 1)));",
+                ERR_SUBQUERY_IN_CHECK,
+            ),
+            (
+                "CREATE TABLE u (a INT CHECK (a = ANY (SELECT id FROM t)));",
+                ERR_SUBQUERY_IN_CHECK,
+            ),
+            (
+                "CREATE TABLE u (a INT CHECK (t.id > 1));",
+                "t: Check constraints can only use columns of their own table.",
+            ),
+            ("CREATE TABLE u (a INT CHECK (b > 1));", "b: Column does not exist."),
+            (
+                "CREATE TABLE u (a INT CHECK (sum(a) > 1));",
+                ERR_AGGREGATE_FUNCTION_IN_CHECK,
+            ),
+            (
+                "CREATE TABLE u (a INT, CONSTRAINT c CHECK (a > 1), CONSTRAINT c CHECK (a < 9));",
+                "Constraint \"c\" for relation \"u\" already exists.",
+            ),
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        for (query, expected) in tests {
+            match mb.eval_query(query) {
+                Err(err) => assert_eq!(err.to_string(), expected, "{}", query),
+                Ok(_) => panic!("{} should have failed", query),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod column_name_tests {
+    use super::*;
+
+    struct ColumnNameTest {
+        query: &'static str,
+        names: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_result_column_names() {
+        let tests = vec![
+            ColumnNameTest {
+                query: "SELECT * FROM a JOIN b ON a.id = b.a_id;",
+                names: vec!["a.id", "name", "b.id", "a_id", "title"],
+            },
+            ColumnNameTest {
+                query: "SELECT * FROM a AS x JOIN b AS y ON x.id = y.a_id;",
+                names: vec!["x.id", "name", "y.id", "a_id", "title"],
+            },
+            ColumnNameTest {
+                query: "SELECT b.id, a.id, title FROM a JOIN b ON a.id = b.a_id;",
+                names: vec!["b.id", "a.id", "title"],
+            },
+            // Only names that clash get qualified
+            ColumnNameTest {
+                query: "SELECT a.id, title FROM a JOIN b ON a.id = b.a_id;",
+                names: vec!["id", "title"],
+            },
+            // AS names win, the column they clash with gets qualified
+            ColumnNameTest {
+                query: "SELECT a.id AS id, b.id, title AS name FROM a JOIN b ON a.id = b.a_id;",
+                names: vec!["id", "b.id", "name"],
+            },
+            ColumnNameTest {
+                query: "SELECT id, id FROM a;",
+                names: vec!["id", "id"],
+            },
+            ColumnNameTest {
+                query: "SELECT upper(name), id + 1, id::TEXT, 1, length(name) AS len FROM a;",
+                names: vec!["upper", "?column?", "id", "?column?", "len"],
+            },
+            ColumnNameTest {
+                query: "SELECT count(*), sum(id) FROM a;",
+                names: vec!["count", "sum"],
+            },
+            ColumnNameTest {
+                query: "SELECT row_number() OVER (ORDER BY id) FROM a;",
+                names: vec!["row_number"],
+            },
+            ColumnNameTest {
+                query: "SELECT (SELECT name), (SELECT 1 AS one) FROM a;",
+                names: vec!["name", "one"],
+            },
+            ColumnNameTest {
+                query: "SELECT * FROM (SELECT name, id * 2 FROM a) AS s;",
+                names: vec!["name", "?column?"],
+            },
+            // Without rows the names are the same
+            ColumnNameTest {
+                query: "SELECT * FROM a JOIN b ON a.id = b.a_id WHERE a.id > 10;",
+                names: vec!["a.id", "name", "b.id", "a_id", "title"],
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE a (id INT, name TEXT);
+            CREATE TABLE b (id INT, a_id INT, title TEXT);
+            INSERT INTO a VALUES (1, 'x');
+            INSERT INTO b VALUES (10, 1, 't');",
+        )
+        .unwrap();
+
+        for test in tests {
+            let results = match mb.eval_query(test.query).expect(test.query).pop() {
+                Some(EvalResult::Select { results, .. }) => results,
+                _ => panic!("Expected select results for {}", test.query),
+            };
+            let names: Vec<&str> = results.columns.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, test.names, "{}", test.query);
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_code_tests {
+    use super::*;
+
+    struct ErrorCodeTest {
+        query: &'static str,
+        code: &'static str,
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let tests = vec![
+            ErrorCodeTest {
+                query: "SELECT id FRM t;",
+                code: "42601",
+            },
+            ErrorCodeTest {
+                query: "SELECT * FROM missing;",
+                code: "42P01",
+            },
+            ErrorCodeTest {
+                query: "SELECT missing FROM t;",
+                code: "42703",
+            },
+            ErrorCodeTest {
+                query: "SELECT nope(id) FROM t;",
+                code: "42883",
+            },
+            ErrorCodeTest {
+                query: "CREATE TABLE t (id INT);",
+                code: "42P07",
+            },
+            ErrorCodeTest {
+                query: "INSERT INTO t VALUES (1, 'x', 1);",
+                code: "23505",
+            },
+            ErrorCodeTest {
+                query: "INSERT INTO t VALUES (2, NULL, 1);",
+                code: "23502",
+            },
+            ErrorCodeTest {
+                query: "INSERT INTO t VALUES (2, 'x', -1);",
+                code: "23514",
+            },
+            ErrorCodeTest {
+                query: "SELECT 1 / 0;",
+                code: "22012",
+            },
+            ErrorCodeTest {
+                query: "SELECT abs((-32768)::SMALLINT);",
+                code: "22003",
+            },
+            ErrorCodeTest {
+                query: "SELECT 'x'::INT;",
+                code: "22P02",
+            },
+            ErrorCodeTest {
+                query: "INSERT INTO t VALUES ((SELECT id FROM t), 'z', 1);",
+                code: "21000",
+            },
+            ErrorCodeTest {
+                query: "SELECT id, count(*) FROM t;",
+                code: "42803",
+            },
+            ErrorCodeTest {
+                query: "SELECT id FROM t WHERE row_number() OVER () > 1;",
+                code: "42P20",
+            },
+            ErrorCodeTest {
+                query: "SELECT * FROM generate_series(1, 100);",
+                code: "54000",
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT NOT NULL, n INT CHECK (n > 0));
+            INSERT INTO t VALUES (1, 'x', 1);
+            INSERT INTO t VALUES (2, 'y', 2);",
+        )
+        .unwrap();
+        mb.set_result_limits(ResultLimits {
+            max_result_rows: Some(10),
+            max_result_bytes: None,
+        });
+
+        for test in tests {
+            match mb.eval_query(test.query) {
+                Err(err) => assert_eq!(err.code(), test.code, "{}: {}", test.query, err),
+                Ok(_) => panic!("{} should have failed", test.query),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_message_keeps_code() {
+        let err = BackendError::UniqueViolation("Duplicate".to_string())
+            .map_message(|msg| format!("Row 1: {}", msg));
+        assert_eq!(err.code(), "23505");
+        assert_eq!(err.to_string(), "Row 1: Duplicate");
+    }
+}
+
+#[cfg(test)]
+mod statement_split_tests {
+    use super::*;
+
+    fn count(mb: &mut MemoryBackend, query: &str) -> usize {
+        mb.eval_query(query).unwrap().len()
+    }
+
+    #[test]
+    fn test_empty_statements() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            ("", 0),
+            ("  \n\t", 0),
+            (";", 0),
+            ("-- just a comment\n", 0),
+            ("/* a; b */ ;; -- c", 0),
+            (";;  -- comment\nSELECT 1;", 1),
+            ("SELECT 1;;;", 1),
+            ("SELECT 1; ; SELECT ';'; -- done", 2),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(count(&mut mb, query), expected, "{:?}", query);
+        }
+        assert!(mb.eval_query("SELECT 1 SELECT 2;").is_err());
+    }
+
+    #[test]
+    fn test_failed_statement_is_located() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        let err = mb
+            .eval_query(
+                ";SELECT (1); -- first
+                INSERT INTO t VALUES (1);
+                SELECT nope FROM t;
+                INSERT INTO t VALUES (2);",
+            )
+            .unwrap_err();
+        assert_eq!(err.code(), "42703");
+        assert_eq!(
+            err.message(),
+            "Statement 3 (line 3, column 17 to line 3, column 34) failed: \
+            nope: Column does not exist.\nStatement: SELECT nope FROM t"
+        );
+        // The statements before the failed one ran, those after it did not
+        match mb.eval_query("SELECT id FROM t;").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => assert_eq!(results.rows.len(), 1),
+            _ => panic!("Expected select results"),
+        }
+
+        // A statement on its own is already known to be the one that failed
+        let err = mb.eval_query("SELECT nope FROM t;").unwrap_err();
+        assert_eq!(err.message(), "nope: Column does not exist.");
+
+        // So do statements that don't parse or lex, none of the query runs then
+        let err = mb
+            .eval_query("SELECT 1; SELECT 2; SELECT 1 +;")
+            .unwrap_err();
+        assert_eq!(err.code(), "42601");
+        assert_eq!(
+            err.message(),
+            format!(
+                "Statement 3 (line 1, column 21 to line 1, column 30) failed: [1, 30]: \
+                Expected right operand, got Plus\nSELECT 1; SELECT 2; SELECT 1 +;\n{}^\n\
+                Statement: SELECT 1 +",
+                " ".repeat(30)
+            )
+        );
+        let err = mb
+            .eval_query("SELECT 1;\nSELECT 2;\nSELECT 1 ? 2;")
+            .unwrap_err();
+        assert_eq!(err.code(), "42601");
+        assert_eq!(
+            err.message(),
+            "Statement 3 failed: Unable to lex token '?', at 3:10"
+        );
+    }
+}
+
+#[cfg(test)]
+mod literal_tests {
+    use super::*;
+
+    // The type and value of `SELECT literal`
+    fn literal(mb: &mut MemoryBackend, literal: &str) -> Result<(SqlType, String), BackendError> {
+        match mb.eval_query(&format!("SELECT {};", literal))?.pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                Ok((results.columns[0].col_type, results.rows[0][0].to_string()))
+            }
+            _ => panic!("Expected select results for {}", literal),
+        }
+    }
+
+    #[test]
+    fn test_numeric_literal_types() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            ("1", SqlType::Int, "1"),
+            ("2147483647", SqlType::Int, "2147483647"),
+            ("2147483648", SqlType::BigInt, "2147483648"),
+            ("-2147483648", SqlType::Int, "-2147483648"),
+            ("-2147483649", SqlType::BigInt, "-2147483649"),
+            ("3000000000", SqlType::BigInt, "3000000000"),
+            (
+                "9223372036854775807",
+                SqlType::BigInt,
+                "9223372036854775807",
+            ),
+            (
+                "-9223372036854775808",
+                SqlType::BigInt,
+                "-9223372036854775808",
+            ),
+            (
+                "9223372036854775808",
+                SqlType::DoublePrecision,
+                "9223372036854776000",
+            ),
+            ("1.5", SqlType::DoublePrecision, "1.5"),
+            ("1e3", SqlType::DoublePrecision, "1000"),
+            ("-(5)", SqlType::Int, "-5"),
+            // Whole numbers divide into whole numbers, anything else into a fraction
+            ("1 / 2", SqlType::Int, "0"),
+            ("7 / 2", SqlType::Int, "3"),
+            ("1 / 2.0", SqlType::DoublePrecision, "0.5"),
+            ("3000000000 / 2", SqlType::BigInt, "1500000000"),
+        ];
+        for (query, typ, value) in tests {
+            assert_eq!(
+                literal(&mut mb, query),
+                Ok((typ, value.to_string())),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_numeric_literal_out_of_range() {
+        let mut mb = MemoryBackend::new();
+        match literal(&mut mb, "1e400") {
+            Err(BackendError::NumericValueOutOfRange(msg)) => assert_eq!(
+                msg,
+                "Value \"1e400\" is out of range for type double precision."
+            ),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        // Also when it only comes up in a condition
+        mb.eval_query("CREATE TABLE t (id BIGINT); INSERT INTO t VALUES (1), (2);")
+            .unwrap();
+        match mb.eval_query("SELECT id FROM t WHERE id < 1e400;") {
+            Err(BackendError::NumericValueOutOfRange(_)) => {}
+            result => panic!("Expected an error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_pg_typeof() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            ("pg_typeof(1 + 1.5)", SqlType::Type, "Double Precision"),
+            ("pg_typeof(1 + 2::SMALLINT)", SqlType::Type, "Int"),
+            ("pg_typeof(lower('A'))", SqlType::Type, "Text"),
+            ("pg_typeof(1 > 2)", SqlType::Type, "Bool"),
+            // The type is the one worked out for the argument, even when its value is NULL
+            ("pg_typeof(NULL::BIGINT)", SqlType::Type, "Bigint"),
+            ("pg_typeof(NULL)", SqlType::Type, "Null"),
+            ("pg_typeof(pg_typeof(1))", SqlType::Type, "Type"),
+            ("pg_typeof(1)::TEXT", SqlType::Text, "Int"),
+            ("pg_typeof(1)::VARCHAR", SqlType::VarChar, "Int"),
+        ];
+        for (query, typ, value) in tests {
+            assert_eq!(
+                literal(&mut mb, query),
+                Ok((typ, value.to_string())),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(
+            literal(&mut mb, "pg_typeof(1, 2)").unwrap_err().code(),
+            "42883"
+        );
+
+        mb.eval_query(
+            "CREATE TABLE t (id BIGINT, name VARCHAR(10));
+            INSERT INTO t VALUES (1, 'a'), (2, NULL);",
+        )
+        .unwrap();
+        match mb
+            .eval_query(
+                "SELECT pg_typeof(name), pg_typeof(id * 2) FROM t
+                WHERE pg_typeof(id)::TEXT = 'Bigint';",
+            )
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                let rows: Vec<String> = results
+                    .rows
+                    .iter()
+                    .map(|row| format!("{}|{}", row[0], row[1]))
+                    .collect();
+                assert_eq!(rows, vec!["Varchar|Bigint", "Varchar|Bigint"]);
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_concat_casts() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            ("'user ' || 1", "user 1"),
+            ("2.5 || 'x'", "2.5x"),
+            ("'is ' || true || ', ' || false", "is true, false"),
+            ("'typed ' || pg_typeof(1)", "typed Int"),
+            ("'x' || 1::BIGINT || 'y'", "x1y"),
+            ("'x' || NULL", "NULL"),
+            ("NULL::INT || 'x'", "NULL"),
+        ];
+        for (query, value) in tests {
+            assert_eq!(
+                literal(&mut mb, query).map(|(_, value)| value),
+                Ok(value.to_string()),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(literal(&mut mb, "1 || 2").unwrap_err().code(), "42804");
+
+        mb.eval_query(
+            "CREATE TABLE users (id INT, name VARCHAR(10));
+            INSERT INTO users VALUES (1, 'ann'), (2, NULL);",
+        )
+        .unwrap();
+        match mb
+            .eval_query("SELECT 'user ' || id, name || id FROM users;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                let rows: Vec<String> = results
+                    .rows
+                    .iter()
+                    .map(|row| format!("{}|{}", row[0], row[1]))
+                    .collect();
+                assert_eq!(rows, vec!["user 1|ann1", "user 2|NULL"]);
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            (
+                "format('user %s has %s points', 'ann', 10)",
+                "user ann has 10 points",
+            ),
+            ("format('%s|%s|%s', NULL, true, 1.5)", "|true|1.5"),
+            ("format('%I.%I', 'users', 'Name')", "users.\"Name\""),
+            ("format('%I %I', 'select', 'a\"b')", "\"select\" \"a\"\"b\""),
+            (
+                "format('%L, %L, %L', 'it''s', 3, NULL)",
+                "'it''s', '3', NULL",
+            ),
+            ("format('100%% %s', 'done')", "100% done"),
+            ("format('no args')", "no args"),
+            ("format(NULL, 1)", "NULL"),
+        ];
+        for (query, value) in tests {
+            assert_eq!(
+                literal(&mut mb, query).map(|(_, value)| value),
+                Ok(value.to_string()),
+                "{}",
+                query
+            );
+        }
+
+        let errors = vec![
+            (
+                "format('%s and %s', 'one')",
+                "Too few arguments for format(), at position 8.",
+            ),
+            (
+                "format('%d', 1)",
+                "Unrecognized format() type specifier \"d\", at position 1.",
+            ),
+            (
+                "format('%I', NULL)",
+                "Null values cannot be formatted as an SQL identifier.",
+            ),
+        ];
+        for (query, msg) in errors {
+            match literal(&mut mb, query) {
+                Err(BackendError::InvalidParameterValue(err)) => assert_eq!(err, msg, "{}", query),
+                result => panic!("{}: expected an error, got {:?}", query, result),
+            }
+        }
+        assert_eq!(literal(&mut mb, "format()").unwrap_err().code(), "42883");
     }
 }
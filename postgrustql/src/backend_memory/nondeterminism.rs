@@ -0,0 +1,596 @@
+use super::{BackendConfig, MemoryBackend};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// A point in time, in microseconds since 1970-01-01 00:00:00 UTC
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Timestamp {
+    micros: i64,
+}
+
+impl Timestamp {
+    pub fn from_unix_micros(micros: i64) -> Self {
+        Timestamp { micros }
+    }
+
+    pub fn from_unix_secs(secs: i64) -> Self {
+        Timestamp {
+            micros: secs * 1_000_000,
+        }
+    }
+
+    pub fn unix_micros(&self) -> i64 {
+        self.micros
+    }
+
+    // The time the system clock gives, None where there is no clock to read
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn now() -> Option<Self> {
+        let since_epoch = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some(Timestamp {
+            micros: since_epoch.as_micros() as i64,
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn now() -> Option<Self> {
+        None
+    }
+}
+
+// Written the way Postgres shows a timestamp with time zone in UTC
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.micros.div_euclid(1_000_000);
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let secs_of_day = secs.rem_euclid(86400);
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}+00",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day % 3600 / 60,
+            secs_of_day % 60,
+            self.micros.rem_euclid(1_000_000)
+        )
+    }
+}
+
+// Year, month and day of a count of days since 1970-01-01, from Howard Hinnant's
+// date algorithms
+pub(super) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// The count of days since 1970-01-01 of a year, month and day, the inverse of
+// `civil_from_days`
+pub(super) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Where now() and the other timestamp functions read the time from in place of the
+// system clock, e.g. a clock a test moves forward by itself
+#[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> Timestamp + Send + Sync>);
+
+impl Clock {
+    pub fn new(clock: impl Fn() -> Timestamp + Send + Sync + 'static) -> Self {
+        Clock(Arc::new(clock))
+    }
+
+    // A clock that always reads `time`
+    pub fn fixed(time: Timestamp) -> Self {
+        Clock::new(move || time)
+    }
+
+    pub fn read(&self) -> Timestamp {
+        (self.0)()
+    }
+}
+
+// What statements are timed by in place of the monotonic clock of the system, giving the
+// time since any point it likes as long as it never goes back, e.g. one a test moves
+// forward by itself
+#[derive(Clone)]
+pub struct Timer(Arc<dyn Fn() -> Duration + Send + Sync>);
+
+impl Timer {
+    pub fn new(timer: impl Fn() -> Duration + Send + Sync + 'static) -> Self {
+        Timer(Arc::new(timer))
+    }
+
+    pub fn read(&self) -> Duration {
+        (self.0)()
+    }
+}
+
+// What gen_random_uuid() gives in place of random version 4 UUIDs
+#[derive(Clone)]
+pub struct UuidGenerator(Arc<dyn Fn() -> [u8; 16] + Send + Sync>);
+
+impl UuidGenerator {
+    pub fn new(generator: impl Fn() -> [u8; 16] + Send + Sync + 'static) -> Self {
+        UuidGenerator(Arc::new(generator))
+    }
+
+    pub fn generate(&self) -> [u8; 16] {
+        (self.0)()
+    }
+}
+
+// Hooks are the same when they are the same closure
+impl PartialEq for Clock {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Clock {}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Timer {}
+
+impl PartialEq for UuidGenerator {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for UuidGenerator {}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Timer")
+    }
+}
+
+impl fmt::Debug for UuidGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UuidGenerator")
+    }
+}
+
+// A splitmix64 generator, shared by the statements of a backend so each picks up where
+// the last one left off
+#[derive(Clone, Debug)]
+pub(super) struct SeededRng(Arc<AtomicU64>);
+
+impl SeededRng {
+    pub(super) fn new(seed: u64) -> Self {
+        SeededRng(Arc::new(AtomicU64::new(seed)))
+    }
+
+    pub(super) fn next(&self) -> u64 {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut z = self
+            .0
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl PartialEq for SeededRng {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.load(Ordering::Relaxed) == other.0.load(Ordering::Relaxed)
+    }
+}
+
+// Where the functions whose results aren't decided by their arguments get their values:
+// the time, random numbers and UUIDs. By default those are the system clock and
+// randomness std seeds on its own. The hooks of `BackendConfig` replace them, and in
+// deterministic mode every timestamp function gives the time the transaction started
+// and random numbers come from the seed, 0 when none was given. A workload then gives
+// the same results every time it runs.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct FunctionSources {
+    clock: Option<Clock>,
+    random_seed: Option<u64>,
+    uuid_generator: Option<UuidGenerator>,
+    pub(super) deterministic: bool,
+    rng: SeededRng,
+    // When the running transaction started, None outside of transactions
+    transaction_start: Option<Timestamp>,
+}
+
+impl FunctionSources {
+    pub(super) fn new(config: &BackendConfig) -> Self {
+        FunctionSources {
+            clock: config.clock.clone(),
+            random_seed: config.random_seed,
+            uuid_generator: config.uuid_generator.clone(),
+            deterministic: config.deterministic,
+            rng: SeededRng::new(config.random_seed.unwrap_or(0)),
+            transaction_start: None,
+        }
+    }
+
+    // The hooks, as `BackendConfig` holds them
+    pub(super) fn hooks(&self) -> (Option<Clock>, Option<u64>, Option<UuidGenerator>) {
+        (
+            self.clock.clone(),
+            self.random_seed,
+            self.uuid_generator.clone(),
+        )
+    }
+
+    fn read_clock(&self) -> Option<Timestamp> {
+        match &self.clock {
+            Some(clock) => Some(clock.read()),
+            None => Timestamp::now(),
+        }
+    }
+}
+
+// The sources of the statement running on this thread
+#[derive(Clone)]
+pub(super) struct StatementSources {
+    clock: Option<Clock>,
+    uuid_generator: Option<UuidGenerator>,
+    // Set when random numbers come from the seed
+    rng: Option<SeededRng>,
+    deterministic: bool,
+    transaction_start: Option<Timestamp>,
+    statement_start: Option<Timestamp>,
+}
+
+thread_local! {
+    static STATEMENT_SOURCES: RefCell<Option<StatementSources>> = const { RefCell::new(None) };
+}
+
+// Puts back the sources of the statement that was running before when dropped
+pub(super) struct StatementSourcesGuard {
+    outer: Option<StatementSources>,
+}
+
+impl Drop for StatementSourcesGuard {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        STATEMENT_SOURCES.with(|sources| *sources.borrow_mut() = outer);
+    }
+}
+
+impl MemoryBackend {
+    // Makes the sources of the backend those of the functions called on this thread until
+    // the guard is dropped. A statement outside of a transaction is a transaction of its
+    // own.
+    pub(super) fn enter_statement(&self) -> StatementSourcesGuard {
+        let sources = &self.sources;
+        let (transaction_start, statement_start) =
+            match (sources.transaction_start, sources.deterministic) {
+                (Some(start), true) => (Some(start), Some(start)),
+                (Some(start), false) => (Some(start), sources.read_clock()),
+                (None, _) => {
+                    let start = sources.read_clock();
+                    (start, start)
+                }
+            };
+        let statement = StatementSources {
+            clock: sources.clock.clone(),
+            uuid_generator: sources.uuid_generator.clone(),
+            rng: if sources.random_seed.is_some() || sources.deterministic {
+                Some(sources.rng.clone())
+            } else {
+                None
+            },
+            deterministic: sources.deterministic,
+            transaction_start,
+            statement_start,
+        };
+        enter_statement_sources(Some(statement))
+    }
+
+    // Notes the time a transaction starts at, unless it is nested in another. Returns
+    // whether it did, the transaction then has to call `end_transaction_time`.
+    pub(super) fn begin_transaction_time(&mut self) -> bool {
+        if self.sources.transaction_start.is_some() {
+            return false;
+        }
+        self.sources.transaction_start = self.sources.read_clock();
+        true
+    }
+
+    pub(super) fn end_transaction_time(&mut self) {
+        self.sources.transaction_start = None;
+    }
+}
+
+// The sources of the statement running on this thread, for the threads of a parallel scan
+// to evaluate its expressions with
+#[cfg(feature = "parallel")]
+pub(super) fn statement_sources() -> Option<StatementSources> {
+    STATEMENT_SOURCES.with(|sources| sources.borrow().clone())
+}
+
+// Makes `sources` those of the functions called on this thread until the guard is dropped
+pub(super) fn enter_statement_sources(sources: Option<StatementSources>) -> StatementSourcesGuard {
+    let outer = STATEMENT_SOURCES.with(|current| current.replace(sources));
+    StatementSourcesGuard { outer }
+}
+
+// Runs `f` on the sources of the running statement. Without one, outside of statements,
+// the system clock and std's randomness are used.
+fn with_sources<T>(f: impl FnOnce(&StatementSources) -> T) -> T {
+    STATEMENT_SOURCES.with(|sources| match &*sources.borrow() {
+        Some(sources) => f(sources),
+        None => {
+            let now = Timestamp::now();
+            f(&StatementSources {
+                clock: None,
+                uuid_generator: None,
+                rng: None,
+                deterministic: false,
+                transaction_start: now,
+                statement_start: now,
+            })
+        }
+    })
+}
+
+// The time the transaction started at, what now() gives
+pub(super) fn transaction_timestamp() -> Option<Timestamp> {
+    with_sources(|sources| sources.transaction_start)
+}
+
+// The time the statement started at
+pub(super) fn statement_timestamp() -> Option<Timestamp> {
+    with_sources(|sources| sources.statement_start)
+}
+
+// The time it is, changing even within a statement, unless in deterministic mode
+pub(super) fn clock_timestamp() -> Option<Timestamp> {
+    with_sources(|sources| match (&sources.clock, sources.deterministic) {
+        (_, true) => sources.transaction_start,
+        (Some(clock), false) => Some(clock.read()),
+        (None, false) => Timestamp::now(),
+    })
+}
+
+pub(super) fn random_u64() -> u64 {
+    with_sources(|sources| match &sources.rng {
+        Some(rng) => rng.next(),
+        None => unseeded_u64(),
+    })
+}
+
+// The bytes of a UUID, of version 4 unless a generator gave them
+pub(super) fn random_uuid() -> [u8; 16] {
+    if let Some(generator) = with_sources(|sources| sources.uuid_generator.clone()) {
+        return generator.generate();
+    }
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        chunk.copy_from_slice(&random_u64().to_be_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    bytes
+}
+
+// The bits come from std's randomly keyed hasher fed with a counter, so no two calls
+// give the same, though they are not fit for cryptography
+pub(super) fn unseeded_u64() -> u64 {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(call);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod nondeterminism_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend_with, rows};
+    use std::sync::atomic::AtomicI64;
+
+    // Every row of every table, in the order they went in
+    fn dump(mb: &mut MemoryBackend) -> String {
+        let mut names: Vec<String> = mb.storage.table_names();
+        names.sort();
+        let mut dump = String::new();
+        for name in names {
+            dump.push_str(&format!("{}:\n", name));
+            for row in rows(mb, &format!("SELECT * FROM {};", name)) {
+                dump.push_str(&row);
+                dump.push('\n');
+            }
+        }
+        dump
+    }
+
+    #[test]
+    fn test_timestamp_display() {
+        let tests = vec![
+            (0, "1970-01-01 00:00:00.000000+00"),
+            (1_614_834_367_000_008, "2021-03-04 05:06:07.000008+00"),
+            (951_782_400_000_000, "2000-02-29 00:00:00.000000+00"),
+            (-1, "1969-12-31 23:59:59.999999+00"),
+        ];
+        for (micros, expected) in tests {
+            assert_eq!(Timestamp::from_unix_micros(micros).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_clock_hook() {
+        // A clock the test moves forward by hand
+        let time = Arc::new(AtomicI64::new(1_000_000_000));
+        let clock_time = time.clone();
+        let mut mb = MemoryBackend::with_config(
+            BackendConfig::new()
+                .clock(move || Timestamp::from_unix_secs(clock_time.load(Ordering::SeqCst))),
+        );
+
+        let first = rows(&mut mb, "SELECT now(), transaction_timestamp();");
+        assert_eq!(
+            first,
+            vec!["2001-09-09 01:46:40.000000+00|2001-09-09 01:46:40.000000+00"]
+        );
+        time.store(2_000_000_000, Ordering::SeqCst);
+        let later = rows(
+            &mut mb,
+            "SELECT now(), statement_timestamp(), clock_timestamp();",
+        );
+        assert_eq!(
+            later,
+            vec![
+                "2033-05-18 03:33:20.000000+00|2033-05-18 03:33:20.000000+00|2033-05-18 03:33:20.000000+00"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transaction_timestamps() {
+        let time = Arc::new(AtomicI64::new(0));
+        let clock_time = time.clone();
+        // Every reading of the clock moves it a second forward
+        let config = BackendConfig::new()
+            .clock(move || Timestamp::from_unix_secs(clock_time.fetch_add(1, Ordering::SeqCst)));
+
+        let mut mb = MemoryBackend::with_config(config.clone());
+        let results = mb
+            .transaction(|mb| {
+                let first = rows(mb, "SELECT now(), statement_timestamp();");
+                let second = rows(mb, "SELECT now(), statement_timestamp();");
+                Ok::<_, ()>(vec![first, second])
+            })
+            .unwrap();
+        // now() stays at the start of the transaction, statements start later on
+        assert_eq!(
+            results,
+            vec![
+                vec!["1970-01-01 00:00:00.000000+00|1970-01-01 00:00:01.000000+00"],
+                vec!["1970-01-01 00:00:00.000000+00|1970-01-01 00:00:02.000000+00"],
+            ]
+        );
+
+        // In deterministic mode the clock is only read when the transaction starts
+        time.store(0, Ordering::SeqCst);
+        let mut mb = MemoryBackend::with_config(config.deterministic(true));
+        let results = mb
+            .transaction(|mb| {
+                Ok::<_, ()>(rows(
+                    mb,
+                    "SELECT now(), statement_timestamp(), clock_timestamp(), clock_timestamp();",
+                ))
+            })
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![["1970-01-01 00:00:00.000000+00"; 4].join("|")]
+        );
+    }
+
+    #[test]
+    fn test_seeded_functions() {
+        let config = BackendConfig::new().random_seed(42);
+        let mut first = MemoryBackend::with_config(config.clone());
+        let mut second = MemoryBackend::with_config(config);
+        let query = "SELECT random(), gen_random_uuid(), random();";
+        let first_rows = rows(&mut first, query);
+        assert_eq!(first_rows, rows(&mut second, query));
+        assert_ne!(first_rows, rows(&mut first, query));
+
+        let random = rows(&mut first, "SELECT random();")[0]
+            .parse::<f64>()
+            .unwrap();
+        assert!(random >= 0.0 && random < 1.0);
+
+        // Without a seed no two backends agree
+        let mut first = MemoryBackend::new();
+        let mut second = MemoryBackend::new();
+        assert_ne!(rows(&mut first, query), rows(&mut second, query));
+    }
+
+    #[test]
+    fn test_uuid_generator() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let generator_calls = calls.clone();
+        let mut mb = MemoryBackend::with_config(BackendConfig::new().uuid_generator(move || {
+            let mut bytes = [0u8; 16];
+            bytes[15] = generator_calls.fetch_add(1, Ordering::SeqCst) as u8;
+            bytes
+        }));
+        assert_eq!(
+            rows(&mut mb, "SELECT gen_random_uuid(), gen_random_uuid();"),
+            vec!["00000000-0000-0000-0000-000000000000|00000000-0000-0000-0000-000000000001"]
+        );
+    }
+
+    #[test]
+    fn test_deterministic_setting() {
+        let mut mb = MemoryBackend::new();
+        assert_eq!(
+            rows(&mut mb, "SHOW postgrustql.deterministic;"),
+            vec!["off"]
+        );
+        mb.eval_query("SET postgrustql.deterministic = on;")
+            .unwrap();
+        assert!(mb.config().deterministic);
+        let query = "SELECT random(), gen_random_uuid();";
+        let seeded = rows(&mut mb, query);
+        // Without a seed, deterministic mode seeds with 0
+        let mut other = MemoryBackend::with_config(BackendConfig::new().random_seed(0));
+        assert_eq!(seeded, rows(&mut other, query));
+
+        mb.eval_query("RESET postgrustql.deterministic;").unwrap();
+        assert!(!mb.config().deterministic);
+    }
+
+    #[test]
+    fn test_seeded_workload_dumps() {
+        let workload = "CREATE TABLE events (id TEXT, at TEXT, score DOUBLE PRECISION);
+            INSERT INTO events VALUES (gen_random_uuid(), now(), random());
+            INSERT INTO events VALUES (gen_random_uuid(), clock_timestamp(), random());
+            CREATE TABLE copies AS SELECT id, statement_timestamp(), random() * 10 FROM events;
+            INSERT INTO events VALUES (gen_random_uuid(), now(), random());";
+        let run = || {
+            let config = BackendConfig::new()
+                .clock(|| Timestamp::from_unix_secs(1_600_000_000))
+                .random_seed(7)
+                .deterministic(true);
+            dump(&mut backend_with(config, workload))
+        };
+
+        let dump = run();
+        assert_eq!(dump.as_bytes(), run().as_bytes());
+        assert!(dump.contains("2020-09-13 12:26:40.000000+00"));
+    }
+}
@@ -0,0 +1,258 @@
+use super::MemoryBackend;
+use crate::ast::{ListenStatement, NotifyStatement, UnlistenStatement};
+use crate::backend::BackendError;
+use std::collections::{HashMap, HashSet};
+
+// Payloads must be shorter than this many bytes, as in Postgres
+pub const MAX_NOTIFY_PAYLOAD_BYTES: usize = 8000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Notification {
+    pub channel: String,
+    pub payload: String,
+    // The session that sent it, see `Connection::session_id`
+    pub sender: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PendingAction {
+    Listen(String),
+    // None for every channel
+    Unlisten(Option<String>),
+    Notify(Notification),
+}
+
+// Channels listened to and notifications waiting to be polled, for every session of a
+// backend. Session 0 is that of whoever uses the backend directly rather than through a
+// `Connection`. Within transactions LISTEN, UNLISTEN and NOTIFY only take effect once
+// the outermost one succeeds.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Notifications {
+    // The session running statements
+    session: u64,
+    last_session: u64,
+    channels: HashMap<u64, HashSet<String>>,
+    queues: HashMap<u64, Vec<Notification>>,
+    pending: Vec<PendingAction>,
+    // Transactions running, actions are applied when the outermost one ends
+    depth: usize,
+}
+
+impl Notifications {
+    pub(super) fn open_session(&mut self) -> u64 {
+        self.last_session += 1;
+        self.last_session
+    }
+
+    // Stops listening for the session and drops whatever it didn't poll
+    pub(super) fn close_session(&mut self, session: u64) {
+        self.channels.remove(&session);
+        self.queues.remove(&session);
+    }
+
+    // Makes `session` the one running statements, handing back the one that was
+    pub(super) fn swap_session(&mut self, session: &mut u64) {
+        std::mem::swap(&mut self.session, session);
+    }
+
+    pub(super) fn take(&mut self, session: u64) -> Vec<Notification> {
+        self.queues.remove(&session).unwrap_or_default()
+    }
+
+    pub(super) fn begin(&mut self) -> usize {
+        self.depth += 1;
+        self.pending.len()
+    }
+
+    pub(super) fn end(&mut self, start: usize, succeeded: bool) {
+        self.depth -= 1;
+        if !succeeded {
+            self.pending.truncate(start);
+        }
+        if self.depth == 0 {
+            self.apply();
+        }
+    }
+
+    fn push(&mut self, action: PendingAction) {
+        // Like Postgres, the same notification sent twice in a transaction goes out once
+        if let PendingAction::Notify(_) = action {
+            if self.pending.contains(&action) {
+                return;
+            }
+        }
+        self.pending.push(action);
+        if self.depth == 0 {
+            self.apply();
+        }
+    }
+
+    // Listening changes come first, so a session that starts listening to a channel
+    // in the transaction that notifies it gets the notification as well
+    fn apply(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        let session = self.session;
+        let mut notifications = vec![];
+        for action in pending {
+            match action {
+                PendingAction::Listen(channel) => {
+                    self.channels.entry(session).or_default().insert(channel);
+                }
+                PendingAction::Unlisten(Some(channel)) => {
+                    if let Some(channels) = self.channels.get_mut(&session) {
+                        channels.remove(&channel);
+                    }
+                }
+                PendingAction::Unlisten(None) => {
+                    self.channels.remove(&session);
+                }
+                PendingAction::Notify(notification) => notifications.push(notification),
+            }
+        }
+
+        for notification in notifications {
+            for (listener, channels) in &self.channels {
+                if channels.contains(&notification.channel) {
+                    self.queues
+                        .entry(*listener)
+                        .or_default()
+                        .push(notification.clone());
+                }
+            }
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub(super) fn listen(&mut self, statement: ListenStatement) {
+        self.notifications
+            .push(PendingAction::Listen(statement.channel));
+    }
+
+    pub(super) fn unlisten(&mut self, statement: UnlistenStatement) {
+        self.notifications
+            .push(PendingAction::Unlisten(statement.channel));
+    }
+
+    pub(super) fn notify(&mut self, statement: NotifyStatement) -> Result<(), BackendError> {
+        let payload = statement.payload.unwrap_or_default();
+        if payload.len() >= MAX_NOTIFY_PAYLOAD_BYTES {
+            return Err(BackendError::InvalidParameterValue(
+                "Payload string too long.".to_string(),
+            ));
+        }
+        let sender = self.notifications.session;
+        self.notifications.push(PendingAction::Notify(Notification {
+            channel: statement.channel,
+            payload,
+            sender,
+        }));
+        Ok(())
+    }
+
+    // Notifications on the channels the backend's own session listens to, oldest first
+    pub fn poll_notifications(&mut self) -> Vec<Notification> {
+        let session = self.notifications.session;
+        self.notifications.take(session)
+    }
+}
+
+#[cfg(test)]
+mod notify_tests {
+    use super::*;
+    use crate::backend_memory::Connection;
+    use std::sync::{Arc, Mutex};
+
+    fn notification(channel: &str, payload: &str, sender: u64) -> Notification {
+        Notification {
+            channel: channel.to_string(),
+            payload: payload.to_string(),
+            sender,
+        }
+    }
+
+    #[test]
+    fn test_notify_other_connection() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut listener = Connection::open(backend.clone());
+        let mut sender = Connection::open(backend.clone());
+        let sender_id = sender.session_id();
+
+        listener.eval_query("LISTEN jobs;").unwrap();
+        sender
+            .eval_query("NOTIFY jobs, 'first'; NOTIFY other, 'ignored'; NOTIFY jobs;")
+            .unwrap();
+        assert_eq!(
+            listener.poll_notifications(),
+            vec![
+                notification("jobs", "first", sender_id),
+                notification("jobs", "", sender_id),
+            ]
+        );
+        assert_eq!(listener.poll_notifications(), vec![]);
+        assert_eq!(sender.poll_notifications(), vec![]);
+
+        // Notifications wait for the transaction to commit
+        let sent = sender.with_session(|backend| {
+            backend.transaction(|backend| {
+                backend.eval_query("NOTIFY jobs, 'a'; NOTIFY jobs, 'a'; NOTIFY jobs, 'b';")?;
+                Ok(backend.poll_notifications())
+            })
+        });
+        assert_eq!(sent, Ok(vec![]));
+        assert_eq!(
+            listener.poll_notifications(),
+            vec![
+                notification("jobs", "a", sender_id),
+                notification("jobs", "b", sender_id),
+            ]
+        );
+
+        // And are dropped when it rolls back
+        let failed = sender.with_session(|backend| {
+            backend.transaction(|backend| {
+                backend.eval_query("NOTIFY jobs, 'c';")?;
+                backend.eval_query("SELECT * FROM missing;")
+            })
+        });
+        assert!(failed.is_err());
+        assert_eq!(listener.poll_notifications(), vec![]);
+
+        listener.eval_query("UNLISTEN jobs;").unwrap();
+        sender.eval_query("NOTIFY jobs, 'd';").unwrap();
+        assert_eq!(listener.poll_notifications(), vec![]);
+    }
+
+    #[test]
+    fn test_notify_self() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("LISTEN a; LISTEN b; NOTIFY a, 'x'; NOTIFY b, 'y';")
+            .unwrap();
+        assert_eq!(
+            mb.poll_notifications(),
+            vec![notification("a", "x", 0), notification("b", "y", 0)]
+        );
+
+        mb.eval_query("UNLISTEN *; NOTIFY a, 'x';").unwrap();
+        assert_eq!(mb.poll_notifications(), vec![]);
+
+        let payload = "p".repeat(MAX_NOTIFY_PAYLOAD_BYTES);
+        let err = mb
+            .eval_query(&format!("NOTIFY a, '{}';", payload))
+            .unwrap_err();
+        assert_eq!(err.code(), "22023");
+    }
+
+    #[test]
+    fn test_closed_connection_stops_listening() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut listener = Connection::open(backend.clone());
+        let session = listener.session_id();
+        listener.eval_query("LISTEN jobs;").unwrap();
+        drop(listener);
+
+        let mut mb = backend.lock().unwrap();
+        mb.eval_query("NOTIFY jobs, 'x';").unwrap();
+        assert_eq!(mb.notifications.take(session), vec![]);
+    }
+}
@@ -0,0 +1,396 @@
+use rayon::prelude::*;
+
+use super::catalog::{enter_visible_relations, visible_relations, VisibleRelationsGuard};
+use super::division::{enter_division_by_zero, DivisionByZero, DivisionByZeroGuard};
+use super::nondeterminism::{
+    enter_statement_sources, statement_sources, StatementSources, StatementSourcesGuard,
+};
+use super::stored_row::StoredRow;
+use super::table_size::{
+    enter_statement_sizes, statement_sizes, RelationSizesGuard, StatementSizes,
+};
+use super::warnings::{enter_warnings, take_warnings, warn, WarningsGuard};
+use super::{Aggregation, CompiledExpression, Groups, MemoryBackend, Table};
+use crate::backend::{BackendError, MemoryCellData};
+use crate::sql_types::SqlValue;
+use std::collections::HashMap;
+
+// Tables smaller than this are scanned on the calling thread, splitting them up would
+// cost more than it saves
+pub const PARALLEL_SCAN_MIN_ROWS: usize = 8192;
+// Smallest number of rows a thread is handed at once
+const SCAN_CHUNK_ROWS: usize = 2048;
+
+// Scans share the table and the compiled expressions between threads
+const _: fn() = || {
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<Table>();
+    assert_sync::<CompiledExpression>();
+};
+
+// What the statement running on the calling thread made known to the expressions it
+// evaluates, for the threads of the pool to evaluate them the same way. The sequences and
+// the monitor are left out, only volatile functions use them and those are never called
+// on other threads.
+#[derive(Clone)]
+struct StatementContext {
+    division_by_zero: DivisionByZero,
+    sizes: StatementSizes,
+    visible_relations: Option<HashMap<i64, bool>>,
+    sources: Option<StatementSources>,
+}
+
+// Puts back what the thread knew before when dropped, and collects the warnings raised
+// until then
+struct StatementContextGuard {
+    _division_by_zero: DivisionByZeroGuard,
+    _sizes: RelationSizesGuard,
+    _visible_relations: VisibleRelationsGuard,
+    _sources: StatementSourcesGuard,
+    _warnings: WarningsGuard,
+}
+
+impl StatementContext {
+    fn enter(&self) -> StatementContextGuard {
+        StatementContextGuard {
+            _division_by_zero: enter_division_by_zero(self.division_by_zero),
+            _sizes: enter_statement_sizes(self.sizes.clone()),
+            _visible_relations: enter_visible_relations(self.visible_relations.clone()),
+            _sources: enter_statement_sources(self.sources.clone()),
+            _warnings: enter_warnings(),
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Parallel scans are on by default, this allows turning them off, e.g. to leave the
+    // thread pool to something else
+    pub fn set_parallel_scans(&mut self, enabled: bool) {
+        self.parallel_scans = enabled;
+    }
+
+    // Rows written by a transaction that is still running are always scanned serially
+    pub(super) fn scans_in_parallel(&self, table_name: &str, rows: usize) -> bool {
+        self.parallel_scans
+            && rows >= PARALLEL_SCAN_MIN_ROWS
+            && !self.has_pending_writes(table_name)
+    }
+
+    // Evaluates `condition` for every row on the rayon thread pool. Errors are returned
+    // per row, so they surface in the same order as in a serial scan. Conditions calling
+    // volatile functions are left to the calling thread, which has the sources of the
+    // statement and calls them in row order. The threads evaluate it in the context of
+    // the statement, and what they warn about goes to the statement.
+    pub(super) fn filter_in_parallel(
+        &self,
+        table_name: &str,
+        rows: &[StoredRow],
+        condition: &CompiledExpression,
+    ) -> Option<Vec<Result<bool, BackendError>>> {
+        if !self.scans_in_parallel(table_name, rows.len()) || condition.is_volatile() {
+            return None;
+        }
+        let context = self.statement_context();
+        let results: Vec<_> = rows
+            .par_iter()
+            .with_min_len(SCAN_CHUNK_ROWS)
+            .map_init(
+                || context.enter(),
+                |_, row| (condition.is_true(row), take_warnings()),
+            )
+            .collect();
+        Some(
+            results
+                .into_iter()
+                .map(|(result, warnings)| {
+                    warnings.into_iter().for_each(warn);
+                    result
+                })
+                .collect(),
+        )
+    }
+
+    // Groups the rows that pass `where_clause` a chunk at a time on the rayon thread
+    // pool, each chunk into groups of its own. Those are merged in the order of the
+    // chunks, so the groups come out in the order of their first rows like in a serial
+    // scan, and so do errors and warnings.
+    pub(super) fn group_rows(
+        &self,
+        table_name: &str,
+        aggregation: &Aggregation,
+        rows: &[StoredRow],
+        where_clause: &Option<CompiledExpression>,
+    ) -> Result<Groups, BackendError> {
+        let is_divisible = aggregation.is_divisible()
+            && where_clause
+                .as_ref()
+                .is_none_or(|where_clause| !where_clause.is_volatile());
+        if !self.scans_in_parallel(table_name, rows.len()) || !is_divisible {
+            return aggregation.groups(rows, where_clause);
+        }
+        let context = self.statement_context();
+        let chunks: Vec<_> = rows
+            .par_chunks(SCAN_CHUNK_ROWS)
+            .map_init(
+                || context.enter(),
+                |_, chunk| (aggregation.groups(chunk, where_clause), take_warnings()),
+            )
+            .collect();
+        let mut merged: Option<Groups> = None;
+        for (groups, warnings) in chunks {
+            warnings.into_iter().for_each(warn);
+            let groups = groups?;
+            match merged.as_mut() {
+                Some(merged) => merged.merge(groups)?,
+                None => merged = Some(groups),
+            }
+        }
+        match merged {
+            Some(merged) => Ok(merged),
+            None => aggregation.groups(&[], where_clause),
+        }
+    }
+
+    fn statement_context(&self) -> StatementContext {
+        StatementContext {
+            division_by_zero: self.division_by_zero,
+            sizes: statement_sizes(),
+            visible_relations: visible_relations(),
+            sources: statement_sources(),
+        }
+    }
+
+    // Both sorts are stable, so the output order is the same either way
+    pub(super) fn sort_rows(&self, table_name: &str, rows: &mut [(MemoryCellData, Vec<SqlValue>)]) {
+        if self.scans_in_parallel(table_name, rows.len()) {
+            rows.par_sort_by(|a, b| a.0.cmp(&b.0));
+        } else {
+            rows.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod parallel_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{rows, try_rows};
+    use crate::sql_types::{SqlNumeric, SqlText};
+
+    // Small xorshift generator, the data only needs to look random and be reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, max: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % max
+        }
+    }
+
+    fn backend(seed: u64) -> MemoryBackend {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE people (id INT, name TEXT, age INT, score DOUBLE PRECISION);")
+            .unwrap();
+        let mut rng = Rng(seed);
+        let insert = mb
+            .prepare("INSERT INTO people VALUES ($1, $2, $3, $4);")
+            .unwrap();
+        insert
+            .execute_many(
+                &mut mb,
+                (0..PARALLEL_SCAN_MIN_ROWS as i32 * 3).map(|i| {
+                    let age = match rng.next(20) {
+                        0 => SqlValue::Null,
+                        _ => SqlValue::Numeric(SqlNumeric::Int {
+                            value: rng.next(90) as i32,
+                        }),
+                    };
+                    vec![
+                        SqlValue::Numeric(SqlNumeric::Int { value: i }),
+                        SqlValue::Text(SqlText::Text {
+                            value: format!("name{}", rng.next(500)),
+                        }),
+                        age,
+                        SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                            value: rng.next(10000) as f64 / 100.0,
+                        }),
+                    ]
+                }),
+            )
+            .unwrap();
+        mb
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let queries = vec![
+            "SELECT id, name FROM people WHERE age >= 18 AND age < 65 AND score > 25.5;",
+            "SELECT * FROM people WHERE name = 'name7' OR age = NULL;",
+            "SELECT id, age FROM people WHERE score < 50.0 ORDER BY age;",
+            "SELECT id, age FROM people WHERE score < 50.0 ORDER BY age DESC;",
+            "SELECT name FROM people WHERE id % 7 = 0 ORDER BY score LIMIT 100 OFFSET 20;",
+            "SELECT DISTINCT age FROM people WHERE score > 10.0 ORDER BY age;",
+            "SELECT id FROM people ORDER BY name DESC;",
+            "SELECT id FROM people WHERE name + 1 = 2;",
+            "SELECT id FROM people WHERE missing = 2;",
+        ];
+
+        for seed in &[7, 1234, 987654321] {
+            let mut mb = backend(*seed);
+            for query_text in &queries {
+                mb.set_parallel_scans(false);
+                let serial = try_rows(&mut mb, query_text);
+                mb.set_parallel_scans(true);
+                let parallel = try_rows(&mut mb, query_text);
+                assert_eq!(parallel, serial, "{} with seed {}", query_text, seed);
+            }
+        }
+    }
+
+    // Sums and averages are over integers, those of floating point values could come out
+    // different in the last digits
+    #[test]
+    fn test_parallel_aggregates_match_serial() {
+        let queries = vec![
+            "SELECT count(*), count(age), sum(age), avg(age), min(score), max(name) FROM people;",
+            "SELECT age, count(*), sum(id), min(name), max(score) FROM people GROUP BY age;",
+            "SELECT name, count(*) FROM people WHERE score > 50.0 GROUP BY name
+                HAVING count(*) > 20 ORDER BY count(*) DESC, name;",
+            "SELECT age % 3, avg(id), count(*) FILTER (WHERE score < 10.0) FROM people
+                GROUP BY age % 3;",
+            "SELECT count(*), max(id) FROM people WHERE id < 0;",
+            "SELECT count(DISTINCT name) FROM people;",
+            "SELECT sum(name) FROM people;",
+            "SELECT age, sum(id / age) FROM people GROUP BY age;",
+        ];
+
+        for seed in &[7, 1234, 987654321] {
+            let mut mb = backend(*seed);
+            for query_text in &queries {
+                mb.set_parallel_scans(false);
+                let serial = try_rows(&mut mb, query_text);
+                mb.set_parallel_scans(true);
+                let parallel = try_rows(&mut mb, query_text);
+                assert_eq!(parallel, serial, "{} with seed {}", query_text, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_division_by_zero() {
+        let mut mb = backend(7);
+        let query_text = "SELECT id FROM people WHERE id % age = 1;";
+        assert!(try_rows(&mut mb, query_text).is_err());
+
+        mb.eval_query("SET postgrustql.division_by_zero = 'null';")
+            .unwrap();
+        mb.set_parallel_scans(false);
+        let serial = rows(&mut mb, query_text);
+        mb.set_parallel_scans(true);
+        assert_eq!(rows(&mut mb, query_text), serial);
+        assert!(!serial.is_empty());
+    }
+
+    #[test]
+    fn test_parallel_warnings() {
+        let mut mb = backend(7);
+        let query_text = "SELECT id FROM people WHERE score::INT = 50;";
+        for parallel in [false, true] {
+            mb.set_parallel_scans(parallel);
+            let warnings: Vec<String> = mb
+                .eval_query(query_text)
+                .unwrap()
+                .iter()
+                .flat_map(EvalResult::warnings)
+                .map(|warning| warning.to_string())
+                .collect();
+            assert_eq!(
+                warnings,
+                vec!["Casting Double Precision to Int dropped the fractional part."]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_relation_sizes() {
+        let mut mb = backend(7);
+        mb.eval_query(
+            "CREATE TABLE big AS SELECT 'people' AS name, n AS id
+                FROM generate_series(1, 20000) AS g(n);",
+        )
+        .unwrap();
+        let queries = vec![
+            "SELECT count(*) FROM big WHERE table_rows(name) = 24576;",
+            "SELECT id FROM big WHERE table_bytes(name) > 0 AND id % 1000 = 0;",
+            "SELECT count(*), sum(table_rows(name) - id) FROM big
+                WHERE index_bytes(name) = 0;",
+            "SELECT age, count(*) FROM people WHERE table_rows('big') = 20000 GROUP BY age;",
+        ];
+        for query_text in &queries {
+            mb.set_parallel_scans(false);
+            let serial = rows(&mut mb, query_text);
+            mb.set_parallel_scans(true);
+            let parallel = rows(&mut mb, query_text);
+            assert_eq!(parallel, serial, "{}", query_text);
+            assert_ne!(serial, vec!["0"]);
+        }
+    }
+
+    // pg_table_is_visible gives on every thread what it gives on the calling one.
+    // Their arguments depend on the row, so that they aren't folded before the scan.
+    #[test]
+    fn test_parallel_visibility() {
+        let mut mb = backend(7);
+        let oid = match rows(
+            &mut mb,
+            "SELECT oid FROM pg_class WHERE relname = 'people';",
+        )
+        .as_slice()
+        {
+            [oid] => oid.clone(),
+            _ => panic!("Expected the OID of people"),
+        };
+        let queries = vec![
+            format!(
+                "SELECT id FROM people WHERE pg_table_is_visible(id - id + {}) AND id % 1000 = 0;",
+                oid
+            ),
+            format!(
+                "SELECT age, count(*) FROM people WHERE pg_table_is_visible(id - id + {})
+                    GROUP BY age;",
+                oid
+            ),
+        ];
+        for query_text in &queries {
+            mb.set_parallel_scans(false);
+            let serial = rows(&mut mb, query_text);
+            mb.set_parallel_scans(true);
+            let parallel = rows(&mut mb, query_text);
+            assert_eq!(parallel, serial, "{}", query_text);
+            assert!(!serial.is_empty(), "{}", query_text);
+        }
+    }
+
+    #[test]
+    fn test_pending_writes_scan_serially() {
+        let mut mb = backend(42);
+        assert!(mb.scans_in_parallel("people", PARALLEL_SCAN_MIN_ROWS));
+
+        mb.transaction(|mb| -> Result<(), BackendError> {
+            assert!(mb.scans_in_parallel("people", PARALLEL_SCAN_MIN_ROWS));
+            mb.eval_query("INSERT INTO people VALUES (100000, 'new', 20, 1.5);")?;
+            assert!(!mb.scans_in_parallel("people", PARALLEL_SCAN_MIN_ROWS));
+
+            assert_eq!(
+                try_rows(mb, "SELECT id FROM people WHERE id > 99999;")?,
+                vec!["100000"]
+            );
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(mb.scans_in_parallel("people", PARALLEL_SCAN_MIN_ROWS));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::backend::BackendError;
+use crate::lexer::Token;
+use crate::sql_types::{SqlText, SqlValue};
+use regex::{Regex, RegexBuilder};
+use std::cell::RefCell;
+
+// Patterns compiled last on this thread. A WHERE clause matches every row against the
+// same few of them, so they are only compiled once per query.
+const CACHED_PATTERNS: usize = 32;
+
+thread_local! {
+    static PATTERNS: RefCell<Vec<(String, bool, Regex)>> = const { RefCell::new(Vec::new()) };
+}
+
+// `value ~ pattern` and its case insensitive and negated forms, and `value SIMILAR TO
+// pattern`. Either being NULL is left to the caller. A pattern that doesn't compile is
+// an error when it is first matched against.
+pub(super) fn apply_match_operator(
+    operand: &Token,
+    value: &SqlValue,
+    pattern: &SqlValue,
+) -> Result<SqlValue, BackendError> {
+    let (text, pattern) = match (value, pattern) {
+        (SqlValue::Text(text), SqlValue::Text(pattern)) => (text_of(text), text_of(pattern)),
+        _ => {
+            return Err(BackendError::UndefinedFunction(format!(
+                "Operator does not exist: {} {} {}.",
+                value.get_type(),
+                operand.generate_code(),
+                pattern.get_type()
+            )))
+        }
+    };
+    let (regex, case_insensitive, negated) = match operand {
+        Token::RegexMatch => (pattern.to_string(), false, false),
+        Token::RegexMatchInsensitive => (pattern.to_string(), true, false),
+        Token::RegexNotMatch => (pattern.to_string(), false, true),
+        Token::RegexNotMatchInsensitive => (pattern.to_string(), true, true),
+        Token::Similar => (similar_to_regex(pattern)?, false, false),
+        _ => return Err(BackendError::Internal(format!("{:?} is no match", operand))),
+    };
+    let found = with_regex(&regex, case_insensitive, |regex| regex.is_match(text))?;
+    Ok(SqlValue::Boolean(found != negated))
+}
+
+fn text_of(text: &SqlText) -> &str {
+    match text {
+        SqlText::Text { value } | SqlText::Char { value, .. } | SqlText::VarChar { value, .. } => {
+            value
+        }
+    }
+}
+
+fn with_regex<T>(
+    pattern: &str,
+    case_insensitive: bool,
+    f: impl FnOnce(&Regex) -> T,
+) -> Result<T, BackendError> {
+    PATTERNS.with(|patterns| {
+        let mut patterns = patterns.borrow_mut();
+        let cached = patterns.iter().position(|(cached, insensitive, _)| {
+            cached == pattern && *insensitive == case_insensitive
+        });
+        match cached {
+            // The most recently used stay at the front
+            Some(at) => {
+                let entry = patterns.remove(at);
+                patterns.insert(0, entry);
+            }
+            None => {
+                // Like in Postgres `.` matches newlines too
+                let regex = RegexBuilder::new(pattern)
+                    .case_insensitive(case_insensitive)
+                    .dot_matches_new_line(true)
+                    .build()
+                    .map_err(|err| {
+                        BackendError::InvalidRegularExpression(format!(
+                            "Invalid regular expression: {}",
+                            err
+                        ))
+                    })?;
+                patterns.truncate(CACHED_PATTERNS - 1);
+                patterns.insert(0, (pattern.to_string(), case_insensitive, regex));
+            }
+        }
+        Ok(f(&patterns[0].2))
+    })
+}
+
+// The regular expression matching what the SIMILAR TO `pattern` does. Like LIKE it has to
+// match the whole value and `%` and `_` stand for any text and any character, while
+// `|`, `*`, `+`, `?`, `{m,n}`, parentheses and bracket expressions work like in regular
+// expressions. Anything else, a `\` before it included, is matched as it is.
+fn similar_to_regex(pattern: &str) -> Result<String, BackendError> {
+    let mut regex = String::from("^(?:");
+    let mut chars = pattern.chars();
+    let mut in_brackets = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => regex.push_str(&regex::escape(&escaped.to_string())),
+                None => {
+                    return Err(BackendError::InvalidRegularExpression(
+                        "Invalid regular expression: pattern ends with an escape.".to_string(),
+                    ))
+                }
+            },
+            ']' if in_brackets => {
+                in_brackets = false;
+                regex.push(c);
+            }
+            // Other characters within brackets are taken as they are
+            _ if in_brackets => regex.push(c),
+            '[' => {
+                in_brackets = true;
+                regex.push(c);
+                // A `]` first or right after the `^` is one of the characters
+                let mut rest = chars.clone().peekable();
+                if rest.peek() == Some(&'^') {
+                    regex.push(chars.next().unwrap_or('^'));
+                    rest.next();
+                }
+                if rest.peek() == Some(&']') {
+                    chars.next();
+                    regex.push_str("\\]");
+                }
+            }
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            '(' => regex.push_str("(?:"),
+            '|' | '*' | '+' | '?' | '{' | '}' | ')' | ',' => regex.push(c),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push_str(")$");
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE words (id INT, word TEXT);
+        INSERT INTO words VALUES (1, 'apple');
+        INSERT INTO words VALUES (2, 'Apricot');
+        INSERT INTO words VALUES (3, 'banana');
+        INSERT INTO words VALUES (4, 'grape');
+        INSERT INTO words VALUES (5, NULL);";
+
+    #[test]
+    fn test_regex_match_operators() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word ~ '^a';"),
+            ["apple"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word ~* '^a';"),
+            ["apple", "Apricot"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word !~ 'an';"),
+            ["apple", "Apricot", "grape"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word !~* '^A|e$';"),
+            ["banana"]
+        );
+        // Unanchored patterns match anywhere, anchored ones at the ends only
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word ~ 'p';"),
+            ["apple", "Apricot", "grape"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM words WHERE word ~ '^[a-z]+e$';"),
+            ["apple", "grape"]
+        );
+
+        // Matching NULL gives NULL
+        assert_eq!(
+            rows(&mut mb, "SELECT word ~ 'a' FROM words WHERE id > 3;"),
+            ["true", "NULL"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT 'a' !~* NULL;"), ["NULL"]);
+
+        // Before an operand `~` is still bitwise NOT
+        assert_eq!(rows(&mut mb, "SELECT ~5;"), ["-6"]);
+        assert_eq!(rows(&mut mb, "SELECT 1 + ~1;"), ["-1"]);
+        assert_eq!(
+            rows(&mut mb, "SELECT 'abc' ~ 'b' AND 'abc' !~ 'd';"),
+            ["true"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let mut mb = backend(SETUP);
+        let err = mb
+            .eval_query("SELECT word FROM words WHERE word ~ 'a(';")
+            .unwrap_err();
+        assert_eq!(err.code(), "2201B");
+        assert!(err.message().starts_with("Invalid regular expression: "));
+        assert!(err.message().contains("unclosed group"));
+
+        // Not before there is something to match
+        mb.eval_query("CREATE TABLE empty (word TEXT);").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT word FROM empty WHERE word ~ 'a(';"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            mb.eval_query("SELECT 1 ~ 'a';").unwrap_err().code(),
+            "42883"
+        );
+    }
+
+    #[test]
+    fn test_similar_to() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word SIMILAR TO '%(an|ap)%';"
+            ),
+            ["apple", "banana", "grape"]
+        );
+        // The whole value has to match
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word SIMILAR TO 'ap';"
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word SIMILAR TO '_r%';"
+            ),
+            ["grape"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word NOT SIMILAR TO '[ab]%';"
+            ),
+            ["Apricot", "grape"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT word FROM words WHERE word SIMILAR TO 'b(an)+a';"
+            ),
+            ["banana"]
+        );
+        // Dots and other regex syntax match themselves
+        assert_eq!(rows(&mut mb, "SELECT 'a.c' SIMILAR TO 'a.c';"), ["true"]);
+        assert_eq!(rows(&mut mb, "SELECT 'abc' SIMILAR TO 'a.c';"), ["false"]);
+        assert_eq!(rows(&mut mb, "SELECT '5%' SIMILAR TO '_\\%';"), ["true"]);
+        assert_eq!(rows(&mut mb, "SELECT NULL SIMILAR TO 'a';"), ["NULL"]);
+    }
+
+    #[test]
+    fn test_similar_to_regex() {
+        assert_eq!(similar_to_regex("a%b_").unwrap(), "^(?:a.*b.)$");
+        assert_eq!(similar_to_regex("(a|b)*.").unwrap(), "^(?:(?:a|b)*\\.)$");
+        assert_eq!(similar_to_regex("[%_]x").unwrap(), "^(?:[%_]x)$");
+        assert_eq!(similar_to_regex("[^]a]").unwrap(), "^(?:[^\\]a])$");
+        assert_eq!(similar_to_regex("a\\%").unwrap(), "^(?:a%)$");
+        assert!(similar_to_regex("a\\").is_err());
+    }
+}
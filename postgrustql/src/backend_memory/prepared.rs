@@ -0,0 +1,592 @@
+use super::{literal_to_memory_cell, GivenValue, MemoryBackend, QueryTiming};
+use crate::ast::*;
+use crate::backend::{BackendError, EvalResult, ERR_TABLE_DOES_NOT_EXIST};
+use crate::lexer::Token;
+use crate::parser::parse;
+use crate::sql_types::{check_no_nul, SqlText, SqlValue};
+
+// A parsed statement with `$1`, `$2`, ... placeholders, executed with bound values
+#[derive(Clone, Debug)]
+pub struct PreparedStatement {
+    statement: Statement,
+    param_count: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BatchOptions {
+    // Keep executing the remaining parameter sets when one fails, instead of undoing the batch
+    pub continue_on_error: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchFailure {
+    // Position of the failed parameter set in the batch
+    pub index: usize,
+    pub error: BackendError,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BatchResult {
+    pub executed: u64,
+    pub failures: Vec<BatchFailure>,
+}
+
+// Where each column value of an insert comes from, resolved once per batch
+enum InsertSource {
+    Param(usize),
+    Const(SqlValue),
+}
+
+impl MemoryBackend {
+    pub fn prepare(&self, query: &str) -> Result<PreparedStatement, BackendError> {
+        let mut ast = parse(query)?;
+        if ast.statements.len() != 1 {
+            return Err(BackendError::SyntaxError(
+                "Prepared statements must contain exactly one statement.".to_string(),
+            ));
+        }
+        let mut statement = ast.statements.remove(0);
+        self.bind_identifiers_quietly(&mut statement)?;
+
+        let mut param_count = 0;
+        let mut invalid_param = false;
+        visit_statement_literals(&statement, &mut |literal| {
+            if let Token::Parameter { index } = literal {
+                param_count = param_count.max(*index);
+                invalid_param = invalid_param || *index == 0;
+            }
+        });
+        if invalid_param {
+            return Err(BackendError::SyntaxError(
+                "Parameters are numbered starting from $1.".to_string(),
+            ));
+        }
+
+        Ok(PreparedStatement {
+            statement,
+            param_count,
+        })
+    }
+}
+
+impl PreparedStatement {
+    pub fn param_count(&self) -> usize {
+        self.param_count
+    }
+
+    pub fn statement(&self) -> &Statement {
+        &self.statement
+    }
+
+    pub(super) fn statement_mut(&mut self) -> &mut Statement {
+        &mut self.statement
+    }
+
+    // Returns the statement with every placeholder replaced by its value
+    pub fn bind(&self, params: &[SqlValue]) -> Result<Statement, BackendError> {
+        self.check_params(params)?;
+        let mut statement = self.statement.clone();
+        let mut result = Ok(());
+        visit_statement_literals_mut(&mut statement, &mut |literal| {
+            if let Token::Parameter { index } = literal {
+                match value_to_token(&params[*index - 1]) {
+                    Ok(token) => *literal = token,
+                    Err(err) => result = Err(err),
+                }
+            }
+        });
+        result.map(|_| statement)
+    }
+
+    pub fn execute(
+        &self,
+        backend: &mut MemoryBackend,
+        params: &[SqlValue],
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        let statement = self.bind(params)?;
+        // Nothing was lexed or parsed for this run
+        backend.timing.last = QueryTiming::default();
+        backend.eval_statement(statement)
+    }
+
+    // Executes the statement once for every parameter set. Either every execution
+    // succeeds or none of them takes effect.
+    pub fn execute_many(
+        &self,
+        backend: &mut MemoryBackend,
+        params: impl IntoIterator<Item = Vec<SqlValue>>,
+    ) -> Result<u64, BackendError> {
+        let result = self.execute_many_with(backend, params, BatchOptions::default())?;
+        Ok(result.executed)
+    }
+
+    pub fn execute_many_with(
+        &self,
+        backend: &mut MemoryBackend,
+        params: impl IntoIterator<Item = Vec<SqlValue>>,
+        opts: BatchOptions,
+    ) -> Result<BatchResult, BackendError> {
+        match &self.statement {
+            Statement::InsertStatement(insert_statement)
+                if insert_statement.rows.len() == 1
+                    && insert_statement.rows[0].iter().all(|value| match value {
+                        Expression::Literal(literal) => literal.literal != Token::Default,
+                        _ => false,
+                    }) =>
+            {
+                self.insert_many(backend, insert_statement, params, &opts)
+            }
+            _ if opts.continue_on_error => {
+                let mut result = BatchResult::default();
+                for (index, params) in params.into_iter().enumerate() {
+                    match self.execute(backend, &params) {
+                        Ok(_) => result.executed += 1,
+                        Err(error) => result.failures.push(BatchFailure { index, error }),
+                    }
+                }
+                Ok(result)
+            }
+            _ => backend.transaction(|backend| {
+                let mut result = BatchResult::default();
+                for (index, params) in params.into_iter().enumerate() {
+                    self.execute(backend, &params)
+                        .map_err(|err| batch_error(index, err))?;
+                    result.executed += 1;
+                }
+                Ok(result)
+            }),
+        }
+    }
+
+    // Fast path for inserts of one row of plain values: the statement is bound and looked
+    // up once, the constants in it worked out once and the checks of the table compiled once
+    // for the whole batch. Every parameter set then goes through what an INSERT of it would,
+    // a row at a time.
+    fn insert_many(
+        &self,
+        backend: &mut MemoryBackend,
+        insert_statement: &InsertStatement,
+        params: impl IntoIterator<Item = Vec<SqlValue>>,
+        opts: &BatchOptions,
+    ) -> Result<BatchResult, BackendError> {
+        backend.check_read_only(&self.statement)?;
+        let table = match backend.table(&insert_statement.table) {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(
+                    ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                ))
+            }
+        };
+        let mut sources = Vec::with_capacity(insert_statement.rows[0].len());
+        for value in &insert_statement.rows[0] {
+            if let Expression::Literal(literal) = value {
+                sources.push(match &literal.literal {
+                    Token::Parameter { index } => InsertSource::Param(*index - 1),
+                    token => InsertSource::Const(literal_to_memory_cell(token)?),
+                });
+            }
+        }
+        let checks = table.compile_checks();
+        let generated = table.compile_generated();
+
+        let insert = |backend: &mut MemoryBackend, params: Vec<SqlValue>| {
+            self.check_params(&params)?;
+            let values: Vec<SqlValue> = sources
+                .iter()
+                .map(|source| match source {
+                    InsertSource::Param(i) => params[*i].clone(),
+                    InsertSource::Const(value) => value.clone(),
+                })
+                .collect();
+            let table = backend
+                .table(&insert_statement.table)
+                .ok_or_else(|| BackendError::UndefinedTable(ERR_TABLE_DOES_NOT_EXIST.to_owned()))?;
+            // Bound values go in as the values of a query would
+            let given = values.iter().map(GivenValue::Selected).collect();
+            let row = backend.insert_row(table, &checks, &generated, given, 1)?;
+            backend.write_inserts(&insert_statement.table, vec![row])
+        };
+
+        if opts.continue_on_error {
+            let mut result = BatchResult::default();
+            for (index, params) in params.into_iter().enumerate() {
+                match insert(backend, params) {
+                    Ok(_) => result.executed += 1,
+                    Err(error) => result.failures.push(BatchFailure { index, error }),
+                }
+            }
+            return Ok(result);
+        }
+        backend.transaction(|backend| {
+            let mut result = BatchResult::default();
+            for (index, params) in params.into_iter().enumerate() {
+                insert(backend, params).map_err(|err| batch_error(index, err))?;
+                result.executed += 1;
+            }
+            Ok(result)
+        })
+    }
+
+    // Checks there is a value for every parameter, and that the text among them is text
+    // Postgres could hold
+    fn check_params(&self, params: &[SqlValue]) -> Result<(), BackendError> {
+        if params.len() != self.param_count {
+            return Err(BackendError::ProtocolViolation(format!(
+                "Expected {} parameters, got {}.",
+                self.param_count,
+                params.len()
+            )));
+        }
+        for (idx, param) in params.iter().enumerate() {
+            if let SqlValue::Text(
+                SqlText::Text { value }
+                | SqlText::Char { value, .. }
+                | SqlText::VarChar { value, .. },
+            ) = param
+            {
+                check_no_nul(value).map_err(|err| {
+                    BackendError::from(err)
+                        .map_message(|msg| format!("Parameter ${}: {}", idx + 1, msg))
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn batch_error(index: usize, err: BackendError) -> BackendError {
+    err.map_message(|msg| format!("Parameter set {} failed: {}", index + 1, msg))
+}
+
+pub(super) fn value_to_token(value: &SqlValue) -> Result<Token, BackendError> {
+    match value {
+        SqlValue::Null => Ok(Token::Null),
+        SqlValue::Boolean(value) => Ok(Token::BoolValue { value: *value }),
+        SqlValue::Numeric(num) => Ok(Token::NumericValue {
+            value: num.to_string().into(),
+        }),
+        SqlValue::Text(text) => Ok(Token::StringValue {
+            value: text.to_string().into(),
+        }),
+        SqlValue::Type(_) => Err(BackendError::DatatypeMismatch(
+            "Types cannot be bound as parameters.".to_string(),
+        )),
+    }
+}
+
+pub(super) fn visit_statement_literals(statement: &Statement, f: &mut impl FnMut(&Token)) {
+    let mut statement = statement.clone();
+    visit_statement_literals_mut(&mut statement, &mut |token| f(token));
+}
+
+pub(super) fn visit_statement_literals_mut(
+    statement: &mut Statement,
+    f: &mut impl FnMut(&mut Token),
+) {
+    match statement {
+        Statement::InsertStatement(insert) => {
+            for value in insert.rows.iter_mut().flatten() {
+                visit_expression_literals(value, f);
+            }
+            if let Some(query) = insert.query.as_mut() {
+                visit_select_literals(query, f);
+            }
+            visit_item_literals(&mut insert.returning, f);
+        }
+        Statement::UpdateStatement(update) => {
+            for assignment in update.assignments.iter_mut() {
+                visit_expression_literals(&mut assignment.value, f);
+            }
+            visit_expression_literals(&mut update.where_clause, f);
+            for order_by in update.order_by.iter_mut() {
+                visit_expression_literals(&mut order_by.exp, f);
+            }
+            visit_item_literals(&mut update.returning, f);
+        }
+        Statement::DeleteStatement(delete) => {
+            visit_expression_literals(&mut delete.where_clause, f);
+            for order_by in delete.order_by.iter_mut() {
+                visit_expression_literals(&mut order_by.exp, f);
+            }
+            visit_item_literals(&mut delete.returning, f);
+        }
+        Statement::WithStatement(with) => {
+            for query in with.queries.iter_mut() {
+                visit_statement_literals_mut(&mut query.statement, f);
+            }
+            visit_statement_literals_mut(&mut with.statement, f);
+        }
+        Statement::SelectStatement(select) => visit_select_literals(select, f),
+        Statement::CreateTableAsStatement(create_table_as) => {
+            visit_select_literals(&mut create_table_as.query, f)
+        }
+        Statement::ExplainStatement(explain) => visit_select_literals(&mut explain.select, f),
+        Statement::DeclareCursorStatement(declare) => visit_select_literals(&mut declare.query, f),
+        Statement::CreateIndexStatement(create_index) => {
+            visit_expression_literals(&mut create_index.expression, f)
+        }
+        Statement::SetStatement(set) => f(&mut set.value),
+        // The literals of a view are in the text it keeps of its query too, and can't be
+        // swapped for others
+        Statement::CreateTableStatement(_)
+        | Statement::CreateViewStatement(_)
+        | Statement::DropTableStatement(_)
+        | Statement::DropIndexStatement(_)
+        | Statement::CreateSequenceStatement(_)
+        | Statement::DropSequenceStatement(_)
+        | Statement::DropViewStatement(_)
+        | Statement::CreateTriggerStatement(_)
+        | Statement::DropTriggerStatement(_)
+        | Statement::AlterTableStatement(_)
+        | Statement::ReindexStatement(_)
+        | Statement::VacuumStatement(_)
+        | Statement::ShowStatement(_)
+        | Statement::ResetStatement(_)
+        | Statement::ListenStatement(_)
+        | Statement::NotifyStatement(_)
+        | Statement::UnlistenStatement(_)
+        | Statement::FetchStatement(_)
+        | Statement::CloseStatement(_)
+        | Statement::TransactionStatement(_) => {}
+    }
+}
+
+fn visit_item_literals(items: &mut [SelectItem], f: &mut impl FnMut(&mut Token)) {
+    for item in items.iter_mut() {
+        visit_expression_literals(&mut item.expression, f);
+    }
+}
+
+// Literals are visited in the order they appear in the query text
+fn visit_select_literals(select: &mut SelectStatement, f: &mut impl FnMut(&mut Token)) {
+    visit_item_literals(&mut select.items, f);
+    for source in select.from.iter_mut() {
+        let joins = match source {
+            RowDataSource::SubSelect { select, joins, .. } => {
+                visit_select_literals(select, f);
+                joins
+            }
+            RowDataSource::Table { sample, joins, .. } => {
+                visit_sample_literals(sample, f);
+                joins
+            }
+            RowDataSource::Function { call, joins, .. } => {
+                for arg in call.args.iter_mut() {
+                    visit_expression_literals(arg, f);
+                }
+                joins
+            }
+            RowDataSource::Values { rows, joins, .. } => {
+                for exp in rows.iter_mut().flatten() {
+                    visit_expression_literals(exp, f);
+                }
+                joins
+            }
+        };
+        for join in joins.iter_mut() {
+            match &mut join.source {
+                RowDataSource::SubSelect { select, .. } => visit_select_literals(select, f),
+                RowDataSource::Function { call, .. } => {
+                    for arg in call.args.iter_mut() {
+                        visit_expression_literals(arg, f);
+                    }
+                }
+                RowDataSource::Values { rows, .. } => {
+                    for exp in rows.iter_mut().flatten() {
+                        visit_expression_literals(exp, f);
+                    }
+                }
+                RowDataSource::Table { sample, .. } => visit_sample_literals(sample, f),
+            }
+            visit_expression_literals(&mut join.on, f);
+        }
+    }
+    visit_expression_literals(&mut select.where_clause, f);
+    for exp in select.group_by.iter_mut() {
+        visit_expression_literals(exp, f);
+    }
+    visit_expression_literals(&mut select.having, f);
+    for order_by in select.order_by.iter_mut() {
+        visit_expression_literals(&mut order_by.exp, f);
+    }
+}
+
+fn visit_sample_literals(sample: &mut Option<TableSample>, f: &mut impl FnMut(&mut Token)) {
+    if let Some(sample) = sample {
+        visit_expression_literals(&mut sample.percentage, f);
+        if let Some(repeatable) = &mut sample.repeatable {
+            visit_expression_literals(repeatable, f);
+        }
+    }
+}
+
+pub(super) fn visit_expression_literals(
+    expression: &mut Expression,
+    f: &mut impl FnMut(&mut Token),
+) {
+    match expression {
+        Expression::Literal(literal) => f(&mut literal.literal),
+        Expression::Binary(binary) => {
+            visit_expression_literals(&mut binary.first, f);
+            visit_expression_literals(&mut binary.second, f);
+        }
+        Expression::Unary(unary) => visit_expression_literals(&mut unary.first, f),
+        Expression::SubSelect(select) | Expression::Exists(select) => {
+            visit_select_literals(select, f)
+        }
+        Expression::Quantified(quantified) => {
+            visit_expression_literals(&mut quantified.first, f);
+            if let QuantifiedSet::SubSelect(select) = &mut quantified.set {
+                visit_select_literals(select, f);
+            }
+        }
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            visit_expression_literals(data, f)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                visit_expression_literals(arg, f);
+            }
+            if let Some(filter) = call.filter.as_mut() {
+                visit_expression_literals(filter, f);
+            }
+            if let Some(over) = &mut call.over {
+                for exp in over.partition_by.iter_mut() {
+                    visit_expression_literals(exp, f);
+                }
+                for order_by in over.order_by.iter_mut() {
+                    visit_expression_literals(&mut order_by.exp, f);
+                }
+            }
+        }
+        Expression::TableColumn(_) | Expression::ProcessedTableColumn(_) | Expression::Empty => {}
+    }
+}
+
+#[cfg(test)]
+mod prepared_tests {
+    use super::*;
+    use crate::sql_types::SqlNumeric;
+
+    fn int(value: i32) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::Int { value })
+    }
+
+    fn text(value: &str) -> SqlValue {
+        SqlValue::Text(SqlText::Text {
+            value: value.to_string(),
+        })
+    }
+
+    fn count(mb: &mut MemoryBackend, query: &str) -> usize {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results.rows.len(),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_execute_many() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE people (id INT PRIMARY KEY, name TEXT, age SMALLINT);
+            CREATE INDEX people_age ON people (age);",
+        )
+        .unwrap();
+
+        let insert = mb
+            .prepare("INSERT INTO people VALUES ($1, $2, 30);")
+            .unwrap();
+        assert_eq!(insert.param_count(), 2);
+        let executed = insert
+            .execute_many(&mut mb, (0..100).map(|i| vec![int(i), text("Baam")]))
+            .unwrap();
+        assert_eq!(executed, 100);
+        assert_eq!(count(&mut mb, "SELECT id FROM people WHERE age = 30;"), 100);
+        assert_eq!(count(&mut mb, "SELECT id FROM people WHERE id = 42;"), 1);
+
+        // A duplicate key undoes the whole batch by default
+        let err = insert
+            .execute_many(
+                &mut mb,
+                (100..110).chain(5..6).map(|i| vec![int(i), text("Rachel")]),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parameter set 11 failed: Duplicate Value violates UNIQUE Constraint"
+        );
+        assert_eq!(count(&mut mb, "SELECT id FROM people;"), 100);
+        assert_eq!(count(&mut mb, "SELECT id FROM people WHERE id = 105;"), 0);
+
+        // Or skips the failing rows when asked to
+        let result = insert
+            .execute_many_with(
+                &mut mb,
+                vec![
+                    vec![int(200), text("Khun")],
+                    vec![int(1), text("Khun")],
+                    vec![text("abc"), text("Khun")],
+                    vec![int(201)],
+                    vec![int(202), text("Khun")],
+                ],
+                BatchOptions {
+                    continue_on_error: true,
+                },
+            )
+            .unwrap();
+        assert_eq!(result.executed, 2);
+        assert_eq!(
+            result.failures.iter().map(|f| f.index).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(count(&mut mb, "SELECT id FROM people WHERE age = 30;"), 102);
+
+        // Other statements bind parameters into the parsed statement
+        let select = mb
+            .prepare("SELECT id FROM people WHERE id > $1 AND name = $2;")
+            .unwrap();
+        match select.execute(&mut mb, &[int(195), text("Khun")]).unwrap() {
+            EvalResult::Select { results, .. } => {
+                assert_eq!(results.rows, vec![vec![int(200)], vec![int(202)]])
+            }
+            _ => panic!("Expected select results"),
+        }
+        assert!(select.execute(&mut mb, &[int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_text_params_checked() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT, name VARCHAR(10));")
+            .unwrap();
+
+        let insert = mb.prepare("INSERT INTO t VALUES ($1, $2);").unwrap();
+        let err = insert
+            .execute(&mut mb, &[int(1), text("a\0b")])
+            .unwrap_err();
+        assert_eq!(err.code(), "22P05");
+        assert_eq!(
+            err.message(),
+            "Parameter $2: Text can't hold the NUL character, found at byte 2."
+        );
+        let batch = vec![vec![int(1), text("a")], vec![int(2), text("\0")]];
+        let err = insert.execute_many(&mut mb, batch).unwrap_err();
+        assert_eq!(err.code(), "22P05");
+        assert_eq!(count(&mut mb, "SELECT id FROM t;"), 0);
+
+        let select = mb.prepare("SELECT id FROM t WHERE name = $1;").unwrap();
+        let err = select.execute(&mut mb, &[text("\0")]).unwrap_err();
+        assert_eq!(err.code(), "22P05");
+
+        // Literals are checked too, escapes being how a NUL gets into one
+        mb.eval_query("SET standard_conforming_strings = off;")
+            .unwrap();
+        let err = mb
+            .eval_query("INSERT INTO t VALUES (1, 'a\\0');")
+            .unwrap_err();
+        assert_eq!(err.code(), "22P05");
+        assert_eq!(count(&mut mb, "SELECT id FROM t;"), 0);
+    }
+}
@@ -0,0 +1,311 @@
+use super::MemoryBackend;
+use crate::backend::BackendError;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+// Rows gone through between two reports of the progress of a statement
+const PROGRESS_ROWS: u64 = 64;
+// Longest pg_sleep goes without seeing whether the statement was canceled
+const SLEEP_STEP: Duration = Duration::from_millis(5);
+
+// How far a statement that scans or builds got, like CREATE INDEX, VACUUM, UPDATE or
+// DELETE. Read as a whole, never half updated.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StatementProgress {
+    pub command: &'static str,
+    pub relation: String,
+    pub rows_done: u64,
+    // The rows of the relation when it started, VACUUM goes by whole tables
+    pub rows_total: u64,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    canceled: AtomicBool,
+    progress: Mutex<Option<StatementProgress>>,
+}
+
+// A handle on the statements a backend runs, for other threads to see how far the one
+// running got and to cancel it. The backend itself is taken by the statement until it
+// ends, its monitor can be used all along.
+#[derive(Debug, Clone, Default)]
+pub struct StatementMonitor(Arc<MonitorState>);
+
+impl StatementMonitor {
+    // The progress of the statement running, None when it reports none
+    pub fn progress(&self) -> Option<StatementProgress> {
+        self.lock_progress().clone()
+    }
+
+    // Makes the running statement fail with 57014 the next time it looks, as soon as a
+    // row is done or pg_sleep wakes up. It does nothing to the statements run after it.
+    pub fn cancel(&self) {
+        self.0.canceled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.0.canceled.load(Ordering::Relaxed)
+    }
+
+    fn lock_progress(&self) -> MutexGuard<'_, Option<StatementProgress>> {
+        self.0
+            .progress
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+thread_local! {
+    // The monitor of the statement running on this thread
+    static STATEMENT_MONITOR: RefCell<Option<StatementMonitor>> = const { RefCell::new(None) };
+}
+
+// Puts back the monitor of the statement that was running before when dropped
+pub(super) struct StatementMonitorGuard {
+    outer: Option<StatementMonitor>,
+}
+
+impl Drop for StatementMonitorGuard {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        STATEMENT_MONITOR.with(|monitor| *monitor.borrow_mut() = outer);
+    }
+}
+
+impl MemoryBackend {
+    pub fn monitor(&self) -> StatementMonitor {
+        self.monitor.clone()
+    }
+
+    // See `StatementMonitor::progress`
+    pub fn progress(&self) -> Option<StatementProgress> {
+        self.monitor.progress()
+    }
+
+    // Makes the monitor of the backend that of the statement running on this thread
+    // until the guard is dropped. A statement not run by another starts uncanceled.
+    pub(super) fn enter_monitor(&self) -> StatementMonitorGuard {
+        let outer = STATEMENT_MONITOR.with(|monitor| monitor.borrow_mut().take());
+        if outer.is_none() {
+            self.monitor.0.canceled.store(false, Ordering::Relaxed);
+        }
+        let monitor = Some(self.monitor.clone());
+        STATEMENT_MONITOR.with(|current| *current.borrow_mut() = monitor);
+        StatementMonitorGuard { outer }
+    }
+}
+
+// Leaves this thread without a monitor until the guard is dropped. Expressions evaluated
+// on sample values, only for the type of what they give, must not sleep.
+pub(super) fn leave_monitor() -> StatementMonitorGuard {
+    let outer = STATEMENT_MONITOR.with(|monitor| monitor.borrow_mut().take());
+    StatementMonitorGuard { outer }
+}
+
+fn current_monitor() -> Option<StatementMonitor> {
+    STATEMENT_MONITOR.with(|monitor| monitor.borrow().clone())
+}
+
+fn canceled() -> BackendError {
+    BackendError::QueryCanceled("Canceling statement due to user request.".to_string())
+}
+
+// Reports how far the statement running on this thread got through the rows of
+// `relation`, until dropped. A report made within another, like that of a statement a
+// trigger runs, takes its place while it lasts.
+pub(super) struct ProgressReport {
+    monitor: Option<StatementMonitor>,
+    progress: StatementProgress,
+    outer: Option<StatementProgress>,
+}
+
+impl ProgressReport {
+    pub(super) fn start(command: &'static str, relation: &str, rows_total: usize) -> Self {
+        let monitor = current_monitor();
+        let progress = StatementProgress {
+            command,
+            relation: relation.to_string(),
+            rows_done: 0,
+            rows_total: rows_total as u64,
+        };
+        let outer = monitor
+            .as_ref()
+            .and_then(|monitor| monitor.lock_progress().replace(progress.clone()));
+        ProgressReport {
+            monitor,
+            progress,
+            outer,
+        }
+    }
+
+    // Counts a row as done, failing if the statement was canceled
+    #[inline]
+    pub(super) fn row(&mut self) -> Result<(), BackendError> {
+        self.rows(1)
+    }
+
+    pub(super) fn rows(&mut self, rows: u64) -> Result<(), BackendError> {
+        let monitor = match &self.monitor {
+            Some(monitor) => monitor,
+            None => return Ok(()),
+        };
+        if monitor.is_canceled() {
+            return Err(canceled());
+        }
+        let before = self.progress.rows_done;
+        self.progress.rows_done += rows;
+        if before / PROGRESS_ROWS != self.progress.rows_done / PROGRESS_ROWS {
+            *monitor.lock_progress() = Some(self.progress.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ProgressReport {
+    fn drop(&mut self) {
+        if let Some(monitor) = &self.monitor {
+            *monitor.lock_progress() = self.outer.take();
+        }
+    }
+}
+
+// What pg_sleep does: sleeps in steps short enough to notice the statement was canceled
+// soon after. It returns at once outside of statements.
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn sleep(seconds: f64) -> Result<(), BackendError> {
+    let monitor = match current_monitor() {
+        Some(monitor) => monitor,
+        None => return Ok(()),
+    };
+    let duration = match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => duration,
+        // Negative and NaN don't sleep, too long sleeps until canceled
+        Err(_) if seconds > 0.0 => Duration::MAX,
+        Err(_) => Duration::ZERO,
+    };
+    let start = std::time::Instant::now();
+    loop {
+        if monitor.is_canceled() {
+            return Err(canceled());
+        }
+        let remaining = duration.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(remaining.min(SLEEP_STEP));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(super) fn sleep(_seconds: f64) -> Result<(), BackendError> {
+    Err(BackendError::FeatureNotSupported(
+        "pg_sleep: Threads can't sleep on this platform.".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use std::thread;
+    use std::time::Instant;
+
+    // Cancels the statement `mb` runs after `delay`, on another thread
+    fn cancel_after(mb: &MemoryBackend, delay: Duration) -> thread::JoinHandle<Instant> {
+        let monitor = mb.monitor();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            monitor.cancel();
+            Instant::now()
+        })
+    }
+
+    #[test]
+    fn test_pg_sleep() {
+        let mut mb = MemoryBackend::new();
+        let start = Instant::now();
+        let results = mb
+            .eval_query("SELECT pg_sleep(0.03), pg_sleep(-1), pg_sleep(NULL);")
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        match &results[0] {
+            EvalResult::Select { results, .. } => assert!(results.rows[0][0].is_null()),
+            _ => panic!("Expected select results"),
+        }
+
+        // Types are worked out without sleeping
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        let start = Instant::now();
+        mb.eval_query("SELECT pg_sleep(10) FROM t;").unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let err = mb.eval_query("SELECT pg_sleep('soon');").unwrap_err();
+        assert_eq!(err.code(), "42883");
+    }
+
+    #[test]
+    fn test_cancel_pg_sleep() {
+        let mut mb = MemoryBackend::new();
+        let canceler = cancel_after(&mb, Duration::from_millis(20));
+        let err = mb.eval_query("SELECT pg_sleep(10);").unwrap_err();
+        let canceled_at = canceler.join().unwrap();
+        assert_eq!(err.code(), "57014");
+        assert_eq!(err.message(), "Canceling statement due to user request.");
+        assert!(canceled_at.elapsed() < Duration::from_millis(50));
+
+        // Canceling with nothing running leaves the next statement alone
+        mb.monitor().cancel();
+        assert!(mb.eval_query("SELECT pg_sleep(0);").is_ok());
+    }
+
+    #[test]
+    fn test_progress_of_update() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT, n INT);
+            INSERT INTO t SELECT n, 0 FROM generate_series(1, 1000) AS g(n);",
+        )
+        .unwrap();
+        assert_eq!(mb.progress(), None);
+
+        // Watches the UPDATE until it gets through some of the rows, then cancels it
+        let monitor = mb.monitor();
+        let watcher = thread::spawn(move || {
+            let start = Instant::now();
+            let mut progress = None;
+            while progress.is_none() && start.elapsed() < Duration::from_secs(10) {
+                progress = monitor.progress().filter(|progress| progress.rows_done > 0);
+                thread::sleep(Duration::from_millis(1));
+            }
+            monitor.cancel();
+            progress
+        });
+        let err = mb
+            .eval_query("UPDATE t SET n = 1 WHERE format('%s', pg_sleep(0.001)) = '';")
+            .unwrap_err();
+        let progress = watcher
+            .join()
+            .unwrap()
+            .expect("Expected the UPDATE's progress");
+        assert_eq!(err.code(), "57014");
+        assert_eq!(
+            (progress.command, progress.relation.as_str()),
+            ("UPDATE", "t")
+        );
+        assert_eq!(progress.rows_total, 1000);
+        assert!(progress.rows_done < 1000);
+        assert_eq!(mb.progress(), None);
+        match mb
+            .eval_query("SELECT count(*) FROM t WHERE n = 1;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.rows[0][0].to_string(), "0")
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use super::stored_row::StoredRow;
+use super::Table;
+use crate::ast::*;
+use crate::lexer::Token;
+use crate::sql_types::SqlValue;
+
+// The names of the columns a query reads. Expressions are resolved by column name alone,
+// so `b.id` reads the first `id` of the joined row just like a bare `id` does. A name
+// then keeps that column of every FROM item, which leaves what it resolves to unchanged.
+#[derive(Debug, Default)]
+pub(super) struct ReferencedColumns {
+    names: Vec<String>,
+    // FROM items named by qualified references, which need to keep a column at least
+    sources: Vec<String>,
+}
+
+impl ReferencedColumns {
+    // Collects the columns read by the select items, WHERE, GROUP BY, HAVING, ORDER BY
+    // and the join conditions. Gives None when every column is needed: for `SELECT *`,
+    // and for subqueries, whose names could point at the outer row too.
+    pub(super) fn of_select(select: &SelectStatement) -> Option<Self> {
+        let mut referenced = Self::default();
+        for item in &select.items {
+            if item.asterisk {
+                return None;
+            }
+            referenced.add(&item.expression)?;
+        }
+        referenced.add(&select.where_clause)?;
+        for exp in &select.group_by {
+            referenced.add(exp)?;
+        }
+        referenced.add(&select.having)?;
+        for order_by in &select.order_by {
+            referenced.add(&order_by.exp)?;
+        }
+        for source in &select.from {
+            let joins = match source {
+                RowDataSource::Table { joins, .. }
+                | RowDataSource::SubSelect { joins, .. }
+                | RowDataSource::Function { joins, .. }
+                | RowDataSource::Values { joins, .. } => joins,
+            };
+            for join in joins {
+                referenced.add(&join.on)?;
+            }
+        }
+        Some(referenced)
+    }
+
+    pub(super) fn add(&mut self, expression: &Expression) -> Option<()> {
+        match expression {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => self.add_name(value),
+            Expression::TableColumn(table_column) => {
+                self.add_name(&table_column.col_name);
+                if let Some(table_name) = &table_column.table_name {
+                    if !self.sources.contains(table_name) {
+                        self.sources.push(table_name.clone());
+                    }
+                }
+            }
+            Expression::Binary(binary) => {
+                self.add(&binary.first)?;
+                self.add(&binary.second)?;
+            }
+            Expression::Unary(unary) => self.add(&unary.first)?,
+            Expression::Cast { data, .. } | Expression::Collate { data, .. } => self.add(data)?,
+            Expression::FunctionCall(call) => {
+                for arg in &call.args {
+                    self.add(arg)?;
+                }
+                if let Some(filter) = &call.filter {
+                    self.add(filter)?;
+                }
+                if let Some(over) = &call.over {
+                    for exp in &over.partition_by {
+                        self.add(exp)?;
+                    }
+                    for order_by in &over.order_by {
+                        self.add(&order_by.exp)?;
+                    }
+                }
+            }
+            Expression::Quantified(quantified) => match quantified.set {
+                QuantifiedSet::Values(_) => self.add(&quantified.first)?,
+                QuantifiedSet::SubSelect(_) => return None,
+            },
+            Expression::SubSelect(_) | Expression::Exists(_) => return None,
+            Expression::Literal(_) | Expression::ProcessedTableColumn(_) | Expression::Empty => {}
+        }
+        Some(())
+    }
+
+    fn add_name(&mut self, name: &str) {
+        if !self.names.iter().any(|known| known == name) {
+            self.names.push(name.to_string());
+        }
+    }
+}
+
+// Which columns of a FROM item the rows of its scan carry: the cell at position `i` of a
+// scanned row is the column at `columns[i]` of the source. Rows of a `SELECT *`, or of a
+// query reading every column, are the source rows themselves.
+#[derive(Debug)]
+pub(super) struct ColumnMapping {
+    columns: Option<Vec<usize>>,
+}
+
+impl ColumnMapping {
+    pub(super) fn new(
+        referenced: Option<&ReferencedColumns>,
+        source: &str,
+        columns: &[String],
+    ) -> Self {
+        let kept = referenced.and_then(|referenced| {
+            let kept: Vec<usize> = (0..columns.len())
+                .filter(|idx| referenced.names.contains(&columns[*idx]))
+                .collect();
+            // A qualified name is looked up among the columns of its FROM item, which
+            // can't all be gone
+            if kept.is_empty() && referenced.sources.iter().any(|name| name == source) {
+                None
+            } else {
+                Some(kept)
+            }
+        });
+        ColumnMapping {
+            columns: kept.filter(|kept| kept.len() < columns.len()),
+        }
+    }
+
+    pub(super) fn project<T: Clone>(&self, row: &[T]) -> Vec<T> {
+        match &self.columns {
+            Some(columns) => columns.iter().map(|idx| row[*idx].clone()).collect(),
+            None => row.to_vec(),
+        }
+    }
+
+    // The values of a scanned row, of which only the columns kept are decoded
+    pub(super) fn project_row(&self, row: &StoredRow) -> Vec<SqlValue> {
+        match &self.columns {
+            Some(columns) => row.project(columns),
+            None => row.values(),
+        }
+    }
+
+    pub(super) fn project_rows(&self, rows: &[StoredRow]) -> Vec<Vec<SqlValue>> {
+        rows.iter().map(|row| self.project_row(row)).collect()
+    }
+
+    pub(super) fn project_table(&self, table: &Table) -> Option<Table> {
+        self.columns.as_ref()?;
+        Some(Table {
+            name: table.name.clone(),
+            columns: self.project(&table.columns),
+            column_types: self.project(&table.column_types),
+            rows: table
+                .rows
+                .iter()
+                .map(|row| StoredRow::new(&self.project_row(row)))
+                .collect(),
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod projection_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::backend;
+    use crate::backend_memory::MemoryBackend;
+
+    const SETUP: &str = "CREATE TABLE a (id INT, name TEXT, x INT, y INT);
+        CREATE TABLE b (id INT, a_id INT, name TEXT, z INT);
+        INSERT INTO a VALUES (1, 'one', 10, 100), (2, 'two', 20, 200);
+        INSERT INTO b VALUES (1, 2, 'b1', 5), (2, 1, 'b2', 6), (3, 1, 'b3', 7);";
+
+    fn select(mb: &mut MemoryBackend, query: &str) -> (Vec<String>, Vec<String>) {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => (
+                results.columns.into_iter().map(|c| c.name).collect(),
+                results
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|value| value.to_string())
+                            .collect::<Vec<String>>()
+                            .join(",")
+                    })
+                    .collect(),
+            ),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_mapping() {
+        let statement = |query: &str| match crate::parser::parse(query).unwrap().statements.pop() {
+            Some(Statement::SelectStatement(select)) => select,
+            _ => panic!("Expected a select statement"),
+        };
+        let columns: Vec<String> = vec!["id".into(), "name".into(), "x".into()];
+        let tests = vec![
+            ("SELECT x FROM a WHERE id > 1;", "a", Some(vec![0, 2])),
+            (
+                "SELECT a.x FROM a JOIN b ON b.id = a.id;",
+                "a",
+                Some(vec![0, 2]),
+            ),
+            (
+                "SELECT a.y FROM a JOIN b ON b.z = a.id;",
+                "b",
+                Some(vec![0]),
+            ),
+            ("SELECT b.q FROM a JOIN b ON q = w;", "a", Some(vec![])),
+            ("SELECT b.q FROM a JOIN b ON q = w;", "b", None),
+            (
+                "SELECT upper(name) FROM a ORDER BY x;",
+                "a",
+                Some(vec![1, 2]),
+            ),
+            ("SELECT count(*) FROM a;", "a", Some(vec![])),
+            ("SELECT * FROM a;", "a", None),
+            ("SELECT name, x, id FROM a;", "a", None),
+            ("SELECT (SELECT x) FROM a;", "a", None),
+        ];
+        for (query, source, expected) in tests {
+            let referenced = ReferencedColumns::of_select(&statement(query));
+            let mapping = ColumnMapping::new(referenced.as_ref(), source, &columns);
+            assert_eq!(mapping.columns, expected, "{} ({})", query, source);
+        }
+    }
+
+    #[test]
+    fn test_pruned_joins() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            (
+                "SELECT a.name, b.name FROM a INNER JOIN b ON b.a_id = a.id ORDER BY z;",
+                vec!["a.name", "b.name"],
+                vec!["two,b1", "one,b2", "one,b3"],
+            ),
+            (
+                "SELECT y, z FROM a INNER JOIN b ON b.a_id = a.id WHERE x > 10;",
+                vec!["y", "z"],
+                vec!["200,5"],
+            ),
+            (
+                "SELECT name FROM a LEFT JOIN b ON b.z = a.x;",
+                vec!["name"],
+                vec!["one", "two"],
+            ),
+            (
+                "SELECT * FROM a INNER JOIN b ON b.a_id = a.id WHERE z = 5;",
+                vec!["a.id", "a.name", "x", "y", "b.id", "a_id", "b.name", "z"],
+                vec!["2,two,20,200,1,2,b1,5"],
+            ),
+        ];
+        for (query, columns, rows) in tests {
+            assert_eq!(
+                select(&mut mb, query),
+                (
+                    columns.into_iter().map(String::from).collect(),
+                    rows.into_iter().map(String::from).collect(),
+                ),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_pruned_window_scan() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            select(
+                &mut mb,
+                "SELECT name, sum(x) OVER (ORDER BY id) AS total FROM a;"
+            ),
+            (
+                vec!["name".to_string(), "total".to_string()],
+                vec!["one,10".to_string(), "two,30".to_string()],
+            )
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use super::MemoryBackend;
+use crate::ast::Statement;
+use crate::backend::BackendError;
+
+impl MemoryBackend {
+    // Whether statements that change tables other than temporary ones fail, see
+    // `BackendConfig::read_only`
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, enabled: bool) {
+        self.read_only = enabled;
+    }
+
+    // Fails for a statement that changes a table or the schema, unless the backend can
+    // be written to or the statement only changes temporary tables
+    pub(super) fn check_read_only(&self, statement: &Statement) -> Result<(), BackendError> {
+        if !self.read_only || statement.is_read_only() {
+            return Ok(());
+        }
+        let is_temporary = |table_name: &str| self.is_temporary(table_name);
+        let temporary = match statement {
+            Statement::CreateTableStatement(create_table) => create_table.is_temporary,
+            Statement::CreateTableAsStatement(create_table_as) => create_table_as.is_temporary,
+            Statement::InsertStatement(insert) => is_temporary(&insert.table),
+            Statement::UpdateStatement(update) => is_temporary(&update.table),
+            Statement::DeleteStatement(delete) => is_temporary(&delete.table),
+            Statement::WithStatement(with) => with
+                .queries
+                .iter()
+                .map(|query| &*query.statement)
+                .chain(std::iter::once(&*with.statement))
+                .filter_map(Statement::changed_table)
+                .all(is_temporary),
+            Statement::CreateIndexStatement(create_index) => is_temporary(&create_index.table),
+            Statement::DropTableStatement(drop_table) => is_temporary(&drop_table.name),
+            Statement::DropIndexStatement(drop_index) => {
+                matches!(self.index_table(&drop_index.name), Some((true, _)))
+            }
+            Statement::AlterTableStatement(alter_table) => is_temporary(&alter_table.name),
+            Statement::ReindexStatement(reindex) => is_temporary(&reindex.table),
+            Statement::VacuumStatement(vacuum) => vacuum.table.as_deref().is_some_and(is_temporary),
+            _ => false,
+        };
+        if temporary {
+            return Ok(());
+        }
+        Err(BackendError::ReadOnlySqlTransaction(format!(
+            "Cannot execute {} in a read-only connection.",
+            statement.command()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod read_only_tests {
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::{rows, shared};
+    use crate::backend_memory::{Connection, MemoryBackend, StatementInfo};
+    use crate::parser::parse;
+    use crate::sql_types::{SqlNumeric, SqlValue};
+
+    const SETUP: &str = "CREATE TABLE people (id INT PRIMARY KEY, name TEXT);
+        CREATE INDEX people_name ON people (name);
+        INSERT INTO people VALUES (1, 'Baam'), (2, 'Rachel');";
+
+    fn read_only_error(connection: &mut Connection, query: &str) -> String {
+        match connection.eval_query(query) {
+            Err(BackendError::ReadOnlySqlTransaction(msg)) => msg,
+            result => panic!("{}: expected an error, got {:?}", query, result),
+        }
+    }
+
+    #[test]
+    fn test_read_only_connection() {
+        let backend = shared(SETUP);
+        let mut reader = Connection::open_read_only(backend.clone());
+        let mut writer = Connection::open(backend);
+
+        assert_eq!(
+            rows(
+                &mut reader,
+                "SELECT name FROM (SELECT * FROM people WHERE id > 1) AS p;"
+            ),
+            vec!["Rachel"]
+        );
+        assert_eq!(rows(&mut reader, "SHOW transaction_read_only;"), vec!["on"]);
+        reader
+            .eval_query("EXPLAIN ANALYZE SELECT * FROM people;")
+            .unwrap();
+        for (query, command) in &[
+            ("INSERT INTO people VALUES (3, 'Khun');", "INSERT"),
+            ("UPDATE people SET name = 'Khun' WHERE id = 1;", "UPDATE"),
+            ("CREATE TABLE notes (note TEXT);", "CREATE TABLE"),
+            (
+                "SELECT name INTO TABLE names FROM people;",
+                "CREATE TABLE AS",
+            ),
+            ("CREATE INDEX people_id ON people (id);", "CREATE INDEX"),
+            ("DROP INDEX people_name;", "DROP INDEX"),
+            ("ALTER TABLE people DROP COLUMN name;", "ALTER TABLE"),
+            ("REINDEX TABLE people;", "REINDEX"),
+            ("DROP TABLE people;", "DROP TABLE"),
+        ] {
+            assert_eq!(
+                read_only_error(&mut reader, query),
+                format!("Cannot execute {} in a read-only connection.", command)
+            );
+        }
+        // The statements before the one that failed still ran
+        match reader.eval_query("SELECT 1; INSERT INTO people VALUES (3, 'Khun');") {
+            Err(err) => assert_eq!(err.code(), "25006"),
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        // Neither SET nor a prepared batch gets around it
+        assert_eq!(
+            reader
+                .eval_query("SET transaction_read_only = off;")
+                .unwrap_err()
+                .code(),
+            "55P02"
+        );
+        let batch = reader.with_session(|backend| {
+            let insert = backend.prepare("INSERT INTO people VALUES ($1, 'Khun');")?;
+            insert.execute_many(
+                backend,
+                vec![vec![SqlValue::Numeric(SqlNumeric::Int { value: 3 })]],
+            )
+        });
+        assert_eq!(batch.unwrap_err().code(), "25006");
+
+        // Other connections still write
+        writer
+            .eval_query("INSERT INTO people VALUES (3, 'Khun');")
+            .unwrap();
+        assert_eq!(rows(&mut reader, "SELECT count(*) FROM people;"), vec!["3"]);
+    }
+
+    #[test]
+    fn test_temp_tables_stay_writable() {
+        let mut reader = Connection::open_read_only(shared(SETUP));
+        reader
+            .eval_query(
+                "CREATE TEMP TABLE picks (id INT, note TEXT);
+                INSERT INTO picks VALUES ((SELECT id FROM people WHERE id = 2), 'x');
+                UPDATE picks SET note = 'y';
+                CREATE INDEX picks_id ON picks (id);
+                REINDEX TABLE picks;
+                ALTER TABLE picks DROP COLUMN note;
+                DROP INDEX picks_id;
+                SELECT name INTO TEMP TABLE names FROM people;",
+            )
+            .unwrap();
+        assert_eq!(rows(&mut reader, "SELECT * FROM picks;"), vec!["2"]);
+
+        // A temporary table shadowing a permanent one takes the writes
+        reader
+            .eval_query(
+                "CREATE TEMP TABLE people (id INT, name TEXT);
+                INSERT INTO people VALUES (9, 'Temp');
+                DROP TABLE people;",
+            )
+            .unwrap();
+        read_only_error(&mut reader, "DROP TABLE people;");
+        assert_eq!(
+            rows(&mut reader, "SELECT name FROM people;"),
+            vec!["Baam", "Rachel"]
+        );
+    }
+
+    #[test]
+    fn test_statement_is_read_only() {
+        let cases = [
+            (
+                "SELECT * FROM people WHERE id IN (SELECT id FROM people);",
+                true,
+            ),
+            ("EXPLAIN ANALYZE SELECT 1;", true),
+            ("DECLARE c CURSOR FOR SELECT * FROM people;", true),
+            ("SET max_result_rows = 5;", true),
+            ("NOTIFY changes;", true),
+            ("INSERT INTO people VALUES (3, 'Khun');", false),
+            ("UPDATE people SET name = upper(name);", false),
+            ("CREATE TEMP TABLE picks (id INT);", false),
+            ("SELECT * INTO picks FROM people;", false),
+            ("REINDEX TABLE people;", false),
+            ("DELETE FROM people WHERE id = 1;", false),
+            (
+                "WITH named AS (SELECT name FROM people) SELECT * FROM named;",
+                true,
+            ),
+            (
+                "WITH gone AS (DELETE FROM people RETURNING id) SELECT * FROM gone;",
+                false,
+            ),
+        ];
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE people (id INT, name TEXT);")
+            .unwrap();
+        for (query, read_only) in cases.iter() {
+            let statement = parse(query).unwrap().statements.remove(0);
+            assert_eq!(statement.is_read_only(), *read_only, "{}", query);
+            // Authorizers are told the same
+            let info: StatementInfo = mb.statement_info(&statement);
+            assert_eq!(info.writes, !*read_only, "{}", query);
+        }
+
+        // A WITH wrapping a write is turned down like the write
+        let mut reader = Connection::open_read_only(shared(SETUP));
+        let err = reader
+            .eval_query("WITH added AS (INSERT INTO people VALUES (3, 'Khun')) SELECT 1;")
+            .unwrap_err();
+        assert_eq!(err.code(), "25006");
+        assert_eq!(
+            err.to_string(),
+            "Cannot execute INSERT in a read-only connection."
+        );
+        assert_eq!(rows(&mut reader, "SELECT count(*) FROM people;"), vec!["2"]);
+    }
+}
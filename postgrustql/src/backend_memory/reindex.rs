@@ -0,0 +1,440 @@
+use super::progress::ProgressReport;
+use super::{Index, MemoryBackend, Table};
+use crate::ast::ReindexStatement;
+use crate::backend::{BackendError, MemoryCellData};
+
+// Rows read between two reports of how far building an index got
+const BUILD_PROGRESS_ROWS: usize = 100_000;
+
+// Where an index and the rows of its table disagree, found by `verify_indexes`. Rows are
+// identified by their position in the table.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Inconsistency {
+    // The row has no entry in the index
+    MissingEntry { index: String, row: usize },
+    // The index has an entry for a row that doesn't exist, or one more for a row that is
+    // found under its key already
+    ExtraEntry { index: String, row: usize },
+    // The row is found under a key other than its value
+    MismatchedKey { index: String, row: usize },
+}
+
+impl Index {
+    // Every entry as its key and the row it points to, in key order
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8], usize)> {
+        self.tree
+            .iter()
+            .flat_map(|(key, rows)| rows.iter().map(move |row| (key.as_slice(), *row)))
+    }
+
+    // Fills the index with the entries of every row of the table in one go: the keys of
+    // the rows are read, sorted by their sortable encoding and the tree is put together
+    // from the sorted run, rather than entry by entry. It ends up as adding the rows one
+    // at a time in order would leave it, and fails with the error adding the first row
+    // breaking its constraints would give. How far it got is reported as events of the
+    // `build_index` span of `tracing`, and as the progress of CREATE INDEX by the rows read.
+    pub(super) fn build(&mut self, table: &Table) -> Result<(), BackendError> {
+        let span = tracing::info_span!(
+            "build_index",
+            index = %self.name,
+            table = %table.name,
+            rows = table.rows.len()
+        );
+        let _entered = span.enter();
+
+        let mut progress = ProgressReport::start("CREATE INDEX", &table.name, table.rows.len());
+        let mut entries = Vec::with_capacity(table.rows.len());
+        // Only the rows before the first whose key fails can be checked for duplicates
+        let mut failed = None;
+        for row in 0..table.rows.len() {
+            if row > 0 && row % BUILD_PROGRESS_ROWS == 0 {
+                tracing::debug!(rows_read = row, "reading keys");
+            }
+            progress.row()?;
+            match table.index_value(self, row) {
+                Ok(value) if value.is_null() && self.primary_key => {
+                    failed = Some(BackendError::NotNullViolation(
+                        "Violates NOT NULL Constraint".to_string(),
+                    ));
+                    break;
+                }
+                // Any other index keeps rows with a NULL key out
+                Ok(value) if value.is_null() => {}
+                Ok(value) => entries.push((value.encode_sortable().bytes, row)),
+                Err(err) => {
+                    failed = Some(err);
+                    break;
+                }
+            }
+        }
+        tracing::debug!(entries = entries.len(), "sorting keys");
+        // Rows of the same key stay in the order of the table
+        entries.sort_unstable();
+
+        tracing::debug!("building tree");
+        let mut keys: Vec<(MemoryCellData, Vec<usize>)> = Vec::with_capacity(entries.len());
+        for (key, row) in entries {
+            match keys.last_mut() {
+                Some((last, rows)) if *last == key => {
+                    if self.unique {
+                        return Err(BackendError::UniqueViolation(
+                            "Duplicate Value violates UNIQUE Constraint".to_string(),
+                        ));
+                    }
+                    rows.push(row);
+                }
+                _ => keys.push((key, vec![row])),
+            }
+        }
+        if let Some(err) = failed {
+            return Err(err);
+        }
+        // Collecting a sorted run builds the tree bottom up
+        self.set_entries(keys.into_iter().collect());
+        tracing::debug!(keys = self.tree.len(), "index built");
+        Ok(())
+    }
+}
+
+impl MemoryBackend {
+    // Rebuilds every index of the table from its rows. If the rows break the constraint of
+    // an index, the indexes are left as they were.
+    pub fn reindex(&mut self, statement: ReindexStatement) -> Result<bool, BackendError> {
+        let table = match self.table(&statement.table) {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Table \"{}\" doesn't exist.",
+                    statement.table
+                )));
+            }
+        };
+
+        let mut indexes = table.indexes.clone();
+        for index in indexes.iter_mut() {
+            index.build(table)?;
+        }
+
+        self.mark_written(&statement.table);
+        if let Some(table) = self.table_mut(&statement.table)? {
+            table.indexes = indexes;
+        }
+        Ok(true)
+    }
+
+    // Compares the entries of every index of the table against its rows, index by index
+    // and row by row. Nothing is reported for a table whose indexes are sound.
+    pub fn verify_indexes(&self, table_name: &str) -> Result<Vec<Inconsistency>, BackendError> {
+        let table = match self.table(table_name) {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Table \"{}\" doesn't exist.",
+                    table_name
+                )));
+            }
+        };
+
+        let mut inconsistencies = vec![];
+        for index in &table.indexes {
+            let extra = |row| Inconsistency::ExtraEntry {
+                index: index.name.clone(),
+                row,
+            };
+
+            // The keys each row is found under
+            let mut found: Vec<Vec<&[u8]>> = vec![vec![]; table.rows.len()];
+            let mut unknown_rows = vec![];
+            for (key, row) in index.entries() {
+                match found.get_mut(row) {
+                    Some(keys) => keys.push(key),
+                    None => unknown_rows.push(row),
+                }
+            }
+
+            for (row, mut keys) in found.into_iter().enumerate() {
+                // Rows with a NULL value, or one that can't be worked out, are kept out
+                let key = match table.index_value(index, row) {
+                    Ok(value) if !value.is_null() => Some(value.encode_sortable().bytes),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    match keys.iter().position(|found| *found == key.as_slice()) {
+                        Some(position) => {
+                            keys.remove(position);
+                        }
+                        None if keys.is_empty() => {
+                            inconsistencies.push(Inconsistency::MissingEntry {
+                                index: index.name.clone(),
+                                row,
+                            });
+                        }
+                        None => {
+                            keys.remove(0);
+                            inconsistencies.push(Inconsistency::MismatchedKey {
+                                index: index.name.clone(),
+                                row,
+                            });
+                        }
+                    }
+                }
+                inconsistencies.extend(keys.iter().map(|_| extra(row)));
+            }
+            inconsistencies.extend(unknown_rows.into_iter().map(extra));
+        }
+
+        Ok(inconsistencies)
+    }
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    // Lets tests corrupt an index on purpose
+    fn index_mut(&mut self, table_name: &str, index_name: &str) -> Option<&mut Index> {
+        self.table_mut(table_name)
+            .ok()??
+            .indexes
+            .iter_mut()
+            .find(|index| index.name == index_name)
+    }
+}
+
+#[cfg(test)]
+mod reindex_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{backend, rows};
+    use crate::sql_types::{SqlText, SqlValue};
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    const SETUP: &str = "CREATE TABLE t (id INT PRIMARY KEY, name TEXT);
+        CREATE INDEX t_name_idx ON t (name);
+        INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'c');";
+
+    fn key(value: &str) -> Vec<u8> {
+        let value = value.to_string();
+        SqlValue::Text(SqlText::Text { value })
+            .encode_sortable()
+            .bytes
+    }
+
+    #[test]
+    fn test_verify_sound_indexes() {
+        let mb = backend(SETUP);
+        assert_eq!(mb.verify_indexes("t").unwrap(), vec![]);
+        assert_eq!(mb.verify_indexes("u").unwrap_err().code(), "42P01");
+    }
+
+    #[test]
+    fn test_reindex_fixes_corrupt_index() {
+        let mut mb = backend(SETUP);
+        let index = mb.index_mut("t", "t_name_idx").unwrap();
+        // Row 0 loses its entry, row 1 moves to another key and a row that isn't there
+        // shows up
+        index.tree.remove(&key("a"));
+        index.tree.remove(&key("b"));
+        index.tree.insert(key("z"), vec![1, 7]);
+        index.tree.get_mut(&key("c")).unwrap().push(2);
+
+        let index = || "t_name_idx".to_string();
+        assert_eq!(
+            mb.verify_indexes("t").unwrap(),
+            vec![
+                Inconsistency::MissingEntry {
+                    index: index(),
+                    row: 0,
+                },
+                Inconsistency::MismatchedKey {
+                    index: index(),
+                    row: 1,
+                },
+                Inconsistency::ExtraEntry {
+                    index: index(),
+                    row: 2,
+                },
+                Inconsistency::ExtraEntry {
+                    index: index(),
+                    row: 7,
+                },
+            ]
+        );
+
+        match mb.eval_query("REINDEX TABLE t;").unwrap().pop() {
+            Some(EvalResult::Reindex { success: true, .. }) => {}
+            _ => panic!("Expected the table to be reindexed"),
+        }
+        assert_eq!(mb.verify_indexes("t").unwrap(), vec![]);
+        let entries: Vec<(&[u8], usize)> = mb.table("t").unwrap().indexes[1].entries().collect();
+        assert_eq!(
+            entries,
+            vec![
+                (key("a").as_slice(), 0),
+                (key("b").as_slice(), 1),
+                (key("c").as_slice(), 2)
+            ]
+        );
+    }
+
+    // Names of the spans made and messages of the events sent while it is the subscriber
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<String>>,
+        events: Mutex<Vec<String>>,
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name().to_string());
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message<'a>(&'a Mutex<Vec<String>>);
+            impl tracing::field::Visit for Message<'_> {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+                    if field.name() == "message" {
+                        self.0.lock().unwrap().push(format!("{:?}", value));
+                    }
+                }
+            }
+            event.record(&mut Message(&self.events));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_bulk_index_build() {
+        // The same rows, indexed before and after they go in
+        let create = "CREATE TABLE b (id INT, name TEXT, score INT);";
+        let indexes = "CREATE UNIQUE INDEX b_id ON b (id);
+            CREATE INDEX b_name ON b (name);
+            CREATE INDEX b_double ON b ((score * 2));";
+        let insert = (0..500)
+            .map(|id| {
+                format!(
+                    "INSERT INTO b VALUES ({}, 'n{}', {});",
+                    (id * 7) % 500,
+                    id % 37,
+                    250 - id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut incremental = MemoryBackend::new();
+        incremental.eval_query(create).unwrap();
+        incremental.eval_query(indexes).unwrap();
+        incremental.eval_query(&insert).unwrap();
+
+        let recorder = Arc::new(Recorder::default());
+        let mut bulk = MemoryBackend::new();
+        bulk.eval_query(create).unwrap();
+        bulk.eval_query(&insert).unwrap();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            bulk.eval_query(indexes).unwrap();
+        });
+        assert_eq!(*recorder.spans.lock().unwrap(), vec!["build_index"; 3]);
+        assert_eq!(
+            recorder.events.lock().unwrap()[..3],
+            ["sorting keys", "building tree", "index built"]
+        );
+
+        assert_eq!(bulk.verify_indexes("b").unwrap(), vec![]);
+        assert_eq!(incremental.verify_indexes("b").unwrap(), vec![]);
+        let (bulk, incremental) = (bulk.table("b").unwrap(), incremental.table("b").unwrap());
+        for (built, added) in bulk.indexes.iter().zip(&incremental.indexes) {
+            assert_eq!(built.name, added.name);
+            assert_eq!(built.tree, added.tree);
+        }
+    }
+
+    #[test]
+    fn test_bulk_index_build_violations() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT, name TEXT);
+            INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'a'), (4, NULL);
+            CREATE TABLE u (id INT, name TEXT);
+            INSERT INTO u VALUES (1, 'a'), (2, NULL), (3, 'a');",
+        )
+        .unwrap();
+        // Whichever row would have failed first when added one at a time
+        let code = |mb: &mut MemoryBackend, query| mb.eval_query(query).unwrap_err().code();
+        assert_eq!(
+            code(&mut mb, "CREATE UNIQUE INDEX t_name ON t (name);"),
+            "23505"
+        );
+        assert_eq!(
+            code(&mut mb, "CREATE UNIQUE INDEX u_name ON u (name);"),
+            "23505"
+        );
+        assert_eq!(
+            code(&mut mb, "CREATE INDEX t_div ON t ((id / 0));"),
+            "22012"
+        );
+        // Nothing is left of the indexes that failed
+        assert!(mb.table("t").unwrap().indexes.is_empty());
+        mb.eval_query("CREATE INDEX t_id ON t (id);").unwrap();
+        assert_eq!(mb.verify_indexes("t").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_index_leaves_out_null_keys() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT);
+            INSERT INTO t VALUES (1, 'a'), (2, NULL), (3, NULL);
+            CREATE INDEX t_name ON t (name);
+            CREATE UNIQUE INDEX t_name_key ON t (name);
+            INSERT INTO t VALUES (4, NULL);",
+        )
+        .unwrap();
+        let index = mb.index_mut("t", "t_name_key").unwrap();
+        assert_eq!(index.tree, vec![(key("a"), vec![0])].into_iter().collect());
+        mb.eval_query("REINDEX TABLE t;").unwrap();
+        assert_eq!(mb.verify_indexes("t").unwrap(), vec![]);
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM t WHERE name = 'a';"),
+            vec!["1"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM t;"), vec!["4"]);
+        // A primary key still needs a value
+        assert_eq!(
+            mb.eval_query("INSERT INTO t VALUES (NULL, 'b');")
+                .unwrap_err()
+                .code(),
+            "23502"
+        );
+    }
+
+    #[test]
+    fn test_reindex_keeps_indexes_on_violation() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("CREATE UNIQUE INDEX t_name_key ON t (name);")
+            .unwrap();
+        // A duplicate the broken index let in
+        mb.index_mut("t", "t_name_key").unwrap().tree.clear();
+        mb.eval_query("INSERT INTO t VALUES (4, 'a');").unwrap();
+        mb.index_mut("t", "t_pkey").unwrap().tree.clear();
+
+        let err = mb.eval_query("REINDEX TABLE t;").unwrap_err();
+        assert_eq!(err.code(), "23505");
+        assert!(mb.index_mut("t", "t_pkey").unwrap().tree.is_empty());
+        assert_eq!(
+            mb.eval_query("REINDEX TABLE u;").unwrap_err().code(),
+            "42P01"
+        );
+    }
+}
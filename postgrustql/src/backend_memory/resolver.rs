@@ -0,0 +1,511 @@
+use super::catalog::{is_catalog_table, CATALOG_SCHEMA, PUBLIC_SCHEMA, TEMP_SCHEMA};
+use super::{MemoryBackend, StorageEngine};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Past this many search_path settings in a session the names looked up are forgotten,
+// so that a session setting one after another doesn't keep them all
+const MAX_SEARCH_PATHS: usize = 16;
+
+// What a name read by a statement refers to, found in this order: the WITH queries, then
+// the schemas in the order of search_path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Resolution {
+    // A query of the running WITH, or anything else read in place of the tables, like
+    // the catalog tables the statement reads and copies of those it changes
+    WithQuery,
+    Temporary,
+    Catalog,
+    Table,
+    View,
+}
+
+// The schemas there is something in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Schema {
+    Temporary,
+    Catalog,
+    Public,
+}
+
+// The schemas names are looked up in, from search_path as SHOW gives it. As in Postgres
+// the temporary schema comes first and pg_catalog next unless they are listed. The parser
+// takes names qualified by public for names that aren't, so public comes last when left
+// out. Schemas that don't exist, "$user" among them, have nothing to find.
+fn search_path(setting: &str) -> Vec<Schema> {
+    let mut path: Vec<Schema> = setting
+        .split(',')
+        .filter_map(|schema| match schema.trim().trim_matches('"') {
+            "pg_temp" | TEMP_SCHEMA => Some(Schema::Temporary),
+            CATALOG_SCHEMA => Some(Schema::Catalog),
+            PUBLIC_SCHEMA => Some(Schema::Public),
+            _ => None,
+        })
+        .collect();
+    if !path.contains(&Schema::Catalog) {
+        path.insert(0, Schema::Catalog);
+    }
+    if !path.contains(&Schema::Temporary) {
+        path.insert(0, Schema::Temporary);
+    }
+    if !path.contains(&Schema::Public) {
+        path.push(Schema::Public);
+    }
+    path
+}
+
+// The name a table a view reads is given when the statement reading the view would take
+// it for something else, which only finds the permanent table
+pub(super) fn qualified_name(name: &str) -> String {
+    format!("{}.{}", PUBLIC_SCHEMA, name)
+}
+
+// The name of a table where it is kept, without the schema it was qualified by
+pub(super) fn local_name(name: &str) -> &str {
+    schema_name(name).map_or(name, |(_, name)| name)
+}
+
+// The schema a name is qualified by and the name in it. Names in other schemas are kept
+// whole, there is nothing in them to find.
+fn schema_name(name: &str) -> Option<(Schema, &str)> {
+    let (schema, name) = name.split_once('.')?;
+    let schema = match schema {
+        "pg_temp" | TEMP_SCHEMA => Schema::Temporary,
+        CATALOG_SCHEMA => Schema::Catalog,
+        PUBLIC_SCHEMA => Schema::Public,
+        _ => return None,
+    };
+    Some((schema, name))
+}
+
+// What names refer to in the schemas, for a session. Names are looked up again once the
+// tables or views of any session change, which bumps the catalog version of the backend.
+#[derive(Debug, Default)]
+pub(super) struct Resolver {
+    cache: RefCell<ResolverCache>,
+}
+
+#[derive(Debug, Default)]
+struct ResolverCache {
+    // The catalog version the names were looked up at
+    catalog_version: u64,
+    // The search_path settings the session had and their schemas, the index of each is
+    // its generation
+    search_paths: Vec<(String, Vec<Schema>)>,
+    // What names refer to, by the generation of the search_path they were looked up with
+    names: HashMap<usize, HashMap<String, Option<Resolution>>>,
+}
+
+impl ResolverCache {
+    // The generation of the search_path setting, a new one for a setting not seen since
+    // the catalog changed
+    fn generation(&mut self, setting: &str, catalog_version: u64) -> usize {
+        if self.catalog_version != catalog_version || self.search_paths.len() > MAX_SEARCH_PATHS {
+            self.catalog_version = catalog_version;
+            self.search_paths.clear();
+            self.names.clear();
+        }
+        match self
+            .search_paths
+            .iter()
+            .position(|(known, _)| known == setting)
+        {
+            Some(generation) => generation,
+            None => {
+                self.search_paths
+                    .push((setting.to_string(), search_path(setting)));
+                self.search_paths.len() - 1
+            }
+        }
+    }
+}
+
+impl Resolver {
+    // What `name` refers to, found by `find` in the schemas of the setting when it isn't
+    // known yet
+    fn resolve(
+        &self,
+        setting: &str,
+        catalog_version: u64,
+        name: &str,
+        find: impl FnOnce(&[Schema]) -> Option<Resolution>,
+    ) -> Option<Resolution> {
+        let (generation, path) = {
+            let mut cache = self.cache.borrow_mut();
+            let generation = cache.generation(setting, catalog_version);
+            if let Some(resolution) = cache
+                .names
+                .get(&generation)
+                .and_then(|names| names.get(name))
+            {
+                return *resolution;
+            }
+            (generation, cache.search_paths[generation].1.clone())
+        };
+        let resolution = find(&path);
+        self.cache
+            .borrow_mut()
+            .names
+            .entry(generation)
+            .or_default()
+            .insert(name.to_string(), resolution);
+        resolution
+    }
+}
+
+impl MemoryBackend {
+    // What a name read by a statement refers to. A name qualified by its schema is only
+    // looked up there, so that those `qualified_name` gave only find the permanent table.
+    pub(super) fn resolve(&self, name: &str) -> Option<Resolution> {
+        if let Some((schema, name)) = schema_name(name) {
+            return self.find(schema, name);
+        }
+        if self.with_tables.table(name).is_some() {
+            return Some(Resolution::WithQuery);
+        }
+        self.resolve_in_schemas(name)
+    }
+
+    // What a name refers to when no WITH query is named that
+    pub(super) fn resolve_in_schemas(&self, name: &str) -> Option<Resolution> {
+        if let Some((schema, name)) = schema_name(name) {
+            return self.find(schema, name);
+        }
+        let setting = self.variables.value("search_path").unwrap_or_default();
+        self.resolver
+            .resolve(setting, self.catalog_version, name, |path| {
+                path.iter().find_map(|schema| self.find(*schema, name))
+            })
+    }
+
+    // What a name read by a view refers to. It is looked up whenever the view is read,
+    // but never finds a temporary table, which would be gone by the next session.
+    pub(super) fn resolve_permanent(&self, name: &str) -> Option<Resolution> {
+        match schema_name(name) {
+            Some((Schema::Temporary, _)) => return None,
+            Some((schema, name)) => return self.find(schema, name),
+            None => {}
+        }
+        let setting = self.variables.value("search_path").unwrap_or_default();
+        search_path(setting)
+            .into_iter()
+            .filter(|schema| *schema != Schema::Temporary)
+            .find_map(|schema| self.find(schema, name))
+    }
+
+    // The name the table a name refers to is kept under, without the schema it may be
+    // qualified by. A schema without the table keeps the name whole, so nothing is found.
+    pub(super) fn kept_name<'a>(&self, name: &'a str) -> &'a str {
+        match schema_name(name) {
+            Some((schema, local)) if self.find(schema, local).is_some() => local,
+            _ => name,
+        }
+    }
+
+    // Whether a name refers to a temporary table
+    pub(super) fn is_temporary(&self, name: &str) -> bool {
+        self.resolve_in_schemas(name) == Some(Resolution::Temporary)
+    }
+
+    fn find(&self, schema: Schema, name: &str) -> Option<Resolution> {
+        match schema {
+            Schema::Temporary => self.temp_tables.table(name).map(|_| Resolution::Temporary),
+            Schema::Catalog if is_catalog_table(name) => Some(Resolution::Catalog),
+            Schema::Public if self.storage.table(name).is_some() => Some(Resolution::Table),
+            Schema::Public if self.views.contains(name) => Some(Resolution::View),
+            Schema::Catalog | Schema::Public => None,
+        }
+    }
+
+    // Names are looked up again after tables or views are created, dropped or renamed,
+    // or a transaction doing so is undone
+    pub(super) fn catalog_changed(&mut self) {
+        self.catalog_version += 1;
+    }
+}
+
+#[cfg(test)]
+mod resolver_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE people (id INT, name TEXT);
+        INSERT INTO people VALUES (1, 'ann');
+        INSERT INTO people VALUES (2, 'bob');
+        CREATE TABLE pets (owner INT);
+        INSERT INTO pets VALUES (1);
+        INSERT INTO pets VALUES (2);
+        INSERT INTO pets VALUES (5);
+        CREATE VIEW named AS SELECT id FROM people;";
+
+    #[test]
+    fn test_search_path() {
+        use Schema::*;
+        assert_eq!(
+            search_path("\"$user\", public"),
+            [Temporary, Catalog, Public]
+        );
+        assert_eq!(search_path("public, pg_temp"), [Catalog, Public, Temporary]);
+        assert_eq!(
+            search_path("pg_catalog, pg_temp"),
+            [Catalog, Temporary, Public]
+        );
+        assert_eq!(search_path("myschema"), [Temporary, Catalog, Public]);
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("CREATE TEMP TABLE people (id INT);").unwrap();
+        assert_eq!(mb.resolve("people"), Some(Resolution::Temporary));
+        assert_eq!(mb.resolve("public.people"), Some(Resolution::Table));
+        assert_eq!(mb.resolve("pg_temp.people"), Some(Resolution::Temporary));
+        assert_eq!(mb.resolve("pg_catalog.people"), None);
+        assert_eq!(mb.resolve("pg_catalog.pg_class"), Some(Resolution::Catalog));
+        assert_eq!(mb.resolve("public.named"), Some(Resolution::View));
+        assert_eq!(mb.kept_name("public.people"), "people");
+        assert_eq!(mb.kept_name("pg_catalog.people"), "pg_catalog.people");
+    }
+
+    #[test]
+    fn test_with_query_before_temporary_before_table() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TEMP TABLE people (id INT);
+            INSERT INTO people VALUES (7);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH people AS (SELECT 5 AS id) SELECT id FROM people;"
+            ),
+            ["5"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["7"]);
+        mb.eval_query("DROP TABLE people;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+
+        // A query only reads those before it, its own name is the table's
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH people AS (SELECT id + 10 AS id FROM people) SELECT id FROM people;"
+            ),
+            ["11", "12"]
+        );
+    }
+
+    #[test]
+    fn test_with_query_before_view() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH named AS (SELECT 9 AS id) SELECT id FROM named;"
+            ),
+            ["9"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH first AS (SELECT id FROM named), named AS (SELECT 9 AS id) \
+                SELECT id FROM first;"
+            ),
+            ["1", "2"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH named AS (SELECT id FROM named WHERE id > 1) SELECT id FROM named;"
+            ),
+            ["2"]
+        );
+    }
+
+    #[test]
+    fn test_temporary_before_view() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TEMP TABLE named (id INT);
+            INSERT INTO named VALUES (7);",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM named;"), ["7"]);
+        mb.eval_query("DROP TABLE named;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM named;"), ["1", "2"]);
+    }
+
+    // A view reads the tables of its query, whatever the statement reading it has of the
+    // same name
+    #[test]
+    fn test_view_reads_its_tables() {
+        let mut mb = backend(SETUP);
+        let with_query = "WITH people AS (SELECT 5 AS id)";
+        assert_eq!(
+            rows(&mut mb, &format!("{} SELECT id FROM named;", with_query)),
+            ["1", "2"]
+        );
+        // Both in FROM and in a subquery, as the people of the statement itself are
+        assert_eq!(
+            rows(
+                &mut mb,
+                &format!(
+                    "{} SELECT owner FROM pets WHERE owner IN (SELECT id FROM named);",
+                    with_query
+                )
+            ),
+            ["1", "2"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                &format!(
+                    "{} SELECT owner FROM pets \
+                    WHERE EXISTS (SELECT 1 FROM people WHERE people.id = pets.owner);",
+                    with_query
+                )
+            ),
+            ["5"]
+        );
+
+        mb.eval_query(
+            "CREATE TEMP TABLE people (id INT);
+            INSERT INTO people VALUES (7);",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM named;"), ["1", "2"]);
+        assert_eq!(
+            rows(&mut mb, "SELECT people.id, named.id FROM people, named;"),
+            ["7|1", "7|2"]
+        );
+    }
+
+    #[test]
+    fn test_temporary_before_catalog() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TEMP TABLE pg_namespace (id INT);
+            INSERT INTO pg_namespace VALUES (7);",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM pg_namespace;"), ["7"]);
+        mb.eval_query("SET search_path = 'pg_catalog, pg_temp';")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT nspname FROM pg_namespace;"),
+            ["pg_catalog", "public", "pg_temp_1"]
+        );
+    }
+
+    #[test]
+    fn test_schemas_in_search_path_order() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TEMP TABLE people (id INT);
+            INSERT INTO people VALUES (7);
+            SET search_path = 'public, pg_temp';",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT relnamespace FROM pg_class \
+                WHERE relname = 'people' AND pg_table_is_visible(oid);"
+            ),
+            ["2200"]
+        );
+        // DROP TABLE goes by the search path too
+        mb.eval_query("DROP TABLE pets;").unwrap();
+        assert!(mb.table("pets").is_none());
+
+        // Schemas that don't exist find nothing, public is still searched
+        mb.eval_query("SET search_path = myschema;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["7"]);
+        mb.eval_query("RESET search_path;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["7"]);
+    }
+
+    #[test]
+    fn test_search_path_lists() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TEMP TABLE people (id INT);
+            INSERT INTO people VALUES (7);
+            CREATE TEMP TABLE pg_namespace (id INT);
+            INSERT INTO pg_namespace VALUES (7);
+            SET search_path = a, public;",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["7"]);
+        assert_eq!(rows(&mut mb, "SELECT * FROM pg_namespace;"), ["7"]);
+
+        // The schemas listed are searched in the order they are listed in
+        mb.eval_query("SET search_path = a, public, pg_temp;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+        mb.eval_query("SET search_path = a, pg_temp, public;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["7"]);
+        mb.eval_query("SET search_path = a, pg_catalog, pg_temp;")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT nspname FROM pg_namespace;"),
+            ["pg_catalog", "public", "pg_temp_1"]
+        );
+        mb.eval_query("SET search_path = a, pg_temp, pg_catalog;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM pg_namespace;"), ["7"]);
+    }
+
+    #[test]
+    fn test_names_looked_up_again_after_changes() {
+        let mut mb = backend(SETUP);
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+        let cached = |mb: &MemoryBackend| {
+            let cache = mb.resolver.cache.borrow();
+            cache
+                .names
+                .values()
+                .filter_map(|names| names.get("people").copied())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cached(&mb), [Some(Resolution::Table)]);
+
+        // The table created is found, and no longer once the rollback undoes it
+        mb.eval_query(
+            "BEGIN;
+            CREATE TEMP TABLE people (id INT);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM people;"),
+            Vec::<String>::new()
+        );
+        assert_eq!(cached(&mb), [Some(Resolution::Temporary)]);
+        mb.eval_query("ROLLBACK;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+        assert_eq!(cached(&mb), [Some(Resolution::Table)]);
+
+        // Each search_path setting has its own
+        mb.eval_query("SET search_path = 'public, pg_temp';")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM people;"), ["1", "2"]);
+        assert_eq!(cached(&mb).len(), 2);
+    }
+
+    #[test]
+    fn test_trigger_reads_tables() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TABLE log (people BIGINT);
+            CREATE TRIGGER count_people AFTER INSERT ON pets FOR EACH ROW
+                EXECUTE (INSERT INTO log SELECT count(*) FROM people);",
+        )
+        .unwrap();
+        mb.eval_query("WITH people AS (SELECT 5 AS id) INSERT INTO pets SELECT id FROM people;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT people FROM log;"), ["2"]);
+    }
+}
@@ -0,0 +1,398 @@
+use super::nondeterminism::unseeded_u64;
+use super::MemoryBackend;
+use crate::ast::Statement;
+use crate::backend::{BackendError, EvalResult};
+use crate::sql_types::SqlValue;
+use std::time::Duration;
+
+// How a write that lost a serialization conflict is run again, see
+// `BackendConfig::retry_on_conflict`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConflictRetry {
+    // Runs of the statement in all, the first one included
+    pub max_attempts: u32,
+    // The wait before the first retry, doubling with every one after it
+    pub backoff: Duration,
+}
+
+impl ConflictRetry {
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        ConflictRetry {
+            max_attempts,
+            backoff,
+        }
+    }
+
+    // The wait after the `attempt`th run failed, somewhere between half and all of the
+    // backoff for it, so writers that conflicted don't all come back at once
+    pub(super) fn delay(&self, attempt: u32) -> Duration {
+        let full = self.backoff.saturating_mul(1 << (attempt - 1).min(16));
+        let full = full.as_nanos().min(u64::MAX as u128) as u64;
+        let half = full / 2;
+        Duration::from_nanos(half + unseeded_u64() % (full - half + 1))
+    }
+}
+
+// Where a write that conflicted is in its runs when a `Connection` runs the query. The
+// connection waits before the next one itself, with the backend unlocked for the writer
+// in the way, then runs the query on from the write.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct SessionRetry {
+    // Runs of the write that failed so far
+    pub(super) failed: u32,
+    // The wait before running it again, once it just conflicted
+    pub(super) delay: Option<Duration>,
+}
+
+// Errors another writer caused, that running the statement again may not meet
+fn is_conflict(err: &BackendError) -> bool {
+    matches!(err, BackendError::SerializationFailure(_))
+}
+
+impl MemoryBackend {
+    // With retries on, writes outside of transactions are run again when they conflict.
+    // Inside one the earlier statements would have to run again too, which is left to
+    // whoever started it. `Connection::eval_query` also runs again what the transaction
+    // of another connection kept out.
+    pub fn set_retry_on_conflict(&mut self, retry: Option<ConflictRetry>) {
+        self.conflict_retry = retry;
+    }
+
+    pub(super) fn retries_conflicts(&self, statement: &Statement) -> Option<ConflictRetry> {
+        match self.conflict_retry {
+            Some(retry) if self.pending_writes.is_none() && !statement.is_read_only() => {
+                Some(retry)
+            }
+            _ => None,
+        }
+    }
+
+    // Every run is a transaction of its own, so one that failed halfway leaves nothing
+    // behind for the next. The runs it took are in `last_timing`.
+    pub(super) fn eval_retrying(
+        &mut self,
+        statement: Statement,
+        retry: ConflictRetry,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        let mut attempt = self.session_retry.map_or(1, |retrying| retrying.failed + 1);
+        loop {
+            let result = self.transaction(|backend| backend.eval_attempt(statement.clone()));
+            match result {
+                Err(err) if is_conflict(&err) && attempt < retry.max_attempts => {
+                    let delay = retry.delay(attempt);
+                    // Waiting here would keep every other connection waiting as well
+                    if let Some(retrying) = &mut self.session_retry {
+                        *retrying = SessionRetry {
+                            failed: attempt,
+                            delay: Some(delay),
+                        };
+                        return Err(err);
+                    }
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => {
+                    self.finish_runs(attempt);
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Done with the statement after `attempt` runs, the next starts counting them anew
+    pub(super) fn finish_runs(&mut self, attempt: u32) {
+        self.timing.last.retries = attempt - 1;
+        if let Some(retrying) = &mut self.session_retry {
+            *retrying = SessionRetry::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+    use crate::backend::BackendError;
+    use crate::backend_memory::test_support::try_rows;
+    use crate::backend_memory::{
+        BackendConfig, Connection, Index, MemoryStorage, RowIter, StorageEngine, Table,
+    };
+    use crate::lexer::Token;
+    use std::borrow::Cow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier, Mutex};
+
+    // Tables in memory, but every `every`th row update fails as if another writer got to
+    // the row first
+    struct ConflictingStorage {
+        tables: MemoryStorage,
+        every: usize,
+        updates: AtomicUsize,
+    }
+
+    impl ConflictingStorage {
+        fn new(every: usize) -> Self {
+            ConflictingStorage {
+                tables: MemoryStorage::default(),
+                every,
+                updates: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl StorageEngine for ConflictingStorage {
+        fn table(&self, name: &str) -> Option<&Table> {
+            self.tables.table(name)
+        }
+
+        fn table_mut(&mut self, name: &str) -> Result<Option<&mut Table>, BackendError> {
+            self.tables.table_mut(name)
+        }
+
+        fn table_names(&self) -> Vec<String> {
+            self.tables.table_names()
+        }
+
+        fn create_table(&mut self, table: Table) -> Result<(), BackendError> {
+            self.tables.create_table(table)
+        }
+
+        fn drop_table(&mut self, name: &str) -> Result<Option<Table>, BackendError> {
+            self.tables.drop_table(name)
+        }
+
+        fn scan(&self, name: &str) -> Result<RowIter<'_>, BackendError> {
+            self.tables.scan(name)
+        }
+
+        fn scan_table(&self, name: &str) -> Result<Option<Cow<'_, Table>>, BackendError> {
+            self.tables.scan_table(name)
+        }
+
+        fn insert_row(&mut self, name: &str, row: Vec<SqlValue>) -> Result<usize, BackendError> {
+            self.tables.insert_row(name, row)
+        }
+
+        fn update_row(
+            &mut self,
+            name: &str,
+            position: usize,
+            row: Vec<SqlValue>,
+        ) -> Result<(), BackendError> {
+            if (self.updates.fetch_add(1, Ordering::Relaxed) + 1) % self.every == 0 {
+                return Err(BackendError::SerializationFailure(
+                    "Could not serialize access due to concurrent update.".to_string(),
+                ));
+            }
+            self.tables.update_row(name, position, row)
+        }
+
+        fn delete_row(&mut self, name: &str, position: usize) -> Result<(), BackendError> {
+            self.tables.delete_row(name, position)
+        }
+
+        fn create_index(&mut self, name: &str, index: Index) -> Result<(), BackendError> {
+            self.tables.create_index(name, index)
+        }
+
+        fn index_lookup(
+            &self,
+            name: &str,
+            index: &str,
+            operand: &Token,
+            key: &[u8],
+        ) -> Result<Vec<usize>, BackendError> {
+            self.tables.index_lookup(name, index, operand, key)
+        }
+
+        fn begin(&mut self) {
+            self.tables.begin()
+        }
+
+        fn end(&mut self, succeeded: bool) {
+            self.tables.end(succeeded)
+        }
+    }
+
+    fn backend(every: usize, retry: Option<ConflictRetry>) -> MemoryBackend {
+        let mut mb = MemoryBackend::with_storage(ConflictingStorage::new(every));
+        mb.set_retry_on_conflict(retry);
+        mb.eval_query(
+            "CREATE TABLE counter (id INT PRIMARY KEY, n INT);
+            INSERT INTO counter VALUES (1, 0), (2, 0);",
+        )
+        .unwrap();
+        mb
+    }
+
+    fn count(mb: &mut MemoryBackend) -> String {
+        match mb
+            .eval_query("SELECT n FROM counter WHERE id = 1;")
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => results.rows[0][0].to_string(),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    fn retry(max_attempts: u32) -> Option<ConflictRetry> {
+        Some(ConflictRetry::new(max_attempts, Duration::from_micros(50)))
+    }
+
+    #[test]
+    fn test_retry_on_conflict() {
+        // Without retries the conflict reaches the caller
+        let mut mb = backend(2, None);
+        mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        assert_eq!(
+            mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+                .unwrap_err()
+                .code(),
+            "40001"
+        );
+        assert_eq!(count(&mut mb), "1");
+
+        let mut mb = backend(2, retry(3));
+        mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        assert_eq!(mb.last_timing().retries, 0);
+        mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        assert_eq!(mb.last_timing().retries, 1);
+        assert_eq!(count(&mut mb), "2");
+
+        // The second row conflicts once the first changed, the run that failed halfway
+        // leaves the first row as it was
+        let mut mb = backend(4, retry(3));
+        mb.eval_query("UPDATE counter SET n = n + 1;").unwrap();
+        mb.eval_query("UPDATE counter SET n = n + 1;").unwrap();
+        assert_eq!(mb.last_timing().retries, 1);
+        assert_eq!(count(&mut mb), "2");
+
+        // Every run conflicts, the statement gives up after the last
+        let mut mb = backend(1, retry(3));
+        assert_eq!(
+            mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+                .unwrap_err()
+                .code(),
+            "40001"
+        );
+        assert_eq!(mb.last_timing().retries, 2);
+        assert_eq!(count(&mut mb), "0");
+
+        // Inside a transaction the caller has to run it all again
+        let mut mb = backend(2, retry(3));
+        let result = mb.transaction(|mb| {
+            mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")?;
+            mb.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+        });
+        assert_eq!(result.unwrap_err().code(), "40001");
+        assert_eq!(mb.last_timing().retries, 0);
+        assert_eq!(count(&mut mb), "0");
+
+        let mb = MemoryBackend::with_config(
+            BackendConfig::new().retry_on_conflict(4, Duration::from_millis(10)),
+        );
+        assert_eq!(
+            mb.config().retry_on_conflict,
+            Some(ConflictRetry::new(4, Duration::from_millis(10)))
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = ConflictRetry::new(10, Duration::from_millis(8));
+        for (attempt, full) in [(1, 8), (2, 16), (4, 64)] {
+            let delay = retry.delay(attempt);
+            let full = Duration::from_millis(full);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+        // No overflow however many attempts failed
+        ConflictRetry::new(u32::MAX, Duration::MAX).delay(u32::MAX - 1);
+    }
+
+    #[test]
+    fn test_retry_waits_out_concurrent_transactions() {
+        let mut mb = MemoryBackend::new();
+        mb.set_retry_on_conflict(Some(ConflictRetry::new(12, Duration::from_millis(1))));
+        mb.eval_query(
+            "CREATE TABLE counter (id INT PRIMARY KEY, n INT);
+            INSERT INTO counter VALUES (1, 0);",
+        )
+        .unwrap();
+        let backend = Arc::new(Mutex::new(mb));
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+
+        first
+            .eval_query("BEGIN; UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        let started = Arc::new(Barrier::new(2));
+        let writer = {
+            let started = started.clone();
+            std::thread::spawn(move || {
+                started.wait();
+                second.eval_query("UPDATE counter SET n = n + 10 WHERE id = 1;")?;
+                second.with_session(|backend| Ok(backend.last_timing().retries))
+            })
+        };
+
+        // The transaction goes on while the other write waits for it to end
+        started.wait();
+        std::thread::sleep(Duration::from_millis(20));
+        first
+            .eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        first.eval_query("COMMIT;").unwrap();
+        let retries = writer.join().unwrap().unwrap();
+        assert!(retries > 0);
+        assert_eq!(count(&mut backend.lock().unwrap()), "12");
+
+        // Without retries the transaction's conflict reaches the caller
+        backend.lock().unwrap().set_retry_on_conflict(None);
+        let mut second = Connection::open(backend.clone());
+        first.eval_query("BEGIN;").unwrap();
+        let err = second
+            .eval_query("UPDATE counter SET n = n + 10 WHERE id = 1;")
+            .unwrap_err();
+        assert_eq!(err.code(), "55P03");
+        first.eval_query("COMMIT;").unwrap();
+    }
+
+    #[test]
+    fn test_connection_retries_with_backend_unlocked() {
+        let backend = Arc::new(Mutex::new(backend(3, retry(3))));
+        let mut connection = Connection::open(backend.clone());
+        connection
+            .eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+
+        // The conflict of the second statement doesn't run the first again
+        connection
+            .eval_query(
+                "UPDATE counter SET n = n + 1 WHERE id = 2;
+                UPDATE counter SET n = n + 1 WHERE id = 1;",
+            )
+            .unwrap();
+        let retries = connection
+            .with_session(|backend| Ok(backend.last_timing().retries))
+            .unwrap();
+        assert_eq!(retries, 1);
+        assert_eq!(
+            try_rows(&mut connection, "SELECT n FROM counter ORDER BY id;").unwrap(),
+            ["2", "1"]
+        );
+
+        // Writes run by `with_session` leave their conflicts to the caller
+        connection
+            .eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            .unwrap();
+        let err = connection
+            .with_session(|backend| {
+                backend.eval_query("UPDATE counter SET n = n + 1 WHERE id = 1;")
+            })
+            .unwrap_err();
+        assert_eq!(err.code(), "40001");
+    }
+}
@@ -0,0 +1,304 @@
+use super::nondeterminism::{random_u64, SeededRng};
+use super::{MemoryBackend, Table};
+use crate::ast::{Expression, TableSample};
+use crate::backend::BackendError;
+use crate::sql_types::{SqlNumeric, SqlType, SqlValue};
+
+// The rows SYSTEM keeps or skips together, standing in for the pages Postgres samples
+const SYSTEM_BLOCK_ROWS: usize = 100;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum SampleMethod {
+    Bernoulli,
+    System,
+}
+
+impl SampleMethod {
+    fn name(&self) -> &'static str {
+        match self {
+            SampleMethod::Bernoulli => "bernoulli",
+            SampleMethod::System => "system",
+        }
+    }
+}
+
+// A TABLESAMPLE with its arguments worked out
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Sampling {
+    method: SampleMethod,
+    percentage: f64,
+    seed: Option<f64>,
+}
+
+impl Sampling {
+    // What EXPLAIN shows under the scan
+    pub(super) fn detail(&self) -> String {
+        match self.seed {
+            Some(seed) => format!(
+                "Sampling: {} ({}) REPEATABLE ({})",
+                self.method.name(),
+                self.percentage,
+                seed
+            ),
+            None => format!("Sampling: {} ({})", self.method.name(), self.percentage),
+        }
+    }
+
+    // The rows picked from those of `table`. BERNOULLI decides on every row by itself,
+    // SYSTEM on whole blocks of rows stored together. With a seed the same table gives the
+    // same rows on every run, without one they come from the random numbers of the
+    // backend, which are seeded in deterministic mode.
+    pub(super) fn sample(&self, table: &Table) -> Table {
+        let rng = self.seed.map(|seed| SeededRng::new(seed.to_bits()));
+        let fraction = self.percentage / 100.0;
+        let picked = || {
+            let bits = match &rng {
+                Some(rng) => rng.next(),
+                None => random_u64(),
+            };
+            ((bits >> 11) as f64 / (1u64 << 53) as f64) < fraction
+        };
+        let block = match self.method {
+            SampleMethod::Bernoulli => 1,
+            SampleMethod::System => SYSTEM_BLOCK_ROWS,
+        };
+
+        let mut rows = vec![];
+        for rows_block in table.rows.chunks(block) {
+            if picked() {
+                rows.extend_from_slice(rows_block);
+            }
+        }
+        Table {
+            name: table.name.clone(),
+            columns: table.columns.clone(),
+            column_types: table.column_types.clone(),
+            rows,
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Works out the arguments of a TABLESAMPLE, which can't read any column
+    pub(super) fn sampling(&self, sample: &TableSample) -> Result<Sampling, BackendError> {
+        let method = match sample.method.as_str() {
+            "bernoulli" => SampleMethod::Bernoulli,
+            "system" => SampleMethod::System,
+            name => {
+                return Err(BackendError::UndefinedObject(format!(
+                    "Tablesample method \"{}\" doesn't exist.",
+                    name
+                )))
+            }
+        };
+        let percentage = match self.sample_argument(&sample.percentage)? {
+            Some(percentage) if (0.0..=100.0).contains(&percentage) => percentage,
+            Some(_) => {
+                return Err(BackendError::InvalidTablesampleArgument(
+                    "Sample percentage must be between 0 and 100.".to_string(),
+                ))
+            }
+            None => {
+                return Err(BackendError::InvalidTablesampleArgument(
+                    "TABLESAMPLE parameter cannot be null.".to_string(),
+                ))
+            }
+        };
+        let seed = match &sample.repeatable {
+            Some(repeatable) => match self.sample_argument(repeatable)? {
+                Some(seed) => Some(seed),
+                None => {
+                    return Err(BackendError::InvalidTablesampleRepeat(
+                        "TABLESAMPLE REPEATABLE parameter cannot be null.".to_string(),
+                    ))
+                }
+            },
+            None => None,
+        };
+        Ok(Sampling {
+            method,
+            percentage,
+            seed,
+        })
+    }
+
+    // The share of the rows of a table a sample is expected to keep, for estimating the
+    // size of joins. Samples whose arguments are wrong fail once the table is scanned.
+    pub(super) fn sampled_fraction(&self, sample: Option<&TableSample>) -> f64 {
+        match sample.map(|sample| self.sampling(sample)) {
+            Some(Ok(sampling)) => sampling.percentage / 100.0,
+            _ => 1.0,
+        }
+    }
+
+    // The value of a percentage or seed as a number, None for NULL
+    fn sample_argument(&self, argument: &Expression) -> Result<Option<f64>, BackendError> {
+        match self.evaluate_constant(argument)? {
+            SqlValue::Null => Ok(None),
+            value @ SqlValue::Numeric(_) => match value.to_type(SqlType::DoublePrecision)? {
+                SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => Ok(Some(value)),
+                _ => Ok(None),
+            },
+            value => Err(BackendError::DatatypeMismatch(format!(
+                "Argument of TABLESAMPLE must be a number, not {}.",
+                value.get_type()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod sample_tests {
+    use crate::backend_memory::test_support::{backend, backend_with, rows};
+    use crate::backend_memory::{BackendConfig, MemoryBackend};
+
+    // A table of 10000 rows, numbered from 1
+    const SETUP: &str = "CREATE TABLE big AS SELECT n AS id FROM generate_series(1, 10000) AS g(n);
+        CREATE INDEX big_id ON big (id);";
+
+    fn count(mb: &mut MemoryBackend, query: &str) -> usize {
+        rows(mb, query)[0].parse().unwrap()
+    }
+
+    #[test]
+    fn test_table_sample() {
+        let mut mb = backend(SETUP);
+        for method in &["BERNOULLI", "SYSTEM"] {
+            let sampled = count(
+                &mut mb,
+                &format!("SELECT count(*) FROM big TABLESAMPLE {} (50);", method),
+            );
+            assert!((2000..8000).contains(&sampled), "{}: {}", method, sampled);
+            assert_eq!(
+                count(
+                    &mut mb,
+                    &format!("SELECT count(*) FROM big TABLESAMPLE {} (0);", method)
+                ),
+                0
+            );
+            assert_eq!(
+                count(
+                    &mut mb,
+                    &format!("SELECT count(*) FROM big TABLESAMPLE {} (100);", method)
+                ),
+                10000
+            );
+        }
+        // SYSTEM keeps rows stored together, a sample is made of whole blocks of them
+        let ids = rows(&mut mb, "SELECT id FROM big TABLESAMPLE SYSTEM (50);");
+        assert_eq!(ids.len() % 100, 0);
+        assert!(ids
+            .chunks(100)
+            .all(|block| block[0].parse::<usize>().unwrap() % 100 == 1));
+
+        // The same seed picks the same rows, another seed others
+        let query = "SELECT id FROM big b TABLESAMPLE BERNOULLI (1) REPEATABLE (42);";
+        let sample = rows(&mut mb, query);
+        assert_eq!(rows(&mut mb, query), sample);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT id FROM big TABLESAMPLE BERNOULLI (0.5 + 0.5) REPEATABLE (42.0);"
+            ),
+            sample
+        );
+        assert_ne!(
+            rows(
+                &mut mb,
+                "SELECT id FROM big TABLESAMPLE BERNOULLI (1) REPEATABLE (43);"
+            ),
+            sample
+        );
+
+        // Rows are sampled first and filtered after, an index on the condition is not used
+        let even: Vec<String> = sample
+            .iter()
+            .filter(|id| id.parse::<usize>().unwrap() % 2 == 0)
+            .cloned()
+            .collect();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT id FROM big TABLESAMPLE BERNOULLI (1) REPEATABLE (42) WHERE id % 2 = 0;"
+            ),
+            even
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                &format!(
+                    "SELECT id FROM big TABLESAMPLE BERNOULLI (1) REPEATABLE (42) WHERE id = {};",
+                    sample[0]
+                )
+            ),
+            vec![sample[0].clone()]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "EXPLAIN SELECT id FROM big b TABLESAMPLE BERNOULLI (1) REPEATABLE (42) \
+                WHERE id = 5;"
+            ),
+            vec![
+                "Result",
+                "  ->  Sample Scan on big b",
+                "        Sampling: bernoulli (1) REPEATABLE (42)"
+            ]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "EXPLAIN SELECT id FROM big TABLESAMPLE SYSTEM (2.5);"
+            ),
+            vec![
+                "Result",
+                "  ->  Sample Scan on big",
+                "        Sampling: system (2.5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_sample_seeded_by_backend() {
+        // Without REPEATABLE the sample comes from the seed of the backend
+        let query = "SELECT id FROM big TABLESAMPLE BERNOULLI (1);";
+        let mut first = backend_with(BackendConfig::new().random_seed(7), SETUP);
+        let mut second = backend_with(BackendConfig::new().random_seed(7), SETUP);
+        assert_eq!(rows(&mut first, query), rows(&mut second, query));
+        let mut first = backend_with(BackendConfig::new().deterministic(true), SETUP);
+        let mut second = backend_with(BackendConfig::new().deterministic(true), SETUP);
+        assert_eq!(rows(&mut first, query), rows(&mut second, query));
+    }
+
+    #[test]
+    fn test_table_sample_errors() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            ("SELECT id FROM big TABLESAMPLE BERNOULLI (101);", "2202H"),
+            ("SELECT id FROM big TABLESAMPLE SYSTEM (-1);", "2202H"),
+            ("SELECT id FROM big TABLESAMPLE SYSTEM (NULL);", "2202H"),
+            (
+                "SELECT id FROM big TABLESAMPLE SYSTEM (1) REPEATABLE (NULL);",
+                "2202G",
+            ),
+            ("SELECT id FROM big TABLESAMPLE SYSTEM ('a');", "42804"),
+            ("SELECT id FROM big TABLESAMPLE SYSTEM (id);", "42703"),
+            ("SELECT id FROM big TABLESAMPLE RESERVOIR (1);", "42704"),
+            (
+                "EXPLAIN SELECT id FROM big TABLESAMPLE BERNOULLI (101);",
+                "2202H",
+            ),
+            ("SELECT id FROM big TABLESAMPLE BERNOULLI;", "42601"),
+            ("SELECT id FROM big TABLESAMPLE (1);", "42601"),
+        ];
+        for (query, code) in tests {
+            assert_eq!(mb.eval_query(query).unwrap_err().code(), code, "{}", query);
+        }
+    }
+}
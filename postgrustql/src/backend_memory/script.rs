@@ -0,0 +1,396 @@
+use std::io::{BufRead, BufReader, Read};
+
+use super::{MemoryBackend, QueryTiming};
+use crate::backend::{BackendError, EvalResult};
+use crate::lexer::{Lexer, SplitStatement, StatementSplitter, TokenContainer, TokenLocation};
+use crate::parser::{error_token, parse_tokens, ParsingError};
+use crate::sql_types::SqlValue;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ScriptError {
+    // Zero based index of the failed statement
+    pub statement: usize,
+    // Location of the error in the script, zero based like every TokenLocation
+    pub loc: TokenLocation,
+    // The start of the failed statement, empty when it isn't known
+    pub excerpt: String,
+    pub error: BackendError,
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Statement {} failed at line {}, column {}: {}",
+            self.statement + 1,
+            self.loc.line + 1,
+            self.loc.col + 1,
+            self.error
+        )?;
+        match self.excerpt.is_empty() {
+            true => Ok(()),
+            false => write!(f, "\nStatement: {}", self.excerpt),
+        }
+    }
+}
+
+pub struct ScriptProgress<'a> {
+    pub statement: usize,
+    // Location of the statement's first token in the script
+    pub loc: TokenLocation,
+    pub result: Result<&'a EvalResult<SqlValue>, &'a ScriptError>,
+}
+
+pub type ProgressCallback<'a> = Box<dyn FnMut(&ScriptProgress) + 'a>;
+
+#[derive(Default)]
+pub struct ScriptOptions<'a> {
+    // Undo every statement of the script if one of them fails
+    pub transaction: bool,
+    // Called after each statement is executed
+    pub on_progress: Option<ProgressCallback<'a>>,
+}
+
+impl MemoryBackend {
+    // Executes the statements read from `reader` one at a time, without loading the
+    // whole script in memory. Returns the number of statements executed.
+    pub fn execute_script<R: Read>(
+        &mut self,
+        reader: R,
+        mut opts: ScriptOptions,
+    ) -> Result<usize, ScriptError> {
+        if opts.transaction {
+            self.transaction(|backend| backend.run_script(reader, &mut opts))
+        } else {
+            self.run_script(reader, &mut opts)
+        }
+    }
+
+    fn run_script<R: Read>(
+        &mut self,
+        reader: R,
+        opts: &mut ScriptOptions,
+    ) -> Result<usize, ScriptError> {
+        let mut reader = BufReader::new(reader);
+        let mut splitter = StatementSplitter::new();
+        let mut line = String::new();
+        let mut executed = 0;
+
+        loop {
+            line.clear();
+            let read = match reader.read_line(&mut line) {
+                Ok(read) => read,
+                Err(err) => {
+                    return Err(ScriptError {
+                        statement: executed,
+                        loc: TokenLocation::new(),
+                        excerpt: String::new(),
+                        error: BackendError::Internal(format!("Failed to read script: {}", err)),
+                    });
+                }
+            };
+
+            if read == 0 {
+                let last = match splitter.finish() {
+                    Ok(last) => last,
+                    Err(err) => {
+                        return Err(ScriptError {
+                            statement: executed,
+                            loc: err.loc(),
+                            excerpt: String::new(),
+                            error: BackendError::SyntaxError(err.to_string()),
+                        });
+                    }
+                };
+                if let Some(statement) = last {
+                    self.run_script_statement(&statement, executed, opts)?;
+                    executed += 1;
+                }
+                return Ok(executed);
+            }
+
+            splitter.push_str(&line);
+            while let Some(statement) = splitter.next_statement() {
+                self.run_script_statement(&statement, executed, opts)?;
+                executed += 1;
+            }
+        }
+    }
+
+    fn run_script_statement(
+        &mut self,
+        statement: &SplitStatement,
+        index: usize,
+        opts: &mut ScriptOptions,
+    ) -> Result<(), ScriptError> {
+        let lexing = self.timing.now();
+        // Locations count from the start of the script, in errors as everywhere else
+        let tokens = lex_statement(statement);
+        let lex = self.timing.since(lexing);
+        let parsing = self.timing.now();
+        let parsed = tokens.and_then(|tokens| parse_tokens(&statement.text, tokens));
+        self.timing.last = QueryTiming {
+            lex,
+            parse: self.timing.since(parsing),
+            ..QueryTiming::default()
+        };
+        let result = match parsed {
+            Ok(ast) => {
+                let mut result = Err(BackendError::SyntaxError(
+                    "Expected a statement.".to_string(),
+                ));
+                for parsed in ast.statements {
+                    result = self.eval_statement(parsed);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result.map_err(|error| ScriptError {
+                    statement: index,
+                    loc: statement_location(statement, 0),
+                    excerpt: statement.excerpt(),
+                    error,
+                })
+            }
+            Err(err) => {
+                self.fail_transaction_block();
+                let loc = match &err {
+                    ParsingError::General { msg: _, cursor }
+                    | ParsingError::Delimiter { msg: _, cursor } => {
+                        statement_location(statement, *cursor)
+                    }
+                    ParsingError::Lexing { msg: _, loc } => *loc,
+                };
+                Err(ScriptError {
+                    statement: index,
+                    loc,
+                    excerpt: statement.excerpt(),
+                    error: BackendError::from(err),
+                })
+            }
+        };
+
+        if let Some(on_progress) = opts.on_progress.as_mut() {
+            on_progress(&ScriptProgress {
+                statement: index,
+                loc: statement_location(statement, 0),
+                result: result.as_ref(),
+            });
+        }
+
+        result.map(|_| ())
+    }
+}
+
+// The tokens of a statement that lexes cleanly, else all of its lexing errors at once
+fn lex_statement(statement: &SplitStatement) -> Result<Vec<TokenContainer>, ParsingError> {
+    let lexer = Lexer::new().at_origin(statement.loc);
+    let (tokens, errors) = lexer.lex_recovering(&statement.text);
+    match errors.first() {
+        Some(first) => Err(ParsingError::Lexing {
+            msg: errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            loc: first.loc(),
+        }),
+        None => Ok(tokens),
+    }
+}
+
+// Location in the script of the token at `cursor` of the statement
+fn statement_location(statement: &SplitStatement, cursor: usize) -> TokenLocation {
+    let tokens = match Lexer::new().at_origin(statement.loc).lex(&statement.text) {
+        Ok(tokens) => tokens,
+        Err(_) => return statement.loc,
+    };
+    // The same token parse errors name in their message
+    match error_token(&tokens, cursor) {
+        Some(token) => token.loc,
+        None => statement.loc,
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+    use std::cell::RefCell;
+
+    const SCRIPT: &str = "-- Setup; with a semicolon in a comment
+CREATE TABLE notes (id INT, body TEXT);
+/* multi line comment;
+   INSERT INTO notes VALUES (0, 'nope'); */
+INSERT INTO notes VALUES (1, 'first; second');
+INSERT INTO notes VALUES (2, 'it''s; quoted');   INSERT INTO notes VALUES (3, $$dollar; quoted$$);
+INSERT INTO notes VALUES (4, $tag$nested $$;$$ quotes$tag$)
+";
+
+    const BODIES: &str = "SELECT body FROM notes;";
+
+    #[test]
+    fn test_execute_script() {
+        let mut mb = MemoryBackend::new();
+        let progress = RefCell::new(vec![]);
+        let executed = mb
+            .execute_script(
+                SCRIPT.as_bytes(),
+                ScriptOptions {
+                    transaction: false,
+                    on_progress: Some(Box::new(|p: &ScriptProgress| {
+                        progress.borrow_mut().push((
+                            p.statement,
+                            p.loc.line,
+                            p.loc.col,
+                            p.result.is_ok(),
+                        ))
+                    })),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(executed, 5);
+        assert_eq!(
+            progress.into_inner(),
+            vec![
+                (0, 1, 0, true),
+                (1, 4, 0, true),
+                (2, 5, 0, true),
+                (3, 5, 49, true),
+                (4, 6, 0, true),
+            ]
+        );
+        assert_eq!(
+            rows(&mut mb, BODIES),
+            vec![
+                "first; second",
+                "it's; quoted",
+                "dollar; quoted",
+                "nested $$;$$ quotes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_script_errors() {
+        let mut mb = backend("CREATE TABLE notes (id INT, body TEXT);");
+
+        // Parse errors point at the tokens that failed to parse
+        let script = "INSERT INTO notes VALUES (1, 'a');\n\n  INSERT INTO notes VALUES (2 'b');";
+        let err = mb
+            .execute_script(script.as_bytes(), ScriptOptions::default())
+            .unwrap_err();
+        assert_eq!(err.statement, 1);
+        assert_eq!((err.loc.line, err.loc.col), (2, 30));
+        assert_eq!(rows(&mut mb, BODIES).len(), 1);
+
+        // Execution errors point at the statement, and transactions undo everything
+        let script = "INSERT INTO notes VALUES (2, 'b');\n/* ; */ SELECT id FROM missing;";
+        let err = mb
+            .execute_script(
+                script.as_bytes(),
+                ScriptOptions {
+                    transaction: true,
+                    ..ScriptOptions::default()
+                },
+            )
+            .unwrap_err();
+        assert_eq!(err.statement, 1);
+        assert_eq!((err.loc.line, err.loc.col), (1, 8));
+        assert_eq!(rows(&mut mb, BODIES).len(), 1);
+
+        let err = mb
+            .execute_script("SELECT 'abc;".as_bytes(), ScriptOptions::default())
+            .unwrap_err();
+        assert_eq!(err.statement, 0);
+
+        // Every lexing error of the statement is reported, and none of it runs
+        let script =
+            "INSERT INTO notes VALUES (3, 'c');\nINSERT INTO notes VALUES (4 ? 1.2.3, 'd');";
+        let err = mb
+            .execute_script(script.as_bytes(), ScriptOptions::default())
+            .unwrap_err();
+        assert_eq!(err.statement, 1);
+        assert_eq!((err.loc.line, err.loc.col), (1, 28));
+        assert_eq!(err.error.code(), "42601");
+        assert_eq!(
+            err.error.message(),
+            "Unable to lex token '?', at 2:29\nInvalid numeric literal 1.2.3, at 2:31"
+        );
+        assert_eq!(rows(&mut mb, BODIES).len(), 2);
+    }
+
+    #[test]
+    fn test_script_errors_point_into_the_script() {
+        // Ten statements over sixteen lines, the seventh failing on the thirteenth
+        let script = |seventh: &str| {
+            format!(
+                "CREATE TABLE t (id INT);\n\
+                INSERT INTO t VALUES (1);\n\
+                -- a comment\n\
+                INSERT INTO t VALUES (2);\n\
+                INSERT INTO t\n  VALUES (3);\n\n\
+                INSERT INTO t VALUES (4);\n\
+                /* two\n lines */ INSERT INTO t VALUES (5);\n\
+                SELECT id\n  FROM t\n  {};\n\
+                INSERT INTO t VALUES (7);\n\
+                INSERT INTO t VALUES (8);\n\
+                SELECT id FROM t;\n",
+                seventh
+            )
+        };
+
+        // Parse errors, and their messages, give positions in the script
+        let mut mb = MemoryBackend::new();
+        let err = mb
+            .execute_script(script("WHERE id >").as_bytes(), ScriptOptions::default())
+            .unwrap_err();
+        assert_eq!((err.statement, err.loc.line, err.loc.col), (6, 12, 11));
+        assert!(
+            err.error
+                .message()
+                .starts_with("[13, 12]: Expected right operand"),
+            "{}",
+            err.error
+        );
+        assert!(err
+            .to_string()
+            .starts_with("Statement 7 failed at line 13, column 12: "));
+        assert!(err
+            .to_string()
+            .ends_with("\nStatement: SELECT id FROM t WHERE id >"));
+
+        // So do lexing errors
+        let mut mb = MemoryBackend::new();
+        let err = mb
+            .execute_script(script("WHERE id ? 1").as_bytes(), ScriptOptions::default())
+            .unwrap_err();
+        assert_eq!((err.statement, err.loc.line, err.loc.col), (6, 12, 11));
+        assert_eq!(err.error.message(), "Unable to lex token '?', at 13:12");
+
+        // Execution errors point at the statement's first token
+        let mut mb = MemoryBackend::new();
+        let err = mb
+            .execute_script(
+                script("WHERE nope = 1").as_bytes(),
+                ScriptOptions::default(),
+            )
+            .unwrap_err();
+        assert_eq!((err.statement, err.loc.line, err.loc.col), (6, 10, 0));
+        assert!(err
+            .to_string()
+            .starts_with("Statement 7 failed at line 11, column 1: "));
+
+        // As a single query, the failed statement is located in it the same way
+        let mut mb = MemoryBackend::new();
+        let err = mb.eval_query(&script("WHERE nope = 1")).unwrap_err();
+        assert_eq!(err.code(), "42703");
+        assert_eq!(
+            err.message(),
+            "Statement 7 (line 11, column 1 to line 13, column 16) failed: \
+            nope: Column does not exist.\n\
+            Statement: SELECT id FROM t WHERE nope = 1"
+        );
+    }
+}
@@ -0,0 +1,979 @@
+use super::collation::given_collation;
+use super::join_order::split_conjuncts;
+use super::prepared::value_to_token;
+use super::timing::{source_joins, PlanBuilder};
+use super::{
+    apply_binary_operator, apply_quantified_operator, contains_aggregate, contains_window_function,
+    MemoryBackend, Table,
+};
+use crate::ast::*;
+use crate::backend::{BackendError, ERR_CORRELATED_SUBQUERY_NOT_SUPPORTED};
+use crate::lexer::Token;
+use crate::sql_types::{SqlNumeric, SqlText, SqlValue};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(super) enum SemiJoinKind {
+    Exists,
+    NotExists,
+    In,
+    NotIn,
+    Any,
+    All,
+}
+
+// A condition of WHERE testing a subquery for every row: EXISTS, NOT EXISTS, IN,
+// NOT IN, or a comparison with ANY or ALL of its values. A subquery reading columns of
+// the outer row runs once for every row, unless it only compares them for equality. It
+// then runs once, its rows go in a hash table and every outer row is looked up in it.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SemiJoin {
+    kind: SemiJoinKind,
+    pub(super) subquery: SelectStatement,
+    // What IN, NOT IN, ANY and ALL compare with the values of the subquery, and how
+    first: Option<Expression>,
+    operand: Token,
+    // The columns of the outer row the subquery reads
+    outer_columns: Vec<Expression>,
+    // The expressions of the outer row to look up and the subquery giving the rows of
+    // the hash table, None when the subquery runs for every row
+    pub(super) hashed: Option<(Vec<Expression>, SelectStatement)>,
+}
+
+impl SemiJoin {
+    // The expressions of the outer row it reads
+    pub(super) fn reads(&self) -> impl Iterator<Item = &Expression> {
+        self.first.iter().chain(&self.outer_columns)
+    }
+
+    pub(super) fn name(&self) -> String {
+        match self.kind {
+            SemiJoinKind::Exists | SemiJoinKind::In | SemiJoinKind::Any => "Hash Semi Join",
+            SemiJoinKind::NotExists | SemiJoinKind::NotIn | SemiJoinKind::All => "Hash Anti Join",
+        }
+        .to_string()
+    }
+
+    // Whether a row passes, given whether its keys were found among `rows`, None for
+    // keys with a NULL, which equal nothing. ANY and ALL are never hashed.
+    fn keeps(&self, found: Option<bool>, rows: &KeyedRows) -> bool {
+        match self.kind {
+            SemiJoinKind::Exists | SemiJoinKind::In | SemiJoinKind::Any => found == Some(true),
+            SemiJoinKind::NotExists | SemiJoinKind::All => found != Some(true),
+            // Even NULL is NOT IN an empty set, while nothing is NOT IN a set with a NULL
+            SemiJoinKind::NotIn => rows.total == 0 || (found == Some(false) && !rows.has_null),
+        }
+    }
+}
+
+// The FROM items of a subquery along with their columns, which tell the columns it
+// reads of its own tables from those of the outer row
+struct Scope {
+    sources: Vec<String>,
+    columns: Vec<String>,
+}
+
+impl Scope {
+    fn is_outer(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Literal(LiteralExpression {
+                literal: Token::IdentifierValue { value },
+            }) => !self.columns.iter().any(|column| column == value.as_str()),
+            Expression::TableColumn(TableColumn {
+                table_name: Some(source),
+                ..
+            }) => !self.sources.contains(source),
+            Expression::TableColumn(TableColumn {
+                col_name,
+                table_name: None,
+            }) => !self.columns.contains(col_name),
+            _ => false,
+        }
+    }
+
+    fn reads_outer(&self, expression: &Expression) -> bool {
+        let mut columns = vec![];
+        column_references(expression, &mut columns);
+        columns.iter().any(|column| self.is_outer(column))
+    }
+
+    fn reads_inner(&self, expression: &Expression) -> bool {
+        let mut columns = vec![];
+        column_references(expression, &mut columns);
+        columns.iter().any(|column| !self.is_outer(column))
+    }
+
+    // The columns of the outer row `select` reads, each once
+    fn outer_columns(&self, select: &SelectStatement) -> Vec<Expression> {
+        let mut columns = vec![];
+        for expression in select_expressions(select) {
+            column_references(expression, &mut columns);
+        }
+        let mut outer_columns: Vec<Expression> = vec![];
+        for column in columns {
+            if self.is_outer(column) && !outer_columns.contains(column) {
+                outer_columns.push(column.clone());
+            }
+        }
+        outer_columns
+    }
+}
+
+// The column references of an expression, subqueries left out
+pub(super) fn column_references<'a>(expression: &'a Expression, columns: &mut Vec<&'a Expression>) {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { .. },
+        })
+        | Expression::TableColumn(_) => columns.push(expression),
+        Expression::Binary(binary) => {
+            column_references(&binary.first, columns);
+            column_references(&binary.second, columns);
+        }
+        Expression::Unary(unary) => column_references(&unary.first, columns),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            column_references(data, columns)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in &call.args {
+                column_references(arg, columns);
+            }
+            if let Some(filter) = &call.filter {
+                column_references(filter, columns);
+            }
+            if let Some(over) = &call.over {
+                for exp in &over.partition_by {
+                    column_references(exp, columns);
+                }
+                for order_by in &over.order_by {
+                    column_references(&order_by.exp, columns);
+                }
+            }
+        }
+        Expression::Quantified(quantified) => column_references(&quantified.first, columns),
+        Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Literal(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+// Puts what `replace` gives in place of the column references it gives something for
+pub(super) fn substitute(
+    expression: &mut Expression,
+    replace: &impl Fn(&Expression) -> Option<Expression>,
+) {
+    if let Some(replacement) = replace(expression) {
+        *expression = replacement;
+        return;
+    }
+    match expression {
+        Expression::Binary(binary) => {
+            substitute(&mut binary.first, replace);
+            substitute(&mut binary.second, replace);
+        }
+        Expression::Unary(unary) => substitute(&mut unary.first, replace),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            substitute(data, replace)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                substitute(arg, replace);
+            }
+            if let Some(filter) = call.filter.as_mut() {
+                substitute(filter, replace);
+            }
+            if let Some(over) = call.over.as_mut() {
+                for exp in over.partition_by.iter_mut() {
+                    substitute(exp, replace);
+                }
+                for order_by in over.order_by.iter_mut() {
+                    substitute(&mut order_by.exp, replace);
+                }
+            }
+        }
+        Expression::Quantified(quantified) => substitute(&mut quantified.first, replace),
+        Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+pub(super) fn has_subquery(expression: &Expression) -> bool {
+    match expression {
+        Expression::SubSelect(_) | Expression::Exists(_) => true,
+        Expression::Quantified(quantified) => {
+            matches!(quantified.set, QuantifiedSet::SubSelect(_)) || has_subquery(&quantified.first)
+        }
+        Expression::Binary(binary) => has_subquery(&binary.first) || has_subquery(&binary.second),
+        Expression::Unary(unary) => has_subquery(&unary.first),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => has_subquery(data),
+        Expression::FunctionCall(call) => {
+            call.args.iter().any(has_subquery) || call.filter.as_deref().is_some_and(has_subquery)
+        }
+        Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_) => false,
+        Expression::Empty => false,
+    }
+}
+
+// The expressions of the select items, WHERE, GROUP BY, HAVING, ORDER BY and the join
+// conditions of a SELECT
+pub(super) fn select_expressions(select: &SelectStatement) -> Vec<&Expression> {
+    let mut expressions: Vec<&Expression> =
+        select.items.iter().map(|item| &item.expression).collect();
+    expressions.push(&select.where_clause);
+    expressions.extend(select.group_by.iter());
+    expressions.push(&select.having);
+    for order_by in &select.order_by {
+        expressions.push(&order_by.exp);
+    }
+    for source in &select.from {
+        expressions.extend(source_joins(source).iter().map(|join| &join.on));
+    }
+    expressions
+}
+
+pub(super) fn select_expressions_mut(select: &mut SelectStatement) -> Vec<&mut Expression> {
+    let mut expressions: Vec<&mut Expression> = select
+        .items
+        .iter_mut()
+        .map(|item| &mut item.expression)
+        .collect();
+    expressions.push(&mut select.where_clause);
+    expressions.extend(select.group_by.iter_mut());
+    expressions.push(&mut select.having);
+    for order_by in select.order_by.iter_mut() {
+        expressions.push(&mut order_by.exp);
+    }
+    for source in select.from.iter_mut() {
+        let joins = match source {
+            RowDataSource::Table { joins, .. }
+            | RowDataSource::SubSelect { joins, .. }
+            | RowDataSource::Function { joins, .. }
+            | RowDataSource::Values { joins, .. } => joins,
+        };
+        expressions.extend(joins.iter_mut().map(|join| &mut join.on));
+    }
+    expressions
+}
+
+// The conditions joined with AND, Empty for none
+fn and_all(conditions: Vec<Expression>) -> Expression {
+    conditions
+        .into_iter()
+        .reduce(|first, second| {
+            Expression::Binary(BinaryExpression {
+                first: Box::new(first),
+                second: Box::new(second),
+                operand: Token::And,
+            })
+        })
+        .unwrap_or(Expression::Empty)
+}
+
+// A constant of the value an outer column has, of the type of that value
+pub(super) fn value_expression(value: &SqlValue) -> Result<Expression, BackendError> {
+    let literal = Expression::Literal(LiteralExpression {
+        literal: value_to_token(value)?,
+    });
+    Ok(match value {
+        SqlValue::Null => literal,
+        value => Expression::Cast {
+            data: Box::new(literal),
+            typ: value.get_type(),
+        },
+    })
+}
+
+// For a subquery that reads the outer row only in conditions of WHERE like
+// `inner = outer`, the outer expressions to look up and the subquery giving the rows to
+// look them up in. Those conditions turn into select items, the rest of the subquery
+// runs once for all rows.
+fn decorrelate(
+    scope: &Scope,
+    kind: SemiJoinKind,
+    subquery: &SelectStatement,
+    first: Option<&Expression>,
+) -> Option<(Vec<Expression>, SelectStatement)> {
+    // A NULL makes NOT IN never hold, but only the NULLs of the rows the outer row
+    // picks out count, which a single hash table can't tell
+    if kind == SemiJoinKind::NotIn
+        || !subquery.group_by.is_empty()
+        || !subquery.having.is_empty()
+        || subquery.limit.is_some()
+        || subquery.offset.is_some()
+        || subquery.items.iter().any(|item| {
+            contains_aggregate(&item.expression) || contains_window_function(&item.expression)
+        })
+    {
+        return None;
+    }
+    for source in &subquery.from {
+        if source_joins(source)
+            .iter()
+            .any(|join| scope.reads_outer(&join.on))
+        {
+            return None;
+        }
+    }
+
+    let mut keys = vec![];
+    let mut items = vec![];
+    if let Some(first) = first {
+        match subquery.items.as_slice() {
+            [item] if !item.asterisk && !scope.reads_outer(&item.expression) => {
+                keys.push(first.clone());
+                items.push(SelectItem {
+                    expression: item.expression.clone(),
+                    as_clause: None,
+                    asterisk: false,
+                    asterisk_source: None,
+                    asterisk_modifiers: None,
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    let mut conjuncts = vec![];
+    split_conjuncts(&subquery.where_clause, &mut conjuncts);
+    let mut rest = vec![];
+    for conjunct in conjuncts {
+        if !scope.reads_outer(conjunct) {
+            rest.push(conjunct.clone());
+            continue;
+        }
+        let (inner, outer) = match conjunct {
+            Expression::Binary(binary) if binary.operand == Token::Equal => {
+                if !scope.reads_outer(&binary.first) && !scope.reads_inner(&binary.second) {
+                    (&binary.first, &binary.second)
+                } else if !scope.reads_outer(&binary.second) && !scope.reads_inner(&binary.first) {
+                    (&binary.second, &binary.first)
+                } else {
+                    return None;
+                }
+            }
+            _ => return None,
+        };
+        keys.push(outer.as_ref().clone());
+        items.push(SelectItem {
+            expression: inner.as_ref().clone(),
+            as_clause: None,
+            asterisk: false,
+            asterisk_source: None,
+            asterisk_modifiers: None,
+        });
+    }
+    if keys.is_empty() {
+        return None;
+    }
+
+    let mut inner = SelectStatement::new();
+    inner.items = items;
+    inner.from = subquery.from.clone();
+    inner.where_clause = and_all(rest);
+    Some((keys, inner))
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum KeyClass {
+    Integer,
+    Text,
+    Boolean,
+}
+
+// The values that are equal exactly when their sortable encodings are, which is what
+// lets them be hashed
+fn key_class(value: &SqlValue) -> Option<KeyClass> {
+    match value {
+        SqlValue::Numeric(SqlNumeric::SmallInt { .. })
+        | SqlValue::Numeric(SqlNumeric::Int { .. })
+        | SqlValue::Numeric(SqlNumeric::BigInt { .. }) => Some(KeyClass::Integer),
+        SqlValue::Text(SqlText::Text { .. }) => Some(KeyClass::Text),
+        SqlValue::Boolean(_) => Some(KeyClass::Boolean),
+        _ => None,
+    }
+}
+
+fn key_classes(values: &[SqlValue]) -> Option<Vec<KeyClass>> {
+    values.iter().map(key_class).collect()
+}
+
+pub(super) fn encode_keys(values: &[SqlValue]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.encode_sortable().bytes)
+        .collect()
+}
+
+fn keys_match(row: &[SqlValue], keys: &[SqlValue]) -> Result<bool, BackendError> {
+    for (value, key) in row.iter().zip(keys) {
+        if apply_binary_operator(&Token::Equal, value, key)? != SqlValue::Boolean(true) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// The rows of a subquery to look keys up in. Rows of the key classes of the first one
+// are hashed, those of other types are compared one by one.
+struct KeyedRows {
+    // The rows without NULLs, the only ones a key can equal
+    rows: Vec<Vec<SqlValue>>,
+    classes: Option<Vec<KeyClass>>,
+    hashed: HashSet<Vec<u8>>,
+    others: Vec<usize>,
+    total: usize,
+    has_null: bool,
+}
+
+impl KeyedRows {
+    fn new(all_rows: Vec<Vec<SqlValue>>) -> Self {
+        let mut keyed = KeyedRows {
+            rows: vec![],
+            classes: None,
+            hashed: HashSet::new(),
+            others: vec![],
+            total: all_rows.len(),
+            has_null: false,
+        };
+        for row in all_rows {
+            if row.iter().any(SqlValue::is_null) {
+                keyed.has_null = true;
+                continue;
+            }
+            match (&keyed.classes, key_classes(&row)) {
+                (None, Some(classes)) => {
+                    keyed.classes = Some(classes);
+                    keyed.hashed.insert(encode_keys(&row));
+                }
+                (Some(classes), Some(row_classes)) if *classes == row_classes => {
+                    keyed.hashed.insert(encode_keys(&row));
+                }
+                _ => keyed.others.push(keyed.rows.len()),
+            }
+            keyed.rows.push(row);
+        }
+        keyed
+    }
+
+    // Whether a row equals `keys`, None when one of them is NULL
+    fn find(&self, keys: &[SqlValue]) -> Result<Option<bool>, BackendError> {
+        if keys.iter().any(SqlValue::is_null) {
+            return Ok(None);
+        }
+        if self.classes.is_some() && self.classes == key_classes(keys) {
+            if self.hashed.contains(&encode_keys(keys)) {
+                return Ok(Some(true));
+            }
+            for idx in &self.others {
+                if keys_match(&self.rows[*idx], keys)? {
+                    return Ok(Some(true));
+                }
+            }
+            return Ok(Some(false));
+        }
+        for row in &self.rows {
+            if keys_match(row, keys)? {
+                return Ok(Some(true));
+            }
+        }
+        Ok(Some(false))
+    }
+}
+
+impl MemoryBackend {
+    // Takes the conditions of WHERE that run as semi joins out of it, giving them along
+    // with the conditions left. Those are the EXISTS and NOT EXISTS reading the outer
+    // row, and the IN and NOT IN that do or are hashed.
+    pub(super) fn plan_semi_joins(&self, where_clause: &Expression) -> (Vec<SemiJoin>, Expression) {
+        let mut conjuncts = vec![];
+        split_conjuncts(where_clause, &mut conjuncts);
+        let mut semi_joins = vec![];
+        let mut rest = vec![];
+        for conjunct in conjuncts {
+            match self.plan_semi_join(conjunct) {
+                Some(semi_join) => semi_joins.push(semi_join),
+                None => rest.push(conjunct.clone()),
+            }
+        }
+        if semi_joins.is_empty() {
+            return (semi_joins, where_clause.clone());
+        }
+        (semi_joins, and_all(rest))
+    }
+
+    fn plan_semi_join(&self, conjunct: &Expression) -> Option<SemiJoin> {
+        let (kind, subquery, first) = match conjunct {
+            Expression::Exists(subquery) => (SemiJoinKind::Exists, subquery, None),
+            Expression::Unary(UnaryExpression {
+                first,
+                operand: Token::Not,
+            }) => match first.as_ref() {
+                Expression::Exists(subquery) => (SemiJoinKind::NotExists, subquery, None),
+                _ => return None,
+            },
+            Expression::Quantified(QuantifiedExpression {
+                first,
+                operand,
+                all,
+                set: QuantifiedSet::SubSelect(subquery),
+            }) => {
+                let kind = match (operand, all) {
+                    (Token::Equal, false) => SemiJoinKind::In,
+                    (Token::NotEqual, true) => SemiJoinKind::NotIn,
+                    (_, false) => SemiJoinKind::Any,
+                    (_, true) => SemiJoinKind::All,
+                };
+                (kind, subquery, Some((first.as_ref(), operand)))
+            }
+            _ => return None,
+        };
+        let operand = first.map_or(Token::Empty, |(_, operand)| operand.clone());
+        let first = first.map(|(first, _)| first);
+        // Hashing compares values as they are, a collated IN runs its subquery in full
+        if first.is_some_and(|first| has_subquery(first) || given_collation(first).is_some()) {
+            return None;
+        }
+        let scope = self.scope(subquery)?;
+        let outer_columns = scope.outer_columns(subquery);
+        let correlated = !outer_columns.is_empty();
+        // The rest run once in full, the same as any other subquery
+        let is_exists = matches!(kind, SemiJoinKind::Exists | SemiJoinKind::NotExists);
+        let is_quantified = matches!(kind, SemiJoinKind::Any | SemiJoinKind::All);
+        if !correlated && (is_exists || is_quantified || !self.hash_semi_joins) {
+            return None;
+        }
+
+        let hashed = match (self.hash_semi_joins, correlated) {
+            (false, _) => None,
+            _ if is_quantified => None,
+            (true, true) => decorrelate(&scope, kind, subquery, first),
+            (true, false) => Some((vec![first?.clone()], subquery.as_ref().clone())),
+        };
+        Some(SemiJoin {
+            kind,
+            subquery: subquery.as_ref().clone(),
+            first: first.cloned(),
+            operand,
+            outer_columns,
+            hashed,
+        })
+    }
+
+    // None unless every FROM item of the subquery is a table and it has no subqueries of
+    // its own, the only case its columns are told from those of the outer row
+    fn scope(&self, select: &SelectStatement) -> Option<Scope> {
+        let mut scope = Scope {
+            sources: vec![],
+            columns: vec![],
+        };
+        for from in &select.from {
+            let joined = source_joins(from).iter().map(|join| &join.source);
+            for source in std::iter::once(from).chain(joined) {
+                match source {
+                    RowDataSource::Table {
+                        table_name,
+                        as_clause,
+                        ..
+                    } => {
+                        let table = self.table(table_name)?;
+                        scope
+                            .sources
+                            .push(as_clause.as_ref().unwrap_or(table_name).clone());
+                        scope.columns.extend(table.columns.iter().cloned());
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        if select_expressions(select).into_iter().any(has_subquery) {
+            return None;
+        }
+        Some(scope)
+    }
+
+    // Whether a subquery gives any row. Outside of the conditions of WHERE it runs once,
+    // so it can't read the outer row.
+    pub(super) fn exists(&self, subquery: &SelectStatement) -> Result<bool, BackendError> {
+        if let Some(scope) = self.scope(subquery) {
+            if !scope.outer_columns(subquery).is_empty() {
+                return Err(BackendError::FeatureNotSupported(
+                    ERR_CORRELATED_SUBQUERY_NOT_SUPPORTED.to_string(),
+                ));
+            }
+        }
+        Ok(!self.select(subquery.clone())?.rows.is_empty())
+    }
+
+    // The rows of `table` every semi join keeps
+    pub(super) fn semi_join(
+        &self,
+        semi_joins: &[SemiJoin],
+        table: &Table,
+        plan: &mut PlanBuilder,
+    ) -> Result<Table, BackendError> {
+        let mut kept: Vec<usize> = (0..table.rows.len()).collect();
+        for semi_join in semi_joins {
+            kept = match &semi_join.hashed {
+                Some((keys, inner)) => {
+                    self.hash_semi_join(semi_join, keys, inner, table, kept, plan)?
+                }
+                None => self.correlated_semi_join(semi_join, table, kept, plan)?,
+            };
+        }
+        Ok(Table {
+            column_types: table.column_types.clone(),
+            columns: table.columns.clone(),
+            indexes: vec![],
+            name: table.name.clone(),
+            rows: kept
+                .into_iter()
+                .map(|idx| table.rows[idx].clone())
+                .collect(),
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        })
+    }
+
+    fn hash_semi_join(
+        &self,
+        semi_join: &SemiJoin,
+        keys: &[Expression],
+        inner: &SelectStatement,
+        table: &Table,
+        rows: Vec<usize>,
+        plan: &mut PlanBuilder,
+    ) -> Result<Vec<usize>, BackendError> {
+        let results = self.select(inner.clone())?;
+        plan.take_subquery(&self.timing);
+        if results.columns.len() != keys.len() {
+            return Err(BackendError::SyntaxError(
+                "Subquery must return only one column".to_string(),
+            ));
+        }
+        let keyed = KeyedRows::new(results.rows);
+        plan.add(|| "Hash".to_string(), keyed.total, 1);
+
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|key| table.compile_expression(key))
+            .collect();
+        let mut kept = vec![];
+        for idx in rows {
+            let values = keys
+                .iter()
+                .map(|key| key.evaluate(&table.rows[idx]).map(Cow::into_owned))
+                .collect::<Result<Vec<_>, _>>()?;
+            if semi_join.keeps(keyed.find(&values)?, &keyed) {
+                kept.push(idx);
+            }
+        }
+        plan.add(|| semi_join.name(), kept.len(), 2);
+        Ok(kept)
+    }
+
+    // Runs the subquery for every row, with the values of the row in place of the outer
+    // columns it reads
+    fn correlated_semi_join(
+        &self,
+        semi_join: &SemiJoin,
+        table: &Table,
+        rows: Vec<usize>,
+        plan: &mut PlanBuilder,
+    ) -> Result<Vec<usize>, BackendError> {
+        let outer_columns: Vec<_> = semi_join
+            .outer_columns
+            .iter()
+            .map(|column| table.compile_expression(column))
+            .collect();
+        let first = semi_join
+            .first
+            .as_ref()
+            .map(|first| table.compile_expression(first));
+        let mut kept = vec![];
+        for idx in rows {
+            let row = &table.rows[idx];
+            let values = outer_columns
+                .iter()
+                .map(|column| value_expression(&*column.evaluate(row)?))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut subquery = semi_join.subquery.clone();
+            for expression in select_expressions_mut(&mut subquery) {
+                substitute(expression, &|column| {
+                    let idx = semi_join
+                        .outer_columns
+                        .iter()
+                        .position(|outer| outer == column)?;
+                    Some(values[idx].clone())
+                });
+            }
+            let holds = match (semi_join.kind, &first) {
+                (SemiJoinKind::Exists, _) => !self.select(subquery)?.rows.is_empty(),
+                (SemiJoinKind::NotExists, _) => self.select(subquery)?.rows.is_empty(),
+                (kind, Some(first)) => {
+                    let first_val = first.evaluate(row)?;
+                    let values = self.subquery_values(&subquery)?;
+                    let all = matches!(kind, SemiJoinKind::NotIn | SemiJoinKind::All);
+                    let verdict =
+                        apply_quantified_operator(&semi_join.operand, all, &first_val, &values)?;
+                    verdict == SqlValue::Boolean(true)
+                }
+                (_, None) => return Err(BackendError::Internal("Missing IN operand".to_string())),
+            };
+            if holds {
+                kept.push(idx);
+            }
+        }
+        plan.take_correlated_subplan(&self.timing);
+        Ok(kept)
+    }
+}
+
+#[cfg(test)]
+mod semi_join_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{backend, try_rows};
+
+    const SETUP: &str = "CREATE TABLE a (id INT, grp INT, name TEXT);
+        CREATE TABLE b (k INT, grp INT, label TEXT);
+        INSERT INTO a VALUES (1, 1, 'one'), (2, 1, 'two'), (3, 2, 'three'), (NULL, 2, 'none');
+        INSERT INTO b VALUES (1, 1, 'x'), (1, 2, 'y'), (3, 2, 'z'), (5, 3, 'w');";
+
+    #[test]
+    fn test_semi_joins() {
+        let tests = vec![
+            (
+                "SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k = a.id);",
+                vec!["one", "three"],
+            ),
+            (
+                "SELECT name FROM a WHERE NOT EXISTS (SELECT 1 FROM b WHERE k = id);",
+                vec!["two", "none"],
+            ),
+            (
+                "SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k = a.id AND b.grp = a.grp);",
+                vec!["one", "three"],
+            ),
+            (
+                "SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k = a.id AND label = 'y');",
+                vec!["one"],
+            ),
+            // Compared with something besides equality the subquery runs for every row
+            (
+                "SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k > a.id);",
+                vec!["one", "two", "three"],
+            ),
+            (
+                "SELECT name FROM a WHERE id IN (SELECT k FROM b);",
+                vec!["one", "three"],
+            ),
+            (
+                "SELECT name FROM a WHERE id IN (SELECT k FROM b WHERE b.grp = a.grp);",
+                vec!["one", "three"],
+            ),
+            (
+                "SELECT name FROM a WHERE id NOT IN (SELECT k FROM b);",
+                vec!["two"],
+            ),
+            (
+                "SELECT name FROM a WHERE id NOT IN (SELECT k FROM b WHERE b.grp = a.grp);",
+                vec!["two"],
+            ),
+            // Even NULL is NOT IN an empty set
+            (
+                "SELECT name FROM a WHERE id NOT IN (SELECT k FROM b WHERE k > 10);",
+                vec!["one", "two", "three", "none"],
+            ),
+            (
+                "SELECT name FROM a WHERE grp = 1 AND EXISTS (SELECT 1 FROM b WHERE b.grp = a.grp) AND id IN (SELECT k FROM b);",
+                vec!["one"],
+            ),
+            // Uncorrelated EXISTS runs once
+            (
+                "SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE k = 5) AND grp = 2;",
+                vec!["three", "none"],
+            ),
+            (
+                "SELECT name FROM a WHERE NOT EXISTS (SELECT 1 FROM b WHERE k = 6) AND grp = 2;",
+                vec!["three", "none"],
+            ),
+        ];
+        for hashed in ["on", "off"] {
+            let mut mb = backend(SETUP);
+            mb.eval_query(&format!("SET hash_semi_joins = {};", hashed))
+                .unwrap();
+            for (query, expected) in &tests {
+                assert_eq!(
+                    try_rows(&mut mb, query),
+                    Ok(expected.iter().map(|name| name.to_string()).collect()),
+                    "{} with hash_semi_joins {}",
+                    query,
+                    hashed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_not_in_null() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("INSERT INTO b VALUES (NULL, 4, 'n');")
+            .unwrap();
+        for hashed in ["on", "off"] {
+            mb.eval_query(&format!("SET hash_semi_joins = {};", hashed))
+                .unwrap();
+            // A NULL among the values makes NOT IN hold for no row
+            assert_eq!(
+                try_rows(
+                    &mut mb,
+                    "SELECT name FROM a WHERE id NOT IN (SELECT k FROM b);"
+                ),
+                Ok(vec![])
+            );
+            assert_eq!(
+                try_rows(
+                    &mut mb,
+                    "SELECT name FROM a WHERE id NOT IN (SELECT k FROM b WHERE b.grp = a.grp + 2);"
+                ),
+                Ok(vec!["one".to_string(), "two".to_string()])
+            );
+            assert_eq!(
+                try_rows(&mut mb, "SELECT name FROM a WHERE id IN (SELECT k FROM b);"),
+                Ok(vec!["one".to_string(), "three".to_string()])
+            );
+        }
+    }
+
+    #[test]
+    fn test_correlated_exists_outside_where() {
+        let mut mb = backend(SETUP);
+        let err = try_rows(
+            &mut mb,
+            "SELECT name FROM a WHERE grp = 2 OR EXISTS (SELECT 1 FROM b WHERE k = id);",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            BackendError::FeatureNotSupported(ERR_CORRELATED_SUBQUERY_NOT_SUPPORTED.to_string())
+        );
+        assert_eq!(
+            try_rows(
+                &mut mb,
+                "SELECT name FROM a WHERE grp = 2 OR EXISTS (SELECT 1 FROM b WHERE k = 5);"
+            ),
+            Ok(vec!["one", "two", "three", "none"]
+                .into_iter()
+                .map(String::from)
+                .collect())
+        );
+    }
+
+    #[test]
+    fn test_explain_semi_joins() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            (
+                "EXPLAIN SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k = a.id);",
+                vec![
+                    "Result",
+                    "  ->  Hash Semi Join",
+                    "        ->  Seq Scan on a",
+                    "        ->  Hash",
+                    "              ->  Result",
+                    "                    ->  Seq Scan on b",
+                ],
+            ),
+            (
+                "EXPLAIN SELECT name FROM a WHERE id NOT IN (SELECT k FROM b);",
+                vec![
+                    "Result",
+                    "  ->  Hash Anti Join",
+                    "        ->  Seq Scan on a",
+                    "        ->  Hash",
+                    "              ->  Result",
+                    "                    ->  Seq Scan on b",
+                ],
+            ),
+            (
+                "EXPLAIN SELECT name FROM a WHERE EXISTS (SELECT 1 FROM b WHERE b.k > a.id);",
+                vec![
+                    "Result",
+                    "  ->  Seq Scan on a",
+                    "  ->  SubPlan",
+                    "        ->  Result",
+                    "              ->  Seq Scan on b",
+                ],
+            ),
+        ];
+        for (query, expected) in tests {
+            let lines = match mb.eval_query(query).unwrap().pop() {
+                Some(EvalResult::Select { results, .. }) => results
+                    .rows
+                    .iter()
+                    .map(|row| row[0].to_string())
+                    .collect::<Vec<_>>(),
+                _ => panic!("Expected select results"),
+            };
+            assert_eq!(lines, expected, "{}", query);
+        }
+    }
+
+    // Tables of random values, NULLs among them, give the same rows hashed as they do
+    // with the subquery run for every row
+    #[test]
+    fn test_hashed_matches_naive() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |max: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % max
+        };
+        let queries = [
+            "SELECT id, x FROM o WHERE EXISTS (SELECT 1 FROM i WHERE i.x = o.x);",
+            "SELECT id, x FROM o WHERE NOT EXISTS (SELECT 1 FROM i WHERE i.x = o.x AND i.y = o.y);",
+            "SELECT id, x FROM o WHERE x IN (SELECT x FROM i);",
+            "SELECT id, x FROM o WHERE x IN (SELECT x FROM i WHERE i.y = o.y);",
+            "SELECT id, x FROM o WHERE x NOT IN (SELECT x FROM i);",
+            "SELECT id, x FROM o WHERE x NOT IN (SELECT x FROM i WHERE i.y = o.y);",
+            "SELECT id, x FROM o WHERE y NOT IN (SELECT y FROM i WHERE y > 2);",
+        ];
+        for _ in 0..20 {
+            let mut mb = MemoryBackend::new();
+            mb.eval_query("CREATE TABLE o (id INT, x INT, y INT); CREATE TABLE i (x INT, y INT);")
+                .unwrap();
+            let mut value = |null_odds: u64| match next(null_odds) {
+                0 => "NULL".to_string(),
+                _ => next(8).to_string(),
+            };
+            for id in 0..30 {
+                let (x, y) = (value(10), value(10));
+                mb.eval_query(&format!("INSERT INTO o VALUES ({}, {}, {});", id, x, y))
+                    .unwrap();
+            }
+            for _ in 0..12 {
+                let (x, y) = (value(25), value(25));
+                mb.eval_query(&format!("INSERT INTO i VALUES ({}, {});", x, y))
+                    .unwrap();
+            }
+            for query in &queries {
+                mb.eval_query("SET hash_semi_joins = on;").unwrap();
+                let hashed = try_rows(&mut mb, query);
+                mb.eval_query("SET hash_semi_joins = off;").unwrap();
+                assert_eq!(hashed, try_rows(&mut mb, query), "{}", query);
+            }
+        }
+    }
+}
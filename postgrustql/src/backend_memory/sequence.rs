@@ -0,0 +1,665 @@
+use super::MemoryBackend;
+use crate::ast::{CreateSequenceStatement, DropSequenceStatement};
+use crate::backend::BackendError;
+use crate::sql_types::{SqlNumeric, SqlValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+// A sequence of numbers handed out by nextval, `increment` apart from `min_value` to
+// `max_value`. Past the end it starts over at the other end when it cycles, and fails
+// otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Sequence {
+    increment: i64,
+    min_value: i64,
+    max_value: i64,
+    cycle: bool,
+    // The number last handed out, or the first one until one is
+    last_value: i64,
+    is_called: bool,
+    // The SERIAL column the sequence was made for, it goes along with the column
+    owner: Option<SequenceOwner>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SequenceOwner {
+    pub(super) table: String,
+    pub(super) column: String,
+    pub(super) is_temporary: bool,
+}
+
+impl Sequence {
+    fn next(&mut self, name: &str) -> Result<i64, BackendError> {
+        if !self.is_called {
+            self.is_called = true;
+            return Ok(self.last_value);
+        }
+        let next = match self.last_value.checked_add(self.increment) {
+            Some(next) if next >= self.min_value && next <= self.max_value => next,
+            _ if self.cycle && self.increment > 0 => self.min_value,
+            _ if self.cycle => self.max_value,
+            _ => {
+                let (bound, limit) = match self.increment > 0 {
+                    true => ("maximum", self.max_value),
+                    false => ("minimum", self.min_value),
+                };
+                return Err(BackendError::SequenceGeneratorLimitExceeded(format!(
+                    "nextval: reached {} value of sequence \"{}\" ({}).",
+                    bound, name, limit
+                )));
+            }
+        };
+        self.last_value = next;
+        Ok(next)
+    }
+
+    fn set(&mut self, name: &str, value: i64) -> Result<i64, BackendError> {
+        if value < self.min_value || value > self.max_value {
+            return Err(BackendError::NumericValueOutOfRange(format!(
+                "setval: value {} is out of bounds for sequence \"{}\" ({}..{}).",
+                value, name, self.min_value, self.max_value
+            )));
+        }
+        self.last_value = value;
+        self.is_called = true;
+        Ok(value)
+    }
+}
+
+type SequenceMap = HashMap<String, Arc<Mutex<Sequence>>>;
+
+// The sequences of the backend, by name. Creating and dropping them is undone along with
+// a failed transaction, the numbers taken from them are not: each sequence is shared with
+// the snapshots, which only hold on to which sequences there were.
+#[derive(Debug, Default)]
+pub(super) struct Sequences {
+    sequences: Arc<SequenceMap>,
+    // The sequences when each running transaction began, the innermost last
+    snapshots: Vec<Arc<SequenceMap>>,
+    session: SequenceSession,
+}
+
+// What nextval last gave in the session for each sequence, which currval gives back
+#[derive(Debug, Default, Clone)]
+pub(super) struct SequenceSession(Arc<Mutex<HashMap<String, i64>>>);
+
+impl Sequences {
+    pub(super) fn begin(&mut self) {
+        self.snapshots.push(self.sequences.clone());
+    }
+
+    pub(super) fn end(&mut self, succeeded: bool) {
+        if let Some(snapshot) = self.snapshots.pop() {
+            if !succeeded {
+                self.sequences = snapshot;
+            }
+        }
+    }
+
+    pub(super) fn swap_session(&mut self, session: &mut SequenceSession) {
+        std::mem::swap(&mut self.session, session);
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.sequences.contains_key(name)
+    }
+
+    // Names of the sequences, in no particular order
+    pub(super) fn names(&self) -> Vec<&str> {
+        self.sequences.keys().map(String::as_str).collect()
+    }
+
+    fn insert(&mut self, name: String, sequence: Sequence) {
+        Arc::make_mut(&mut self.sequences).insert(name, Arc::new(Mutex::new(sequence)));
+    }
+
+    fn remove(&mut self, name: &str) {
+        Arc::make_mut(&mut self.sequences).remove(name);
+        lock(&self.session.0).remove(name);
+    }
+
+    fn owner(&self, name: &str) -> Option<SequenceOwner> {
+        let sequence = self.sequences.get(name)?;
+        lock(sequence).owner.clone()
+    }
+
+    // The sequences made for the SERIAL columns of a table, or of one of its columns
+    fn owned_by(&self, table: &str, column: Option<&str>, is_temporary: bool) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .sequences
+            .iter()
+            .filter(|(_, sequence)| {
+                lock(sequence).owner.as_ref().is_some_and(|owner| {
+                    owner.table == table
+                        && owner.is_temporary == is_temporary
+                        && column.is_none_or(|column| owner.column == column)
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+// A sequence is left as it was by whatever panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// The sequences of the statement running on this thread, for the functions using them
+struct StatementSequences {
+    sequences: Arc<SequenceMap>,
+    session: SequenceSession,
+    read_only: bool,
+}
+
+thread_local! {
+    static STATEMENT_SEQUENCES: RefCell<Option<StatementSequences>> = const { RefCell::new(None) };
+}
+
+// Puts back the sequences of the statement that was running before when dropped
+pub(super) struct StatementSequencesGuard {
+    outer: Option<StatementSequences>,
+}
+
+impl Drop for StatementSequencesGuard {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        STATEMENT_SEQUENCES.with(|sequences| *sequences.borrow_mut() = outer);
+    }
+}
+
+// Leaves the sequences alone until the guard is dropped. Expressions evaluated on sample
+// values, only for the type of what they give, must not take numbers from them.
+pub(super) fn enter_sampling() -> StatementSequencesGuard {
+    let outer = STATEMENT_SEQUENCES.with(|sequences| sequences.borrow_mut().take());
+    StatementSequencesGuard { outer }
+}
+
+// What the sequence functions do outside of statements, where they are only evaluated
+// for their type
+const SAMPLE_VALUE: i64 = 1;
+
+pub(super) fn nextval(name: &str) -> Result<SqlValue, BackendError> {
+    with_sequence("nextval", name, true, |name, sequence, session| {
+        let value = sequence.next(name)?;
+        session.insert(name.to_string(), value);
+        Ok(value)
+    })
+}
+
+pub(super) fn currval(name: &str) -> Result<SqlValue, BackendError> {
+    with_sequence("currval", name, false, |name, _, session| {
+        match session.get(name) {
+            Some(value) => Ok(*value),
+            None => Err(BackendError::ObjectNotInPrerequisiteState(format!(
+                "currval of sequence \"{}\" is not yet defined in this session.",
+                name
+            ))),
+        }
+    })
+}
+
+pub(super) fn setval(name: &str, value: i64) -> Result<SqlValue, BackendError> {
+    with_sequence("setval", name, true, |name, sequence, session| {
+        sequence.set(name, value)?;
+        session.insert(name.to_string(), value);
+        Ok(value)
+    })
+}
+
+fn with_sequence(
+    function: &str,
+    name: &str,
+    writes: bool,
+    f: impl FnOnce(&str, &mut Sequence, &mut HashMap<String, i64>) -> Result<i64, BackendError>,
+) -> Result<SqlValue, BackendError> {
+    let name = sequence_name(name);
+    let value = STATEMENT_SEQUENCES.with(|sequences| {
+        let sequences = sequences.borrow();
+        let statement = match sequences.as_ref() {
+            Some(statement) => statement,
+            None => return Ok(SAMPLE_VALUE),
+        };
+        if writes && statement.read_only {
+            return Err(BackendError::ReadOnlySqlTransaction(format!(
+                "Cannot execute {}() in a read-only connection.",
+                function
+            )));
+        }
+        match statement.sequences.get(&name) {
+            Some(sequence) => f(&name, &mut lock(sequence), &mut lock(&statement.session.0)),
+            None => Err(BackendError::UndefinedTable(format!(
+                "Sequence \"{}\" doesn't exist.",
+                name
+            ))),
+        }
+    })?;
+    Ok(SqlValue::Numeric(SqlNumeric::BigInt { value }))
+}
+
+// The sequence a name given as text stands for, read like a name in a statement is: folded
+// to lower case unless it is quoted, and found in the public schema when that is given
+fn sequence_name(text: &str) -> String {
+    let parts = name_parts(text);
+    match parts.as_slice() {
+        [schema, name] if schema == "public" || schema == "pg_catalog" => name.clone(),
+        _ => parts.join("."),
+    }
+}
+
+// The parts of a name given as text separated by dots, folded to lower case unless quoted
+pub(super) fn name_parts(text: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                part.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut part)),
+            _ if quoted => part.push(c),
+            _ => part.extend(c.to_lowercase()),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+impl MemoryBackend {
+    // Makes the sequences of the backend those the functions called on this thread take
+    // numbers from until the guard is dropped
+    pub(super) fn enter_sequences(&self) -> StatementSequencesGuard {
+        let statement = StatementSequences {
+            sequences: self.sequences.sequences.clone(),
+            session: self.sequences.session.clone(),
+            read_only: self.read_only,
+        };
+        let outer = STATEMENT_SEQUENCES.with(|sequences| sequences.borrow_mut().replace(statement));
+        StatementSequencesGuard { outer }
+    }
+
+    // Without options, a sequence counts up by 1 from 1, and one counting down starts
+    // at -1
+    pub fn create_sequence(
+        &mut self,
+        statement: CreateSequenceStatement,
+    ) -> Result<bool, BackendError> {
+        if self.sequences.contains(&statement.name)
+            || self.views.contains(&statement.name)
+            || self.storage.table(&statement.name).is_some()
+        {
+            return Err(BackendError::DuplicateTable(format!(
+                "Relation \"{}\" already exists.",
+                statement.name
+            )));
+        }
+        let increment = statement.increment.unwrap_or(1);
+        if increment == 0 {
+            return Err(BackendError::InvalidParameterValue(
+                "INCREMENT must not be zero.".to_string(),
+            ));
+        }
+        let (min_value, max_value) = match increment > 0 {
+            true => (
+                statement.min_value.unwrap_or(1),
+                statement.max_value.unwrap_or(i64::MAX),
+            ),
+            false => (
+                statement.min_value.unwrap_or(i64::MIN),
+                statement.max_value.unwrap_or(-1),
+            ),
+        };
+        if min_value >= max_value {
+            return Err(BackendError::InvalidParameterValue(format!(
+                "MINVALUE ({}) must be less than MAXVALUE ({}).",
+                min_value, max_value
+            )));
+        }
+        let start = match increment > 0 {
+            true => statement.start.unwrap_or(min_value),
+            false => statement.start.unwrap_or(max_value),
+        };
+        if start < min_value || start > max_value {
+            return Err(BackendError::InvalidParameterValue(format!(
+                "START value ({}) must be between MINVALUE ({}) and MAXVALUE ({}).",
+                start, min_value, max_value
+            )));
+        }
+        self.sequences.insert(
+            statement.name,
+            Sequence {
+                increment,
+                min_value,
+                max_value,
+                cycle: statement.cycle,
+                last_value: start,
+                is_called: false,
+                owner: None,
+            },
+        );
+        Ok(true)
+    }
+
+    pub fn drop_sequence(
+        &mut self,
+        statement: DropSequenceStatement,
+    ) -> Result<bool, BackendError> {
+        if !self.sequences.contains(&statement.name) {
+            if statement.if_exists {
+                return Ok(false);
+            }
+            return Err(BackendError::UndefinedTable(format!(
+                "Sequence \"{}\" doesn't exist.",
+                statement.name
+            )));
+        }
+        if let Some(owner) = self.sequences.owner(&statement.name) {
+            return Err(BackendError::DependentObjectsStillExist(format!(
+                "Cannot drop sequence \"{}\" because column \"{}\" of table \"{}\" depends on it.",
+                statement.name, owner.column, owner.table
+            )));
+        }
+        self.sequences.remove(&statement.name);
+        Ok(true)
+    }
+
+    // A name for the sequence of a SERIAL column no relation has yet, the way Postgres
+    // picks it
+    pub(super) fn serial_sequence_name(&self, table: &str, column: &str) -> String {
+        let base = format!("{}_{}_seq", table, column);
+        let mut name = base.clone();
+        let mut suffix = 0;
+        while self.sequences.contains(&name)
+            || self.views.contains(&name)
+            || self.storage.table(&name).is_some()
+        {
+            suffix += 1;
+            name = format!("{}{}", base, suffix);
+        }
+        name
+    }
+
+    // The sequence of a SERIAL column, counting up from 1 to the largest value of the
+    // column type
+    pub(super) fn create_serial_sequence(&mut self, name: String, owner: SequenceOwner, max: i64) {
+        let sequence = Sequence {
+            increment: 1,
+            min_value: 1,
+            max_value: max,
+            cycle: false,
+            last_value: 1,
+            is_called: false,
+            owner: Some(owner),
+        };
+        self.sequences.insert(name, sequence);
+    }
+
+    // Drops the sequences of the SERIAL columns of a table that is dropped, or of a column
+    // dropped from it
+    pub(super) fn drop_owned_sequences(
+        &mut self,
+        table: &str,
+        column: Option<&str>,
+        is_temporary: bool,
+    ) {
+        for name in self.sequences.owned_by(table, column, is_temporary) {
+            self.sequences.remove(&name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod sequence_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::Connection;
+
+    #[test]
+    fn test_nextval_cycles() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE SEQUENCE s START 100 INCREMENT 5 MAXVALUE 110 MINVALUE 90 CYCLE;")
+            .unwrap();
+        let values: Vec<String> = (0..5)
+            .flat_map(|_| rows(&mut mb, "SELECT nextval('s');"))
+            .collect();
+        assert_eq!(values, ["100", "105", "110", "90", "95"]);
+        assert_eq!(rows(&mut mb, "SELECT currval('S');"), ["95"]);
+
+        // Counting down it starts over at the top
+        mb.eval_query("CREATE SEQUENCE down INCREMENT BY -2 MINVALUE -4 MAXVALUE -1 CYCLE;")
+            .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT nextval('down') FROM generate_series(1, 4) AS g(n);"
+            ),
+            ["-1", "-3", "-1", "-3"]
+        );
+    }
+
+    #[test]
+    fn test_nextval_reaches_maxvalue() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE SEQUENCE s START 999 MAXVALUE 1000;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT nextval('s');"), ["999"]);
+        assert_eq!(rows(&mut mb, "SELECT nextval('public.s');"), ["1000"]);
+        let err = mb.eval_query("SELECT nextval('s');").unwrap_err();
+        assert_eq!(
+            (err.code(), err.message()),
+            (
+                "2200H",
+                "nextval: reached maximum value of sequence \"s\" (1000)."
+            )
+        );
+        // It stays at the end
+        assert!(mb.eval_query("SELECT nextval('s');").is_err());
+        assert_eq!(rows(&mut mb, "SELECT currval('s');"), ["1000"]);
+
+        mb.eval_query("CREATE SEQUENCE down INCREMENT -1 MINVALUE -2 START -1;")
+            .unwrap();
+        let err = mb
+            .eval_query("SELECT nextval('down'); SELECT nextval('down'); SELECT nextval('down');")
+            .unwrap_err();
+        assert!(err
+            .message()
+            .contains("reached minimum value of sequence \"down\" (-2)."));
+    }
+
+    #[test]
+    fn test_currval_and_setval() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE SEQUENCE s;").unwrap();
+        let err = mb.eval_query("SELECT currval('s');").unwrap_err();
+        assert_eq!(
+            (err.code(), err.message()),
+            (
+                "55000",
+                "currval of sequence \"s\" is not yet defined in this session."
+            )
+        );
+        assert_eq!(rows(&mut mb, "SELECT nextval('s'), nextval('s');"), ["1|2"]);
+        assert_eq!(rows(&mut mb, "SELECT currval('s');"), ["2"]);
+        assert_eq!(rows(&mut mb, "SELECT setval('s', 40);"), ["40"]);
+        assert_eq!(rows(&mut mb, "SELECT currval('s');"), ["40"]);
+        assert_eq!(rows(&mut mb, "SELECT nextval('s');"), ["41"]);
+        assert_eq!(
+            mb.eval_query("SELECT setval('s', 0);").unwrap_err().code(),
+            "22003"
+        );
+        assert_eq!(rows(&mut mb, "SELECT nextval(NULL);"), ["NULL"]);
+        // Working out the type of what no rows give takes no number
+        mb.eval_query("CREATE TABLE empty (id INT);").unwrap();
+        assert!(rows(&mut mb, "SELECT nextval('s') FROM empty;").is_empty());
+        assert_eq!(rows(&mut mb, "SELECT currval('s');"), ["41"]);
+        assert_eq!(rows(&mut mb, "SELECT pg_typeof(nextval('s'));"), ["Bigint"]);
+
+        // Each session has its own currval
+        let backend = Arc::new(Mutex::new(mb));
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+        first.eval_query("SELECT nextval('s');").unwrap();
+        assert_eq!(
+            second
+                .eval_query("SELECT currval('s');")
+                .unwrap_err()
+                .code(),
+            "55000"
+        );
+        second.eval_query("SELECT nextval('s');").unwrap();
+        match first.eval_query("SELECT currval('s');").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.rows[0][0].to_string(), "43")
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_sequence_errors() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE SEQUENCE s; CREATE TABLE t (id INT);")
+            .unwrap();
+        let code = |mb: &mut MemoryBackend, query: &str| mb.eval_query(query).unwrap_err().code();
+        assert_eq!(code(&mut mb, "SELECT nextval('nope');"), "42P01");
+        assert_eq!(code(&mut mb, "CREATE SEQUENCE s;"), "42P07");
+        assert_eq!(code(&mut mb, "CREATE SEQUENCE t;"), "42P07");
+        assert_eq!(code(&mut mb, "CREATE TABLE s (id INT);"), "42P07");
+        assert_eq!(code(&mut mb, "CREATE SEQUENCE z INCREMENT 0;"), "22023");
+        assert_eq!(
+            code(&mut mb, "CREATE SEQUENCE z MINVALUE 5 MAXVALUE 5;"),
+            "22023"
+        );
+        assert_eq!(code(&mut mb, "CREATE SEQUENCE z START 0;"), "22023");
+        assert_eq!(code(&mut mb, "DROP SEQUENCE nope;"), "42P01");
+        assert_eq!(code(&mut mb, "SELECT nextval(1);"), "42883");
+
+        mb.eval_query("DROP SEQUENCE IF EXISTS nope; DROP SEQUENCE s;")
+            .unwrap();
+        assert_eq!(code(&mut mb, "SELECT nextval('s');"), "42P01");
+
+        // Reading a sequence doesn't change it, taking a number from it does
+        let backend = Arc::new(Mutex::new(mb));
+        backend
+            .lock()
+            .unwrap()
+            .eval_query("CREATE SEQUENCE r;")
+            .unwrap();
+        let mut read_only = Connection::open_read_only(backend);
+        assert_eq!(
+            read_only
+                .eval_query("SELECT nextval('r');")
+                .unwrap_err()
+                .code(),
+            "25006"
+        );
+        assert_eq!(
+            read_only
+                .eval_query("CREATE SEQUENCE q;")
+                .unwrap_err()
+                .code(),
+            "25006"
+        );
+    }
+
+    #[test]
+    fn test_sequences_in_transactions() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE SEQUENCE s;").unwrap();
+        let result: Result<(), BackendError> = mb.transaction(|mb| {
+            mb.eval_query("SELECT nextval('s'); SELECT nextval('s'); CREATE SEQUENCE new;")?;
+            Err(BackendError::Internal("rolled back".to_string()))
+        });
+        assert!(result.is_err());
+        // Numbers taken are not given back, sequences created are gone
+        assert_eq!(rows(&mut mb, "SELECT nextval('s');"), ["3"]);
+        assert_eq!(
+            mb.eval_query("SELECT nextval('new');").unwrap_err().code(),
+            "42P01"
+        );
+
+        // A sequence dropped comes back as far along as it got
+        let result: Result<(), BackendError> = mb.transaction(|mb| {
+            mb.eval_query("SELECT nextval('s'); DROP SEQUENCE s;")?;
+            Err(BackendError::Internal("rolled back".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(rows(&mut mb, "SELECT nextval('s');"), ["5"]);
+    }
+
+    #[test]
+    fn test_serial_columns() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id SERIAL, small SMALLSERIAL, big BIGSERIAL, name TEXT);
+            INSERT INTO t VALUES (DEFAULT, DEFAULT, DEFAULT, 'a');
+            INSERT INTO t VALUES (DEFAULT, DEFAULT, DEFAULT, 'b'),
+                (DEFAULT, DEFAULT, DEFAULT, 'c');",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT id, small, big, name FROM t;"),
+            ["1|1|1|a", "2|2|2|b", "3|3|3|c"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT pg_typeof(id), pg_typeof(small), pg_typeof(big) FROM t;"
+            )[0],
+            "Int|Smallint|Bigint"
+        );
+        // The sequences are real ones
+        assert_eq!(rows(&mut mb, "SELECT currval('t_id_seq');"), ["3"]);
+        assert_eq!(rows(&mut mb, "SELECT setval('t_id_seq', 10);"), ["10"]);
+        mb.eval_query("INSERT INTO t VALUES (DEFAULT, 7, 7, 'd');")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM t WHERE name = 'd';"), ["11"]);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT relname, relkind FROM pg_class WHERE relkind = 'S';"
+            ),
+            ["t_big_seq|S", "t_id_seq|S", "t_small_seq|S"]
+        );
+        assert_eq!(
+            mb.eval_query("INSERT INTO t VALUES (NULL, 1, 1, 'e');")
+                .unwrap_err()
+                .code(),
+            "23502"
+        );
+        assert_eq!(
+            mb.eval_query("DROP SEQUENCE t_id_seq;").unwrap_err().code(),
+            "2BP01"
+        );
+
+        // The sequences go along with the table
+        mb.eval_query("DROP TABLE t;").unwrap();
+        assert_eq!(
+            mb.eval_query("SELECT nextval('t_id_seq');")
+                .unwrap_err()
+                .code(),
+            "42P01"
+        );
+        // A name already taken gets a number
+        mb.eval_query("CREATE SEQUENCE u_id_seq; CREATE TABLE u (id SERIAL);")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT nextval('u_id_seq1');"), ["1"]);
+    }
+
+    #[test]
+    fn test_sequence_name() {
+        assert_eq!(sequence_name("Seq"), "seq");
+        assert_eq!(sequence_name("\"Seq\""), "Seq");
+        assert_eq!(sequence_name("public.\"S.q\""), "S.q");
+        assert_eq!(sequence_name("other.s"), "other.s");
+        assert_eq!(sequence_name("\"a\"\"b\""), "a\"b");
+    }
+}
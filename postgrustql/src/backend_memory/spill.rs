@@ -0,0 +1,689 @@
+use super::{MaterializedRows, MemoryBackend};
+use crate::backend::{BackendError, MemoryCell, MemoryCellData};
+use crate::sql_types::{SqlNumeric, SqlValue, ALL_TYPES};
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+
+// The files DISTINCT hashes its rows into once they don't fit in work_mem
+const DISTINCT_PARTITIONS: usize = 16;
+
+// How much a sort or DISTINCT may hold in memory before going on in temporary files, and
+// the directory those go in
+#[derive(Clone, Debug)]
+pub(super) struct SpillSettings {
+    // In bytes, estimated like the result limits are
+    work_mem: usize,
+    directory: PathBuf,
+}
+
+impl MemoryBackend {
+    // None while work_mem is not set, nothing is written to files then
+    pub(super) fn spill_settings(&self) -> Option<SpillSettings> {
+        let work_mem = self.work_mem?;
+        Some(SpillSettings {
+            work_mem: work_mem.saturating_mul(1024),
+            directory: self
+                .temp_directory
+                .clone()
+                .unwrap_or_else(std::env::temp_dir),
+        })
+    }
+}
+
+// The rows of a SELECT once through DISTINCT, in the order they come. Without ORDER BY the
+// OFFSET and LIMIT apply as they come in, since nothing reorders them after.
+pub(super) struct ResultRows {
+    materialized: MaterializedRows,
+    sorted: Option<SortedRows>,
+    rows: Vec<Vec<SqlValue>>,
+    offset: usize,
+    limit: usize,
+    skipped: usize,
+}
+
+impl ResultRows {
+    pub fn new(
+        materialized: MaterializedRows,
+        sorted: Option<SortedRows>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Self {
+        ResultRows {
+            materialized,
+            sorted,
+            rows: vec![],
+            offset: offset.unwrap_or(0),
+            limit: limit.unwrap_or(usize::MAX),
+            skipped: 0,
+        }
+    }
+
+    // Whether every row after would be left out
+    pub fn is_full(&self) -> bool {
+        self.sorted.is_none() && self.rows.len() >= self.limit
+    }
+
+    // The rows taken so far, all of them when sorting
+    pub fn len(&self) -> usize {
+        match &self.sorted {
+            Some(sorted) => sorted.len(),
+            None => self.rows.len(),
+        }
+    }
+
+    // `order_key` is the sortable encoding of the row's ORDER BY values when sorting
+    pub fn add(
+        &mut self,
+        row: Vec<SqlValue>,
+        order_key: Option<MemoryCellData>,
+    ) -> Result<(), BackendError> {
+        if let (Some(sorted), Some(order_key)) = (&mut self.sorted, order_key) {
+            self.materialized.add(&row)?;
+            return sorted.push(order_key, row);
+        }
+        if self.skipped < self.offset {
+            self.skipped += 1;
+            return Ok(());
+        }
+        if !self.is_full() {
+            self.materialized.add(&row)?;
+            self.rows.push(row);
+        }
+        Ok(())
+    }
+
+    // `sort` sorts rows that never left memory
+    pub fn finish(
+        self,
+        sort: impl FnOnce(&mut [(MemoryCellData, Vec<SqlValue>)]),
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        match self.sorted {
+            Some(sorted) => sorted.finish(self.offset, self.limit, sort),
+            None => Ok(self.rows),
+        }
+    }
+}
+
+// The rows ORDER BY sorts, each along with the encoding of its values. Once they take more
+// than work_mem they are sorted and written to a file, a run, and the next ones start
+// another. The runs are merged at the end, rows of the same values coming out in the
+// order they came in like in memory.
+pub(super) struct SortedRows {
+    spill: Option<SpillSettings>,
+    rows: Vec<(MemoryCellData, Vec<SqlValue>)>,
+    bytes: usize,
+    runs: Vec<SpillFile>,
+    count: usize,
+}
+
+impl SortedRows {
+    pub fn new(spill: Option<SpillSettings>) -> Self {
+        SortedRows {
+            spill,
+            rows: vec![],
+            bytes: 0,
+            runs: vec![],
+            count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn push(&mut self, key: MemoryCellData, row: Vec<SqlValue>) -> Result<(), BackendError> {
+        self.bytes += key.len() + row_bytes(&row);
+        self.rows.push((key, row));
+        self.count += 1;
+        match &self.spill {
+            Some(spill) if self.bytes > spill.work_mem => self.write_run(),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_run(&mut self) -> Result<(), BackendError> {
+        let directory = match &self.spill {
+            Some(spill) => spill.directory.clone(),
+            None => return Ok(()),
+        };
+        self.rows.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut run = SpillFile::create(&directory)?;
+        let mut record = vec![];
+        for (key, row) in self.rows.drain(..) {
+            record.clear();
+            encode_bytes(&mut record, &key);
+            encode_values(&mut record, &row);
+            run.write(&record)?;
+        }
+        self.bytes = 0;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    // The rows in order, those before `offset` and after `limit` of them left out
+    fn finish(
+        mut self,
+        offset: usize,
+        limit: usize,
+        sort: impl FnOnce(&mut [(MemoryCellData, Vec<SqlValue>)]),
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        if self.runs.is_empty() {
+            sort(&mut self.rows);
+            return Ok(self
+                .rows
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(|(_, row)| row)
+                .collect());
+        }
+        if !self.rows.is_empty() {
+            self.write_run()?;
+        }
+
+        let mut readers = Vec::with_capacity(self.runs.len());
+        for run in self.runs.iter_mut() {
+            readers.push(run.read()?);
+        }
+        let mut heads = BinaryHeap::new();
+        for (run, reader) in readers.iter_mut().enumerate() {
+            heads.extend(RunHead::read(reader, run)?);
+        }
+        let mut rows = vec![];
+        let mut skipped = 0;
+        while rows.len() < limit {
+            let head = match heads.pop() {
+                Some(head) => head,
+                None => break,
+            };
+            heads.extend(RunHead::read(&mut readers[head.run], head.run)?);
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            rows.push(head.row);
+        }
+        Ok(rows)
+    }
+}
+
+// The next row of a run. The heap of them gives the one that sorts first, and of equal
+// values the one of the earliest run.
+struct RunHead {
+    key: MemoryCellData,
+    row: Vec<SqlValue>,
+    run: usize,
+}
+
+impl RunHead {
+    fn read(reader: &mut SpillReader, run: usize) -> Result<Option<Self>, BackendError> {
+        let record = match reader.next()? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let mut fields = Fields::new(&record);
+        let key = fields.bytes()?.to_vec();
+        let row = fields.values()?;
+        Ok(Some(RunHead { key, row, run }))
+    }
+}
+
+impl Ord for RunHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.cmp(&self.key).then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for RunHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RunHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RunHead {}
+
+// Whether DISTINCT lets a row through
+pub(super) enum Distinct {
+    New,
+    Seen,
+    // Told at the end, see `DistinctRows::defer`
+    Deferred,
+}
+
+// The rows DISTINCT has let through, as they compare under the collations of the items.
+// Once they take more than work_mem grace hashing takes over: those rows and every one
+// after them go to one of a few files by the hash of their values, then the files are
+// gone through one at a time to find the first of each set of equal rows. The rows found
+// come out in the order they came in, like they do in memory.
+pub(super) struct DistinctRows {
+    spill: Option<SpillSettings>,
+    kept: Vec<Vec<SqlValue>>,
+    bytes: usize,
+    partitions: Vec<SpillFile>,
+    // Of the next row written to a partition, among all of them
+    position: u64,
+}
+
+impl DistinctRows {
+    pub fn new(spill: Option<SpillSettings>) -> Self {
+        DistinctRows {
+            spill,
+            kept: vec![],
+            bytes: 0,
+            partitions: vec![],
+            position: 0,
+        }
+    }
+
+    pub fn check(&mut self, keys: &[SqlValue]) -> Result<Distinct, BackendError> {
+        if !self.partitions.is_empty() {
+            return Ok(Distinct::Deferred);
+        }
+        if self.kept.iter().any(|row| row[..] == keys[..]) {
+            return Ok(Distinct::Seen);
+        }
+        self.bytes += row_bytes(keys);
+        self.kept.push(keys.to_vec());
+        if let Some(spill) = &self.spill {
+            if self.bytes > spill.work_mem {
+                let directory = spill.directory.clone();
+                self.write_kept(&directory)?;
+            }
+        }
+        Ok(Distinct::New)
+    }
+
+    // The rows let through so far go to the partitions to keep those after them out
+    fn write_kept(&mut self, directory: &Path) -> Result<(), BackendError> {
+        for _ in 0..DISTINCT_PARTITIONS {
+            self.partitions.push(SpillFile::create(directory)?);
+        }
+        let mut record = vec![];
+        for keys in std::mem::take(&mut self.kept) {
+            record.clear();
+            record.extend_from_slice(&self.position.to_be_bytes());
+            record.push(0);
+            encode_values(&mut record, &keys);
+            self.partitions[partition(&keys)].write(&record)?;
+            self.position += 1;
+        }
+        self.bytes = 0;
+        Ok(())
+    }
+
+    // Writes out a row DISTINCT tells apart from the others at the end, along with the
+    // encoding of its ORDER BY values if any
+    pub fn defer(
+        &mut self,
+        keys: &[SqlValue],
+        row: &[SqlValue],
+        order_key: Option<&[u8]>,
+    ) -> Result<(), BackendError> {
+        let mut record = self.position.to_be_bytes().to_vec();
+        record.push(1);
+        encode_values(&mut record, keys);
+        encode_values(&mut record, row);
+        if let Some(order_key) = order_key {
+            encode_bytes(&mut record, order_key);
+        }
+        self.partitions[partition(keys)].write(&record)?;
+        self.position += 1;
+        Ok(())
+    }
+
+    // Gives `add` each deferred row that is the first of its values, in the order they
+    // came in
+    pub fn finish(
+        mut self,
+        mut add: impl FnMut(Vec<SqlValue>, Option<MemoryCellData>) -> Result<(), BackendError>,
+    ) -> Result<(), BackendError> {
+        let directory = match &self.spill {
+            Some(spill) if !self.partitions.is_empty() => spill.directory.clone(),
+            _ => return Ok(()),
+        };
+        let mut firsts = Vec::with_capacity(self.partitions.len());
+        for partition in self.partitions.iter_mut() {
+            let mut reader = partition.read()?;
+            let mut found = SpillFile::create(&directory)?;
+            let mut seen: HashMap<u64, Vec<Vec<SqlValue>>> = HashMap::new();
+            while let Some(record) = reader.next()? {
+                let mut fields = Fields::new(&record);
+                fields.u64()?;
+                let deferred = fields.byte()? == 1;
+                let keys = fields.values()?;
+                let equal = seen.entry(hash_keys(&keys)).or_default();
+                if equal.iter().any(|other| other[..] == keys[..]) {
+                    continue;
+                }
+                equal.push(keys);
+                if deferred {
+                    found.write(&record)?;
+                }
+            }
+            firsts.push(found);
+        }
+
+        let mut readers = Vec::with_capacity(firsts.len());
+        for found in firsts.iter_mut() {
+            readers.push(found.read()?);
+        }
+        let mut heads = BinaryHeap::new();
+        for (idx, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = reader.next()? {
+                heads.push(Reverse((Fields::new(&record).u64()?, idx, record)));
+            }
+        }
+        while let Some(Reverse((_, idx, record))) = heads.pop() {
+            if let Some(next) = readers[idx].next()? {
+                heads.push(Reverse((Fields::new(&next).u64()?, idx, next)));
+            }
+            let mut fields = Fields::new(&record);
+            fields.u64()?;
+            fields.byte()?;
+            fields.values()?;
+            let row = fields.values()?;
+            let order_key = match fields.is_empty() {
+                true => None,
+                false => Some(fields.bytes()?.to_vec()),
+            };
+            add(row, order_key)?;
+        }
+        Ok(())
+    }
+}
+
+// A temporary file records are written to and then read back in the same order. It is
+// removed once dropped, so when the query fails as well as when it is done.
+struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl SpillFile {
+    fn create(directory: &Path) -> Result<Self, BackendError> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let name = format!(
+                "postgrustql_spill_{}_{}",
+                std::process::id(),
+                NEXT.fetch_add(1, atomic::Ordering::Relaxed)
+            );
+            let path = directory.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    return Ok(SpillFile {
+                        path,
+                        writer: BufWriter::new(file),
+                    })
+                }
+                // Left behind by a process of the same id that was killed
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(io_error(&path, err)),
+            }
+        }
+    }
+
+    fn write(&mut self, record: &[u8]) -> Result<(), BackendError> {
+        let len = (record.len() as u64).to_be_bytes();
+        self.writer
+            .write_all(&len)
+            .and_then(|_| self.writer.write_all(record))
+            .map_err(|err| io_error(&self.path, err))
+    }
+
+    // Reads the records written so far from the first on
+    fn read(&mut self) -> Result<SpillReader, BackendError> {
+        self.writer
+            .flush()
+            .map_err(|err| io_error(&self.path, err))?;
+        let file = File::open(&self.path).map_err(|err| io_error(&self.path, err))?;
+        Ok(SpillReader {
+            path: self.path.clone(),
+            reader: BufReader::new(file),
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct SpillReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+}
+
+impl SpillReader {
+    fn next(&mut self) -> Result<Option<Vec<u8>>, BackendError> {
+        let mut len = [0; 8];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(io_error(&self.path, err)),
+        }
+        let mut record = vec![0; u64::from_be_bytes(len) as usize];
+        self.reader
+            .read_exact(&mut record)
+            .map_err(|err| io_error(&self.path, err))?;
+        Ok(Some(record))
+    }
+}
+
+fn io_error(path: &Path, err: io::Error) -> BackendError {
+    BackendError::IoError(format!(
+        "Could not use temporary file \"{}\": {}.",
+        path.display(),
+        err
+    ))
+}
+
+// Estimated like the result limits estimate rows
+fn row_bytes(row: &[SqlValue]) -> usize {
+    row.iter().map(SqlValue::encoded_len).sum()
+}
+
+fn partition(keys: &[SqlValue]) -> usize {
+    (hash_keys(keys) % DISTINCT_PARTITIONS as u64) as usize
+}
+
+// Values that are equal hash the same, zeros of either sign too
+fn hash_keys(keys: &[SqlValue]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for value in keys {
+        let bytes = match value {
+            SqlValue::Numeric(SqlNumeric::Real { value }) if *value == 0.0 => {
+                0.0f32.to_be_bytes().to_vec()
+            }
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) if *value == 0.0 => {
+                0.0f64.to_be_bytes().to_vec()
+            }
+            value => value.encode().bytes,
+        };
+        (value.get_type() as u8).hash(&mut hasher);
+        bytes.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn encode_bytes(record: &mut Vec<u8>, bytes: &[u8]) {
+    record.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+    record.extend_from_slice(bytes);
+}
+
+// Each value as a byte for its type then its MemoryCell encoding, which holds NULLs of
+// any type as no bytes
+fn encode_values(record: &mut Vec<u8>, values: &[SqlValue]) {
+    record.extend_from_slice(&(values.len() as u64).to_be_bytes());
+    for value in values {
+        record.push(value.get_type() as u8);
+        encode_bytes(record, &value.encode().bytes);
+    }
+}
+
+// Reads back a record made with the encode functions, from its start on
+struct Fields<'a> {
+    record: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(record: &'a [u8]) -> Self {
+        Fields { record }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.record.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BackendError> {
+        if self.record.len() < len {
+            return Err(BackendError::Internal(
+                "Temporary file record is cut short.".to_string(),
+            ));
+        }
+        let (taken, rest) = self.record.split_at(len);
+        self.record = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, BackendError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, BackendError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], BackendError> {
+        let len = self.u64()? as usize;
+        self.take(len)
+    }
+
+    fn values(&mut self) -> Result<Vec<SqlValue>, BackendError> {
+        let len = self.u64()? as usize;
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            let type_byte = self.byte()?;
+            let typ = ALL_TYPES
+                .iter()
+                .find(|typ| **typ as u8 == type_byte)
+                .ok_or_else(|| {
+                    BackendError::Internal("Temporary file record has no such type.".to_string())
+                })?;
+            let cell = MemoryCell {
+                bytes: self.bytes()?.to_vec(),
+            };
+            let value = SqlValue::decode(&cell, *typ)
+                .map_err(|err| BackendError::Internal(err.to_string()))?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod spill_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::backend_memory::{push_order_key, BackendConfig};
+
+    // A directory of its own for every test, as they run at the same time
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "postgrustql_spill_tests_{}_{}",
+            std::process::id(),
+            name
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn files(directory: &Path) -> usize {
+        fs::read_dir(directory).unwrap().count()
+    }
+
+    #[test]
+    fn test_sorted_rows_merge_runs() {
+        let directory = directory("sorted");
+        let spill = SpillSettings {
+            work_mem: 64,
+            directory: directory.clone(),
+        };
+        let mut sorted = SortedRows::new(Some(spill));
+        for n in 0..100i32 {
+            let mut key = vec![];
+            let value = SqlValue::Numeric(SqlNumeric::Int { value: n % 10 });
+            push_order_key(&mut key, &value, false);
+            let row = vec![SqlValue::Numeric(SqlNumeric::Int { value: n })];
+            sorted.push(key, row).unwrap();
+        }
+        assert!(sorted.runs.len() > 1);
+        assert_eq!(files(&directory), sorted.runs.len());
+
+        let rows = sorted.finish(5, 20, |_| unreachable!()).unwrap();
+        // Descending, the rows of equal values in the order they were pushed
+        let expected: Vec<Vec<SqlValue>> = (0..10)
+            .rev()
+            .flat_map(|key| (0..10).map(move |n| key + n * 10))
+            .skip(5)
+            .take(20)
+            .map(|value| vec![SqlValue::Numeric(SqlNumeric::Int { value })])
+            .collect();
+        assert_eq!(rows, expected);
+        assert_eq!(files(&directory), 0);
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_spilled_results_match_in_memory() {
+        let directory = directory("queries");
+        let mut mb =
+            MemoryBackend::with_config(BackendConfig::new().temp_directory(directory.clone()));
+        mb.eval_query(
+            "CREATE TABLE items AS SELECT n AS id, n % 7 AS score, \
+            (n % 13)::TEXT AS name FROM generate_series(1, 300) AS g(n);",
+        )
+        .unwrap();
+        let queries = [
+            "SELECT id, name FROM items ORDER BY score;",
+            "SELECT id, score FROM items ORDER BY name DESC;",
+            "SELECT id FROM items ORDER BY score OFFSET 40 LIMIT 25;",
+            "SELECT id FROM items ORDER BY score DESC LIMIT 3;",
+            "SELECT id FROM items ORDER BY score DESC, name, id DESC;",
+            "SELECT DISTINCT name FROM items;",
+            "SELECT DISTINCT id / 3, name FROM items;",
+            "SELECT DISTINCT score, name FROM items OFFSET 10 LIMIT 30;",
+            "SELECT DISTINCT name, score FROM items ORDER BY score DESC OFFSET 3 LIMIT 20;",
+            "SELECT id FROM items WHERE score = 2 ORDER BY name;",
+        ];
+        let in_memory: Vec<Vec<String>> = queries.iter().map(|q| rows(&mut mb, q)).collect();
+
+        mb.eval_query("SET work_mem = 1;").unwrap();
+        for (query, expected) in queries.iter().zip(&in_memory) {
+            assert_eq!(&rows(&mut mb, query), expected, "{}", query);
+            assert_eq!(files(&directory), 0, "{}", query);
+        }
+
+        // The files go as well when the query fails after writing some
+        assert!(mb
+            .eval_query("SELECT 10 / (id - 250) FROM items ORDER BY name;")
+            .is_err());
+        assert_eq!(files(&directory), 0);
+        fs::remove_dir(&directory).unwrap();
+    }
+}
@@ -0,0 +1,859 @@
+use super::stored_row::StoredRow;
+use super::table_size::row_bytes;
+use super::{ColumnConstraints, Index, MemoryBackend, Table, TableSize, VacuumStats};
+use crate::backend::{BackendError, MemoryCellData};
+use crate::lexer::Token;
+use crate::sql_types::{SqlType, SqlValue};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// The rows of a table, in the order a scan reads them
+pub type RowIter<'a> = Box<dyn Iterator<Item = Vec<SqlValue>> + 'a>;
+
+// The rows a statement changes in a table, handed to the engine as one change
+#[derive(Clone, PartialEq, Debug)]
+pub enum RowWrites {
+    // Rows to add at the end of the table
+    Insert(Vec<Vec<SqlValue>>),
+    // Rows to change: their positions and their values before and after
+    Update(Vec<(usize, Vec<SqlValue>, Vec<SqlValue>)>),
+    // Rows to take out: their positions and their values
+    Delete(Vec<(usize, Vec<SqlValue>)>),
+}
+
+impl RowWrites {
+    // The rows added, the rows changed as they are after or the rows taken out
+    pub fn into_rows(self) -> Vec<Vec<SqlValue>> {
+        match self {
+            RowWrites::Insert(rows) => rows,
+            RowWrites::Update(rows) => rows.into_iter().map(|(_, _, new_row)| new_row).collect(),
+            RowWrites::Delete(rows) => rows.into_iter().map(|(_, row)| row).collect(),
+        }
+    }
+}
+
+// Where a backend keeps its tables. The executor reads and writes them only through this,
+// leaving how the rows are stored to the engine. A `Table` describes a table as the
+// executor needs it, its columns, constraints and indexes, along with its rows for
+// engines that keep them in memory. The rows of the others are read through `scan`.
+pub trait StorageEngine: Send + Sync {
+    fn table(&self, name: &str) -> Option<&Table>;
+
+    // The table to change its definition in place, an error for engines that can't
+    fn table_mut(&mut self, name: &str) -> Result<Option<&mut Table>, BackendError>;
+
+    fn table_names(&self) -> Vec<String>;
+
+    fn create_table(&mut self, table: Table) -> Result<(), BackendError>;
+
+    // Gives the table it dropped, None if there was no table of that name
+    fn drop_table(&mut self, name: &str) -> Result<Option<Table>, BackendError>;
+
+    fn scan(&self, name: &str) -> Result<RowIter<'_>, BackendError>;
+
+    // The table along with all of its rows, None if there is no table of that name
+    fn scan_table(&self, name: &str) -> Result<Option<Cow<'_, Table>>, BackendError> {
+        let mut table = match self.table(name) {
+            Some(table) => table.clone(),
+            None => return Ok(None),
+        };
+        table.rows = self.scan(name)?.map(StoredRow::from).collect();
+        Ok(Some(Cow::Owned(table)))
+    }
+
+    // Adds a row and its index entries, or nothing at all if it breaks an index
+    // constraint. Gives the position of the row among those of the table.
+    fn insert_row(&mut self, name: &str, row: Vec<SqlValue>) -> Result<usize, BackendError>;
+
+    fn update_row(
+        &mut self,
+        name: &str,
+        position: usize,
+        row: Vec<SqlValue>,
+    ) -> Result<(), BackendError>;
+
+    // Removes the row at `position`, those after it moving up by one
+    fn delete_row(&mut self, name: &str, position: usize) -> Result<(), BackendError>;
+
+    // Makes every change of a statement to the rows of a table, or none at all. By
+    // default they are made row by row, those made undone if one fails, rows taken out
+    // going back in at the end of the table.
+    fn write_rows(&mut self, name: &str, writes: &RowWrites) -> Result<(), BackendError> {
+        match writes {
+            RowWrites::Insert(rows) => {
+                let mut positions = Vec::with_capacity(rows.len());
+                for row in rows {
+                    match self.insert_row(name, row.clone()) {
+                        Ok(position) => positions.push(position),
+                        Err(err) => {
+                            for position in positions.into_iter().rev() {
+                                self.delete_row(name, position)?;
+                            }
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+            RowWrites::Update(rows) => {
+                for (done, (position, _, new_row)) in rows.iter().enumerate() {
+                    if let Err(err) = self.update_row(name, *position, new_row.clone()) {
+                        for (position, old_row, _) in rows[..done].iter().rev() {
+                            self.update_row(name, *position, old_row.clone())?;
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+            // From the last row up, so the rows still to go keep their positions
+            RowWrites::Delete(rows) => {
+                let mut rows: Vec<_> = rows.iter().collect();
+                rows.sort_by_key(|(position, _)| std::cmp::Reverse(*position));
+                for (done, (position, _)) in rows.iter().enumerate() {
+                    if let Err(err) = self.delete_row(name, *position) {
+                        for (_, row) in rows.iter().take(done) {
+                            self.insert_row(name, row.clone())?;
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Adds an index to the table, with the entries it was given
+    fn create_index(&mut self, name: &str, index: Index) -> Result<(), BackendError>;
+
+    // The positions of the rows whose value of the index compares with `key` as `operand`
+    // does, `key` being the sortable encoding of a value
+    fn index_lookup(
+        &self,
+        name: &str,
+        index: &str,
+        operand: &Token,
+        key: &[u8],
+    ) -> Result<Vec<usize>, BackendError>;
+
+    // How many rows the table has and what they and its indexes take. By default the rows
+    // are read to count them, engines keeping count give what they have.
+    fn table_size(&self, name: &str) -> Result<TableSize, BackendError> {
+        let mut size = match self.table(name) {
+            Some(table) => TableSize {
+                index_bytes: table.size().index_bytes,
+                ..TableSize::default()
+            },
+            None => return Err(no_table(name)),
+        };
+        for row in self.scan(name)? {
+            size.rows += 1;
+            size.heap_bytes += row_bytes(&StoredRow::new(&row));
+        }
+        Ok(size)
+    }
+
+    // Gives back the room rows taken out of the table left behind, in memory or on disk,
+    // the rows staying at their positions. Engines with nothing to reclaim keep the
+    // default.
+    fn vacuum(&mut self, _name: &str) -> Result<VacuumStats, BackendError> {
+        Ok(VacuumStats::default())
+    }
+
+    // A transaction starts. What changes after this is undone when it ends, unless it
+    // succeeded.
+    fn begin(&mut self);
+
+    fn end(&mut self, succeeded: bool);
+}
+
+// The tables held in memory, the engine of `MemoryBackend::new` and of temporary tables
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MemoryStorage {
+    tables: HashMap<String, Table>,
+    // The tables as they were when each of the running transactions began
+    snapshots: Vec<HashMap<String, Table>>,
+}
+
+impl MemoryStorage {
+    fn existing_mut(&mut self, name: &str) -> Result<&mut Table, BackendError> {
+        self.tables.get_mut(name).ok_or_else(|| no_table(name))
+    }
+}
+
+fn no_table(name: &str) -> BackendError {
+    BackendError::UndefinedTable(format!("Table \"{}\" doesn't exist.", name))
+}
+
+fn no_row(position: usize) -> BackendError {
+    BackendError::Internal(format!("Row {} doesn't exist.", position))
+}
+
+impl StorageEngine for MemoryStorage {
+    fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    fn table_mut(&mut self, name: &str) -> Result<Option<&mut Table>, BackendError> {
+        Ok(self.tables.get_mut(name))
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    // Tables may come with rows, what they take is counted from here on
+    fn create_table(&mut self, mut table: Table) -> Result<(), BackendError> {
+        table.recount_size();
+        self.tables.insert(table.name.clone(), table);
+        Ok(())
+    }
+
+    fn drop_table(&mut self, name: &str) -> Result<Option<Table>, BackendError> {
+        Ok(self.tables.remove(name))
+    }
+
+    fn scan(&self, name: &str) -> Result<RowIter<'_>, BackendError> {
+        match self.tables.get(name) {
+            Some(table) => Ok(Box::new(table.rows.iter().map(StoredRow::values))),
+            None => Err(no_table(name)),
+        }
+    }
+
+    // The rows are in the table already
+    fn scan_table(&self, name: &str) -> Result<Option<Cow<'_, Table>>, BackendError> {
+        Ok(self.tables.get(name).map(Cow::Borrowed))
+    }
+
+    fn insert_row(&mut self, name: &str, row: Vec<SqlValue>) -> Result<usize, BackendError> {
+        let table = self.existing_mut(name)?;
+        table.push_row(row)?;
+        Ok(table.rows.len() - 1)
+    }
+
+    fn update_row(
+        &mut self,
+        name: &str,
+        position: usize,
+        row: Vec<SqlValue>,
+    ) -> Result<(), BackendError> {
+        self.existing_mut(name)?.replace_row(position, row)
+    }
+
+    fn delete_row(&mut self, name: &str, position: usize) -> Result<(), BackendError> {
+        self.existing_mut(name)?.remove_rows(&[position])
+    }
+
+    // The table is changed in place and, if a change fails, put back as it was, rows
+    // taken out staying where they were as they only go once all can
+    fn write_rows(&mut self, name: &str, writes: &RowWrites) -> Result<(), BackendError> {
+        let table = self.existing_mut(name)?;
+        match writes {
+            RowWrites::Insert(rows) => {
+                let start = table.rows.len();
+                for row in rows {
+                    if let Err(err) = table.push_row(row.clone()) {
+                        table.truncate_rows(start);
+                        return Err(err);
+                    }
+                }
+                Ok(())
+            }
+            RowWrites::Update(rows) => {
+                for (done, (position, _, new_row)) in rows.iter().enumerate() {
+                    if let Err(err) = table.replace_row(*position, new_row.clone()) {
+                        // In reverse, each row going back to values no other row has
+                        for (position, old_row, _) in rows[..done].iter().rev() {
+                            table.replace_row(*position, old_row.clone())?;
+                        }
+                        return Err(err);
+                    }
+                }
+                Ok(())
+            }
+            RowWrites::Delete(rows) => {
+                let positions: Vec<usize> = rows.iter().map(|(position, _)| *position).collect();
+                table.remove_rows(&positions)
+            }
+        }
+    }
+
+    fn create_index(&mut self, name: &str, mut index: Index) -> Result<(), BackendError> {
+        index.recount_bytes();
+        self.existing_mut(name)?.indexes.push(index);
+        Ok(())
+    }
+
+    fn index_lookup(
+        &self,
+        name: &str,
+        index: &str,
+        operand: &Token,
+        key: &[u8],
+    ) -> Result<Vec<usize>, BackendError> {
+        let table = self.tables.get(name).ok_or_else(|| no_table(name))?;
+        match table.indexes.iter().find(|found| found.name == index) {
+            Some(index) => Ok(index.lookup(operand, key)),
+            None => Err(BackendError::UndefinedObject(format!(
+                "Index \"{}\" doesn't exist.",
+                index
+            ))),
+        }
+    }
+
+    fn table_size(&self, name: &str) -> Result<TableSize, BackendError> {
+        match self.tables.get(name) {
+            Some(table) => Ok(table.size()),
+            None => Err(no_table(name)),
+        }
+    }
+
+    fn vacuum(&mut self, name: &str) -> Result<VacuumStats, BackendError> {
+        Ok(self.existing_mut(name)?.compact())
+    }
+
+    fn begin(&mut self) {
+        self.snapshots.push(self.tables.clone());
+    }
+
+    fn end(&mut self, succeeded: bool) {
+        if let Some(snapshot) = self.snapshots.pop() {
+            if !succeeded {
+                self.tables = snapshot;
+            }
+        }
+    }
+}
+
+impl Table {
+    // An empty table of the columns given, without constraints or indexes, for engines
+    // to describe the tables they hold
+    pub fn new(name: &str, columns: Vec<(String, SqlType)>) -> Table {
+        let (columns, column_types): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
+        Table {
+            name: name.to_string(),
+            column_constraints: vec![ColumnConstraints::default(); columns.len()],
+            columns,
+            column_types,
+            rows: vec![],
+            indexes: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        }
+    }
+
+    // Gives the row at `position` the values given, or leaves it as it was if they break
+    // an index constraint
+    fn replace_row(&mut self, position: usize, row: Vec<SqlValue>) -> Result<(), BackendError> {
+        if position >= self.rows.len() {
+            return Err(no_row(position));
+        }
+        self.remove_index_entries(position);
+        let old_row = match self.replace_stored_row(position, StoredRow::new(&row)) {
+            Some(old_row) => old_row,
+            None => return Err(no_row(position)),
+        };
+        if let Err(err) = self.add_index_entries(position) {
+            self.replace_stored_row(position, old_row);
+            self.add_index_entries(position)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    // Takes out the rows at `positions`, those after them moving up. Nothing is taken out
+    // if one of them isn't there.
+    fn remove_rows(&mut self, positions: &[usize]) -> Result<(), BackendError> {
+        let mut removed = vec![false; self.rows.len()];
+        for position in positions {
+            match removed.get_mut(*position) {
+                Some(removed) => *removed = true,
+                None => return Err(no_row(*position)),
+            }
+        }
+        // Where each row ends up, counting the rows kept before it
+        let mut moved = Vec::with_capacity(removed.len());
+        let mut kept = 0;
+        for gone in &removed {
+            moved.push(kept);
+            kept += usize::from(!gone);
+        }
+
+        let mut position = 0;
+        self.retain_stored_rows(|_| {
+            position += 1;
+            !removed[position - 1]
+        });
+        for index in self.indexes.iter_mut() {
+            index.retain_entries(|_, rows| {
+                rows.retain(|row| !removed[*row]);
+                for row in rows.iter_mut() {
+                    *row = moved[*row];
+                }
+                !rows.is_empty()
+            });
+        }
+        Ok(())
+    }
+
+    // Takes out the rows from `start` on, along with their index entries
+    fn truncate_rows(&mut self, start: usize) {
+        self.truncate_stored_rows(start);
+        for index in self.indexes.iter_mut() {
+            index.retain_entries(|_, rows| {
+                rows.retain(|row| *row < start);
+                !rows.is_empty()
+            });
+        }
+    }
+
+    // Takes out the index entries of the row at `position`, found by their keys. Rows
+    // with no entry in an index, like those with a NULL key, are passed over.
+    fn remove_index_entries(&mut self, position: usize) {
+        for i in 0..self.indexes.len() {
+            if let Ok(value) = self.index_value(&self.indexes[i], position) {
+                let key = value.encode_sortable().bytes;
+                self.indexes[i].remove_entry(&key, position);
+            }
+        }
+    }
+}
+
+impl Index {
+    // The rows of the entries that compare with `key` as `operand` does, in the order of
+    // the entries
+    pub fn lookup(&self, operand: &Token, key: &[u8]) -> Vec<usize> {
+        let key: MemoryCellData = key.to_vec();
+        let mut row_indexes: Vec<usize> = Vec::with_capacity(100);
+        match operand {
+            Token::Equal => {
+                if let Some(indexes) = self.tree.get(&key) {
+                    row_indexes.extend_from_slice(indexes);
+                }
+            }
+            Token::NotEqual => {
+                for (_, indexes) in self.tree.iter().filter(|(found, _)| **found != key) {
+                    row_indexes.extend_from_slice(indexes);
+                }
+            }
+            Token::LessThan => {
+                for (_, indexes) in self.tree.range(..key) {
+                    row_indexes.extend_from_slice(indexes);
+                }
+            }
+            Token::LessThanOrEqual => {
+                for (_, indexes) in self.tree.range(..=key) {
+                    row_indexes.extend_from_slice(indexes);
+                }
+            }
+            Token::GreaterThan => {
+                for (found, indexes) in self.tree.range(key.clone()..) {
+                    if *found != key {
+                        row_indexes.extend_from_slice(indexes);
+                    }
+                }
+            }
+            Token::GreaterThanOrEqual => {
+                for (_, indexes) in self.tree.range(key..) {
+                    row_indexes.extend_from_slice(indexes);
+                }
+            }
+            _ => {}
+        }
+        row_indexes
+    }
+}
+
+impl MemoryBackend {
+    // A backend keeping its tables in `storage`. Temporary tables are kept in memory all
+    // the same.
+    pub fn with_storage(storage: impl StorageEngine + 'static) -> MemoryBackend {
+        let mut backend = MemoryBackend::new();
+        backend.storage = Box::new(storage);
+        backend
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::sql_types::SqlText;
+
+    // Read-only tables of comma separated lines, parsed anew by every scan
+    #[derive(Default)]
+    struct CsvStorage {
+        tables: HashMap<String, (Table, String)>,
+    }
+
+    impl CsvStorage {
+        fn with_table(mut self, name: &str, columns: &[(&str, SqlType)], csv: &str) -> Self {
+            let columns = columns
+                .iter()
+                .map(|(column, typ)| (column.to_string(), *typ))
+                .collect();
+            let table = Table::new(name, columns);
+            self.tables
+                .insert(name.to_string(), (table, csv.trim().to_string()));
+            self
+        }
+    }
+
+    fn read_only(name: &str) -> BackendError {
+        BackendError::FeatureNotSupported(format!("Table \"{}\" is read-only.", name))
+    }
+
+    impl StorageEngine for CsvStorage {
+        fn table(&self, name: &str) -> Option<&Table> {
+            self.tables.get(name).map(|(table, _)| table)
+        }
+
+        fn table_mut(&mut self, name: &str) -> Result<Option<&mut Table>, BackendError> {
+            Err(read_only(name))
+        }
+
+        fn table_names(&self) -> Vec<String> {
+            self.tables.keys().cloned().collect()
+        }
+
+        fn create_table(&mut self, table: Table) -> Result<(), BackendError> {
+            Err(read_only(&table.name))
+        }
+
+        fn drop_table(&mut self, name: &str) -> Result<Option<Table>, BackendError> {
+            match self.tables.contains_key(name) {
+                true => Err(read_only(name)),
+                false => Ok(None),
+            }
+        }
+
+        fn scan(&self, name: &str) -> Result<RowIter<'_>, BackendError> {
+            let (table, csv) = self.tables.get(name).ok_or_else(|| no_table(name))?;
+            Ok(Box::new(csv.lines().map(move |line| {
+                line.split(',')
+                    .zip(&table.column_types)
+                    .map(|(field, typ)| {
+                        SqlValue::Text(SqlText::Text {
+                            value: field.trim().to_string(),
+                        })
+                        .explicit_cast_to_type(*typ)
+                        .unwrap_or(SqlValue::Null)
+                    })
+                    .collect()
+            })))
+        }
+
+        fn insert_row(&mut self, name: &str, _: Vec<SqlValue>) -> Result<usize, BackendError> {
+            Err(read_only(name))
+        }
+
+        fn update_row(
+            &mut self,
+            name: &str,
+            _: usize,
+            _: Vec<SqlValue>,
+        ) -> Result<(), BackendError> {
+            Err(read_only(name))
+        }
+
+        fn delete_row(&mut self, name: &str, _: usize) -> Result<(), BackendError> {
+            Err(read_only(name))
+        }
+
+        fn create_index(&mut self, name: &str, _: Index) -> Result<(), BackendError> {
+            Err(read_only(name))
+        }
+
+        fn index_lookup(
+            &self,
+            _: &str,
+            index: &str,
+            _: &Token,
+            _: &[u8],
+        ) -> Result<Vec<usize>, BackendError> {
+            Err(BackendError::UndefinedObject(format!(
+                "Index \"{}\" doesn't exist.",
+                index
+            )))
+        }
+
+        fn begin(&mut self) {}
+
+        fn end(&mut self, _: bool) {}
+    }
+
+    fn csv_backend() -> MemoryBackend {
+        MemoryBackend::with_storage(
+            CsvStorage::default()
+                .with_table(
+                    "cities",
+                    &[
+                        ("name", SqlType::Text),
+                        ("country", SqlType::Text),
+                        ("population", SqlType::Int),
+                    ],
+                    "Athens, gr, 664046
+                    Patras, gr, 213984
+                    Lyon, fr, 522250
+                    Nice, fr, 342669",
+                )
+                .with_table(
+                    "countries",
+                    &[("code", SqlType::Text), ("name", SqlType::Text)],
+                    "gr, Greece
+                    fr, France",
+                ),
+        )
+    }
+
+    #[test]
+    fn test_queries_over_csv_storage() {
+        let mut mb = csv_backend();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT name FROM cities WHERE population > 400000 ORDER BY name;"
+            ),
+            vec!["Athens", "Lyon"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT country, sum(population) FROM cities GROUP BY country ORDER BY country;"
+            ),
+            vec!["fr|864919", "gr|878030"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT cities.name, countries.name FROM cities
+                JOIN countries ON cities.country = countries.code WHERE population < 300000;"
+            ),
+            vec!["Patras|Greece"]
+        );
+        // Temporary tables are kept in memory next to them
+        mb.eval_query(
+            "CREATE TEMPORARY TABLE visits (city TEXT, days INT);
+            INSERT INTO visits VALUES ('Lyon', 3), ('Patras', 2);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT visits.city, days, population FROM visits
+                JOIN cities ON cities.name = visits.city ORDER BY days;"
+            ),
+            vec!["Patras|2|213984", "Lyon|3|522250"]
+        );
+    }
+
+    #[test]
+    fn test_csv_storage_is_read_only() {
+        let mut mb = csv_backend();
+        for query in &[
+            "INSERT INTO cities VALUES ('Kos', 'gr', 33388);",
+            "CREATE TABLE notes (note TEXT);",
+            "CREATE INDEX cities_name ON cities (name);",
+            "DROP TABLE countries;",
+        ] {
+            assert_eq!(
+                mb.eval_query(query).unwrap_err().code(),
+                "0A000",
+                "{}",
+                query
+            );
+        }
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM cities;"), vec!["4"]);
+    }
+
+    #[test]
+    fn test_memory_storage_rows() {
+        let mut storage = MemoryStorage::default();
+        let mut table = Table::new("t", vec![("id".to_string(), SqlType::Int)]);
+        table.indexes.push(Index {
+            name: "t_pkey".to_string(),
+            expression: crate::ast::Expression::new_literal_id("id".to_string()),
+            columns: vec!["id".to_string()],
+            unique: true,
+            primary_key: true,
+            typ: "btreemap".to_string(),
+            tree: Default::default(),
+            bytes: 0,
+        });
+        storage.create_table(table).unwrap();
+        let int = |value| SqlValue::Numeric(crate::sql_types::SqlNumeric::Int { value });
+        for id in 1..=3 {
+            storage.insert_row("t", vec![int(id)]).unwrap();
+        }
+        let key = |id| int(id).encode_sortable().bytes;
+        let lookup = |storage: &MemoryStorage, operand, id| {
+            storage
+                .index_lookup("t", "t_pkey", &operand, &key(id))
+                .unwrap()
+        };
+
+        assert_eq!(lookup(&storage, Token::Equal, 2), vec![1]);
+        assert_eq!(lookup(&storage, Token::GreaterThan, 1), vec![1, 2]);
+        assert_eq!(
+            storage.insert_row("t", vec![int(2)]).unwrap_err().code(),
+            "23505"
+        );
+        // A row taking the value of another is turned down and left as it was
+        assert_eq!(
+            storage.update_row("t", 0, vec![int(3)]).unwrap_err().code(),
+            "23505"
+        );
+        storage.update_row("t", 0, vec![int(7)]).unwrap();
+        storage.delete_row("t", 1).unwrap();
+        assert_eq!(lookup(&storage, Token::Equal, 3), vec![1]);
+        assert_eq!(lookup(&storage, Token::LessThanOrEqual, 7), vec![1, 0]);
+        let ids: Vec<String> = storage
+            .scan("t")
+            .unwrap()
+            .map(|row| row[0].to_string())
+            .collect();
+        assert_eq!(ids, vec!["7", "3"]);
+    }
+
+    #[test]
+    fn test_statements_are_atomic() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, n INT);
+            CREATE INDEX t_n ON t (n);
+            INSERT INTO t VALUES (1, 10), (2, 20), (3, 30);",
+        )
+        .unwrap();
+        let before = rows(&mut mb, "SELECT * FROM t;");
+
+        // Row 500 takes the key of row 1 of the same statement
+        let values: Vec<String> = (1..=1000)
+            .map(|row| match row {
+                500 => "(4, 0)".to_string(),
+                _ => format!("({}, {})", row + 3, row),
+            })
+            .collect();
+        let insert = format!("INSERT INTO t VALUES {};", values.join(", "));
+        assert_eq!(mb.eval_query(&insert).unwrap_err().code(), "23505");
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), before);
+        // Nothing is left in the indexes of the rows that went in before it
+        assert_eq!(
+            rows(&mut mb, "SELECT n FROM t WHERE id = 4;"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM t WHERE n >= 10;"),
+            vec!["1", "2", "3"]
+        );
+
+        let values: Vec<String> = (4..=1000)
+            .map(|id| format!("({}, {})", id, id * 10))
+            .collect();
+        mb.eval_query(&format!("INSERT INTO t VALUES {};", values.join(", ")))
+            .unwrap();
+        let before = rows(&mut mb, "SELECT * FROM t;");
+        // Row 1 takes the key of row 2 while row 2 still has it
+        assert_eq!(
+            mb.eval_query("UPDATE t SET id = id + 1;")
+                .unwrap_err()
+                .code(),
+            "23505"
+        );
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), before);
+
+        // The rows after those taken out move up, their index entries with them
+        mb.eval_query("DELETE FROM t WHERE n < 9000;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM t;"), vec!["101"]);
+        assert_eq!(
+            rows(&mut mb, "SELECT n FROM t WHERE id = 950;"),
+            vec!["9500"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM t WHERE n > 9970;"),
+            vec!["998", "999", "1000"]
+        );
+    }
+
+    #[test]
+    fn test_updates_change_only_their_index_entries() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, n INT);
+            INSERT INTO t SELECT i, i % 3 FROM generate_series(1, 30) AS g(i);
+            CREATE INDEX t_n ON t (n);",
+        )
+        .unwrap();
+
+        // Rows sharing a key keep theirs while the others leave it
+        mb.eval_query("UPDATE t SET n = 7 WHERE id <= 6;").unwrap();
+        mb.eval_query("UPDATE t SET id = id + 100 WHERE n = 2;")
+            .unwrap();
+        assert!(mb.verify_indexes("t").unwrap().is_empty());
+        let table = mb.table("t").unwrap();
+        assert_eq!(table.size(), table.measure_size());
+        assert_eq!(
+            rows(&mut mb, "SELECT count(*) FROM t WHERE n = 1;"),
+            vec!["8"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM t WHERE n = 7;"),
+            vec!["1", "2", "3", "4", "5", "6"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM t WHERE n = 2 AND id < 112;"),
+            vec!["108", "111"]
+        );
+    }
+
+    #[test]
+    fn test_memory_storage_writes() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY);
+            INSERT INTO t VALUES (1), (2), (3), (4);",
+        )
+        .unwrap();
+        let mut storage = MemoryStorage::default();
+        storage
+            .create_table(mb.table("t").unwrap().clone())
+            .unwrap();
+        let int = |value| SqlValue::Numeric(crate::sql_types::SqlNumeric::Int { value });
+        let ids = |storage: &MemoryStorage| {
+            storage
+                .scan("t")
+                .unwrap()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // A row that isn't there keeps any from going
+        let writes = RowWrites::Delete(vec![(1, vec![int(2)]), (7, vec![int(8)])]);
+        assert_eq!(
+            storage.write_rows("t", &writes).unwrap_err().code(),
+            "XX000"
+        );
+        assert_eq!(ids(&storage), vec!["1", "2", "3", "4"]);
+
+        // Rows changed before the one that fails get their values back
+        let writes = RowWrites::Update(vec![
+            (3, vec![int(4)], vec![int(5)]),
+            (0, vec![int(1)], vec![int(2)]),
+        ]);
+        assert_eq!(
+            storage.write_rows("t", &writes).unwrap_err().code(),
+            "23505"
+        );
+        assert_eq!(ids(&storage), vec!["1", "2", "3", "4"]);
+
+        let writes = RowWrites::Delete(vec![(0, vec![int(1)]), (2, vec![int(3)])]);
+        storage.write_rows("t", &writes).unwrap();
+        assert_eq!(ids(&storage), vec!["2", "4"]);
+        let key = int(4).encode_sortable().bytes;
+        assert_eq!(
+            storage
+                .index_lookup("t", "t_pkey", &Token::Equal, &key)
+                .unwrap(),
+            vec![1]
+        );
+    }
+}
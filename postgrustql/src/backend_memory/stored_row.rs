@@ -0,0 +1,324 @@
+use crate::backend::Cell;
+use crate::sql_types::{SqlNumeric, SqlType, SqlValue, ALL_TYPES};
+use std::borrow::Cow;
+use std::mem::size_of;
+
+// Bytes taken by the column count at the start of a row
+const COUNT_LEN: usize = size_of::<u32>();
+// Bytes taken by the end of each column's cell
+const OFFSET_LEN: usize = size_of::<u32>();
+
+// A row of a table, kept in a single allocation rather than a value per column:
+//   the number of columns as a u32
+//   a bitmap of the columns that are NULL, a bit each from the lowest of the first byte
+//   where the cell of each column ends as a u32, counted from the start of the cells
+//   the cells of the columns that aren't NULL, each the position of its type in
+//     SqlType followed by the value's MemoryCell encoding
+// Numbers here are little endian. A NULL takes no room past its bit and its end.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StoredRow {
+    bytes: Box<[u8]>,
+}
+
+impl StoredRow {
+    pub fn new(values: &[SqlValue]) -> Self {
+        let count = values.len();
+        let cells_start = cells_start(count);
+        let cells_len: usize = values
+            .iter()
+            .filter(|value| !value.is_null())
+            .map(|value| 1 + value.encoded_len())
+            .sum();
+        let mut bytes = Vec::with_capacity(cells_start + cells_len);
+        bytes.extend_from_slice(&(count as u32).to_le_bytes());
+        bytes.resize(COUNT_LEN + bitmap_len(count), 0);
+        let mut end = 0;
+        for (column, value) in values.iter().enumerate() {
+            if value.is_null() {
+                bytes[COUNT_LEN + column / 8] |= 1 << (column % 8);
+            } else {
+                end += 1 + value.encoded_len();
+            }
+            bytes.extend_from_slice(&(end as u32).to_le_bytes());
+        }
+        for value in values.iter().filter(|value| !value.is_null()) {
+            bytes.push(value.get_type() as u8);
+            value.encode_into(&mut bytes);
+        }
+        StoredRow {
+            bytes: bytes.into_boxed_slice(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        read_u32(&self.bytes, 0)
+    }
+
+    pub fn is_null(&self, column: usize) -> bool {
+        self.check_column(column);
+        self.bytes[COUNT_LEN + column / 8] & (1 << (column % 8)) != 0
+    }
+
+    // The cell of a column, borrowed from the row. None for a NULL.
+    pub fn cell(&self, column: usize) -> Option<CellRef<'_>> {
+        if self.is_null(column) {
+            return None;
+        }
+        let offsets = COUNT_LEN + bitmap_len(self.len());
+        let start = match column {
+            0 => 0,
+            _ => read_u32(&self.bytes, offsets + (column - 1) * OFFSET_LEN),
+        };
+        let end = read_u32(&self.bytes, offsets + column * OFFSET_LEN);
+        let cells = &self.bytes[cells_start(self.len())..];
+        Some(CellRef {
+            typ: ALL_TYPES[usize::from(cells[start])],
+            bytes: &cells[start + 1..end],
+        })
+    }
+
+    // The value of a column, decoded from its cell
+    pub fn value(&self, column: usize) -> SqlValue {
+        match self.cell(column) {
+            Some(cell) => cell.value(),
+            None => SqlValue::Null,
+        }
+    }
+
+    // Like value, None for a column the row doesn't have
+    pub fn get(&self, column: usize) -> Option<SqlValue> {
+        match column < self.len() {
+            true => Some(self.value(column)),
+            false => None,
+        }
+    }
+
+    pub fn values(&self) -> Vec<SqlValue> {
+        (0..self.len()).map(|column| self.value(column)).collect()
+    }
+
+    // The values of the columns at `columns`, in that order, the other cells left as
+    // they are
+    pub fn project(&self, columns: &[usize]) -> Vec<SqlValue> {
+        columns.iter().map(|column| self.value(*column)).collect()
+    }
+
+    // The memory the row takes outside of the table's list of rows
+    pub fn heap_bytes(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn check_column(&self, column: usize) {
+        let len = self.len();
+        assert!(
+            column < len,
+            "column {} of a row of {} columns",
+            column,
+            len
+        );
+    }
+}
+
+impl From<&[SqlValue]> for StoredRow {
+    fn from(values: &[SqlValue]) -> Self {
+        StoredRow::new(values)
+    }
+}
+
+impl From<Vec<SqlValue>> for StoredRow {
+    fn from(values: Vec<SqlValue>) -> Self {
+        StoredRow::new(&values)
+    }
+}
+
+// The cell of a column that isn't NULL, borrowed from its row. It reads like a
+// MemoryCell, whose bytes are the same.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellRef<'a> {
+    pub typ: SqlType,
+    pub bytes: &'a [u8],
+}
+
+impl CellRef<'_> {
+    pub fn value(&self) -> SqlValue {
+        SqlValue::decode_bytes(self.bytes, self.typ)
+            .expect("A stored cell holds a value of its own type")
+    }
+}
+
+impl Cell for CellRef<'_> {
+    fn as_int(&self) -> Result<i32, &'static str> {
+        match SqlValue::decode_bytes(self.bytes, SqlType::Int) {
+            Ok(SqlValue::Numeric(SqlNumeric::Int { value })) => Ok(value),
+            _ => Err("Failed to parse bytes to int32."),
+        }
+    }
+
+    fn as_num(&self, typ: SqlType) -> Result<f64, &'static str> {
+        let value = SqlValue::decode_bytes(self.bytes, typ)
+            .map(|value| value.to_type(SqlType::DoublePrecision));
+        match value {
+            Ok(Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision { value }))) => Ok(value),
+            _ => Err("Failed to parse bytes to double precision."),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool, &'static str> {
+        match SqlValue::decode_bytes(self.bytes, SqlType::Boolean) {
+            Ok(SqlValue::Boolean(value)) => Ok(value),
+            _ => Err("Failed to parse bytes to bool."),
+        }
+    }
+
+    fn as_text(&self) -> Result<String, &'static str> {
+        match SqlValue::decode_bytes(self.bytes, SqlType::Text) {
+            Ok(SqlValue::Text(text)) => Ok(text.to_string()),
+            _ => Err("Failed to parse bytes to String."),
+        }
+    }
+
+    fn equals(&self, other: Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+// A row compiled expressions can read the columns of. Stored rows decode just the
+// columns read, rows of values lend them.
+pub trait RowValues {
+    fn column(&self, idx: usize) -> Option<Cow<'_, SqlValue>>;
+}
+
+impl RowValues for [SqlValue] {
+    fn column(&self, idx: usize) -> Option<Cow<'_, SqlValue>> {
+        self.get(idx).map(Cow::Borrowed)
+    }
+}
+
+impl<const N: usize> RowValues for [SqlValue; N] {
+    fn column(&self, idx: usize) -> Option<Cow<'_, SqlValue>> {
+        self[..].column(idx)
+    }
+}
+
+impl RowValues for Vec<SqlValue> {
+    fn column(&self, idx: usize) -> Option<Cow<'_, SqlValue>> {
+        self.as_slice().column(idx)
+    }
+}
+
+impl RowValues for StoredRow {
+    fn column(&self, idx: usize) -> Option<Cow<'_, SqlValue>> {
+        self.get(idx).map(Cow::Owned)
+    }
+}
+
+fn bitmap_len(count: usize) -> usize {
+    count.div_ceil(8)
+}
+
+fn cells_start(count: usize) -> usize {
+    COUNT_LEN + bitmap_len(count) + count * OFFSET_LEN
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> usize {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[at..at + 4]);
+    u32::from_le_bytes(word) as usize
+}
+
+#[cfg(test)]
+mod stored_row_tests {
+    use super::*;
+    use crate::backend_memory::Table;
+    use crate::sql_types::SqlText;
+
+    #[test]
+    fn test_stored_row_round_trip() {
+        let values = vec![
+            SqlValue::Numeric(SqlNumeric::Int { value: -7 }),
+            SqlValue::Null,
+            SqlValue::Text(SqlText::Text {
+                value: String::new(),
+            }),
+            SqlValue::Text(SqlText::VarChar {
+                value: "ab\0c".to_string(),
+                maxlen: 10,
+                len: 4,
+            }),
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value: 1.5 }),
+            SqlValue::Boolean(false),
+            SqlValue::Null,
+            SqlValue::Type(SqlType::BigInt),
+            SqlValue::Numeric(SqlNumeric::BigInt { value: i64::MIN }),
+        ];
+        let row = StoredRow::new(&values);
+        assert_eq!(row.len(), values.len());
+        assert_eq!(row.values(), values);
+        assert!(row.is_null(1) && row.is_null(6) && !row.is_null(2));
+        assert_eq!(
+            row.project(&[8, 1, 0]),
+            [values[8].clone(), SqlValue::Null, values[0].clone()]
+        );
+
+        // Cells read like the MemoryCells of their values
+        let cell = row.cell(0).unwrap();
+        assert_eq!(
+            (cell.typ, cell.bytes),
+            (SqlType::Int, &values[0].encode().bytes[..])
+        );
+        assert_eq!(cell.as_int(), Ok(-7));
+        assert_eq!(row.cell(2).unwrap().as_text(), Ok(String::new()));
+        assert_eq!(row.cell(1), None);
+
+        assert_eq!(StoredRow::new(&[]).len(), 0);
+    }
+
+    #[test]
+    fn test_stored_rows_take_less_memory() {
+        let columns = (0..10)
+            .map(|idx| {
+                let typ = if idx < 8 { SqlType::Int } else { SqlType::Text };
+                (format!("c{}", idx), typ)
+            })
+            .collect();
+        let mut table = Table::new("t", columns);
+        // What the rows took as a Vec of values each, counted like allocated_bytes did
+        let mut as_values = 0;
+        let mut values = Vec::with_capacity(10);
+        for idx in 0..10_000 {
+            values.clear();
+            values.extend((0..8).map(|value| SqlValue::Numeric(SqlNumeric::Int { value })));
+            values.push(SqlValue::Text(SqlText::Text {
+                value: format!("name {}", idx),
+            }));
+            values.push(match idx % 3 {
+                0 => SqlValue::Null,
+                _ => SqlValue::Text(SqlText::Text {
+                    value: "x".to_string(),
+                }),
+            });
+            as_values += size_of::<Vec<SqlValue>>() + values.len() * size_of::<SqlValue>();
+            as_values += values
+                .iter()
+                .map(|value| match value {
+                    SqlValue::Text(SqlText::Text { value }) => value.capacity(),
+                    _ => 0,
+                })
+                .sum::<usize>();
+            table.rows.push(StoredRow::new(&values));
+        }
+        let stored = table.allocated_bytes();
+        assert!(
+            stored * 3 < as_values,
+            "{} bytes stored, {} as values",
+            stored,
+            as_values
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "column 2 of a row of 2 columns")]
+    fn test_stored_row_out_of_range() {
+        StoredRow::new(&[SqlValue::Null, SqlValue::Null]).value(2);
+    }
+}
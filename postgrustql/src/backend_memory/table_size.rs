@@ -0,0 +1,471 @@
+use super::catalog::{PUBLIC_SCHEMA, TEMP_SCHEMA};
+use super::sequence::name_parts;
+use super::stored_row::StoredRow;
+use super::{Index, MemoryBackend, StorageEngine, Table};
+use crate::backend::{BackendError, MemoryCellData};
+use crate::sql_types::{SqlNumeric, SqlValue};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+
+// What an entry of an index takes besides its key and the rows it points to
+const ENTRY_BYTES: usize = size_of::<(MemoryCellData, Vec<usize>)>();
+// What each row an entry points to takes
+const ROW_POSITION_BYTES: usize = size_of::<usize>();
+
+// The functions telling the size of a relation, by the name of the relation given as text
+const SIZE_FUNCTIONS: &[&str] = &["index_bytes", "table_bytes", "table_rows"];
+
+// How many rows a table has and about how much memory they and its indexes take, counting
+// what they use rather than the room allocated for them. Tables in memory keep it up to
+// date as their rows change, rather than counting it when asked.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TableSize {
+    pub rows: usize,
+    // The rows as they are stored, each along with its place in the list of rows
+    pub heap_bytes: usize,
+    // What the entries of each index take, by the name of the index
+    pub index_bytes: Vec<(String, usize)>,
+}
+
+impl TableSize {
+    pub fn total_index_bytes(&self) -> usize {
+        self.index_bytes.iter().map(|(_, bytes)| bytes).sum()
+    }
+}
+
+// What a row of a table takes
+pub(super) fn row_bytes(row: &StoredRow) -> usize {
+    size_of::<StoredRow>() + row.heap_bytes()
+}
+
+impl Table {
+    pub(super) fn size(&self) -> TableSize {
+        TableSize {
+            rows: self.rows.len(),
+            heap_bytes: self.heap_bytes,
+            index_bytes: self
+                .indexes
+                .iter()
+                .map(|index| (index.name.clone(), index.bytes))
+                .collect(),
+        }
+    }
+
+    // The size counted from the rows and index entries, which is what `size` keeps track of
+    #[cfg(test)]
+    pub(super) fn measure_size(&self) -> TableSize {
+        TableSize {
+            rows: self.rows.len(),
+            heap_bytes: self.rows.iter().map(row_bytes).sum(),
+            index_bytes: self
+                .indexes
+                .iter()
+                .map(|index| (index.name.clone(), index.measure_bytes()))
+                .collect(),
+        }
+    }
+
+    // Counts the size anew, for a table put together with its rows before it is stored
+    pub(super) fn recount_size(&mut self) {
+        self.heap_bytes = self.rows.iter().map(row_bytes).sum();
+        for index in self.indexes.iter_mut() {
+            index.recount_bytes();
+        }
+    }
+
+    // The rows of a table stored change only through these, keeping count of what they take
+
+    pub(super) fn push_stored_row(&mut self, row: StoredRow) {
+        self.heap_bytes += row_bytes(&row);
+        self.rows.push(row);
+    }
+
+    pub(super) fn pop_stored_row(&mut self) {
+        if let Some(row) = self.rows.pop() {
+            self.heap_bytes -= row_bytes(&row);
+            self.dead_rows += 1;
+        }
+    }
+
+    // Gives back the row that was at `position`, None if there is no such row
+    pub(super) fn replace_stored_row(
+        &mut self,
+        position: usize,
+        row: StoredRow,
+    ) -> Option<StoredRow> {
+        let old_row = std::mem::replace(self.rows.get_mut(position)?, row);
+        self.heap_bytes = self.heap_bytes - row_bytes(&old_row) + row_bytes(&self.rows[position]);
+        Some(old_row)
+    }
+
+    pub(super) fn retain_stored_rows(&mut self, mut keep: impl FnMut(&StoredRow) -> bool) {
+        let heap_bytes = &mut self.heap_bytes;
+        let dead_rows = &mut self.dead_rows;
+        self.rows.retain(|row| {
+            let kept = keep(row);
+            if !kept {
+                *heap_bytes -= row_bytes(row);
+                *dead_rows += 1;
+            }
+            kept
+        });
+    }
+
+    pub(super) fn truncate_stored_rows(&mut self, len: usize) {
+        let removed: usize = self.rows.iter().skip(len).map(row_bytes).sum();
+        self.dead_rows += self.rows.len().saturating_sub(len);
+        self.rows.truncate(len);
+        self.heap_bytes -= removed;
+    }
+}
+
+impl Index {
+    fn measure_bytes(&self) -> usize {
+        self.tree
+            .iter()
+            .map(|(key, rows)| ENTRY_BYTES + key.len() + rows.len() * ROW_POSITION_BYTES)
+            .sum()
+    }
+
+    // The entries of an index stored change only through these, keeping count of what they
+    // take
+
+    pub(super) fn insert_entry(&mut self, key: MemoryCellData, row: usize) {
+        self.bytes += ROW_POSITION_BYTES;
+        match self.tree.get_mut(&key) {
+            Some(rows) => rows.push(row),
+            None => {
+                self.bytes += ENTRY_BYTES + key.len();
+                self.tree.insert(key, vec![row]);
+            }
+        }
+    }
+
+    pub(super) fn remove_entry(&mut self, key: &[u8], row: usize) {
+        let rows = match self.tree.get_mut(key) {
+            Some(rows) => rows,
+            None => return,
+        };
+        if let Some(found) = rows.iter().position(|found| *found == row) {
+            rows.remove(found);
+            self.bytes -= ROW_POSITION_BYTES;
+        }
+        if rows.is_empty() {
+            self.tree.remove(key);
+            self.bytes -= ENTRY_BYTES + key.len();
+        }
+    }
+
+    // Keeps the entries `keep` gives true for, along with the rows it leaves them
+    pub(super) fn retain_entries(
+        &mut self,
+        mut keep: impl FnMut(&MemoryCellData, &mut Vec<usize>) -> bool,
+    ) {
+        let bytes = &mut self.bytes;
+        self.tree.retain(|key, rows| {
+            *bytes -= rows.len() * ROW_POSITION_BYTES;
+            let kept = keep(key, rows);
+            match kept {
+                true => *bytes += rows.len() * ROW_POSITION_BYTES,
+                false => *bytes -= ENTRY_BYTES + key.len(),
+            }
+            kept
+        });
+    }
+
+    pub(super) fn set_entries(&mut self, tree: BTreeMap<MemoryCellData, Vec<usize>>) {
+        self.tree = tree;
+        self.recount_bytes();
+    }
+
+    pub(super) fn recount_bytes(&mut self) {
+        self.bytes = self.measure_bytes();
+    }
+}
+
+// What the size functions give for a relation by name: a table has rows and takes bytes
+// of its own, its indexes are counted together, an index is only counted
+#[derive(Clone, Copy, Debug)]
+struct RelationSize {
+    rows: Option<usize>,
+    table_bytes: Option<usize>,
+    index_bytes: usize,
+}
+
+thread_local! {
+    // The sizes of the relations for the statement running on this thread, known only
+    // when it calls a size function
+    static RELATION_SIZES: RefCell<Option<HashMap<String, RelationSize>>> =
+        const { RefCell::new(None) };
+}
+
+// The sizes known to the statement running on one thread, for the threads of a parallel
+// scan to evaluate its expressions with
+#[cfg(feature = "parallel")]
+#[derive(Clone)]
+pub(super) struct StatementSizes(Option<HashMap<String, RelationSize>>);
+
+#[cfg(feature = "parallel")]
+pub(super) fn statement_sizes() -> StatementSizes {
+    StatementSizes(RELATION_SIZES.with(|sizes| sizes.borrow().clone()))
+}
+
+// Makes `sizes` those of the functions called on this thread until the guard is dropped
+#[cfg(feature = "parallel")]
+pub(super) fn enter_statement_sizes(sizes: StatementSizes) -> RelationSizesGuard {
+    let outer = RELATION_SIZES.with(|current| current.replace(sizes.0));
+    RelationSizesGuard { outer: Some(outer) }
+}
+
+// Puts back the sizes of the statement that was running before when dropped
+pub(super) struct RelationSizesGuard {
+    outer: Option<Option<HashMap<String, RelationSize>>>,
+}
+
+impl Drop for RelationSizesGuard {
+    fn drop(&mut self) {
+        if let Some(outer) = self.outer.take() {
+            RELATION_SIZES.with(|sizes| *sizes.borrow_mut() = outer);
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Every table with its schema and size, the temporary ones first and each schema's by
+    // name, the order names are looked up in
+    pub(super) fn table_sizes(
+        &self,
+    ) -> Result<Vec<(&'static str, String, TableSize)>, BackendError> {
+        let mut sizes = vec![];
+        for (schema, engine) in [
+            (TEMP_SCHEMA, &self.temp_tables as &dyn StorageEngine),
+            (PUBLIC_SCHEMA, self.storage.as_ref()),
+        ] {
+            let mut names = engine.table_names();
+            names.sort();
+            for name in names {
+                let size = engine.table_size(&name)?;
+                sizes.push((schema, name, size));
+            }
+        }
+        Ok(sizes)
+    }
+
+    // The size of a table by name, the temporary one if there are two
+    pub fn table_size(&self, name: &str) -> Result<TableSize, BackendError> {
+        match self.table(name) {
+            Some(_) => self.engine(name).table_size(name),
+            None => Err(BackendError::UndefinedTable(format!(
+                "Table \"{}\" doesn't exist.",
+                name
+            ))),
+        }
+    }
+
+    // Makes the sizes of the relations known to the size functions until the guard is
+    // dropped, if the statement calls any of the `functions`
+    pub(super) fn enter_relation_sizes(
+        &self,
+        functions: &[String],
+    ) -> Result<RelationSizesGuard, BackendError> {
+        if !functions
+            .iter()
+            .any(|function| SIZE_FUNCTIONS.contains(&function.as_str()))
+        {
+            return Ok(RelationSizesGuard { outer: None });
+        }
+        // By name alone, the first found, and by the name of the schema along with it
+        let mut relations = HashMap::new();
+        let mut add = |schema: &str, name: &str, size: RelationSize| {
+            relations.insert(format!("{}.{}", schema, name), size);
+            relations.entry(name.to_string()).or_insert(size);
+        };
+        for (schema, table, size) in self.table_sizes()? {
+            add(
+                schema,
+                &table,
+                RelationSize {
+                    rows: Some(size.rows),
+                    table_bytes: Some(size.heap_bytes),
+                    index_bytes: size.total_index_bytes(),
+                },
+            );
+            for (index, bytes) in &size.index_bytes {
+                let size = RelationSize {
+                    rows: None,
+                    table_bytes: None,
+                    index_bytes: *bytes,
+                };
+                add(schema, index, size);
+            }
+        }
+        let outer = RELATION_SIZES.with(|sizes| sizes.replace(Some(relations)));
+        Ok(RelationSizesGuard { outer: Some(outer) })
+    }
+}
+
+// table_rows, table_bytes or index_bytes of the relation named. Outside of statements,
+// where they are only evaluated for their type, they give 0.
+pub(super) fn relation_size(function: &str, name: &str) -> Result<SqlValue, BackendError> {
+    let name = name_parts(name).join(".");
+    let value = RELATION_SIZES.with(|sizes| {
+        let sizes = sizes.borrow();
+        let size = match sizes.as_ref() {
+            Some(sizes) => sizes.get(&name).copied(),
+            None => return Ok(0),
+        };
+        let size = size.ok_or_else(|| {
+            BackendError::UndefinedTable(format!("Relation \"{}\" doesn't exist.", name))
+        })?;
+        let value = match function {
+            "table_rows" => size.rows,
+            "table_bytes" => size.table_bytes,
+            _ => Some(size.index_bytes),
+        };
+        value.ok_or_else(|| {
+            BackendError::WrongObjectType(format!("{}: \"{}\" is not a table.", function, name))
+        })
+    })?;
+    Ok(SqlValue::Numeric(SqlNumeric::BigInt {
+        value: value as i64,
+    }))
+}
+
+#[cfg(test)]
+mod table_size_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::sql_types::SqlText;
+
+    fn check_sizes(mb: &MemoryBackend) {
+        for name in ["t", "other"] {
+            let table = mb.table(name).unwrap();
+            assert_eq!(table.size(), table.measure_size(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_sizes_kept_up_to_date() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT, note TEXT);
+            CREATE INDEX t_name_idx ON t (name);
+            CREATE TABLE other AS SELECT n AS id FROM generate_series(1, 50) AS g(n);",
+        )
+        .unwrap();
+        check_sizes(&mb);
+
+        let steps = [
+            "INSERT INTO t SELECT n, 'name ' || n, NULL FROM generate_series(1, 2000) AS g(n);",
+            // Values of other sizes, some to NULL, keys shared and keys left behind
+            "UPDATE t SET note = name || name WHERE id % 3 = 0;",
+            "UPDATE t SET name = 'same' WHERE id % 7 = 0;",
+            "UPDATE t SET note = NULL WHERE id % 9 = 0;",
+            "DELETE FROM t WHERE id % 4 = 0;",
+            "VACUUM t;",
+            "ALTER TABLE t DROP COLUMN note;",
+            "DELETE FROM t WHERE id > 1500;",
+            "CREATE UNIQUE INDEX t_id_key ON t (id);",
+            "REINDEX TABLE t;",
+            "INSERT INTO other VALUES (51);",
+        ];
+        for step in steps {
+            mb.eval_query(step).unwrap();
+            check_sizes(&mb);
+        }
+        let err = mb
+            .eval_query("INSERT INTO t VALUES (5, 'x'), (3, 'taken');")
+            .unwrap_err();
+        assert_eq!(err.code(), "23505");
+        check_sizes(&mb);
+
+        // Rolled back, the tables are as they were along with their sizes
+        let size = mb.table_size("t").unwrap();
+        mb.eval_query("BEGIN; DELETE FROM t; INSERT INTO t VALUES (-1, 'x'); ROLLBACK;")
+            .unwrap();
+        assert_eq!(mb.table_size("t").unwrap(), size);
+
+        // Prepared inserts in batches, one of which fails
+        let statement = mb.prepare("INSERT INTO t VALUES ($1, $2);").unwrap();
+        let params = |ids: std::ops::Range<i32>| {
+            ids.map(|value| {
+                vec![
+                    SqlValue::Numeric(SqlNumeric::Int { value }),
+                    SqlValue::Text(SqlText::Text {
+                        value: "batch".to_string(),
+                    }),
+                ]
+            })
+            .collect::<Vec<_>>()
+        };
+        statement.execute_many(&mut mb, params(5000..5010)).unwrap();
+        check_sizes(&mb);
+        assert_eq!(mb.table_size("t").unwrap().rows, size.rows + 10);
+        assert!(statement
+            .execute_many(&mut mb, params(6000..6010).into_iter().chain(params(1..2)))
+            .is_err());
+        check_sizes(&mb);
+        assert_eq!(mb.table_size("t").unwrap().rows, size.rows + 10);
+    }
+
+    #[test]
+    fn test_size_functions() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT);
+            INSERT INTO users VALUES (1, 'a'), (2, 'b'), (3, NULL);",
+        )
+        .unwrap();
+        let size = mb.table_size("users").unwrap();
+        assert_eq!(size.rows, 3);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT table_rows('users'), table_bytes('public.users'), \
+                index_bytes('users_pkey'), index_bytes('Users');"
+            ),
+            [format!(
+                "3|{}|{}|{}",
+                size.heap_bytes,
+                size.index_bytes[0].1,
+                size.total_index_bytes()
+            )]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT pg_typeof(table_rows('users'));"),
+            ["Bigint"]
+        );
+
+        // The names can come from the rows, and the sizes change along with the tables
+        mb.eval_query(
+            "DELETE FROM users WHERE id < 3;
+            CREATE TEMPORARY TABLE users (id INT);
+            INSERT INTO users VALUES (7), (8);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT table_schema, table_name, row_count, index_bytes > 0, \
+                table_rows(table_schema || '.' || table_name) FROM postgrustql_tables;"
+            ),
+            ["pg_temp_1|users|2|false|2", "public|users|1|true|1"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT total_bytes = table_bytes + index_bytes FROM postgrustql_tables;"
+            ),
+            ["true", "true"]
+        );
+        // The temporary table hides the other
+        assert_eq!(rows(&mut mb, "SELECT table_rows('users');"), ["2"]);
+
+        assert_eq!(rows(&mut mb, "SELECT table_rows(NULL);"), ["NULL"]);
+        let code = |mb: &mut MemoryBackend, query| mb.eval_query(query).unwrap_err().code();
+        assert_eq!(code(&mut mb, "SELECT table_rows('nope');"), "42P01");
+        assert_eq!(code(&mut mb, "SELECT table_bytes('users_pkey');"), "42809");
+        assert_eq!(code(&mut mb, "SELECT table_rows(1);"), "42883");
+    }
+}
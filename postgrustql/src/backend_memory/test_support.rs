@@ -0,0 +1,69 @@
+// What the tests of the backend share: a backend made by some setup SQL, and what its queries
+// give, row by row with the values of each joined by `|`
+use super::{BackendConfig, Connection, MemoryBackend};
+use crate::backend::{BackendError, EvalResult, QueryResults};
+use crate::sql_types::SqlValue;
+use std::sync::{Arc, Mutex};
+
+// What the tests run queries on, a backend of their own or a connection to a shared one
+pub(super) trait Session {
+    fn run(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, BackendError>;
+}
+
+impl Session for MemoryBackend {
+    fn run(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, BackendError> {
+        self.eval_query(query)
+    }
+}
+
+impl Session for Connection {
+    fn run(&mut self, query: &str) -> Result<Vec<EvalResult<SqlValue>>, BackendError> {
+        self.eval_query(query)
+    }
+}
+
+pub(super) fn backend(setup: &str) -> MemoryBackend {
+    let mut mb = MemoryBackend::new();
+    mb.eval_query(setup).unwrap();
+    mb
+}
+
+// The same, configured by `config`
+pub(super) fn backend_with(config: BackendConfig, setup: &str) -> MemoryBackend {
+    let mut mb = MemoryBackend::with_config(config);
+    mb.eval_query(setup).unwrap();
+    mb
+}
+
+// The same, for connections to open on
+pub(super) fn shared(setup: &str) -> Arc<Mutex<MemoryBackend>> {
+    Arc::new(Mutex::new(backend(setup)))
+}
+
+pub(super) fn rows(session: &mut impl Session, query: &str) -> Vec<String> {
+    try_rows(session, query).unwrap()
+}
+
+// The rows of the last statement of `query`, or the error running it gave
+pub(super) fn try_rows(
+    session: &mut impl Session,
+    query: &str,
+) -> Result<Vec<String>, BackendError> {
+    match session.run(query)?.pop() {
+        Some(EvalResult::Select { results, .. }) => Ok(joined(&results)),
+        _ => panic!("Expected select results for {}", query),
+    }
+}
+
+pub(super) fn joined(results: &QueryResults<SqlValue>) -> Vec<String> {
+    results
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join("|")
+        })
+        .collect()
+}
@@ -0,0 +1,957 @@
+use super::join_order::join_relation;
+use super::nondeterminism::Timer;
+use super::{contains_aggregate, contains_window_function, MemoryBackend};
+use crate::ast::*;
+use crate::backend::{BackendError, QueryResults, ResultColumn};
+use crate::sql_types::{SqlText, SqlType, SqlValue};
+use instant::Instant;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+// Where the time of the last query went. Lexing and parsing cover the whole query text,
+// planning and execution the last statement run from it. Planning is the resolving of
+// names and compiling of conditions a SELECT does before it reads any row. The `time` of
+// the statement's `EvalResult` is its planning and execution together, never lexing or
+// parsing, which are only found here.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryTiming {
+    pub lex: Duration,
+    pub parse: Duration,
+    pub plan: Duration,
+    pub execute: Duration,
+    // Times the last statement was run again after a serialization conflict, see
+    // `BackendConfig::retry_on_conflict`
+    pub retries: u32,
+    // The operators of the last SELECT run, only timed with `collect_timing` on or by
+    // EXPLAIN ANALYZE
+    pub plan_tree: Option<PlanNode>,
+}
+
+// An operator of a SELECT, along with the operators it reads rows from
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    pub name: String,
+    // The rows it gave and the time spent in it and its inputs, when it was run
+    pub rows: Option<usize>,
+    pub time: Option<Duration>,
+    // Lines shown under it, like the number of rows a filter removed
+    pub details: Vec<String>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    // The plan as lines of text, with every input below the operator reading it like
+    // postgres shows them
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        self.push_lines(0, &mut lines);
+        lines
+    }
+
+    fn push_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        let prefix = match depth {
+            0 => String::new(),
+            _ => format!("{}->  ", " ".repeat(6 * depth - 4)),
+        };
+        match (self.rows, self.time) {
+            (Some(rows), Some(time)) => lines.push(format!(
+                "{}{}  (actual rows={} time={})",
+                prefix,
+                self.name,
+                rows,
+                format_ms(time)
+            )),
+            _ => lines.push(format!("{}{}", prefix, self.name)),
+        }
+        for detail in &self.details {
+            lines.push(format!("{}{}", " ".repeat(6 * depth + 2), detail));
+        }
+        for child in &self.children {
+            child.push_lines(depth + 1, lines);
+        }
+    }
+}
+
+fn format_ms(time: Duration) -> String {
+    format!("{:.3} ms", time.as_secs_f64() * 1000.0)
+}
+
+// Reads the time statements are timed by, from the `Timer` if one was given and else
+// from the monotonic clock of the system
+#[derive(Debug, Clone)]
+pub(super) struct Stopwatch {
+    timer: Option<Timer>,
+    origin: Instant,
+}
+
+impl Stopwatch {
+    pub(super) fn new(timer: Option<Timer>) -> Self {
+        Stopwatch {
+            timer,
+            origin: Instant::now(),
+        }
+    }
+
+    pub(super) fn timer(&self) -> Option<Timer> {
+        self.timer.clone()
+    }
+
+    pub(super) fn read(&self) -> Duration {
+        match &self.timer {
+            Some(timer) => timer.read(),
+            None => self.origin.elapsed(),
+        }
+    }
+}
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Stopwatch::new(None)
+    }
+}
+
+// Stopwatches are the same when they read the same timer
+impl PartialEq for Stopwatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.timer == other.timer
+    }
+}
+
+// Timing of the statements a backend runs. The phases are timed unless `enabled` is
+// off, which takes a few clock readings per statement, the operators only with `collect`
+// on.
+#[derive(Debug, PartialEq)]
+pub(super) struct TimingRecorder {
+    pub(super) enabled: bool,
+    pub(super) collect: bool,
+    pub(super) stopwatch: Stopwatch,
+    pub(super) last: QueryTiming,
+    planning: Cell<Duration>,
+    // Plans of the SELECTs the statement ran, until whichever SELECT ran them as a
+    // subquery takes them
+    plans: RefCell<Vec<PlanNode>>,
+}
+
+impl Default for TimingRecorder {
+    fn default() -> Self {
+        TimingRecorder {
+            enabled: true,
+            collect: false,
+            stopwatch: Stopwatch::default(),
+            last: QueryTiming::default(),
+            planning: Cell::default(),
+            plans: RefCell::default(),
+        }
+    }
+}
+
+impl TimingRecorder {
+    // The time now, or None without reading the clock when timing is off
+    pub(super) fn now(&self) -> Option<Duration> {
+        match self.enabled {
+            true => Some(self.stopwatch.read()),
+            false => None,
+        }
+    }
+
+    // The time since `start` was read, nothing when it wasn't
+    pub(super) fn since(&self, start: Option<Duration>) -> Duration {
+        match start {
+            Some(start) => self.stopwatch.read().saturating_sub(start),
+            None => Duration::default(),
+        }
+    }
+
+    pub(super) fn start_statement(&mut self) {
+        self.planning.set(Duration::default());
+        self.plans.get_mut().clear();
+    }
+
+    pub(super) fn finish_statement(&mut self, elapsed: Duration) {
+        let plan = self.planning.get();
+        self.last.plan = plan;
+        self.last.execute = elapsed.saturating_sub(plan);
+        self.last.plan_tree = self.plans.get_mut().pop();
+    }
+
+    pub(super) fn add_planning(&self, time: Duration) {
+        self.planning.set(self.planning.get() + time);
+    }
+}
+
+// Puts together the plan of a SELECT one operator at a time, in the order they run, every
+// operator reading from those added right before it. Run operators are timed from the
+// end of the one before, and nothing is kept at all when timing is off.
+pub(super) struct PlanBuilder {
+    recording: bool,
+    stopwatch: Stopwatch,
+    lap: Option<Duration>,
+    // Where the plans of the subqueries this SELECT runs start
+    first_plan: usize,
+    nodes: Vec<PlanNode>,
+    subplans: Vec<PlanNode>,
+}
+
+impl PlanBuilder {
+    pub(super) fn new(recorder: &TimingRecorder) -> Self {
+        PlanBuilder {
+            recording: recorder.collect,
+            stopwatch: recorder.stopwatch.clone(),
+            lap: if recorder.collect {
+                Some(recorder.stopwatch.read())
+            } else {
+                None
+            },
+            first_plan: recorder.plans.borrow().len(),
+            nodes: vec![],
+            subplans: vec![],
+        }
+    }
+
+    // A plan of what would run, without rows or times
+    fn untimed() -> Self {
+        PlanBuilder {
+            recording: true,
+            stopwatch: Stopwatch::default(),
+            lap: None,
+            first_plan: 0,
+            nodes: vec![],
+            subplans: vec![],
+        }
+    }
+
+    // Starts timing the next operator from now on
+    pub(super) fn restart(&mut self) {
+        if let Some(lap) = &mut self.lap {
+            *lap = self.stopwatch.read();
+        }
+    }
+
+    // Adds an operator reading the rows of the last `inputs` operators added
+    pub(super) fn add(&mut self, name: impl FnOnce() -> String, rows: usize, inputs: usize) {
+        if !self.recording {
+            return;
+        }
+        let children = self
+            .nodes
+            .split_off(self.nodes.len().saturating_sub(inputs));
+        let time = self.lap.map(|lap| {
+            children
+                .iter()
+                .filter_map(|child| child.time)
+                .fold(self.stopwatch.read().saturating_sub(lap), |total, time| {
+                    total + time
+                })
+        });
+        self.nodes.push(PlanNode {
+            name: name(),
+            rows: self.lap.map(|_| rows),
+            time,
+            details: vec![],
+            children,
+        });
+        self.restart();
+    }
+
+    // Adds a line to show under the operator added last
+    pub(super) fn add_detail(&mut self, detail: impl FnOnce() -> String) {
+        if !self.recording {
+            return;
+        }
+        if let Some(node) = self.nodes.last_mut() {
+            node.details.push(detail());
+        }
+    }
+
+    // Adds the operator running the select items over the rows its input gives, which
+    // also runs the subqueries of ANY and ALL. `removed` is the count of rows the
+    // condition filtered out, if there is one.
+    pub(super) fn add_result(&mut self, rows: usize, removed: Option<usize>) {
+        if !self.recording {
+            return;
+        }
+        self.add(|| "Result".to_string(), rows, 1);
+        let subplans = std::mem::take(&mut self.subplans);
+        if let Some(result) = self.nodes.last_mut() {
+            if let (Some(_), Some(removed)) = (result.time, removed) {
+                result
+                    .details
+                    .push(format!("Rows Removed by Filter: {}", removed));
+            }
+            for subplan in subplans {
+                if let (Some(time), Some(subplan_time)) = (result.time, subplan.time) {
+                    result.time = Some(time + subplan_time);
+                }
+                result.children.push(PlanNode {
+                    name: "SubPlan".to_string(),
+                    rows: None,
+                    time: None,
+                    details: vec![],
+                    children: vec![subplan],
+                });
+            }
+        }
+    }
+
+    // Takes the plans of the subqueries of ANY and ALL, which run before anything else
+    pub(super) fn take_subplans(&mut self, recorder: &TimingRecorder) {
+        if !self.recording {
+            return;
+        }
+        let mut plans = recorder.plans.borrow_mut();
+        let first_plan = self.first_plan.min(plans.len());
+        self.subplans.extend(plans.drain(first_plan..));
+        self.restart();
+    }
+
+    // Takes the plan of the subquery in FROM that just ran, as the input of its scan
+    pub(super) fn take_subquery(&mut self, recorder: &TimingRecorder) {
+        if !self.recording {
+            return;
+        }
+        let mut plans = recorder.plans.borrow_mut();
+        if plans.len() > self.first_plan {
+            self.nodes.extend(plans.pop());
+        }
+        self.restart();
+    }
+
+    // Takes the plans of a subquery run once for every row, keeping that of the first
+    // run with the time of them all
+    pub(super) fn take_correlated_subplan(&mut self, recorder: &TimingRecorder) {
+        if !self.recording {
+            return;
+        }
+        let mut plans = recorder.plans.borrow_mut();
+        let first_plan = self.first_plan.min(plans.len());
+        let mut runs = plans.drain(first_plan..);
+        if let Some(mut subplan) = runs.next() {
+            let mut loops = 1;
+            for run in runs {
+                if let (Some(time), Some(run_time)) = (subplan.time, run.time) {
+                    subplan.time = Some(time + run_time);
+                }
+                loops += 1;
+            }
+            if subplan.time.is_some() {
+                subplan.details.push(format!("Loops: {}", loops));
+            }
+            self.subplans.push(subplan);
+        }
+        self.restart();
+    }
+
+    // Leaves the plan for the statement, or for the SELECT running this one as a subquery
+    pub(super) fn finish(mut self, recorder: &TimingRecorder) {
+        if let Some(plan) = self.nodes.pop() {
+            recorder.plans.borrow_mut().push(plan);
+        }
+    }
+}
+
+// How the rows of a FROM item are read, `index` being the index a table is read through
+pub(super) fn scan_name(source: &RowDataSource, index: Option<&str>) -> String {
+    match source {
+        RowDataSource::Table {
+            table_name,
+            as_clause,
+            sample,
+            ..
+        } => {
+            let alias = match as_clause {
+                Some(alias) if alias != table_name => format!(" {}", alias),
+                _ => String::new(),
+            };
+            match (index, sample) {
+                (_, Some(_)) => format!("Sample Scan on {}{}", table_name, alias),
+                (Some(index), None) => {
+                    format!("Index Scan using {} on {}{}", index, table_name, alias)
+                }
+                (None, None) => format!("Seq Scan on {}{}", table_name, alias),
+            }
+        }
+        RowDataSource::SubSelect { as_clause, .. } => format!("Subquery Scan on {}", as_clause),
+        RowDataSource::Function { call, .. } => format!("Function Scan on {}", call.name),
+        RowDataSource::Values { .. } => "Values Scan".to_string(),
+    }
+}
+
+// A scan of a subquery reads the rows of its plan, other scans have no inputs
+pub(super) fn scan_inputs(source: &RowDataSource) -> usize {
+    match source {
+        RowDataSource::SubSelect { .. } => 1,
+        _ => 0,
+    }
+}
+
+pub(super) fn join_name(kind: &JoinKind) -> String {
+    match kind {
+        JoinKind::Inner => "Nested Loop",
+        JoinKind::LeftOuter => "Nested Loop Left Join",
+        JoinKind::RightOuter => "Nested Loop Right Join",
+        JoinKind::FullOuter => "Nested Loop Full Join",
+    }
+    .to_string()
+}
+
+pub(super) fn source_joins(source: &RowDataSource) -> &Vec<JoinClause> {
+    match source {
+        RowDataSource::Table { joins, .. }
+        | RowDataSource::SubSelect { joins, .. }
+        | RowDataSource::Function { joins, .. }
+        | RowDataSource::Values { joins, .. } => joins,
+    }
+}
+
+// The subqueries of ANY, ALL and EXISTS in an expression, outermost first
+fn quantified_subqueries<'a>(
+    expression: &'a Expression,
+    subqueries: &mut Vec<&'a SelectStatement>,
+) {
+    match expression {
+        Expression::Quantified(quantified) => {
+            quantified_subqueries(&quantified.first, subqueries);
+            if let QuantifiedSet::SubSelect(select) = &quantified.set {
+                subqueries.push(select);
+            }
+        }
+        Expression::Binary(binary) => {
+            quantified_subqueries(&binary.first, subqueries);
+            quantified_subqueries(&binary.second, subqueries);
+        }
+        Expression::Unary(unary) => quantified_subqueries(&unary.first, subqueries),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            quantified_subqueries(data, subqueries)
+        }
+        Expression::FunctionCall(call) => {
+            for arg in &call.args {
+                quantified_subqueries(arg, subqueries);
+            }
+        }
+        Expression::Exists(select) => subqueries.push(select),
+        Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::SubSelect(_)
+        | Expression::Empty => {}
+    }
+}
+
+impl MemoryBackend {
+    // How long the phases of the last query took, and with `collect_timing` on what each
+    // operator of its last SELECT did
+    pub fn last_timing(&self) -> &QueryTiming {
+        &self.timing.last
+    }
+
+    // Timing operators reads the clock a few times per operator, never per row
+    pub fn set_collect_timing(&mut self, enabled: bool) {
+        self.timing.collect = enabled;
+    }
+
+    // The plan of the query as rows of text. With ANALYZE the query runs, its results are
+    // thrown away, and every operator shows the rows it gave and the time it took.
+    pub(super) fn explain(
+        &mut self,
+        statement: ExplainStatement,
+    ) -> Result<QueryResults<SqlValue>, BackendError> {
+        let lines = if statement.analyze {
+            let collect = std::mem::replace(&mut self.timing.collect, true);
+            let planning = self.timing.planning.get();
+            let before = self.timing.stopwatch.read();
+            let result = self.select(statement.select);
+            let elapsed = self.timing.stopwatch.read().saturating_sub(before);
+            self.timing.collect = collect;
+            result?;
+
+            let planning = self.timing.planning.get() - planning;
+            let mut lines = match self.timing.plans.borrow().last() {
+                Some(plan) => plan.lines(),
+                None => vec![],
+            };
+            lines.push(format!("Lex Time: {}", format_ms(self.timing.last.lex)));
+            lines.push(format!("Parse Time: {}", format_ms(self.timing.last.parse)));
+            lines.push(format!("Planning Time: {}", format_ms(planning)));
+            lines.push(format!(
+                "Execution Time: {}",
+                format_ms(elapsed.saturating_sub(planning))
+            ));
+            lines
+        } else {
+            self.select_plan(&statement.select)?.lines()
+        };
+
+        Ok(QueryResults {
+            columns: vec![ResultColumn {
+                col_type: SqlType::Text,
+                name: "QUERY PLAN".to_string(),
+                type_modifier: None,
+                nullable: true,
+                origin: None,
+            }],
+            rows: lines
+                .into_iter()
+                .map(|value| vec![SqlValue::Text(SqlText::Text { value })])
+                .collect(),
+        })
+    }
+
+    // The operators `select` would run for the statement, without running any of them
+    fn select_plan(&self, select: &SelectStatement) -> Result<PlanNode, BackendError> {
+        let mut plan = PlanBuilder::untimed();
+
+        let (semi_joins, where_clause) = self.plan_semi_joins(&select.where_clause);
+        let mut subqueries = vec![];
+        for item in &select.items {
+            quantified_subqueries(&item.expression, &mut subqueries);
+        }
+        quantified_subqueries(&where_clause, &mut subqueries);
+        quantified_subqueries(&select.having, &mut subqueries);
+        for order_by in &select.order_by {
+            quantified_subqueries(&order_by.exp, &mut subqueries);
+        }
+        for subquery in subqueries {
+            plan.subplans.push(self.select_plan(subquery)?);
+        }
+        // Semi joins that aren't hashed run their subquery for every row
+        for semi_join in &semi_joins {
+            if semi_join.hashed.is_none() {
+                plan.subplans.push(self.select_plan(&semi_join.subquery)?);
+            }
+        }
+
+        if let Some(from) = select.from.first() {
+            let join_plan = self.plan_joins(from, &where_clause);
+            let first = join_relation(from, join_plan.order[0]);
+            self.source_plan(&mut plan, first, &where_clause)?;
+            for (step, relation) in join_plan.steps.iter().zip(&join_plan.order[1..]) {
+                let source = join_relation(from, *relation);
+                self.source_plan(&mut plan, source, &where_clause)?;
+                plan.add(|| join_name(&step.kind), 0, 2);
+            }
+        }
+        for semi_join in &semi_joins {
+            if let Some((_, inner)) = &semi_join.hashed {
+                plan.nodes.push(self.select_plan(inner)?);
+                plan.add(|| "Hash".to_string(), 0, 1);
+                plan.add(|| semi_join.name(), 0, 2);
+            }
+        }
+        let is_grouped = !select.group_by.is_empty()
+            || !select.having.is_empty()
+            || select
+                .items
+                .iter()
+                .any(|item| contains_aggregate(&item.expression))
+            || select
+                .order_by
+                .iter()
+                .any(|order_by| contains_aggregate(&order_by.exp));
+        if is_grouped {
+            plan.add(|| "Aggregate".to_string(), 0, 1);
+        }
+        let is_windowed = select
+            .items
+            .iter()
+            .any(|item| contains_window_function(&item.expression))
+            || select
+                .order_by
+                .iter()
+                .any(|order_by| contains_window_function(&order_by.exp));
+        if is_windowed {
+            plan.add(|| "WindowAgg".to_string(), 0, 1);
+        }
+        plan.add_result(0, None);
+        if !select.order_by.is_empty() {
+            plan.add(|| "Sort".to_string(), 0, 1);
+        }
+        if select.limit.is_some() || select.offset.is_some() {
+            plan.add(|| "Limit".to_string(), 0, 1);
+        }
+
+        match plan.nodes.pop() {
+            Some(node) => Ok(node),
+            None => Err(BackendError::Internal("Empty query plan".to_string())),
+        }
+    }
+
+    fn source_plan(
+        &self,
+        plan: &mut PlanBuilder,
+        source: &RowDataSource,
+        where_clause: &Expression,
+    ) -> Result<(), BackendError> {
+        match source {
+            RowDataSource::Table {
+                table_name, sample, ..
+            } => {
+                let table = match self.table(table_name) {
+                    Some(table) => table,
+                    None => {
+                        return Err(BackendError::UndefinedTable(format!(
+                            "Table \"{}\" doesn't exist.",
+                            table_name
+                        )));
+                    }
+                };
+                if let Some(sample) = sample {
+                    let sampling = self.sampling(sample)?;
+                    plan.add(|| scan_name(source, None), 0, 0);
+                    plan.add_detail(|| sampling.detail());
+                    return Ok(());
+                }
+                // The same index `select` reads the table through
+                let mut index_name = None;
+                for (index, exp) in table.get_applicable_indexes(Some(where_clause))? {
+                    if let Expression::Binary(_) = exp {
+                        index_name = Some(index.name.clone());
+                    }
+                }
+                plan.add(|| scan_name(source, index_name.as_deref()), 0, 0);
+            }
+            RowDataSource::SubSelect { select, .. } => {
+                plan.nodes.push(self.select_plan(select)?);
+                plan.add(|| scan_name(source, None), 0, 1);
+            }
+            RowDataSource::Function { .. } | RowDataSource::Values { .. } => {
+                plan.add(|| scan_name(source, None), 0, 0)
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod timing_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::test_support::{backend, backend_with};
+    use crate::backend_memory::BackendConfig;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    const SETUP: &str = "CREATE TABLE t (id INT PRIMARY KEY, name TEXT, qty INT);
+        CREATE TABLE u (t_id INT, tag TEXT);
+        INSERT INTO t VALUES (1, 'a', 5), (2, 'b', 10), (3, 'c', 15), (4, 'd', 20);
+        INSERT INTO u VALUES (1, 'x'), (1, 'y'), (3, 'z');";
+
+    fn plan(mb: &mut MemoryBackend, query: &str) -> Vec<String> {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.columns[0].name, "QUERY PLAN");
+                results.rows.iter().map(|row| row[0].to_string()).collect()
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    // The lines of a plan with the times left out, which change from run to run
+    fn without_times(lines: Vec<String>) -> Vec<String> {
+        lines
+            .into_iter()
+            .filter(|line| !line.ends_with(" ms") || line.contains("(actual"))
+            .map(|line| match line.find(" time=") {
+                Some(idx) => format!("{})", &line[..idx]),
+                None => line,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("CREATE INDEX t_name_idx ON t (name);")
+            .unwrap();
+        let tests = vec![
+            (
+                "EXPLAIN SELECT name FROM t WHERE qty > 5 ORDER BY name LIMIT 2;",
+                vec![
+                    "Limit",
+                    "  ->  Sort",
+                    "        ->  Result",
+                    "              ->  Seq Scan on t",
+                ],
+            ),
+            (
+                "EXPLAIN SELECT id FROM t WHERE name = 'b';",
+                vec!["Result", "  ->  Index Scan using t_name_idx on t"],
+            ),
+            (
+                "EXPLAIN SELECT name, tag FROM t AS a LEFT JOIN u ON u.t_id = a.id;",
+                vec![
+                    "Result",
+                    "  ->  Nested Loop Left Join",
+                    "        ->  Seq Scan on t a",
+                    "        ->  Seq Scan on u",
+                ],
+            ),
+            (
+                "EXPLAIN SELECT count(*) FROM (SELECT id FROM t) AS s;",
+                vec![
+                    "Result",
+                    "  ->  Aggregate",
+                    "        ->  Subquery Scan on s",
+                    "              ->  Result",
+                    "                    ->  Seq Scan on t",
+                ],
+            ),
+            (
+                "EXPLAIN SELECT id FROM t WHERE id > ANY (SELECT t_id FROM u);",
+                vec![
+                    "Result",
+                    "  ->  Seq Scan on t",
+                    "  ->  SubPlan",
+                    "        ->  Result",
+                    "              ->  Seq Scan on u",
+                ],
+            ),
+            // `= ANY` is IN, which looks rows up in a hash of the subquery's values
+            (
+                "EXPLAIN SELECT id FROM t WHERE id = ANY (SELECT t_id FROM u);",
+                vec![
+                    "Result",
+                    "  ->  Hash Semi Join",
+                    "        ->  Seq Scan on t",
+                    "        ->  Hash",
+                    "              ->  Result",
+                    "                    ->  Seq Scan on u",
+                ],
+            ),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(plan(&mut mb, query), expected, "{}", query);
+            // Running the query goes through the same operators
+            let analyzed: Vec<String> =
+                plan(&mut mb, &query.replacen("EXPLAIN", "EXPLAIN ANALYZE", 1))
+                    .into_iter()
+                    .filter(|line| !line.contains(':'))
+                    .map(|line| match line.find("  (actual") {
+                        Some(idx) => line[..idx].to_string(),
+                        None => line,
+                    })
+                    .collect();
+            assert_eq!(analyzed, expected, "{}", query);
+        }
+        // Explaining alone runs nothing
+        plan(&mut mb, "EXPLAIN SELECT id FROM t;");
+        assert_eq!(mb.last_timing().plan_tree, None);
+        assert_eq!(
+            mb.eval_query("EXPLAIN SELECT * FROM nope;")
+                .unwrap_err()
+                .code(),
+            "42P01"
+        );
+    }
+
+    #[test]
+    fn test_explain_analyze() {
+        let mut mb = backend(SETUP);
+        let lines = plan(
+            &mut mb,
+            "EXPLAIN ANALYZE SELECT name, tag FROM t INNER JOIN u ON u.t_id = t.id \
+            WHERE qty > 5 ORDER BY tag LIMIT 1;",
+        );
+        assert_eq!(
+            without_times(lines.clone()),
+            vec![
+                "Limit  (actual rows=1)",
+                "  ->  Sort  (actual rows=1)",
+                "        ->  Result  (actual rows=1)",
+                "              Rows Removed by Filter: 2",
+                "              ->  Nested Loop  (actual rows=3)",
+                "                    ->  Seq Scan on t  (actual rows=4)",
+                "                    ->  Seq Scan on u  (actual rows=3)",
+            ]
+        );
+        let footer: Vec<&str> = lines[7..]
+            .iter()
+            .map(|line| &line[..line.find(':').unwrap()])
+            .collect();
+        assert_eq!(
+            footer,
+            vec!["Lex Time", "Parse Time", "Planning Time", "Execution Time"]
+        );
+
+        // The plan is kept with the timing of the query, operators of later queries
+        // aren't timed
+        let timing = mb.last_timing();
+        assert!(timing.plan > Duration::default());
+        assert_eq!(timing.plan_tree.as_ref().unwrap().name, "Limit");
+        mb.eval_query("SELECT name FROM t;").unwrap();
+        assert_eq!(mb.last_timing().plan_tree, None);
+    }
+
+    #[test]
+    fn test_collect_timing() {
+        let mut mb = backend_with(BackendConfig::new().collect_timing(true), SETUP);
+        mb.eval_query(
+            "SELECT qty, sum(qty) OVER (ORDER BY id) AS total \
+            FROM (SELECT id, qty FROM t WHERE id > 1) AS s;",
+        )
+        .unwrap();
+
+        let timing = mb.last_timing().clone();
+        assert!(timing.lex > Duration::default());
+        assert!(timing.parse > Duration::default());
+        assert!(timing.execute > Duration::default());
+        let tree = timing.plan_tree.unwrap();
+        assert_eq!(
+            without_times(tree.lines()),
+            vec![
+                "Result  (actual rows=3)",
+                "  ->  WindowAgg  (actual rows=3)",
+                "        ->  Subquery Scan on s  (actual rows=3)",
+                "              ->  Result  (actual rows=3)",
+                "                    Rows Removed by Filter: 1",
+                "                    ->  Seq Scan on t  (actual rows=4)",
+            ]
+        );
+        // Operators take at least as long as their inputs
+        let scan = &tree.children[0].children[0];
+        assert!(tree.time.unwrap() >= scan.time.unwrap());
+        assert!(scan.time.unwrap() >= scan.children[0].time.unwrap());
+
+        match mb.eval_query("SHOW collect_timing;").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => {
+                assert_eq!(results.rows[0][0].to_string(), "on")
+            }
+            _ => panic!("Expected select results"),
+        }
+        mb.eval_query("SET collect_timing = off;").unwrap();
+        mb.eval_query("SELECT name FROM t;").unwrap();
+        assert_eq!(mb.last_timing().plan_tree, None);
+        assert!(!mb.config().collect_timing);
+    }
+
+    #[test]
+    fn test_injected_timer() {
+        // A timer moving 1 ms forward every time it is read, so every phase takes as many
+        // milliseconds as the readings in it
+        let readings = Arc::new(AtomicU64::new(0));
+        let timer = {
+            let readings = readings.clone();
+            move || Duration::from_millis(readings.fetch_add(1, Ordering::SeqCst))
+        };
+        let mut mb = backend_with(BackendConfig::new().timer(timer), SETUP);
+        let ms = Duration::from_millis;
+
+        let result = mb.eval_query("CREATE TABLE v (id INT);").unwrap().remove(0);
+        let timing = mb.last_timing().clone();
+        // Looking the query up in the statement cache counts as parsing it
+        assert_eq!((timing.lex, timing.parse), (ms(1), ms(2)));
+        assert_eq!((timing.plan, timing.execute), (ms(0), ms(1)));
+        assert_eq!(result.time(), ms(1));
+
+        // The time of a result is its planning and execution alone
+        let result = mb
+            .eval_query("SELECT id FROM t WHERE qty > 5;")
+            .unwrap()
+            .remove(0);
+        let timing = mb.last_timing().clone();
+        assert_eq!((timing.lex, timing.parse), (ms(1), ms(2)));
+        assert_eq!((timing.plan, timing.execute), (ms(3), ms(4)));
+        assert_eq!(result.time(), ms(7));
+
+        // Every statement of a query is timed on its own
+        let results = mb
+            .eval_query("INSERT INTO v VALUES (1); DELETE FROM v;")
+            .unwrap();
+        assert_eq!(results[0].time(), ms(1));
+        assert_eq!(results[1].time(), ms(1));
+
+        // Scripts lex and parse each statement on its own
+        mb.execute_script("SELECT 1;".as_bytes(), Default::default())
+            .unwrap();
+        let timing = mb.last_timing().clone();
+        assert_eq!((timing.lex, timing.parse), (ms(1), ms(1)));
+
+        // With timing off the clock isn't read at all
+        let mut mb = MemoryBackend::with_config(
+            BackendConfig::new()
+                .time_statements(false)
+                .timer(move || Duration::from_millis(readings.fetch_add(1, Ordering::SeqCst))),
+        );
+        let results = mb
+            .eval_query("CREATE TABLE t (id INT); SELECT id FROM t;")
+            .unwrap();
+        assert!(results.iter().all(|result| result.time() == ms(0)));
+        assert_eq!(*mb.last_timing(), QueryTiming::default());
+        assert!(!mb.config().time_statements);
+    }
+
+    #[test]
+    fn test_index_scan_across_numeric_types() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE TABLE b (v BIGINT, s SMALLINT);
+            CREATE INDEX b_v ON b (v);
+            CREATE INDEX b_s ON b (s);
+            INSERT INTO b VALUES (1, 1), (5, 5), (5000000000, 7), (-5000000000, 9);",
+        )
+        .unwrap();
+        let values = |mb: &mut MemoryBackend, query: &str| match mb.eval_query(query) {
+            Ok(mut results) => match results.pop() {
+                Some(EvalResult::Select { results, .. }) => results
+                    .rows
+                    .iter()
+                    .map(|row| row[0].to_string())
+                    .collect::<Vec<_>>(),
+                _ => panic!("Expected select results"),
+            },
+            Err(err) => panic!("{}: {}", query, err),
+        };
+
+        // The INT constant is cast to the BIGINT column
+        let tests = vec![
+            ("v = 5", vec!["5"]),
+            ("v = 5000000000", vec!["5000000000"]),
+            ("5 < v", vec!["5000000000"]),
+            ("v >= 4294967296", vec!["5000000000"]),
+            ("v = 5.0", vec!["5"]),
+        ];
+        for (condition, expected) in tests {
+            let query = format!("SELECT v FROM b WHERE {};", condition);
+            assert_eq!(
+                plan(&mut mb, &format!("EXPLAIN {}", query)),
+                vec!["Result", "  ->  Index Scan using b_v on b"],
+                "{}",
+                condition
+            );
+            assert_eq!(values(&mut mb, &query), expected, "{}", condition);
+        }
+
+        // Constants that would change when cast are compared without the index
+        let tests = vec![
+            ("SELECT v FROM b WHERE v < 2.5;", vec!["1", "-5000000000"]),
+            ("SELECT s FROM b WHERE s = 100000;", vec![]),
+        ];
+        for (query, expected) in tests {
+            assert_eq!(
+                plan(&mut mb, &format!("EXPLAIN {}", query)),
+                vec!["Result", "  ->  Seq Scan on b"],
+                "{}",
+                query
+            );
+            assert_eq!(values(&mut mb, query), expected, "{}", query);
+        }
+
+        // Bound parameters are matched like constants
+        let explain = mb.prepare("EXPLAIN SELECT v FROM b WHERE v = $1;").unwrap();
+        let param = SqlValue::Numeric(crate::sql_types::SqlNumeric::BigInt { value: 5000000000 });
+        match explain.execute(&mut mb, &[param.clone()]).unwrap() {
+            EvalResult::Select { results, .. } => assert_eq!(
+                results.rows[1][0].to_string(),
+                "  ->  Index Scan using b_v on b"
+            ),
+            _ => panic!("Expected select results"),
+        }
+        let select = mb.prepare("SELECT v FROM b WHERE v = $1;").unwrap();
+        match select.execute(&mut mb, &[param]).unwrap() {
+            EvalResult::Select { results, .. } => {
+                assert_eq!(results.rows.len(), 1);
+                assert_eq!(results.rows[0][0].to_string(), "5000000000");
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use super::warnings::{
+    enter_warnings, warn, ACTIVE_SQL_TRANSACTION, IN_FAILED_SQL_TRANSACTION,
+    NO_ACTIVE_SQL_TRANSACTION,
+};
+use super::{MemoryBackend, TransactionStart};
+use crate::ast::{Statement, TransactionStatement};
+use crate::backend::{BackendError, EvalResult, TransactionStatus, Warning};
+use crate::sql_types::SqlValue;
+use std::time::Duration;
+
+// The transaction BEGIN started, which lasts over the statements after it until COMMIT or
+// ROLLBACK ends it
+pub(super) struct TransactionBlock {
+    start: TransactionStart,
+    // Whether one of its statements failed, after which it can only be rolled back
+    failed: bool,
+}
+
+impl MemoryBackend {
+    // Whether a transaction BEGIN started is running, and whether it failed. Transactions
+    // run by `transaction` end before it returns, they are never seen here.
+    pub fn transaction_status(&self) -> TransactionStatus {
+        match &self.transaction_block {
+            None => TransactionStatus::Idle,
+            Some(block) if block.failed => TransactionStatus::Failed,
+            Some(_) => TransactionStatus::InTransaction,
+        }
+    }
+
+    // Once the transaction failed only COMMIT and ROLLBACK run, like in Postgres
+    pub(super) fn check_transaction_block(
+        &self,
+        statement: &Statement,
+    ) -> Result<(), BackendError> {
+        let ends_block = matches!(
+            statement,
+            Statement::TransactionStatement(
+                TransactionStatement::Commit | TransactionStatement::Rollback
+            )
+        );
+        match &self.transaction_block {
+            Some(block) if block.failed && !ends_block => {
+                Err(BackendError::InFailedSqlTransaction(
+                    "Current transaction is aborted, commands ignored until end of transaction \
+                    block."
+                        .to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Any error within the transaction BEGIN started fails it, parse errors included
+    pub(super) fn fail_transaction_block(&mut self) {
+        if let Some(block) = &mut self.transaction_block {
+            block.failed = true;
+        }
+    }
+
+    pub(super) fn eval_transaction_statement(
+        &mut self,
+        statement: TransactionStatement,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        let start = self.timing.now();
+        let warnings = enter_warnings();
+        self.timing.start_statement();
+        let mut result = match statement {
+            TransactionStatement::Begin => {
+                self.begin_transaction_block();
+                EvalResult::Begin {
+                    time: Duration::default(),
+                    warnings: vec![],
+                }
+            }
+            TransactionStatement::Commit | TransactionStatement::Rollback => {
+                let commit = statement == TransactionStatement::Commit;
+                match self.end_transaction_block(commit)? {
+                    true => EvalResult::Commit {
+                        time: Duration::default(),
+                        warnings: vec![],
+                    },
+                    false => EvalResult::Rollback {
+                        time: Duration::default(),
+                        warnings: vec![],
+                    },
+                }
+            }
+        };
+        let elapsed = self.timing.since(start);
+        self.timing.finish_statement(elapsed);
+        *result.warnings_mut() = warnings.finish();
+        *result.time_mut() = elapsed;
+        Ok(result)
+    }
+
+    fn begin_transaction_block(&mut self) {
+        if self.transaction_depth > 0 {
+            warn(Warning::new(
+                ACTIVE_SQL_TRANSACTION,
+                "There is already a transaction in progress.".to_string(),
+            ));
+            return;
+        }
+        let start = self.begin_transaction();
+        self.transaction_block = Some(TransactionBlock {
+            start,
+            failed: false,
+        });
+    }
+
+    // Ends the transaction BEGIN started, committing it unless it failed. Whether it was
+    // committed.
+    fn end_transaction_block(&mut self, commit: bool) -> Result<bool, BackendError> {
+        // A transaction run by `transaction` is in the way, like that of a script
+        if self.transaction_block.is_some() && self.transaction_depth > 1 {
+            return Err(BackendError::ActiveSqlTransaction(
+                "The transaction BEGIN started can't end inside another transaction.".to_string(),
+            ));
+        }
+        let block = match self.transaction_block.take() {
+            Some(block) => block,
+            None => {
+                warn(Warning::new(
+                    NO_ACTIVE_SQL_TRANSACTION,
+                    "There is no transaction in progress.".to_string(),
+                ));
+                return Ok(commit);
+            }
+        };
+        if commit && block.failed {
+            warn(Warning::new(
+                IN_FAILED_SQL_TRANSACTION,
+                "The transaction was aborted, COMMIT rolled it back.".to_string(),
+            ));
+        }
+        let commit = commit && !block.failed;
+        self.end_transaction(block.start, commit);
+        Ok(commit)
+    }
+
+    // Rolls back the transaction BEGIN started if it is still running. Whether it was.
+    pub(super) fn abandon_transaction_block(&mut self) -> bool {
+        match self.transaction_block.take() {
+            Some(block) => {
+                self.end_transaction(block.start, false);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod transaction_block_tests {
+    use super::*;
+    use crate::backend_memory::test_support::try_rows;
+    use crate::backend_memory::Connection;
+    use std::sync::{Arc, Mutex};
+
+    fn count(mb: &mut MemoryBackend) -> usize {
+        match mb.eval_query("SELECT id FROM t;").unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results.rows.len(),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    fn warning_codes(result: &EvalResult<SqlValue>) -> Vec<&str> {
+        result.warnings().iter().map(|w| w.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_failed_transaction_until_rollback() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        assert_eq!(mb.transaction_status().ready_for_query(), b'I');
+
+        let results = mb.eval_query("BEGIN;").unwrap();
+        assert!(matches!(results[0], EvalResult::Begin { .. }));
+        assert_eq!(mb.transaction_status().ready_for_query(), b'T');
+        mb.eval_query("INSERT INTO t VALUES (1);").unwrap();
+
+        let err = mb.eval_query("SELECT nope FROM t;").unwrap_err();
+        assert_eq!(err.code(), "42703");
+        assert_eq!(mb.transaction_status().ready_for_query(), b'E');
+        let err = mb.eval_query("SELECT 1;").unwrap_err();
+        assert_eq!(err.code(), "25P02");
+        assert_eq!(
+            err.message(),
+            "Current transaction is aborted, commands ignored until end of transaction block."
+        );
+
+        let results = mb.eval_query("ROLLBACK;").unwrap();
+        assert!(matches!(results[0], EvalResult::Rollback { .. }));
+        assert_eq!(mb.transaction_status(), TransactionStatus::Idle);
+        assert!(mb.eval_query("SELECT 1;").is_ok());
+        assert_eq!(count(&mut mb), 0);
+    }
+
+    #[test]
+    fn test_commit_and_rollback() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+
+        let results = mb
+            .eval_query("BEGIN; INSERT INTO t VALUES (1); COMMIT WORK;")
+            .unwrap();
+        assert!(matches!(results[2], EvalResult::Commit { .. }));
+        mb.eval_query("BEGIN TRANSACTION; INSERT INTO t VALUES (2);")
+            .unwrap();
+        assert_eq!(count(&mut mb), 2);
+        mb.eval_query("ROLLBACK;").unwrap();
+        assert_eq!(count(&mut mb), 1);
+
+        // Committing a failed transaction rolls it back, parse errors fail it too
+        mb.eval_query("BEGIN; INSERT INTO t VALUES (3);").unwrap();
+        assert!(mb.eval_query("INSERT INTO t VALUES (4").is_err());
+        assert_eq!(mb.transaction_status(), TransactionStatus::Failed);
+        let results = mb.eval_query("COMMIT;").unwrap();
+        assert!(matches!(results[0], EvalResult::Rollback { .. }));
+        assert_eq!(warning_codes(&results[0]), ["25P02"]);
+        assert_eq!(mb.transaction_status(), TransactionStatus::Idle);
+        assert_eq!(count(&mut mb), 1);
+    }
+
+    #[test]
+    fn test_transaction_statements_with_nothing_to_do() {
+        let mut mb = MemoryBackend::new();
+        let results = mb.eval_query("COMMIT; ROLLBACK;").unwrap();
+        assert_eq!(warning_codes(&results[0]), ["25P01"]);
+        assert!(matches!(results[1], EvalResult::Rollback { .. }));
+        assert_eq!(warning_codes(&results[1]), ["25P01"]);
+
+        let results = mb.eval_query("BEGIN; BEGIN;").unwrap();
+        assert_eq!(warning_codes(&results[1]), ["25001"]);
+        mb.eval_query("COMMIT;").unwrap();
+        assert_eq!(mb.transaction_status(), TransactionStatus::Idle);
+
+        // Transactions run by the application can't be ended or nested with statements
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        let results = mb
+            .transaction(|mb| mb.eval_query("BEGIN; INSERT INTO t VALUES (1);"))
+            .unwrap();
+        assert_eq!(warning_codes(&results[0]), ["25001"]);
+        assert_eq!(mb.transaction_status(), TransactionStatus::Idle);
+        mb.eval_query("BEGIN;").unwrap();
+        let err = mb.transaction(|mb| mb.eval_query("COMMIT;")).unwrap_err();
+        assert_eq!(err.code(), "25001");
+        mb.eval_query("ROLLBACK;").unwrap();
+        assert_eq!(count(&mut mb), 1);
+    }
+
+    #[test]
+    fn test_connection_transactions_span_queries() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut connection = Connection::open(backend.clone());
+        connection.eval_query("CREATE TABLE t (id INT);").unwrap();
+
+        // As psql sends them, one statement at a time
+        connection.eval_query("BEGIN;").unwrap();
+        assert_eq!(
+            connection.transaction_status(),
+            TransactionStatus::InTransaction
+        );
+        connection.eval_query("INSERT INTO t VALUES (1);").unwrap();
+        let err = connection.eval_query("SELECT nope FROM t;").unwrap_err();
+        assert_eq!(err.code(), "42703");
+        assert_eq!(connection.transaction_status(), TransactionStatus::Failed);
+        let err = connection.eval_query("SELECT 1;").unwrap_err();
+        assert_eq!(err.code(), "25P02");
+        let results = connection.eval_query("ROLLBACK;").unwrap();
+        assert!(matches!(results[0], EvalResult::Rollback { .. }));
+        assert_eq!(connection.transaction_status(), TransactionStatus::Idle);
+        assert_eq!(count(&mut backend.lock().unwrap()), 0);
+
+        connection.eval_query("BEGIN;").unwrap();
+        connection.eval_query("INSERT INTO t VALUES (2);").unwrap();
+        let results = connection.eval_query("COMMIT;").unwrap();
+        assert!(matches!(results[0], EvalResult::Commit { .. }));
+        assert!(results[0].warnings().is_empty());
+        assert_eq!(count(&mut backend.lock().unwrap()), 1);
+
+        // Closing the connection rolls back what it left running
+        connection
+            .eval_query("BEGIN; INSERT INTO t VALUES (3);")
+            .unwrap();
+        drop(connection);
+        let mut backend = backend.lock().unwrap();
+        assert_eq!(backend.transaction_status(), TransactionStatus::Idle);
+        assert_eq!(count(&mut backend), 1);
+    }
+
+    #[test]
+    fn test_connections_wait_for_transactions_of_others() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+        let mut third = Connection::open(backend.clone());
+        first.eval_query("CREATE TABLE t (id INT);").unwrap();
+        second.eval_query("SET lock_timeout = 10;").unwrap();
+        third.eval_query("SET lock_timeout = 60000;").unwrap();
+        first
+            .eval_query("BEGIN; INSERT INTO t VALUES (1);")
+            .unwrap();
+        assert_eq!(second.transaction_status(), TransactionStatus::Idle);
+        let err = second.eval_query("SELECT id FROM t;").unwrap_err();
+        assert_eq!(err.code(), "55P03");
+
+        // They wait as long as the timeout lets them
+        let waiting = std::thread::spawn(move || {
+            third.eval_query("INSERT INTO t VALUES (2);").unwrap();
+            third
+        });
+        first.eval_query("INSERT INTO t VALUES (3);").unwrap();
+        first.eval_query("COMMIT;").unwrap();
+        drop(waiting.join().unwrap());
+        assert_eq!(count(&mut backend.lock().unwrap()), 3);
+    }
+
+    #[test]
+    fn test_connections_without_lock_timeout_fail_at_once() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+        first.eval_query("CREATE TABLE t (id INT);").unwrap();
+        first
+            .eval_query("BEGIN; INSERT INTO t VALUES (1);")
+            .unwrap();
+
+        // On the same thread as the transaction, waiting for it would never end
+        for query in ["SELECT id FROM t;", "INSERT INTO t VALUES (2);"] {
+            let err = second.eval_query(query).unwrap_err();
+            assert_eq!(err.code(), "55P03");
+        }
+
+        first.eval_query("COMMIT;").unwrap();
+        assert_eq!(try_rows(&mut second, "SELECT id FROM t;").unwrap(), ["1"]);
+    }
+}
@@ -0,0 +1,676 @@
+use super::cache::statement_tables;
+use super::semi_join::value_expression;
+use super::{MemoryBackend, RowWrites};
+use crate::ast::*;
+use crate::backend::BackendError;
+use crate::sql_types::SqlValue;
+use std::sync::Arc;
+
+// How deep triggers can fire one another, the statement of one changing the table of
+// the next
+pub const MAX_TRIGGER_DEPTH: usize = 16;
+
+// A statement run after each INSERT, UPDATE or DELETE of a table, within it
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Trigger {
+    pub(super) name: String,
+    pub(super) table: String,
+    pub(super) events: Vec<TriggerEvent>,
+    pub(super) for_each_row: bool,
+    action: Statement,
+    // The other tables the statement reads or changes, which can't be dropped while it does
+    depends_on: Vec<String>,
+}
+
+// The triggers of the backend, of each table by name, the order they fire in. Creating
+// and dropping them is undone along with a failed transaction.
+#[derive(Debug, Default)]
+pub(super) struct Triggers {
+    triggers: Arc<Vec<Trigger>>,
+    // The triggers when each running transaction began, the innermost last
+    snapshots: Vec<Arc<Vec<Trigger>>>,
+    // Triggers firing at the moment, each within the one before
+    depth: usize,
+}
+
+impl Triggers {
+    pub(super) fn begin(&mut self) {
+        self.snapshots.push(self.triggers.clone());
+    }
+
+    pub(super) fn end(&mut self, succeeded: bool) {
+        if let Some(snapshot) = self.snapshots.pop() {
+            if !succeeded {
+                self.triggers = snapshot;
+            }
+        }
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = &Trigger> {
+        self.triggers.iter()
+    }
+
+    pub(super) fn on(&self, table: &str) -> bool {
+        self.triggers.iter().any(|trigger| trigger.table == table)
+    }
+
+    fn find(&self, name: &str, table: &str) -> Option<usize> {
+        self.triggers
+            .iter()
+            .position(|trigger| trigger.name == name && trigger.table == table)
+    }
+
+    fn firing(&self, table: &str, event: TriggerEvent) -> Vec<Trigger> {
+        self.triggers
+            .iter()
+            .filter(|trigger| trigger.table == table && trigger.events.contains(&event))
+            .cloned()
+            .collect()
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Trigger) -> bool) {
+        Arc::make_mut(&mut self.triggers).retain(keep);
+    }
+}
+
+impl MemoryBackend {
+    // Triggers are only created on the tables of the backend, temporary ones would be
+    // gone by the next session
+    pub fn create_trigger(
+        &mut self,
+        statement: CreateTriggerStatement,
+    ) -> Result<bool, BackendError> {
+        let table = &statement.table;
+        if self.is_temporary(table) {
+            return Err(BackendError::FeatureNotSupported(format!(
+                "Triggers on temporary table \"{}\" are not supported.",
+                table
+            )));
+        }
+        if self.views.contains(table) {
+            return Err(BackendError::WrongObjectType(format!(
+                "\"{}\" is a view, triggers can only be created on tables.",
+                table
+            )));
+        }
+        let columns = match self.storage.table(table) {
+            Some(table) => table.columns.clone(),
+            None => {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Table \"{}\" doesn't exist.",
+                    table
+                )))
+            }
+        };
+        if self.triggers.find(&statement.name, table).is_some() {
+            return Err(BackendError::DuplicateObject(format!(
+                "Trigger \"{}\" for table \"{}\" already exists.",
+                statement.name, table
+            )));
+        }
+        match statement.action.as_ref() {
+            Statement::InsertStatement(_)
+            | Statement::UpdateStatement(_)
+            | Statement::DeleteStatement(_)
+            | Statement::SelectStatement(_)
+            | Statement::WithStatement(_) => {}
+            _ => {
+                return Err(BackendError::FeatureNotSupported(
+                    "Triggers can only run INSERT, UPDATE, DELETE or a query.".to_string(),
+                ))
+            }
+        }
+
+        // NEW and OLD only name columns of the table, and only for each row
+        let mut action = statement.action.as_ref().clone();
+        let mut invalid = None;
+        bind_statement(&mut action, &mut |expression| {
+            if let Some((_, column)) = pseudo_row_column(expression) {
+                if !statement.for_each_row {
+                    invalid.get_or_insert(BackendError::FeatureNotSupported(
+                        "Triggers for each statement can't read NEW or OLD.".to_string(),
+                    ));
+                } else if !columns.iter().any(|name| name == column) {
+                    invalid.get_or_insert(BackendError::UndefinedColumn(format!(
+                        "Record has no field \"{}\".",
+                        column
+                    )));
+                }
+            }
+            None
+        });
+        if let Some(err) = invalid {
+            return Err(err);
+        }
+
+        let mut depends_on = vec![];
+        statement_tables(&action, &mut depends_on);
+        depends_on.extend(action.changed_table().map(str::to_string));
+        depends_on.sort();
+        depends_on.dedup();
+        depends_on.retain(|name| name != table && self.storage.table(name).is_some());
+
+        let trigger = Trigger {
+            name: statement.name,
+            table: statement.table,
+            events: statement.events,
+            for_each_row: statement.for_each_row,
+            action,
+            depends_on,
+        };
+        let triggers = Arc::make_mut(&mut self.triggers.triggers);
+        let position = triggers
+            .iter()
+            .position(|other| (&other.table, &other.name) > (&trigger.table, &trigger.name))
+            .unwrap_or(triggers.len());
+        triggers.insert(position, trigger);
+        Ok(true)
+    }
+
+    pub fn drop_trigger(&mut self, statement: DropTriggerStatement) -> Result<bool, BackendError> {
+        match self.triggers.find(&statement.name, &statement.table) {
+            Some(position) => {
+                Arc::make_mut(&mut self.triggers.triggers).remove(position);
+                Ok(true)
+            }
+            None if statement.if_exists => Ok(false),
+            None => Err(BackendError::UndefinedObject(format!(
+                "Trigger \"{}\" for table \"{}\" doesn't exist.",
+                statement.name, statement.table
+            ))),
+        }
+    }
+
+    // Fails when the statement of a trigger of another table reads or changes a table that
+    // is dropped, unless the trigger is to be dropped along with it
+    pub(super) fn check_dependent_triggers(
+        &self,
+        table: &str,
+        cascade: bool,
+    ) -> Result<(), BackendError> {
+        let dependent = self
+            .triggers
+            .iter()
+            .find(|trigger| trigger.depends_on.iter().any(|name| name == table));
+        match dependent {
+            Some(trigger) if !cascade => Err(BackendError::DependentObjectsStillExist(format!(
+                "Cannot drop table \"{}\" because trigger \"{}\" on table \"{}\" depends on it.",
+                table, trigger.name, trigger.table
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // Drops the triggers of a table that is dropped, and those using it
+    pub(super) fn drop_table_triggers(&mut self, table: &str) {
+        self.triggers.retain(|trigger| {
+            trigger.table != table && !trigger.depends_on.iter().any(|name| name == table)
+        });
+    }
+
+    // Writes rows to a table and fires its triggers for them, as part of the statement
+    // writing them: when a trigger fails the rows are not written either
+    pub(super) fn write_rows_firing(
+        &mut self,
+        table: &str,
+        writes: &RowWrites,
+    ) -> Result<(), BackendError> {
+        let event = match writes {
+            RowWrites::Insert(_) => TriggerEvent::Insert,
+            RowWrites::Update(_) => TriggerEvent::Update,
+            RowWrites::Delete(_) => TriggerEvent::Delete,
+        };
+        let triggers = match self.is_temporary(table) {
+            true => vec![],
+            false => self.triggers.firing(table, event),
+        };
+        if triggers.is_empty() {
+            return self.engine_mut(table).write_rows(table, writes);
+        }
+        self.transaction(|backend| {
+            backend.engine_mut(table).write_rows(table, writes)?;
+            for trigger in &triggers {
+                backend.fire(trigger, writes)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn fire(&mut self, trigger: &Trigger, writes: &RowWrites) -> Result<(), BackendError> {
+        if self.triggers.depth >= MAX_TRIGGER_DEPTH {
+            return Err(BackendError::ProgramLimitExceeded(format!(
+                "Trigger \"{}\" on table \"{}\" fired more than {} triggers deep.",
+                trigger.name, trigger.table, MAX_TRIGGER_DEPTH
+            )));
+        }
+        self.triggers.depth += 1;
+        let result = self.run_trigger(trigger, writes);
+        self.triggers.depth -= 1;
+        result
+    }
+
+    fn run_trigger(&mut self, trigger: &Trigger, writes: &RowWrites) -> Result<(), BackendError> {
+        if !trigger.for_each_row {
+            return self.run_trigger_action(trigger.action.clone());
+        }
+        let columns = match self.table(&trigger.table) {
+            Some(table) => table.columns.clone(),
+            None => return Ok(()),
+        };
+        // The old and the new row of each row written
+        let rows: Vec<_> = match writes {
+            RowWrites::Insert(rows) => rows.iter().map(|row| (None, Some(row))).collect(),
+            RowWrites::Update(rows) => rows
+                .iter()
+                .map(|(_, old_row, new_row)| (Some(old_row), Some(new_row)))
+                .collect(),
+            RowWrites::Delete(rows) => rows.iter().map(|(_, row)| (Some(row), None)).collect(),
+        };
+        for (old_row, new_row) in rows {
+            let value = |row: Option<&Vec<SqlValue>>, column: &str| {
+                let idx = columns.iter().position(|name| name == column);
+                match (row, idx) {
+                    (Some(row), Some(idx)) => value_expression(&row[idx]),
+                    _ => value_expression(&SqlValue::Null),
+                }
+            };
+            let mut action = trigger.action.clone();
+            let mut failed = None;
+            bind_statement(&mut action, &mut |expression| {
+                let (name, column) = pseudo_row_column(expression)?;
+                let row = if name == "new" { new_row } else { old_row };
+                match value(row, column) {
+                    Ok(expression) => Some(expression),
+                    Err(err) => {
+                        failed.get_or_insert(err);
+                        None
+                    }
+                }
+            });
+            if let Some(err) = failed {
+                return Err(err);
+            }
+            self.run_trigger_action(action)?;
+        }
+        Ok(())
+    }
+
+    // The action reads the tables, not the WITH queries of the statement firing it
+    fn run_trigger_action(&mut self, action: Statement) -> Result<(), BackendError> {
+        let outer = std::mem::take(&mut self.with_tables);
+        let result = self.run_trigger_statement(action);
+        self.with_tables = outer;
+        result
+    }
+
+    fn run_trigger_statement(&mut self, mut action: Statement) -> Result<(), BackendError> {
+        self.expand_views(&mut action)?;
+        let catalog = self.enter_catalog(&action)?;
+        let result = self.run_statement(action);
+        self.leave_catalog(catalog)?;
+        result.map(|_| ())
+    }
+}
+
+// The row, `new` or `old`, and the column a reference like `NEW.col` names
+fn pseudo_row_column(expression: &Expression) -> Option<(&str, &str)> {
+    match expression {
+        Expression::TableColumn(TableColumn {
+            table_name: Some(name),
+            col_name,
+        }) if name == "new" || name == "old" => Some((name, col_name)),
+        _ => None,
+    }
+}
+
+type Replace<'a> = dyn FnMut(&Expression) -> Option<Expression> + 'a;
+
+// Puts what `replace` gives in place of the expressions of a statement it gives something
+// for, in its subqueries too
+fn bind_statement(statement: &mut Statement, replace: &mut Replace) {
+    match statement {
+        Statement::SelectStatement(select) => bind_select(select, replace),
+        Statement::InsertStatement(insert) => {
+            for value in insert.rows.iter_mut().flatten() {
+                bind_expression(value, replace);
+            }
+            if let Some(query) = insert.query.as_mut() {
+                bind_select(query, replace);
+            }
+            bind_items(&mut insert.returning, replace);
+        }
+        Statement::UpdateStatement(update) => {
+            for assignment in update.assignments.iter_mut() {
+                bind_expression(&mut assignment.value, replace);
+            }
+            bind_expression(&mut update.where_clause, replace);
+            for order_by in update.order_by.iter_mut() {
+                bind_expression(&mut order_by.exp, replace);
+            }
+            bind_items(&mut update.returning, replace);
+        }
+        Statement::DeleteStatement(delete) => {
+            bind_expression(&mut delete.where_clause, replace);
+            for order_by in delete.order_by.iter_mut() {
+                bind_expression(&mut order_by.exp, replace);
+            }
+            bind_items(&mut delete.returning, replace);
+        }
+        Statement::WithStatement(with) => {
+            for query in with.queries.iter_mut() {
+                bind_statement(&mut query.statement, replace);
+            }
+            bind_statement(&mut with.statement, replace);
+        }
+        _ => {}
+    }
+}
+
+fn bind_items(items: &mut [SelectItem], replace: &mut Replace) {
+    for item in items.iter_mut() {
+        bind_expression(&mut item.expression, replace);
+    }
+}
+
+fn bind_select(select: &mut SelectStatement, replace: &mut Replace) {
+    bind_items(&mut select.items, replace);
+    for source in select.from.iter_mut() {
+        bind_source(source, replace);
+    }
+    bind_expression(&mut select.where_clause, replace);
+    for exp in select.group_by.iter_mut() {
+        bind_expression(exp, replace);
+    }
+    bind_expression(&mut select.having, replace);
+    for order_by in select.order_by.iter_mut() {
+        bind_expression(&mut order_by.exp, replace);
+    }
+}
+
+fn bind_source(source: &mut RowDataSource, replace: &mut Replace) {
+    let joins = match source {
+        RowDataSource::SubSelect { select, joins, .. } => {
+            bind_select(select, replace);
+            joins
+        }
+        RowDataSource::Table { joins, .. } => joins,
+        RowDataSource::Values { rows, joins, .. } => {
+            for exp in rows.iter_mut().flatten() {
+                bind_expression(exp, replace);
+            }
+            joins
+        }
+        RowDataSource::Function { call, joins, .. } => {
+            for arg in call.args.iter_mut() {
+                bind_expression(arg, replace);
+            }
+            joins
+        }
+    };
+    for join in joins.iter_mut() {
+        bind_source(&mut join.source, replace);
+        bind_expression(&mut join.on, replace);
+    }
+}
+
+fn bind_expression(expression: &mut Expression, replace: &mut Replace) {
+    if let Some(replacement) = replace(expression) {
+        *expression = replacement;
+        return;
+    }
+    match expression {
+        Expression::Binary(binary) => {
+            bind_expression(&mut binary.first, replace);
+            bind_expression(&mut binary.second, replace);
+        }
+        Expression::Unary(unary) => bind_expression(&mut unary.first, replace),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            bind_expression(data, replace)
+        }
+        Expression::SubSelect(select) | Expression::Exists(select) => bind_select(select, replace),
+        Expression::Quantified(quantified) => {
+            bind_expression(&mut quantified.first, replace);
+            if let QuantifiedSet::SubSelect(select) = &mut quantified.set {
+                bind_select(select, replace);
+            }
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                bind_expression(arg, replace);
+            }
+            if let Some(filter) = call.filter.as_mut() {
+                bind_expression(filter, replace);
+            }
+            if let Some(over) = &mut call.over {
+                for exp in over.partition_by.iter_mut() {
+                    bind_expression(exp, replace);
+                }
+                for order_by in over.order_by.iter_mut() {
+                    bind_expression(&mut order_by.exp, replace);
+                }
+            }
+        }
+        Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+#[cfg(test)]
+mod triggers_tests {
+    use super::*;
+    use crate::backend_memory::test_support::rows;
+    use crate::sql_types::SqlNumeric;
+
+    #[test]
+    fn test_insert_trigger_counter() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE orders (id INT, amount INT);
+            CREATE TABLE stats (n INT, total INT);
+            CREATE TABLE log (statements INT);
+            INSERT INTO stats VALUES (0, 0);
+            CREATE TRIGGER count_orders AFTER INSERT ON orders FOR EACH ROW
+                EXECUTE (UPDATE stats SET n = n + 1, total = total + NEW.amount);
+            CREATE TRIGGER count_inserts AFTER INSERT ON orders
+                EXECUTE (INSERT INTO log VALUES (1));",
+        )
+        .unwrap();
+        mb.eval_query("INSERT INTO orders VALUES (1, 10), (2, 5);")
+            .unwrap();
+        mb.eval_query("INSERT INTO orders VALUES (3, 7);").unwrap();
+        // The row triggers see each row, the statement ones fire once per statement
+        assert_eq!(rows(&mut mb, "SELECT n, total FROM stats;"), ["3|22"]);
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM log;"), ["2"]);
+
+        // A failing trigger undoes the statement firing it
+        mb.eval_query(
+            "DROP TRIGGER count_inserts ON orders;
+            CREATE TRIGGER fail AFTER INSERT ON orders FOR EACH ROW
+                EXECUTE (SELECT 1 / 0 WHERE NEW.id = 4);",
+        )
+        .unwrap();
+        assert_eq!(
+            mb.eval_query("INSERT INTO orders VALUES (4, 1);")
+                .unwrap_err()
+                .code(),
+            "22012"
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM orders;"), ["3"]);
+        assert_eq!(rows(&mut mb, "SELECT n, total FROM stats;"), ["3|22"]);
+
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT tgname, tgtype FROM pg_trigger ORDER BY tgname;"
+            ),
+            ["count_orders|5", "fail|5"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT tablename, hastriggers FROM pg_tables;")
+                .into_iter()
+                .filter(|row| !row.starts_with("pg_"))
+                .collect::<Vec<_>>(),
+            ["log|false", "orders|true", "stats|false"]
+        );
+        assert_eq!(
+            mb.eval_query("DROP TRIGGER nope ON orders;")
+                .unwrap_err()
+                .code(),
+            "42704"
+        );
+        mb.eval_query("DROP TRIGGER IF EXISTS nope ON orders;")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_execute_many_fires_triggers() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE orders (id INT, amount INT);
+            CREATE TABLE stats (n INT, total INT);
+            CREATE TABLE log (statements INT);
+            INSERT INTO stats VALUES (0, 0);
+            CREATE TRIGGER count_orders AFTER INSERT ON orders FOR EACH ROW
+                EXECUTE (UPDATE stats SET n = n + 1, total = total + NEW.amount);
+            CREATE TRIGGER count_inserts AFTER INSERT ON orders
+                EXECUTE (INSERT INTO log VALUES (1));",
+        )
+        .unwrap();
+        let int = |value| SqlValue::Numeric(SqlNumeric::Int { value });
+        let insert = mb.prepare("INSERT INTO orders VALUES ($1, $2);").unwrap();
+        let batch = vec![vec![int(1), int(10)], vec![int(2), int(5)]];
+        assert_eq!(insert.execute_many(&mut mb, batch).unwrap(), 2);
+        // Each parameter set fires the triggers as an INSERT of its own would
+        assert_eq!(rows(&mut mb, "SELECT n, total FROM stats;"), ["2|15"]);
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM log;"), ["2"]);
+
+        // A failing trigger undoes the whole batch
+        mb.eval_query(
+            "CREATE TRIGGER fail AFTER INSERT ON orders FOR EACH ROW
+                EXECUTE (SELECT 1 / 0 WHERE NEW.id = 4);",
+        )
+        .unwrap();
+        let batch = vec![vec![int(3), int(1)], vec![int(4), int(1)]];
+        let err = insert.execute_many(&mut mb, batch).unwrap_err();
+        assert_eq!(err.code(), "22012");
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM orders;"), ["2"]);
+        assert_eq!(rows(&mut mb, "SELECT n, total FROM stats;"), ["2|15"]);
+    }
+
+    #[test]
+    fn test_delete_trigger_cascade() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE customers (id INT, name TEXT);
+            CREATE TABLE orders (id INT, customer_id INT);
+            INSERT INTO customers VALUES (1, 'a'), (2, 'b');
+            INSERT INTO orders VALUES (1, 1), (2, 1), (3, 2);
+            CREATE TRIGGER delete_orders AFTER DELETE OR UPDATE ON customers FOR EACH ROW
+                EXECUTE (DELETE FROM orders WHERE customer_id = OLD.id);",
+        )
+        .unwrap();
+        mb.eval_query("DELETE FROM customers WHERE name = 'a';")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM orders;"), ["3"]);
+        mb.eval_query("UPDATE customers SET id = 3;").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM orders;"),
+            Vec::<String>::new()
+        );
+
+        // The orders can't be dropped while the trigger deletes from them
+        assert_eq!(
+            mb.eval_query("DROP TABLE orders;").unwrap_err().code(),
+            "2BP01"
+        );
+        mb.eval_query("DROP TABLE orders CASCADE;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM pg_trigger;"), ["0"]);
+        mb.eval_query("DELETE FROM customers;").unwrap();
+    }
+
+    #[test]
+    fn test_trigger_recursion_limit() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE items (depth INT);
+            CREATE TRIGGER again AFTER INSERT ON items FOR EACH ROW
+                EXECUTE (INSERT INTO items VALUES (NEW.depth + 1));",
+        )
+        .unwrap();
+        let err = mb.eval_query("INSERT INTO items VALUES (0);").unwrap_err();
+        assert_eq!(err.code(), "54000");
+        assert!(err
+            .message()
+            .contains(&format!("more than {} triggers deep", MAX_TRIGGER_DEPTH)));
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM items;"), ["0"]);
+
+        // Stopping before the limit is fine
+        mb.eval_query(
+            "DROP TRIGGER again ON items;
+            CREATE TRIGGER again AFTER INSERT ON items FOR EACH ROW
+                EXECUTE (INSERT INTO items SELECT NEW.depth + 1 WHERE NEW.depth < 3);",
+        )
+        .unwrap();
+        mb.eval_query("INSERT INTO items VALUES (0);").unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT depth FROM items ORDER BY depth;"),
+            ["0", "1", "2", "3"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_triggers() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT);
+            CREATE TEMP TABLE tmp (id INT);
+            CREATE VIEW v AS SELECT id FROM t;
+            CREATE TRIGGER tr AFTER INSERT ON t EXECUTE (SELECT 1);",
+        )
+        .unwrap();
+        for (query, code) in [
+            (
+                "CREATE TRIGGER tr AFTER INSERT ON t EXECUTE (SELECT 1);",
+                "42710",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON nope EXECUTE (SELECT 1);",
+                "42P01",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON tmp EXECUTE (SELECT 1);",
+                "0A000",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON v EXECUTE (SELECT 1);",
+                "42809",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON t EXECUTE (SELECT NEW.id);",
+                "0A000",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON t FOR EACH ROW EXECUTE (SELECT NEW.nope);",
+                "42703",
+            ),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON t EXECUTE (DROP TABLE t);",
+                "0A000",
+            ),
+            (
+                "CREATE TRIGGER x BEFORE INSERT ON t EXECUTE (SELECT 1);",
+                "42601",
+            ),
+        ] {
+            assert_eq!(mb.eval_query(query).unwrap_err().code(), code, "{}", query);
+        }
+
+        // Dropping the table drops its triggers
+        mb.eval_query("DROP VIEW v; DROP TABLE t; CREATE TABLE t (id INT);")
+            .unwrap();
+        mb.eval_query("CREATE TRIGGER tr AFTER INSERT ON t EXECUTE (SELECT 1);")
+            .unwrap();
+    }
+}
@@ -0,0 +1,581 @@
+use super::progress::ProgressReport;
+use super::semi_join::{
+    column_references, has_subquery, select_expressions_mut, substitute, value_expression,
+};
+use super::{push_order_key, MemoryBackend, RowWrites, Table};
+use crate::ast::*;
+use crate::backend::{BackendError, ERR_COLUMN_DOES_NOT_EXIST, ERR_TABLE_DOES_NOT_EXIST};
+use crate::lexer::Token;
+use crate::sql_types::{SqlType, SqlValue};
+
+// A row an UPDATE changes: its position in the table, its values before and after
+type RowUpdate = (usize, Vec<SqlValue>, Vec<SqlValue>);
+
+fn is_default(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Literal(LiteralExpression {
+            literal: Token::Default,
+        })
+    )
+}
+
+impl MemoryBackend {
+    // Changes the rows WHERE holds for, giving how many there were. Every new value is
+    // worked out from the rows as they were before the statement, so `SET a = b, b = a`
+    // swaps the two. Either every row changes or none does.
+    pub fn update(&mut self, statement: UpdateStatement) -> Result<usize, BackendError> {
+        Ok(self.update_rows(&statement)?.len())
+    }
+
+    // Like `update`, giving the rows changed as they are after it
+    pub(super) fn update_rows(
+        &mut self,
+        statement: &UpdateStatement,
+    ) -> Result<Vec<Vec<SqlValue>>, BackendError> {
+        let updates = self.row_updates(statement)?;
+
+        self.mark_written(&statement.table);
+        let writes = RowWrites::Update(updates);
+        self.write_rows_firing(&statement.table, &writes)?;
+
+        if self.changes.hooked() {
+            if let RowWrites::Update(updates) = &writes {
+                let rows = updates
+                    .iter()
+                    .map(|(_, old_row, new_row)| (old_row.clone(), new_row.clone()));
+                self.changes
+                    .record_updates(&statement.table, rows.collect());
+            }
+        }
+        Ok(writes.into_rows())
+    }
+
+    fn row_updates(&self, statement: &UpdateStatement) -> Result<Vec<RowUpdate>, BackendError> {
+        let table = match self.engine(&statement.table).scan_table(&statement.table)? {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(
+                    ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                ))
+            }
+        };
+        // The alias hides the name of the table, as in Postgres
+        let source = statement.as_clause.as_ref().unwrap_or(&statement.table);
+        let sources = vec![source.clone(); table.columns.len()];
+
+        // Subqueries that don't read the row run once, before any row changes
+        let mut assignments: Vec<(usize, Expression)> = vec![];
+        for assignment in &statement.assignments {
+            let column = match table.columns.iter().position(|c| *c == assignment.column) {
+                Some(column) => column,
+                None => {
+                    return Err(BackendError::UndefinedColumn(format!(
+                        "{}: {}",
+                        assignment.column, ERR_COLUMN_DOES_NOT_EXIST
+                    )))
+                }
+            };
+            if assignments.iter().any(|(known, _)| *known == column) {
+                return Err(BackendError::SyntaxError(format!(
+                    "Multiple assignments to same column \"{}\".",
+                    assignment.column
+                )));
+            }
+            if table.is_generated(column) && !is_default(&assignment.value) {
+                return Err(BackendError::GeneratedAlways(format!(
+                    "Column \"{}\" can only be updated to DEFAULT.",
+                    assignment.column
+                )));
+            }
+            check_source(&assignment.value, source)?;
+            let mut value = assignment.value.clone();
+            self.run_subqueries(&mut value, &table, &sources, None)?;
+            assignments.push((column, value));
+        }
+        check_source(&statement.where_clause, source)?;
+        let mut where_clause = statement.where_clause.clone();
+        self.run_subqueries(&mut where_clause, &table, &sources, None)?;
+
+        let checks = table.compile_checks();
+        let generated = table.compile_generated();
+        let mut updates = vec![];
+        let rows = self.matching_rows(
+            &table,
+            &sources,
+            &where_clause,
+            &statement.order_by,
+            statement.limit,
+            "UPDATE",
+        )?;
+        for row_idx in rows {
+            let old_row = table.rows[row_idx].values();
+            let mut new_row = old_row.clone();
+            for (column, value) in &assignments {
+                let value = match value {
+                    _ if table.is_generated(*column) => SqlValue::Null,
+                    value if is_default(value) => match table
+                        .column_constraints
+                        .get(*column)
+                        .and_then(|c| c.default.as_ref())
+                    {
+                        Some(default) => self.evaluate_constant(default)?,
+                        None => SqlValue::Null,
+                    },
+                    value => self.row_value(&table, &sources, row_idx, value)?.0,
+                };
+                new_row[*column] =
+                    table.assign(*column, &value, updates.len() + 1, self.cast_mode)?;
+            }
+            table.fill_generated(&generated, &mut new_row)?;
+            table.check_not_null(&new_row)?;
+            table.check_row(&checks, &new_row)?;
+            updates.push((row_idx, old_row, new_row));
+        }
+        Ok(updates)
+    }
+
+    // The positions of the rows of an UPDATE or DELETE that WHERE holds for. With ORDER BY
+    // they are sorted by it, rows with equal values keeping the order of the table, and
+    // LIMIT keeps the first of them. The rows gone through are reported as the progress
+    // of `command`.
+    pub(super) fn matching_rows(
+        &self,
+        table: &Table,
+        sources: &[String],
+        where_clause: &Expression,
+        order_by: &[OrderByClause],
+        limit: Option<usize>,
+        command: &'static str,
+    ) -> Result<Vec<usize>, BackendError> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut rows = vec![];
+        let mut progress = ProgressReport::start(command, &table.name, table.rows.len());
+        for row_idx in 0..table.rows.len() {
+            if order_by.is_empty() && rows.len() == limit {
+                break;
+            }
+            progress.row()?;
+            if *where_clause != Expression::Empty {
+                let (holds, _) = self.row_value(table, sources, row_idx, where_clause)?;
+                if holds != SqlValue::Boolean(true) {
+                    continue;
+                }
+            }
+            rows.push(row_idx);
+        }
+
+        if let (false, Some(source)) = (order_by.is_empty(), sources.first()) {
+            let mut clauses = Vec::with_capacity(order_by.len());
+            for clause in order_by {
+                check_source(&clause.exp, source)?;
+                let mut exp = clause.exp.clone();
+                self.run_subqueries(&mut exp, table, sources, None)?;
+                let collation = table.collation_of(&exp);
+                clauses.push((exp, collation, clause.asc));
+            }
+            let mut keyed = Vec::with_capacity(rows.len());
+            for row_idx in rows {
+                let mut key = vec![];
+                for (exp, collation, asc) in &clauses {
+                    let (value, _) = self.row_value(table, sources, row_idx, exp)?;
+                    let value = match collation {
+                        Some(collation) => collation.key(&value).into_owned(),
+                        None => value,
+                    };
+                    push_order_key(&mut key, &value, *asc);
+                }
+                keyed.push((key, row_idx));
+            }
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            rows = keyed.into_iter().map(|(_, row_idx)| row_idx).collect();
+            rows.truncate(limit);
+        }
+        Ok(rows)
+    }
+
+    // The value of an expression for the row at `row_idx` and the type it was worked out
+    // to have
+    pub(super) fn row_value(
+        &self,
+        table: &Table,
+        sources: &[String],
+        row_idx: usize,
+        expression: &Expression,
+    ) -> Result<(SqlValue, SqlType), BackendError> {
+        let (value, _, typ) = if has_subquery(expression) {
+            let mut expression = expression.clone();
+            self.run_subqueries(&mut expression, table, sources, Some(row_idx))?;
+            table.evaluate_cell(row_idx, &expression)?
+        } else {
+            table.evaluate_cell(row_idx, expression)?
+        };
+        Ok((value, typ))
+    }
+
+    // The rows of `table` a condition running subqueries for every row holds for
+    pub(super) fn filter_rows(
+        &self,
+        table: &Table,
+        sources: &[String],
+        condition: &Expression,
+    ) -> Result<Table, BackendError> {
+        let mut rows = vec![];
+        for (row_idx, row) in table.rows.iter().enumerate() {
+            if let (SqlValue::Boolean(true), _) =
+                self.row_value(table, sources, row_idx, condition)?
+            {
+                rows.push(row.clone());
+            }
+        }
+        Ok(Table {
+            name: table.name.clone(),
+            columns: table.columns.clone(),
+            column_types: table.column_types.clone(),
+            rows,
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        })
+    }
+
+    // Runs the subqueries of an expression on the rows of `table`, putting what they give
+    // in their place. Those reading the columns of the row at `row_idx` get its values
+    // for them, without a row they are left as they are.
+    pub(super) fn run_subqueries(
+        &self,
+        expression: &mut Expression,
+        table: &Table,
+        sources: &[String],
+        row_idx: Option<usize>,
+    ) -> Result<(), BackendError> {
+        match expression {
+            Expression::SubSelect(select) | Expression::Exists(select) => {
+                let select = match self.correlate(select, table, sources, row_idx)? {
+                    Some(select) => select,
+                    None => return Ok(()),
+                };
+                *expression = match expression {
+                    Expression::Exists(_) => {
+                        value_expression(&SqlValue::Boolean(!self.select(select)?.rows.is_empty()))?
+                    }
+                    // A NULL keeps the type of the column it was found in
+                    _ => match self.scalar_subquery(&select)? {
+                        (SqlValue::Null, typ) => Expression::Cast {
+                            data: Box::new(value_expression(&SqlValue::Null)?),
+                            typ,
+                        },
+                        (value, _) => value_expression(&value)?,
+                    },
+                };
+            }
+            Expression::Quantified(quantified) => {
+                self.run_subqueries(&mut quantified.first, table, sources, row_idx)?;
+                if let QuantifiedSet::SubSelect(select) = &quantified.set {
+                    if let Some(select) = self.correlate(select, table, sources, row_idx)? {
+                        quantified.set = QuantifiedSet::Values(self.subquery_values(&select)?);
+                    }
+                }
+            }
+            Expression::Binary(binary) => {
+                self.run_subqueries(&mut binary.first, table, sources, row_idx)?;
+                self.run_subqueries(&mut binary.second, table, sources, row_idx)?;
+            }
+            Expression::Unary(unary) => {
+                self.run_subqueries(&mut unary.first, table, sources, row_idx)?
+            }
+            Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+                self.run_subqueries(data, table, sources, row_idx)?
+            }
+            Expression::FunctionCall(call) => {
+                for arg in call.args.iter_mut() {
+                    self.run_subqueries(arg, table, sources, row_idx)?;
+                }
+            }
+            Expression::Literal(_)
+            | Expression::TableColumn(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::Empty => {}
+        }
+        Ok(())
+    }
+
+    // The subquery with the values of the row at `row_idx` in place of the columns of the
+    // row it reads. None if it reads any but there is no row.
+    fn correlate(
+        &self,
+        select: &SelectStatement,
+        table: &Table,
+        sources: &[String],
+        row_idx: Option<usize>,
+    ) -> Result<Option<SelectStatement>, BackendError> {
+        let source = RowDataSource::SubSelect {
+            select: Box::new(select.clone()),
+            as_clause: String::new(),
+            lateral: true,
+            joins: vec![],
+        };
+        let references = self.outer_references(&source, sources, &table.columns);
+        let mut select = match source {
+            RowDataSource::SubSelect { select, .. } => *select,
+            _ => return Err(BackendError::Internal("Expected a subquery".to_string())),
+        };
+        if references.is_empty() {
+            return Ok(Some(select));
+        }
+        let row = match row_idx {
+            Some(row_idx) => &table.rows[row_idx],
+            None => return Ok(None),
+        };
+
+        let values = references
+            .iter()
+            .map(|(_, idx)| value_expression(&row.value(*idx)))
+            .collect::<Result<Vec<_>, _>>()?;
+        for expression in select_expressions_mut(&mut select) {
+            substitute(expression, &|column| {
+                let idx = references
+                    .iter()
+                    .position(|(reference, _)| reference == column)?;
+                Some(values[idx].clone())
+            });
+        }
+        Ok(Some(select))
+    }
+}
+
+// Fails for a column qualified by another name than the one the table goes by
+pub(super) fn check_source(expression: &Expression, source: &str) -> Result<(), BackendError> {
+    let mut columns = vec![];
+    column_references(expression, &mut columns);
+    for column in columns {
+        if let Expression::TableColumn(TableColumn {
+            table_name: Some(table_name),
+            ..
+        }) = column
+        {
+            if table_name != source {
+                return Err(BackendError::UndefinedTable(format!(
+                    "Missing FROM-clause entry for table \"{}\".",
+                    table_name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod update_tests {
+    use crate::backend::{BackendError, EvalResult};
+    use crate::backend_memory::test_support::{backend, rows};
+    use crate::backend_memory::MemoryBackend;
+
+    const SETUP: &str = "CREATE TABLE accounts (id INT PRIMARY KEY, balance INT, owner TEXT);
+        CREATE TABLE payments (account_id INT, amount INT);
+        INSERT INTO accounts VALUES (1, 500, 'ann'), (2, 20, 'bob'), (3, 0, 'cy');
+        INSERT INTO payments VALUES (1, 50), (1, 70), (2, 5);";
+
+    fn updated(mb: &mut MemoryBackend, query: &str) -> usize {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Update { rows_updated, .. }) => rows_updated,
+            _ => panic!("Expected update results for {}", query),
+        }
+    }
+
+    #[test]
+    fn test_update_expressions() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            updated(
+                &mut mb,
+                "UPDATE accounts SET balance = balance - 100 WHERE id = 1;"
+            ),
+            1
+        );
+        assert_eq!(
+            updated(
+                &mut mb,
+                "UPDATE accounts AS a SET owner = upper(a.owner) WHERE a.balance < 100;"
+            ),
+            2
+        );
+        assert_eq!(
+            updated(&mut mb, "UPDATE accounts SET balance = 0 WHERE id > 5;"),
+            0
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM accounts;"),
+            vec!["1|400|ann", "2|20|BOB", "3|0|CY"]
+        );
+    }
+
+    #[test]
+    fn test_update_sees_old_values() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (a INT, b INT, c INT);
+            INSERT INTO t VALUES (1, 2, 0), (3, 4, 0);
+            UPDATE t SET a = b, b = a, c = a + b;",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM t;"), vec!["2|1|3", "4|3|7"]);
+
+        mb.eval_query("UPDATE t SET (a, b) = (b * 10, a), c = DEFAULT WHERE a = 2;")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM t;"),
+            vec!["10|2|NULL", "4|3|7"]
+        );
+    }
+
+    #[test]
+    fn test_update_subqueries() {
+        let mut mb = backend(SETUP);
+        // Correlated to the row being updated, or not at all
+        mb.eval_query(
+            "UPDATE accounts SET balance = (
+                SELECT max(amount) FROM payments WHERE payments.account_id = accounts.id
+            );",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM accounts;"),
+            vec!["1|70|ann", "2|5|bob", "3|NULL|cy"]
+        );
+        mb.eval_query(
+            "UPDATE accounts a SET balance = (SELECT sum(amount) FROM payments)
+            WHERE EXISTS (SELECT 1 FROM payments p WHERE p.account_id = a.id AND amount > 5)
+                OR a.id IN (SELECT 3);",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM accounts;"),
+            vec!["1|125|ann", "2|5|bob", "3|125|cy"]
+        );
+        // Subqueries see the table as it was before the statement
+        mb.eval_query("UPDATE accounts SET balance = (SELECT max(balance) FROM accounts) + id;")
+            .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT balance FROM accounts;"),
+            vec!["126", "127", "128"]
+        );
+        // Reading the table being updated under an alias of its own
+        mb.eval_query(
+            "UPDATE accounts SET balance = (
+                SELECT max(a.balance) FROM accounts a WHERE a.id <= accounts.id
+            ) - id;",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(&mut mb, "SELECT balance FROM accounts;"),
+            vec!["125", "125", "125"]
+        );
+    }
+
+    #[test]
+    fn test_update_order_limit() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "UPDATE accounts SET balance = balance + 1 WHERE balance < 100
+                ORDER BY owner DESC LIMIT 1 RETURNING id, balance;"
+            ),
+            vec!["3|1"]
+        );
+        assert_eq!(
+            updated(&mut mb, "UPDATE accounts SET owner = 'x' LIMIT 2;"),
+            2
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM accounts;"),
+            vec!["1|500|x", "2|20|x", "3|1|cy"]
+        );
+        // Rows of the same owner go by their balance
+        assert_eq!(
+            rows(
+                &mut mb,
+                "UPDATE accounts SET balance = balance * 2 ORDER BY owner DESC, balance LIMIT 2
+                RETURNING id;"
+            ),
+            vec!["2", "1"]
+        );
+
+        // Rows change in the order given, each key moving to one already let go of
+        assert_eq!(
+            mb.eval_query("UPDATE accounts SET id = id + 1;")
+                .unwrap_err()
+                .code(),
+            "23505"
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "UPDATE accounts SET id = id + 1 ORDER BY id DESC RETURNING id;"
+            ),
+            vec!["4", "3", "2"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT id FROM accounts WHERE id = 4;"),
+            vec!["4"]
+        );
+    }
+
+    #[test]
+    fn test_update_errors() {
+        let mut mb = backend(SETUP);
+        let tests = vec![
+            ("UPDATE nope SET a = 1;", "42P01"),
+            ("UPDATE accounts SET nope = 1;", "42703"),
+            ("UPDATE accounts SET id = 1, id = 2;", "42601"),
+            ("UPDATE accounts SET (id, owner) = (1);", "42601"),
+            (
+                "UPDATE accounts a SET id = 1 WHERE accounts.id = 1;",
+                "42P01",
+            ),
+            ("UPDATE accounts SET balance = 'x';", "42804"),
+            ("UPDATE accounts SET id = 1;", "23505"),
+            (
+                "UPDATE accounts SET balance = (SELECT amount FROM payments);",
+                "21000",
+            ),
+        ];
+        for (query, code) in tests {
+            match mb.eval_query(query) {
+                Err(err) => assert_eq!(err.code(), code, "{}: {:?}", query, err),
+                result => panic!("{}: expected an error, got {:?}", query, result),
+            }
+        }
+        // Nothing changed, even for the rows updated before one failed
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM accounts;"),
+            vec!["1|500|ann", "2|20|bob", "3|0|cy"]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT owner FROM accounts WHERE id = 1;"),
+            vec!["ann"]
+        );
+
+        mb.eval_query(
+            "CREATE TABLE g (a INT CHECK (a > 0), b INT GENERATED ALWAYS AS (a * 2) STORED);
+            INSERT INTO g VALUES (1, DEFAULT);
+            UPDATE g SET a = 5;",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT * FROM g;"), vec!["5|10"]);
+        match mb.eval_query("UPDATE g SET b = 3;") {
+            Err(BackendError::GeneratedAlways(msg)) => {
+                assert_eq!(msg, "Column \"b\" can only be updated to DEFAULT.")
+            }
+            result => panic!("Expected an error, got {:?}", result),
+        }
+        assert_eq!(
+            mb.eval_query("UPDATE g SET a = -1;").unwrap_err().code(),
+            "23514"
+        );
+    }
+}
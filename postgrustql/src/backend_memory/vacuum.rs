@@ -0,0 +1,200 @@
+use super::progress::ProgressReport;
+use super::stored_row::StoredRow;
+use super::{MemoryBackend, StorageEngine, Table};
+use crate::backend::{BackendError, MemoryCellData};
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+// What VACUUM freed. Tables in memory free the room left behind by the rows taken out of
+// them, and that of their index entries.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct VacuumStats {
+    // Rows taken out of the tables since they were last vacuumed
+    pub rows_reclaimed: usize,
+    pub bytes_reclaimed: usize,
+}
+
+impl VacuumStats {
+    fn add(&mut self, other: VacuumStats) {
+        self.rows_reclaimed += other.rows_reclaimed;
+        self.bytes_reclaimed += other.bytes_reclaimed;
+    }
+}
+
+impl MemoryBackend {
+    // Compacts the table named, or every table, temporary ones included. Rows keep their
+    // positions, so cursors and the index entries stay valid. Like in Postgres it can't
+    // run within a transaction, whose snapshots hold on to the tables as they were. Its
+    // progress goes by whole tables, it can be canceled between two of them.
+    pub fn vacuum(&mut self, table_name: Option<&str>) -> Result<VacuumStats, BackendError> {
+        if self.pending_writes.is_some() {
+            return Err(BackendError::ActiveSqlTransaction(
+                "VACUUM cannot run inside a transaction block.".to_string(),
+            ));
+        }
+        let mut stats = VacuumStats::default();
+        match table_name {
+            Some(name) => {
+                if self.table(name).is_none() {
+                    return Err(BackendError::UndefinedTable(format!(
+                        "Table \"{}\" doesn't exist.",
+                        name
+                    )));
+                }
+                let _progress = self.vacuum_progress(name)?;
+                stats.add(self.engine_mut(name).vacuum(name)?);
+            }
+            None => {
+                for name in self.temp_tables.table_names() {
+                    let _progress = self.vacuum_progress(&name)?;
+                    stats.add(self.temp_tables.vacuum(&name)?);
+                }
+                for name in self.storage.table_names() {
+                    let _progress = self.vacuum_progress(&name)?;
+                    stats.add(self.storage.vacuum(&name)?);
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    // Reports the table as the one being vacuumed, unless VACUUM was canceled
+    fn vacuum_progress(&self, name: &str) -> Result<ProgressReport, BackendError> {
+        let rows = self.table(name).map_or(0, |table| table.rows.len());
+        let mut progress = ProgressReport::start("VACUUM", name, rows);
+        progress.rows(0)?;
+        Ok(progress)
+    }
+}
+
+impl Table {
+    // Gives back the room the rows and index entries no longer use. The indexes are built
+    // anew from their entries, which are in key order already.
+    pub(super) fn compact(&mut self) -> VacuumStats {
+        let before = self.allocated_bytes();
+        let rows_reclaimed = std::mem::take(&mut self.dead_rows);
+        self.rows.shrink_to_fit();
+        for index in self.indexes.iter_mut() {
+            let tree = std::mem::take(&mut index.tree);
+            index.set_entries(
+                tree.into_iter()
+                    .map(|(mut key, mut rows)| {
+                        key.shrink_to_fit();
+                        rows.shrink_to_fit();
+                        (key, rows)
+                    })
+                    .collect::<BTreeMap<_, _>>(),
+            );
+        }
+        VacuumStats {
+            rows_reclaimed,
+            bytes_reclaimed: before.saturating_sub(self.allocated_bytes()),
+        }
+    }
+
+    // An estimate of the memory the rows and indexes take, counting the room allocated
+    // for them rather than what they use of it
+    pub(super) fn allocated_bytes(&self) -> usize {
+        let mut bytes = self.rows.capacity() * size_of::<StoredRow>();
+        bytes += self.rows.iter().map(StoredRow::heap_bytes).sum::<usize>();
+        for index in &self.indexes {
+            for (key, rows) in &index.tree {
+                bytes += size_of::<(MemoryCellData, Vec<usize>)>();
+                bytes += key.capacity() + rows.capacity() * size_of::<usize>();
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod vacuum_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+
+    fn count(mb: &mut MemoryBackend, query: &str) -> String {
+        match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results.rows[0][0].to_string(),
+            _ => panic!("Expected select results for {}", query),
+        }
+    }
+
+    #[test]
+    fn test_vacuum_after_deleting_most_rows() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT);
+            INSERT INTO t SELECT n, 'name ' || n FROM generate_series(1, 5000) AS g(n);
+            CREATE INDEX t_name_idx ON t (name);
+            DELETE FROM t WHERE id % 10 <> 0;",
+        )
+        .unwrap();
+        let before = mb.table("t").unwrap().allocated_bytes();
+
+        let (rows_reclaimed, bytes_reclaimed) = match mb.eval_query("VACUUM t;").unwrap().pop() {
+            Some(EvalResult::Vacuum {
+                rows_reclaimed,
+                bytes_reclaimed,
+                ..
+            }) => (rows_reclaimed, bytes_reclaimed),
+            _ => panic!("Expected the table to be vacuumed"),
+        };
+        let after = mb.table("t").unwrap().allocated_bytes();
+        assert_eq!(rows_reclaimed, 4500);
+        assert_eq!(bytes_reclaimed, before - after);
+        assert!(
+            after * 2 < before,
+            "{} bytes before, {} after",
+            before,
+            after
+        );
+
+        // The rows and indexes read the same as before
+        assert_eq!(count(&mut mb, "SELECT COUNT(*) FROM t;"), "500");
+        assert_eq!(
+            count(&mut mb, "SELECT name FROM t WHERE id = 4990;"),
+            "name 4990"
+        );
+        assert_eq!(
+            count(&mut mb, "SELECT id FROM t WHERE name = 'name 20';"),
+            "20"
+        );
+
+        // Nothing is left to reclaim the second time
+        assert_eq!(mb.vacuum(None).unwrap(), VacuumStats::default());
+    }
+
+    #[test]
+    fn test_vacuum_counts_rows_taken_out() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE t (id INT);
+            INSERT INTO t SELECT n FROM generate_series(1, 1000) AS g(n);",
+        )
+        .unwrap();
+        // The room the rows were given to grow into holds no row that was taken out
+        assert_eq!(mb.vacuum(Some("t")).unwrap().rows_reclaimed, 0);
+
+        mb.eval_query(
+            "DELETE FROM t WHERE id <= 300;
+            UPDATE t SET id = id + 1000 WHERE id > 900;",
+        )
+        .unwrap();
+        assert_eq!(mb.vacuum(Some("t")).unwrap().rows_reclaimed, 300);
+        assert_eq!(count(&mut mb, "SELECT COUNT(*) FROM t;"), "700");
+    }
+
+    #[test]
+    fn test_vacuum_errors() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("CREATE TABLE t (id INT);").unwrap();
+        let err = mb.eval_query("VACUUM nope;").unwrap_err();
+        assert_eq!(err.code(), "42P01");
+
+        let err = mb.transaction(|mb| mb.eval_query("VACUUM t;")).unwrap_err();
+        assert_eq!(
+            (err.code(), err.message()),
+            ("25001", "VACUUM cannot run inside a transaction block.")
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use crate::sql_types::{SqlNumeric, SqlText, SqlValue};
+
+// Values the backend makes up itself, like counts and the rows of the catalog
+
+pub(super) fn int(value: i32) -> SqlValue {
+    SqlValue::Numeric(SqlNumeric::Int { value })
+}
+
+pub(super) fn big_int(value: usize) -> SqlValue {
+    SqlValue::Numeric(SqlNumeric::BigInt {
+        value: value as i64,
+    })
+}
+
+pub(super) fn small_int(value: usize) -> SqlValue {
+    SqlValue::Numeric(SqlNumeric::SmallInt {
+        value: value as i16,
+    })
+}
+
+pub(super) fn text(value: &str) -> SqlValue {
+    SqlValue::Text(SqlText::Text {
+        value: value.to_string(),
+    })
+}
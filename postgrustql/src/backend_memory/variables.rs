@@ -0,0 +1,597 @@
+use super::config::{invalid_value, located, unknown_option};
+use super::warnings::{warn, WARNING};
+use super::{BackendConfig, MemoryBackend};
+use crate::ast::SetStatement;
+use crate::backend::{BackendError, Warning};
+use crate::lexer::{LexOptions, Token, TokenLocation};
+use std::collections::HashMap;
+
+// A setting drivers and tools read or set on connecting to a Postgres server. Apart from
+// standard_conforming_strings, search_path and lock_timeout the backend doesn't act on
+// them, so those it can't follow are limited to their default.
+struct Variable {
+    // As Postgres spells it, SHOW names its column after this
+    name: &'static str,
+    default: &'static str,
+    accepts: Accepts,
+}
+
+#[derive(PartialEq)]
+enum Accepts {
+    Anything,
+    // Ways of spelling the default, which is shown whichever of them was used
+    DefaultOnly(&'static [&'static str]),
+    Integer { min: i64, max: i64 },
+    // Shown as on or off
+    Boolean,
+    ReadOnly,
+}
+
+// The version of Postgres the backend answers as
+pub(super) const SERVER_VERSION: &str = "13.0";
+
+const VARIABLES: &[Variable] = &[
+    Variable {
+        name: "application_name",
+        default: "",
+        accepts: Accepts::Anything,
+    },
+    Variable {
+        name: "client_encoding",
+        default: "UTF8",
+        accepts: Accepts::DefaultOnly(&["utf8", "utf-8", "unicode"]),
+    },
+    Variable {
+        name: "DateStyle",
+        default: "ISO, MDY",
+        accepts: Accepts::Anything,
+    },
+    Variable {
+        name: "extra_float_digits",
+        default: "1",
+        accepts: Accepts::Integer { min: -15, max: 3 },
+    },
+    Variable {
+        name: "integer_datetimes",
+        default: "on",
+        accepts: Accepts::ReadOnly,
+    },
+    Variable {
+        name: "IntervalStyle",
+        default: "postgres",
+        accepts: Accepts::DefaultOnly(&["postgres"]),
+    },
+    // In milliseconds, how long a connection waits for the transaction of another to end
+    // before giving up, 0 for not at all, unlike Postgres which then waits as long as it takes
+    Variable {
+        name: "lock_timeout",
+        default: "0",
+        accepts: Accepts::Integer {
+            min: 0,
+            max: i32::MAX as i64,
+        },
+    },
+    Variable {
+        name: "search_path",
+        default: "\"$user\", public",
+        accepts: Accepts::Anything,
+    },
+    Variable {
+        name: "server_encoding",
+        default: "UTF8",
+        accepts: Accepts::ReadOnly,
+    },
+    Variable {
+        name: "server_version",
+        default: SERVER_VERSION,
+        accepts: Accepts::ReadOnly,
+    },
+    Variable {
+        name: "server_version_num",
+        default: "130000",
+        accepts: Accepts::ReadOnly,
+    },
+    Variable {
+        name: "standard_conforming_strings",
+        default: "on",
+        accepts: Accepts::Boolean,
+    },
+    Variable {
+        name: "TimeZone",
+        default: "UTC",
+        accepts: Accepts::Anything,
+    },
+    // The default of Postgres, the only level there is
+    Variable {
+        name: "transaction_isolation",
+        default: "read committed",
+        accepts: Accepts::DefaultOnly(&["read committed"]),
+    },
+];
+
+// Names are matched whatever their case, as in Postgres
+fn variable(name: &str) -> Option<&'static Variable> {
+    VARIABLES
+        .iter()
+        .find(|variable| variable.name.eq_ignore_ascii_case(name))
+}
+
+// Postgres takes any name with a dot in it as that of a variable set by an extension or
+// an application
+fn is_custom(name: &str) -> bool {
+    name.contains('.')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    name: String,
+    // None for a variable that wasn't set, or is back to its default
+    previous: Option<String>,
+    value: Option<String>,
+    local: bool,
+}
+
+// The variables of a session along with what SET and RESET changed in the running
+// transaction. Settings in `BackendConfig` are kept by the backend, but their changes
+// are tracked here as well so that a transaction undoes every kind of setting alike.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SessionVariables {
+    // Those set by the session, by lowercase name
+    values: HashMap<String, String>,
+    // What RESET brings the settings in `BackendConfig` back to
+    reset_config: BackendConfig,
+    changes: Vec<Change>,
+    // Transactions running, LOCAL settings last until the outermost one ends
+    depth: usize,
+}
+
+impl SessionVariables {
+    pub(super) fn new(reset_config: BackendConfig) -> Self {
+        SessionVariables {
+            values: HashMap::new(),
+            reset_config,
+            changes: vec![],
+            depth: 0,
+        }
+    }
+
+    pub(super) fn reset_config(&self) -> &BackendConfig {
+        &self.reset_config
+    }
+
+    pub(super) fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+
+    // Current value of a variable, None if it is neither built in nor set
+    pub(super) fn get(&self, name: &str) -> Option<String> {
+        self.value(name).map(str::to_string)
+    }
+
+    pub(super) fn value(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(value) => Some(value),
+            None => variable(name).map(|variable| variable.default),
+        }
+    }
+
+    // Name of the column SHOW returns a variable in
+    pub(super) fn display_name(name: &str) -> Option<&'static str> {
+        variable(name).map(|variable| variable.name)
+    }
+
+    // Variables the session set, for RESET ALL
+    pub(super) fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub(super) fn restore(&mut self, name: &str, value: Option<String>) {
+        match value {
+            Some(value) => self.values.insert(name.to_string(), value),
+            None => self.values.remove(name),
+        };
+    }
+
+    // Outside of transactions there is nothing to undo, so nothing is kept
+    pub(super) fn changed(
+        &mut self,
+        name: &str,
+        previous: Option<String>,
+        value: Option<String>,
+        local: bool,
+    ) {
+        if self.depth > 0 {
+            self.changes.push(Change {
+                name: name.to_string(),
+                previous,
+                value,
+                local,
+            });
+        }
+    }
+
+    pub(super) fn begin(&mut self) -> usize {
+        self.depth += 1;
+        self.changes.len()
+    }
+
+    // Settings to restore and what to, as the transaction leaves them. A failed one
+    // undoes every change it made. Once the outermost one succeeds, settings SET LOCAL
+    // go back to the last value SET without it, or to the one before the transaction.
+    pub(super) fn end(&mut self, start: usize, succeeded: bool) -> Vec<(String, Option<String>)> {
+        self.depth -= 1;
+        if !succeeded {
+            let undone = self.changes.split_off(start);
+            return undone
+                .into_iter()
+                .rev()
+                .map(|change| (change.name, change.previous))
+                .collect();
+        }
+        if self.depth > 0 {
+            return vec![];
+        }
+
+        let changes = std::mem::take(&mut self.changes);
+        let mut restored: Vec<(String, Option<String>)> = vec![];
+        for change in changes.iter().filter(|change| change.local) {
+            if restored.iter().any(|(name, _)| *name == change.name) {
+                continue;
+            }
+            let same: Vec<&Change> = changes
+                .iter()
+                .filter(|other| other.name == change.name)
+                .collect();
+            let value = match same.iter().rev().find(|other| !other.local) {
+                Some(session) => session.value.clone(),
+                None => same[0].previous.clone(),
+            };
+            restored.push((change.name.clone(), value));
+        }
+        restored
+    }
+}
+
+impl MemoryBackend {
+    pub(super) fn set_variable(
+        &mut self,
+        name: &str,
+        statement: &SetStatement,
+    ) -> Result<(), BackendError> {
+        let value = match &statement.value {
+            Token::IdentifierValue { value }
+            | Token::StringValue { value }
+            | Token::NumericValue { value } => value.to_string(),
+            Token::BoolValue { value } => value.to_string(),
+            token => token.generate_code(),
+        };
+        let value = match variable(name) {
+            Some(variable) => match &variable.accepts {
+                Accepts::Anything => value,
+                Accepts::DefaultOnly(spellings) => {
+                    let value = value.to_ascii_lowercase();
+                    if !spellings.contains(&value.as_str()) {
+                        return Err(invalid_value(statement));
+                    }
+                    variable.default.to_string()
+                }
+                Accepts::Integer { min, max } => match value.parse::<i64>() {
+                    Ok(number) if number >= *min && number <= *max => number.to_string(),
+                    _ => return Err(invalid_value(statement)),
+                },
+                Accepts::Boolean => match value.to_ascii_lowercase().as_str() {
+                    "on" | "true" | "yes" | "1" => "on".to_string(),
+                    "off" | "false" | "no" | "0" => "off".to_string(),
+                    _ => return Err(invalid_value(statement)),
+                },
+                Accepts::ReadOnly => return Err(read_only(name, statement.name_loc)),
+            },
+            None if is_custom(name) => value,
+            None if self.allow_unknown_settings => {
+                warn(
+                    Warning::new(
+                        WARNING,
+                        format!(
+                            "Unrecognized configuration parameter \"{}\" was set anyway.",
+                            statement.name
+                        ),
+                    )
+                    .at(statement.name_loc),
+                );
+                value
+            }
+            None => return Err(unknown_option(&statement.name, statement.name_loc)),
+        };
+        self.variables.values.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub(super) fn reset_variable(
+        &mut self,
+        name: &str,
+        loc: TokenLocation,
+    ) -> Result<(), BackendError> {
+        match variable(name) {
+            Some(variable) if variable.accepts == Accepts::ReadOnly => {
+                return Err(read_only(name, loc))
+            }
+            Some(_) => {}
+            None if self.variables.values.contains_key(name)
+                || is_custom(name)
+                || self.allow_unknown_settings => {}
+            None => return Err(unknown_option(name, loc)),
+        }
+        self.variables.values.remove(name);
+        Ok(())
+    }
+
+    // How queries of the session lex
+    pub(super) fn lex_options(&self) -> LexOptions {
+        LexOptions {
+            standard_conforming_strings: self
+                .variables
+                .get("standard_conforming_strings")
+                .as_deref()
+                != Some("off"),
+        }
+    }
+}
+
+fn read_only(name: &str, loc: TokenLocation) -> BackendError {
+    BackendError::CantChangeRuntimeParam(located(
+        loc,
+        format!("Parameter \"{}\" cannot be changed.", name),
+    ))
+}
+
+#[cfg(test)]
+mod variables_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::Connection;
+    use std::sync::{Arc, Mutex};
+
+    fn show(mb: &mut MemoryBackend, name: &str) -> (String, String) {
+        match mb.eval_query(&format!("SHOW {};", name)).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => (
+                results.columns[0].name.clone(),
+                results.rows[0][0].to_string(),
+            ),
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    fn value(mb: &mut MemoryBackend, name: &str) -> String {
+        show(mb, name).1
+    }
+
+    #[test]
+    fn test_builtin_variables() {
+        let mut mb = MemoryBackend::new();
+        let tests = vec![
+            ("server_version", "server_version", "13.0"),
+            ("client_encoding", "client_encoding", "UTF8"),
+            (
+                "standard_conforming_strings",
+                "standard_conforming_strings",
+                "on",
+            ),
+            ("timezone", "TimeZone", "UTC"),
+            ("\"TimeZone\"", "TimeZone", "UTC"),
+            ("DateStyle", "DateStyle", "ISO, MDY"),
+            ("search_path", "search_path", "\"$user\", public"),
+            (
+                "transaction_isolation",
+                "transaction_isolation",
+                "read committed",
+            ),
+        ];
+        for (name, column, expected) in tests {
+            assert_eq!(
+                show(&mut mb, name),
+                (column.to_string(), expected.to_string()),
+                "{}",
+                name
+            );
+        }
+
+        mb.eval_query(
+            "SET client_encoding = 'utf-8';
+            SET application_name TO 'psql';
+            SET TimeZone = 'Europe/Athens';
+            SET extra_float_digits = 3;
+            SET search_path TO myschema;",
+        )
+        .unwrap();
+        assert_eq!(value(&mut mb, "client_encoding"), "UTF8");
+        assert_eq!(value(&mut mb, "application_name"), "psql");
+        assert_eq!(value(&mut mb, "timezone"), "Europe/Athens");
+        assert_eq!(value(&mut mb, "extra_float_digits"), "3");
+        assert_eq!(value(&mut mb, "search_path"), "myschema");
+
+        mb.eval_query("RESET timezone; SET search_path = DEFAULT;")
+            .unwrap();
+        assert_eq!(value(&mut mb, "timezone"), "UTC");
+        assert_eq!(value(&mut mb, "search_path"), "\"$user\", public");
+
+        // Lists are set as one text, whether written as one or not
+        mb.eval_query("SET search_path = pg_catalog, public;")
+            .unwrap();
+        assert_eq!(value(&mut mb, "search_path"), "pg_catalog, public");
+        mb.eval_query("SET search_path TO 'pg_catalog, public';")
+            .unwrap();
+        assert_eq!(value(&mut mb, "search_path"), "pg_catalog, public");
+    }
+
+    #[test]
+    fn test_unknown_variables() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("SET myapp.user_id = 42;").unwrap();
+        assert_eq!(value(&mut mb, "myapp.user_id"), "42");
+        mb.eval_query("RESET myapp.user_id;").unwrap();
+        assert!(mb.eval_query("SHOW myapp.user_id;").is_err());
+
+        assert!(mb.eval_query("SET statement_timeout = 0;").is_err());
+        mb.eval_query("SET allow_unknown_settings = on;").unwrap();
+        mb.eval_query("SET statement_timeout = 0;").unwrap();
+        assert_eq!(value(&mut mb, "statement_timeout"), "0");
+        mb.eval_query("RESET ALL;").unwrap();
+        assert_eq!(value(&mut mb, "allow_unknown_settings"), "off");
+        assert!(mb.eval_query("SHOW statement_timeout;").is_err());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut mb = MemoryBackend::with_config(BackendConfig::new().max_result_rows(Some(5)));
+        mb.eval_query(
+            "SET max_result_rows = 10;
+            SET collect_timing = on;
+            SET application_name = 'app';",
+        )
+        .unwrap();
+        mb.eval_query("RESET max_result_rows;").unwrap();
+        assert_eq!(value(&mut mb, "max_result_rows"), "5");
+        assert_eq!(value(&mut mb, "collect_timing"), "on");
+
+        mb.eval_query("RESET ALL;").unwrap();
+        assert_eq!(mb.config(), BackendConfig::new().max_result_rows(Some(5)));
+        assert_eq!(value(&mut mb, "application_name"), "");
+    }
+
+    #[test]
+    fn test_set_local() {
+        let mut mb = MemoryBackend::new();
+        // Outside of a transaction it does nothing
+        mb.eval_query("SET LOCAL timezone = 'Asia/Tokyo';").unwrap();
+        assert_eq!(value(&mut mb, "timezone"), "UTC");
+
+        mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query(
+                "SET LOCAL timezone = 'Asia/Tokyo';
+                SET LOCAL max_result_rows = 3;
+                SET application_name = 'kept';",
+            )?;
+            assert_eq!(value(mb, "timezone"), "Asia/Tokyo");
+            assert_eq!(value(mb, "max_result_rows"), "3");
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(value(&mut mb, "timezone"), "UTC");
+        assert_eq!(value(&mut mb, "max_result_rows"), "0");
+        assert_eq!(value(&mut mb, "application_name"), "kept");
+
+        // A later SET without LOCAL outlasts the transaction, an earlier one is what
+        // SET LOCAL goes back to
+        mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query(
+                "SET LOCAL timezone = 'Asia/Tokyo';
+                SET timezone = 'Europe/Paris';
+                SET application_name = 'session';
+                SET LOCAL application_name = 'local';",
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(value(&mut mb, "timezone"), "Europe/Paris");
+        assert_eq!(value(&mut mb, "application_name"), "session");
+    }
+
+    #[test]
+    fn test_failed_transaction_undoes_set() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query("SET application_name = 'before';").unwrap();
+        let result = mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query(
+                "SET application_name = 'during';
+                SET reorder_joins = off;
+                SET myapp.flag = 'yes';",
+            )?;
+            mb.transaction(|mb| mb.eval_query("SET LOCAL timezone = 'Asia/Tokyo';"))?;
+            mb.eval_query("SET nope = 1;")?;
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(value(&mut mb, "application_name"), "before");
+        assert_eq!(value(&mut mb, "reorder_joins"), "on");
+        assert_eq!(value(&mut mb, "timezone"), "UTC");
+        assert!(mb.eval_query("SHOW myapp.flag;").is_err());
+
+        // Only the nested transaction that fails is undone
+        mb.transaction(|mb| -> Result<(), BackendError> {
+            mb.eval_query("SET application_name = 'outer';")?;
+            let _ = mb.transaction(|mb| -> Result<(), BackendError> {
+                mb.eval_query("SET application_name = 'inner';")?;
+                Err(BackendError::Internal("rolled back".to_string()))
+            });
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(value(&mut mb, "application_name"), "outer");
+    }
+
+    #[test]
+    fn test_variables_are_per_connection() {
+        let backend = Arc::new(Mutex::new(MemoryBackend::new()));
+        let mut first = Connection::open(backend.clone());
+        let mut second = Connection::open(backend.clone());
+
+        first.eval_query("SET timezone = 'Asia/Tokyo';").unwrap();
+        let timezone = |connection: &mut Connection| {
+            connection
+                .with_session(|backend| Ok(value(backend, "timezone")))
+                .unwrap()
+        };
+        assert_eq!(timezone(&mut first), "Asia/Tokyo");
+        assert_eq!(timezone(&mut second), "UTC");
+        assert_eq!(value(&mut backend.lock().unwrap(), "timezone"), "UTC");
+    }
+
+    #[test]
+    fn test_variable_errors() {
+        let tests = vec![
+            (
+                "SET server_version = '14.0';",
+                "55P02",
+                "[1, 5]: Parameter \"server_version\" cannot be changed.",
+            ),
+            (
+                "RESET server_version_num;",
+                "55P02",
+                "[1, 7]: Parameter \"server_version_num\" cannot be changed.",
+            ),
+            (
+                "SET client_encoding = 'LATIN1';",
+                "22023",
+                "[1, 23]: Invalid value for parameter \"client_encoding\": 'LATIN1'.",
+            ),
+            (
+                "SET standard_conforming_strings = maybe;",
+                "22023",
+                "[1, 35]: Invalid value for parameter \"standard_conforming_strings\": maybe.",
+            ),
+            (
+                "SET extra_float_digits = 4;",
+                "22023",
+                "[1, 26]: Invalid value for parameter \"extra_float_digits\": 4.",
+            ),
+            (
+                "SET transaction_isolation = 'serializable';",
+                "22023",
+                "[1, 29]: Invalid value for parameter \"transaction_isolation\": 'serializable'.",
+            ),
+            (
+                "RESET nope;",
+                "42704",
+                "[1, 7]: Unrecognized configuration parameter \"nope\".",
+            ),
+        ];
+
+        let mut mb = MemoryBackend::new();
+        for (query, code, msg) in tests {
+            let err = mb.eval_query(query).unwrap_err();
+            assert_eq!((err.code(), err.message()), (code, msg), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use super::cache::select_tables;
+use super::catalog::is_catalog_table;
+use super::resolver::{local_name, qualified_name, Resolution};
+use super::MemoryBackend;
+use crate::ast::*;
+use crate::backend::BackendError;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// A query kept under a name, read in place of the name wherever a table can be. It is
+// expanded into the statements reading it before they run, so it reads the tables as they
+// are then.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct View {
+    query: SelectStatement,
+    // The query as it was written, which information_schema.views shows
+    pub(super) definition: String,
+    // The tables and views the query reads, which can't be dropped while it does
+    depends_on: Vec<String>,
+}
+
+// The views of the backend, by name. Creating and dropping them is undone along with a
+// failed transaction.
+#[derive(Debug, Default)]
+pub(super) struct Views {
+    views: Arc<HashMap<String, View>>,
+    // The views when each running transaction began, the innermost last
+    snapshots: Vec<Arc<HashMap<String, View>>>,
+}
+
+impl Views {
+    pub(super) fn begin(&mut self) {
+        self.snapshots.push(self.views.clone());
+    }
+
+    pub(super) fn end(&mut self, succeeded: bool) {
+        if let Some(snapshot) = self.snapshots.pop() {
+            if !succeeded {
+                self.views = snapshot;
+            }
+        }
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.views.contains_key(name)
+    }
+
+    // The views with their names, by name
+    pub(super) fn sorted(&self) -> Vec<(&str, &View)> {
+        let mut views: Vec<(&str, &View)> = self
+            .views
+            .iter()
+            .map(|(name, view)| (name.as_str(), view))
+            .collect();
+        views.sort_unstable_by_key(|(name, _)| *name);
+        views
+    }
+
+    // The views reading the table or view `name`, by name
+    fn dependents(&self, name: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .views
+            .iter()
+            .filter(|(_, view)| view.depends_on.iter().any(|table| table == name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+// What the names a statement reads can refer to besides the tables and views of the
+// schemas
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    // The WITH queries run before the part of the statement being expanded, read in place
+    // of tables and views of the same name
+    with_queries: Vec<String>,
+    // Inside the query of a view, which only reads the tables and views of the schemas
+    in_view: bool,
+    // Inside the query of a view, the WITH queries of the statement reading it, which the
+    // tables the view reads must not be taken for
+    hidden: Vec<String>,
+}
+
+impl Scope {
+    // The scope of the query of a view read in this one
+    fn view(&self) -> Scope {
+        Scope {
+            with_queries: vec![],
+            in_view: true,
+            hidden: [&self.hidden[..], &self.with_queries[..]].concat(),
+        }
+    }
+}
+
+impl MemoryBackend {
+    // Checks the query reads only tables and views there are and gives columns of distinct
+    // names. A temporary table would be gone by the next session, so it can't be read.
+    pub fn create_view(&mut self, statement: CreateViewStatement) -> Result<bool, BackendError> {
+        if self.views.contains(&statement.name)
+            || self.storage.table(&statement.name).is_some()
+            || self.sequences.contains(&statement.name)
+            || is_catalog_table(&statement.name)
+        {
+            return Err(BackendError::DuplicateTable(format!(
+                "Relation \"{}\" already exists.",
+                statement.name
+            )));
+        }
+
+        let mut read = vec![];
+        select_tables(&statement.query, &mut read);
+        let mut depends_on: Vec<String> = vec![];
+        for table in read {
+            if self.is_temporary(&table) {
+                return Err(BackendError::FeatureNotSupported(format!(
+                    "View \"{}\" can't read temporary table \"{}\".",
+                    statement.name, table
+                )));
+            }
+            match self.resolve_permanent(&table) {
+                Some(Resolution::Table) | Some(Resolution::View) => {
+                    if !depends_on.contains(&table) {
+                        depends_on.push(table);
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    return Err(BackendError::UndefinedTable(format!(
+                        "Table \"{}\" doesn't exist.",
+                        table
+                    )))
+                }
+            }
+        }
+
+        // Reading no rows is enough to find the columns
+        let mut query = statement.query.clone();
+        self.expand_select(&mut query, &Scope::default().view())?;
+        query.limit = Some(0);
+        let columns = self.select(query)?.columns;
+        for (idx, column) in columns.iter().enumerate() {
+            if columns[..idx].iter().any(|other| other.name == column.name) {
+                return Err(BackendError::DuplicateColumn(format!(
+                    "Column \"{}\" specified more than once in view \"{}\".",
+                    column.name, statement.name
+                )));
+            }
+        }
+
+        let view = View {
+            query: statement.query,
+            definition: statement.definition,
+            depends_on,
+        };
+        Arc::make_mut(&mut self.views.views).insert(statement.name, view);
+        self.catalog_changed();
+        Ok(true)
+    }
+
+    pub fn drop_view(&mut self, statement: DropViewStatement) -> Result<bool, BackendError> {
+        if !self.views.contains(&statement.name) {
+            if self.storage.table(&statement.name).is_some() {
+                return Err(BackendError::WrongObjectType(format!(
+                    "\"{}\" is not a view, use DROP TABLE to drop it.",
+                    statement.name
+                )));
+            }
+            if statement.if_exists {
+                return Ok(false);
+            }
+            return Err(BackendError::UndefinedTable(format!(
+                "View \"{}\" doesn't exist.",
+                statement.name
+            )));
+        }
+        self.check_dependent_views("view", &statement.name, statement.cascade)?;
+        self.drop_dependent_views(&statement.name);
+        Arc::make_mut(&mut self.views.views).remove(&statement.name);
+        self.catalog_changed();
+        Ok(true)
+    }
+
+    // Fails when views read the table or view `name` that is dropped, unless they are to
+    // be dropped along with it
+    pub(super) fn check_dependent_views(
+        &self,
+        kind: &str,
+        name: &str,
+        cascade: bool,
+    ) -> Result<(), BackendError> {
+        match self.views.dependents(name).first() {
+            Some(view) if !cascade => Err(BackendError::DependentObjectsStillExist(format!(
+                "Cannot drop {} \"{}\" because view \"{}\" depends on it.",
+                kind, name, view
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // Drops the views reading the table or view `name`, and those reading them
+    pub(super) fn drop_dependent_views(&mut self, name: &str) {
+        for view in self.views.dependents(name) {
+            self.drop_dependent_views(&view);
+            Arc::make_mut(&mut self.views.views).remove(&view);
+            self.catalog_changed();
+        }
+    }
+
+    // Puts the query of each view the statement reads in place of its name, as a subquery
+    // under the name or the alias it was given. INSERT, UPDATE and DELETE can't change the
+    // rows of a view.
+    pub(super) fn expand_views(&self, statement: &mut Statement) -> Result<(), BackendError> {
+        if self.views.views.is_empty() {
+            return Ok(());
+        }
+        self.expand_statement(statement, &Scope::default())
+    }
+
+    fn expand_statement(
+        &self,
+        statement: &mut Statement,
+        scope: &Scope,
+    ) -> Result<(), BackendError> {
+        if let Some(table) = statement.changed_table() {
+            if self.view(table, scope).is_some() {
+                let action = match statement {
+                    Statement::InsertStatement(_) => "insert into",
+                    Statement::UpdateStatement(_) => "update",
+                    _ => "delete from",
+                };
+                return Err(BackendError::ObjectNotInPrerequisiteState(format!(
+                    "Cannot {} view \"{}\".",
+                    action, table
+                )));
+            }
+        }
+        match statement {
+            Statement::SelectStatement(select) => self.expand_select(select, scope),
+            Statement::InsertStatement(insert) => {
+                for value in insert.rows.iter_mut().flatten() {
+                    self.expand_expression(value, scope)?;
+                }
+                if let Some(query) = insert.query.as_mut() {
+                    self.expand_select(query, scope)?;
+                }
+                self.expand_items(&mut insert.returning, scope)
+            }
+            Statement::UpdateStatement(update) => {
+                for assignment in update.assignments.iter_mut() {
+                    self.expand_expression(&mut assignment.value, scope)?;
+                }
+                self.expand_expression(&mut update.where_clause, scope)?;
+                self.expand_items(&mut update.returning, scope)
+            }
+            Statement::DeleteStatement(delete) => {
+                self.expand_expression(&mut delete.where_clause, scope)?;
+                self.expand_items(&mut delete.returning, scope)
+            }
+            // Each query reads those before it, as they are run in order
+            Statement::WithStatement(with) => {
+                let mut scope = scope.clone();
+                for query in with.queries.iter_mut() {
+                    self.expand_statement(&mut query.statement, &scope)?;
+                    scope.with_queries.push(query.name.clone());
+                }
+                self.expand_statement(&mut with.statement, &scope)
+            }
+            Statement::CreateTableAsStatement(create_table_as) => {
+                self.expand_select(&mut create_table_as.query, scope)
+            }
+            Statement::ExplainStatement(explain) => self.expand_select(&mut explain.select, scope),
+            Statement::DeclareCursorStatement(declare) => {
+                self.expand_select(&mut declare.query, scope)
+            }
+            // A view keeps the names of the views it reads, they are expanded when it is
+            Statement::CreateViewStatement(_)
+            | Statement::CreateTableStatement(_)
+            | Statement::CreateIndexStatement(_)
+            | Statement::CreateSequenceStatement(_)
+            | Statement::DropTableStatement(_)
+            | Statement::DropIndexStatement(_)
+            | Statement::DropSequenceStatement(_)
+            | Statement::DropViewStatement(_)
+            | Statement::CreateTriggerStatement(_)
+            | Statement::DropTriggerStatement(_)
+            | Statement::AlterTableStatement(_)
+            | Statement::ReindexStatement(_)
+            | Statement::VacuumStatement(_)
+            | Statement::SetStatement(_)
+            | Statement::ShowStatement(_)
+            | Statement::ResetStatement(_)
+            | Statement::ListenStatement(_)
+            | Statement::NotifyStatement(_)
+            | Statement::UnlistenStatement(_)
+            | Statement::FetchStatement(_)
+            | Statement::CloseStatement(_)
+            | Statement::TransactionStatement(_) => Ok(()),
+        }
+    }
+
+    // The view a name read by a statement stands for, unless it is a WITH query or a
+    // table, see `resolve`
+    fn view(&self, name: &str, scope: &Scope) -> Option<&View> {
+        if scope.with_queries.iter().any(|query| query == name) {
+            return None;
+        }
+        let resolution = match scope.in_view {
+            true => self.resolve_permanent(name),
+            false => self.resolve_in_schemas(name),
+        };
+        match resolution {
+            Some(Resolution::View) => self.views.views.get(local_name(name)),
+            _ => None,
+        }
+    }
+
+    // Whether the statement reading a view would take a table the view reads for a WITH
+    // query or a temporary table
+    fn hidden_from_view(&self, name: &str, scope: &Scope) -> bool {
+        scope.in_view
+            && self.resolve_permanent(name) == Some(Resolution::Table)
+            && (scope.hidden.iter().any(|query| query == name)
+                || self.resolve_in_schemas(name) != Some(Resolution::Table))
+    }
+
+    fn expand_select(
+        &self,
+        select: &mut SelectStatement,
+        scope: &Scope,
+    ) -> Result<(), BackendError> {
+        self.expand_items(&mut select.items, scope)?;
+        for source in select.from.iter_mut() {
+            self.expand_source(source, scope)?;
+        }
+        self.expand_expression(&mut select.where_clause, scope)?;
+        for exp in select.group_by.iter_mut() {
+            self.expand_expression(exp, scope)?;
+        }
+        self.expand_expression(&mut select.having, scope)?;
+        for order_by in select.order_by.iter_mut() {
+            self.expand_expression(&mut order_by.exp, scope)?;
+        }
+        Ok(())
+    }
+
+    fn expand_items(&self, items: &mut [SelectItem], scope: &Scope) -> Result<(), BackendError> {
+        for item in items.iter_mut() {
+            self.expand_expression(&mut item.expression, scope)?;
+        }
+        Ok(())
+    }
+
+    fn expand_source(&self, source: &mut RowDataSource, scope: &Scope) -> Result<(), BackendError> {
+        let mut expanded = false;
+        if let RowDataSource::Table {
+            table_name,
+            as_clause,
+            sample,
+            joins,
+        } = source
+        {
+            if let Some(view) = self.view(table_name, scope) {
+                if sample.is_some() {
+                    return Err(BackendError::WrongObjectType(format!(
+                        "TABLESAMPLE can't be used on view \"{}\".",
+                        table_name
+                    )));
+                }
+                // What the view reads isn't in the scope of the statement reading it
+                let mut select = view.query.clone();
+                self.expand_select(&mut select, &scope.view())?;
+                let as_clause = as_clause.take().unwrap_or_else(|| table_name.clone());
+                let joins = std::mem::take(joins);
+                *source = RowDataSource::SubSelect {
+                    select: Box::new(select),
+                    as_clause,
+                    lateral: false,
+                    joins,
+                };
+                expanded = true;
+            } else if self.hidden_from_view(table_name, scope) {
+                let name = std::mem::replace(table_name, qualified_name(table_name));
+                as_clause.get_or_insert(name);
+            }
+        }
+
+        let joins = match source {
+            RowDataSource::SubSelect { select, joins, .. } => {
+                if !expanded {
+                    self.expand_select(select, scope)?;
+                }
+                joins
+            }
+            RowDataSource::Table { joins, .. } => joins,
+            RowDataSource::Values { rows, joins, .. } => {
+                for exp in rows.iter_mut().flatten() {
+                    self.expand_expression(exp, scope)?;
+                }
+                joins
+            }
+            RowDataSource::Function { call, joins, .. } => {
+                for arg in call.args.iter_mut() {
+                    self.expand_expression(arg, scope)?;
+                }
+                joins
+            }
+        };
+        for join in joins.iter_mut() {
+            self.expand_source(&mut join.source, scope)?;
+            self.expand_expression(&mut join.on, scope)?;
+        }
+        Ok(())
+    }
+
+    fn expand_expression(
+        &self,
+        expression: &mut Expression,
+        scope: &Scope,
+    ) -> Result<(), BackendError> {
+        match expression {
+            Expression::Binary(binary) => {
+                self.expand_expression(&mut binary.first, scope)?;
+                self.expand_expression(&mut binary.second, scope)
+            }
+            Expression::Unary(unary) => self.expand_expression(&mut unary.first, scope),
+            Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+                self.expand_expression(data, scope)
+            }
+            Expression::SubSelect(select) | Expression::Exists(select) => {
+                self.expand_select(select, scope)
+            }
+            Expression::Quantified(quantified) => {
+                self.expand_expression(&mut quantified.first, scope)?;
+                match &mut quantified.set {
+                    QuantifiedSet::SubSelect(select) => self.expand_select(select, scope),
+                    QuantifiedSet::Values(_) => Ok(()),
+                }
+            }
+            Expression::FunctionCall(call) => {
+                for arg in call.args.iter_mut() {
+                    self.expand_expression(arg, scope)?;
+                }
+                if let Some(filter) = call.filter.as_mut() {
+                    self.expand_expression(filter, scope)?;
+                }
+                if let Some(over) = &mut call.over {
+                    for exp in over.partition_by.iter_mut() {
+                        self.expand_expression(exp, scope)?;
+                    }
+                    for order_by in over.order_by.iter_mut() {
+                        self.expand_expression(&mut order_by.exp, scope)?;
+                    }
+                }
+                Ok(())
+            }
+            Expression::Literal(_)
+            | Expression::TableColumn(_)
+            | Expression::ProcessedTableColumn(_)
+            | Expression::Empty => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod views_tests {
+    use super::*;
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE users (id INT PRIMARY KEY, name TEXT, active BOOLEAN);
+        INSERT INTO users VALUES (1, 'ann', true);
+        INSERT INTO users VALUES (2, 'bob', false);
+        INSERT INTO users VALUES (3, 'cid', true);
+        CREATE TABLE orders (id INT, user_id INT, total INT);
+        INSERT INTO orders VALUES (10, 1, 5);
+        INSERT INTO orders VALUES (11, 3, 7);
+        INSERT INTO orders VALUES (12, 1, 9);
+        CREATE VIEW active_users AS SELECT * FROM users WHERE active;";
+
+    #[test]
+    fn test_view_reads_its_query() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(&mut mb, "SELECT id, name FROM active_users ORDER BY id;"),
+            ["1|ann", "3|cid"]
+        );
+        // Under an alias, in a subquery and joined to a table
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT a.name FROM active_users AS a WHERE a.id > 1;"
+            ),
+            ["cid"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT COUNT(*) FROM orders WHERE user_id IN (SELECT id FROM active_users);"
+            ),
+            ["3"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT active_users.name, orders.total FROM orders \
+                JOIN active_users ON orders.user_id = active_users.id ORDER BY orders.id;"
+            ),
+            ["ann|5", "cid|7", "ann|9"]
+        );
+
+        // The rows are those of the table when the view is read
+        mb.eval_query("UPDATE users SET active = true WHERE id = 2;")
+            .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT COUNT(*) FROM active_users;"), ["3"]);
+    }
+
+    #[test]
+    fn test_view_over_join_and_view_of_view() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "CREATE VIEW user_orders AS SELECT users.name, orders.total FROM users \
+            JOIN orders ON orders.user_id = users.id;
+            CREATE VIEW big_orders AS SELECT name, total FROM user_orders WHERE total > 6;",
+        )
+        .unwrap();
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT name, total FROM user_orders ORDER BY total;"
+            ),
+            ["ann|5", "cid|7", "ann|9"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT name, SUM(total) FROM big_orders GROUP BY name ORDER BY name;"
+            ),
+            ["ann|9", "cid|7"]
+        );
+
+        // A WITH query of the same name is read in place of the view
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH big_orders AS (SELECT 1 AS n) SELECT n FROM big_orders;"
+            ),
+            ["1"]
+        );
+    }
+
+    #[test]
+    fn test_drop_with_dependent_views() {
+        let mut mb = backend(SETUP);
+        mb.eval_query("CREATE VIEW active_names AS SELECT name FROM active_users;")
+            .unwrap();
+
+        let err = mb.eval_query("DROP TABLE users;").unwrap_err();
+        assert_eq!(
+            (err.code(), err.message()),
+            (
+                "2BP01",
+                "Cannot drop table \"users\" because view \"active_users\" depends on it."
+            )
+        );
+        let err = mb.eval_query("DROP VIEW active_users;").unwrap_err();
+        assert_eq!(err.code(), "2BP01");
+
+        // Dropping the view on top leaves the one below
+        mb.eval_query("DROP VIEW active_names;").unwrap();
+        assert_eq!(rows(&mut mb, "SELECT COUNT(*) FROM active_users;"), ["2"]);
+
+        mb.eval_query("CREATE VIEW active_names AS SELECT name FROM active_users;")
+            .unwrap();
+        mb.eval_query("DROP TABLE users CASCADE;").unwrap();
+        assert_eq!(
+            mb.eval_query("SELECT * FROM active_names;")
+                .unwrap_err()
+                .code(),
+            "42P01"
+        );
+        assert!(!mb.views.contains("active_users"));
+        assert!(mb.eval_query("DROP VIEW IF EXISTS active_users;").is_ok());
+
+        // A failed transaction takes back the views it dropped
+        mb.eval_query(
+            "CREATE TABLE users (id INT, name TEXT, active BOOLEAN);
+            CREATE VIEW active_users AS SELECT * FROM users WHERE active;",
+        )
+        .unwrap();
+        let result: Result<(), BackendError> = mb.transaction(|mb| {
+            mb.eval_query("DROP TABLE users CASCADE;")?;
+            Err(BackendError::Internal("rolled back".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(rows(&mut mb, "SELECT COUNT(*) FROM active_users;"), ["0"]);
+    }
+
+    #[test]
+    fn test_view_errors() {
+        let mut mb = backend(SETUP);
+        let cases = [
+            ("INSERT INTO active_users VALUES (4, 'dan', true);", "55000"),
+            ("UPDATE active_users SET name = 'x';", "55000"),
+            ("DELETE FROM active_users WHERE id = 1;", "55000"),
+            ("CREATE VIEW users AS SELECT 1;", "42P07"),
+            ("CREATE TABLE active_users (id INT);", "42P07"),
+            ("CREATE VIEW v AS SELECT * FROM nope;", "42P01"),
+            ("CREATE VIEW v AS SELECT id, id FROM users;", "42701"),
+            ("DROP TABLE active_users;", "42809"),
+            ("DROP VIEW users;", "42809"),
+            ("DROP VIEW nope;", "42P01"),
+            (
+                "SELECT * FROM active_users TABLESAMPLE BERNOULLI (50);",
+                "42809",
+            ),
+        ];
+        for (query, code) in cases {
+            let err = mb.eval_query(query).expect_err(query);
+            assert_eq!(err.code(), code, "{}", query);
+        }
+        let err = mb
+            .eval_query("DELETE FROM active_users WHERE id = 1;")
+            .unwrap_err();
+        assert_eq!(err.message(), "Cannot delete from view \"active_users\".");
+
+        mb.eval_query("CREATE TEMPORARY TABLE scratch (id INT);")
+            .unwrap();
+        assert_eq!(
+            mb.eval_query("CREATE VIEW v AS SELECT * FROM scratch;")
+                .unwrap_err()
+                .code(),
+            "0A000"
+        );
+    }
+
+    #[test]
+    fn test_views_in_catalog() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT table_schema, table_name, view_definition, is_updatable \
+                FROM information_schema.views;"
+            ),
+            ["public|active_users|SELECT * FROM users WHERE active|NO"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT relkind FROM pg_class WHERE relname = 'active_users';"
+            ),
+            ["v"]
+        );
+        assert_eq!(
+            rows(
+                &mut mb,
+                "SELECT COUNT(*) FROM pg_tables WHERE tablename = 'active_users';"
+            ),
+            ["0"]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::backend::Warning;
+use crate::lexer::{LexOptions, Lexer, Token};
+use crate::sql_types::{SqlNumeric, SqlType, SqlTypeError, SqlValue};
+use std::cell::RefCell;
+
+// SQLSTATE codes of the warnings raised, of the class Postgres gives its own
+pub(super) const WARNING: &str = "01000";
+pub(super) const STRING_DATA_RIGHT_TRUNCATION: &str = "01004";
+// Postgres gives the error code with its notice of an identifier cut down
+pub(super) const NAME_TOO_LONG: &str = "42622";
+// And with those of transaction statements that had nothing to do
+pub(super) const ACTIVE_SQL_TRANSACTION: &str = "25001";
+pub(super) const NO_ACTIVE_SQL_TRANSACTION: &str = "25P01";
+pub(super) const IN_FAILED_SQL_TRANSACTION: &str = "25P02";
+const NONSTANDARD_USE_OF_ESCAPE_CHARACTER: &str = "22P06";
+
+thread_local! {
+    static WARNINGS: RefCell<Vec<Warning>> = const { RefCell::new(vec![]) };
+}
+
+// Gives back the warnings of the statement that was running before when dropped
+pub(super) struct WarningsGuard {
+    outer: Vec<Warning>,
+}
+
+impl WarningsGuard {
+    // The warnings raised on this thread since the guard was made
+    pub(super) fn finish(self) -> Vec<Warning> {
+        take_warnings()
+    }
+}
+
+impl Drop for WarningsGuard {
+    fn drop(&mut self) {
+        let outer = std::mem::take(&mut self.outer);
+        WARNINGS.with(|warnings| *warnings.borrow_mut() = outer);
+    }
+}
+
+// Collects the warnings raised on this thread apart from those of any statement running
+// already, until the guard is dropped
+pub(super) fn enter_warnings() -> WarningsGuard {
+    WarningsGuard {
+        outer: WARNINGS.with(|warnings| warnings.replace(vec![])),
+    }
+}
+
+// The warnings raised on this thread so far, leaving none
+pub(super) fn take_warnings() -> Vec<Warning> {
+    WARNINGS.with(|warnings| warnings.take())
+}
+
+// Adds a warning for the running statement. A condition met on many rows is told once.
+pub(super) fn warn(warning: Warning) {
+    WARNINGS.with(|warnings| {
+        let mut warnings = warnings.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    })
+}
+
+// The warnings Postgres gives of the strings of a query with backslashes in them, which
+// only escape characters without standard_conforming_strings
+pub(super) fn nonstandard_escapes(query: &str, options: LexOptions) -> Vec<Warning> {
+    if options.standard_conforming_strings {
+        return vec![];
+    }
+    let tokens = Lexer::with_options(options).lex(query).unwrap_or_default();
+    tokens
+        .iter()
+        .filter(|token| matches!(token.token, Token::StringValue { .. }))
+        .filter(|token| query[token.span.start..token.span.end].contains('\\'))
+        .map(|token| {
+            Warning::new(
+                NONSTANDARD_USE_OF_ESCAPE_CHARACTER,
+                "Nonstandard use of \\ in a string literal.".to_string(),
+            )
+            .at(token.loc)
+        })
+        .collect()
+}
+
+// `value` cast to `typ` as `::` does, warning when a number doesn't come out the same
+pub(super) fn explicit_cast(value: &SqlValue, typ: SqlType) -> Result<SqlValue, SqlTypeError> {
+    let cast = value.explicit_cast_to_type(typ)?;
+    if let (SqlValue::Numeric(from), SqlValue::Numeric(to)) = (value, &cast) {
+        if let Some(lost) = lost_in_cast(from, to) {
+            warn(Warning::new(
+                WARNING,
+                format!("Casting {} to {} {}.", value.get_type(), typ, lost),
+            ));
+        }
+    }
+    Ok(cast)
+}
+
+pub(super) fn has_fraction(value: f64) -> bool {
+    value.is_finite() && value.fract() != 0.0
+}
+
+// What casting a number lost, if anything
+fn lost_in_cast(from: &SqlNumeric, to: &SqlNumeric) -> Option<&'static str> {
+    let fraction = Some("dropped the fractional part");
+    let precision = Some("lost precision");
+    let float = |num: &SqlNumeric| match num {
+        SqlNumeric::Real { value } => Some(f64::from(*value)),
+        SqlNumeric::DoublePrecision { value } => Some(*value),
+        _ => None,
+    };
+    match (from, to) {
+        _ if float(to).is_none() && float(from).is_some_and(has_fraction) => fraction,
+        (SqlNumeric::DoublePrecision { value }, SqlNumeric::Real { value: real })
+            if value.is_finite() && f64::from(*real) != *value =>
+        {
+            precision
+        }
+        (SqlNumeric::Int { value }, SqlNumeric::Real { value: real })
+            if *real as i64 != i64::from(*value) =>
+        {
+            precision
+        }
+        (SqlNumeric::BigInt { value }, SqlNumeric::Real { value: real })
+            if *real as i64 != *value =>
+        {
+            precision
+        }
+        (SqlNumeric::BigInt { value }, SqlNumeric::DoublePrecision { value: double })
+            if *double as i64 != *value =>
+        {
+            precision
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod warnings_tests {
+    use crate::backend::{EvalResult, Warning};
+    use crate::backend_memory::test_support::{backend, backend_with, rows};
+    use crate::backend_memory::{BackendConfig, CastMode, MemoryBackend};
+
+    const SETUP: &str = "CREATE TABLE t (id INT, code CHAR(3), name VARCHAR(5), ratio REAL);
+        INSERT INTO t VALUES (1, 'abc', 'first', 2.0), (2, 'de', 'two', 1.5);";
+
+    fn warnings(mb: &mut MemoryBackend, query: &str) -> Vec<String> {
+        mb.eval_query(query)
+            .unwrap()
+            .iter()
+            .flat_map(EvalResult::warnings)
+            .map(|warning| format!("{} {}", warning.code, warning))
+            .collect()
+    }
+
+    #[test]
+    fn test_lossy_cast_warnings() {
+        let mut mb = backend(SETUP);
+        assert!(warnings(
+            &mut mb,
+            "SELECT id::REAL, id::BIGINT, ratio::BIGINT FROM t WHERE id = 1;"
+        )
+        .is_empty());
+        assert_eq!(
+            warnings(&mut mb, "SELECT ratio::INT FROM t;"),
+            vec!["01000 Casting Real to Int dropped the fractional part."]
+        );
+        // A condition is told once however many values it was met on
+        assert_eq!(
+            warnings(&mut mb, "SELECT 2.5::BIGINT, 3.5::BIGINT, 0.1::REAL;"),
+            vec![
+                "01000 Casting Double Precision to Bigint dropped the fractional part.",
+                "01000 Casting Double Precision to Real lost precision."
+            ]
+        );
+        assert_eq!(
+            warnings(
+                &mut mb,
+                "INSERT INTO t VALUES (2.5, 'a', 'b', 1); UPDATE t SET id = 3.5 WHERE id = 1;"
+            ),
+            vec![
+                "01000 Value rounded to fit type Int.",
+                "01000 Value rounded to fit type Int."
+            ]
+        );
+        // A statement that fails has no results to carry them
+        assert!(mb.eval_query("SELECT 1.5::INT, 1 / 0;").is_err());
+        assert!(warnings(&mut mb, "SELECT 1;").is_empty());
+    }
+
+    #[test]
+    fn test_truncating_cast_mode() {
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            mb.eval_query("INSERT INTO t VALUES (4, 'abcd', 'a', 1);")
+                .unwrap_err()
+                .code(),
+            "22001"
+        );
+        // Trailing spaces go silently whatever the mode
+        assert!(warnings(&mut mb, "INSERT INTO t VALUES (4, 'ab   ', 'a', 1);").is_empty());
+
+        mb.eval_query("SET postgrustql.cast_mode = 'truncate';")
+            .unwrap();
+        assert_eq!(mb.config().cast_mode, CastMode::Truncate);
+        assert_eq!(
+            warnings(&mut mb, "INSERT INTO t VALUES (3, 'abcd', 'second', 1);"),
+            vec![
+                "01004 Value truncated to fit type Char(3).",
+                "01004 Value truncated to fit type Varchar(5)."
+            ]
+        );
+        assert_eq!(
+            warnings(&mut mb, "UPDATE t SET code = 'defg' WHERE id = 2;"),
+            vec!["01004 Value truncated to fit type Char(3)."]
+        );
+        assert_eq!(
+            rows(&mut mb, "SELECT code, name FROM t WHERE id = 2 OR id = 3;"),
+            vec!["def|two", "abc|secon"]
+        );
+
+        assert_eq!(
+            mb.eval_query("SET postgrustql.cast_mode = 'lenient';")
+                .unwrap_err()
+                .code(),
+            "22023"
+        );
+        mb.eval_query("RESET postgrustql.cast_mode;").unwrap();
+        assert!(mb
+            .eval_query("INSERT INTO t VALUES (4, 'abcd', 'a', 1);")
+            .is_err());
+
+        let mut mb = backend_with(BackendConfig::new().cast_mode(CastMode::Truncate), SETUP);
+        assert_eq!(
+            warnings(&mut mb, "INSERT INTO t VALUES (4, 'abcd', 'a', 1);").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_unknown_setting_warning() {
+        let mut mb = backend_with(BackendConfig::new().allow_unknown_settings(true), SETUP);
+        let result = mb
+            .eval_query("SET maintenance_work_mem = '64MB';")
+            .unwrap()
+            .pop()
+            .unwrap();
+        let warning = Warning::new(
+            "01000",
+            "Unrecognized configuration parameter \"maintenance_work_mem\" was set anyway."
+                .to_string(),
+        );
+        assert_eq!(
+            result.warnings(),
+            &[warning.at(crate::lexer::TokenLocation::new_with_col_and_line(4, 0))]
+        );
+        // Names with a dot in them are taken as custom ones, like in Postgres
+        assert!(warnings(&mut mb, "SET app.user_id = '7';").is_empty());
+        assert!(warnings(&mut mb, "SET application_name = 'app';").is_empty());
+
+        let mut mb = backend(SETUP);
+        assert_eq!(
+            mb.eval_query("SET maintenance_work_mem = '64MB';")
+                .unwrap_err()
+                .code(),
+            "42704"
+        );
+    }
+
+    #[test]
+    fn test_nonstandard_escape_warnings() {
+        let mut mb = backend(SETUP);
+        let path = |mb: &mut MemoryBackend, query: &str| match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results.rows[0][0].to_string(),
+            _ => panic!("Expected select results for {}", query),
+        };
+        let query = r"SELECT 'C:\data\new.csv';";
+        assert_eq!(path(&mut mb, query), r"C:\data\new.csv");
+        assert!(warnings(&mut mb, query).is_empty());
+
+        mb.eval_query("SET standard_conforming_strings = off;")
+            .unwrap();
+        assert_eq!(path(&mut mb, query), "C:data\new.csv");
+        assert_eq!(
+            warnings(&mut mb, query),
+            vec!["22P06 [1, 8]: Nonstandard use of \\ in a string literal."]
+        );
+        assert_eq!(path(&mut mb, r"SELECT 'it\'s';"), "it's");
+        assert!(warnings(&mut mb, "SELECT 'it''s';").is_empty());
+
+        // Each statement carries the warnings of its own strings
+        let results = mb
+            .eval_query("SELECT 'a';\nSELECT 'b\\n', 'c\\t';")
+            .unwrap();
+        assert!(results[0].warnings().is_empty());
+        let locations: Vec<String> = results[1]
+            .warnings()
+            .iter()
+            .map(|warning| format!("{} {}", warning.code, warning))
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                "22P06 [2, 8]: Nonstandard use of \\ in a string literal.",
+                "22P06 [2, 15]: Nonstandard use of \\ in a string literal."
+            ]
+        );
+
+        mb.eval_query("RESET standard_conforming_strings;").unwrap();
+        assert_eq!(path(&mut mb, query), r"C:\data\new.csv");
+        assert!(warnings(&mut mb, query).is_empty());
+    }
+}
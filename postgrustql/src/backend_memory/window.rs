@@ -0,0 +1,432 @@
+use super::aggregate::RunningAggregate;
+use super::stored_row::StoredRow;
+use super::values::big_int;
+use super::{compare_order_keys, CompiledExpression, Table, ANONYMOUS_COL_NAME};
+use crate::ast::*;
+use crate::backend::{BackendError, ERR_FUNCTION_DOES_NOT_EXIST};
+use crate::sql_types::{SqlType, SqlValue};
+use std::collections::BTreeMap;
+
+// Functions that can be given an OVER clause. They see the rows of their partition up
+// to the last peer of the current row, rows being peers when their ORDER BY values
+// are equal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    Sum,
+    Count,
+    Avg,
+}
+
+impl WindowFunction {
+    pub fn from_call(call: &FunctionCall) -> Result<Self, BackendError> {
+        let (function, arg_count) = match call.name.as_str() {
+            "row_number" => (WindowFunction::RowNumber, 0),
+            "rank" => (WindowFunction::Rank, 0),
+            "dense_rank" => (WindowFunction::DenseRank, 0),
+            "sum" => (WindowFunction::Sum, 1),
+            "count" if call.asterisk => (WindowFunction::Count, 0),
+            "count" => (WindowFunction::Count, 1),
+            "avg" => (WindowFunction::Avg, 1),
+            "abs" | "div" | "format" | "length" | "lower" | "mod" | "pg_typeof" | "upper" => {
+                return Err(BackendError::WrongObjectType(format!(
+                "{}: OVER specified, but it is not a window function nor an aggregate function.",
+                call.name
+            )))
+            }
+            _ => {
+                return Err(BackendError::UndefinedFunction(format!(
+                    "{}: {}",
+                    call.name, ERR_FUNCTION_DOES_NOT_EXIST
+                )))
+            }
+        };
+        if call.distinct {
+            return Err(BackendError::FeatureNotSupported(format!(
+                "{}: DISTINCT is not implemented for window functions.",
+                call.name
+            )));
+        }
+        if call.filter.is_some() && arg_count == 0 && !call.asterisk {
+            return Err(BackendError::FeatureNotSupported(format!(
+                "{}: FILTER is not implemented for non-aggregate window functions.",
+                call.name
+            )));
+        }
+        if call.args.len() != arg_count || (call.asterisk && function != WindowFunction::Count) {
+            return Err(BackendError::UndefinedFunction(format!(
+                "{}: Expected {} argument(s), got {}.",
+                call.name,
+                arg_count,
+                call.args.len()
+            )));
+        }
+        Ok(function)
+    }
+
+    fn result_type(&self, values: &[SqlValue]) -> SqlType {
+        match self {
+            WindowFunction::Avg => SqlType::DoublePrecision,
+            WindowFunction::Sum => values
+                .iter()
+                .map(|value| value.get_type())
+                .find(|typ| *typ != SqlType::Null)
+                .unwrap_or(SqlType::BigInt),
+            _ => SqlType::BigInt,
+        }
+    }
+}
+
+pub fn contains_window_function(expression: &Expression) -> bool {
+    match expression {
+        Expression::FunctionCall(call) => {
+            call.over.is_some() || call.args.iter().any(contains_window_function)
+        }
+        Expression::Binary(binary) => {
+            contains_window_function(&binary.first) || contains_window_function(&binary.second)
+        }
+        Expression::Unary(unary) => contains_window_function(&unary.first),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            contains_window_function(data)
+        }
+        Expression::Quantified(quantified) => contains_window_function(&quantified.first),
+        Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => false,
+    }
+}
+
+// Swaps every window function call in `expression` for a reference to the column its
+// results will be put in, the calls are collected in column order
+fn take_window_calls(expression: &mut Expression, first_col: usize, calls: &mut Vec<FunctionCall>) {
+    match expression {
+        Expression::FunctionCall(call) if call.over.is_some() => {
+            let col_idx = first_col + calls.len();
+            calls.push(call.clone());
+            *expression = Expression::ProcessedTableColumn(ProcessedTableColumn {
+                col_name: None,
+                col_idx,
+            });
+        }
+        Expression::FunctionCall(call) => {
+            for arg in call.args.iter_mut() {
+                take_window_calls(arg, first_col, calls);
+            }
+        }
+        Expression::Binary(binary) => {
+            take_window_calls(&mut binary.first, first_col, calls);
+            take_window_calls(&mut binary.second, first_col, calls);
+        }
+        Expression::Unary(unary) => take_window_calls(&mut unary.first, first_col, calls),
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            take_window_calls(data, first_col, calls)
+        }
+        Expression::Quantified(quantified) => {
+            take_window_calls(&mut quantified.first, first_col, calls)
+        }
+        Expression::SubSelect(_)
+        | Expression::Exists(_)
+        | Expression::Literal(_)
+        | Expression::TableColumn(_)
+        | Expression::ProcessedTableColumn(_)
+        | Expression::Empty => {}
+    }
+}
+
+impl Table {
+    // Computes the window functions of the select items and ORDER BY over the rows that
+    // pass `where_clause`. The results are added to a copy of the table as columns, and
+    // the calls are replaced with references to them. Gives nothing when there are no
+    // window functions to compute.
+    pub(super) fn with_window_columns(
+        &self,
+        where_clause: &Option<CompiledExpression>,
+        items: &mut [SelectItem],
+        order_by: &mut [OrderByClause],
+    ) -> Result<Option<Table>, BackendError> {
+        let first_col = self.columns.len();
+        let mut calls = vec![];
+        for item in items.iter_mut() {
+            // A bare call keeps the name of its function, like in postgres
+            if let Expression::FunctionCall(FunctionCall {
+                name,
+                over: Some(_),
+                ..
+            }) = &item.expression
+            {
+                if item.as_clause.is_none() {
+                    item.as_clause = Some(name.clone());
+                }
+            }
+            take_window_calls(&mut item.expression, first_col, &mut calls);
+        }
+        for order_by in order_by.iter_mut() {
+            take_window_calls(&mut order_by.exp, first_col, &mut calls);
+        }
+        if calls.is_empty() {
+            return Ok(None);
+        }
+
+        let mut passing = Vec::with_capacity(self.rows.len());
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let is_true = match where_clause {
+                Some(where_clause) => where_clause.is_true(row)?,
+                None => true,
+            };
+            if is_true {
+                passing.push(row_index);
+            }
+        }
+
+        let mut table = self.clone();
+        // The values of the new columns, NULL for the rows WHERE leaves out
+        let mut added = vec![vec![SqlValue::Null; calls.len()]; self.rows.len()];
+        for (col_idx, call) in calls.iter().enumerate() {
+            let function = WindowFunction::from_call(call)?;
+            let values = self.evaluate_window(function, call, &passing)?;
+            table.columns.push(ANONYMOUS_COL_NAME.to_string());
+            table.column_types.push(function.result_type(&values));
+            for (row_index, value) in passing.iter().zip(values) {
+                added[*row_index][col_idx] = value;
+            }
+        }
+        for (row, added) in table.rows.iter_mut().zip(added) {
+            let mut values = row.values();
+            values.extend(added);
+            *row = StoredRow::new(&values);
+        }
+        Ok(Some(table))
+    }
+
+    // Gives the result of the window function for each of `rows`, in their order
+    fn evaluate_window(
+        &self,
+        function: WindowFunction,
+        call: &FunctionCall,
+        rows: &[usize],
+    ) -> Result<Vec<SqlValue>, BackendError> {
+        let (partition_by, order_by): (&[Expression], &[OrderByClause]) = match &call.over {
+            Some(over) => (&over.partition_by, &over.order_by),
+            None => (&[], &[]),
+        };
+
+        let mut partitions: BTreeMap<Vec<SqlValue>, Vec<usize>> = BTreeMap::new();
+        let mut order_keys = Vec::with_capacity(rows.len());
+        let mut args = Vec::with_capacity(rows.len());
+        for (position, row_index) in rows.iter().enumerate() {
+            let mut partition_key = Vec::with_capacity(partition_by.len());
+            for exp in partition_by {
+                partition_key.push(self.evaluate_cell(*row_index, exp)?.0);
+            }
+            partitions.entry(partition_key).or_default().push(position);
+            let mut order_key = Vec::with_capacity(order_by.len());
+            for clause in order_by {
+                order_key.push(
+                    self.evaluate_cell(*row_index, &clause.exp)?
+                        .0
+                        .encode_sortable()
+                        .bytes,
+                );
+            }
+            order_keys.push(order_key);
+            // Rows FILTER leaves out are skipped by the aggregate like NULLs are
+            let filtered_out = match &call.filter {
+                Some(filter) => !matches!(
+                    self.evaluate_cell(*row_index, filter)?.0,
+                    SqlValue::Boolean(true)
+                ),
+                None => false,
+            };
+            let arg = match call.args.first() {
+                _ if filtered_out => SqlValue::Null,
+                Some(arg) => self.evaluate_cell(*row_index, arg)?.0,
+                None => SqlValue::Boolean(true),
+            };
+            if let (WindowFunction::Sum, SqlValue::Boolean(_))
+            | (WindowFunction::Sum, SqlValue::Text(_))
+            | (WindowFunction::Avg, SqlValue::Boolean(_))
+            | (WindowFunction::Avg, SqlValue::Text(_)) = (function, &arg)
+            {
+                return Err(BackendError::UndefinedFunction(format!(
+                    "{}: Expected a numeric argument.",
+                    call.name
+                )));
+            }
+            args.push(arg);
+        }
+
+        let compare = |a: &usize, b: &usize| {
+            order_keys[*a]
+                .iter()
+                .zip(&order_keys[*b])
+                .zip(order_by)
+                .map(|((a, b), clause)| compare_order_keys(a, b, clause.asc))
+                .find(|ordering| *ordering != std::cmp::Ordering::Equal)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+
+        let mut results = vec![SqlValue::Null; rows.len()];
+        for partition in partitions.values_mut() {
+            partition.sort_by(compare);
+
+            let mut aggregate = RunningAggregate::default();
+            let mut peers_start = 0;
+            let mut dense_rank = 0;
+            while peers_start < partition.len() {
+                let mut peers_end = peers_start + 1;
+                while peers_end < partition.len()
+                    && compare(&partition[peers_start], &partition[peers_end])
+                        == std::cmp::Ordering::Equal
+                {
+                    peers_end += 1;
+                }
+                dense_rank += 1;
+                for position in &partition[peers_start..peers_end] {
+                    aggregate.add(&args[*position])?;
+                }
+
+                for (offset, position) in partition[peers_start..peers_end].iter().enumerate() {
+                    results[*position] = match function {
+                        WindowFunction::RowNumber => big_int(peers_start + offset + 1),
+                        WindowFunction::Rank => big_int(peers_start + 1),
+                        WindowFunction::DenseRank => big_int(dense_rank),
+                        WindowFunction::Count => big_int(aggregate.count),
+                        WindowFunction::Sum => aggregate.sum.clone(),
+                        WindowFunction::Avg => aggregate.avg()?,
+                    };
+                }
+                peers_start = peers_end;
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod window_tests {
+    use crate::backend::EvalResult;
+    use crate::backend_memory::MemoryBackend;
+
+    struct WindowTest {
+        query: &'static str,
+        // The result rows joined by commas, or the error
+        result: Result<Vec<&'static str>, &'static str>,
+    }
+
+    #[test]
+    fn test_window_functions() {
+        let tests = vec![
+            // NULLs come last, so Yuri's comes first when descending
+            WindowTest {
+                query: "SELECT name, dept, RANK() OVER (PARTITION BY dept ORDER BY salary DESC)
+                    FROM emp ORDER BY name;",
+                result: Ok(vec![
+                    "Baam,eng,3",
+                    "Khun,eng,1",
+                    "Rachel,eng,1",
+                    "Rak,ops,2",
+                    "Yuri,ops,1",
+                ]),
+            },
+            WindowTest {
+                query: "SELECT name, row_number() OVER (ORDER BY salary DESC, name),
+                    dense_rank() OVER (ORDER BY salary DESC) FROM emp WHERE salary > 10
+                    ORDER BY name;",
+                result: Ok(vec!["Baam,3,2", "Khun,1,1", "Rachel,2,1", "Rak,4,3"]),
+            },
+            // With ORDER BY the frame ends at the last peer of the row, without it the
+            // frame is the whole partition
+            // Yuri's NULL salary comes after Rak's, so the running sum is already 50
+            WindowTest {
+                query: "SELECT name, sum(salary) OVER (PARTITION BY dept ORDER BY salary),
+                    count(*) OVER (PARTITION BY dept), avg(salary) OVER () FROM emp
+                    ORDER BY name;",
+                result: Ok(vec![
+                    "Baam,100,3,137.5",
+                    "Khun,500,3,137.5",
+                    "Rachel,500,3,137.5",
+                    "Rak,50,2,137.5",
+                    "Yuri,50,2,137.5",
+                ]),
+            },
+            WindowTest {
+                query: "SELECT name FROM emp ORDER BY row_number() OVER (ORDER BY name DESC);",
+                result: Ok(vec!["Yuri", "Rak", "Rachel", "Khun", "Baam"]),
+            },
+            WindowTest {
+                query: "SELECT name FROM emp WHERE rank() OVER (ORDER BY salary) = 1;",
+                result: Err("Window functions are not allowed in WHERE."),
+            },
+            WindowTest {
+                query: "SELECT lower(name) OVER () FROM emp;",
+                result: Err(
+                    "lower: OVER specified, but it is not a window function nor an aggregate function.",
+                ),
+            },
+            WindowTest {
+                query: "SELECT sum(name) OVER () FROM emp;",
+                result: Err("sum: Expected a numeric argument."),
+            },
+        ];
+
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE emp (name TEXT, dept TEXT, salary INT);
+            INSERT INTO emp VALUES ('Baam', 'eng', 100);
+            INSERT INTO emp VALUES ('Khun', 'eng', 200);
+            INSERT INTO emp VALUES ('Rachel', 'eng', 200);
+            INSERT INTO emp VALUES ('Rak', 'ops', 50);
+            INSERT INTO emp VALUES ('Yuri', 'ops', NULL);",
+        )
+        .unwrap();
+
+        for test in tests {
+            let rows = match mb.eval_query(test.query).map(|mut results| results.pop()) {
+                Ok(Some(EvalResult::Select { results, .. })) => Ok(results
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    })
+                    .collect::<Vec<_>>()),
+                Ok(_) => panic!("Expected select results for {}", test.query),
+                Err(err) => Err(err.to_string()),
+            };
+            match (rows, test.result) {
+                (Ok(rows), Ok(expected)) => assert_eq!(rows, expected, "{}", test.query),
+                (Err(err), Err(expected)) => assert_eq!(err, expected, "{}", test.query),
+                (result, expected) => panic!("{}: {:?} vs {:?}", test.query, result, expected),
+            }
+        }
+
+        // Syntax errors say what was expected where
+        for (query, expected) in [
+            ("SELECT rank() OVER name FROM emp;", "Expected ("),
+            (
+                "SELECT rank() OVER (PARTITION BY) FROM emp;",
+                "Expected PARTITION BY expression",
+            ),
+            (
+                "SELECT rank() OVER (ORDER BY name ROWS UNBOUNDED PRECEDING) FROM emp;",
+                "Window frames are not supported",
+            ),
+            (
+                "SELECT rank() OVER (ORDER BY) FROM emp;",
+                "Expected ORDER BY expression",
+            ),
+            ("SELECT sum(salary, FROM emp;", "Expected function argument"),
+        ] {
+            let err = mb.eval_query(query).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}: {}", query, err);
+        }
+    }
+}
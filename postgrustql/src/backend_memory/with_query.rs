@@ -0,0 +1,306 @@
+use super::stored_row::StoredRow;
+use super::{MemoryBackend, StorageEngine, Table};
+use crate::ast::*;
+use crate::backend::{BackendError, EvalResult, QueryResults, ERR_TABLE_DOES_NOT_EXIST};
+use crate::sql_types::SqlValue;
+
+impl MemoryBackend {
+    // Runs the queries of a WITH in order, each once, then the statement after them. The
+    // tables the queries change are read as they were before the statement, from copies
+    // taken first, and either every change is made or none is.
+    pub(super) fn with_query(
+        &mut self,
+        statement: WithStatement,
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        for (idx, query) in statement.queries.iter().enumerate() {
+            if statement.queries[..idx]
+                .iter()
+                .any(|known| known.name == query.name)
+            {
+                return Err(BackendError::DuplicateAlias(format!(
+                    "WITH query name \"{}\" specified more than once.",
+                    query.name
+                )));
+            }
+        }
+
+        // Which of two changes to the same rows wins can't be told, Postgres leaves it
+        // unspecified. Neither is made here.
+        let statements = statement.queries.iter().map(|query| &*query.statement);
+        let mut changed: Vec<String> = vec![];
+        for table_name in statements
+            .chain(std::iter::once(&*statement.statement))
+            .filter_map(Statement::changed_table)
+        {
+            if changed.iter().any(|known| known == table_name) {
+                return Err(BackendError::FeatureNotSupported(format!(
+                    "Table \"{}\" is changed more than once by the statement.",
+                    table_name
+                )));
+            }
+            if statement
+                .queries
+                .iter()
+                .any(|query| query.name == table_name)
+            {
+                return Err(BackendError::FeatureNotSupported(format!(
+                    "WITH query \"{}\" has the name of a table the statement changes.",
+                    table_name
+                )));
+            }
+            changed.push(table_name.to_string());
+        }
+        // The statement itself reads what it changes before changing it, no copy is needed
+        if statement.statement.changed_table().is_some() {
+            changed.pop();
+        }
+
+        let outer = self.with_tables.clone();
+        let result = match changed.is_empty() {
+            true => self.run_with(statement, &changed),
+            false => self.transaction(|backend| backend.run_with(statement, &changed)),
+        };
+        self.with_tables = outer;
+        result
+    }
+
+    fn run_with(
+        &mut self,
+        statement: WithStatement,
+        changed: &[String],
+    ) -> Result<EvalResult<SqlValue>, BackendError> {
+        for table_name in changed {
+            let table = match self.engine(table_name).scan_table(table_name)? {
+                Some(table) => table.into_owned(),
+                None => {
+                    return Err(BackendError::UndefinedTable(
+                        ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                    ))
+                }
+            };
+            self.with_tables.create_table(table)?;
+        }
+
+        for query in statement.queries {
+            // A change without RETURNING gives nothing to read
+            let read = query.statement.changed_table().is_none()
+                || !query.statement.returning().is_empty();
+            let results = match *query.statement {
+                Statement::SelectStatement(select) => self.select(select)?,
+                statement => self.run_returning(&statement)?,
+            };
+            if read {
+                let table = query_table(&query.name, &query.columns, results)?;
+                self.with_tables.create_table(table)?;
+            }
+        }
+        self.run_statement(*statement.statement)
+    }
+
+    // Runs an INSERT, UPDATE or DELETE, giving the rows of its RETURNING. They are read
+    // like the rows of a table holding those changed, under the name or alias of the one
+    // changed.
+    pub(super) fn run_returning(
+        &mut self,
+        statement: &Statement,
+    ) -> Result<QueryResults<SqlValue>, BackendError> {
+        let (table_name, as_clause, rows) = match statement {
+            Statement::InsertStatement(insert) => (&insert.table, None, self.insert_rows(insert)?),
+            Statement::UpdateStatement(update) => (
+                &update.table,
+                update.as_clause.as_ref(),
+                self.update_rows(update)?,
+            ),
+            Statement::DeleteStatement(delete) => (
+                &delete.table,
+                delete.as_clause.as_ref(),
+                self.delete_rows(delete)?,
+            ),
+            _ => {
+                return Err(BackendError::Internal(
+                    "Only INSERT, UPDATE and DELETE return rows.".to_string(),
+                ))
+            }
+        };
+        if statement.returning().is_empty() {
+            return Ok(QueryResults {
+                columns: vec![],
+                rows: vec![],
+            });
+        }
+
+        let name = as_clause.unwrap_or(table_name);
+        let table = match self.table(table_name) {
+            Some(table) => table,
+            None => {
+                return Err(BackendError::UndefinedTable(
+                    ERR_TABLE_DOES_NOT_EXIST.to_owned(),
+                ))
+            }
+        };
+        let changed = Table {
+            name: name.clone(),
+            columns: table.columns.clone(),
+            column_types: table.column_types.clone(),
+            rows: rows.into_iter().map(StoredRow::from).collect(),
+            indexes: vec![],
+            column_constraints: vec![],
+            checks: vec![],
+            schema_version: 0,
+            heap_bytes: 0,
+            dead_rows: 0,
+        };
+
+        let mut select = SelectStatement::new();
+        select.items = statement.returning().to_vec();
+        select.from.push(RowDataSource::Table {
+            table_name: name.clone(),
+            as_clause: None,
+            sample: None,
+            joins: vec![],
+        });
+        let shadowed = self.with_tables.drop_table(name)?;
+        self.with_tables.create_table(changed)?;
+        let results = self.select(select);
+        self.with_tables.drop_table(name)?;
+        if let Some(shadowed) = shadowed {
+            self.with_tables.create_table(shadowed)?;
+        }
+        results
+    }
+}
+
+// The rows of a query of a WITH as a table of its name, with the columns named as given
+pub(super) fn query_table(
+    name: &str,
+    columns: &[String],
+    results: QueryResults<SqlValue>,
+) -> Result<Table, BackendError> {
+    let mut table = Table::from(results);
+    if columns.len() > table.columns.len() {
+        return Err(BackendError::InvalidColumnReference(format!(
+            "WITH query \"{}\" has {} columns available but {} columns specified.",
+            name,
+            table.columns.len(),
+            columns.len()
+        )));
+    }
+    table.name = name.to_string();
+    for (column, alias) in table.columns.iter_mut().zip(columns) {
+        *column = alias.clone();
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod with_query_tests {
+    use crate::backend_memory::test_support::{backend, rows};
+
+    const SETUP: &str = "CREATE TABLE queue (id INT PRIMARY KEY, ts INT, body TEXT);
+        CREATE TABLE archive (id INT PRIMARY KEY, ts INT, body TEXT);
+        INSERT INTO queue VALUES (1, 1, 'first'), (2, 2, 'second'), (3, 3, 'third'),
+            (4, 4, 'fourth');";
+
+    #[test]
+    fn test_move_rows() {
+        let mut mb = backend(SETUP);
+        mb.eval_query(
+            "WITH moved AS (DELETE FROM queue WHERE ts < 3 RETURNING *) \
+            INSERT INTO archive SELECT * FROM moved;",
+        )
+        .unwrap();
+        assert_eq!(rows(&mut mb, "SELECT id FROM queue;"), vec!["3", "4"]);
+        assert_eq!(
+            rows(&mut mb, "SELECT * FROM archive;"),
+            vec!["1|1|first", "2|2|second"]
+        );
+
+        // The rows come back under the names given to the columns
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH moved (key, at) AS (DELETE FROM archive WHERE id = 1 RETURNING id, ts) \
+                SELECT key, at FROM moved;"
+            ),
+            vec!["1|1"]
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut mb = backend(SETUP);
+        // The statement reads the table as it was before the query of the WITH changed it
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH gone AS (DELETE FROM queue WHERE ts > 2) SELECT count(*) FROM queue;"
+            ),
+            vec!["4"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM queue;"), vec!["2"]);
+
+        // Run once however many times it is read
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH added AS (INSERT INTO archive VALUES (5, 5, 'fifth') RETURNING id) \
+                SELECT a.id, b.id FROM added a JOIN added b ON a.id = b.id;"
+            ),
+            vec!["5|5"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM archive;"), vec!["1"]);
+
+        // Queries that change nothing read what the others changed as it was before
+        assert_eq!(
+            rows(
+                &mut mb,
+                "WITH bumped AS (UPDATE queue SET ts = ts + 10 RETURNING ts), \
+                old AS (SELECT ts FROM queue) SELECT ts FROM old;"
+            ),
+            vec!["1", "2"]
+        );
+        assert_eq!(rows(&mut mb, "SELECT ts FROM queue;"), vec!["11", "12"]);
+    }
+
+    #[test]
+    fn test_with_errors() {
+        let mut mb = backend(SETUP);
+        // Either every change is made or none is
+        assert_eq!(
+            mb.eval_query(
+                "WITH moved AS (DELETE FROM queue RETURNING *) \
+                INSERT INTO missing SELECT * FROM moved;"
+            )
+            .unwrap_err()
+            .code(),
+            "42P01"
+        );
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM queue;"), vec!["4"]);
+
+        let tests = vec![
+            (
+                "WITH a AS (SELECT 1), a AS (SELECT 2) SELECT * FROM a;",
+                "42712",
+            ),
+            (
+                "WITH gone AS (DELETE FROM queue WHERE id = 1) DELETE FROM queue;",
+                "0A000",
+            ),
+            (
+                "WITH queue AS (DELETE FROM archive RETURNING *) DELETE FROM queue;",
+                "0A000",
+            ),
+            ("WITH a (x, y) AS (SELECT 1) SELECT * FROM a;", "42P10"),
+            ("WITH a AS (CREATE TABLE b (id INT)) SELECT 1;", "42601"),
+        ];
+        for (query, code) in tests {
+            assert_eq!(mb.eval_query(query).unwrap_err().code(), code, "{}", query);
+        }
+        assert_eq!(rows(&mut mb, "SELECT count(*) FROM queue;"), vec!["4"]);
+        // The tables of a WITH are gone after it
+        assert_eq!(
+            mb.eval_query("SELECT * FROM moved;").unwrap_err().code(),
+            "42P01"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+// Atom is the immutable text of identifier, string and numeric tokens. It is a single
+// pointer wide, which keeps `Token` at 16 bytes, and cloning it never copies the text.
+#[derive(Clone)]
+pub struct Atom(Arc<String>);
+
+impl Atom {
+    pub fn new(value: &str) -> Self {
+        Atom(Arc::new(value.to_owned()))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Deref for Atom {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Atom {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Atom {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Atom {
+    fn from(value: &str) -> Self {
+        Atom::new(value)
+    }
+}
+
+impl From<String> for Atom {
+    fn from(value: String) -> Self {
+        Atom(Arc::new(value))
+    }
+}
+
+impl From<Atom> for String {
+    fn from(atom: Atom) -> Self {
+        match Arc::try_unwrap(atom.0) {
+            Ok(value) => value,
+            Err(shared) => shared.as_ref().clone(),
+        }
+    }
+}
+
+impl PartialEq for Atom {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Atom {}
+
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Atom {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Atom {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+// Must agree with the `str` hash for `Borrow<str>` lookups
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl std::fmt::Debug for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl std::fmt::Display for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Interner hands out a shared Atom for every distinct text, so the identifiers that
+// repeat throughout a script are only allocated once.
+#[derive(Default)]
+pub struct Interner {
+    atoms: RefCell<HashSet<Atom>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&self, value: &str) -> Atom {
+        let mut atoms = self.atoms.borrow_mut();
+        if let Some(atom) = atoms.get(value) {
+            return atom.clone();
+        }
+        let atom = Atom::new(value);
+        atoms.insert(atom.clone());
+        atom
+    }
+
+    pub fn len(&self) -> usize {
+        self.atoms.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.atoms.borrow().is_empty()
+    }
+}
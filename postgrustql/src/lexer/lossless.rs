@@ -0,0 +1,223 @@
+use super::*;
+use crate::quote_identifier;
+
+// A token along with the whitespace and comments before it, for tools that rewrite SQL
+// and have to leave everything they don't rewrite as it was written
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PreservedToken {
+    // Whitespace and comments between the token before and this one
+    pub leading: String,
+    // The lexeme as written, quotes included, or what replaced it
+    pub text: String,
+    // Its span stays where the token was in the source, even once replaced
+    pub token: TokenContainer,
+}
+
+impl PreservedToken {
+    // Makes the token the identifier `name`, quoted if it has to be. Identifiers and
+    // the keywords that can be used as names can be replaced, whether this one was.
+    pub fn replace_identifier(&mut self, name: &str) -> bool {
+        let is_name = match &self.token.token {
+            Token::IdentifierValue { .. } => true,
+            token => token.is_non_reserved_keyword(),
+        };
+        if is_name {
+            self.text = quote_identifier(name);
+            self.token.token = Token::IdentifierValue {
+                value: Atom::from(name),
+            };
+        }
+        is_name
+    }
+}
+
+// The source as tokens, of which `render` gives back the text
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PreservedTokens {
+    pub tokens: Vec<PreservedToken>,
+    // Whitespace and comments after the last token
+    pub trailing: String,
+    // What stopped the lexer, the tokens then covering the source up to it
+    pub error: Option<LexingError>,
+}
+
+impl Lexer {
+    // Lexes `source` keeping the text between tokens, so that rendering the tokens gives
+    // the source back byte for byte, or the part of it before the first error
+    pub fn lex_preserving(&self, source: &str) -> PreservedTokens {
+        let (tokens, errors, end) = self.lex_tokens(source, false);
+        let mut last_end = 0;
+        let tokens = tokens
+            .into_iter()
+            .map(|token| {
+                let leading = source[last_end..token.span.start].to_string();
+                last_end = token.span.end;
+                PreservedToken {
+                    leading,
+                    text: token.text(source).to_string(),
+                    token,
+                }
+            })
+            .collect();
+        PreservedTokens {
+            tokens,
+            trailing: source[last_end..end].to_string(),
+            error: errors.into_iter().next(),
+        }
+    }
+}
+
+// The text of the tokens and what is between them, the source they were lexed from if
+// none was replaced
+pub fn render(tokens: &PreservedTokens) -> String {
+    let len = tokens
+        .tokens
+        .iter()
+        .map(|token| token.leading.len() + token.text.len())
+        .sum::<usize>()
+        + tokens.trailing.len();
+    let mut text = String::with_capacity(len);
+    for token in &tokens.tokens {
+        text.push_str(&token.leading);
+        text.push_str(&token.text);
+    }
+    text.push_str(&tokens.trailing);
+    text
+}
+
+#[cfg(test)]
+mod lossless_tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Checks the tokens render what they were lexed from, up to the error if there was
+    // one, with only whitespace and comments left out of them
+    fn check_round_trip(source: &str) -> PreservedTokens {
+        let lexer = Lexer::new();
+        let preserved = lexer.lex_preserving(source);
+        let text = render(&preserved);
+        assert!(
+            source.starts_with(&text),
+            "{:?} rendered {:?}",
+            source,
+            text
+        );
+        match &preserved.error {
+            None => assert_eq!(text, source),
+            Some(err) => assert_eq!(get_location_from_cursor(source, text.len()), err.loc()),
+        }
+        let (tokens, _) = lexer.lex_prefix(source);
+        assert_eq!(preserved.tokens.len(), tokens.len(), "{:?}", source);
+        let trivia = preserved.tokens.iter().map(|token| &token.leading);
+        for trivia in trivia.chain([&preserved.trailing]) {
+            assert_eq!(
+                lexer.lex(trivia),
+                Ok(vec![]),
+                "{:?} in {:?}",
+                trivia,
+                source
+            );
+        }
+        preserved
+    }
+
+    #[test]
+    fn test_lex_preserving_corpus() {
+        let corpus = [
+            "",
+            "  \n\t ",
+            "SELECT 1;",
+            "select  id,\n\tNAME from People -- trailing comment\n where id = $1 /* block */",
+            "SELECT \"Id\", \"odd\"\"name\" FROM t ORDER   BY x DESC, y;",
+            "SELECT * FROM t GROUP /* between */ BY a;\r\n\r\n",
+            "CREATE TABLE t (a DOUBLE\n PRECISION, b TEXT DEFAULT 'it''s');",
+            "SELECT E'a\\'b', $$dollar ; quoted$$, $tag$ x $tag$, 0x1F, 1_000, .5e-3;",
+            "INSERT INTO \"Ünïcode\" VALUES ('日本語'); -- done",
+            "SELECT a::INT, b !~* 'x', |/ 4, @ -2 FROM t WHERE c <> d AND e != f;",
+            "/* only a comment */",
+            // Invalid ones, which render up to their error
+            "SELECT 'unterminated",
+            "SELECT 1 /* unterminated comment",
+            "SELECT id FROM t WHERE name = ? AND 1;",
+            "SELECT 12abc FROM t;",
+        ];
+        for source in corpus {
+            check_round_trip(source);
+        }
+
+        let preserved = check_round_trip("SELECT a FROM t; -- the end\n");
+        assert_eq!(preserved.tokens[0].leading, "");
+        assert_eq!(preserved.tokens[1].leading, " ");
+        assert_eq!(preserved.tokens[1].text, "a");
+        assert_eq!(preserved.trailing, " -- the end\n");
+        let preserved = check_round_trip("SELECT 1 + 'oops");
+        assert_eq!(render(&preserved), "SELECT 1 + ");
+        assert_eq!(preserved.error.unwrap().error_code(), "unterminated_string");
+    }
+
+    #[test]
+    fn test_replace_identifier() {
+        let source = "SELECT people.name\nFROM people -- all of them\nWHERE key = 'people';";
+        let mut preserved = Lexer::new().lex_preserving(source);
+        for token in preserved.tokens.iter_mut() {
+            if let Token::IdentifierValue { value } = &token.token.token {
+                if value == "people" {
+                    assert!(token.replace_identifier("people_archive"));
+                }
+            }
+        }
+        assert_eq!(
+            render(&preserved),
+            "SELECT people_archive.name\nFROM people_archive -- all of them\nWHERE key = 'people';"
+        );
+
+        // Names that don't read back as they are get quoted, keywords that can be names
+        // can be replaced, other tokens can't
+        assert!(preserved.tokens[5].replace_identifier("Old People"));
+        assert!(preserved.tokens[7].replace_identifier("select"));
+        assert!(!preserved.tokens[0].replace_identifier("x"));
+        assert!(!preserved.tokens[9].replace_identifier("x"));
+        assert_eq!(
+            render(&preserved),
+            "SELECT people_archive.name\nFROM \"Old People\" -- all of them\nWHERE \"select\" \
+            = 'people';"
+        );
+    }
+
+    fn sql_like() -> impl Strategy<Value = String> {
+        let pieces = prop_oneof![
+            Just("SELECT"),
+            Just("FROM"),
+            Just("ORDER"),
+            Just("BY"),
+            Just("id"),
+            Just("\"Quoted\"\"\""),
+            Just("'str''ing'"),
+            Just("E'\\n'"),
+            Just("$$a;b$$"),
+            Just("12.5e3"),
+            Just("0b101"),
+            Just("-- comment\n"),
+            Just("/* block */"),
+            Just("::"),
+            Just("<="),
+            Just(";"),
+            Just("("),
+            Just(" "),
+            Just("\n"),
+            Just("é"),
+        ];
+        let piece = prop_oneof![
+            4 => pieces.prop_map(str::to_string),
+            1 => any::<char>().prop_map(String::from),
+        ];
+        prop::collection::vec(piece, 0..24).prop_map(|pieces| pieces.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn test_lex_preserving_round_trip(source in sql_like()) {
+            check_round_trip(&source);
+        }
+    }
+}
@@ -1,5 +1,17 @@
+mod atom;
+mod lossless;
+mod normalize;
+mod splitter;
+
+pub use atom::*;
+pub use lossless::*;
+pub use normalize::*;
+pub use splitter::*;
+
+use serde::{Deserialize, Serialize};
+// location of the token in source code, zero based; messages show it one based
 // location of the token in source code
-#[derive(Clone, Eq, PartialEq, Debug, Ord, PartialOrd)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Ord, PartialOrd, Default, Deserialize, Serialize)]
 pub struct TokenLocation {
     pub line: usize,
     pub col: usize,
@@ -12,6 +24,21 @@ impl TokenLocation {
     pub fn new_with_col_and_line(col: usize, line: usize) -> Self {
         TokenLocation { col, line }
     }
+
+    // This location, counted from the start of a text, counted from the start of a
+    // larger one the text begins at `origin` of instead
+    pub fn from_origin(self, origin: TokenLocation) -> Self {
+        match self.line {
+            0 => TokenLocation {
+                line: origin.line,
+                col: origin.col + self.col,
+            },
+            line => TokenLocation {
+                line: origin.line + line,
+                col: self.col,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -26,9 +53,19 @@ pub enum TokenKind {
 pub struct TokenContainer {
     pub token: Token,
     pub loc: TokenLocation,
+    pub span: Span,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+// Byte range of a token in the source, `end` being exclusive, and the location right
+// after its last character
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub end_loc: TokenLocation,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub struct Cursor {
     pub pointer: usize,
     pub loc: TokenLocation,
@@ -89,6 +126,58 @@ pub enum Token {
     Limit,
     Outer,
     Full,
+    With,
+    No,
+    Data,
+    Temporary,
+    Over,
+    Partition,
+    Rows,
+    Any,
+    All,
+    Group,
+    GroupBy,
+    Having,
+    Filter,
+    Default,
+    Check,
+    Set,
+    Show,
+    To,
+    If,
+    Exists,
+    Column,
+    Cascade,
+    Restrict,
+    Reindex,
+    Vacuum,
+    Explain,
+    Analyze,
+    Listen,
+    Notify,
+    Unlisten,
+    Reset,
+    Generated,
+    Always,
+    Stored,
+    Collate,
+    Lateral,
+    Tablesample,
+    Declare,
+    Cursor,
+    Close,
+    Fetch,
+    Next,
+    Forward,
+    For,
+    In,
+    Returning,
+    Similar,
+    Begin,
+    Commit,
+    Rollback,
+    Transaction,
+    Work,
 
     // Symbols
     Semicolon,
@@ -121,12 +210,18 @@ pub enum Token {
     BitwiseShiftRight,
     TypeCast,
     Dot,
+    // `~` lexes as BitwiseNot, the parser makes it RegexMatch where it follows an operand
+    RegexMatch,
+    RegexMatchInsensitive,
+    RegexNotMatch,
+    RegexNotMatchInsensitive,
 
     // Values
-    IdentifierValue { value: String },
-    StringValue { value: String },
-    NumericValue { value: String },
+    IdentifierValue { value: Atom },
+    StringValue { value: Atom },
+    NumericValue { value: Atom },
     BoolValue { value: bool },
+    Parameter { index: usize },
 
     // Default
     Empty,
@@ -135,84 +230,85 @@ pub enum Token {
     Comment,
 }
 
+// How a chain of binary operators of the same precedence groups
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Associativity {
+    Left,
+    // Chaining them is an error, as with comparisons
+    None,
+}
+
 impl Token {
+    // How tightly a binary operator binds its operands, 0 for tokens that aren't one. The
+    // levels follow the precedence table of Postgres' documentation, loosest first:
+    //   OR                                   left
+    //   AND                                  left
+    //   NOT                                  prefix, see prefix_binding_power
+    //   = <> < > <= >=                       not associative
+    //   IN, SIMILAR TO                       not associative
+    //   any other operator                   left, e.g. || & | # << >> ~ ~* !~ !~*
+    //   + -                                  left
+    //   * / %                                left
+    //   ^                                    left
+    //   COLLATE                              left
+    //   unary -                              prefix, see prefix_binding_power
+    //   ::                                   left
+    // Of Postgres' table this leaves out IS, BETWEEN, LIKE and AT TIME ZONE, which aren't
+    // parsed.
     pub fn binding_power(&self) -> u32 {
         match self {
-            Token::And => {
-                return 1;
-            }
-            Token::Or => {
-                return 1;
-            }
-
-            Token::Equal => {
-                return 2;
-            }
-            Token::NotEqual => {
-                return 2;
-            }
-
-            Token::LessThan => {
-                return 3;
-            }
-            Token::GreaterThan => {
-                return 3;
-            }
-
-            // For some reason these are grouped separately
-            Token::LessThanOrEqual => {
-                return 4;
-            }
-            Token::GreaterThanOrEqual => {
-                return 4;
-            }
-
-            Token::Plus => {
-                return 5;
-            }
-            Token::Minus => {
-                return 5;
-            }
-
-            Token::Concat => {
-                return 6;
-            }
-            Token::Asterisk => {
-                return 6;
-            }
-            Token::Slash => {
-                return 6;
-            }
-            Token::Modulo => {
-                return 6;
-            }
-            Token::Exponentiation => {
-                return 6;
-            }
-
-            // Unary ops
-            Token::SquareRoot => {
-                return 7;
-            }
-            Token::CubeRoot => {
-                return 7;
-            }
-            Token::Factorial => {
-                return 7;
-            }
-            Token::FactorialPrefix => {
-                return 7;
-            }
+            Token::Or => 1,
+            Token::And => 2,
+            Token::Equal
+            | Token::NotEqual
+            | Token::LessThan
+            | Token::GreaterThan
+            | Token::LessThanOrEqual
+            | Token::GreaterThanOrEqual => 4,
+            Token::In | Token::Similar => 5,
+            Token::Concat
+            | Token::BitwiseAnd
+            | Token::BitwiseOr
+            | Token::BitwiseXor
+            | Token::BitwiseShiftLeft
+            | Token::BitwiseShiftRight
+            | Token::RegexMatch
+            | Token::RegexMatchInsensitive
+            | Token::RegexNotMatch
+            | Token::RegexNotMatchInsensitive => 6,
+            Token::Plus | Token::Minus => 7,
+            Token::Asterisk | Token::Slash | Token::Modulo => 8,
+            Token::Exponentiation => 9,
+            Token::Collate => 10,
+            Token::TypeCast => 12,
+            _ => 0,
+        }
+    }
 
-            // Cast
-            Token::TypeCast => {
-                return 8;
-            }
+    // How tightly a prefix operator binds the operand after it, None for tokens that
+    // aren't one. The operand takes every operator binding at least as tightly, so
+    // `NOT a = b` is `NOT (a = b)` and `|/ 16 + 9` is `|/ (16 + 9)` like in Postgres,
+    // while `-2 ^ 2` is `(-2) ^ 2`.
+    pub fn prefix_binding_power(&self) -> Option<u32> {
+        match self {
+            Token::Not => Some(3),
+            Token::SquareRoot
+            | Token::CubeRoot
+            | Token::AbsoluteValue
+            | Token::FactorialPrefix
+            | Token::BitwiseNot => Some(6),
+            Token::Minus => Some(11),
+            _ => None,
+        }
+    }
 
-            _ => {
-                return 0;
-            }
-        };
+    // Whether `a op b op c` groups as `(a op b) op c`, or isn't allowed at all
+    pub fn associativity(&self) -> Associativity {
+        match self.binding_power() {
+            // Comparisons, IN and SIMILAR TO
+            4 | 5 => Associativity::None,
+            _ => Associativity::Left,
+        }
     }
 
     pub fn is_symbol(&self) -> bool {
@@ -246,7 +342,11 @@ impl Token {
             | Token::Exponentiation
             | Token::Modulo
             | Token::TypeCast
-            | Token::Dot => {
+            | Token::Dot
+            | Token::RegexMatch
+            | Token::RegexMatchInsensitive
+            | Token::RegexNotMatch
+            | Token::RegexNotMatchInsensitive => {
                 return true;
             }
             _ => {}
@@ -306,7 +406,59 @@ impl Token {
             | Token::By
             | Token::OrderBy
             | Token::Outer
-            | Token::Full => {
+            | Token::Full
+            | Token::With
+            | Token::No
+            | Token::Data
+            | Token::Temporary
+            | Token::Over
+            | Token::Partition
+            | Token::Rows
+            | Token::Any
+            | Token::All
+            | Token::Group
+            | Token::GroupBy
+            | Token::Having
+            | Token::Filter
+            | Token::Default
+            | Token::Check
+            | Token::Set
+            | Token::Show
+            | Token::To
+            | Token::If
+            | Token::Exists
+            | Token::Column
+            | Token::Cascade
+            | Token::Restrict
+            | Token::Reindex
+            | Token::Vacuum
+            | Token::Explain
+            | Token::Analyze
+            | Token::Listen
+            | Token::Notify
+            | Token::Unlisten
+            | Token::Reset
+            | Token::Generated
+            | Token::Always
+            | Token::Stored
+            | Token::Collate
+            | Token::Lateral
+            | Token::Tablesample
+            | Token::Declare
+            | Token::Cursor
+            | Token::Close
+            | Token::Fetch
+            | Token::Next
+            | Token::Forward
+            | Token::For
+            | Token::In
+            | Token::Returning
+            | Token::Similar
+            | Token::Begin
+            | Token::Commit
+            | Token::Rollback
+            | Token::Transaction
+            | Token::Work => {
                 return true;
             }
             _ => {}
@@ -329,22 +481,126 @@ impl Token {
             _ => false,
         }
     }
+
+    // Keywords that never start a clause, so that like Postgres' non-reserved keywords
+    // they still work as plain table and column names
+    pub fn is_non_reserved_keyword(&self) -> bool {
+        matches!(
+            self,
+            Token::Alter
+                | Token::Always
+                | Token::Begin
+                | Token::Bool
+                | Token::Cascade
+                | Token::Close
+                | Token::Commit
+                | Token::Cursor
+                | Token::Data
+                | Token::Declare
+                | Token::Delete
+                | Token::Exists
+                | Token::Explain
+                | Token::Filter
+                | Token::Forward
+                | Token::Generated
+                | Token::If
+                | Token::Index
+                | Token::Insert
+                | Token::Key
+                | Token::Listen
+                | Token::Next
+                | Token::No
+                | Token::Notify
+                | Token::Over
+                | Token::Partition
+                | Token::Reindex
+                | Token::Reset
+                | Token::Restrict
+                | Token::Rollback
+                | Token::Rows
+                | Token::Set
+                | Token::Show
+                | Token::Stored
+                | Token::Temporary
+                | Token::Text
+                | Token::To
+                | Token::Transaction
+                | Token::Unlisten
+                | Token::Update
+                | Token::Vacuum
+                | Token::Work
+        )
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum LexingError {
-    General { msg: String, loc: TokenLocation },
+    // A string, quoted identifier or dollar quoted string runs to the end of the input.
+    // `fragment` is the start of it, opening quote included.
+    UnterminatedString {
+        fragment: String,
+        loc: TokenLocation,
+    },
+    UnterminatedComment {
+        fragment: String,
+        loc: TokenLocation,
+    },
+    InvalidNumericLiteral {
+        fragment: String,
+        loc: TokenLocation,
+    },
+    UnknownCharacter {
+        ch: char,
+        loc: TokenLocation,
+    },
+}
+
+impl LexingError {
+    pub fn loc(&self) -> TokenLocation {
+        match self {
+            LexingError::UnterminatedString { loc, .. }
+            | LexingError::UnterminatedComment { loc, .. }
+            | LexingError::InvalidNumericLiteral { loc, .. }
+            | LexingError::UnknownCharacter { loc, .. } => *loc,
+        }
+    }
+
+    fn loc_mut(&mut self) -> &mut TokenLocation {
+        match self {
+            LexingError::UnterminatedString { loc, .. }
+            | LexingError::UnterminatedComment { loc, .. }
+            | LexingError::InvalidNumericLiteral { loc, .. }
+            | LexingError::UnknownCharacter { loc, .. } => loc,
+        }
+    }
+
+    // Stable identifier of the kind of error, unlike the message it never changes
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            LexingError::UnterminatedString { .. } => "unterminated_string",
+            LexingError::UnterminatedComment { .. } => "unterminated_comment",
+            LexingError::InvalidNumericLiteral { .. } => "invalid_numeric_literal",
+            LexingError::UnknownCharacter { .. } => "unknown_character",
+        }
+    }
 }
 
 impl std::fmt::Display for LexingError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                LexingError::General { msg, loc: _ } => msg.clone(),
+        let loc = self.loc();
+        match self {
+            LexingError::UnterminatedString { fragment, .. } => {
+                write!(f, "Unterminated quoted string {}", fragment)?
             }
-        )
+            LexingError::UnterminatedComment { fragment, .. } => {
+                write!(f, "Unterminated comment {}", fragment)?
+            }
+            LexingError::InvalidNumericLiteral { fragment, .. } => {
+                write!(f, "Invalid numeric literal {}", fragment)?
+            }
+            LexingError::UnknownCharacter { ch, .. } => write!(f, "Unable to lex token {:?}", ch)?,
+        }
+        write!(f, ", at {}:{}", loc.line + 1, loc.col + 1)
     }
 }
 
@@ -401,6 +657,59 @@ pub const OFFSET_KEYWORD: Keyword = "offset";
 pub const LIMIT_KEYWORD: Keyword = "limit";
 pub const OUTER_KEYWORD: Keyword = "outer";
 pub const FULL_KEYWORD: Keyword = "full";
+pub const WITH_KEYWORD: Keyword = "with";
+pub const NO_KEYWORD: Keyword = "no";
+pub const DATA_KEYWORD: Keyword = "data";
+pub const TEMP_KEYWORD: Keyword = "temp";
+pub const TEMPORARY_KEYWORD: Keyword = "temporary";
+pub const OVER_KEYWORD: Keyword = "over";
+pub const PARTITION_KEYWORD: Keyword = "partition";
+pub const ROWS_KEYWORD: Keyword = "rows";
+pub const ANY_KEYWORD: Keyword = "any";
+pub const SOME_KEYWORD: Keyword = "some";
+pub const ALL_KEYWORD: Keyword = "all";
+pub const GROUP_KEYWORD: Keyword = "group";
+pub const HAVING_KEYWORD: Keyword = "having";
+pub const FILTER_KEYWORD: Keyword = "filter";
+pub const DEFAULT_KEYWORD: Keyword = "default";
+pub const CHECK_KEYWORD: Keyword = "check";
+pub const SET_KEYWORD: Keyword = "set";
+pub const SHOW_KEYWORD: Keyword = "show";
+pub const TO_KEYWORD: Keyword = "to";
+pub const IF_KEYWORD: Keyword = "if";
+pub const EXISTS_KEYWORD: Keyword = "exists";
+pub const COLUMN_KEYWORD: Keyword = "column";
+pub const CASCADE_KEYWORD: Keyword = "cascade";
+pub const RESTRICT_KEYWORD: Keyword = "restrict";
+pub const REINDEX_KEYWORD: Keyword = "reindex";
+pub const VACUUM_KEYWORD: Keyword = "vacuum";
+pub const EXPLAIN_KEYWORD: Keyword = "explain";
+pub const ANALYZE_KEYWORD: Keyword = "analyze";
+pub const LISTEN_KEYWORD: Keyword = "listen";
+pub const NOTIFY_KEYWORD: Keyword = "notify";
+pub const UNLISTEN_KEYWORD: Keyword = "unlisten";
+pub const RESET_KEYWORD: Keyword = "reset";
+pub const GENERATED_KEYWORD: Keyword = "generated";
+pub const ALWAYS_KEYWORD: Keyword = "always";
+pub const STORED_KEYWORD: Keyword = "stored";
+pub const COLLATE_KEYWORD: Keyword = "collate";
+pub const LATERAL_KEYWORD: Keyword = "lateral";
+pub const TABLESAMPLE_KEYWORD: Keyword = "tablesample";
+pub const DECLARE_KEYWORD: Keyword = "declare";
+pub const CURSOR_KEYWORD: Keyword = "cursor";
+pub const CLOSE_KEYWORD: Keyword = "close";
+pub const FETCH_KEYWORD: Keyword = "fetch";
+pub const NEXT_KEYWORD: Keyword = "next";
+pub const FORWARD_KEYWORD: Keyword = "forward";
+pub const FOR_KEYWORD: Keyword = "for";
+pub const IN_KEYWORD: Keyword = "in";
+pub const RETURNING_KEYWORD: Keyword = "returning";
+pub const SIMILAR_KEYWORD: Keyword = "similar";
+pub const BEGIN_KEYWORD: Keyword = "begin";
+pub const COMMIT_KEYWORD: Keyword = "commit";
+pub const ROLLBACK_KEYWORD: Keyword = "rollback";
+pub const TRANSACTION_KEYWORD: Keyword = "transaction";
+pub const WORK_KEYWORD: Keyword = "work";
 // new
 pub const DECIMAL_KEYWORD: Keyword = "decimal";
 pub const NUMERIC_KEYWORD: Keyword = "numeric";
@@ -439,8 +748,30 @@ pub const BITWISE_SHIFT_LEFT_SYMBOL: Symbol = "<<";
 pub const BITWISE_SHIFT_RIGHT_SYMBOL: Symbol = ">>";
 pub const TYPE_CAST_SYMBOL: Symbol = "::";
 pub const DOT_SYMBOL: Symbol = ".";
+pub const REGEX_MATCH_INSENSITIVE_SYMBOL: Symbol = "~*";
+pub const REGEX_NOT_MATCH_SYMBOL: Symbol = "!~";
+pub const REGEX_NOT_MATCH_INSENSITIVE_SYMBOL: Symbol = "!~*";
 
 impl TokenContainer {
+    // Token read from the source between the two cursors
+    #[inline]
+    pub fn new(token: Token, start: Cursor, end: Cursor) -> Self {
+        TokenContainer {
+            token,
+            loc: start.loc,
+            span: Span {
+                start: start.pointer,
+                end: end.pointer,
+                end_loc: end.loc,
+            },
+        }
+    }
+
+    // The lexeme as written in `source`, quotes included
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
+
     #[inline]
     pub fn equals(&self, other: &Self) -> bool {
         self.token == other.token
@@ -448,88 +779,40 @@ impl TokenContainer {
 
     #[inline]
     pub fn binding_power(&self) -> u32 {
-        match self.token {
-            Token::And => {
-                return 1;
-            }
-            Token::Or => {
-                return 1;
-            }
-
-            Token::Equal => {
-                return 2;
-            }
-            Token::NotEqual => {
-                return 2;
-            }
-
-            Token::LessThan => {
-                return 3;
-            }
-            Token::GreaterThan => {
-                return 3;
-            }
-
-            // For some reason these are grouped separately
-            Token::LessThanOrEqual => {
-                return 4;
-            }
-            Token::GreaterThanOrEqual => {
-                return 4;
-            }
-
-            Token::Plus => {
-                return 5;
-            }
-            Token::Minus => {
-                return 5;
-            }
+        self.token.binding_power()
+    }
+}
 
-            Token::Concat => {
-                return 6;
-            }
-            Token::Asterisk => {
-                return 6;
-            }
-            Token::Slash => {
-                return 6;
-            }
-            Token::Modulo => {
-                return 6;
-            }
-            Token::Exponentiation => {
-                return 6;
-            }
+pub type LexerFn = fn(&Lexer, &str, Cursor) -> Option<(TokenContainer, Cursor)>;
 
-            // Unary ops
-            Token::SquareRoot => {
-                return 7;
-            }
-            Token::CubeRoot => {
-                return 7;
-            }
-            Token::Factorial => {
-                return 7;
-            }
-            Token::FactorialPrefix => {
-                return 7;
-            }
+// Session settings that change how a query lexes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct LexOptions {
+    // Off, backslashes in `'...'` strings start escapes like `\n` and `\'`, as they did
+    // before Postgres 9.1. On, they are characters like any other.
+    pub standard_conforming_strings: bool,
+}
 
-            _ => {
-                return 0;
-            }
-        };
+impl Default for LexOptions {
+    fn default() -> Self {
+        LexOptions {
+            standard_conforming_strings: true,
+        }
     }
 }
 
-pub type LexerFn = fn(&Lexer, &str, Cursor) -> Option<(TokenContainer, Cursor)>;
-
 pub struct Lexer {
     // Syntax that should be kept
     symbols: Vec<String>,
     keywords: Vec<String>,
     max_keyword_length: usize,
     max_symbol_length: usize,
+    // Identifiers tend to repeat a lot, share their text between tokens
+    interner: Interner,
+    options: LexOptions,
+    // Where the source starts in a larger text, like a statement of a script, for
+    // locations to point into that text. Spans stay within the source.
+    origin: TokenLocation,
 }
 
 impl Lexer {
@@ -566,6 +849,9 @@ impl Lexer {
             BITWISE_NOT_SYMBOL.to_string(),
             TYPE_CAST_SYMBOL.to_string(),
             DOT_SYMBOL.to_string(),
+            REGEX_MATCH_INSENSITIVE_SYMBOL.to_string(),
+            REGEX_NOT_MATCH_SYMBOL.to_string(),
+            REGEX_NOT_MATCH_INSENSITIVE_SYMBOL.to_string(),
         ];
         let keywords = vec![
             SELECT_KEYWORD.to_string(),
@@ -581,6 +867,7 @@ impl Lexer {
             AND_KEYWORD.to_string(),
             ORDER_KEYWORD.to_string(),
             OR_KEYWORD.to_string(),
+            NOT_KEYWORD.to_string(),
             DESC_KEYWORD.to_string(),
             ASC_KEYWORD.to_string(),
             AS_KEYWORD.to_string(),
@@ -592,6 +879,59 @@ impl Lexer {
             RIGHT_KEYWORD.to_string(),
             OUTER_KEYWORD.to_string(),
             FULL_KEYWORD.to_string(),
+            WITH_KEYWORD.to_string(),
+            NO_KEYWORD.to_string(),
+            DATA_KEYWORD.to_string(),
+            TEMP_KEYWORD.to_string(),
+            TEMPORARY_KEYWORD.to_string(),
+            OVER_KEYWORD.to_string(),
+            PARTITION_KEYWORD.to_string(),
+            ROWS_KEYWORD.to_string(),
+            ANY_KEYWORD.to_string(),
+            SOME_KEYWORD.to_string(),
+            ALL_KEYWORD.to_string(),
+            GROUP_KEYWORD.to_string(),
+            HAVING_KEYWORD.to_string(),
+            FILTER_KEYWORD.to_string(),
+            DEFAULT_KEYWORD.to_string(),
+            CHECK_KEYWORD.to_string(),
+            SET_KEYWORD.to_string(),
+            SHOW_KEYWORD.to_string(),
+            TO_KEYWORD.to_string(),
+            IF_KEYWORD.to_string(),
+            EXISTS_KEYWORD.to_string(),
+            COLUMN_KEYWORD.to_string(),
+            CASCADE_KEYWORD.to_string(),
+            RESTRICT_KEYWORD.to_string(),
+            REINDEX_KEYWORD.to_string(),
+            VACUUM_KEYWORD.to_string(),
+            EXPLAIN_KEYWORD.to_string(),
+            ANALYZE_KEYWORD.to_string(),
+            LISTEN_KEYWORD.to_string(),
+            NOTIFY_KEYWORD.to_string(),
+            UNLISTEN_KEYWORD.to_string(),
+            RESET_KEYWORD.to_string(),
+            GENERATED_KEYWORD.to_string(),
+            ALWAYS_KEYWORD.to_string(),
+            STORED_KEYWORD.to_string(),
+            COLLATE_KEYWORD.to_string(),
+            LATERAL_KEYWORD.to_string(),
+            TABLESAMPLE_KEYWORD.to_string(),
+            DECLARE_KEYWORD.to_string(),
+            CURSOR_KEYWORD.to_string(),
+            CLOSE_KEYWORD.to_string(),
+            FETCH_KEYWORD.to_string(),
+            NEXT_KEYWORD.to_string(),
+            FORWARD_KEYWORD.to_string(),
+            FOR_KEYWORD.to_string(),
+            IN_KEYWORD.to_string(),
+            RETURNING_KEYWORD.to_string(),
+            SIMILAR_KEYWORD.to_string(),
+            BEGIN_KEYWORD.to_string(),
+            COMMIT_KEYWORD.to_string(),
+            ROLLBACK_KEYWORD.to_string(),
+            TRANSACTION_KEYWORD.to_string(),
+            WORK_KEYWORD.to_string(),
             IS_KEYWORD.to_string(),
             LIMIT_KEYWORD.to_string(),
             OFFSET_KEYWORD.to_string(),
@@ -645,9 +985,24 @@ impl Lexer {
             keywords,
             max_symbol_length,
             max_keyword_length,
+            interner: Interner::new(),
+            options: LexOptions::default(),
+            origin: TokenLocation::new(),
+        }
+    }
+
+    pub fn with_options(options: LexOptions) -> Self {
+        Lexer {
+            options,
+            ..Lexer::new()
         }
     }
 
+    // The same lexer, for a source that starts at `origin` of the text it was taken from
+    pub fn at_origin(self, origin: TokenLocation) -> Self {
+        Lexer { origin, ..self }
+    }
+
     // lex splits an input string into a list of tokens. This process
     // can be divided into following tasks:
     //
@@ -658,28 +1013,66 @@ impl Lexer {
     // 3. If any of the lexer generate a token then add the token to the
     // token slice, update the cursor and restart the process from the new
     pub fn lex(&self, source: &str) -> Result<Vec<TokenContainer>, LexingError> {
+        match self.lex_prefix(source) {
+            (tokens, None) => Ok(tokens),
+            (_, Some(err)) => Err(err),
+        }
+    }
+
+    // Like `lex`, but also returns the tokens read before an error
+    pub fn lex_prefix(&self, source: &str) -> (Vec<TokenContainer>, Option<LexingError>) {
+        let (tokens, errors, _) = self.lex_tokens(source, false);
+        (tokens, errors.into_iter().next())
+    }
+
+    // Keeps lexing past errors so that all of them can be reported at once. After an
+    // error the lexer skips to the next whitespace or symbol and carries on from there,
+    // the returned tokens are only meant for reporting.
+    pub fn lex_recovering(&self, source: &str) -> (Vec<TokenContainer>, Vec<LexingError>) {
+        let (tokens, errors, _) = self.lex_tokens(source, true);
+        (tokens, errors)
+    }
+
+    // The tokens, the errors, and the position lexing stopped at, the end of the source
+    // unless an error stopped it
+    fn lex_tokens(
+        &self,
+        source: &str,
+        recover: bool,
+    ) -> (Vec<TokenContainer>, Vec<LexingError>, usize) {
         let mut tokens = Vec::with_capacity(100);
+        let mut errors = Vec::new();
         let mut cur: Cursor = Cursor {
             pointer: 0,
             loc: TokenLocation { line: 0, col: 0 },
         };
 
         'lex: while cur.pointer < source.len() {
-            if let Some((token, new_cursor)) = self.lex_keyword(source, cur.clone()) {
+            if let Some((token, new_cursor)) = self.lex_keyword(source, cur) {
                 cur = new_cursor;
 
                 // Omit empty tokens for valid, but empty syntax like newlines
                 if token.token != Token::Empty {
                     if let Some(TokenContainer {
-                        token: Token::Order,
+                        token: previous @ (Token::Order | Token::Group),
                         loc,
+                        span,
                     }) = tokens.last()
                     {
                         if token.token == Token::By {
                             let token_cur = tokens.len() - 1;
                             tokens[token_cur] = TokenContainer {
-                                token: Token::OrderBy,
-                                loc: loc.clone(),
+                                token: if previous == &Token::Order {
+                                    Token::OrderBy
+                                } else {
+                                    Token::GroupBy
+                                },
+                                loc: *loc,
+                                span: Span {
+                                    end: token.span.end,
+                                    end_loc: token.span.end_loc,
+                                    ..*span
+                                },
                             };
                             continue 'lex;
                         }
@@ -687,13 +1080,19 @@ impl Lexer {
                     if let Some(TokenContainer {
                         token: Token::Double,
                         loc,
+                        span,
                     }) = tokens.last()
                     {
                         if token.token == Token::Precision {
                             let token_cur = tokens.len() - 1;
                             tokens[token_cur] = TokenContainer {
                                 token: Token::DoublePrecision,
-                                loc: loc.clone(),
+                                loc: *loc,
+                                span: Span {
+                                    end: token.span.end,
+                                    end_loc: token.span.end_loc,
+                                    ..*span
+                                },
                             };
                             continue 'lex;
                         }
@@ -702,10 +1101,21 @@ impl Lexer {
                     tokens.push(token);
                 }
                 continue 'lex;
-            } else if let Some((_, new_cursor)) = self.lex_comment(source, cur.clone()) {
+            } else if let Some((_, new_cursor)) = self.lex_comment(source, cur) {
+                let text = &source[cur.pointer..new_cursor.pointer];
+                if text.starts_with("/*") && !(text.len() >= 4 && text.ends_with("*/")) {
+                    let error = LexingError::UnterminatedComment {
+                        fragment: error_fragment(text),
+                        loc: get_location_from_cursor(source, cur.pointer),
+                    };
+                    errors.push(error);
+                    if !recover {
+                        break 'lex;
+                    }
+                }
                 cur = new_cursor;
                 continue 'lex;
-            } else if let Some((token, new_cursor)) = self.lex_symbol(source, cur.clone()) {
+            } else if let Some((token, new_cursor)) = self.lex_symbol(source, cur) {
                 cur = new_cursor;
 
                 // Omit empty tokens for valid, but empty syntax like newlines
@@ -713,7 +1123,7 @@ impl Lexer {
                     tokens.push(token);
                 }
                 continue 'lex;
-            } else if let Some((token, new_cursor)) = self.lex_numeric(source, cur.clone()) {
+            } else if let Some((token, new_cursor)) = self.lex_numeric(source, cur) {
                 cur = new_cursor;
 
                 // Omit empty tokens for valid, but empty syntax like newlines
@@ -721,7 +1131,7 @@ impl Lexer {
                     tokens.push(token);
                 }
                 continue 'lex;
-            } else if let Some((token, new_cursor)) = self.lex_identifier(source, cur.clone()) {
+            } else if let Some((token, new_cursor)) = self.lex_identifier(source, cur) {
                 cur = new_cursor;
 
                 // Omit empty tokens for valid, but empty syntax like newlines
@@ -729,7 +1139,7 @@ impl Lexer {
                     tokens.push(token);
                 }
                 continue 'lex;
-            } else if let Some((token, new_cursor)) = self.lex_string(source, cur.clone()) {
+            } else if let Some((token, new_cursor)) = self.lex_string(source, cur) {
                 cur = new_cursor;
 
                 // Omit empty tokens for valid, but empty syntax like newlines
@@ -737,70 +1147,127 @@ impl Lexer {
                     tokens.push(token);
                 }
                 continue 'lex;
+            } else if let Some((token, new_cursor)) = self.lex_parameter(source, cur) {
+                cur = new_cursor;
+                tokens.push(token);
+                continue 'lex;
+            } else if let Some((token, new_cursor)) = self.lex_dollar_quoted(source, cur) {
+                cur = new_cursor;
+                tokens.push(token);
+                continue 'lex;
             }
-            let mut hint = "".to_owned();
-
-            if tokens.len() > 0 {
-                hint = "after ".to_owned();
-                hint.push_str(format!("{:?}", &tokens[tokens.len() - 1].token).as_str());
+            let (error, skip) = self.lexing_error(source, cur.pointer);
+            errors.push(error);
+            if !recover {
+                break 'lex;
+            }
+            cur.pointer += skip;
+            cur.loc = get_location_from_cursor(source, cur.pointer);
+        }
+        if self.origin != TokenLocation::new() {
+            for token in tokens.iter_mut() {
+                token.loc = token.loc.from_origin(self.origin);
+                token.span.end_loc = token.span.end_loc.from_origin(self.origin);
+            }
+            for error in errors.iter_mut() {
+                let loc = error.loc_mut();
+                *loc = loc.from_origin(self.origin);
             }
-            let loc = get_location_from_cursor(source, cur.pointer);
-            let error = LexingError::General {
-                msg: format!("Unable to lex token {}, at {}:{}", hint, loc.line, loc.col),
+        }
+        (tokens, errors, cur.pointer.min(source.len()))
+    }
+
+    // Works out why nothing could be lexed at `pointer`, and how many bytes to skip to
+    // get past it
+    fn lexing_error(&self, source: &str, pointer: usize) -> (LexingError, usize) {
+        let loc = get_location_from_cursor(source, pointer);
+        let rest = &source[pointer..];
+        let ch = rest.chars().next().unwrap_or_default();
+        if ch == '\'' || ch == '"' || dollar_quote_tag(source, pointer).is_some() {
+            // Without its end the string runs until the end of the source
+            let error = LexingError::UnterminatedString {
+                fragment: error_fragment(rest),
+                loc,
+            };
+            (error, rest.len())
+        } else if is_char_digit(ch) || (ch == '.' && rest[1..].starts_with(is_char_digit)) {
+            let mut prev = ch;
+            let len = rest
+                .find(|c: char| {
+                    let in_literal = c.is_ascii_alphanumeric()
+                        || c == '.'
+                        || c == '_'
+                        || ((c == '+' || c == '-') && prev == 'e');
+                    prev = c;
+                    !in_literal
+                })
+                .unwrap_or(rest.len());
+            let error = LexingError::InvalidNumericLiteral {
+                fragment: error_fragment(&rest[..len]),
                 loc,
             };
-            return Err(error);
+            (error, len)
+        } else {
+            // A run of unknown characters is reported once
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| {
+                    c.is_whitespace()
+                        || *c == '\''
+                        || *c == '"'
+                        || self.symbols.iter().any(|symbol| symbol.starts_with(*c))
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            (LexingError::UnknownCharacter { ch, loc }, len)
         }
-        Ok(tokens)
     }
 
     pub fn lex_comment(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
-        let mut cur = ic.clone();
+        let mut cur = ic;
         if source[cur.pointer..].starts_with("/*") {
             cur.pointer += 2;
+            cur.loc.col += 2;
             let mut char_iter = source[cur.pointer..].chars().peekable();
             while let Some(c) = char_iter.next() {
-                cur.pointer += 1;
+                cur.pointer += c.len_utf8();
                 if c == '\n' {
                     cur.loc.col = 0;
                     cur.loc.line += 1;
                 } else if c == '*' && char_iter.peek() == Some(&'/') {
-                    cur.pointer += 2;
+                    cur.pointer += 1;
+                    cur.loc.col += 2;
                     break;
+                } else {
+                    cur.loc.col += 1;
                 }
             }
-            return Some((
-                TokenContainer {
-                    token: Token::Comment,
-                    loc: ic.loc,
-                },
-                cur,
-            ));
+            return Some((TokenContainer::new(Token::Comment, ic, cur), cur));
         }
         if source[cur.pointer..].starts_with("--") {
             cur.pointer += 2;
-            let mut char_iter = source[cur.pointer..].chars().peekable();
-            while let Some(c) = char_iter.next() {
-                cur.pointer += 1;
+            cur.loc.col += 2;
+            let char_iter = source[cur.pointer..].chars();
+            for c in char_iter {
+                cur.pointer += c.len_utf8();
                 if c == '\n' {
                     cur.loc.col = 0;
                     cur.loc.line += 1;
                     break;
                 }
+                cur.loc.col += 1;
             }
-            return Some((
-                TokenContainer {
-                    token: Token::Comment,
-                    loc: ic.loc,
-                },
-                cur,
-            ));
+            return Some((TokenContainer::new(Token::Comment, ic, cur), cur));
         }
         None
     }
 
     pub fn lex_numeric(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
-        let mut cur = ic.clone();
+        if let Some(radix) = non_decimal_radix(&source[ic.pointer..]) {
+            return lex_non_decimal(source, ic, radix);
+        }
+
+        let mut cur = ic;
 
         let mut period_found = false;
         let mut exp_marker_found = false;
@@ -808,8 +1275,6 @@ impl Lexer {
         let mut char_iter = source[cur.pointer..].chars().peekable();
 
         while let Some(c) = char_iter.next() {
-            cur.loc.col += 1;
-
             let is_digit = is_char_digit(c);
             let is_period = c == '.';
             let is_exp_marker = c == 'e';
@@ -825,6 +1290,18 @@ impl Lexer {
                 continue;
             }
 
+            // Underscores separate digits, never anything else
+            if c == '_' {
+                let prev = source.as_bytes()[cur.pointer - 1] as char;
+                let next_is_digit = char_iter.peek().is_some_and(|c| is_char_digit(*c));
+                if !is_char_digit(prev) || !next_is_digit {
+                    return None;
+                }
+
+                cur.pointer += 1;
+                continue;
+            }
+
             if is_period {
                 if period_found {
                     return None;
@@ -857,7 +1334,6 @@ impl Lexer {
                 };
                 if c_next == '-' || c_next == '+' {
                     cur.pointer += 1;
-                    cur.loc.col += 1;
                     char_iter.next();
                 }
 
@@ -875,13 +1351,15 @@ impl Lexer {
         if cur.pointer == ic.pointer {
             return None;
         }
+        // Numerics are all ASCII
+        cur.loc.col += cur.pointer - ic.pointer;
+        let literal = &source[ic.pointer..cur.pointer];
+        let value = match literal.contains('_') {
+            true => Atom::from(literal.replace('_', "")),
+            false => Atom::from(literal),
+        };
         Some((
-            TokenContainer {
-                loc: ic.loc,
-                token: Token::NumericValue {
-                    value: source[ic.pointer..cur.pointer].to_owned(),
-                },
-            },
+            TokenContainer::new(Token::NumericValue { value }, ic, cur),
             cur,
         ))
     }
@@ -896,7 +1374,7 @@ impl Lexer {
         delimiter: char,
         kind: TokenKind,
     ) -> Option<(TokenContainer, Cursor)> {
-        let mut cur = ic.clone();
+        let mut cur = ic;
 
         if source[cur.pointer..].len() == 0 {
             return None;
@@ -920,87 +1398,230 @@ impl Lexer {
 
         let mut char_iter = source[cur.pointer..].chars().peekable();
 
+        // The cursor moves past every character as it is read, the second of an escaped
+        // pair of delimiters included
         while let Some(c) = char_iter.next() {
-            if c == delimiter {
-                cur.pointer += 1;
+            cur.pointer += c.len_utf8();
+            if c == '\n' {
+                cur.loc.line += 1;
+                cur.loc.col = 0;
+            } else {
                 cur.loc.col += 1;
+            }
+
+            if c == delimiter {
                 // SQL escapes are via double characters, not backslash.
-                match char_iter.peek() {
-                    None => {
-                        return Some((
-                            TokenContainer {
-                                loc: ic.loc,
-                                token: if kind == TokenKind::String {
-                                    Token::StringValue { value }
-                                } else {
-                                    Token::IdentifierValue { value }
-                                },
-                            },
-                            cur,
-                        ));
-                    }
-                    Some(char) => {
-                        if *char != delimiter {
-                            return Some((
-                                TokenContainer {
-                                    loc: ic.loc,
-                                    token: if kind == TokenKind::String {
-                                        Token::StringValue { value }
-                                    } else {
-                                        Token::IdentifierValue { value }
-                                    },
-                                },
-                                cur,
-                            ));
-                        } else if *char == delimiter {
-                            char_iter.next();
-                        } else {
-                            value.push(delimiter);
+                if char_iter.peek() != Some(&delimiter) {
+                    let token = if kind == TokenKind::String {
+                        Token::StringValue {
+                            value: value.into(),
                         }
-                    }
+                    } else {
+                        Token::IdentifierValue {
+                            value: self.interner.intern(&value),
+                        }
+                    };
+                    return Some((TokenContainer::new(token, ic, cur), cur));
                 }
+                char_iter.next();
+                cur.pointer += delimiter.len_utf8();
+                cur.loc.col += 1;
             }
 
             value.push(c);
-            cur.loc.col += 1;
-            cur.pointer += 1;
         }
 
+        // Without a closing delimiter, `lexing_error` reports it from the opening one
         None
     }
 
     pub fn lex_string(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
+        if !self.options.standard_conforming_strings {
+            return self.lex_escaped_string(source, ic);
+        }
         return self.lex_character_delimited(source, ic, '\'', TokenKind::String);
     }
 
+    // Lexes a `'...'` string in which a backslash escapes the characters after it, as
+    // Postgres does without standard_conforming_strings. `\b`, `\f`, `\n`, `\r` and `\t`
+    // are the control characters, `\o` to `\ooo` and `\xh` or `\xhh` give a character by
+    // its octal or hexadecimal code, `\uXXXX` and `\UXXXXXXXX` by its Unicode code point,
+    // and any other character after a backslash is taken as it is.
+    fn lex_escaped_string(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
+        if !source[ic.pointer..].starts_with('\'') {
+            return None;
+        }
+        let mut value = String::new();
+        let mut chars = source[ic.pointer + 1..].char_indices().peekable();
+        let end = loop {
+            let (at, c) = chars.next()?;
+            match c {
+                '\'' if chars.peek().map(|(_, c)| *c) == Some('\'') => {
+                    chars.next();
+                    value.push('\'');
+                }
+                '\'' => break ic.pointer + 1 + at + 1,
+                '\\' => {
+                    let (_, escaped) = chars.next()?;
+                    let (radix, max_digits) = match escaped {
+                        '0'..='7' => (8, 3),
+                        'x' => (16, 2),
+                        'u' => (16, 4),
+                        'U' => (16, 8),
+                        'b' => {
+                            value.push('\u{8}');
+                            continue;
+                        }
+                        'f' => {
+                            value.push('\u{c}');
+                            continue;
+                        }
+                        'n' => {
+                            value.push('\n');
+                            continue;
+                        }
+                        'r' => {
+                            value.push('\r');
+                            continue;
+                        }
+                        't' => {
+                            value.push('\t');
+                            continue;
+                        }
+                        _ => {
+                            value.push(escaped);
+                            continue;
+                        }
+                    };
+                    let mut digits = String::new();
+                    if radix == 8 {
+                        digits.push(escaped);
+                    }
+                    while digits.len() < max_digits {
+                        match chars.peek() {
+                            Some((_, c)) if c.is_digit(radix) => {
+                                digits.push(*c);
+                                chars.next();
+                            }
+                            _ => break,
+                        }
+                    }
+                    // Without digits after it, all of those of a code point, or with a code
+                    // that is no character, the letter is taken as it is
+                    let code = u32::from_str_radix(&digits, radix).ok();
+                    let c = match escaped {
+                        'u' | 'U' if digits.len() == max_digits => code.and_then(char::from_u32),
+                        'u' | 'U' => None,
+                        // Codes of single bytes, of which the lowest is kept
+                        _ => code.map(|code| char::from((code & 0xff) as u8)),
+                    };
+                    match c {
+                        Some(c) => value.push(c),
+                        None => {
+                            value.push(escaped);
+                            value.push_str(&digits);
+                        }
+                    }
+                }
+                _ => value.push(c),
+            }
+        };
+
+        let mut cur = ic;
+        cur.pointer = end;
+        for c in source[ic.pointer..end].chars() {
+            if c == '\n' {
+                cur.loc.line += 1;
+                cur.loc.col = 0;
+            } else {
+                cur.loc.col += 1;
+            }
+        }
+        let token = Token::StringValue {
+            value: value.into(),
+        };
+        Some((TokenContainer::new(token, ic, cur), cur))
+    }
+
+    // lex_parameter lexes positional parameters of prepared statements, `$1`, `$2` and so on
+    pub fn lex_parameter(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
+        if !source[ic.pointer..].starts_with('$') {
+            return None;
+        }
+        let digits = source[ic.pointer + 1..]
+            .find(|c| !is_char_digit(c))
+            .unwrap_or(source.len() - ic.pointer - 1);
+        if digits == 0 {
+            return None;
+        }
+        let index = source[ic.pointer + 1..ic.pointer + 1 + digits]
+            .parse::<usize>()
+            .ok()?;
+
+        let mut cur = ic;
+        cur.pointer += 1 + digits;
+        cur.loc.col += 1 + digits;
+        Some((
+            TokenContainer::new(Token::Parameter { index }, ic, cur),
+            cur,
+        ))
+    }
+
+    // lex_dollar_quoted lexes PostgreSQL style dollar quoted strings, `$$text$$` or
+    // `$tag$text$tag$`. Their content is taken verbatim, no escapes apply.
+    pub fn lex_dollar_quoted(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
+        let tag = dollar_quote_tag(source, ic.pointer)?;
+        let content_start = ic.pointer + tag.len();
+        let content_len = source[content_start..].find(tag)?;
+        let value = &source[content_start..content_start + content_len];
+
+        let mut cur = ic;
+        cur.pointer = content_start + content_len + tag.len();
+        for c in source[ic.pointer..cur.pointer].chars() {
+            if c == '\n' {
+                cur.loc.line += 1;
+                cur.loc.col = 0;
+            } else {
+                cur.loc.col += 1;
+            }
+        }
+
+        let value = Atom::from(value);
+        Some((
+            TokenContainer::new(Token::StringValue { value }, ic, cur),
+            cur,
+        ))
+    }
+
     // longestMatch iterates through a source string starting at the given
     // cursor to find the longest matching substring among the provided
     // options
-    pub fn longest_match(
+    pub fn longest_match<'o>(
         &self,
         source: &str,
         ic: Cursor,
-        options: &Vec<String>,
+        options: &'o [String],
         max_length: Option<usize>,
-    ) -> String {
-        let mut text_match: String = "".to_string();
-        let cur = ic.clone();
+    ) -> &'o str {
+        let mut text_match = "";
 
-        let rest_of_text = if let Some(mut max_length) = max_length {
-            if cur.pointer + max_length > source.len() {
-                max_length = source.len() - cur.pointer;
+        // Options are lowercase ASCII, compare bytes rather than lowercasing the source
+        let mut rest_of_text = &source.as_bytes()[ic.pointer..];
+        if let Some(max_length) = max_length {
+            if max_length < rest_of_text.len() {
+                rest_of_text = &rest_of_text[..max_length];
             }
-            source[cur.pointer..(cur.pointer + max_length)].to_lowercase()
-        } else {
-            source[cur.pointer..].to_lowercase()
-        };
+        }
 
         for option in options {
-            if option.len() > text_match.len() && rest_of_text.starts_with(option) {
-                text_match = option.to_string();
+            if option.len() > text_match.len()
+                && rest_of_text.len() >= option.len()
+                && rest_of_text[..option.len()].eq_ignore_ascii_case(option.as_bytes())
+            {
+                text_match = option;
             }
         }
-        return text_match;
+        text_match
     }
 
     pub fn lex_symbol(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
@@ -1010,7 +1631,7 @@ impl Lexer {
             }
             Some(value) => value,
         };
-        let mut cur = ic.clone();
+        let mut cur = ic;
 
         // Will get overwritten later if not an ignored syntax
         cur.pointer += 1;
@@ -1024,30 +1645,20 @@ impl Lexer {
 
         match c {
             ' ' | '\n' | '\r' | '\t' => {
-                return Some((
-                    TokenContainer {
-                        token: Token::Empty,
-                        loc: TokenLocation { line: 0, col: 0 },
-                    },
-                    cur,
-                ));
+                return Some((TokenContainer::new(Token::Empty, ic, cur), cur));
             }
             _ => {}
         }
 
         // Use `ic`, not `cur`
-        let symbol_match = self.longest_match(
-            source,
-            ic.clone(),
-            &self.symbols,
-            Some(self.max_symbol_length),
-        );
+        let symbol_match =
+            self.longest_match(source, ic, &self.symbols, Some(self.max_symbol_length));
         // Unknown character
         if symbol_match == "" {
             return None;
         }
         // != is rewritten as <>: https://www.postgresql.org/docs/9.5/functions-comparison.html
-        let kind = match symbol_match.as_str() {
+        let kind = match symbol_match {
             COMMA_SYMBOL => Token::Comma,
             EQUAL_SYMBOL => Token::Equal,
             NOT_EQUAL_SYMBOL | NOT_EQUAL_SYMBOL_2 => Token::NotEqual,
@@ -1078,6 +1689,9 @@ impl Lexer {
             SEMICOLON_SYMBOL => Token::Semicolon,
             DOT_SYMBOL => Token::Dot,
             CONCAT_SYMBOL => Token::Concat,
+            REGEX_MATCH_INSENSITIVE_SYMBOL => Token::RegexMatchInsensitive,
+            REGEX_NOT_MATCH_SYMBOL => Token::RegexNotMatch,
+            REGEX_NOT_MATCH_INSENSITIVE_SYMBOL => Token::RegexNotMatchInsensitive,
             _ => {
                 return None;
             }
@@ -1086,37 +1700,27 @@ impl Lexer {
         cur.pointer = ic.pointer + symbol_match.len();
         cur.loc.col = ic.loc.col + symbol_match.len();
 
-        Some((
-            TokenContainer {
-                loc: ic.loc,
-                token: kind,
-            },
-            cur,
-        ))
+        Some((TokenContainer::new(kind, ic, cur), cur))
     }
 
     pub fn lex_keyword(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
-        let mut cur = ic.clone();
+        let mut cur = ic;
 
-        let keyword_match = self.longest_match(
-            source,
-            ic.clone(),
-            &self.keywords,
-            Some(self.max_keyword_length),
-        );
+        let keyword_match =
+            self.longest_match(source, ic, &self.keywords, Some(self.max_keyword_length));
         if keyword_match == "" {
             return None;
         }
         cur.pointer = ic.pointer + keyword_match.len();
         cur.loc.col = ic.loc.col + keyword_match.len();
         // Check if the word continues, thus being an identifier
-        if let Some(next_char) = source.chars().nth(cur.pointer) {
+        if let Some(next_char) = get_chat_at(source, cur.pointer) {
             if is_char_valid_for_identifier(next_char) {
                 return None;
             }
         }
 
-        let mut kind = match keyword_match.as_str() {
+        let mut kind = match keyword_match {
             SELECT_KEYWORD => Token::Select,
             FROM_KEYWORD => Token::From,
             WHERE_KEYWORD => Token::Where,
@@ -1136,6 +1740,57 @@ impl Lexer {
             RIGHT_KEYWORD => Token::Right,
             OUTER_KEYWORD => Token::Outer,
             FULL_KEYWORD => Token::Full,
+            WITH_KEYWORD => Token::With,
+            NO_KEYWORD => Token::No,
+            DATA_KEYWORD => Token::Data,
+            TEMP_KEYWORD | TEMPORARY_KEYWORD => Token::Temporary,
+            OVER_KEYWORD => Token::Over,
+            PARTITION_KEYWORD => Token::Partition,
+            ROWS_KEYWORD => Token::Rows,
+            ANY_KEYWORD | SOME_KEYWORD => Token::Any,
+            ALL_KEYWORD => Token::All,
+            GROUP_KEYWORD => Token::Group,
+            HAVING_KEYWORD => Token::Having,
+            FILTER_KEYWORD => Token::Filter,
+            DEFAULT_KEYWORD => Token::Default,
+            CHECK_KEYWORD => Token::Check,
+            SET_KEYWORD => Token::Set,
+            SHOW_KEYWORD => Token::Show,
+            TO_KEYWORD => Token::To,
+            IF_KEYWORD => Token::If,
+            EXISTS_KEYWORD => Token::Exists,
+            COLUMN_KEYWORD => Token::Column,
+            CASCADE_KEYWORD => Token::Cascade,
+            RESTRICT_KEYWORD => Token::Restrict,
+            REINDEX_KEYWORD => Token::Reindex,
+            VACUUM_KEYWORD => Token::Vacuum,
+            EXPLAIN_KEYWORD => Token::Explain,
+            ANALYZE_KEYWORD => Token::Analyze,
+            LISTEN_KEYWORD => Token::Listen,
+            NOTIFY_KEYWORD => Token::Notify,
+            UNLISTEN_KEYWORD => Token::Unlisten,
+            RESET_KEYWORD => Token::Reset,
+            GENERATED_KEYWORD => Token::Generated,
+            ALWAYS_KEYWORD => Token::Always,
+            STORED_KEYWORD => Token::Stored,
+            COLLATE_KEYWORD => Token::Collate,
+            LATERAL_KEYWORD => Token::Lateral,
+            TABLESAMPLE_KEYWORD => Token::Tablesample,
+            DECLARE_KEYWORD => Token::Declare,
+            CURSOR_KEYWORD => Token::Cursor,
+            CLOSE_KEYWORD => Token::Close,
+            FETCH_KEYWORD => Token::Fetch,
+            NEXT_KEYWORD => Token::Next,
+            FORWARD_KEYWORD => Token::Forward,
+            FOR_KEYWORD => Token::For,
+            IN_KEYWORD => Token::In,
+            RETURNING_KEYWORD => Token::Returning,
+            SIMILAR_KEYWORD => Token::Similar,
+            BEGIN_KEYWORD => Token::Begin,
+            COMMIT_KEYWORD => Token::Commit,
+            ROLLBACK_KEYWORD => Token::Rollback,
+            TRANSACTION_KEYWORD => Token::Transaction,
+            WORK_KEYWORD => Token::Work,
             IS_KEYWORD => Token::Is,
             LIMIT_KEYWORD => Token::Limit,
             OFFSET_KEYWORD => Token::Offset,
@@ -1145,6 +1800,8 @@ impl Lexer {
             ON_KEYWORD => Token::On,
             INT_KEYWORD => Token::Int,
             TEXT_KEYWORD => Token::Text,
+            VARCHAR_KEYWORD => Token::Varchar,
+            CHAR_KEYWORD => Token::Char,
             BIGINT_KEYWORD => Token::BigInt,
             SMALLINT_KEYWORD => Token::SmallInt,
             REAL_KEYWORD => Token::Real,
@@ -1170,43 +1827,26 @@ impl Lexer {
             }
         };
 
-        if keyword_match == TRUE_KEYWORD.to_owned() || keyword_match == FALSE_KEYWORD.to_owned() {
+        if keyword_match == TRUE_KEYWORD || keyword_match == FALSE_KEYWORD {
             kind = Token::BoolValue {
-                value: if keyword_match == TRUE_KEYWORD.to_owned() {
-                    true
-                } else {
-                    false
-                },
+                value: keyword_match == TRUE_KEYWORD,
             };
         }
 
-        if keyword_match == NULL_KEYWORD.to_owned() {
+        if keyword_match == NULL_KEYWORD {
             kind = Token::Null;
         }
 
-        Some((
-            TokenContainer {
-                loc: ic.loc,
-                token: kind,
-            },
-            cur,
-        ))
+        Some((TokenContainer::new(kind, ic, cur), cur))
     }
 
     pub fn lex_identifier(&self, source: &str, ic: Cursor) -> Option<(TokenContainer, Cursor)> {
         // Handle separately if is a double-quoted identifier
-        let token_result =
-            self.lex_character_delimited(source, ic.clone(), '"', TokenKind::Identifier);
-        if token_result.is_some() {
-            return token_result;
-        }
-        if let Some(res) =
-            self.lex_character_delimited(source, ic.clone(), '"', TokenKind::Identifier)
-        {
+        if let Some(res) = self.lex_character_delimited(source, ic, '"', TokenKind::Identifier) {
             return Some(res);
         }
 
-        let mut cur = ic.clone();
+        let mut cur = ic;
         let c = match get_chat_at(source, ic.pointer) {
             None => {
                 return None;
@@ -1219,62 +1859,157 @@ impl Lexer {
             return None;
         }
 
-        cur.pointer += 1;
-        cur.loc.col += 1;
-
-        let mut value: String = format!("{}", c);
-
-        for c in source[cur.pointer..].chars() {
-            // Other characters count too, big ignoring non-ascii for now
-            if is_char_valid_for_identifier(c) {
-                value.push(c);
-                cur.pointer += 1;
-                cur.loc.col += 1;
-                continue;
-            }
-
-            break;
-        }
-
-        if value.len() == 0 {
-            return None;
-        }
+        // Identifier characters are all ASCII, so their count is their length in bytes
+        let len = source[ic.pointer..]
+            .find(|c| !is_char_valid_for_identifier(c))
+            .unwrap_or(source.len() - ic.pointer);
+        let value = &source[ic.pointer..ic.pointer + len];
+        cur.pointer += len;
+        cur.loc.col += len;
+
+        // Unquoted identifiers are case-insensitive
+        let value = if value.bytes().any(|b| b.is_ascii_uppercase()) {
+            self.interner.intern(&value.to_ascii_lowercase())
+        } else {
+            self.interner.intern(value)
+        };
 
         Some((
-            TokenContainer {
-                // Unquoted identifiers are case-insensitive
-                loc: ic.loc,
-                token: Token::IdentifierValue {
-                    value: value.to_lowercase(),
-                },
-            },
+            TokenContainer::new(Token::IdentifierValue { value }, ic, cur),
             cur,
         ))
     }
 }
 
 pub fn get_location_from_cursor(source: &str, cursor: usize) -> TokenLocation {
-    let rev_pos = source[..(cursor + 1)]
-        .chars()
-        .rev()
-        .collect::<String>()
-        .find('\n');
-    let mut col = cursor;
-    match rev_pos {
-        Some(rev_pos) => {
-            col = source[..(cursor + 1)].len() - rev_pos;
-        }
-        _ => {}
+    // A cursor past the end or inside a character goes back to the last whole one
+    let mut end = cursor.min(source.len());
+    while !source.is_char_boundary(end) {
+        end -= 1;
     }
+    let before = &source[..end];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
     TokenLocation {
-        col,
-        line: source[..(cursor + 1)].matches('\n').count(),
+        col: before[line_start..].chars().count(),
+        line: before.matches('\n').count(),
     }
 }
 
-#[inline]
+// Longest part of the source quoted by an error
+const ERROR_FRAGMENT_MAX_CHARS: usize = 24;
+
+fn error_fragment(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(ERROR_FRAGMENT_MAX_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+// The radix of an integer literal written with a `0x`, `0o` or `0b` prefix
+fn non_decimal_radix(rest: &str) -> Option<u32> {
+    let mut chars = rest.chars();
+    if chars.next() != Some('0') {
+        return None;
+    }
+    match chars.next() {
+        Some('x') | Some('X') => Some(16),
+        Some('o') | Some('O') => Some(8),
+        Some('b') | Some('B') => Some(2),
+        _ => None,
+    }
+}
+
+// Lexes an integer literal of a radix other than ten, prefix included. It is stored in
+// decimal like any other numeric, so past the lexer no one needs to know about radixes.
+fn lex_non_decimal(source: &str, ic: Cursor, radix: u32) -> Option<(TokenContainer, Cursor)> {
+    let body = &source[ic.pointer + 2..];
+    // Letters and digits right after the prefix are all part of the literal, so `0b102`
+    // is an invalid binary number rather than `0b10` followed by `2`
+    let len = body
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(body.len());
+    // The prefix counts as a digit, so an underscore may follow it
+    let literal = body[..len].strip_prefix('_').unwrap_or(&body[..len]);
+    let digits = strip_digit_separators(literal, |c| c.is_digit(radix))?;
+    let mut cur = ic;
+    cur.pointer += 2 + len;
+    cur.loc.col += 2 + len;
+    let value = Atom::from(radix_to_decimal(&digits, radix));
+    Some((
+        TokenContainer::new(Token::NumericValue { value }, ic, cur),
+        cur,
+    ))
+}
+
+// The digits of `text` without the underscores separating them, as long as there is at
+// least one digit and every underscore is between two of them
+fn strip_digit_separators(text: &str, is_digit: impl Fn(char) -> bool) -> Option<String> {
+    let mut digits = String::with_capacity(text.len());
+    let mut prev = None;
+    for c in text.chars() {
+        match c {
+            '_' if prev.is_some_and(&is_digit) => {}
+            c if is_digit(c) => digits.push(c),
+            _ => return None,
+        }
+        prev = Some(c);
+    }
+    if digits.is_empty() || prev == Some('_') {
+        return None;
+    }
+    Some(digits)
+}
+
+// Converts digits of the given radix to decimal ones. Numeric literals have no size
+// limit, so neither does this.
+fn radix_to_decimal(digits: &str, radix: u32) -> String {
+    // Decimal digits, least significant first
+    let mut decimal: Vec<u32> = vec![0];
+    for c in digits.chars() {
+        let mut carry = c.to_digit(radix).unwrap_or_default();
+        for digit in decimal.iter_mut() {
+            let value = *digit * radix + carry;
+            *digit = value % 10;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            decimal.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    decimal
+        .iter()
+        .rev()
+        .map(|digit| (b'0' + *digit as u8) as char)
+        .collect()
+}
+
 fn get_chat_at(source: &str, position: usize) -> Option<char> {
-    source[position..(position + 1)].chars().nth(0)
+    source.get(position..)?.chars().next()
+}
+
+// Returns the opening tag of a dollar quoted string starting at `position`, `$$` or
+// `$tag$` where the tag follows the rules of unquoted identifiers.
+pub fn dollar_quote_tag(source: &str, position: usize) -> Option<&str> {
+    let rest = source.get(position..)?;
+    if !rest.starts_with('$') {
+        return None;
+    }
+    for (i, c) in rest.char_indices().skip(1) {
+        if c == '$' {
+            return Some(&rest[..=i]);
+        }
+        let valid = if i == 1 {
+            is_char_alphabetical(c) || c == '_'
+        } else {
+            is_char_alphabetical(c) || is_char_digit(c) || c == '_'
+        };
+        if !valid {
+            return None;
+        }
+    }
+    None
 }
 
 #[inline]
@@ -1373,87 +2108,259 @@ mod lexer_tests {
                 expected_result: true,
                 value: "105",
                 expected_value: Token::NumericValue {
-                    value: "105".to_owned(),
+                    value: "105".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "105 ",
                 expected_value: Token::NumericValue {
-                    value: "105".to_owned(),
+                    value: "105".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "123.",
                 expected_value: Token::NumericValue {
-                    value: "123.".to_owned(),
+                    value: "123.".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "123.145",
                 expected_value: Token::NumericValue {
-                    value: "123.145".to_owned(),
+                    value: "123.145".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1e5",
                 expected_value: Token::NumericValue {
-                    value: "1e5".to_owned(),
+                    value: "1e5".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1.e21",
                 expected_value: Token::NumericValue {
-                    value: "1.e21".to_owned(),
+                    value: "1.e21".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1.1e2",
                 expected_value: Token::NumericValue {
-                    value: "1.1e2".to_owned(),
+                    value: "1.1e2".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1.1e-2",
                 expected_value: Token::NumericValue {
-                    value: "1.1e-2".to_owned(),
+                    value: "1.1e-2".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1.1e+2",
                 expected_value: Token::NumericValue {
-                    value: "1.1e+2".to_owned(),
+                    value: "1.1e+2".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "1e-1",
                 expected_value: Token::NumericValue {
-                    value: "1e-1".to_owned(),
+                    value: "1e-1".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: ".1",
+                expected_value: Token::NumericValue { value: ".1".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "4.",
+                expected_value: Token::NumericValue { value: "4.".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "1_000_000",
                 expected_value: Token::NumericValue {
-                    value: ".1".to_owned(),
+                    value: "1000000".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
-                value: "4.",
+                value: "1_000.000_5",
+                expected_value: Token::NumericValue {
+                    value: "1000.0005".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "1_0e1_0",
+                expected_value: Token::NumericValue {
+                    value: "10e10".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: ".0_1",
                 expected_value: Token::NumericValue {
-                    value: "4.".to_owned(),
+                    value: ".01".into(),
                 },
             },
+            LexerTest {
+                expected_result: true,
+                value: "0x1F",
+                expected_value: Token::NumericValue { value: "31".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0X1f ",
+                expected_value: Token::NumericValue { value: "31".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0x_FF",
+                expected_value: Token::NumericValue {
+                    value: "255".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0x00",
+                expected_value: Token::NumericValue { value: "0".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0o777",
+                expected_value: Token::NumericValue {
+                    value: "511".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0O1_0",
+                expected_value: Token::NumericValue { value: "8".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0b1010",
+                expected_value: Token::NumericValue { value: "10".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0B1_1",
+                expected_value: Token::NumericValue { value: "3".into() },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+                expected_value: Token::NumericValue {
+                    value: "340282366920938463463374607431768211455".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0x1_0000_0000_0000_0000_0000_0000_0000_0000",
+                expected_value: Token::NumericValue {
+                    value: "340282366920938463463374607431768211456".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "0b1010+1",
+                expected_value: Token::NumericValue { value: "10".into() },
+            },
             // false
+            LexerTest {
+                expected_result: false,
+                value: "0x",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0x ",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0xG",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0x_",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0x1_",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0x1__0",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0o8",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0o_",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0b102",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "0b",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1__0",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1_",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "_1",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1_.5",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1._5",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1e_5",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1_e5",
+                expected_value: Token::Empty,
+            },
             LexerTest {
                 expected_result: false,
                 value: "e4",
@@ -1487,42 +2394,40 @@ mod lexer_tests {
                 expected_result: true,
                 value: "'abc'",
                 expected_value: Token::StringValue {
-                    value: "abc".to_owned(),
+                    value: "abc".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "'a'",
-                expected_value: Token::StringValue {
-                    value: "a".to_owned(),
-                },
+                expected_value: Token::StringValue { value: "a".into() },
             },
             LexerTest {
                 expected_result: true,
                 value: "'a b'",
                 expected_value: Token::StringValue {
-                    value: "a b".to_owned(),
+                    value: "a b".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "'a b  c '",
                 expected_value: Token::StringValue {
-                    value: "a b  c ".to_owned(),
+                    value: "a b  c ".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "'a b '''' c'",
                 expected_value: Token::StringValue {
-                    value: "a b '' c".to_owned(),
+                    value: "a b '' c".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "'a''b'",
                 expected_value: Token::StringValue {
-                    value: "a'b".to_owned(),
+                    value: "a'b".into(),
                 },
             },
             // false
@@ -1551,6 +2456,199 @@ mod lexer_tests {
         run_lexer_tests(Lexer::lex_string, string_tests, "lex_string");
     }
 
+    #[test]
+    fn test_lex_standard_conforming_strings() {
+        let standard = Lexer::new();
+        let escaping = Lexer::with_options(LexOptions {
+            standard_conforming_strings: false,
+        });
+        let tests = vec![
+            (
+                r"'C:\data\file.csv' x",
+                r"C:\data\file.csv",
+                "C:data\u{c}ile.csv",
+            ),
+            (r"'a\\b' x", r"a\\b", r"a\b"),
+            (r"'\n\t\r\b' x", r"\n\t\r\b", "\n\t\r\u{8}"),
+            (
+                r"'\101\x42\x4a\u00e9\U0001F600' x",
+                r"\101\x42\x4a\u00e9\U0001F600",
+                "ABJé😀",
+            ),
+            (r"'\xz\u12' x", r"\xz\u12", "xzu12"),
+            (r"'it''s' x", "it's", "it's"),
+        ];
+        for (input, standard_value, escaped_value) in tests {
+            for (lexer, value) in [(&standard, standard_value), (&escaping, escaped_value)] {
+                let tokens = lexer.lex(input).unwrap();
+                assert_eq!(
+                    tokens[0].token,
+                    Token::StringValue {
+                        value: value.into()
+                    },
+                    "{}",
+                    input
+                );
+                assert_eq!(tokens[1].text(input), "x", "{}", input);
+                assert_eq!(tokens[1].loc.col, input.chars().count() - 1, "{}", input);
+            }
+        }
+
+        // A backslash escapes the quote, which no longer ends the string
+        assert_eq!(standard.lex(r"'a\' x").unwrap().len(), 2);
+        let tokens = escaping.lex(r"'a\'b' x").unwrap();
+        assert_eq!(
+            tokens[0].token,
+            Token::StringValue {
+                value: "a'b".into()
+            }
+        );
+        assert!(escaping.lex(r"'a\' x").is_err());
+    }
+
+    #[test]
+    fn test_lex_escaped_delimiters() {
+        let lexer = Lexer::new();
+        let start = Cursor {
+            pointer: 0,
+            loc: TokenLocation { line: 0, col: 0 },
+        };
+        let tests = vec![
+            (
+                "'a''b' x",
+                Token::StringValue {
+                    value: "a'b".into(),
+                },
+                6,
+                (0, 6),
+            ),
+            (
+                "\"we\"\"ird\" x",
+                Token::IdentifierValue {
+                    value: lexer.interner.intern("we\"ird"),
+                },
+                9,
+                (0, 9),
+            ),
+            (
+                "'''é''\n''' x",
+                Token::StringValue {
+                    value: "'é'\n'".into(),
+                },
+                11,
+                (1, 3),
+            ),
+        ];
+        for (input, token, pointer, (line, col)) in tests {
+            let (lexed, cur) = match lexer.lex_string(input, start) {
+                Some(lexed) => lexed,
+                None => lexer.lex_identifier(input, start).unwrap(),
+            };
+            assert_eq!(lexed.token, token, "{}", input);
+            assert_eq!(cur.pointer, pointer, "{}", input);
+            assert_eq!(cur.loc, TokenLocation { line, col }, "{}", input);
+            assert_eq!(lexed.span.end, pointer, "{}", input);
+
+            // The token after it is where the source has it
+            let tokens = lexer.lex(input).unwrap();
+            assert_eq!(
+                tokens[1].loc,
+                TokenLocation { line, col: col + 1 },
+                "{}",
+                input
+            );
+            assert_eq!(tokens[1].text(input), "x", "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_token_lex_parameter() {
+        let parameter_tests = vec![
+            // true
+            LexerTest {
+                expected_result: true,
+                value: "$1",
+                expected_value: Token::Parameter { index: 1 },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "$12)",
+                expected_value: Token::Parameter { index: 12 },
+            },
+            // false
+            LexerTest {
+                expected_result: false,
+                value: "$a",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "$",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "1",
+                expected_value: Token::Empty,
+            },
+        ];
+
+        run_lexer_tests(Lexer::lex_parameter, parameter_tests, "lex_parameter");
+    }
+
+    #[test]
+    fn test_token_lex_dollar_quoted() {
+        let dollar_quoted_tests = vec![
+            // true
+            LexerTest {
+                expected_result: true,
+                value: "$$a; b$$",
+                expected_value: Token::StringValue {
+                    value: "a; b".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "$fn$it's $$ here$fn$",
+                expected_value: Token::StringValue {
+                    value: "it's $$ here".into(),
+                },
+            },
+            LexerTest {
+                expected_result: true,
+                value: "$_1$$_1$",
+                expected_value: Token::StringValue { value: "".into() },
+            },
+            // false
+            LexerTest {
+                expected_result: false,
+                value: "$1",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "$1$a$1$",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "$a$abc$b$",
+                expected_value: Token::Empty,
+            },
+            LexerTest {
+                expected_result: false,
+                value: "'abc'",
+                expected_value: Token::Empty,
+            },
+        ];
+
+        run_lexer_tests(
+            Lexer::lex_dollar_quoted,
+            dollar_quoted_tests,
+            "lex_dollar_quoted",
+        );
+    }
+
     #[test]
     fn test_token_lex_symbol() {
         let symbol_tests = vec![
@@ -1588,64 +2686,62 @@ mod lexer_tests {
             LexerTest {
                 expected_result: true,
                 value: "a",
-                expected_value: Token::IdentifierValue {
-                    value: "a".to_owned(),
-                },
+                expected_value: Token::IdentifierValue { value: "a".into() },
             },
             LexerTest {
                 expected_result: true,
                 value: "abc",
                 expected_value: Token::IdentifierValue {
-                    value: "abc".to_owned(),
+                    value: "abc".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "abc ",
                 expected_value: Token::IdentifierValue {
-                    value: "abc".to_owned(),
+                    value: "abc".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "abc ",
                 expected_value: Token::IdentifierValue {
-                    value: "abc".to_owned(),
+                    value: "abc".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "a9$",
                 expected_value: Token::IdentifierValue {
-                    value: "a9$".to_owned(),
+                    value: "a9$".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "userName",
                 expected_value: Token::IdentifierValue {
-                    value: "username".to_owned(),
+                    value: "username".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "\"userName\"",
                 expected_value: Token::IdentifierValue {
-                    value: "userName".to_owned(),
+                    value: "userName".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "indexed_value",
                 expected_value: Token::IdentifierValue {
-                    value: "indexed_value".to_owned(),
+                    value: "indexed_value".into(),
                 },
             },
             LexerTest {
                 expected_result: true,
                 value: "unique_values",
                 expected_value: Token::IdentifierValue {
-                    value: "unique_values".to_owned(),
+                    value: "unique_values".into(),
                 },
             },
             // false
@@ -1719,10 +2815,15 @@ mod lexer_tests {
         run_lexer_tests(Lexer::lex_keyword, keyword_tests, "lex_keyword");
     }
 
+    struct ExpectedToken {
+        loc: TokenLocation,
+        token: Token,
+    }
+
     struct LexTest {
         valid: bool,
         input: &'static str,
-        tokens: Vec<TokenContainer>,
+        tokens: Vec<ExpectedToken>,
     }
 
     #[test]
@@ -1732,15 +2833,13 @@ mod lexer_tests {
                 valid: true,
                 input: "select a",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
-                        token: Token::IdentifierValue {
-                            value: "a".to_owned(),
-                        },
+                        token: Token::IdentifierValue { value: "a".into() },
                     },
                 ],
             },
@@ -1748,11 +2847,11 @@ mod lexer_tests {
                 valid: true,
                 input: "select true",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
                         token: Token::BoolValue { value: true },
                     },
@@ -1762,15 +2861,13 @@ mod lexer_tests {
                 valid: true,
                 input: "select 1",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
-                        token: Token::NumericValue {
-                            value: "1".to_owned(),
-                        },
+                        token: Token::NumericValue { value: "1".into() },
                     },
                 ],
             },
@@ -1778,27 +2875,27 @@ mod lexer_tests {
                 valid: true,
                 input: "select 'foo' || 'bar';",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
                         token: Token::StringValue {
-                            value: "foo".to_owned(),
+                            value: "foo".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 13, line: 0 },
                         token: Token::Concat,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 16, line: 0 },
                         token: Token::StringValue {
-                            value: "bar".to_owned(),
+                            value: "bar".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 21, line: 0 },
                         token: Token::Semicolon,
                     },
@@ -1808,49 +2905,45 @@ mod lexer_tests {
                 valid: true,
                 input: "CREATE TABLE u (id INT, name TEXT)",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Create,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
                         token: Token::Table,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 13, line: 0 },
-                        token: Token::IdentifierValue {
-                            value: "u".to_owned(),
-                        },
+                        token: Token::IdentifierValue { value: "u".into() },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 15, line: 0 },
                         token: Token::LeftParenthesis,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 16, line: 0 },
-                        token: Token::IdentifierValue {
-                            value: "id".to_owned(),
-                        },
+                        token: Token::IdentifierValue { value: "id".into() },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 19, line: 0 },
                         token: Token::Int,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 22, line: 0 },
                         token: Token::Comma,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 24, line: 0 },
                         token: Token::IdentifierValue {
-                            value: "name".to_owned(),
+                            value: "name".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 29, line: 0 },
                         token: Token::Text,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 33, line: 0 },
                         token: Token::RightParenthesis,
                     },
@@ -1860,46 +2953,46 @@ mod lexer_tests {
                 valid: true,
                 input: "insert into users values (545, 232)",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Insert,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
                         token: Token::Into,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 12, line: 0 },
                         token: Token::IdentifierValue {
-                            value: "users".to_owned(),
+                            value: "users".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 18, line: 0 },
                         token: Token::Values,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 25, line: 0 },
                         token: Token::LeftParenthesis,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 26, line: 0 },
                         token: Token::NumericValue {
-                            value: "545".to_owned(),
+                            value: "545".into(),
                         },
                     },
-                    TokenContainer {
-                        loc: TokenLocation { col: 30, line: 0 },
+                    ExpectedToken {
+                        loc: TokenLocation { col: 29, line: 0 },
                         token: Token::Comma,
                     },
-                    TokenContainer {
-                        loc: TokenLocation { col: 32, line: 0 },
+                    ExpectedToken {
+                        loc: TokenLocation { col: 31, line: 0 },
                         token: Token::NumericValue {
-                            value: "232".to_owned(),
+                            value: "232".into(),
                         },
                     },
-                    TokenContainer {
-                        loc: TokenLocation { col: 36, line: 0 },
+                    ExpectedToken {
+                        loc: TokenLocation { col: 34, line: 0 },
                         token: Token::RightParenthesis,
                     },
                 ],
@@ -1908,27 +3001,25 @@ mod lexer_tests {
                 valid: true,
                 input: "SELECT id FROM users;",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
-                        token: Token::IdentifierValue {
-                            value: "id".to_owned(),
-                        },
+                        token: Token::IdentifierValue { value: "id".into() },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 10, line: 0 },
                         token: Token::From,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 15, line: 0 },
                         token: Token::IdentifierValue {
-                            value: "users".to_owned(),
+                            value: "users".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 20, line: 0 },
                         token: Token::Semicolon,
                     },
@@ -1938,37 +3029,35 @@ mod lexer_tests {
                 valid: true,
                 input: "SELECT id, name FROM users;",
                 tokens: vec![
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 0, line: 0 },
                         token: Token::Select,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 7, line: 0 },
-                        token: Token::IdentifierValue {
-                            value: "id".to_owned(),
-                        },
+                        token: Token::IdentifierValue { value: "id".into() },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 9, line: 0 },
                         token: Token::Comma,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 11, line: 0 },
                         token: Token::IdentifierValue {
-                            value: "name".to_owned(),
+                            value: "name".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 16, line: 0 },
                         token: Token::From,
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 21, line: 0 },
                         token: Token::IdentifierValue {
-                            value: "users".to_owned(),
+                            value: "users".into(),
                         },
                     },
-                    TokenContainer {
+                    ExpectedToken {
                         loc: TokenLocation { col: 26, line: 0 },
                         token: Token::Semicolon,
                     },
@@ -2006,7 +3095,23 @@ mod lexer_tests {
                             )
                             .to_owned(),
                         );
-                    } else {
+                    }
+                    for token in &result {
+                        // Spans must cover the lexeme, whatever lines it runs over
+                        let start = get_location_from_cursor(test.input, token.span.start);
+                        let end = get_location_from_cursor(test.input, token.span.end);
+                        if start != token.loc
+                            || end != token.span.end_loc
+                            || token.text(test.input).is_empty()
+                        {
+                            found_faults = true;
+                            err_msg.push_str(&format!(
+                                "For Input `{}` the token `{:?}` has span `{:?}`, expected it to run from `{:?}` to `{:?}`\n\n",
+                                test.input, token.token, token.span, start, end,
+                            ));
+                        }
+                    }
+                    if result.len() == test.tokens.len() {
                         for i in 0..test.tokens.len() {
                             let test_token = &result[i];
                             let expected_token = &test.tokens[i];
@@ -2073,4 +3178,299 @@ mod lexer_tests {
             panic!(err_msg);
         }
     }
+
+    struct LexErrorTest {
+        input: &'static str,
+        error: LexingError,
+        code: &'static str,
+        msg: &'static str,
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        let tests = vec![
+            LexErrorTest {
+                input: "SELECT 'unterminated",
+                error: LexingError::UnterminatedString {
+                    fragment: "'unterminated".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "unterminated_string",
+                msg: "Unterminated quoted string 'unterminated, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT a FROM \"People\nWHERE 1 = 1",
+                error: LexingError::UnterminatedString {
+                    fragment: "\"People".to_owned(),
+                    loc: TokenLocation { line: 0, col: 14 },
+                },
+                code: "unterminated_string",
+                msg: "Unterminated quoted string \"People, at 1:15",
+            },
+            LexErrorTest {
+                input: "SELECT 1, 'it''s\nnot ''closed'' yet",
+                error: LexingError::UnterminatedString {
+                    fragment: "'it''s".to_owned(),
+                    loc: TokenLocation { line: 0, col: 10 },
+                },
+                code: "unterminated_string",
+                msg: "Unterminated quoted string 'it''s, at 1:11",
+            },
+            LexErrorTest {
+                input: "SELECT 'a''b',\n  \"we\"\"ird",
+                error: LexingError::UnterminatedString {
+                    fragment: "\"we\"\"ird".to_owned(),
+                    loc: TokenLocation { line: 1, col: 2 },
+                },
+                code: "unterminated_string",
+                msg: "Unterminated quoted string \"we\"\"ird, at 2:3",
+            },
+            LexErrorTest {
+                input: "SELECT $body$ a long dollar quoted string without its end",
+                error: LexingError::UnterminatedString {
+                    fragment: "$body$ a long dollar quo...".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "unterminated_string",
+                msg: "Unterminated quoted string $body$ a long dollar quo..., at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT 1;\n/* no end",
+                error: LexingError::UnterminatedComment {
+                    fragment: "/* no end".to_owned(),
+                    loc: TokenLocation { line: 1, col: 0 },
+                },
+                code: "unterminated_comment",
+                msg: "Unterminated comment /* no end, at 2:1",
+            },
+            LexErrorTest {
+                input: "SELECT 1.2.3 FROM t",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "1.2.3".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 1.2.3, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT 1e5e+3",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "1e5e+3".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 1e5e+3, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT 0x FROM t",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "0x".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 0x, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT 1,\n  1__0",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "1__0".to_owned(),
+                    loc: TokenLocation { line: 1, col: 2 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 1__0, at 2:3",
+            },
+            LexErrorTest {
+                input: "SELECT 0b102 + 1",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "0b102".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 0b102, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT 0xFG",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "0xFG".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 0xFG, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT a\nFROM t WHERE a = 1_000_",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "1_000_".to_owned(),
+                    loc: TokenLocation { line: 1, col: 17 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 1_000_, at 2:18",
+            },
+            LexErrorTest {
+                input: "SELECT 0o1_",
+                error: LexingError::InvalidNumericLiteral {
+                    fragment: "0o1_".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                code: "invalid_numeric_literal",
+                msg: "Invalid numeric literal 0o1_, at 1:8",
+            },
+            LexErrorTest {
+                input: "SELECT a FROM t WHERE a ? 1",
+                error: LexingError::UnknownCharacter {
+                    ch: '?',
+                    loc: TokenLocation { line: 0, col: 24 },
+                },
+                code: "unknown_character",
+                msg: "Unable to lex token '?', at 1:25",
+            },
+            LexErrorTest {
+                input: "SELECT 'é'\n  , € FROM t",
+                error: LexingError::UnknownCharacter {
+                    ch: '€',
+                    loc: TokenLocation { line: 1, col: 4 },
+                },
+                code: "unknown_character",
+                msg: "Unable to lex token '€', at 2:5",
+            },
+        ];
+
+        let lexer = Lexer::new();
+        for test in tests {
+            let err = lexer.lex(test.input).unwrap_err();
+            assert_eq!(err, test.error, "{}", test.input);
+            assert_eq!(err.error_code(), test.code, "{}", test.input);
+            assert_eq!(err.to_string(), test.msg, "{}", test.input);
+            assert_eq!(lexer.fingerprint(test.input).error, Some(test.error));
+        }
+    }
+
+    #[test]
+    fn test_lex_recovering() {
+        let lexer = Lexer::new();
+
+        let (tokens, errors) = lexer.lex_recovering("SELECT a ? b FROM t;");
+        assert_eq!(
+            errors,
+            vec![LexingError::UnknownCharacter {
+                ch: '?',
+                loc: TokenLocation { line: 0, col: 9 },
+            }]
+        );
+        // Lexing carries on after the error
+        assert_eq!(tokens.len(), 6);
+
+        // A run of bad characters is a single error
+        let (_, errors) = lexer.lex_recovering("SELECT a FROM t WHERE a ?€? 1;");
+        assert_eq!(
+            errors,
+            vec![LexingError::UnknownCharacter {
+                ch: '?',
+                loc: TokenLocation { line: 0, col: 24 },
+            }]
+        );
+
+        let (_, errors) = lexer.lex_recovering("SELECT 1.2.3, a ?, b\n  FROM t WHERE c = 'oops");
+        assert_eq!(
+            errors,
+            vec![
+                LexingError::InvalidNumericLiteral {
+                    fragment: "1.2.3".to_owned(),
+                    loc: TokenLocation { line: 0, col: 7 },
+                },
+                LexingError::UnknownCharacter {
+                    ch: '?',
+                    loc: TokenLocation { line: 0, col: 16 },
+                },
+                LexingError::UnterminatedString {
+                    fragment: "'oops".to_owned(),
+                    loc: TokenLocation { line: 1, col: 19 },
+                },
+            ]
+        );
+
+        let input = "SELECT a FROM t;";
+        let (tokens, errors) = lexer.lex_recovering(input);
+        assert!(errors.is_empty());
+        assert_eq!(tokens, lexer.lex(input).unwrap());
+    }
+
+    #[test]
+    fn test_lex_spans() {
+        let input = "SELECT 'multi\nline', \"Odd \"\"name\"\"\"\n/* a\ncomment */ FROM t ORDER  BY\n$$x\n$$ DESC 1.5e-3;";
+        let expected = vec![
+            ("SELECT", TokenLocation { line: 0, col: 6 }),
+            ("'multi\nline'", TokenLocation { line: 1, col: 5 }),
+            (",", TokenLocation { line: 1, col: 6 }),
+            ("\"Odd \"\"name\"\"\"", TokenLocation { line: 1, col: 21 }),
+            ("FROM", TokenLocation { line: 3, col: 15 }),
+            ("t", TokenLocation { line: 3, col: 17 }),
+            ("ORDER  BY", TokenLocation { line: 3, col: 27 }),
+            ("$$x\n$$", TokenLocation { line: 5, col: 2 }),
+            ("DESC", TokenLocation { line: 5, col: 7 }),
+            ("1.5e-3", TokenLocation { line: 5, col: 14 }),
+            (";", TokenLocation { line: 5, col: 15 }),
+        ];
+
+        let tokens = Lexer::new().lex(input).unwrap();
+        let lexemes: Vec<(&str, TokenLocation)> = tokens
+            .iter()
+            .map(|token| (token.text(input), token.span.end_loc))
+            .collect();
+        assert_eq!(lexemes, expected);
+        assert_eq!(tokens[6].token, Token::OrderBy);
+        assert_eq!(tokens[4].loc, TokenLocation { line: 3, col: 11 });
+    }
+
+    #[test]
+    fn test_lex_interns_identifiers() {
+        assert!(std::mem::size_of::<Token>() <= 16);
+
+        let lexer = Lexer::new();
+        let tokens = lexer
+            .lex("SELECT name FROM people WHERE Name = 'name' AND name = \"name\";")
+            .unwrap();
+        let names: Vec<&Atom> = tokens
+            .iter()
+            .filter_map(|t| match &t.token {
+                Token::IdentifierValue { value } if value == "name" => Some(value),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names.len(), 4);
+        for name in &names {
+            assert_eq!(name.as_ptr(), names[0].as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_location_from_cursor_out_of_range() {
+        let source = "SELECT\n'é'";
+        assert_eq!(
+            get_location_from_cursor(source, 9),
+            TokenLocation { line: 1, col: 1 }
+        );
+        assert_eq!(
+            get_location_from_cursor(source, 100),
+            TokenLocation { line: 1, col: 3 }
+        );
+    }
+
+    #[test]
+    fn test_lex_at_origin() {
+        let origin = TokenLocation::new_with_col_and_line(4, 6);
+        let lexer = Lexer::new().at_origin(origin);
+        let tokens = lexer.lex("SELECT a,\n  b;").unwrap();
+        let locs: Vec<_> = tokens.iter().map(|t| (t.loc.line, t.loc.col)).collect();
+        assert_eq!(locs, vec![(6, 4), (6, 11), (6, 12), (7, 2), (7, 3)]);
+        assert_eq!(
+            tokens[1].span.end_loc,
+            TokenLocation::new_with_col_and_line(12, 6)
+        );
+        // Spans still index the source that was lexed
+        assert_eq!(tokens[3].text("SELECT a,\n  b;"), "b");
+
+        let (_, errors) = lexer.lex_recovering("SELECT\n ? 1;");
+        assert_eq!(errors[0].loc(), TokenLocation::new_with_col_and_line(1, 7));
+    }
 }
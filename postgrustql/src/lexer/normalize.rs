@@ -0,0 +1,162 @@
+use super::*;
+
+// Stand in for the literals of a normalized query
+pub const LITERAL_PLACEHOLDER: &str = "?";
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NormalizedQuery {
+    // Tokens of the query separated by single spaces, with lowercase keywords and
+    // placeholders in place of literals
+    pub text: String,
+    // Stable hash of `text`, the same across runs and builds
+    pub fingerprint: u64,
+    // Set if the query failed to lex, `text` then only covers the tokens before the error
+    pub error: Option<LexingError>,
+}
+
+impl Lexer {
+    // Normalizes `source` so that queries differing only in literal values, letter case,
+    // whitespace or comments come out the same.
+    pub fn fingerprint(&self, source: &str) -> NormalizedQuery {
+        let (tokens, error) = self.lex_prefix(source);
+        NormalizedQuery::from_tokens(&tokens, error)
+    }
+}
+
+impl NormalizedQuery {
+    // Normalizes the tokens of an already lexed query, `error` being what stopped the lexer
+    pub fn from_tokens(tokens: &[TokenContainer], error: Option<LexingError>) -> Self {
+        let mut text = String::with_capacity(tokens.len() * 8);
+        for token in tokens {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            match &token.token {
+                token if is_literal(token) => text.push_str(LITERAL_PLACEHOLDER),
+                Token::IdentifierValue { value } => push_identifier(&mut text, value),
+                token => text.push_str(&token.generate_code().to_lowercase()),
+            }
+        }
+
+        let mut fingerprint = fnv1a(FNV_OFFSET_BASIS, text.as_bytes());
+        if error.is_some() {
+            // Keep a query that failed to lex apart from its valid prefix
+            fingerprint = fnv1a(fingerprint, &[0xff]);
+        }
+
+        NormalizedQuery {
+            text,
+            fingerprint,
+            error,
+        }
+    }
+}
+
+// Whether normalizing replaces the token with a placeholder
+pub fn is_literal(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::StringValue { .. } | Token::NumericValue { .. } | Token::BoolValue { .. }
+    )
+}
+
+// Normalizes `source`, returning the normalized text and its fingerprint.
+// See `Lexer::fingerprint`.
+pub fn normalize(source: &str) -> (String, u64) {
+    let normalized = Lexer::new().fingerprint(source);
+    (normalized.text, normalized.fingerprint)
+}
+
+// Quoted identifiers keep their quotes when they would not read the same without them
+fn push_identifier(text: &mut String, value: &str) {
+    let is_plain = value.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || is_char_digit(c) || c == '_' || c == '$');
+    if is_plain {
+        text.push_str(value);
+    } else {
+        text.push('"');
+        text.push_str(&value.replace('"', "\"\""));
+        text.push('"');
+    }
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// 64 bit FNV-1a, unlike the std hashers its output is guaranteed to never change
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod normalize_tests {
+    use super::*;
+
+    struct NormalizeTest {
+        input: &'static str,
+        text: &'static str,
+        is_err: bool,
+    }
+
+    #[test]
+    fn test_normalize() {
+        let tests = vec![
+            NormalizeTest {
+                input: "INSERT INTO people VALUES (1, 'Baam', true);",
+                text: "insert into people values ( ? , ? , ? ) ;",
+                is_err: false,
+            },
+            NormalizeTest {
+                input: "select  id,\n\tNAME from People -- trailing comment\n where id = $1 /* block */",
+                text: "select id , name from people where id = $1",
+                is_err: false,
+            },
+            NormalizeTest {
+                input: "SELECT \"Id\", \"odd\"\"name\" FROM t ORDER BY x DESC",
+                text: "select \"Id\" , \"odd\"\"name\" from t order by x desc",
+                is_err: false,
+            },
+            NormalizeTest {
+                input: "CREATE TABLE t (a DOUBLE PRECISION, b TEXT);",
+                text: "create table t ( a double precision , b text ) ;",
+                is_err: false,
+            },
+            NormalizeTest {
+                input: "SELECT id FROM people WHERE name = 'unterminated",
+                text: "select id from people where name =",
+                is_err: true,
+            },
+        ];
+
+        let lexer = Lexer::new();
+        for test in tests {
+            let normalized = lexer.fingerprint(test.input);
+            assert_eq!(normalized.text, test.text, "{}", test.input);
+            assert_eq!(normalized.error.is_some(), test.is_err, "{}", test.input);
+            assert_eq!(
+                normalize(test.input),
+                (normalized.text, normalized.fingerprint)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let (_, first) = normalize("INSERT INTO people VALUES (1, 'Baam');");
+        let (_, second) = normalize("insert into PEOPLE values (2,'Rachel') ;");
+        let (_, other_table) = normalize("INSERT INTO pets VALUES (1, 'Baam');");
+        let (_, prefix) = normalize("INSERT INTO people VALUES (1, 'Baam");
+        assert_eq!(first, second);
+        assert_ne!(first, other_table);
+        assert_ne!(first, prefix);
+
+        // Fingerprints are stored and compared across runs, they must not change
+        assert_eq!(normalize("SELECT 1;").1, 0x556c_6d81_c46b_0189);
+    }
+}
@@ -0,0 +1,364 @@
+use super::*;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SplitStatement {
+    // Statement text, including its terminating semicolon if it had one
+    pub text: String,
+    // Location of the first character of `text` in the whole input
+    pub loc: TokenLocation,
+}
+
+// Longest part of a statement quoted by `excerpt`
+const EXCERPT_MAX_CHARS: usize = 80;
+
+impl SplitStatement {
+    // The start of the statement on a single line, for errors to say which one failed
+    pub fn excerpt(&self) -> String {
+        statement_excerpt(&self.text)
+    }
+}
+
+// The first characters of a statement, from past the comments before it, with its runs
+// of whitespace made single spaces and without its terminating semicolon
+pub fn statement_excerpt(text: &str) -> String {
+    let mut text = text.trim();
+    loop {
+        text = if let Some(comment) = text.strip_prefix("--") {
+            comment.find('\n').map_or("", |end| &comment[end..])
+        } else if let Some(comment) = text.strip_prefix("/*") {
+            comment.find("*/").map_or("", |end| &comment[end + 2..])
+        } else {
+            break;
+        }
+        .trim_start();
+    }
+    let text = text.strip_suffix(';').unwrap_or(text);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(EXCERPT_MAX_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+// StatementSplitter cuts SQL text into statements at top level semicolons. Input can
+// be fed in arbitrary chunks, strings, quoted identifiers, dollar quoted strings and
+// comments are skipped with the lexer so semicolons inside them never split.
+pub struct StatementSplitter {
+    lexer: Lexer,
+    buffer: String,
+    // Location of the start of `buffer` in the whole input
+    loc: TokenLocation,
+    // Position up to which `buffer` has been scanned, and its location
+    scanned: usize,
+    scanned_loc: TokenLocation,
+    // Whether anything besides whitespace and comments was seen in the current statement
+    has_content: bool,
+}
+
+impl Default for StatementSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum Scan {
+    // A statement ends right after the given position
+    Boundary(usize),
+    // The buffer ends in the middle of a string or comment
+    Incomplete,
+    Exhausted,
+}
+
+impl StatementSplitter {
+    pub fn new() -> Self {
+        StatementSplitter {
+            lexer: Lexer::new(),
+            buffer: String::new(),
+            loc: TokenLocation::new(),
+            scanned: 0,
+            scanned_loc: TokenLocation::new(),
+            has_content: false,
+        }
+    }
+
+    pub fn push_str(&mut self, chunk: &str) {
+        self.buffer.push_str(chunk);
+    }
+
+    // Returns the next complete statement, if enough input was pushed to find its end.
+    // Statements that consist only of whitespace and comments are skipped.
+    pub fn next_statement(&mut self) -> Option<SplitStatement> {
+        loop {
+            match self.scan(false) {
+                Scan::Boundary(end) => {
+                    if let Some(statement) = self.take(end) {
+                        return Some(statement);
+                    }
+                }
+                Scan::Incomplete | Scan::Exhausted => return None,
+            }
+        }
+    }
+
+    // Signals the end of input and returns the trailing statement if there is one.
+    // Fails if the input ends inside a string or a quoted identifier.
+    pub fn finish(&mut self) -> Result<Option<SplitStatement>, LexingError> {
+        loop {
+            match self.scan(true) {
+                Scan::Boundary(end) => {
+                    if let Some(statement) = self.take(end) {
+                        return Ok(Some(statement));
+                    }
+                }
+                Scan::Incomplete => {
+                    return Err(LexingError::UnterminatedString {
+                        fragment: error_fragment(&self.buffer[self.scanned..]),
+                        loc: self.scanned_loc,
+                    });
+                }
+                Scan::Exhausted => {
+                    let end = self.buffer.len();
+                    return Ok(self.take(end));
+                }
+            }
+        }
+    }
+
+    fn scan(&mut self, at_eof: bool) -> Scan {
+        while self.scanned < self.buffer.len() {
+            let source = self.buffer.as_str();
+            let pointer = self.scanned;
+            let c = match get_chat_at(source, pointer) {
+                Some(c) => c,
+                None => return Scan::Exhausted,
+            };
+            let cur = Cursor {
+                pointer,
+                loc: self.scanned_loc,
+            };
+
+            // Could be the first half of a comment marker
+            if (c == '-' || c == '/') && pointer + 1 == source.len() && !at_eof {
+                return Scan::Incomplete;
+            }
+
+            let skipped =
+                if source[pointer..].starts_with("--") || source[pointer..].starts_with("/*") {
+                    match self.lexer.lex_comment(source, cur) {
+                        Some((_, new_cursor)) => {
+                            let text = &source[pointer..new_cursor.pointer];
+                            let complete = if text.starts_with("/*") {
+                                text.len() >= 4 && text.ends_with("*/")
+                            } else {
+                                text.ends_with('\n')
+                            };
+                            // Unterminated comments simply run to the end of the input
+                            if !complete && !at_eof {
+                                return Scan::Incomplete;
+                            }
+                            Some((new_cursor, false))
+                        }
+                        None => None,
+                    }
+                } else if c == '\'' || c == '"' {
+                    let token = if c == '\'' {
+                        self.lexer.lex_string(source, cur)
+                    } else {
+                        self.lexer.lex_identifier(source, cur)
+                    };
+                    match token {
+                        // A doubled quote right at the end of the buffer could be an escape
+                        Some((_, new_cursor)) if new_cursor.pointer < source.len() || at_eof => {
+                            Some((new_cursor, true))
+                        }
+                        _ => return Scan::Incomplete,
+                    }
+                } else if c == '$' {
+                    if dollar_quote_tag(source, pointer).is_some() {
+                        match self.lexer.lex_dollar_quoted(source, cur) {
+                            Some((_, new_cursor)) => Some((new_cursor, true)),
+                            None => return Scan::Incomplete,
+                        }
+                    } else {
+                        // The tag itself may not have been read in full yet, while `$1` style
+                        // parameters are never dollar quotes
+                        let rest = &source[pointer + 1..];
+                        let may_be_tag = !rest.starts_with(is_char_digit)
+                            && rest
+                                .chars()
+                                .all(|c| is_char_valid_for_identifier(c) && c != '$');
+                        if may_be_tag && !at_eof {
+                            return Scan::Incomplete;
+                        }
+                        None
+                    }
+                } else if is_char_alphabetical(c) {
+                    // Identifiers may contain `$`, skip them whole so it isn't mistaken for a
+                    // dollar quote
+                    let len = source[pointer..]
+                        .find(|c| !is_char_valid_for_identifier(c))
+                        .unwrap_or(source.len() - pointer);
+                    if pointer + len == source.len() && !at_eof {
+                        return Scan::Incomplete;
+                    }
+                    let mut cur = Cursor {
+                        pointer: pointer + len,
+                        loc: self.scanned_loc,
+                    };
+                    cur.loc.col += len;
+                    Some((cur, true))
+                } else {
+                    None
+                };
+
+            match skipped {
+                Some((new_cursor, is_content)) => {
+                    self.scanned = new_cursor.pointer;
+                    self.scanned_loc = new_cursor.loc;
+                    self.has_content = self.has_content || is_content;
+                }
+                None => {
+                    self.scanned += c.len_utf8();
+                    if c == '\n' {
+                        self.scanned_loc.line += 1;
+                        self.scanned_loc.col = 0;
+                    } else {
+                        self.scanned_loc.col += 1;
+                    }
+                    if c == ';' {
+                        return Scan::Boundary(self.scanned);
+                    }
+                    if !c.is_whitespace() {
+                        self.has_content = true;
+                    }
+                }
+            }
+        }
+        Scan::Exhausted
+    }
+
+    // Removes the text up to `end` from the buffer, returning it unless it was empty
+    fn take(&mut self, end: usize) -> Option<SplitStatement> {
+        let text: String = self.buffer.drain(..end).collect();
+        let loc = std::mem::replace(&mut self.loc, self.scanned_loc);
+        self.scanned -= end;
+        let has_content = std::mem::replace(&mut self.has_content, false);
+
+        if has_content {
+            Some(SplitStatement { text, loc })
+        } else {
+            None
+        }
+    }
+}
+
+// Splits `source` into statements, see `StatementSplitter`.
+pub fn split_statements(source: &str) -> Result<Vec<SplitStatement>, LexingError> {
+    let mut splitter = StatementSplitter::new();
+    splitter.push_str(source);
+
+    let mut statements = vec![];
+    while let Some(statement) = splitter.next_statement() {
+        statements.push(statement);
+    }
+    if let Some(statement) = splitter.finish()? {
+        statements.push(statement);
+    }
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod splitter_tests {
+    use super::*;
+
+    struct SplitTest {
+        input: &'static str,
+        statements: Vec<(&'static str, usize, usize)>,
+    }
+
+    fn statements(split: Vec<SplitStatement>) -> Vec<(String, usize, usize)> {
+        split
+            .into_iter()
+            .map(|s| (s.text, s.loc.line, s.loc.col))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let tests = vec![
+            SplitTest {
+                input: "select 1; select 2;",
+                statements: vec![("select 1;", 0, 0), (" select 2;", 0, 9)],
+            },
+            SplitTest {
+                input: "select 'a;b'; select \"c;d\"",
+                statements: vec![("select 'a;b';", 0, 0), (" select \"c;d\"", 0, 13)],
+            },
+            SplitTest {
+                input: "select 'it''s;'; -- comment; here\nselect 2; /* block; \n ; */",
+                statements: vec![
+                    ("select 'it''s;';", 0, 0),
+                    (" -- comment; here\nselect 2;", 0, 16),
+                ],
+            },
+            SplitTest {
+                input: "select $$a;b$$;\nselect $fn$ $$; $fn$;select a$b;",
+                statements: vec![
+                    ("select $$a;b$$;", 0, 0),
+                    ("\nselect $fn$ $$; $fn$;", 0, 15),
+                    ("select a$b;", 1, 21),
+                ],
+            },
+            SplitTest {
+                input: ";; \n ; select 'ü;';select 1",
+                statements: vec![(" select 'ü;';", 1, 2), ("select 1", 1, 15)],
+            },
+        ];
+
+        for test in tests {
+            let expected: Vec<(String, usize, usize)> = test
+                .statements
+                .iter()
+                .map(|(text, line, col)| (text.to_string(), *line, *col))
+                .collect();
+            let split = split_statements(test.input).unwrap();
+            assert_eq!(statements(split), expected, "{}", test.input);
+
+            // Feeding the same input a character at a time must give the same result
+            let mut splitter = StatementSplitter::new();
+            let mut split = vec![];
+            for c in test.input.chars() {
+                splitter.push_str(&c.to_string());
+                while let Some(statement) = splitter.next_statement() {
+                    split.push(statement);
+                }
+            }
+            split.extend(splitter.finish().unwrap());
+            assert_eq!(statements(split), expected, "{} (streamed)", test.input);
+        }
+
+        assert_eq!(
+            split_statements("select 1;\nselect 'abc; select 1;"),
+            Err(LexingError::UnterminatedString {
+                fragment: "'abc; select 1;".to_owned(),
+                loc: TokenLocation { line: 1, col: 7 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_statement_excerpt() {
+        let statement = split_statements("SELECT 1;\n-- first\n/* then; */  SELECT\n\t2  ;")
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(statement.excerpt(), "SELECT 2");
+
+        let long = format!("SELECT '{}';", "é".repeat(100));
+        assert_eq!(
+            statement_excerpt(&long),
+            format!("SELECT '{}...", "é".repeat(72))
+        );
+        assert_eq!(statement_excerpt(" -- nothing"), "");
+    }
+}
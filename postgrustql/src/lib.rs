@@ -3,8 +3,12 @@ pub mod backend;
 pub mod backend_memory;
 pub mod lexer;
 pub mod parser;
+mod quote;
+pub mod row;
 pub mod sql_types;
 
+pub use quote::{quote_identifier, quote_literal};
+
 #[macro_use]
 extern crate lazy_static;
 #[cfg(test)]
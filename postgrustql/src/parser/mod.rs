@@ -1,3 +1,8 @@
+mod recovery;
+
+pub use recovery::*;
+
+use crate::quote::quote_identifier;
 use crate::sql_types::SqlType;
 
 use super::ast::*;
@@ -29,16 +34,18 @@ lazy_static! {
         Token::BitwiseShiftLeft,
         Token::BitwiseShiftRight,
         Token::TypeCast,
+        Token::Collate,
+        Token::RegexMatchInsensitive,
+        Token::RegexNotMatch,
+        Token::RegexNotMatchInsensitive,
     ];
-    static ref UNARY_OPERATORS: Vec<Token> = vec![
-        Token::Minus,
-        Token::Not,
-        Token::FactorialPrefix,
-        Token::SquareRoot,
-        Token::CubeRoot,
-        Token::AbsoluteValue,
-        Token::CubeRoot,
-        Token::BitwiseNot,
+    static ref COMPARISON_OPERATORS: Vec<Token> = vec![
+        Token::Equal,
+        Token::NotEqual,
+        Token::LessThan,
+        Token::LessThanOrEqual,
+        Token::GreaterThan,
+        Token::GreaterThanOrEqual,
     ];
     static ref UNARY_POSTFIX_OPERATORS: Vec<Token> = vec![Token::Factorial];
 }
@@ -66,8 +73,9 @@ impl std::fmt::Display for ParsingError {
 
 impl From<LexingError> for ParsingError {
     fn from(err: LexingError) -> Self {
-        match err {
-            LexingError::General { msg, loc } => ParsingError::Lexing { msg, loc },
+        ParsingError::Lexing {
+            msg: err.to_string(),
+            loc: err.loc(),
         }
     }
 }
@@ -88,26 +96,114 @@ fn expect_token<'a>(
 }
 
 #[inline]
-fn help_message(tokens: &Vec<TokenContainer>, cursor: usize, msg: String) -> String {
-    let token: TokenContainer;
-    if cursor == 0 {
-        token = tokens[0].clone();
-    } else if cursor + 1 < tokens.len() {
-        token = tokens[cursor].clone();
-    } else {
-        token = tokens[cursor - 1].clone();
+// Name given by the token at `cursor`. Besides identifiers this takes the non-reserved
+// keywords, so that e.g. a column can be called `key` without quoting it.
+fn parse_name(tokens: &[TokenContainer], cursor: usize) -> Option<String> {
+    match &tokens.get(cursor)?.token {
+        Token::IdentifierValue { value } => Some(value.to_string()),
+        token if token.is_non_reserved_keyword() => Some(token.generate_code()),
+        _ => None,
+    }
+}
+
+// Name of a function or of what a statement creates or drops at `cursor`, which may be
+// qualified by its schema, and the cursor after it. Functions are found in pg_catalog and
+// everything else is made in public, so naming either makes no difference. Names in other
+// schemas keep it, to be reported as not found.
+fn parse_qualified_name(tokens: &[TokenContainer], cursor: usize) -> Option<(String, usize)> {
+    let (name, cursor) = parse_relation_name(tokens, cursor)?;
+    match name.split_once('.') {
+        Some(("public", name)) | Some(("pg_catalog", name)) => Some((name.to_string(), cursor)),
+        _ => Some((name, cursor)),
+    }
+}
+
+// Name of a table or view read at `cursor`, along with the schema it is qualified by, and
+// the cursor after it. A table is only looked up in the schema it was named with, which
+// may hold another than the one its name alone finds.
+fn parse_relation_name(tokens: &[TokenContainer], cursor: usize) -> Option<(String, usize)> {
+    let name = parse_name(tokens, cursor)?;
+    if let Some(Token::Dot) = tokens.get(cursor + 1).map(|token| &token.token) {
+        let qualified = parse_name(tokens, cursor + 2)?;
+        return Some((format!("{}.{}", name, qualified), cursor + 3));
+    }
+    Some((name, cursor + 1))
+}
+
+// Alias at `cursor`, keywords are only taken right after AS since without it they could
+// just as well start the next clause
+fn parse_alias(tokens: &[TokenContainer], cursor: usize, found_as: bool) -> Option<String> {
+    match tokens.get(cursor) {
+        Some(TokenContainer {
+            token: Token::IdentifierValue { value },
+            ..
+        }) => Some(value.to_string()),
+        _ if found_as => parse_name(tokens, cursor),
+        _ => None,
+    }
+}
+
+// The token an error at `cursor` is reported at, the one before it when `cursor` is past
+// the last token that isn't the closing delimiter
+pub(crate) fn error_token(tokens: &[TokenContainer], cursor: usize) -> Option<&TokenContainer> {
+    match cursor {
+        0 => tokens.first(),
+        _ if cursor + 1 < tokens.len() => tokens.get(cursor),
+        _ => tokens.get(cursor - 1).or_else(|| tokens.last()),
     }
+}
+
+fn help_message(tokens: &Vec<TokenContainer>, cursor: usize, msg: String) -> String {
+    let token = error_token(tokens, cursor);
+    // Nothing was lexed at all
+    let token = match token {
+        Some(token) => token,
+        None => return msg,
+    };
 
     format!(
         "[{}, {}]: {}, got {:?}",
-        token.loc.line, token.loc.col, msg, token.token,
+        token.loc.line + 1,
+        token.loc.col + 1,
+        msg,
+        token.token,
+    )
+}
+
+// Quotes the line of `source` holding the token at `cursor`, with the token underlined
+fn underline_token(source: &str, tokens: &[TokenContainer], cursor: usize) -> String {
+    let span = match tokens.get(cursor).or_else(|| tokens.last()) {
+        Some(token) => token.span,
+        None => return String::new(),
+    };
+    let line_start = source[..span.start].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |pos| span.start + pos);
+
+    // Keep tabs so the underline lines up however wide they are shown
+    let indent: String = source[line_start..span.start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // Tokens running over several lines are underlined up to the end of their first one
+    let width = source[span.start..span.end.min(line_end)].chars().count();
+    format!(
+        "{}\n{}{}",
+        &source[line_start..line_end],
+        indent,
+        "^".repeat(width.max(1))
     )
 }
 
 pub fn parse(source: &str) -> Result<Ast, ParsingError> {
     let lexer = Lexer::new();
-    let mut tokens = lexer.lex(source)?;
+    let tokens = lexer.lex(source)?;
+    parse_tokens(source, tokens)
+}
 
+// Parses the tokens lexed from `source`
+pub fn parse_tokens(source: &str, mut tokens: Vec<TokenContainer>) -> Result<Ast, ParsingError> {
     let mut ast = Ast {
         statements: Vec::with_capacity(10),
     };
@@ -115,25 +211,34 @@ pub fn parse(source: &str) -> Result<Ast, ParsingError> {
     let mut cursor: usize = 0;
     let mut first_statement = true;
     while cursor < tokens.len() {
-        if first_statement == false {
-            let mut at_least_one_semicolon = false;
-            while expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Semicolon) {
-                cursor += 1;
-                at_least_one_semicolon = true;
-            }
-            if first_statement == false && at_least_one_semicolon == false {
-                return Err(ParsingError::Delimiter {
-                    msg: help_message(
+        // Empty statements, such as a bare `;` or one holding only comments, are skipped
+        let mut at_least_one_semicolon = false;
+        while expect_token(&mut tokens[cursor..].iter(), cursor, Token::Semicolon) {
+            cursor += 1;
+            at_least_one_semicolon = true;
+        }
+        if cursor >= tokens.len() {
+            break;
+        }
+        if !first_statement && !at_least_one_semicolon {
+            return Err(ParsingError::Delimiter {
+                msg: format!(
+                    "{}\n{}",
+                    help_message(
                         &tokens,
                         cursor,
                         "Expected Semicolon Delimiter between Statements".to_owned(),
                     ),
-                    cursor,
-                });
-            }
+                    underline_token(source, &tokens, cursor)
+                ),
+                cursor,
+            });
         }
         match parse_statement(&mut tokens, cursor, Token::Semicolon) {
-            Ok((statement, new_cursor)) => {
+            Ok((mut statement, new_cursor)) => {
+                if let Statement::CreateViewStatement(create_view) = &mut statement {
+                    create_view.definition = query_text(source, &tokens[cursor..new_cursor]);
+                }
                 cursor = new_cursor;
 
                 ast.statements.push(statement);
@@ -141,9 +246,16 @@ pub fn parse(source: &str) -> Result<Ast, ParsingError> {
             }
 
             Err(err) => {
+                // Keep pointing at the token that failed to parse rather than the
+                // start of the statement, where the message says it failed
+                let err_cursor = match err {
+                    ParsingError::General { msg: _, cursor }
+                    | ParsingError::Delimiter { msg: _, cursor } => cursor,
+                    ParsingError::Lexing { msg: _, loc: _ } => cursor,
+                };
                 return Err(ParsingError::Delimiter {
-                    msg: help_message(&tokens, cursor, err.to_string()),
-                    cursor,
+                    msg: format!("{}\n{}", err, underline_token(source, &tokens, err_cursor)),
+                    cursor: err_cursor,
                 });
             }
         }
@@ -155,6 +267,45 @@ pub fn parse(source: &str) -> Result<Ast, ParsingError> {
     return Ok(ast);
 }
 
+// The text of the query of `CREATE VIEW name AS query`, from its tokens
+fn query_text(source: &str, tokens: &[TokenContainer]) -> String {
+    let query = match tokens.iter().position(|token| token.token == Token::As) {
+        Some(position) => &tokens[position + 1..],
+        None => return String::new(),
+    };
+    let last = query
+        .iter()
+        .rev()
+        .find(|token| token.token != Token::Semicolon);
+    match (query.first(), last) {
+        (Some(first), Some(last)) => source
+            .get(first.span.start..last.span.end)
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+// Parses a condition on its own, the way the WHERE clause of a query is parsed
+pub fn parse_condition(source: &str) -> Result<Expression, ParsingError> {
+    let tokens = Lexer::new().lex(source)?;
+    match parse_expression(&tokens, 0, &vec![Token::Semicolon], 0, true, false) {
+        Ok((expression, cursor)) if cursor >= tokens.len() => Ok(expression),
+        Ok((_, cursor)) => Err(ParsingError::General {
+            msg: help_message(&tokens, cursor, "Expected end of condition".to_owned()),
+            cursor,
+        }),
+        Err(err) => Err(expression_error(
+            err,
+            0,
+            ParsingError::General {
+                msg: help_message(&tokens, 0, "Expected a condition".to_owned()),
+                cursor: 0,
+            },
+        )),
+    }
+}
+
 fn parse_statement(
     tokens: &mut Vec<TokenContainer>,
     initial_cursor: usize,
@@ -165,14 +316,43 @@ fn parse_statement(
     if let Some(first_token) = tokens.get(cursor) {
         match first_token.token {
             Token::Select => {
-                // Look for a SELECT statement
-                match parse_select_statement(tokens, cursor, delimiter.clone()) {
-                    Ok((select, new_cursor)) => {
-                        return Ok((Statement::SelectStatement(select), new_cursor));
+                // Look for a SELECT statement, which creates a table when it has INTO
+                match parse_select_into_statement(tokens, cursor, delimiter.clone()) {
+                    Ok((select, None, new_cursor)) => {
+                        Ok((Statement::SelectStatement(select), new_cursor))
                     }
+                    Ok((query, Some((name, is_temporary)), new_cursor)) => Ok((
+                        Statement::CreateTableAsStatement(CreateTableAsStatement {
+                            name,
+                            columns: vec![],
+                            query,
+                            with_data: true,
+                            is_temporary,
+                        }),
+                        new_cursor,
+                    )),
                     Err(err) => Err(err),
                 }
             }
+            Token::Values => {
+                // A VALUES list on its own is run like `SELECT * FROM (VALUES ...)`
+                let (rows, new_cursor) = parse_values_list(tokens, cursor)?;
+                let mut select = SelectStatement::new();
+                select.items.push(SelectItem {
+                    expression: Expression::new(),
+                    as_clause: None,
+                    asterisk: true,
+                    asterisk_source: None,
+                    asterisk_modifiers: None,
+                });
+                select.from.push(RowDataSource::Values {
+                    rows,
+                    as_clause: "*VALUES*".to_string(),
+                    column_aliases: vec![],
+                    joins: vec![],
+                });
+                Ok((Statement::SelectStatement(select), new_cursor))
+            }
             Token::Insert => {
                 // Look for an INSERT statement
                 match parse_insert_statement(tokens, cursor, delimiter.clone()) {
@@ -182,18 +362,24 @@ fn parse_statement(
                     Err(err) => Err(err),
                 }
             }
-            Token::Delete => Err(ParsingError::General {
-                msg: "Delete not implemented".to_string(),
-                cursor,
-            }),
-            Token::Update => Err(ParsingError::General {
-                msg: "Update not implemented".to_string(),
-                cursor,
-            }),
-            Token::Alter => Err(ParsingError::General {
-                msg: "Alter not implemented".to_string(),
-                cursor,
-            }),
+            Token::Delete => {
+                let (delete, new_cursor) =
+                    parse_delete_statement(tokens, cursor, delimiter.clone())?;
+                Ok((Statement::DeleteStatement(delete), new_cursor))
+            }
+            Token::With => {
+                let (with, new_cursor) = parse_with_statement(tokens, cursor, delimiter.clone())?;
+                Ok((Statement::WithStatement(with), new_cursor))
+            }
+            Token::Update => {
+                let (update, new_cursor) =
+                    parse_update_statement(tokens, cursor, delimiter.clone())?;
+                Ok((Statement::UpdateStatement(update), new_cursor))
+            }
+            Token::Alter => {
+                let (alter, new_cursor) = parse_alter_table_statement(tokens, cursor)?;
+                Ok((Statement::AlterTableStatement(alter), new_cursor))
+            }
             Token::IdentifierValue { value: _ } => Err(ParsingError::General {
                 msg: "Assignment not implemented".to_string(),
                 cursor,
@@ -213,7 +399,7 @@ fn parse_statement(
                         Token::Unique => match tokens.get(cursor + 2) {
                             Some(TokenContainer {
                                 token: Token::Index,
-                                loc: _,
+                                ..
                             }) => {
                                 // Look for a CREATE UNIQUE INDEX statement
                                 match parse_create_index_statement(
@@ -230,7 +416,7 @@ fn parse_statement(
                             }
                             Some(TokenContainer {
                                 token: Token::Constraint,
-                                loc: _,
+                                ..
                             }) => Err(ParsingError::General {
                                 msg: "Create constraint not implemented".to_string(),
                                 cursor,
@@ -244,14 +430,24 @@ fn parse_statement(
                             msg: "Create constraint not implemented".to_string(),
                             cursor,
                         }),
-                        Token::Table => {
-                            // Look for a CREATE TABLE statement
-                            match parse_create_table_statement(tokens, cursor, delimiter.clone()) {
-                                Ok((create_table, new_cursor)) => {
-                                    Ok((Statement::CreateTableStatement(create_table), new_cursor))
-                                }
-                                Err(err) => (Err(err)),
-                            }
+                        Token::Table | Token::Temporary => {
+                            // Look for a CREATE [TEMPORARY] TABLE statement
+                            parse_create_table_statement(tokens, cursor, delimiter.clone())
+                        }
+                        Token::IdentifierValue { ref value } if value.as_str() == "sequence" => {
+                            let (create, new_cursor) =
+                                parse_create_sequence_statement(tokens, cursor)?;
+                            Ok((Statement::CreateSequenceStatement(create), new_cursor))
+                        }
+                        Token::IdentifierValue { ref value } if value.as_str() == "trigger" => {
+                            let (create, new_cursor) =
+                                parse_create_trigger_statement(tokens, cursor)?;
+                            Ok((Statement::CreateTriggerStatement(create), new_cursor))
+                        }
+                        Token::IdentifierValue { ref value } if value.as_str() == "view" => {
+                            let (create, new_cursor) =
+                                parse_create_view_statement(tokens, cursor, delimiter.clone())?;
+                            Ok((Statement::CreateViewStatement(create), new_cursor))
                         }
                         _ => Err(ParsingError::General {
                             msg: "Invalid Create Statement".to_string(),
@@ -266,12 +462,85 @@ fn parse_statement(
                 }
             }
             Token::Drop => {
+                match tokens.get(cursor + 1).map(|token| &token.token) {
+                    Some(Token::Index) => {
+                        let (drop, new_cursor) = parse_drop_index_statement(tokens, cursor)?;
+                        return Ok((Statement::DropIndexStatement(drop), new_cursor));
+                    }
+                    Some(Token::IdentifierValue { value }) if value.as_str() == "sequence" => {
+                        let (drop, new_cursor) = parse_drop_sequence_statement(tokens, cursor)?;
+                        return Ok((Statement::DropSequenceStatement(drop), new_cursor));
+                    }
+                    Some(Token::IdentifierValue { value }) if value.as_str() == "trigger" => {
+                        let (drop, new_cursor) = parse_drop_trigger_statement(tokens, cursor)?;
+                        return Ok((Statement::DropTriggerStatement(drop), new_cursor));
+                    }
+                    Some(Token::IdentifierValue { value }) if value.as_str() == "view" => {
+                        let (drop, new_cursor) = parse_drop_view_statement(tokens, cursor)?;
+                        return Ok((Statement::DropViewStatement(drop), new_cursor));
+                    }
+                    _ => {}
+                }
                 // Look for an DROP statement
                 match parse_drop_table_statement(tokens, cursor, delimiter.clone()) {
                     Ok((drop, new_cursor)) => Ok((Statement::DropTableStatement(drop), new_cursor)),
                     Err(err) => (Err(err)),
                 }
             }
+            Token::Reindex => {
+                let (reindex, new_cursor) = parse_reindex_statement(tokens, cursor)?;
+                Ok((Statement::ReindexStatement(reindex), new_cursor))
+            }
+            Token::Vacuum => {
+                let (vacuum, new_cursor) = parse_vacuum_statement(tokens, cursor);
+                Ok((Statement::VacuumStatement(vacuum), new_cursor))
+            }
+            Token::Explain => {
+                let (explain, new_cursor) =
+                    parse_explain_statement(tokens, cursor, delimiter.clone())?;
+                Ok((Statement::ExplainStatement(explain), new_cursor))
+            }
+            Token::Set => {
+                let (set, new_cursor) = parse_set_statement(tokens, cursor)?;
+                Ok((Statement::SetStatement(set), new_cursor))
+            }
+            Token::Show => {
+                let (show, new_cursor) = parse_show_statement(tokens, cursor)?;
+                Ok((Statement::ShowStatement(show), new_cursor))
+            }
+            Token::Reset => {
+                let (reset, new_cursor) = parse_reset_statement(tokens, cursor)?;
+                Ok((Statement::ResetStatement(reset), new_cursor))
+            }
+            Token::Listen => {
+                let (listen, new_cursor) = parse_listen_statement(tokens, cursor)?;
+                Ok((Statement::ListenStatement(listen), new_cursor))
+            }
+            Token::Notify => {
+                let (notify, new_cursor) = parse_notify_statement(tokens, cursor)?;
+                Ok((Statement::NotifyStatement(notify), new_cursor))
+            }
+            Token::Unlisten => {
+                let (unlisten, new_cursor) = parse_unlisten_statement(tokens, cursor)?;
+                Ok((Statement::UnlistenStatement(unlisten), new_cursor))
+            }
+            Token::Declare => {
+                let (declare, new_cursor) =
+                    parse_declare_cursor_statement(tokens, cursor, delimiter.clone())?;
+                Ok((Statement::DeclareCursorStatement(declare), new_cursor))
+            }
+            Token::Fetch => {
+                let (fetch, new_cursor) = parse_fetch_statement(tokens, cursor)?;
+                Ok((Statement::FetchStatement(fetch), new_cursor))
+            }
+            Token::Close => {
+                let (close, new_cursor) = parse_close_statement(tokens, cursor)?;
+                Ok((Statement::CloseStatement(close), new_cursor))
+            }
+            Token::Begin | Token::Commit | Token::Rollback => {
+                let (transaction, new_cursor) = parse_transaction_statement(tokens, cursor);
+                Ok((Statement::TransactionStatement(transaction), new_cursor))
+            }
             _ => Err(ParsingError::General {
                 msg: help_message(tokens, cursor, "Expected a valid statement".to_string()),
                 cursor,
@@ -285,14 +554,16 @@ fn parse_statement(
     }
 }
 
+// Parses the columns of a CREATE TABLE along with the CHECK constraints given among them
 fn parse_column_definitions(
     tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
     delimiter: Token,
-) -> Result<(Vec<ColumnDefinition>, usize), ParsingError> {
+) -> Result<(Vec<ColumnDefinition>, Vec<CheckConstraint>, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     let mut column_definitions: Vec<ColumnDefinition> = Vec::with_capacity(10);
+    let mut checks: Vec<CheckConstraint> = vec![];
 
     loop {
         if cursor >= tokens.len() {
@@ -309,7 +580,7 @@ fn parse_column_definitions(
         }
 
         // Look for a comma
-        if column_definitions.len() > 0 {
+        if !column_definitions.is_empty() || !checks.is_empty() {
             if tokens[cursor].token != Token::Comma {
                 return Err(ParsingError::General {
                     msg: help_message(tokens, cursor, "Expected Comma".to_owned()),
@@ -319,10 +590,19 @@ fn parse_column_definitions(
 
             cursor += 1;
         }
+
+        // Look for a table constraint
+        if let Some(Token::Constraint) | Some(Token::Check) = tokens.get(cursor).map(|t| &t.token) {
+            let (check, new_cursor) = parse_check_constraint(tokens, cursor)?;
+            checks.push(check);
+            cursor = new_cursor;
+            continue;
+        }
+
         // Look for a column name
-        let col_name = match &tokens[cursor].token {
-            Token::IdentifierValue { value } => value,
-            _ => {
+        let col_name = match parse_name(tokens, cursor) {
+            Some(name) => name,
+            None => {
                 return Err(ParsingError::General {
                     msg: help_message(tokens, cursor, "Expected Column Name".to_owned()),
                     cursor,
@@ -332,41 +612,315 @@ fn parse_column_definitions(
         cursor += 1;
 
         // Look for a column type
-        if !tokens[cursor].token.is_datatype() {
+        let type_token = tokens.get(cursor).ok_or_else(|| ParsingError::General {
+            msg: "Unexpected end of file.".to_string(),
+            cursor,
+        })?;
+        let serial = serial_type(&type_token.token);
+        let col_type = match serial.clone() {
+            Some(token) => TokenContainer {
+                token,
+                ..type_token.clone()
+            },
+            None if type_token.token.is_datatype() => type_token.clone(),
+            None => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Column Type".to_owned()),
+                    cursor,
+                });
+            }
+        };
+        cursor += 1;
+
+        // Look for the length of a character type
+        let mut type_modifier = None;
+        if expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+            if !matches!(col_type.token, Token::Varchar | Token::Char) {
+                return Err(ParsingError::General {
+                    msg: help_message(
+                        tokens,
+                        cursor,
+                        format!(
+                            "Type modifier is not allowed for type {}",
+                            col_type.token.generate_code()
+                        ),
+                    ),
+                    cursor,
+                });
+            }
+            let length = match tokens.get(cursor + 1).map(|token| &token.token) {
+                Some(Token::NumericValue { value }) => value.parse::<usize>().ok(),
+                _ => None,
+            };
+            match (length, tokens.get(cursor + 2).map(|token| &token.token)) {
+                (Some(length), Some(Token::RightParenthesis)) if length > 0 => {
+                    type_modifier = Some(length);
+                    cursor += 3;
+                }
+                _ => {
+                    return Err(ParsingError::General {
+                        msg: help_message(
+                            tokens,
+                            cursor + 1,
+                            "Expected Positive Length in Parentheses".to_owned(),
+                        ),
+                        cursor: cursor + 1,
+                    });
+                }
+            }
+        }
+
+        // Look for column constraints, in any order
+        let mut is_primary_key = false;
+        let mut default = None;
+        let mut not_null = false;
+        let mut generated = None;
+        let mut collation = None;
+        loop {
+            match tokens.get(cursor).map(|token| &token.token) {
+                Some(Token::Primary)
+                    if tokens.get(cursor + 1).map(|t| &t.token) == Some(&Token::Key) =>
+                {
+                    is_primary_key = true;
+                    cursor += 2;
+                }
+                Some(Token::Not)
+                    if tokens.get(cursor + 1).map(|t| &t.token) == Some(&Token::Null) =>
+                {
+                    not_null = true;
+                    cursor += 2;
+                }
+                Some(Token::Null) => {
+                    not_null = false;
+                    cursor += 1;
+                }
+                Some(Token::Default) => {
+                    cursor += 1;
+                    let delimiters = vec![
+                        Token::Comma,
+                        delimiter.clone(),
+                        Token::Primary,
+                        Token::Not,
+                        Token::Null,
+                        Token::Default,
+                        Token::Constraint,
+                        Token::Check,
+                        Token::Generated,
+                        Token::Collate,
+                    ];
+                    let (expression, new_cursor) =
+                        match parse_expression(tokens, cursor, &delimiters, 0, true, false) {
+                            Ok(value) => value,
+                            Err(err) => {
+                                return Err(expression_error(
+                                    err,
+                                    cursor,
+                                    ParsingError::General {
+                                        msg: help_message(
+                                            tokens,
+                                            cursor,
+                                            "Expected DEFAULT expression".to_owned(),
+                                        ),
+                                        cursor,
+                                    },
+                                ));
+                            }
+                        };
+                    default = Some(expression);
+                    cursor = new_cursor;
+                }
+                Some(Token::Constraint) | Some(Token::Check) => {
+                    let (check, new_cursor) = parse_check_constraint(tokens, cursor)?;
+                    checks.push(check);
+                    cursor = new_cursor;
+                }
+                Some(Token::Generated) => {
+                    let (expression, new_cursor) = parse_generated_column(tokens, cursor)?;
+                    generated = Some(expression);
+                    cursor = new_cursor;
+                }
+                Some(Token::Collate) => match tokens.get(cursor + 1).map(|t| &t.token) {
+                    Some(Token::IdentifierValue { value }) => {
+                        collation = Some(value.to_string());
+                        cursor += 2;
+                    }
+                    _ => {
+                        return Err(ParsingError::General {
+                            msg: help_message(
+                                tokens,
+                                cursor + 1,
+                                "Expected Collation Name".to_owned(),
+                            ),
+                            cursor: cursor + 1,
+                        });
+                    }
+                },
+                _ => break,
+            }
+        }
+
+        column_definitions.push(ColumnDefinition {
+            name: col_name,
+            data_type: col_type,
+            type_modifier,
+            is_primary_key,
+            default,
+            not_null,
+            generated,
+            collation,
+            serial: serial.is_some(),
+        });
+    }
+
+    Ok((column_definitions, checks, cursor))
+}
+
+// The integer type of SMALLSERIAL, SERIAL or BIGSERIAL. Like in Postgres they are not
+// keywords, only names of types.
+fn serial_type(token: &Token) -> Option<Token> {
+    match token {
+        Token::IdentifierValue { value } => match value.as_str() {
+            "smallserial" | "serial2" => Some(Token::SmallInt),
+            "serial" | "serial4" => Some(Token::Int),
+            "bigserial" | "serial8" => Some(Token::BigInt),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Parses `GENERATED ALWAYS AS (expression) STORED`
+fn parse_generated_column(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(Expression, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    for (token, expected) in &[
+        (Token::Generated, "Expected GENERATED"),
+        (Token::Always, "Expected ALWAYS"),
+        (Token::As, "Expected AS"),
+        (Token::LeftParenthesis, "Expected Left Parenthesis"),
+    ] {
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, token.clone()) {
             return Err(ParsingError::General {
-                msg: help_message(tokens, cursor, "Expected Column Type".to_owned()),
+                msg: help_message(tokens, cursor, expected.to_string()),
                 cursor,
             });
         }
-
-        let col_type = &tokens[cursor];
         cursor += 1;
+    }
 
-        // Look for primary key
-        let mut is_primary_key = false;
-        if cursor + 1 < tokens.len() {
-            if &tokens[cursor].token == &Token::Primary && &tokens[cursor + 1].token == &Token::Key
-            {
-                is_primary_key = true;
-                cursor += 2;
+    let delimiters = vec![Token::RightParenthesis];
+    let (expression, new_cursor) =
+        match parse_expression(tokens, cursor, &delimiters, 0, true, false) {
+            Ok(value) => value,
+            Err(err) => {
+                let msg = "Expected GENERATED expression".to_owned();
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, msg),
+                        cursor,
+                    },
+                ));
             }
+        };
+    cursor = new_cursor;
+
+    for (token, expected) in &[
+        (Token::RightParenthesis, "Expected Right Parenthesis"),
+        (Token::Stored, "Expected STORED"),
+    ] {
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, token.clone()) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, expected.to_string()),
+                cursor,
+            });
         }
+        cursor += 1;
+    }
 
-        column_definitions.push(ColumnDefinition {
-            name: col_name.clone(),
-            data_type: col_type.clone(),
-            is_primary_key,
+    Ok((expression, cursor))
+}
+
+// Parses `[CONSTRAINT name] CHECK (expression)`
+fn parse_check_constraint(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(CheckConstraint, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let mut name = None;
+    if expect_token(&mut tokens[cursor..].iter(), cursor, Token::Constraint) {
+        cursor += 1;
+        name = match parse_name(tokens, cursor) {
+            Some(name) => Some(name),
+            None => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Constraint Name".to_owned()),
+                    cursor,
+                });
+            }
+        };
+        cursor += 1;
+    }
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Check) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected CHECK".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let delimiters = vec![Token::RightParenthesis];
+    let (expression, new_cursor) =
+        match parse_expression(tokens, cursor, &delimiters, 0, true, false) {
+            Ok(value) => value,
+            Err(err) => {
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected CHECK expression".to_owned()),
+                        cursor,
+                    },
+                ));
+            }
+        };
+    cursor = new_cursor;
+
+    if !expect_token(
+        &mut tokens[cursor..].iter(),
+        cursor,
+        Token::RightParenthesis,
+    ) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
+            cursor,
         });
     }
+    cursor += 1;
 
-    Ok((column_definitions, cursor))
+    Ok((CheckConstraint { name, expression }, cursor))
 }
 
+// Parses both CREATE TABLE with column definitions and CREATE TABLE ... AS SELECT
 fn parse_create_table_statement(
     tokens: &mut Vec<TokenContainer>,
     initial_cursor: usize,
-    _: Token,
-) -> Result<(CreateTableStatement, usize), ParsingError> {
+    delimiter: Token,
+) -> Result<(Statement, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     if !expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Create) {
@@ -377,6 +931,11 @@ fn parse_create_table_statement(
     }
     cursor += 1;
 
+    let is_temporary = expect_token(&mut tokens[cursor..].iter(), cursor, Token::Temporary);
+    if is_temporary {
+        cursor += 1;
+    }
+
     if !expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Table) {
         return Err(ParsingError::General {
             msg: "Expected table keyword".to_string(),
@@ -385,10 +944,9 @@ fn parse_create_table_statement(
     }
     cursor += 1;
 
-    let curr_token = tokens[cursor].token.clone();
-    let name = match curr_token {
-        Token::IdentifierValue { value } => value,
-        _ => {
+    let name = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
             return Err(ParsingError::General {
                 msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
                 cursor,
@@ -396,7 +954,20 @@ fn parse_create_table_statement(
         }
     };
     cursor += 1;
-    if tokens[cursor].token != Token::LeftParenthesis {
+
+    // A list of bare names, rather than column definitions, can only come before AS
+    let is_name_list = matches!(
+        tokens.get(cursor + 2).map(|token| &token.token),
+        Some(Token::Comma) | Some(Token::RightParenthesis)
+    );
+    if is_as_query(tokens, cursor) || is_name_list {
+        let (mut statement, new_cursor) =
+            parse_create_table_as_statement(tokens, cursor, name, delimiter)?;
+        statement.is_temporary = is_temporary;
+        return Ok((Statement::CreateTableAsStatement(statement), new_cursor));
+    }
+
+    if tokens.get(cursor).map(|token| &token.token) != Some(&Token::LeftParenthesis) {
         return Err(ParsingError::General {
             msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
             cursor,
@@ -404,10 +975,11 @@ fn parse_create_table_statement(
     }
     cursor += 1;
 
-    let (cols, new_cursor) = parse_column_definitions(&tokens, cursor, Token::RightParenthesis)?;
+    let (cols, checks, new_cursor) =
+        parse_column_definitions(tokens, cursor, Token::RightParenthesis)?;
     cursor = new_cursor;
 
-    if tokens[cursor].token != Token::RightParenthesis {
+    if tokens.get(cursor).map(|token| &token.token) != Some(&Token::RightParenthesis) {
         return Err(ParsingError::General {
             msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
             cursor,
@@ -415,20 +987,147 @@ fn parse_create_table_statement(
     }
     cursor += 1;
 
-    Ok((CreateTableStatement { name, cols }, cursor))
+    Ok((
+        Statement::CreateTableStatement(CreateTableStatement {
+            name,
+            cols,
+            checks,
+            is_temporary,
+        }),
+        cursor,
+    ))
 }
 
-fn parse_create_index_statement(
-    tokens: &mut Vec<TokenContainer>,
+// Parses `name, ...)`, the list of column names after an opening parenthesis
+fn parse_column_names(
+    tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
-    delimiter: Token,
-) -> Result<(CreateIndexStatement, usize), ParsingError> {
+) -> Result<(Vec<String>, usize), ParsingError> {
     let mut cursor = initial_cursor;
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::Create,
-    }) = tokens.get(cursor)
-    {
+
+    let mut columns = vec![];
+    loop {
+        match parse_name(tokens, cursor) {
+            Some(column) => columns.push(column),
+            None => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Column Name".to_owned()),
+                    cursor,
+                });
+            }
+        }
+        cursor += 1;
+        match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::Comma) => cursor += 1,
+            Some(Token::RightParenthesis) => return Ok((columns, cursor + 1)),
+            _ => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
+                    cursor,
+                });
+            }
+        }
+    }
+}
+
+fn is_as_query(tokens: &[TokenContainer], cursor: usize) -> bool {
+    matches!(
+        tokens.get(cursor).map(|token| &token.token),
+        Some(Token::As)
+    )
+}
+
+// Parses what follows the table name in CREATE TABLE name [(columns)] AS SELECT ...
+// [WITH [NO] DATA]
+fn parse_create_table_as_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    name: String,
+    delimiter: Token,
+) -> Result<(CreateTableAsStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let mut columns = vec![];
+    if let Some(TokenContainer {
+        token: Token::LeftParenthesis,
+        ..
+    }) = tokens.get(cursor)
+    {
+        let (names, new_cursor) = parse_column_names(tokens, cursor + 1)?;
+        columns = names;
+        cursor = new_cursor;
+    }
+
+    if !is_as_query(tokens, cursor) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected AS".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    // The query ends at a WITH outside of parentheses, it knows nothing of the clause
+    let mut depth = 0;
+    let query_end = tokens[cursor..]
+        .iter()
+        .position(|token| {
+            match token.token {
+                Token::LeftParenthesis => depth += 1,
+                Token::RightParenthesis => depth -= 1,
+                _ => {}
+            }
+            depth == 0 && (token.token == Token::With || token.token == delimiter)
+        })
+        .map_or(tokens.len(), |position| cursor + position);
+    let (query, new_cursor) =
+        parse_select_statement(&tokens[..query_end].to_vec(), cursor, delimiter)?;
+    cursor = new_cursor;
+
+    let mut with_data = true;
+    if let Some(TokenContainer {
+        token: Token::With, ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+        if let Some(TokenContainer {
+            token: Token::No, ..
+        }) = tokens.get(cursor)
+        {
+            with_data = false;
+            cursor += 1;
+        }
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Data) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected DATA".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+    }
+
+    Ok((
+        CreateTableAsStatement {
+            name,
+            columns,
+            query,
+            with_data,
+            is_temporary: false,
+        },
+        cursor,
+    ))
+}
+
+fn parse_create_index_statement(
+    tokens: &mut Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(CreateIndexStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+    if let Some(TokenContainer {
+        token: Token::Create,
+        ..
+    }) = tokens.get(cursor)
+    {
         cursor += 1;
     } else {
         return Err(ParsingError::General {
@@ -438,16 +1137,16 @@ fn parse_create_index_statement(
     }
     let mut is_unique = false;
     if let Some(TokenContainer {
-        loc: _,
         token: Token::Unique,
+        ..
     }) = tokens.get(cursor)
     {
         is_unique = true;
         cursor += 1;
     }
     if let Some(TokenContainer {
-        loc: _,
         token: Token::Index,
+        ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
@@ -458,13 +1157,9 @@ fn parse_create_index_statement(
         });
     }
     let name;
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::IdentifierValue { value },
-    }) = tokens.get(cursor)
-    {
+    if let Some(value) = parse_name(tokens, cursor) {
         cursor += 1;
-        name = value.clone();
+        name = value;
     } else {
         return Err(ParsingError::General {
             msg: "Expected index name".to_string(),
@@ -472,8 +1167,7 @@ fn parse_create_index_statement(
         });
     }
     if let Some(TokenContainer {
-        loc: _,
-        token: Token::On,
+        token: Token::On, ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
@@ -484,13 +1178,9 @@ fn parse_create_index_statement(
         });
     }
     let table;
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::IdentifierValue { value },
-    }) = tokens.get(cursor)
-    {
+    if let Some(value) = parse_name(tokens, cursor) {
         cursor += 1;
-        table = value.clone();
+        table = value;
     } else {
         return Err(ParsingError::General {
             msg: "Expected table name".to_string(),
@@ -499,12 +1189,16 @@ fn parse_create_index_statement(
     }
     let (expression, cursor) =
         match parse_expression(tokens, cursor, &vec![delimiter], 0, true, false) {
-            Some(value) => value,
-            None => {
-                return Err(ParsingError::General {
-                    msg: "Expected index expressions".to_string(),
+            Ok(value) => value,
+            Err(err) => {
+                return Err(expression_error(
+                    err,
                     cursor,
-                });
+                    ParsingError::General {
+                        msg: "Expected index expressions".to_string(),
+                        cursor,
+                    },
+                ));
             }
         };
 
@@ -520,25 +1214,38 @@ fn parse_create_index_statement(
     ))
 }
 
+// Why an expression expected at `cursor` couldn't be parsed. Where nothing at it even
+// starts one `expected`, the caller's error saying what was expected there, is the more
+// telling; past it the expression's own error is.
+fn expression_error(err: ParsingError, cursor: usize, expected: ParsingError) -> ParsingError {
+    match err {
+        ParsingError::General { cursor: at, .. } if at <= cursor => expected,
+        err => err,
+    }
+}
+
 fn parse_expressions(
     tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
     delimiters: &Vec<Token>,
-) -> Option<(Vec<Expression>, usize)> {
+) -> Result<(Vec<Expression>, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     let mut expressions: Vec<Expression> = Vec::with_capacity(10);
 
     loop {
         if cursor >= tokens.len() {
-            return None;
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected closing parenthesis".to_owned()),
+                cursor,
+            });
         }
 
         // Look for delimiter
         let current_token = &tokens[cursor];
         for delimiter in delimiters {
             if delimiter == &current_token.token {
-                return Some((expressions, cursor));
+                return Ok((expressions, cursor));
             }
         }
 
@@ -546,29 +1253,24 @@ fn parse_expressions(
         if expressions.len() > 0 {
             let mut tokens_iter = tokens[cursor..].into_iter();
             if !expect_token(&mut tokens_iter, cursor, Token::Comma) {
-                help_message(tokens, cursor, "Expected Comma".to_owned());
-                return None;
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Comma".to_owned()),
+                    cursor,
+                });
             }
 
             cursor += 1;
         }
 
         // Look for expression
-        let (expression, new_cursor);
-        if let Some((expression_, new_cursor_)) = parse_expression(
+        let (expression, new_cursor) = parse_expression(
             tokens,
             cursor,
             &vec![Token::Comma, Token::RightParenthesis],
-            tokens[cursor].binding_power(),
+            0,
             true,
             false,
-        ) {
-            expression = expression_;
-            new_cursor = new_cursor_;
-        } else {
-            help_message(tokens, cursor, "Expected expression".to_owned());
-            return None;
-        }
+        )?;
         cursor = new_cursor;
         expressions.push(expression);
     }
@@ -581,25 +1283,25 @@ fn parse_expression(
     min_binding_power: u32,
     is_top_level: bool,
     takes_as_clause: bool,
-) -> Option<(Expression, usize)> {
+) -> Result<(Expression, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     let mut expression;
 
     if let Some(TokenContainer {
-        loc: _,
         token: Token::LeftParenthesis,
+        ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
 
         if let Some(TokenContainer {
-            loc: _,
             token: Token::Select,
+            ..
         }) = tokens.get(cursor)
         {
             let (select_statement, new_cursor) =
-                parse_select_statement(tokens, cursor, Token::RightParenthesis).ok()?;
+                parse_select_statement(tokens, cursor, Token::RightParenthesis)?;
             expression = Expression::SubSelect(Box::new(select_statement));
             cursor = new_cursor;
         } else {
@@ -607,118 +1309,60 @@ fn parse_expression(
                 tokens,
                 cursor,
                 &vec![Token::RightParenthesis],
-                min_binding_power,
+                0,
                 true,
                 false,
             ) {
-                Some((expression_, cursor_)) => {
+                Ok((expression_, cursor_)) => {
                     expression = expression_;
                     cursor = cursor_;
                 }
-                None => {
-                    let x = help_message(
-                        tokens,
+                Err(err) => {
+                    let msg = "Expected expression after opening parenthesis".to_string();
+                    return Err(expression_error(
+                        err,
                         cursor,
-                        "Expected expression after opening parenthesis".to_string(),
-                    );
-                    println!("{}", x);
-                    return None;
+                        ParsingError::General {
+                            msg: help_message(tokens, cursor, msg),
+                            cursor,
+                        },
+                    ));
                 }
             };
         }
 
         if let Some(TokenContainer {
-            loc: _,
             token: Token::RightParenthesis,
+            ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
         } else {
-            let x = help_message(tokens, cursor, "Expected closing parenthesis".to_owned());
-            println!("{}", x);
-            return None;
-        }
-    } else if cursor < tokens.len() && UNARY_OPERATORS.contains(&tokens[cursor].token) {
-        let operand;
-        let token = &tokens[cursor];
-        operand = token.token.clone();
-        cursor += 1;
-        let mut nested_un_ops = vec![operand.clone()];
-        let mut inner_exp;
-        loop {
-            if cursor < tokens.len() && UNARY_OPERATORS.contains(&tokens[cursor].token) {
-                nested_un_ops.push(tokens[cursor].token.clone());
-                cursor += 1;
-            } else {
-                break;
-            }
-        }
-        if let Some((expression_, cursor_)) = parse_literal_expression(tokens, cursor) {
-            inner_exp = expression_;
-            cursor = cursor_;
-        } else if let Some(TokenContainer {
-            token: Token::LeftParenthesis,
-            loc: _,
-        }) = tokens.get(cursor)
-        {
-            cursor += 1;
-            if let Some((expression_, cursor_)) = parse_expression(
-                tokens,
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected closing parenthesis".to_owned()),
                 cursor,
-                &vec![Token::RightParenthesis],
-                min_binding_power,
-                true,
-                takes_as_clause,
-            ) {
-                inner_exp = expression_;
-                cursor = cursor_;
-            } else {
-                let x = help_message(
-                    tokens,
-                    cursor,
-                    "Expected expression after opening parenthesis".to_string(),
-                );
-                println!("{}", x);
-                return None;
-            }
-
-            if let Some(TokenContainer {
-                loc: _,
-                token: Token::RightParenthesis,
-            }) = tokens.get(cursor)
-            {
-                cursor += 1;
-            } else {
-                let x = help_message(tokens, cursor, "Expected closing parenthesis".to_owned());
-                println!("{}", x);
-                return None;
-            }
-        } else {
-            return None;
-        }
-
-        if let Some(operand) = nested_un_ops.pop() {
-            inner_exp = Expression::Unary(UnaryExpression {
-                first: Box::from(inner_exp),
-                operand,
-            });
-        } else {
-            return None;
-        }
-        while let Some(operand) = nested_un_ops.pop() {
-            inner_exp = Expression::Unary(UnaryExpression {
-                first: Box::from(inner_exp),
-                operand,
             });
         }
-        expression = inner_exp;
+    } else if let Some(prefix_binding_power) = tokens
+        .get(cursor)
+        .and_then(|token| token.token.prefix_binding_power())
+    {
+        let operand = tokens[cursor].token.clone();
+        let (first, new_cursor) = parse_expression(
+            tokens,
+            cursor + 1,
+            delimiters,
+            prefix_binding_power,
+            false,
+            takes_as_clause,
+        )?;
+        expression = Expression::Unary(UnaryExpression {
+            first: Box::new(first),
+            operand,
+        });
+        cursor = new_cursor;
     } else {
-        let (first_expression, new_cursor) = match parse_literal_expression(tokens, cursor) {
-            None => {
-                return None;
-            }
-            Some(value) => value,
-        };
+        let (first_expression, new_cursor) = parse_literal_expression(tokens, cursor)?;
         expression = first_expression;
         cursor = new_cursor;
     }
@@ -739,6 +1383,8 @@ fn parse_expression(
     }
 
     let mut last_cursor = cursor;
+    // The last operator applied, if it doesn't chain with those of its precedence
+    let mut non_associative: Option<Token> = None;
     'outer: while cursor < tokens.len() {
         if delimiters.contains(&tokens[cursor].token) {
             break 'outer;
@@ -749,65 +1395,183 @@ fn parse_expression(
 
         if let Some(TokenContainer {
             token: Token::IdentifierValue { value: _ },
-            loc: _,
+            ..
         }) = tokens.get(cursor)
         {
             if takes_as_clause {
                 break;
             }
         }
+
+        // `first IN (SELECT ...)` is `first = ANY (SELECT ...)`, and with NOT it is
+        // `first <> ALL (SELECT ...)`, NULLs included
+        let negated = match (tokens.get(cursor), tokens.get(cursor + 1)) {
+            (
+                Some(TokenContainer {
+                    token: Token::In, ..
+                }),
+                _,
+            ) => Some(false),
+            (
+                Some(TokenContainer {
+                    token: Token::Not, ..
+                }),
+                Some(TokenContainer {
+                    token: Token::In, ..
+                }),
+            ) => Some(true),
+            _ => None,
+        };
+        if let Some(negated) = negated {
+            if Token::In.binding_power() < min_binding_power {
+                break;
+            }
+            check_chain(tokens, cursor, &non_associative, &Token::In)?;
+            let (subquery, new_cursor) = parse_subquery(tokens, cursor + 1 + usize::from(negated))?;
+            expression = Expression::Quantified(QuantifiedExpression {
+                first: Box::new(expression),
+                operand: if negated {
+                    Token::NotEqual
+                } else {
+                    Token::Equal
+                },
+                all: negated,
+                set: QuantifiedSet::SubSelect(Box::new(subquery)),
+            });
+            non_associative = Some(Token::In);
+            cursor = new_cursor;
+            last_cursor = cursor;
+            continue;
+        }
+
+        // After an operand `~` is the regex match rather than bitwise NOT, and
+        // `[NOT] SIMILAR TO` is an operator spelled with keywords
         let mut operand = Token::Empty;
-        if cursor < tokens.len() && BINARY_OPERATORS.contains(&tokens[cursor].token) {
-            let token = &tokens[cursor];
-            operand = token.token.clone();
-            cursor += 1;
+        let mut negated = false;
+        match (
+            tokens.get(cursor).map(|t| &t.token),
+            tokens.get(cursor + 1).map(|t| &t.token),
+            tokens.get(cursor + 2).map(|t| &t.token),
+        ) {
+            (Some(Token::BitwiseNot), _, _) => {
+                operand = Token::RegexMatch;
+                cursor += 1;
+            }
+            (Some(Token::Similar), Some(Token::To), _) => {
+                operand = Token::Similar;
+                cursor += 2;
+            }
+            (Some(Token::Not), Some(Token::Similar), Some(Token::To)) => {
+                operand = Token::Similar;
+                negated = true;
+                cursor += 3;
+            }
+            (Some(token), _, _) if BINARY_OPERATORS.contains(token) => {
+                operand = token.clone();
+                cursor += 1;
+            }
+            _ => {}
+        }
+        if operand == Token::Empty {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected binary operator".to_owned()),
+                cursor,
+            });
+        }
+
+        let binding_power = operand.binding_power();
+        if binding_power < min_binding_power {
+            cursor = last_cursor;
+            break;
         }
+        check_chain(tokens, last_cursor, &non_associative, &operand)?;
+
         if operand == Token::TypeCast {
-            if cursor < tokens.len() && tokens[cursor].token.is_datatype() {
+            let typ = tokens
+                .get(cursor)
+                .filter(|token| token.token.is_datatype())
+                .and_then(|token| SqlType::from_token(token.token.clone()).ok());
+            if let Some(typ) = typ {
                 expression = Expression::Cast {
                     data: Box::new(expression),
-                    typ: SqlType::from_token(tokens[cursor].token.clone()).ok()?,
+                    typ,
                 };
                 cursor += 1;
+                last_cursor = cursor;
+                non_associative = None;
                 continue;
             } else {
-                let x = help_message(tokens, cursor, "Expected type for type cast".to_owned());
-                println!("{}", x);
-                return None;
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected type for type cast".to_owned()),
+                    cursor,
+                });
             }
         }
-        if operand == Token::Empty {
-            let x = help_message(tokens, cursor, "Expected binary operator".to_owned());
-            println!("{}", x);
-            return None;
+        if operand == Token::Collate {
+            if let Some(Token::IdentifierValue { value }) = tokens.get(cursor).map(|t| &t.token) {
+                expression = Expression::Collate {
+                    data: Box::new(expression),
+                    collation: value.to_string(),
+                };
+                cursor += 1;
+                last_cursor = cursor;
+                non_associative = None;
+                continue;
+            } else {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected collation name".to_owned()),
+                    cursor,
+                });
+            }
         }
 
-        let binding_power = operand.binding_power();
-        if binding_power < min_binding_power {
-            cursor = last_cursor;
-            break;
+        if let Some((quantified, new_cursor)) =
+            parse_quantified_expression(tokens, cursor, &expression, &operand)?
+        {
+            expression = quantified;
+            non_associative = Some(operand);
+            cursor = new_cursor;
+            last_cursor = cursor;
+            continue;
         }
 
+        // Operators of the same precedence to the right are left for this loop, grouping
+        // them from the left
         let (second_expression, new_cursor) = match parse_expression(
             tokens,
             cursor,
             delimiters,
-            binding_power,
+            binding_power + 1,
             false,
             takes_as_clause,
         ) {
-            None => {
-                let x = help_message(tokens, cursor, "Expected right operand".to_owned());
-                println!("{}", x);
-                return None;
+            Err(err) => {
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected right operand".to_owned()),
+                        cursor,
+                    },
+                ));
             }
-            Some(value) => value,
+            Ok(value) => value,
+        };
+        non_associative = match operand.associativity() {
+            Associativity::Left => None,
+            Associativity::None => Some(operand.clone()),
         };
         expression = Expression::Binary(BinaryExpression {
             first: Box::from(expression),
             second: Box::from(second_expression),
             operand,
         });
+        if negated {
+            expression = Expression::Unary(UnaryExpression {
+                first: Box::from(expression),
+                operand: Token::Not,
+            });
+        }
         cursor = new_cursor;
         last_cursor = cursor;
     }
@@ -826,209 +1590,2042 @@ fn parse_expression(
         });
     }
 
-    return Some((expression, cursor));
+    Ok((expression, cursor))
 }
 
-fn parse_literal_expression(
+// Refuses `operand` right after an operator of its precedence that doesn't chain, like
+// the second `<` of `a < b < c`
+fn check_chain(
     tokens: &Vec<TokenContainer>,
-    initial_cursor: usize,
-) -> Option<(Expression, usize)> {
-    let mut cursor = initial_cursor;
-
-    if let Some(tok) = tokens.get(cursor) {
-        match tok.token {
-            Token::IdentifierValue {
-                value: ref first_identifier,
-            } => {
-                cursor += 1;
-                let mut col_name = first_identifier.clone();
-                let mut table_name = None;
-                if let Some(TokenContainer {
-                    token: Token::Dot,
-                    loc: _,
-                }) = tokens.get(cursor)
-                {
-                    cursor += 1;
-                    if let Some(TokenContainer {
-                        token: Token::IdentifierValue { value },
-                        loc: _,
-                    }) = tokens.get(cursor)
-                    {
-                        cursor += 1;
-                        table_name = Some(col_name);
-                        col_name = value.clone();
-                    } else {
-                        return None;
-                    }
-                }
-                Some((
-                    Expression::TableColumn(TableColumn {
-                        col_name,
-                        table_name,
-                    }),
-                    cursor,
-                ))
-            }
-            Token::NumericValue { value: _ }
-            | Token::StringValue { value: _ }
-            | Token::BoolValue { value: _ }
-            | Token::Null => {
-                cursor += 1;
-                Some((
-                    Expression::Literal(LiteralExpression {
-                        literal: tok.token.clone(),
-                    }),
+    cursor: usize,
+    last: &Option<Token>,
+    operand: &Token,
+) -> Result<(), ParsingError> {
+    match last {
+        Some(last) if last.binding_power() == operand.binding_power() => {
+            Err(ParsingError::General {
+                msg: help_message(
+                    tokens,
                     cursor,
-                ))
-            }
-            _ => None,
+                    format!(
+                        "{} can't follow {}, operators of their precedence don't chain. Hint: \
+                        join the comparisons with AND, as in a < b AND b < c, or add \
+                        parentheses",
+                        operand.generate_code().to_uppercase(),
+                        last.generate_code().to_uppercase()
+                    ),
+                ),
+                cursor,
+            })
         }
-    } else {
-        None
+        _ => Ok(()),
     }
 }
 
-fn parse_insert_statement(
-    tokens: &mut Vec<TokenContainer>,
+// Parses the `ANY (SELECT ...)` or `ALL (SELECT ...)` after a comparison, if that is what
+// follows it. Gives None when it is not.
+fn parse_quantified_expression(
+    tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
-    _: Token,
-) -> Result<(InsertStatement, usize), ParsingError> {
+    first: &Expression,
+    operand: &Token,
+) -> Result<Option<(Expression, usize)>, ParsingError> {
     let mut cursor = initial_cursor;
 
-    // Look for INSERT
-    if !expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Insert) {
+    let all = match tokens.get(cursor) {
+        Some(TokenContainer {
+            token: Token::Any, ..
+        }) => false,
+        Some(TokenContainer {
+            token: Token::All, ..
+        }) => true,
+        _ => return Ok(None),
+    };
+    cursor += 1;
+    if !COMPARISON_OPERATORS.contains(operand) {
+        let msg = format!(
+            "Expected a comparison before {:?}",
+            tokens[cursor - 1].token
+        );
         return Err(ParsingError::General {
-            msg: "Not an insert statement".to_string(),
-            cursor,
+            msg: help_message(tokens, cursor - 1, msg),
+            cursor: cursor - 1,
         });
     }
-    cursor += 1;
+
+    let (subquery, cursor) = parse_subquery(tokens, cursor)?;
+
+    let quantified = Expression::Quantified(QuantifiedExpression {
+        first: Box::new(first.clone()),
+        operand: operand.clone(),
+        all,
+        set: QuantifiedSet::SubSelect(Box::new(subquery)),
+    });
+    Ok(Some((quantified, cursor)))
+}
+
+// Parses the `(SELECT ...)` of ANY, ALL, IN and EXISTS
+fn parse_subquery(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(SelectStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    match (tokens.get(cursor), tokens.get(cursor + 1)) {
+        (
+            Some(TokenContainer {
+                token: Token::LeftParenthesis,
+                ..
+            }),
+            Some(TokenContainer {
+                token: Token::Select,
+                ..
+            }),
+        ) => {}
+        _ => {
+            let msg = "Expected a subquery".to_owned();
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, msg),
+                cursor,
+            });
+        }
+    }
+    let (subquery, new_cursor) =
+        parse_select_statement(tokens, cursor + 1, Token::RightParenthesis)?;
+    cursor = new_cursor;
+    if !expect_token(
+        &mut tokens[cursor..].iter(),
+        cursor,
+        Token::RightParenthesis,
+    ) {
+        let msg = "Expected closing parenthesis".to_owned();
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, msg),
+            cursor,
+        });
+    }
+    Ok((subquery, cursor + 1))
+}
+
+fn parse_literal_expression(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(Expression, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    // EXISTS is not reserved, it only tests a subquery when one follows it
+    if let (
+        Some(TokenContainer {
+            token: Token::Exists,
+            ..
+        }),
+        Some(TokenContainer {
+            token: Token::LeftParenthesis,
+            ..
+        }),
+        Some(TokenContainer {
+            token: Token::Select,
+            ..
+        }),
+    ) = (
+        tokens.get(cursor),
+        tokens.get(cursor + 1),
+        tokens.get(cursor + 2),
+    ) {
+        let (subquery, cursor) = parse_subquery(tokens, cursor + 1)?;
+        return Ok((Expression::Exists(Box::new(subquery)), cursor));
+    }
+
+    if let Some((name, new_cursor)) = parse_qualified_name(tokens, cursor) {
+        if let Some(TokenContainer {
+            token: Token::LeftParenthesis,
+            ..
+        }) = tokens.get(new_cursor)
+        {
+            let (function_call, cursor) = parse_function_call(tokens, new_cursor + 1, name)?;
+            return Ok((Expression::FunctionCall(function_call), cursor));
+        }
+    }
+    if let Some((table_column, cursor)) = parse_table_column(tokens, cursor) {
+        return Ok((Expression::TableColumn(table_column), cursor));
+    }
+
+    match tokens.get(cursor) {
+        Some(tok)
+            if matches!(
+                tok.token,
+                Token::NumericValue { .. }
+                    | Token::StringValue { .. }
+                    | Token::BoolValue { .. }
+                    | Token::Parameter { .. }
+                    | Token::Null
+                    | Token::Default
+            ) =>
+        {
+            cursor += 1;
+            Ok((
+                Expression::Literal(LiteralExpression {
+                    literal: tok.token.clone(),
+                }),
+                cursor,
+            ))
+        }
+        _ => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected expression".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+// Parses the arguments of a call to `name`, starting right after the opening parenthesis,
+// and the OVER clause that may follow them
+fn parse_function_call(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    name: String,
+) -> Result<(FunctionCall, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let mut call = FunctionCall {
+        name,
+        args: vec![],
+        asterisk: false,
+        distinct: false,
+        filter: None,
+        over: None,
+    };
+    if let Some(TokenContainer {
+        token: Token::Distinct,
+        ..
+    }) = tokens.get(cursor)
+    {
+        call.distinct = true;
+        cursor += 1;
+    }
+    match (tokens.get(cursor), tokens.get(cursor + 1)) {
+        (
+            Some(TokenContainer {
+                token: Token::RightParenthesis,
+                ..
+            }),
+            _,
+        ) if !call.distinct => cursor += 1,
+        (
+            Some(TokenContainer {
+                token: Token::Asterisk,
+                ..
+            }),
+            Some(TokenContainer {
+                token: Token::RightParenthesis,
+                ..
+            }),
+        ) if !call.distinct => {
+            call.asterisk = true;
+            cursor += 2;
+        }
+        _ => loop {
+            let (arg, new_cursor) = match parse_expression(
+                tokens,
+                cursor,
+                &vec![Token::Comma, Token::RightParenthesis],
+                0,
+                true,
+                false,
+            ) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Err(expression_error(
+                        err,
+                        cursor,
+                        ParsingError::General {
+                            msg: help_message(
+                                tokens,
+                                cursor,
+                                "Expected function argument".to_owned(),
+                            ),
+                            cursor,
+                        },
+                    ));
+                }
+            };
+            cursor = new_cursor;
+            call.args.push(arg);
+
+            match tokens.get(cursor) {
+                Some(TokenContainer {
+                    token: Token::Comma,
+                    ..
+                }) => cursor += 1,
+                Some(TokenContainer {
+                    token: Token::RightParenthesis,
+                    ..
+                }) => {
+                    cursor += 1;
+                    break;
+                }
+                _ => {
+                    let msg = "Expected closing parenthesis".to_owned();
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, msg),
+                        cursor,
+                    });
+                }
+            }
+        },
+    }
+
+    if let Some(TokenContainer {
+        token: Token::Filter,
+        ..
+    }) = tokens.get(cursor)
+    {
+        let (filter, new_cursor) = parse_filter_clause(tokens, cursor + 1)?;
+        cursor = new_cursor;
+        call.filter = Some(Box::new(filter));
+    }
+
+    if let Some(TokenContainer {
+        token: Token::Over, ..
+    }) = tokens.get(cursor)
+    {
+        let (over, new_cursor) = parse_window_spec(tokens, cursor + 1)?;
+        cursor = new_cursor;
+        call.over = Some(over);
+    }
+
+    Ok((call, cursor))
+}
+
+// Parses the `(WHERE exp)` of an aggregate's FILTER clause
+fn parse_filter_clause(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(Expression, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    for (token, msg) in &[
+        (Token::LeftParenthesis, "Expected ("),
+        (Token::Where, "Expected WHERE"),
+    ] {
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, token.clone()) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, msg.to_string()),
+                cursor,
+            });
+        }
+        cursor += 1;
+    }
+
+    let (filter, new_cursor) = match parse_expression(
+        tokens,
+        cursor,
+        &vec![Token::RightParenthesis],
+        0,
+        true,
+        false,
+    ) {
+        Ok(value) => value,
+        Err(err) => {
+            let msg = "Expected FILTER condition".to_owned();
+            return Err(expression_error(
+                err,
+                cursor,
+                ParsingError::General {
+                    msg: help_message(tokens, cursor, msg),
+                    cursor,
+                },
+            ));
+        }
+    };
+    cursor = new_cursor;
+
+    if !expect_token(
+        &mut tokens[cursor..].iter(),
+        cursor,
+        Token::RightParenthesis,
+    ) {
+        let msg = "Expected closing parenthesis".to_owned();
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, msg),
+            cursor,
+        });
+    }
+
+    Ok((filter, cursor + 1))
+}
+
+// Parses `([PARTITION BY exp, ...] [ORDER BY exp [ASC | DESC], ...])`
+fn parse_window_spec(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(WindowSpec, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected (".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let mut spec = WindowSpec {
+        partition_by: vec![],
+        order_by: vec![],
+    };
+    let delimiters = vec![
+        Token::Comma,
+        Token::OrderBy,
+        Token::Asc,
+        Token::Desc,
+        Token::Rows,
+        Token::RightParenthesis,
+    ];
+
+    if let (
+        Some(TokenContainer {
+            token: Token::Partition,
+            ..
+        }),
+        Some(TokenContainer {
+            token: Token::By, ..
+        }),
+    ) = (tokens.get(cursor), tokens.get(cursor + 1))
+    {
+        cursor += 1;
+        loop {
+            let (exp, new_cursor) =
+                match parse_expression(tokens, cursor + 1, &delimiters, 0, true, false) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        let msg = "Expected PARTITION BY expression".to_owned();
+                        return Err(expression_error(
+                            err,
+                            cursor + 1,
+                            ParsingError::General {
+                                msg: help_message(tokens, cursor + 1, msg),
+                                cursor: cursor + 1,
+                            },
+                        ));
+                    }
+                };
+            cursor = new_cursor;
+            spec.partition_by.push(exp);
+            match tokens.get(cursor) {
+                Some(TokenContainer {
+                    token: Token::Comma,
+                    ..
+                }) => {}
+                _ => break,
+            }
+        }
+    }
+
+    if let Some(TokenContainer {
+        token: Token::OrderBy,
+        ..
+    }) = tokens.get(cursor)
+    {
+        loop {
+            let (exp, new_cursor) =
+                match parse_expression(tokens, cursor + 1, &delimiters, 0, true, false) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        let msg = "Expected ORDER BY expression".to_owned();
+                        return Err(expression_error(
+                            err,
+                            cursor + 1,
+                            ParsingError::General {
+                                msg: help_message(tokens, cursor + 1, msg),
+                                cursor: cursor + 1,
+                            },
+                        ));
+                    }
+                };
+            cursor = new_cursor;
+            let mut order_by_clause = OrderByClause { asc: true, exp };
+            match tokens.get(cursor) {
+                Some(TokenContainer {
+                    token: Token::Asc, ..
+                }) => cursor += 1,
+                Some(TokenContainer {
+                    token: Token::Desc, ..
+                }) => {
+                    cursor += 1;
+                    order_by_clause.asc = false;
+                }
+                _ => {}
+            }
+            spec.order_by.push(order_by_clause);
+            match tokens.get(cursor) {
+                Some(TokenContainer {
+                    token: Token::Comma,
+                    ..
+                }) => {}
+                _ => break,
+            }
+        }
+    }
+
+    match tokens.get(cursor) {
+        Some(TokenContainer {
+            token: Token::RightParenthesis,
+            ..
+        }) => Ok((spec, cursor + 1)),
+        Some(TokenContainer {
+            token: Token::Rows, ..
+        }) => {
+            let msg = "Window frames are not supported".to_owned();
+            Err(ParsingError::General {
+                msg: help_message(tokens, cursor, msg),
+                cursor,
+            })
+        }
+        _ => {
+            let msg = "Expected closing parenthesis".to_owned();
+            Err(ParsingError::General {
+                msg: help_message(tokens, cursor, msg),
+                cursor,
+            })
+        }
+    }
+}
+
+fn parse_insert_statement(
+    tokens: &mut Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(InsertStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    // Look for INSERT
+    if !expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Insert) {
+        return Err(ParsingError::General {
+            msg: "Not an insert statement".to_string(),
+            cursor,
+        });
+    }
+    cursor += 1;
 
     // Look for INTO
     if !expect_token(&mut tokens[cursor..].into_iter(), cursor, Token::Into) {
         return Err(ParsingError::General {
-            msg: help_message(tokens, cursor, "Expected INTO".to_owned()),
+            msg: help_message(tokens, cursor, "Expected INTO".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let table_name = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
+                cursor,
+            });
+        }
+    };
+
+    cursor += 1;
+
+    // Look for VALUES and the rows after it, or a query giving them
+    let (rows, query, cursor) = match tokens.get(cursor) {
+        Some(TokenContainer {
+            token: Token::Select,
+            ..
+        }) => {
+            // The query ends at a RETURNING outside of parentheses, it knows nothing of it
+            let mut depth = 0;
+            let query_end = tokens[cursor..]
+                .iter()
+                .position(|token| {
+                    // Looked at before the depth changes, the delimiter may close a parenthesis
+                    let end =
+                        depth == 0 && (token.token == Token::Returning || token.token == delimiter);
+                    match token.token {
+                        Token::LeftParenthesis => depth += 1,
+                        Token::RightParenthesis => depth -= 1,
+                        _ => {}
+                    }
+                    end
+                })
+                .map_or(tokens.len(), |position| cursor + position);
+            let (query, new_cursor) =
+                parse_select_statement(&tokens[..query_end].to_vec(), cursor, delimiter.clone())?;
+            (vec![], Some(query), new_cursor)
+        }
+        _ => {
+            let (rows, new_cursor) = parse_values_list(tokens, cursor)?;
+            (rows, None, new_cursor)
+        }
+    };
+    let (returning, cursor) = parse_returning(tokens, cursor, delimiter)?;
+
+    Ok((
+        InsertStatement {
+            table: table_name,
+            rows,
+            query,
+            returning,
+        },
+        cursor,
+    ))
+}
+
+// Parses RETURNING items at `cursor` if it is there, giving no items otherwise
+fn parse_returning(
+    tokens: &Vec<TokenContainer>,
+    cursor: usize,
+    delimiter: Token,
+) -> Result<(Vec<SelectItem>, usize), ParsingError> {
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Returning) {
+        return Ok((vec![], cursor));
+    }
+    let expected = ParsingError::General {
+        msg: help_message(tokens, cursor + 1, "Expected RETURNING items".to_owned()),
+        cursor: cursor + 1,
+    };
+    match parse_select_items(tokens, cursor + 1, &vec![delimiter]) {
+        Ok((items, new_cursor)) if !items.is_empty() => Ok((items, new_cursor)),
+        Ok(_) => Err(expected),
+        Err(err) => Err(expression_error(err, cursor + 1, expected)),
+    }
+}
+
+// Parses UPDATE table [[AS] alias] SET column = value, ... [WHERE condition] [ORDER BY
+// expression] [LIMIT count] [RETURNING items]. A list of columns can be set at once, as
+// in `SET (a, b) = (1, 2)`.
+fn parse_update_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(UpdateStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Update) {
+        return Err(ParsingError::General {
+            msg: "Not an update statement".to_string(),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let table = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
+                cursor,
+            });
+        }
+    };
+    cursor += 1;
+
+    let found_as = is_as_query(tokens, cursor);
+    if found_as {
+        cursor += 1;
+    }
+    let as_clause = parse_alias(tokens, cursor, found_as);
+    match as_clause {
+        Some(_) => cursor += 1,
+        None if found_as => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected alias after AS".to_owned()),
+                cursor,
+            });
+        }
+        None => {}
+    }
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Set) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected SET".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let mut assignments = vec![];
+    loop {
+        let start = cursor;
+        let columns = match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::LeftParenthesis) => {
+                let (columns, new_cursor) = parse_column_names(tokens, cursor + 1)?;
+                cursor = new_cursor;
+                columns
+            }
+            _ => match parse_name(tokens, cursor) {
+                Some(column) => {
+                    cursor += 1;
+                    vec![column]
+                }
+                None => {
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected Column Name".to_owned()),
+                        cursor,
+                    });
+                }
+            },
+        };
+
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Equal) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected =".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+
+        let values = if tokens[start].token == Token::LeftParenthesis {
+            if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
+                    cursor,
+                });
+            }
+            cursor += 1;
+            let (values, new_cursor) =
+                match parse_expressions(tokens, cursor, &vec![Token::RightParenthesis]) {
+                    Ok(value) if !value.0.is_empty() => value,
+                    result => {
+                        let expected = ParsingError::General {
+                            msg: help_message(
+                                tokens,
+                                cursor,
+                                "Expected value expressions".to_owned(),
+                            ),
+                            cursor,
+                        };
+                        return Err(match result {
+                            Err(err) => expression_error(err, cursor, expected),
+                            Ok(_) => expected,
+                        });
+                    }
+                };
+            cursor = new_cursor + 1;
+            if values.len() != columns.len() {
+                let msg = "Number of columns does not match number of values".to_owned();
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, start, msg),
+                    cursor: start,
+                });
+            }
+            values
+        } else {
+            match parse_expression(
+                tokens,
+                cursor,
+                &vec![
+                    Token::Comma,
+                    Token::Where,
+                    Token::OrderBy,
+                    Token::Limit,
+                    Token::Returning,
+                    delimiter.clone(),
+                ],
+                0,
+                true,
+                false,
+            ) {
+                Ok((value, new_cursor)) => {
+                    cursor = new_cursor;
+                    vec![value]
+                }
+                Err(err) => {
+                    let msg = "Expected value expression".to_owned();
+                    return Err(expression_error(
+                        err,
+                        cursor,
+                        ParsingError::General {
+                            msg: help_message(tokens, cursor, msg),
+                            cursor,
+                        },
+                    ));
+                }
+            }
+        };
+        assignments.extend(
+            columns
+                .into_iter()
+                .zip(values)
+                .map(|(column, value)| Assignment { column, value }),
+        );
+
+        match tokens.get(cursor) {
+            Some(TokenContainer {
+                token: Token::Comma,
+                ..
+            }) => cursor += 1,
+            _ => break,
+        }
+    }
+
+    let mut where_clause = Expression::Empty;
+    if let Some(TokenContainer {
+        token: Token::Where,
+        ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+        let (condition, new_cursor) = match parse_expression(
+            tokens,
+            cursor,
+            &vec![
+                Token::OrderBy,
+                Token::Limit,
+                Token::Returning,
+                delimiter.clone(),
+            ],
+            0,
+            true,
+            false,
+        ) {
+            Ok(value) => value,
+            Err(err) => {
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected WHERE conditionals".to_owned()),
+                        cursor,
+                    },
+                ));
+            }
+        };
+        cursor = new_cursor;
+        where_clause = condition;
+    }
+    let (order_by, limit, cursor) = parse_change_order_limit(tokens, cursor, &delimiter)?;
+    let (returning, cursor) = parse_returning(tokens, cursor, delimiter)?;
+
+    Ok((
+        UpdateStatement {
+            table,
+            as_clause,
+            assignments,
+            where_clause,
+            order_by,
+            limit,
+            returning,
+        },
+        cursor,
+    ))
+}
+
+// Parses DELETE FROM table [[AS] alias] [WHERE condition] [ORDER BY expression]
+// [LIMIT count] [RETURNING items]
+fn parse_delete_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(DeleteStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Delete) {
+        return Err(ParsingError::General {
+            msg: "Not a delete statement".to_string(),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::From) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected FROM".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let table = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
+                cursor,
+            });
+        }
+    };
+    cursor += 1;
+
+    let found_as = is_as_query(tokens, cursor);
+    if found_as {
+        cursor += 1;
+    }
+    let as_clause = parse_alias(tokens, cursor, found_as);
+    match as_clause {
+        Some(_) => cursor += 1,
+        None if found_as => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected alias after AS".to_owned()),
+                cursor,
+            });
+        }
+        None => {}
+    }
+
+    let mut where_clause = Expression::Empty;
+    if expect_token(&mut tokens[cursor..].iter(), cursor, Token::Where) {
+        cursor += 1;
+        let (condition, new_cursor) = match parse_expression(
+            tokens,
+            cursor,
+            &vec![
+                Token::OrderBy,
+                Token::Limit,
+                Token::Returning,
+                delimiter.clone(),
+            ],
+            0,
+            true,
+            false,
+        ) {
+            Ok(value) => value,
+            Err(err) => {
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected WHERE conditionals".to_owned()),
+                        cursor,
+                    },
+                ));
+            }
+        };
+        cursor = new_cursor;
+        where_clause = condition;
+    }
+    let (order_by, limit, cursor) = parse_change_order_limit(tokens, cursor, &delimiter)?;
+    let (returning, cursor) = parse_returning(tokens, cursor, delimiter)?;
+
+    Ok((
+        DeleteStatement {
+            table,
+            as_clause,
+            where_clause,
+            order_by,
+            limit,
+            returning,
+        },
+        cursor,
+    ))
+}
+
+// Parses WITH name [(columns)] AS (query), ... statement. The queries and the statement
+// can be SELECT, INSERT, UPDATE or DELETE.
+fn parse_with_statement(
+    tokens: &mut Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(WithStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::With) {
+        return Err(ParsingError::General {
+            msg: "Not a with statement".to_string(),
+            cursor,
+        });
+    }
+
+    let mut queries = vec![];
+    loop {
+        cursor += 1;
+        let name = match parse_name(tokens, cursor) {
+            Some(name) => name,
+            None => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected WITH query name".to_owned()),
+                    cursor,
+                });
+            }
+        };
+        cursor += 1;
+
+        let mut columns = vec![];
+        if expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+            let (names, new_cursor) = parse_column_names(tokens, cursor + 1)?;
+            cursor = new_cursor;
+            columns = names;
+        }
+
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::As) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected AS".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+
+        let (statement, new_cursor) = parse_with_query(tokens, cursor, Token::RightParenthesis)?;
+        cursor = new_cursor;
+        if !expect_token(
+            &mut tokens[cursor..].iter(),
+            cursor,
+            Token::RightParenthesis,
+        ) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected closing parenthesis".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+        queries.push(CommonTableExpression {
+            name,
+            columns,
+            statement: Box::new(statement),
+        });
+
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Comma) {
+            break;
+        }
+    }
+
+    let (statement, cursor) = parse_with_query(tokens, cursor, delimiter)?;
+    Ok((
+        WithStatement {
+            queries,
+            statement: Box::new(statement),
+        },
+        cursor,
+    ))
+}
+
+// A query of a WITH or the statement after them, which can read and change rows only
+fn parse_with_query(
+    tokens: &mut Vec<TokenContainer>,
+    cursor: usize,
+    delimiter: Token,
+) -> Result<(Statement, usize), ParsingError> {
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Select) => {
+            let (select, new_cursor) = parse_select_statement(tokens, cursor, delimiter)?;
+            Ok((Statement::SelectStatement(select), new_cursor))
+        }
+        Some(Token::Insert | Token::Update | Token::Delete) => {
+            parse_statement(tokens, cursor, delimiter)
+        }
+        _ => Err(ParsingError::General {
+            msg: help_message(
+                tokens,
+                cursor,
+                "Expected SELECT, INSERT, UPDATE or DELETE".to_owned(),
+            ),
+            cursor,
+        }),
+    }
+}
+
+// Parses `VALUES (exp, ...), ...`, every row having as many values as the first
+fn parse_values_list(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(Vec<Vec<Expression>>, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::Values) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected VALUES".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
+
+    let mut rows: Vec<Vec<Expression>> = vec![];
+    loop {
+        let row_start = cursor;
+        if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+
+        let (values, new_cursor) =
+            match parse_expressions(tokens, cursor, &vec![Token::RightParenthesis]) {
+                Ok(value) if !value.0.is_empty() => value,
+                result => {
+                    let expected = ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected value expressions".to_owned()),
+                        cursor,
+                    };
+                    return Err(match result {
+                        Err(err) => expression_error(err, cursor, expected),
+                        Ok(_) => expected,
+                    });
+                }
+            };
+        cursor = new_cursor;
+
+        if !expect_token(
+            &mut tokens[cursor..].iter(),
+            cursor,
+            Token::RightParenthesis,
+        ) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
+                cursor,
+            });
+        }
+        cursor += 1;
+
+        if let Some(first) = rows.first() {
+            if first.len() != values.len() {
+                let msg = "VALUES lists must all be the same length".to_owned();
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, row_start, msg),
+                    cursor: row_start,
+                });
+            }
+        }
+        rows.push(values);
+
+        match tokens.get(cursor) {
+            Some(TokenContainer {
+                token: Token::Comma,
+                ..
+            }) => cursor += 1,
+            _ => break,
+        }
+    }
+
+    Ok((rows, cursor))
+}
+
+fn parse_drop_table_statement(
+    tokens: &mut Vec<TokenContainer>,
+    initial_cursor: usize,
+    _: Token,
+) -> Result<(DropTableStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+    if let Some(TokenContainer {
+        token: Token::Drop, ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+    } else {
+        return Err(ParsingError::General {
+            msg: "Not a drop table statement".to_string(),
+            cursor,
+        });
+    }
+    if let Some(TokenContainer {
+        token: Token::Table,
+        ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+    } else {
+        return Err(ParsingError::General {
+            msg: "Not a drop table statement".to_string(),
+            cursor,
+        });
+    }
+    let name;
+    if let Some(value) = parse_name(tokens, cursor) {
+        cursor += 1;
+        name = value;
+    } else {
+        return Err(ParsingError::General {
+            msg: "Not a drop table statement".to_string(),
+            cursor,
+        });
+    }
+
+    let (cascade, cursor) = parse_drop_behavior(tokens, cursor);
+    Ok((DropTableStatement { name, cascade }, cursor))
+}
+
+// Whether `CASCADE` or `RESTRICT` at `cursor` asks to drop what depends on the object
+// dropped too, and the cursor after it. Neither is the same as RESTRICT.
+fn parse_drop_behavior(tokens: &[TokenContainer], cursor: usize) -> (bool, usize) {
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Cascade) => (true, cursor + 1),
+        Some(Token::Restrict) => (false, cursor + 1),
+        _ => (false, cursor),
+    }
+}
+
+// Whether `IF EXISTS` is at `cursor`. IF is not reserved, so it is only taken as such
+// when EXISTS follows.
+fn parse_if_exists(tokens: &[TokenContainer], cursor: usize) -> bool {
+    matches!(
+        (tokens.get(cursor), tokens.get(cursor + 1)),
+        (
+            Some(TokenContainer {
+                token: Token::If,
+                ..
+            }),
+            Some(TokenContainer {
+                token: Token::Exists,
+                ..
+            }),
+        )
+    )
+}
+
+fn parse_drop_index_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(DropIndexStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 2;
+
+    let if_exists = parse_if_exists(tokens, cursor);
+    if if_exists {
+        cursor += 2;
+    }
+
+    match parse_name(tokens, cursor) {
+        Some(name) => Ok((DropIndexStatement { name, if_exists }, cursor + 1)),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Index Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+fn parse_reindex_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(ReindexStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+
+    if let Some(Token::Table) = tokens.get(cursor).map(|token| &token.token) {
+        cursor += 1;
+    } else {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected TABLE".to_owned()),
+            cursor,
+        });
+    }
+
+    match parse_name(tokens, cursor) {
+        Some(table) => Ok((ReindexStatement { table }, cursor + 1)),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+// Parses `CREATE SEQUENCE name` and its options, in any order. SEQUENCE and the names of
+// the options are not keywords, they are only taken as such here.
+fn parse_create_sequence_statement(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> Result<(CreateSequenceStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 2;
+    let mut create = match parse_qualified_name(tokens, cursor) {
+        Some((name, new_cursor)) => {
+            cursor = new_cursor;
+            CreateSequenceStatement {
+                name,
+                increment: None,
+                min_value: None,
+                max_value: None,
+                start: None,
+                cycle: false,
+            }
+        }
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(
+                    &tokens.to_vec(),
+                    cursor,
+                    "Expected Sequence Name".to_owned(),
+                ),
+                cursor,
+            });
+        }
+    };
+
+    loop {
+        let option = match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::IdentifierValue { value }) => value.as_str(),
+            // NO MINVALUE and NO MAXVALUE leave the default, NO CYCLE is the default
+            Some(Token::No) => match tokens.get(cursor + 1).map(|token| &token.token) {
+                Some(Token::IdentifierValue { value })
+                    if matches!(value.as_str(), "minvalue" | "maxvalue" | "cycle") =>
+                {
+                    cursor += 2;
+                    continue;
+                }
+                _ => break,
+            },
+            _ => break,
+        };
+        let filler = match option {
+            "increment" => Token::By,
+            "start" => Token::With,
+            "minvalue" | "maxvalue" => Token::Empty,
+            "cycle" => {
+                create.cycle = true;
+                cursor += 1;
+                continue;
+            }
+            _ => break,
+        };
+        cursor += 1;
+        if tokens.get(cursor).map(|token| &token.token) == Some(&filler) {
+            cursor += 1;
+        }
+        let (value, new_cursor) = parse_sequence_value(tokens, cursor)?;
+        cursor = new_cursor;
+        match option {
+            "increment" => create.increment = Some(value),
+            "start" => create.start = Some(value),
+            "minvalue" => create.min_value = Some(value),
+            _ => create.max_value = Some(value),
+        }
+    }
+
+    Ok((create, cursor))
+}
+
+// Parses a whole number, which may be negative, given to an option of CREATE SEQUENCE
+fn parse_sequence_value(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> Result<(i64, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+    let negative = tokens.get(cursor).map(|token| &token.token) == Some(&Token::Minus);
+    if negative {
+        cursor += 1;
+    }
+    let digits = match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::NumericValue { value }) if negative => format!("-{}", value),
+        Some(Token::NumericValue { value }) => value.to_string(),
+        _ => String::new(),
+    };
+    match digits.parse::<i64>() {
+        Ok(value) => Ok((value, cursor + 1)),
+        Err(_) => Err(ParsingError::General {
+            msg: help_message(&tokens.to_vec(), cursor, "Expected Whole Number".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+fn parse_drop_sequence_statement(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> Result<(DropSequenceStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 2;
+
+    let if_exists = parse_if_exists(tokens, cursor);
+    if if_exists {
+        cursor += 2;
+    }
+
+    match parse_qualified_name(tokens, cursor) {
+        Some((name, cursor)) => Ok((DropSequenceStatement { name, if_exists }, cursor)),
+        None => Err(ParsingError::General {
+            msg: help_message(
+                &tokens.to_vec(),
+                cursor,
+                "Expected Sequence Name".to_owned(),
+            ),
+            cursor,
+        }),
+    }
+}
+
+// Parses `CREATE VIEW name AS query`. The text of the query is left for `parse_tokens`
+// to fill in, the tokens don't have it.
+fn parse_create_view_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(CreateViewStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 2;
+    let (name, cursor) = match parse_qualified_name(tokens, cursor) {
+        Some(found) => found,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected View Name".to_owned()),
+                cursor,
+            })
+        }
+    };
+    if !is_as_query(tokens, cursor) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected AS".to_owned()),
+            cursor,
+        });
+    }
+    let (query, cursor) = parse_select_statement(tokens, cursor + 1, delimiter)?;
+    Ok((
+        CreateViewStatement {
+            name,
+            query,
+            definition: String::new(),
+        },
+        cursor,
+    ))
+}
+
+fn parse_drop_view_statement(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> Result<(DropViewStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 2;
+
+    let if_exists = parse_if_exists(tokens, cursor);
+    if if_exists {
+        cursor += 2;
+    }
+
+    match parse_qualified_name(tokens, cursor) {
+        Some((name, cursor)) => {
+            let (cascade, cursor) = parse_drop_behavior(tokens, cursor);
+            Ok((
+                DropViewStatement {
+                    name,
+                    if_exists,
+                    cascade,
+                },
+                cursor,
+            ))
+        }
+        None => Err(ParsingError::General {
+            msg: help_message(&tokens.to_vec(), cursor, "Expected View Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+// Whether the word at `cursor` is `word`, for words that are not keywords
+fn is_word(tokens: &[TokenContainer], cursor: usize, word: &str) -> bool {
+    matches!(
+        tokens.get(cursor).map(|token| &token.token),
+        Some(Token::IdentifierValue { value }) if value.as_str() == word
+    )
+}
+
+// Parses `CREATE TRIGGER`. TRIGGER, AFTER, EACH, ROW, STATEMENT and EXECUTE are not
+// keywords, they are only taken as such here.
+fn parse_create_trigger_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(CreateTriggerStatement, usize), ParsingError> {
+    let expected = |cursor: usize, msg: &str| ParsingError::General {
+        msg: help_message(tokens, cursor, msg.to_owned()),
+        cursor,
+    };
+    let mut cursor = initial_cursor + 2;
+    let name =
+        parse_name(tokens, cursor).ok_or_else(|| expected(cursor, "Expected Trigger Name"))?;
+    cursor += 1;
+    if is_word(tokens, cursor, "before") || is_word(tokens, cursor, "instead") {
+        return Err(expected(cursor, "Only AFTER triggers are supported"));
+    }
+    if !is_word(tokens, cursor, "after") {
+        return Err(expected(cursor, "Expected AFTER"));
+    }
+    cursor += 1;
+
+    let mut events = vec![];
+    loop {
+        let event = match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::Insert) => TriggerEvent::Insert,
+            Some(Token::Update) => TriggerEvent::Update,
+            Some(Token::Delete) => TriggerEvent::Delete,
+            _ => return Err(expected(cursor, "Expected INSERT, UPDATE or DELETE")),
+        };
+        if !events.contains(&event) {
+            events.push(event);
+        }
+        cursor += 1;
+        match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::Or) => cursor += 1,
+            _ => break,
+        }
+    }
+
+    if tokens.get(cursor).map(|token| &token.token) != Some(&Token::On) {
+        return Err(expected(cursor, "Expected ON"));
+    }
+    let (table, new_cursor) = parse_qualified_name(tokens, cursor + 1)
+        .ok_or_else(|| expected(cursor + 1, "Expected Table Name"))?;
+    cursor = new_cursor;
+
+    let mut for_each_row = false;
+    if tokens.get(cursor).map(|token| &token.token) == Some(&Token::For) {
+        cursor += 1;
+        if is_word(tokens, cursor, "each") {
+            cursor += 1;
+        }
+        for_each_row = match () {
+            _ if is_word(tokens, cursor, "row") => true,
+            _ if is_word(tokens, cursor, "statement") => false,
+            _ => return Err(expected(cursor, "Expected ROW or STATEMENT")),
+        };
+        cursor += 1;
+    }
+
+    if !is_word(tokens, cursor, "execute") {
+        return Err(expected(cursor, "Expected EXECUTE"));
+    }
+    cursor += 1;
+    if tokens.get(cursor).map(|token| &token.token) != Some(&Token::LeftParenthesis) {
+        return Err(expected(cursor, "Expected Left Parenthesis"));
+    }
+    // The statement ends at the parenthesis closing the one before it
+    let start = cursor + 1;
+    let mut depth = 0;
+    let end = tokens[start..]
+        .iter()
+        .position(|token| {
+            match token.token {
+                Token::LeftParenthesis => depth += 1,
+                Token::RightParenthesis if depth == 0 => return true,
+                Token::RightParenthesis => depth -= 1,
+                _ => {}
+            }
+            false
+        })
+        .map(|position| start + position)
+        .ok_or_else(|| expected(tokens.len(), "Expected Right Parenthesis"))?;
+    let mut action_tokens = tokens[start..end].to_vec();
+    let (action, action_end) = parse_statement(&mut action_tokens, 0, Token::Semicolon)?;
+    if action_end < action_tokens.len() {
+        return Err(expected(start + action_end, "Expected Right Parenthesis"));
+    }
+
+    Ok((
+        CreateTriggerStatement {
+            name,
+            table,
+            events,
+            for_each_row,
+            action: Box::new(action),
+        },
+        end + 1,
+    ))
+}
+
+fn parse_drop_trigger_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(DropTriggerStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 2;
+
+    let if_exists = parse_if_exists(tokens, cursor);
+    if if_exists {
+        cursor += 2;
+    }
+
+    let name = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Trigger Name".to_owned()),
+                cursor,
+            })
+        }
+    };
+    cursor += 1;
+    let table = match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::On) => parse_qualified_name(tokens, cursor + 1),
+        _ => None,
+    };
+    match table {
+        Some((table, cursor)) => Ok((
+            DropTriggerStatement {
+                name,
+                table,
+                if_exists,
+            },
+            cursor,
+        )),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected ON Table Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+// Parses `VACUUM [table]`, anything after it being left to the caller
+fn parse_vacuum_statement(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> (VacuumStatement, usize) {
+    let cursor = initial_cursor + 1;
+    match parse_qualified_name(tokens, cursor) {
+        Some((table, cursor)) => (VacuumStatement { table: Some(table) }, cursor),
+        None => (VacuumStatement { table: None }, cursor),
+    }
+}
+
+// Parses `EXPLAIN [ANALYZE] select`, other statements can't be explained yet
+fn parse_explain_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(ExplainStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+
+    let analyze = match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Analyze) => {
+            cursor += 1;
+            true
+        }
+        _ => false,
+    };
+
+    if let Some(Token::Select) = tokens.get(cursor).map(|token| &token.token) {
+        let (select, cursor) = parse_select_statement(tokens, cursor, delimiter)?;
+        Ok((ExplainStatement { analyze, select }, cursor))
+    } else {
+        Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected SELECT".to_owned()),
+            cursor,
+        })
+    }
+}
+
+// Parses `ALTER TABLE name DROP [COLUMN] [IF EXISTS] column [CASCADE | RESTRICT]`, the
+// only change to a table there is so far
+fn parse_alter_table_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(AlterTableStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+
+    if let Some(Token::Table) = tokens.get(cursor).map(|token| &token.token) {
+        cursor += 1;
+    } else {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected TABLE".to_owned()),
             cursor,
         });
     }
-    cursor += 1;
 
-    let table_name = match &tokens[cursor].token {
-        Token::IdentifierValue { value } => value,
-        _ => {
+    let name = match parse_name(tokens, cursor) {
+        Some(name) => name,
+        None => {
             return Err(ParsingError::General {
                 msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
                 cursor,
             });
         }
     };
-
     cursor += 1;
 
-    // Look for VALUES
-    if tokens[cursor].token != Token::Values {
-        help_message(tokens, cursor, "Expected VALUES".to_owned());
+    if let Some(Token::Drop) = tokens.get(cursor).map(|token| &token.token) {
+        cursor += 1;
+    } else {
         return Err(ParsingError::General {
-            msg: help_message(tokens, cursor, "Expected VALUES".to_owned()),
+            msg: help_message(tokens, cursor, "Expected DROP".to_owned()),
             cursor,
         });
     }
+    if let Some(Token::Column) = tokens.get(cursor).map(|token| &token.token) {
+        cursor += 1;
+    }
+
+    let if_exists = parse_if_exists(tokens, cursor);
+    if if_exists {
+        cursor += 2;
+    }
+
+    let column = match parse_name(tokens, cursor) {
+        Some(column) => column,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Column Name".to_owned()),
+                cursor,
+            });
+        }
+    };
     cursor += 1;
 
-    // Look for left parenthesis
-    if tokens[cursor].token != Token::LeftParenthesis {
-        return Err(ParsingError::General {
-            msg: help_message(tokens, cursor, "Expected Left Parenthesis".to_owned()),
-            cursor,
-        });
+    let (cascade, cursor) = parse_drop_behavior(tokens, cursor);
+
+    Ok((
+        AlterTableStatement {
+            name,
+            action: AlterTableAction::DropColumn {
+                name: column,
+                if_exists,
+                cascade,
+            },
+        },
+        cursor,
+    ))
+}
+
+// Name of a setting and the cursor after it. Variables of extensions and applications
+// have dotted names such as `myapp.user_id`.
+fn parse_setting_name(tokens: &[TokenContainer], cursor: usize) -> Option<(String, usize)> {
+    let mut name = parse_name(tokens, cursor)?;
+    let mut cursor = cursor + 1;
+    while let Some(Token::Dot) = tokens.get(cursor).map(|token| &token.token) {
+        name.push('.');
+        name.push_str(&parse_name(tokens, cursor + 1)?);
+        cursor += 2;
+    }
+    Some((name, cursor))
+}
+
+// Parses `SET name = value` and `SET name TO value`, optionally with SESSION or LOCAL
+// before the name. The value is a literal or word, a list of them separated by commas, or
+// DEFAULT, whether it suits the setting is left to the backend.
+fn parse_set_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(SetStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+
+    // Neither is a keyword, so they are only taken as one when no `=` or TO follows
+    let mut local = false;
+    if let Some(Token::IdentifierValue { value }) = tokens.get(cursor).map(|token| &token.token) {
+        let follows = tokens.get(cursor + 1).map(|token| &token.token);
+        if (value == "session" || value == "local")
+            && !matches!(follows, Some(Token::Equal) | Some(Token::To))
+        {
+            local = value == "local";
+            cursor += 1;
+        }
+    }
+
+    let name_loc = match tokens.get(cursor) {
+        Some(token) => token.loc,
+        None => tokens[initial_cursor].loc,
+    };
+    let name = match parse_setting_name(tokens, cursor) {
+        Some((name, new_cursor)) => {
+            cursor = new_cursor;
+            name
+        }
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Setting Name".to_owned()),
+                cursor,
+            });
+        }
+    };
+
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Equal) | Some(Token::To) => cursor += 1,
+        _ => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected = or TO".to_owned()),
+                cursor,
+            });
+        }
     }
+
+    let (mut value, value_loc) = match parse_setting_value(tokens, cursor) {
+        Some(value) => value,
+        None => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected Setting Value".to_owned()),
+                cursor,
+            });
+        }
+    };
     cursor += 1;
 
-    // Look for expression list
-    let (values, new_cursor) =
-        match parse_expressions(tokens, cursor, &vec![Token::RightParenthesis]) {
-            None => {
-                return Err(ParsingError::General {
-                    msg: help_message(tokens, cursor, "Expected value expressions".to_owned()),
-                    cursor,
-                });
+    // A list of values, like search_path takes, is set as the text SHOW gives for it
+    let follows = tokens.get(cursor).map(|token| &token.token);
+    if value != Token::Default && follows == Some(&Token::Comma) {
+        let mut items = vec![list_item(&value)];
+        while let Some(Token::Comma) = tokens.get(cursor).map(|token| &token.token) {
+            cursor += 1;
+            match parse_setting_value(tokens, cursor) {
+                Some((item, _)) if item != Token::Default => {
+                    items.push(list_item(&item));
+                    cursor += 1;
+                }
+                _ => {
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected Setting Value".to_owned()),
+                        cursor,
+                    });
+                }
             }
-            Some(value) => value,
+        }
+        value = Token::StringValue {
+            value: items.join(", ").into(),
         };
+    }
 
-    cursor = new_cursor;
+    Ok((
+        SetStatement {
+            name,
+            value,
+            local,
+            name_loc,
+            value_loc,
+        },
+        cursor,
+    ))
+}
 
-    // Look for right parenthesis
-    if tokens[cursor].token != Token::RightParenthesis {
-        return Err(ParsingError::General {
-            msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
+fn parse_setting_value(tokens: &[TokenContainer], cursor: usize) -> Option<(Token, TokenLocation)> {
+    match tokens.get(cursor) {
+        Some(TokenContainer { token, loc, .. })
+            if is_literal(token)
+                || matches!(
+                    token,
+                    Token::IdentifierValue { .. } | Token::On | Token::Default
+                ) =>
+        {
+            Some((token.clone(), *loc))
+        }
+        _ => None,
+    }
+}
+
+// A value in a list of them, names and text quoted as names where they have to be
+fn list_item(value: &Token) -> String {
+    match value {
+        Token::IdentifierValue { value } | Token::StringValue { value } => quote_identifier(value),
+        token => token.generate_code(),
+    }
+}
+
+fn parse_reset_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(ResetStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 1;
+    let name_loc = match tokens.get(cursor) {
+        Some(token) => token.loc,
+        None => tokens[initial_cursor].loc,
+    };
+
+    if let Some(Token::All) = tokens.get(cursor).map(|token| &token.token) {
+        return Ok((
+            ResetStatement {
+                name: None,
+                name_loc,
+            },
+            cursor + 1,
+        ));
+    }
+    match parse_setting_name(tokens, cursor) {
+        Some((name, new_cursor)) => Ok((
+            ResetStatement {
+                name: Some(name),
+                name_loc,
+            },
+            new_cursor,
+        )),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Setting Name or ALL".to_owned()),
             cursor,
-        });
+        }),
     }
+}
+
+fn parse_show_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(ShowStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 1;
+
+    match parse_setting_name(tokens, cursor) {
+        Some((name, new_cursor)) => Ok((
+            ShowStatement {
+                name,
+                name_loc: tokens[cursor].loc,
+            },
+            new_cursor,
+        )),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Setting Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+fn parse_channel(tokens: &Vec<TokenContainer>, cursor: usize) -> Result<String, ParsingError> {
+    match parse_name(tokens, cursor) {
+        Some(channel) => Ok(channel),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Channel Name".to_owned()),
+            cursor,
+        }),
+    }
+}
+
+fn parse_listen_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(ListenStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 1;
+    let channel = parse_channel(tokens, cursor)?;
+    Ok((ListenStatement { channel }, cursor + 1))
+}
+
+fn parse_notify_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(NotifyStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+    let channel = parse_channel(tokens, cursor)?;
     cursor += 1;
 
+    let mut payload = None;
+    if let Some(Token::Comma) = tokens.get(cursor).map(|token| &token.token) {
+        cursor += 1;
+        match tokens.get(cursor).map(|token| &token.token) {
+            Some(Token::StringValue { value }) => payload = Some(value.to_string()),
+            _ => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Payload String".to_owned()),
+                    cursor,
+                });
+            }
+        }
+        cursor += 1;
+    }
+
+    Ok((NotifyStatement { channel, payload }, cursor))
+}
+
+fn parse_unlisten_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(UnlistenStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 1;
+    if let Some(Token::Asterisk) = tokens.get(cursor).map(|token| &token.token) {
+        return Ok((UnlistenStatement { channel: None }, cursor + 1));
+    }
+    let channel = parse_channel(tokens, cursor)?;
     Ok((
-        InsertStatement {
-            table: table_name.clone(),
-            values,
+        UnlistenStatement {
+            channel: Some(channel),
         },
-        cursor,
+        cursor + 1,
     ))
 }
 
-fn parse_drop_table_statement(
-    tokens: &mut Vec<TokenContainer>,
-    initial_cursor: usize,
-    _: Token,
-) -> Result<(DropTableStatement, usize), ParsingError> {
-    let mut cursor = initial_cursor;
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::Drop,
-    }) = tokens.get(cursor)
-    {
-        cursor += 1;
-    } else {
-        return Err(ParsingError::General {
-            msg: "Not a drop table statement".to_string(),
+fn parse_cursor_name(tokens: &Vec<TokenContainer>, cursor: usize) -> Result<String, ParsingError> {
+    match parse_name(tokens, cursor) {
+        Some(name) => Ok(name),
+        None => Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected Cursor Name".to_owned()),
             cursor,
-        });
+        }),
     }
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::Table,
-    }) = tokens.get(cursor)
-    {
+}
+
+fn parse_declare_cursor_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(DeclareCursorStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+    let name = parse_cursor_name(tokens, cursor)?;
+    cursor += 1;
+
+    for (keyword, msg) in [
+        (Token::Cursor, "Expected CURSOR"),
+        (Token::For, "Expected FOR"),
+    ] {
+        if tokens.get(cursor).map(|token| &token.token) != Some(&keyword) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, msg.to_owned()),
+                cursor,
+            });
+        }
         cursor += 1;
+    }
+
+    if let Some(Token::Select) = tokens.get(cursor).map(|token| &token.token) {
+        let (query, cursor) = parse_select_statement(tokens, cursor, delimiter)?;
+        Ok((DeclareCursorStatement { name, query }, cursor))
     } else {
-        return Err(ParsingError::General {
-            msg: "Not a drop table statement".to_string(),
+        Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected SELECT".to_owned()),
             cursor,
-        });
+        })
     }
-    let name;
-    if let Some(TokenContainer {
-        loc: _,
-        token: Token::IdentifierValue { value },
-    }) = tokens.get(cursor)
-    {
+}
+
+// A row count of FETCH, or ALL for every row left which gives None
+fn parse_fetch_count(tokens: &[TokenContainer], cursor: usize) -> Option<Option<usize>> {
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::All) => Some(None),
+        Some(Token::NumericValue { value }) => value.parse::<usize>().ok().map(Some),
+        _ => None,
+    }
+}
+
+fn parse_fetch_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(FetchStatement, usize), ParsingError> {
+    let mut cursor = initial_cursor + 1;
+
+    let mut count = Some(1);
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Next) => cursor += 1,
+        Some(Token::Forward) => {
+            cursor += 1;
+            if let Some(forward) = parse_fetch_count(tokens, cursor) {
+                count = forward;
+                cursor += 1;
+            }
+        }
+        _ => {
+            if let Some(fetched) = parse_fetch_count(tokens, cursor) {
+                count = fetched;
+                cursor += 1;
+            }
+        }
+    }
+    if let Some(Token::From) | Some(Token::In) = tokens.get(cursor).map(|token| &token.token) {
         cursor += 1;
-        name = value.clone();
-    } else {
-        return Err(ParsingError::General {
-            msg: "Not a drop table statement".to_string(),
-            cursor,
-        });
     }
 
-    Ok((DropTableStatement { name }, cursor))
+    let name = parse_cursor_name(tokens, cursor)?;
+    Ok((FetchStatement { name, count }, cursor + 1))
+}
+
+fn parse_close_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(CloseStatement, usize), ParsingError> {
+    let cursor = initial_cursor + 1;
+    if let Some(Token::All) = tokens.get(cursor).map(|token| &token.token) {
+        return Ok((CloseStatement { name: None }, cursor + 1));
+    }
+    let name = parse_cursor_name(tokens, cursor)?;
+    Ok((CloseStatement { name: Some(name) }, cursor + 1))
+}
+
+fn parse_transaction_statement(
+    tokens: &[TokenContainer],
+    initial_cursor: usize,
+) -> (TransactionStatement, usize) {
+    let transaction = match tokens[initial_cursor].token {
+        Token::Begin => TransactionStatement::Begin,
+        Token::Commit => TransactionStatement::Commit,
+        _ => TransactionStatement::Rollback,
+    };
+    let cursor = initial_cursor + 1;
+    match tokens.get(cursor).map(|token| &token.token) {
+        Some(Token::Work) | Some(Token::Transaction) => (transaction, cursor + 1),
+        _ => (transaction, cursor),
+    }
 }
 
 fn parse_select_items(
     tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
     delimiters: &Vec<Token>,
-) -> Option<(Vec<SelectItem>, usize)> {
+) -> Result<(Vec<SelectItem>, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     let mut select_items = Vec::with_capacity(10);
@@ -1039,10 +3636,8 @@ fn parse_select_items(
     delimiters_plus.push(Token::As);
 
     'outer: loop {
-        if cursor == tokens.len() {
-            return Some((select_items, cursor - 1));
-        } else if cursor > tokens.len() {
-            return None;
+        if cursor >= tokens.len() {
+            return Ok((select_items, cursor));
         }
 
         let current_token = &tokens[cursor];
@@ -1054,8 +3649,10 @@ fn parse_select_items(
 
         if select_items.len() > 0 {
             if tokens[cursor].token != Token::Comma {
-                help_message(tokens, cursor, "Expected comma".to_owned());
-                return None;
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected comma".to_owned()),
+                    cursor,
+                });
             }
 
             cursor += 1;
@@ -1065,55 +3662,140 @@ fn parse_select_items(
             expression: Expression::new(),
             as_clause: None,
             asterisk: false,
+            asterisk_source: None,
+            asterisk_modifiers: None,
         };
 
-        if let Some(TokenContainer {
-            token: Token::Asterisk,
-            loc: _,
-        }) = tokens.get(cursor)
-        {
-            cursor += 1;
+        if let Some((source, new_cursor)) = parse_asterisk(tokens, cursor) {
             select_item.asterisk = true;
+            select_item.asterisk_source = source;
+            let (modifiers, new_cursor) = parse_asterisk_modifiers(tokens, new_cursor)?;
+            select_item.asterisk_modifiers = modifiers;
+            cursor = new_cursor;
         } else {
             let (expression, new_cursor) =
-                match parse_expression(tokens, cursor, &delimiters_plus, 0, true, true) {
-                    None => {
-                        let x = help_message(tokens, cursor, "Expected expression".to_owned());
-                        println!("{}", x);
-                        return None;
-                    }
-                    Some(value) => value,
-                };
+                parse_expression(tokens, cursor, &delimiters_plus, 0, true, true)?;
             cursor = new_cursor;
             select_item.expression = expression;
 
             let mut found_as = false;
             if let Some(TokenContainer {
-                loc: _,
-                token: Token::As,
+                token: Token::As, ..
             }) = tokens.get(cursor)
             {
                 found_as = true;
                 cursor += 1;
             }
-            if let Some(TokenContainer {
-                token: Token::IdentifierValue { value },
-                loc: _,
-            }) = tokens.get(cursor)
-            {
-                select_item.as_clause = Some(value.clone());
+            if let Some(value) = parse_alias(tokens, cursor, found_as) {
+                select_item.as_clause = Some(value);
                 cursor += 1;
             } else if found_as {
-                let x = help_message(tokens, cursor, "Expected identifier after AS".to_owned());
-                println!("{}", x);
-                return None;
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected identifier after AS".to_owned()),
+                    cursor,
+                });
+            }
+        }
+
+        select_items.push(select_item);
+    }
+
+    Ok((select_items, cursor))
+}
+
+// A `*` or `name.*` select item at `cursor`, with the FROM item it is qualified by and the
+// cursor after it
+fn parse_asterisk(tokens: &[TokenContainer], cursor: usize) -> Option<(Option<String>, usize)> {
+    if let Some(Token::Asterisk) = tokens.get(cursor).map(|token| &token.token) {
+        return Some((None, cursor + 1));
+    }
+    let source = parse_name(tokens, cursor)?;
+    match (
+        tokens.get(cursor + 1).map(|token| &token.token),
+        tokens.get(cursor + 2).map(|token| &token.token),
+    ) {
+        (Some(Token::Dot), Some(Token::Asterisk)) => Some((Some(source), cursor + 3)),
+        _ => None,
+    }
+}
+
+// EXCEPT (columns) then REPLACE (items AS names) after a `*`, either of them optional.
+// Neither is a keyword, so they are only taken as one right after the `*` and before a
+// parenthesis.
+fn parse_asterisk_modifiers(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Result<(Option<AsteriskModifiers>, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+    let mut modifiers = AsteriskModifiers::default();
+
+    if is_asterisk_modifier(tokens, cursor, "except") {
+        cursor += 2;
+        loop {
+            let (column, new_cursor) = match parse_table_column(tokens, cursor) {
+                Some(value) => value,
+                None => {
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected column name".to_owned()),
+                        cursor,
+                    });
+                }
+            };
+            modifiers.except.push(column);
+            cursor = new_cursor;
+            match tokens.get(cursor).map(|token| &token.token) {
+                Some(Token::Comma) => cursor += 1,
+                Some(Token::RightParenthesis) => {
+                    cursor += 1;
+                    break;
+                }
+                _ => {
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected comma".to_owned()),
+                        cursor,
+                    });
+                }
             }
         }
+    }
 
-        select_items.push(select_item);
+    if is_asterisk_modifier(tokens, cursor, "replace") {
+        let (items, new_cursor) =
+            parse_select_items(tokens, cursor + 2, &vec![Token::RightParenthesis])?;
+        cursor = new_cursor;
+        if tokens.get(cursor).map(|token| &token.token) != Some(&Token::RightParenthesis) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected right parenthesis".to_owned()),
+                cursor,
+            });
+        }
+        // The AS of a replacement names the column it replaces
+        if items.is_empty() || items.iter().any(|item| item.as_clause.is_none()) {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, "Expected AS and column name".to_owned()),
+                cursor,
+            });
+        }
+        modifiers.replace = items;
+        cursor += 1;
     }
 
-    return Some((select_items, cursor));
+    if modifiers == AsteriskModifiers::default() {
+        return Ok((None, cursor));
+    }
+    Ok((Some(modifiers), cursor))
+}
+
+fn is_asterisk_modifier(tokens: &[TokenContainer], cursor: usize, name: &str) -> bool {
+    let is_name = matches!(
+        tokens.get(cursor).map(|token| &token.token),
+        Some(Token::IdentifierValue { value }) if value == name
+    );
+    is_name
+        && matches!(
+            tokens.get(cursor + 1).map(|token| &token.token),
+            Some(Token::LeftParenthesis)
+        )
 }
 
 fn parse_select_statement(
@@ -1121,14 +3803,37 @@ fn parse_select_statement(
     initial_cursor: usize,
     delimiter: Token,
 ) -> Result<(SelectStatement, usize), ParsingError> {
+    match parse_select_into_statement(tokens, initial_cursor, delimiter)? {
+        (select, None, cursor) => Ok((select, cursor)),
+        (_, Some(_), cursor) => Err(ParsingError::General {
+            msg: help_message(
+                tokens,
+                cursor,
+                "SELECT ... INTO is not allowed here".to_owned(),
+            ),
+            cursor,
+        }),
+    }
+}
+
+// The name of the table a SELECT goes INTO and whether it is temporary
+type SelectInto = Option<(String, bool)>;
+
+// Parses a SELECT that may have an INTO [TEMPORARY] [TABLE] name after its items, giving
+// the name of the table and whether it is temporary along with the query
+fn parse_select_into_statement(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: Token,
+) -> Result<(SelectStatement, SelectInto, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
     if let Some(TokenContainer {
         token: Token::Select,
-        loc: _,
+        ..
     }) = tokens.get(cursor)
     {
-    } else if let Some(TokenContainer { token: _, loc: _ }) = tokens.get(cursor) {
+    } else if let Some(TokenContainer { .. }) = tokens.get(cursor) {
         return Err(ParsingError::General {
             msg: "Not a Select statement".to_string(),
             cursor,
@@ -1144,7 +3849,7 @@ fn parse_select_statement(
     let mut distinct = false;
     if let Some(TokenContainer {
         token: Token::Distinct,
-        loc: _,
+        ..
     }) = tokens.get(cursor)
     {
         distinct = true;
@@ -1155,8 +3860,10 @@ fn parse_select_statement(
         items: Vec::with_capacity(10),
         from: vec![],
         where_clause: Expression::new(),
+        group_by: vec![],
+        having: Expression::new(),
         is_distinct: distinct,
-        order_by: None,
+        order_by: vec![],
         limit: None,
         offset: None,
     };
@@ -1165,30 +3872,62 @@ fn parse_select_statement(
         tokens,
         cursor,
         &vec![
+            Token::Into,
             Token::From,
+            Token::Where,
+            Token::GroupBy,
+            Token::Having,
             Token::OrderBy,
             Token::Limit,
             Token::Offset,
             delimiter.clone(),
         ],
     ) {
-        None => {
-            return Err(ParsingError::General {
-                msg: "Expected select items".to_string(),
+        Err(err) => {
+            return Err(expression_error(
+                err,
                 cursor,
-            });
+                ParsingError::General {
+                    msg: "Expected select items".to_string(),
+                    cursor,
+                },
+            ));
         }
-        Some(value) => value,
+        Ok(value) => value,
     };
 
     cursor = new_cursor;
     select.items = select_items;
 
+    let mut into = None;
+    if let Some(TokenContainer {
+        token: Token::Into, ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+        let is_temporary = expect_token(&mut tokens[cursor..].iter(), cursor, Token::Temporary);
+        if is_temporary {
+            cursor += 1;
+        }
+        if expect_token(&mut tokens[cursor..].iter(), cursor, Token::Table) {
+            cursor += 1;
+        }
+        match parse_name(tokens, cursor) {
+            Some(name) => into = Some((name, is_temporary)),
+            None => {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Table Name".to_owned()),
+                    cursor,
+                });
+            }
+        }
+        cursor += 1;
+    }
+
     // let delimiters_plus = vec![delimiter, &where_token];
 
     if let Some(TokenContainer {
-        token: Token::From,
-        loc: _,
+        token: Token::From, ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
@@ -1196,11 +3935,14 @@ fn parse_select_statement(
             tokens,
             cursor,
             &vec![
+                Token::Comma,
                 Token::Inner,
                 Token::Left,
                 Token::Right,
                 Token::Join,
                 Token::Where,
+                Token::GroupBy,
+                Token::Having,
                 Token::OrderBy,
                 Token::Limit,
                 Token::Offset,
@@ -1227,8 +3969,8 @@ fn parse_select_statement(
     cursor = new_cursor;*/
 
     if let Some(TokenContainer {
-        loc: _,
         token: Token::Where,
+        ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
@@ -1236,6 +3978,8 @@ fn parse_select_statement(
             tokens,
             cursor,
             &vec![
+                Token::GroupBy,
+                Token::Having,
                 Token::OrderBy,
                 Token::Limit,
                 Token::Offset,
@@ -1245,13 +3989,17 @@ fn parse_select_statement(
             true,
             false,
         ) {
-            None => {
-                return Err(ParsingError::General {
-                    msg: help_message(tokens, cursor, "Expected WHERE conditionals".to_owned()),
+            Err(err) => {
+                return Err(expression_error(
+                    err,
                     cursor,
-                });
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, "Expected WHERE conditionals".to_owned()),
+                        cursor,
+                    },
+                ));
             }
-            Some(value) => value,
+            Ok(value) => value,
         };
 
         cursor = new_cursor;
@@ -1259,133 +4007,263 @@ fn parse_select_statement(
     }
 
     if let Some(TokenContainer {
-        loc: _,
-        token: Token::OrderBy,
+        token: Token::GroupBy,
+        ..
     }) = tokens.get(cursor)
     {
-        cursor += 1;
+        let delimiters = vec![
+            Token::Comma,
+            Token::Having,
+            Token::OrderBy,
+            Token::Limit,
+            Token::Offset,
+            delimiter.clone(),
+        ];
+        loop {
+            cursor += 1;
+            let (exp, new_cursor) =
+                match parse_expression(tokens, cursor, &delimiters, 0, true, false) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        let msg = "Expected GROUP BY expression".to_owned();
+                        return Err(expression_error(
+                            err,
+                            cursor,
+                            ParsingError::General {
+                                msg: help_message(tokens, cursor, msg),
+                                cursor,
+                            },
+                        ));
+                    }
+                };
+            cursor = new_cursor;
+            select.group_by.push(exp);
+            match tokens.get(cursor) {
+                Some(TokenContainer {
+                    token: Token::Comma,
+                    ..
+                }) => {}
+                _ => break,
+            }
+        }
+    }
 
-        let (exp, new_cursor) = match parse_expression(
+    if let Some(TokenContainer {
+        token: Token::Having,
+        ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+        let (having, new_cursor) = match parse_expression(
             tokens,
             cursor,
             &vec![
-                Token::Desc,
-                Token::Asc,
+                Token::OrderBy,
                 Token::Limit,
                 Token::Offset,
-                delimiter,
+                delimiter.clone(),
             ],
             0,
             true,
-            true,
+            false,
         ) {
-            None => {
-                return Err(ParsingError::General {
-                    msg: help_message(tokens, cursor, "Expected WHERE conditionals".to_owned()),
+            Err(err) => {
+                let msg = "Expected HAVING conditionals".to_owned();
+                return Err(expression_error(
+                    err,
                     cursor,
-                });
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, msg),
+                        cursor,
+                    },
+                ));
             }
-            Some(value) => value,
+            Ok(value) => value,
         };
         cursor = new_cursor;
+        select.having = having;
+    }
+
+    if let Some(TokenContainer {
+        token: Token::OrderBy,
+        ..
+    }) = tokens.get(cursor)
+    {
+        let (order_by, new_cursor) = parse_order_by(
+            tokens,
+            cursor + 1,
+            &[Token::Limit, Token::Offset, delimiter],
+        )?;
+        cursor = new_cursor;
+        select.order_by = order_by;
+    }
+
+    // LIMIT and OFFSET can come in either order
+    loop {
+        match tokens.get(cursor) {
+            Some(TokenContainer {
+                token: Token::Limit,
+                ..
+            }) if select.limit.is_none() => {
+                let (limit, new_cursor) = parse_row_count(tokens, cursor + 1, "Limit")?;
+                cursor = new_cursor;
+                select.limit = Some(limit);
+            }
+            Some(TokenContainer {
+                token: Token::Offset,
+                ..
+            }) if select.offset.is_none() => {
+                let (offset, new_cursor) = parse_row_count(tokens, cursor + 1, "Offset")?;
+                cursor = new_cursor;
+                select.offset = Some(offset);
+            }
+            _ => break,
+        }
+    }
+
+    Ok((select, into, cursor))
+}
+
+// Parses the expressions of ORDER BY, separated by commas, each with ASC or DESC after
+// it. The last one ends at one of `delimiters`.
+fn parse_order_by(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiters: &[Token],
+) -> Result<(Vec<OrderByClause>, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let mut expression_delimiters = vec![Token::Desc, Token::Asc, Token::Comma];
+    expression_delimiters.extend_from_slice(delimiters);
+    let mut order_by = vec![];
+    loop {
+        let (exp, new_cursor) =
+            match parse_expression(tokens, cursor, &expression_delimiters, 0, true, true) {
+                Err(err) => {
+                    let msg = "Expected ORDER BY expression".to_owned();
+                    return Err(expression_error(
+                        err,
+                        cursor,
+                        ParsingError::General {
+                            msg: help_message(tokens, cursor, msg),
+                            cursor,
+                        },
+                    ));
+                }
+                Ok(value) => value,
+            };
+        cursor = new_cursor;
         let mut order_by_clause = OrderByClause { asc: true, exp };
 
         if let Some(TokenContainer {
-            loc: _,
-            token: Token::Asc,
+            token: Token::Asc, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             order_by_clause.asc = true;
         } else if let Some(TokenContainer {
-            loc: _,
-            token: Token::Desc,
+            token: Token::Desc, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             order_by_clause.asc = false;
         }
-
-        select.order_by = Some(order_by_clause);
+        order_by.push(order_by_clause);
+
+        match tokens.get(cursor) {
+            Some(TokenContainer {
+                token: Token::Comma,
+                ..
+            }) => cursor += 1,
+            _ => break,
+        }
     }
 
+    Ok((order_by, cursor))
+}
+
+// Parses the ORDER BY and LIMIT an UPDATE or DELETE can have before RETURNING, to pick
+// which of the rows WHERE holds for it changes
+fn parse_change_order_limit(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiter: &Token,
+) -> Result<(Vec<OrderByClause>, Option<usize>, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let mut order_by = vec![];
     if let Some(TokenContainer {
-        loc: _,
-        token: Token::Limit,
+        token: Token::OrderBy,
+        ..
     }) = tokens.get(cursor)
     {
-        cursor += 1;
-
-        if let Some(TokenContainer {
-            loc: _,
-            token: Token::NumericValue { value },
-        }) = tokens.get(cursor)
-        {
-            cursor += 1;
-            let limit = match value.parse::<f64>() {
-                Ok(val) => val,
-                Err(err) => {
-                    return Err(ParsingError::General {
-                        msg: format!("Failed to parse Limit value: {}", err.to_string()),
-                        cursor,
-                    });
-                }
-            };
-            if limit.is_sign_negative() {
-                return Err(ParsingError::General {
-                    msg: format!("Limit must not be negative"),
-                    cursor,
-                });
-            }
-            if limit.is_nan() || limit.is_infinite() {
-                return Err(ParsingError::General {
-                    msg: format!("Limit cannot be interpreted as a whole number"),
-                    cursor,
-                });
-            }
-            select.limit = Some(limit as usize);
-        }
+        let (clauses, new_cursor) = parse_order_by(
+            tokens,
+            cursor + 1,
+            &[Token::Limit, Token::Returning, delimiter.clone()],
+        )?;
+        cursor = new_cursor;
+        order_by = clauses;
     }
 
+    let mut limit = None;
     if let Some(TokenContainer {
-        loc: _,
-        token: Token::Offset,
+        token: Token::Limit,
+        ..
     }) = tokens.get(cursor)
     {
-        cursor += 1;
+        let (count, new_cursor) = parse_row_count(tokens, cursor + 1, "Limit")?;
+        cursor = new_cursor;
+        limit = Some(count);
+    }
 
-        if let Some(TokenContainer {
-            loc: _,
+    Ok((order_by, limit, cursor))
+}
+
+// Parses the number of rows given to LIMIT or OFFSET, `clause` naming it in errors
+fn parse_row_count(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    clause: &str,
+) -> Result<(usize, usize), ParsingError> {
+    let mut cursor = initial_cursor;
+
+    let value = match tokens.get(cursor) {
+        Some(TokenContainer {
             token: Token::NumericValue { value },
-        }) = tokens.get(cursor)
-        {
-            cursor += 1;
-            let offset = match value.parse::<f64>() {
-                Ok(val) => val,
-                Err(err) => {
-                    return Err(ParsingError::General {
-                        msg: format!("Failed to parse Offset value: {}", err.to_string()),
-                        cursor,
-                    });
-                }
-            };
-            if offset.is_sign_negative() {
-                return Err(ParsingError::General {
-                    msg: format!("Offset must not be negative"),
-                    cursor,
-                });
-            }
-            if offset.is_nan() || offset.is_infinite() {
-                return Err(ParsingError::General {
-                    msg: format!("Offset cannot be interpreted as a whole number"),
-                    cursor,
-                });
-            }
-            select.offset = Some(offset as usize);
+            ..
+        }) => value,
+        _ => {
+            return Err(ParsingError::General {
+                msg: help_message(tokens, cursor, format!("Expected {} value", clause)),
+                cursor,
+            });
+        }
+    };
+    cursor += 1;
+    let count = match value.parse::<f64>() {
+        Ok(val) => val,
+        Err(err) => {
+            return Err(ParsingError::General {
+                msg: format!("Failed to parse {} value: {}", clause, err),
+                cursor,
+            });
         }
     };
+    if count.is_sign_negative() {
+        return Err(ParsingError::General {
+            msg: format!("{} must not be negative", clause),
+            cursor,
+        });
+    }
+    if count.is_nan() || count.is_infinite() {
+        return Err(ParsingError::General {
+            msg: format!("{} cannot be interpreted as a whole number", clause),
+            cursor,
+        });
+    }
 
-    Ok((select, cursor))
+    Ok((count as usize, cursor))
 }
 
 fn parse_joins(
@@ -1402,55 +4280,52 @@ fn parse_joins(
         if let None = tokens.get(cursor) {
             break;
         } else if let Some(TokenContainer {
-            token: Token::On,
-            loc: _,
+            token: Token::On, ..
         }) = tokens.get(cursor)
         {
             break;
         } else if let Some(TokenContainer {
             token: Token::Inner,
-            loc: _,
+            ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             kind = JoinKind::Inner;
         } else if let Some(TokenContainer {
             token: Token::Right,
-            loc: _,
+            ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             if let Some(TokenContainer {
                 token: Token::Outer,
-                loc: _,
+                ..
             }) = tokens.get(cursor)
             {
                 cursor += 1;
             }
             kind = JoinKind::RightOuter;
         } else if let Some(TokenContainer {
-            token: Token::Left,
-            loc: _,
+            token: Token::Left, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             if let Some(TokenContainer {
                 token: Token::Outer,
-                loc: _,
+                ..
             }) = tokens.get(cursor)
             {
                 cursor += 1;
             }
             kind = JoinKind::LeftOuter;
         } else if let Some(TokenContainer {
-            token: Token::Full,
-            loc: _,
+            token: Token::Full, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
             if let Some(TokenContainer {
                 token: Token::Outer,
-                loc: _,
+                ..
             }) = tokens.get(cursor)
             {
                 cursor += 1;
@@ -1461,7 +4336,13 @@ fn parse_joins(
                     cursor,
                 });
             }
-        } else if let Some(TokenContainer { token, loc: _ }) = tokens.get(cursor) {
+        } else if let Some(TokenContainer {
+            token: Token::Join, ..
+        }) = tokens.get(cursor)
+        {
+            // A plain JOIN is an inner one
+            kind = JoinKind::Inner;
+        } else if let Some(TokenContainer { token, .. }) = tokens.get(cursor) {
             if delimiters.contains(token) {
                 break;
             }
@@ -1471,8 +4352,7 @@ fn parse_joins(
             });
         }
         if let Some(TokenContainer {
-            token: Token::Join,
-            loc: _,
+            token: Token::Join, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
@@ -1485,8 +4365,7 @@ fn parse_joins(
         let (table, new_cursor) = parse_table(tokens, cursor, delimiters)?;
         cursor = new_cursor;
         if let Some(TokenContainer {
-            token: Token::On,
-            loc: _,
+            token: Token::On, ..
         }) = tokens.get(cursor)
         {
             cursor += 1;
@@ -1506,7 +4385,7 @@ fn parse_joins(
             }
         };
         cursor = new_cursor;
-        let operand = if let Some(TokenContainer { token, loc: _ }) = tokens.get(cursor) {
+        let operand = if let Some(TokenContainer { token, .. }) = tokens.get(cursor) {
             cursor += 1;
             if BINARY_OPERATORS.contains(token) {
                 token.clone()
@@ -1545,95 +4424,190 @@ fn parse_joins(
         joins.push(join);
     }
 
-    Ok((joins, cursor))
+    Ok((joins, cursor))
+}
+
+fn parse_table_column(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+) -> Option<(TableColumn, usize)> {
+    let mut cursor = initial_cursor;
+
+    let mut col_name = parse_name(tokens, cursor)?;
+    cursor += 1;
+    let mut table_name = None;
+    if let Some(TokenContainer {
+        token: Token::Dot, ..
+    }) = tokens.get(cursor)
+    {
+        cursor += 1;
+        table_name = Some(col_name);
+        col_name = parse_name(tokens, cursor)?;
+        cursor += 1;
+    }
+    Some((
+        TableColumn {
+            col_name,
+            table_name,
+        },
+        cursor,
+    ))
+}
+
+fn parse_tables(
+    tokens: &Vec<TokenContainer>,
+    initial_cursor: usize,
+    delimiters: &Vec<Token>,
+) -> Result<(Vec<RowDataSource>, usize), ParsingError> {
+    let (mut first, mut cursor) = parse_table(tokens, initial_cursor, delimiters)?;
+
+    // Items after a comma are joined to the first on every pair of rows, followed by the
+    // joins of their own
+    while let Some(TokenContainer {
+        token: Token::Comma,
+        ..
+    }) = tokens.get(cursor)
+    {
+        let (mut table, new_cursor) = parse_table(tokens, cursor + 1, delimiters)?;
+        cursor = new_cursor;
+        let table_joins = std::mem::take(source_joins_mut(&mut table));
+        let joins = source_joins_mut(&mut first);
+        joins.push(JoinClause {
+            kind: JoinKind::Inner,
+            source: table,
+            on: Expression::new_literal_bool(TRUE_KEYWORD.to_string()),
+        });
+        joins.extend(table_joins);
+    }
+    match tokens.get(cursor) {
+        Some(TokenContainer { token, .. }) if !delimiters.contains(token) => {
+            return Err(ParsingError::General {
+                msg: format!("Failed to parse table, unexpected {:?}", token),
+                cursor,
+            });
+        }
+        _ => {}
+    }
+
+    Ok((vec![first], cursor))
 }
 
-fn parse_table_column(
+fn source_joins_mut(source: &mut RowDataSource) -> &mut Vec<JoinClause> {
+    match source {
+        RowDataSource::Table { joins, .. }
+        | RowDataSource::SubSelect { joins, .. }
+        | RowDataSource::Function { joins, .. }
+        | RowDataSource::Values { joins, .. } => joins,
+    }
+}
+
+// Parses what follows TABLESAMPLE, `method (percentage)` and optionally `REPEATABLE (seed)`.
+// REPEATABLE is not a keyword, it is only taken as one right after the percentage.
+fn parse_table_sample(
     tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
-) -> Option<(TableColumn, usize)> {
+) -> Result<(TableSample, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
-    if let Some(tok) = tokens.get(cursor) {
-        match tok.token {
-            Token::IdentifierValue {
-                value: ref first_identifier,
-            } => {
-                cursor += 1;
-                let mut col_name = first_identifier.clone();
-                let mut table_name = None;
-                if let Some(TokenContainer {
-                    token: Token::Dot,
-                    loc: _,
-                }) = tokens.get(cursor)
-                {
-                    cursor += 1;
-                    if let Some(TokenContainer {
-                        token: Token::IdentifierValue { value },
-                        loc: _,
-                    }) = tokens.get(cursor)
-                    {
-                        cursor += 1;
-                        table_name = Some(col_name);
-                        col_name = value.clone();
-                    } else {
-                        return None;
-                    }
-                }
-                Some((
-                    TableColumn {
-                        col_name,
-                        table_name,
-                    },
+    let method = match tokens.get(cursor) {
+        Some(TokenContainer {
+            token: Token::IdentifierValue { value },
+            ..
+        }) => value.to_string(),
+        _ => {
+            return Err(ParsingError::General {
+                msg: help_message(
+                    tokens,
                     cursor,
-                ))
-            }
-            _ => None,
+                    "Expected a sampling method after TABLESAMPLE".to_owned(),
+                ),
+                cursor,
+            });
+        }
+    };
+    cursor += 1;
+
+    let (percentage, new_cursor) = parse_sample_argument(tokens, cursor)?;
+    cursor = new_cursor;
+
+    let mut repeatable = None;
+    if let (
+        Some(TokenContainer {
+            token: Token::IdentifierValue { value },
+            ..
+        }),
+        Some(TokenContainer {
+            token: Token::LeftParenthesis,
+            ..
+        }),
+    ) = (tokens.get(cursor), tokens.get(cursor + 1))
+    {
+        if value == "repeatable" {
+            let (seed, new_cursor) = parse_sample_argument(tokens, cursor + 1)?;
+            repeatable = Some(seed);
+            cursor = new_cursor;
         }
-    } else {
-        None
     }
+
+    Ok((
+        TableSample {
+            method,
+            percentage,
+            repeatable,
+        },
+        cursor,
+    ))
 }
 
-fn parse_tables(
+// Parses an argument of TABLESAMPLE or REPEATABLE, an expression in parentheses
+fn parse_sample_argument(
     tokens: &Vec<TokenContainer>,
     initial_cursor: usize,
-    delimiters: &Vec<Token>,
-) -> Result<(Vec<RowDataSource>, usize), ParsingError> {
+) -> Result<(Expression, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
-    let mut tables = vec![];
+    if !expect_token(&mut tokens[cursor..].iter(), cursor, Token::LeftParenthesis) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected (".to_owned()),
+            cursor,
+        });
+    }
+    cursor += 1;
 
-    loop {
-        let (table, new_cursor) = parse_table(tokens, cursor, delimiters)?;
-        cursor = new_cursor;
-        tables.push(table);
-        if let None = tokens.get(cursor) {
-            break;
-        } else if let Some(TokenContainer {
-            token: Token::Comma,
-            loc: _,
-        }) = tokens.get(cursor)
-        {
-            continue;
-        } else if let Some(TokenContainer { token, loc: _ }) = tokens.get(cursor) {
-            if delimiters.contains(token) {
-                break;
-            } else {
-                return Err(ParsingError::General {
-                    msg: format!("Failed to parse table, unexpected {:?}", token),
-                    cursor,
-                });
-            }
-        } else {
-            return Err(ParsingError::General {
-                msg: "Failed to parse table".to_string(),
+    let (argument, new_cursor) = match parse_expression(
+        tokens,
+        cursor,
+        &vec![Token::RightParenthesis],
+        0,
+        true,
+        false,
+    ) {
+        Ok(value) => value,
+        Err(err) => {
+            return Err(expression_error(
+                err,
                 cursor,
-            });
+                ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected sampling argument".to_owned()),
+                    cursor,
+                },
+            ));
         }
+    };
+    cursor = new_cursor;
+
+    if !expect_token(
+        &mut tokens[cursor..].iter(),
+        cursor,
+        Token::RightParenthesis,
+    ) {
+        return Err(ParsingError::General {
+            msg: help_message(tokens, cursor, "Expected closing parenthesis".to_owned()),
+            cursor,
+        });
     }
 
-    Ok((tables, cursor))
+    Ok((argument, cursor + 1))
 }
 
 fn parse_table(
@@ -1643,35 +4617,116 @@ fn parse_table(
 ) -> Result<(RowDataSource, usize), ParsingError> {
     let mut cursor = initial_cursor;
 
-    if let Some(TokenContainer {
-        token: Token::IdentifierValue { value },
-        loc: _,
-    }) = tokens.get(cursor)
-    {
+    let lateral = expect_token(&mut tokens[cursor..].iter(), cursor, Token::Lateral);
+    if lateral {
         cursor += 1;
-        let mut as_clause = None;
-        let table_name = value.clone();
+    }
+
+    let qualified_name = parse_qualified_name(tokens, cursor);
+    let opens_call = |name_end: usize| {
+        matches!(
+            tokens.get(name_end).map(|token| &token.token),
+            Some(Token::LeftParenthesis)
+        )
+    };
+    if let Some((name, name_end)) = qualified_name.filter(|(_, name_end)| opens_call(*name_end)) {
+        let (call, new_cursor) = match parse_function_call(tokens, name_end + 1, name) {
+            Ok(value) => value,
+            Err(err) => {
+                let msg = "Failed to parse function call".to_owned();
+                return Err(expression_error(
+                    err,
+                    cursor,
+                    ParsingError::General {
+                        msg: help_message(tokens, cursor, msg),
+                        cursor,
+                    },
+                ));
+            }
+        };
+        cursor = new_cursor;
         let mut found_as = false;
         if let Some(TokenContainer {
-            token: Token::As,
-            loc: _,
+            token: Token::As, ..
         }) = tokens.get(cursor)
         {
             found_as = true;
             cursor += 1;
         }
+        let mut as_clause = None;
+        let mut column_aliases = vec![];
+        if let Some(value) = parse_alias(tokens, cursor, found_as) {
+            cursor += 1;
+            as_clause = Some(value);
+            if let Some(TokenContainer {
+                token: Token::LeftParenthesis,
+                ..
+            }) = tokens.get(cursor)
+            {
+                let (names, new_cursor) = parse_column_names(tokens, cursor + 1)?;
+                column_aliases = names;
+                cursor = new_cursor;
+            }
+        } else if found_as {
+            return Err(ParsingError::General {
+                msg: "Failed to parse as clause after AS".to_string(),
+                cursor,
+            });
+        }
+        let (joins, new_cursor) = parse_joins(tokens, cursor, delimiters)?;
+        cursor = new_cursor;
+        return Ok((
+            RowDataSource::Function {
+                call,
+                as_clause,
+                column_aliases,
+                lateral,
+                joins,
+            },
+            cursor,
+        ));
+    } else if let Some((table_name, name_end)) = parse_relation_name(tokens, cursor) {
+        if lateral {
+            return Err(ParsingError::General {
+                msg: help_message(
+                    tokens,
+                    cursor,
+                    "Expected a subquery or a function call after LATERAL".to_owned(),
+                ),
+                cursor,
+            });
+        }
+        cursor = name_end;
+        let mut as_clause = None;
+        let mut found_as = false;
         if let Some(TokenContainer {
-            token: Token::IdentifierValue { value },
-            loc: _,
+            token: Token::As, ..
         }) = tokens.get(cursor)
         {
+            found_as = true;
+            cursor += 1;
+        }
+        if let Some(value) = parse_alias(tokens, cursor, found_as) {
             cursor += 1;
-            as_clause = Some(value.clone());
+            as_clause = Some(value);
         } else if found_as {
             return Err(ParsingError::General {
                 msg: "Failed to parse as clause after AS".to_string(),
                 cursor,
             });
+        } else if let Some((_, name)) = table_name.split_once('.') {
+            // Qualified by its schema, a table still goes by its name alone
+            as_clause = Some(name.to_string());
+        }
+        let mut sample = None;
+        if let Some(TokenContainer {
+            token: Token::Tablesample,
+            ..
+        }) = tokens.get(cursor)
+        {
+            let (table_sample, new_cursor) = parse_table_sample(tokens, cursor + 1)?;
+            sample = Some(table_sample);
+            cursor = new_cursor;
         }
         let (joins, new_cursor) = parse_joins(tokens, cursor, delimiters)?;
         cursor = new_cursor;
@@ -1679,19 +4734,88 @@ fn parse_table(
             RowDataSource::Table {
                 table_name,
                 as_clause,
+                sample,
                 joins,
             },
             cursor,
         ));
     } else if let Some(TokenContainer {
         token: Token::LeftParenthesis,
-        loc: _,
+        ..
     }) = tokens.get(cursor)
     {
         cursor += 1;
         if let Some(TokenContainer {
+            token: Token::Values,
+            ..
+        }) = tokens.get(cursor)
+        {
+            if lateral {
+                return Err(ParsingError::General {
+                    msg: help_message(
+                        tokens,
+                        cursor,
+                        "Expected a subquery or a function call after LATERAL".to_owned(),
+                    ),
+                    cursor,
+                });
+            }
+            let (rows, new_cursor) = parse_values_list(tokens, cursor)?;
+            cursor = new_cursor;
+            if !expect_token(
+                &mut tokens[cursor..].iter(),
+                cursor,
+                Token::RightParenthesis,
+            ) {
+                return Err(ParsingError::General {
+                    msg: help_message(tokens, cursor, "Expected Right Parenthesis".to_owned()),
+                    cursor,
+                });
+            }
+            cursor += 1;
+            let mut found_as = false;
+            if let Some(TokenContainer {
+                token: Token::As, ..
+            }) = tokens.get(cursor)
+            {
+                found_as = true;
+                cursor += 1;
+            }
+            let as_clause = match parse_alias(tokens, cursor, found_as) {
+                Some(as_clause) => as_clause,
+                None => {
+                    let msg = "VALUES in FROM must have an alias".to_owned();
+                    return Err(ParsingError::General {
+                        msg: help_message(tokens, cursor, msg),
+                        cursor,
+                    });
+                }
+            };
+            cursor += 1;
+            let mut column_aliases = vec![];
+            if let Some(TokenContainer {
+                token: Token::LeftParenthesis,
+                ..
+            }) = tokens.get(cursor)
+            {
+                let (names, new_cursor) = parse_column_names(tokens, cursor + 1)?;
+                column_aliases = names;
+                cursor = new_cursor;
+            }
+            let (joins, new_cursor) = parse_joins(tokens, cursor, delimiters)?;
+            cursor = new_cursor;
+            return Ok((
+                RowDataSource::Values {
+                    rows,
+                    as_clause,
+                    column_aliases,
+                    joins,
+                },
+                cursor,
+            ));
+        } else if let Some(TokenContainer {
             token: Token::Select,
-            loc: _,
+            ..
         }) = tokens.get(cursor)
         {
             let (select, new_cursor) =
@@ -1699,26 +4823,21 @@ fn parse_table(
             cursor = new_cursor + 1;
             let mut found_as = false;
             if let Some(TokenContainer {
-                token: Token::As,
-                loc: _,
+                token: Token::As, ..
             }) = tokens.get(cursor)
             {
                 found_as = true;
                 cursor += 1;
             }
-            if let Some(TokenContainer {
-                token: Token::IdentifierValue { value },
-                loc: _,
-            }) = tokens.get(cursor)
-            {
+            if let Some(as_clause) = parse_alias(tokens, cursor, found_as) {
                 cursor += 1;
-                let as_clause = value.clone();
                 let (joins, new_cursor) = parse_joins(tokens, cursor, delimiters)?;
                 cursor = new_cursor;
                 return Ok((
                     RowDataSource::SubSelect {
-                        select,
+                        select: Box::new(select),
                         as_clause,
+                        lateral,
                         joins,
                     },
                     cursor,
@@ -1728,7 +4847,7 @@ fn parse_table(
                     msg: "Failed to parse as clause after AS".to_string(),
                     cursor,
                 });
-            } else if let Some(TokenContainer { token, loc: _ }) = tokens.get(cursor) {
+            } else if let Some(TokenContainer { token, .. }) = tokens.get(cursor) {
                 return Err(ParsingError::General {
                     msg: format!("Unexpected {:?}, subquery requires as clause", token),
                     cursor,
@@ -1761,18 +4880,51 @@ mod parser_tests {
                 ast: Ast {
                     statements: vec![Statement::InsertStatement(InsertStatement {
                         table: "users".to_owned(),
-                        values: vec![
+                        rows: vec![vec![
                             Expression::Literal(LiteralExpression {
                                 literal: Token::NumericValue {
-                                    value: "105".to_owned(),
+                                    value: "105".into(),
                                 },
                             }),
                             Expression::Literal(LiteralExpression {
                                 literal: Token::StringValue {
-                                    value: "George".to_owned(),
+                                    value: "George".into(),
                                 },
                             }),
-                        ],
+                        ]],
+                        query: None,
+                        returning: vec![],
+                    })],
+                },
+            },
+            ParseTest {
+                input: "INSERT INTO users VALUES (1 + 2, upper('a'));",
+                ast: Ast {
+                    statements: vec![Statement::InsertStatement(InsertStatement {
+                        table: "users".to_owned(),
+                        rows: vec![vec![
+                            Expression::Binary(BinaryExpression {
+                                first: Box::new(Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "1".into() },
+                                })),
+                                second: Box::new(Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "2".into() },
+                                })),
+                                operand: Token::Plus,
+                            }),
+                            Expression::FunctionCall(FunctionCall {
+                                name: "upper".to_owned(),
+                                args: vec![Expression::Literal(LiteralExpression {
+                                    literal: Token::StringValue { value: "a".into() },
+                                })],
+                                asterisk: false,
+                                distinct: false,
+                                filter: None,
+                                over: None,
+                            }),
+                        ]],
+                        query: None,
+                        returning: vec![],
                     })],
                 },
             },
@@ -1787,21 +4939,292 @@ mod parser_tests {
                                 data_type: TokenContainer {
                                     loc: TokenLocation { col: 23, line: 0 },
                                     token: Token::Int,
+                                    span: Span {
+                                        start: 23,
+                                        end: 26,
+                                        end_loc: TokenLocation { col: 26, line: 0 },
+                                    },
                                 },
+                                type_modifier: None,
                                 is_primary_key: false,
+                                default: None,
+                                not_null: false,
+                                generated: None,
+                                collation: None,
+                                serial: false,
                             },
                             ColumnDefinition {
                                 name: "name".to_owned(),
                                 data_type: TokenContainer {
                                     loc: TokenLocation { col: 33, line: 0 },
                                     token: Token::Text,
+                                    span: Span {
+                                        start: 33,
+                                        end: 37,
+                                        end_loc: TokenLocation { col: 37, line: 0 },
+                                    },
                                 },
+                                type_modifier: None,
                                 is_primary_key: false,
+                                default: None,
+                                not_null: false,
+                                generated: None,
+                                collation: None,
+                                serial: false,
                             },
                         ],
+                        checks: vec![],
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE TABLE t (code VARCHAR(20) NOT NULL);",
+                ast: Ast {
+                    statements: vec![Statement::CreateTableStatement(CreateTableStatement {
+                        name: "t".to_owned(),
+                        cols: vec![ColumnDefinition {
+                            name: "code".to_owned(),
+                            data_type: TokenContainer {
+                                loc: TokenLocation { col: 21, line: 0 },
+                                token: Token::Varchar,
+                                span: Span {
+                                    start: 21,
+                                    end: 28,
+                                    end_loc: TokenLocation { col: 28, line: 0 },
+                                },
+                            },
+                            type_modifier: Some(20),
+                            is_primary_key: false,
+                            default: None,
+                            not_null: true,
+                            generated: None,
+                            collation: None,
+                            serial: false,
+                        }],
+                        checks: vec![],
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE TABLE t (s TEXT COLLATE nocase NOT NULL);",
+                ast: Ast {
+                    statements: vec![Statement::CreateTableStatement(CreateTableStatement {
+                        name: "t".to_owned(),
+                        cols: vec![ColumnDefinition {
+                            name: "s".to_owned(),
+                            data_type: TokenContainer {
+                                loc: TokenLocation { col: 18, line: 0 },
+                                token: Token::Text,
+                                span: Span {
+                                    start: 18,
+                                    end: 22,
+                                    end_loc: TokenLocation { col: 22, line: 0 },
+                                },
+                            },
+                            type_modifier: None,
+                            is_primary_key: false,
+                            default: None,
+                            not_null: true,
+                            generated: None,
+                            collation: Some("nocase".to_owned()),
+                            serial: false,
+                        }],
+                        checks: vec![],
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE TABLE t (n INT GENERATED ALWAYS AS (1 + 2) STORED NOT NULL);",
+                ast: Ast {
+                    statements: vec![Statement::CreateTableStatement(CreateTableStatement {
+                        name: "t".to_owned(),
+                        cols: vec![ColumnDefinition {
+                            name: "n".to_owned(),
+                            data_type: TokenContainer {
+                                loc: TokenLocation { col: 18, line: 0 },
+                                token: Token::Int,
+                                span: Span {
+                                    start: 18,
+                                    end: 21,
+                                    end_loc: TokenLocation { col: 21, line: 0 },
+                                },
+                            },
+                            type_modifier: None,
+                            is_primary_key: false,
+                            default: None,
+                            not_null: true,
+                            generated: Some(Expression::Binary(BinaryExpression {
+                                first: Box::new(Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "1".into() },
+                                })),
+                                second: Box::new(Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "2".into() },
+                                })),
+                                operand: Token::Plus,
+                            })),
+                            collation: None,
+                            serial: false,
+                        }],
+                        checks: vec![],
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE TABLE t (id BIGSERIAL);",
+                ast: Ast {
+                    statements: vec![Statement::CreateTableStatement(CreateTableStatement {
+                        name: "t".to_owned(),
+                        cols: vec![ColumnDefinition {
+                            name: "id".to_owned(),
+                            data_type: TokenContainer {
+                                loc: TokenLocation { col: 19, line: 0 },
+                                token: Token::BigInt,
+                                span: Span {
+                                    start: 19,
+                                    end: 28,
+                                    end_loc: TokenLocation { col: 28, line: 0 },
+                                },
+                            },
+                            type_modifier: None,
+                            is_primary_key: false,
+                            default: None,
+                            not_null: false,
+                            generated: None,
+                            collation: None,
+                            serial: true,
+                        }],
+                        checks: vec![],
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE SEQUENCE s START 100 INCREMENT 5 NO MINVALUE MAXVALUE 1000 CYCLE;",
+                ast: Ast {
+                    statements: vec![Statement::CreateSequenceStatement(CreateSequenceStatement {
+                        name: "s".to_owned(),
+                        increment: Some(5),
+                        min_value: None,
+                        max_value: Some(1000),
+                        start: Some(100),
+                        cycle: true,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE SEQUENCE d INCREMENT BY -1 START WITH -2 MINVALUE -10 NO CYCLE;",
+                ast: Ast {
+                    statements: vec![Statement::CreateSequenceStatement(CreateSequenceStatement {
+                        name: "d".to_owned(),
+                        increment: Some(-1),
+                        min_value: Some(-10),
+                        max_value: None,
+                        start: Some(-2),
+                        cycle: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "DROP SEQUENCE IF EXISTS public.d;",
+                ast: Ast {
+                    statements: vec![Statement::DropSequenceStatement(DropSequenceStatement {
+                        name: "d".to_owned(),
+                        if_exists: true,
                     })],
                 },
             },
+            ParseTest {
+                input: "CREATE VIEW v AS SELECT id FROM users;",
+                ast: Ast {
+                    statements: vec![Statement::CreateViewStatement(CreateViewStatement {
+                        name: "v".to_owned(),
+                        query: SelectStatement {
+                            items: vec![SelectItem {
+                                asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
+                                as_clause: None,
+                                expression: Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                }),
+                            }],
+                            from: vec![RowDataSource::Table {
+                                table_name: "users".to_string(),
+                                as_clause: None,
+                                sample: None,
+                                joins: vec![],
+                            }],
+                            where_clause: Expression::Empty,
+                            group_by: vec![],
+                            having: Expression::Empty,
+                            is_distinct: false,
+                            order_by: vec![],
+                            limit: None,
+                            offset: None,
+                        },
+                        definition: "SELECT id FROM users".to_owned(),
+                    })],
+                },
+            },
+            ParseTest {
+                input: "DROP VIEW IF EXISTS v CASCADE; DROP TABLE t RESTRICT;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::DropViewStatement(DropViewStatement {
+                            name: "v".to_owned(),
+                            if_exists: true,
+                            cascade: true,
+                        }),
+                        Statement::DropTableStatement(DropTableStatement {
+                            name: "t".to_owned(),
+                            cascade: false,
+                        }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "CREATE TRIGGER tr AFTER DELETE OR UPDATE ON users FOR EACH ROW
+                    EXECUTE (DELETE FROM posts WHERE user_id = OLD.id);
+                    DROP TRIGGER IF EXISTS tr ON users;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::CreateTriggerStatement(CreateTriggerStatement {
+                            name: "tr".to_owned(),
+                            table: "users".to_owned(),
+                            events: vec![TriggerEvent::Delete, TriggerEvent::Update],
+                            for_each_row: true,
+                            action: Box::new(Statement::DeleteStatement(DeleteStatement {
+                                table: "posts".to_owned(),
+                                as_clause: None,
+                                where_clause: Expression::Binary(BinaryExpression {
+                                    first: Box::new(Expression::TableColumn(TableColumn {
+                                        col_name: "user_id".to_owned(),
+                                        table_name: None,
+                                    })),
+                                    second: Box::new(Expression::TableColumn(TableColumn {
+                                        col_name: "id".to_owned(),
+                                        table_name: Some("old".to_owned()),
+                                    })),
+                                    operand: Token::Equal,
+                                }),
+                                order_by: vec![],
+                                limit: None,
+                                returning: vec![],
+                            })),
+                        }),
+                        Statement::DropTriggerStatement(DropTriggerStatement {
+                            name: "tr".to_owned(),
+                            table: "users".to_owned(),
+                            if_exists: true,
+                        }),
+                    ],
+                },
+            },
             ParseTest {
                 input: "SELECT id, name AS fullname FROM users;",
                 ast: Ast {
@@ -1809,6 +5232,8 @@ mod parser_tests {
                         items: vec![
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: None,
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "id".to_owned(),
@@ -1817,6 +5242,8 @@ mod parser_tests {
                             },
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: Some("fullname".to_owned()),
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "name".to_owned(),
@@ -1827,11 +5254,14 @@ mod parser_tests {
                         from: vec![RowDataSource::Table {
                             table_name: "users".to_string(),
                             as_clause: None,
+                            sample: None,
                             joins: vec![],
                         }],
                         where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
                         is_distinct: false,
-                        order_by: None,
+                        order_by: vec![],
                         limit: None,
                         offset: None,
                     })],
@@ -1844,6 +5274,8 @@ mod parser_tests {
                         items: vec![
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: None,
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "id".to_owned(),
@@ -1852,6 +5284,8 @@ mod parser_tests {
                             },
                             SelectItem {
                                 asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
                                 as_clause: Some("fullname".to_owned()),
                                 expression: Expression::TableColumn(TableColumn {
                                     col_name: "name".to_owned(),
@@ -1862,11 +5296,636 @@ mod parser_tests {
                         from: vec![RowDataSource::Table {
                             table_name: "users".to_string(),
                             as_clause: None,
+                            sample: None,
                             joins: vec![],
                         }],
                         where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
                         is_distinct: true,
-                        order_by: None,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "CREATE TABLE summary (department) AS SELECT dept FROM emp WITH NO DATA;",
+                ast: Ast {
+                    statements: vec![Statement::CreateTableAsStatement(CreateTableAsStatement {
+                        name: "summary".to_owned(),
+                        columns: vec!["department".to_owned()],
+                        query: SelectStatement {
+                            items: vec![SelectItem {
+                                asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
+                                as_clause: None,
+                                expression: Expression::TableColumn(TableColumn {
+                                    col_name: "dept".to_owned(),
+                                    table_name: None,
+                                }),
+                            }],
+                            from: vec![RowDataSource::Table {
+                                table_name: "emp".to_string(),
+                                as_clause: None,
+                                sample: None,
+                                joins: vec![],
+                            }],
+                            ..SelectStatement::new()
+                        },
+                        with_data: false,
+                        is_temporary: false,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SET max_result_rows TO 10;",
+                ast: Ast {
+                    statements: vec![Statement::SetStatement(SetStatement {
+                        name: "max_result_rows".to_owned(),
+                        value: Token::NumericValue { value: "10".into() },
+                        local: false,
+                        name_loc: TokenLocation { line: 0, col: 4 },
+                        value_loc: TokenLocation { line: 0, col: 23 },
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SET LOCAL timezone = DEFAULT; SET local = 'on';",
+                ast: Ast {
+                    statements: vec![
+                        Statement::SetStatement(SetStatement {
+                            name: "timezone".to_owned(),
+                            value: Token::Default,
+                            local: true,
+                            name_loc: TokenLocation { line: 0, col: 10 },
+                            value_loc: TokenLocation { line: 0, col: 21 },
+                        }),
+                        Statement::SetStatement(SetStatement {
+                            name: "local".to_owned(),
+                            value: Token::StringValue { value: "on".into() },
+                            local: false,
+                            name_loc: TokenLocation { line: 0, col: 34 },
+                            value_loc: TokenLocation { line: 0, col: 42 },
+                        }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "SET search_path = \"$user\", public, 'My Schema';",
+                ast: Ast {
+                    statements: vec![Statement::SetStatement(SetStatement {
+                        name: "search_path".to_owned(),
+                        value: Token::StringValue {
+                            value: "\"$user\", public, \"My Schema\"".into(),
+                        },
+                        local: false,
+                        name_loc: TokenLocation { line: 0, col: 4 },
+                        value_loc: TokenLocation { line: 0, col: 18 },
+                    })],
+                },
+            },
+            ParseTest {
+                input: "RESET myapp.user_id; RESET ALL;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::ResetStatement(ResetStatement {
+                            name: Some("myapp.user_id".to_owned()),
+                            name_loc: TokenLocation { line: 0, col: 6 },
+                        }),
+                        Statement::ResetStatement(ResetStatement {
+                            name: None,
+                            name_loc: TokenLocation { line: 0, col: 27 },
+                        }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "SHOW max_result_rows;",
+                ast: Ast {
+                    statements: vec![Statement::ShowStatement(ShowStatement {
+                        name: "max_result_rows".to_owned(),
+                        name_loc: TokenLocation { line: 0, col: 5 },
+                    })],
+                },
+            },
+            ParseTest {
+                input: "DROP INDEX IF EXISTS t_pkey;",
+                ast: Ast {
+                    statements: vec![Statement::DropIndexStatement(DropIndexStatement {
+                        name: "t_pkey".to_owned(),
+                        if_exists: true,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "REINDEX TABLE t;",
+                ast: Ast {
+                    statements: vec![Statement::ReindexStatement(ReindexStatement {
+                        table: "t".to_owned(),
+                    })],
+                },
+            },
+            ParseTest {
+                input: "VACUUM; VACUUM public.t;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::VacuumStatement(VacuumStatement { table: None }),
+                        Statement::VacuumStatement(VacuumStatement {
+                            table: Some("t".to_owned()),
+                        }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "EXPLAIN ANALYZE SELECT id FROM users;",
+                ast: Ast {
+                    statements: vec![Statement::ExplainStatement(ExplainStatement {
+                        analyze: true,
+                        select: SelectStatement {
+                            items: vec![SelectItem {
+                                asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
+                                as_clause: None,
+                                expression: Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                }),
+                            }],
+                            from: vec![RowDataSource::Table {
+                                table_name: "users".to_string(),
+                                as_clause: None,
+                                sample: None,
+                                joins: vec![],
+                            }],
+                            where_clause: Expression::Empty,
+                            group_by: vec![],
+                            having: Expression::Empty,
+                            is_distinct: false,
+                            order_by: vec![],
+                            limit: None,
+                            offset: None,
+                        },
+                    })],
+                },
+            },
+            ParseTest {
+                input: "ALTER TABLE t DROP COLUMN name CASCADE;",
+                ast: Ast {
+                    statements: vec![Statement::AlterTableStatement(AlterTableStatement {
+                        name: "t".to_owned(),
+                        action: AlterTableAction::DropColumn {
+                            name: "name".to_owned(),
+                            if_exists: false,
+                            cascade: true,
+                        },
+                    })],
+                },
+            },
+            ParseTest {
+                input: "LISTEN jobs; NOTIFY jobs, 'ready'; NOTIFY jobs; UNLISTEN *;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::ListenStatement(ListenStatement {
+                            channel: "jobs".to_owned(),
+                        }),
+                        Statement::NotifyStatement(NotifyStatement {
+                            channel: "jobs".to_owned(),
+                            payload: Some("ready".to_owned()),
+                        }),
+                        Statement::NotifyStatement(NotifyStatement {
+                            channel: "jobs".to_owned(),
+                            payload: None,
+                        }),
+                        Statement::UnlistenStatement(UnlistenStatement { channel: None }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "FETCH 100 FROM cur; FETCH cur; FETCH FORWARD ALL IN cur; CLOSE cur; CLOSE ALL;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::FetchStatement(FetchStatement {
+                            name: "cur".to_owned(),
+                            count: Some(100),
+                        }),
+                        Statement::FetchStatement(FetchStatement {
+                            name: "cur".to_owned(),
+                            count: Some(1),
+                        }),
+                        Statement::FetchStatement(FetchStatement {
+                            name: "cur".to_owned(),
+                            count: None,
+                        }),
+                        Statement::CloseStatement(CloseStatement {
+                            name: Some("cur".to_owned()),
+                        }),
+                        Statement::CloseStatement(CloseStatement { name: None }),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "BEGIN; COMMIT WORK; begin transaction; ROLLBACK;",
+                ast: Ast {
+                    statements: vec![
+                        Statement::TransactionStatement(TransactionStatement::Begin),
+                        Statement::TransactionStatement(TransactionStatement::Commit),
+                        Statement::TransactionStatement(TransactionStatement::Begin),
+                        Statement::TransactionStatement(TransactionStatement::Rollback),
+                    ],
+                },
+            },
+            ParseTest {
+                input: "SELECT id FROM a WHERE NOT EXISTS (SELECT k FROM b WHERE k = id) AND id NOT IN (SELECT k FROM b);",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                            as_clause: None,
+                            expression: Expression::TableColumn(TableColumn {
+                                col_name: "id".to_owned(),
+                                table_name: None,
+                            }),
+                        }],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: None,
+                            sample: None,
+                            joins: vec![],
+                        }],
+                        where_clause: Expression::Binary(BinaryExpression {
+                            first: Box::new(Expression::Unary(UnaryExpression {
+                                first: Box::new(Expression::Exists(Box::new(SelectStatement {
+                                    items: vec![SelectItem {
+                                        asterisk: false,
+                                        asterisk_source: None,
+                                        asterisk_modifiers: None,
+                                        as_clause: None,
+                                        expression: Expression::TableColumn(TableColumn {
+                                            col_name: "k".to_owned(),
+                                            table_name: None,
+                                        }),
+                                    }],
+                                    from: vec![RowDataSource::Table {
+                                        table_name: "b".to_string(),
+                                        as_clause: None,
+                                        sample: None,
+                                        joins: vec![],
+                                    }],
+                                    where_clause: Expression::Binary(BinaryExpression {
+                                        first: Box::new(Expression::TableColumn(TableColumn {
+                                            col_name: "k".to_owned(),
+                                            table_name: None,
+                                        })),
+                                        second: Box::new(Expression::TableColumn(TableColumn {
+                                            col_name: "id".to_owned(),
+                                            table_name: None,
+                                        })),
+                                        operand: Token::Equal,
+                                    }),
+                                    group_by: vec![],
+                                    having: Expression::Empty,
+                                    is_distinct: false,
+                                    order_by: vec![],
+                                    limit: None,
+                                    offset: None,
+                                }))),
+                                operand: Token::Not,
+                            })),
+                            second: Box::new(Expression::Quantified(QuantifiedExpression {
+                                first: Box::new(Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                })),
+                                operand: Token::NotEqual,
+                                all: true,
+                                set: QuantifiedSet::SubSelect(Box::new(SelectStatement {
+                                    items: vec![SelectItem {
+                                        asterisk: false,
+                                        asterisk_source: None,
+                                        asterisk_modifiers: None,
+                                        as_clause: None,
+                                        expression: Expression::TableColumn(TableColumn {
+                                            col_name: "k".to_owned(),
+                                            table_name: None,
+                                        }),
+                                    }],
+                                    from: vec![RowDataSource::Table {
+                                        table_name: "b".to_string(),
+                                        as_clause: None,
+                                        sample: None,
+                                        joins: vec![],
+                                    }],
+                                    where_clause: Expression::Empty,
+                                    group_by: vec![],
+                                    having: Expression::Empty,
+                                    is_distinct: false,
+                                    order_by: vec![],
+                                    limit: None,
+                                    offset: None,
+                                })),
+                            })),
+                            operand: Token::And,
+                        }),
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT ~id FROM a WHERE id ~ 'x' AND id NOT SIMILAR TO 'y';",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                            as_clause: None,
+                            expression: Expression::Unary(UnaryExpression {
+                                first: Box::new(Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                })),
+                                operand: Token::BitwiseNot,
+                            }),
+                        }],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: None,
+                            sample: None,
+                            joins: vec![],
+                        }],
+                        where_clause: Expression::Binary(BinaryExpression {
+                            first: Box::new(Expression::Binary(BinaryExpression {
+                                first: Box::new(Expression::TableColumn(TableColumn {
+                                    col_name: "id".to_owned(),
+                                    table_name: None,
+                                })),
+                                second: Box::new(Expression::Literal(LiteralExpression {
+                                    literal: Token::StringValue { value: "x".into() },
+                                })),
+                                operand: Token::RegexMatch,
+                            })),
+                            second: Box::new(Expression::Unary(UnaryExpression {
+                                first: Box::new(Expression::Binary(BinaryExpression {
+                                    first: Box::new(Expression::TableColumn(TableColumn {
+                                        col_name: "id".to_owned(),
+                                        table_name: None,
+                                    })),
+                                    second: Box::new(Expression::Literal(LiteralExpression {
+                                        literal: Token::StringValue { value: "y".into() },
+                                    })),
+                                    operand: Token::Similar,
+                                })),
+                                operand: Token::Not,
+                            })),
+                            operand: Token::And,
+                        }),
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT id FROM a, LATERAL (SELECT k FROM b) s;",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                            as_clause: None,
+                            expression: Expression::TableColumn(TableColumn {
+                                col_name: "id".to_owned(),
+                                table_name: None,
+                            }),
+                        }],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: None,
+                            sample: None,
+                            joins: vec![JoinClause {
+                                kind: JoinKind::Inner,
+                                source: RowDataSource::SubSelect {
+                                    select: Box::new(SelectStatement {
+                                        items: vec![SelectItem {
+                                            asterisk: false,
+                                            asterisk_source: None,
+                                            asterisk_modifiers: None,
+                                            as_clause: None,
+                                            expression: Expression::TableColumn(TableColumn {
+                                                col_name: "k".to_owned(),
+                                                table_name: None,
+                                            }),
+                                        }],
+                                        from: vec![RowDataSource::Table {
+                                            table_name: "b".to_string(),
+                                            as_clause: None,
+                                            sample: None,
+                                            joins: vec![],
+                                        }],
+                                        where_clause: Expression::Empty,
+                                        group_by: vec![],
+                                        having: Expression::Empty,
+                                        is_distinct: false,
+                                        order_by: vec![],
+                                        limit: None,
+                                        offset: None,
+                                    }),
+                                    as_clause: "s".to_owned(),
+                                    lateral: true,
+                                    joins: vec![],
+                                },
+                                on: Expression::new_literal_bool(TRUE_KEYWORD.to_string()),
+                            }],
+                        }],
+                        where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT id FROM a t TABLESAMPLE SYSTEM (10) REPEATABLE (7);",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                            as_clause: None,
+                            expression: Expression::TableColumn(TableColumn {
+                                col_name: "id".to_owned(),
+                                table_name: None,
+                            }),
+                        }],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: Some("t".to_owned()),
+                            sample: Some(TableSample {
+                                method: "system".to_owned(),
+                                percentage: Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "10".into() },
+                                }),
+                                repeatable: Some(Expression::Literal(LiteralExpression {
+                                    literal: Token::NumericValue { value: "7".into() },
+                                })),
+                            }),
+                            joins: vec![],
+                        }],
+                        where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT 1 AS one WHERE 1 = 1;",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: false,
+                            asterisk_source: None,
+                            asterisk_modifiers: None,
+                            as_clause: Some("one".to_owned()),
+                            expression: Expression::Literal(LiteralExpression {
+                                literal: Token::NumericValue { value: "1".into() },
+                            }),
+                        }],
+                        from: vec![],
+                        where_clause: Expression::Binary(BinaryExpression {
+                            first: Box::new(Expression::Literal(LiteralExpression {
+                                literal: Token::NumericValue { value: "1".into() },
+                            })),
+                            second: Box::new(Expression::Literal(LiteralExpression {
+                                literal: Token::NumericValue { value: "1".into() },
+                            })),
+                            operand: Token::Equal,
+                        }),
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT * EXCEPT (a.id, name) REPLACE (upper(code) AS code) FROM a;",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![SelectItem {
+                            asterisk: true,
+                            asterisk_source: None,
+                            asterisk_modifiers: Some(AsteriskModifiers {
+                                except: vec![
+                                    TableColumn {
+                                        col_name: "id".to_owned(),
+                                        table_name: Some("a".to_owned()),
+                                    },
+                                    TableColumn {
+                                        col_name: "name".to_owned(),
+                                        table_name: None,
+                                    },
+                                ],
+                                replace: vec![SelectItem {
+                                    asterisk: false,
+                                    asterisk_source: None,
+                                    asterisk_modifiers: None,
+                                    as_clause: Some("code".to_owned()),
+                                    expression: Expression::FunctionCall(FunctionCall {
+                                        name: "upper".to_owned(),
+                                        args: vec![Expression::TableColumn(TableColumn {
+                                            col_name: "code".to_owned(),
+                                            table_name: None,
+                                        })],
+                                        asterisk: false,
+                                        distinct: false,
+                                        filter: None,
+                                        over: None,
+                                    }),
+                                }],
+                            }),
+                            as_clause: None,
+                            expression: Expression::new(),
+                        }],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: None,
+                            sample: None,
+                            joins: vec![],
+                        }],
+                        where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                    })],
+                },
+            },
+            ParseTest {
+                input: "SELECT a.* EXCEPT (id), b FROM a;",
+                ast: Ast {
+                    statements: vec![Statement::SelectStatement(SelectStatement {
+                        items: vec![
+                            SelectItem {
+                                asterisk: true,
+                                asterisk_source: Some("a".to_owned()),
+                                asterisk_modifiers: Some(AsteriskModifiers {
+                                    except: vec![TableColumn {
+                                        col_name: "id".to_owned(),
+                                        table_name: None,
+                                    }],
+                                    replace: vec![],
+                                }),
+                                as_clause: None,
+                                expression: Expression::new(),
+                            },
+                            SelectItem {
+                                asterisk: false,
+                                asterisk_source: None,
+                                asterisk_modifiers: None,
+                                as_clause: None,
+                                expression: Expression::TableColumn(TableColumn {
+                                    col_name: "b".to_owned(),
+                                    table_name: None,
+                                }),
+                            },
+                        ],
+                        from: vec![RowDataSource::Table {
+                            table_name: "a".to_string(),
+                            as_clause: None,
+                            sample: None,
+                            joins: vec![],
+                        }],
+                        where_clause: Expression::Empty,
+                        group_by: vec![],
+                        having: Expression::Empty,
+                        is_distinct: false,
+                        order_by: vec![],
                         limit: None,
                         offset: None,
                     })],
@@ -1904,4 +5963,102 @@ mod parser_tests {
             panic!(err_msg);
         }
     }
+
+    #[test]
+    fn test_parse_error_underlines_token() {
+        let tests = vec![
+            (
+                "CREATE TABLE users (id INT, name TEXT)\nSELECT id FROM users;",
+                "SELECT id FROM users;\n^^^^^^",
+            ),
+            (
+                "SELECT id FROM users WHERE name = 'x' INNER JOIN;",
+                "SELECT id FROM users WHERE name = 'x' INNER JOIN;\n                                      ^^^^^",
+            ),
+            (
+                "SELECT id FROM\n\tusers WHERE id = ;",
+                "\tusers WHERE id = ;\n\t                 ^",
+            ),
+        ];
+
+        for (input, underline) in tests {
+            let msg = parse(input).unwrap_err().to_string();
+            assert!(msg.ends_with(underline), "{}:\n{}", input, msg);
+        }
+    }
+
+    #[test]
+    fn test_parse_chained_comparisons() {
+        let input = "SELECT id FROM t WHERE 1 < id <= 3;";
+        assert_eq!(
+            parse(input).unwrap_err().to_string(),
+            format!(
+                "[1, 31]: <= can't follow <, operators of their precedence don't chain. Hint: \
+                join the comparisons with AND, as in a < b AND b < c, or add parentheses, got \
+                LessThanOrEqual\n{}\n{}^^",
+                input,
+                " ".repeat(30)
+            )
+        );
+        assert_eq!(
+            parse_condition("1 IN (SELECT 1) NOT IN (SELECT 2)")
+                .unwrap_err()
+                .to_string(),
+            "[1, 17]: IN can't follow IN, operators of their precedence don't chain. Hint: join \
+            the comparisons with AND, as in a < b AND b < c, or add parentheses, got Not"
+        );
+
+        // Unless they are of different precedence or in parentheses
+        for input in [
+            "1 < id AND id <= 3",
+            "(1 < id) = true",
+            "1 = 1 IN (SELECT 1)",
+        ] {
+            assert!(parse_condition(input).is_ok(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_operator_grouping() {
+        let binary = |exp: Expression| match exp {
+            Expression::Binary(binary) => binary,
+            exp => panic!("Expected a binary expression, got {:?}", exp),
+        };
+        // `a - b - c` is `(a - b) - c`
+        let sum = binary(parse_condition("a - b - c").unwrap());
+        assert_eq!(binary(*sum.first).operand, Token::Minus);
+        // `a + b * c ^ d` is `a + (b * (c ^ d))`
+        let sum = binary(parse_condition("a + b * c ^ d").unwrap());
+        assert_eq!(
+            binary(*binary(*sum.second).second).operand,
+            Token::Exponentiation
+        );
+        // Prefix operators take what binds tighter than they do
+        match parse_condition("NOT a = b AND c").unwrap() {
+            Expression::Binary(and) => match *and.first {
+                Expression::Unary(not) => assert!(matches!(*not.first, Expression::Binary(_))),
+                exp => panic!("Expected NOT, got {:?}", exp),
+            },
+            exp => panic!("Expected AND, got {:?}", exp),
+        }
+        let power = binary(parse_condition("-a ^ 2").unwrap());
+        assert!(matches!(*power.first, Expression::Unary(_)));
+    }
+
+    // Statements that end where a token is still expected, which the parser once read past
+    #[test]
+    fn test_parse_truncated_statements() {
+        let inputs = [
+            "CREATE TABLE a (i",
+            "CREATE TABLE a (i INT, j",
+            "VALUES (1 + 2,",
+            "INSERT INTO a VALUES (1,",
+            "SELECT 1 FROM a WHERE b IN (1,",
+        ];
+        for input in inputs {
+            assert!(parse(input).is_err(), "{}", input);
+            let mut backend = crate::backend_memory::MemoryBackend::new();
+            assert!(backend.eval_query(input).is_err(), "{}", input);
+        }
+    }
 }
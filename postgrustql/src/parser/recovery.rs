@@ -0,0 +1,486 @@
+use super::*;
+
+// Stands for the cursor in the statement `syntax_context` parses
+const CURSOR_NAME: &str = "\u{0}cursor";
+// Repairs tried on a statement before giving up on it
+const MAX_REPAIRS: usize = 64;
+// How far after an error something missing is looked for
+const MAX_LOOKAHEAD: usize = 8;
+
+// Where the cursor is in a statement, for completion to know what to offer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SyntaxContext {
+    // An item of SELECT or RETURNING
+    SelectItem,
+    // A table after FROM or JOIN, or the one INSERT, UPDATE or DELETE change
+    TableName,
+    // Within the condition of WHERE, expecting a column of the tables read
+    ColumnInWhere,
+    // Anywhere else, or nowhere a statement could be made of
+    Unknown,
+}
+
+// Parses what it can of `source`. At each error it records why, makes up what was
+// missing there as a placeholder, or skips to the next comma or keyword when nothing
+// would do, and carries on. Statements past saving are left out, as is everything after
+// a lexing error. Placeholders are named `PLACEHOLDER_NAME`.
+pub fn parse_recovering(source: &str) -> (Vec<Statement>, Vec<ParsingError>) {
+    let (tokens, lexing_error) = Lexer::new().lex_prefix(source);
+    let mut statements = vec![];
+    let mut errors = vec![];
+    let mut start = 0;
+    while start < tokens.len() {
+        let end = tokens[start..]
+            .iter()
+            .position(|token| token.token == Token::Semicolon)
+            .map_or(tokens.len(), |position| start + position);
+        if end > start {
+            statements.extend(recover_statement(source, &tokens, start..end, &mut errors));
+        }
+        start = end + 1;
+    }
+
+    let mut errors: Vec<ParsingError> = errors
+        .into_iter()
+        .map(|(msg, cursor)| ParsingError::General {
+            msg: format!(
+                "{}\n{}",
+                help_message(&tokens, cursor, msg),
+                underline_token(source, &tokens, cursor)
+            ),
+            cursor,
+        })
+        .collect();
+    errors.extend(lexing_error.map(ParsingError::from));
+    (statements, errors)
+}
+
+// Parses the statement of `tokens[range]`, repairing it as needed. The errors met are
+// added to `errors` along with the index in `tokens` of the token they are at.
+fn recover_statement(
+    source: &str,
+    tokens: &[TokenContainer],
+    range: std::ops::Range<usize>,
+    errors: &mut Vec<(String, usize)>,
+) -> Option<Statement> {
+    let end = range.end;
+    let mut repaired = tokens[range.clone()].to_vec();
+    // The index in `tokens` of each repaired token, or of the one it was put before
+    let mut origins: Vec<usize> = range.collect();
+    let mut reported = vec![];
+    for _ in 0..MAX_REPAIRS {
+        let (msg, cursor) = match parse_whole(&mut repaired) {
+            Ok(mut statement) => {
+                if let Statement::CreateViewStatement(create_view) = &mut statement {
+                    create_view.definition = query_text(source, &repaired);
+                }
+                return Some(statement);
+            }
+            Err(err) => err,
+        };
+        // Errors are often reported at the start of the clause they are in, what was
+        // missing can be up to a few tokens after
+        let last = repaired.len().min(cursor + MAX_LOOKAHEAD);
+        let pieces = missing_pieces();
+        let inserted = (cursor..=last).find_map(|position| {
+            pieces.iter().find_map(|piece| {
+                let mut attempt = repaired.clone();
+                let made_up = piece
+                    .iter()
+                    .map(|token| made_up_token(&repaired, position, token.clone()));
+                attempt.splice(position..position, made_up);
+                match parse_whole(&mut attempt) {
+                    Err((_, next)) if next < position + piece.len() => None,
+                    _ => Some((position, piece.len(), attempt)),
+                }
+            })
+        });
+        match inserted {
+            Some((position, len, attempt)) => {
+                let origin = origins.get(position).copied().unwrap_or(end);
+                report(errors, &mut reported, msg, origin);
+                repaired = attempt;
+                origins.splice(position..position, vec![origin; len]);
+            }
+            None if cursor < repaired.len() => {
+                report(errors, &mut reported, msg, origins[cursor]);
+                let sync = repaired[cursor + 1..]
+                    .iter()
+                    .position(|token| token.token == Token::Comma || token.token.is_keyword())
+                    .map_or(repaired.len(), |position| cursor + 1 + position);
+                repaired.drain(cursor..sync);
+                origins.drain(cursor..sync);
+                if repaired.is_empty() {
+                    return None;
+                }
+            }
+            None => {
+                report(errors, &mut reported, msg, end);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+// Adds the error at the token `origin` unless one already was, for what is missing
+// there may take several repairs
+fn report(
+    errors: &mut Vec<(String, usize)>,
+    reported: &mut Vec<usize>,
+    msg: String,
+    origin: usize,
+) {
+    if !reported.contains(&origin) {
+        reported.push(origin);
+        errors.push((msg, origin));
+    }
+}
+
+// The statement of all of `tokens`, or why and at which token it couldn't be parsed
+fn parse_whole(tokens: &mut Vec<TokenContainer>) -> Result<Statement, (String, usize)> {
+    match parse_statement(tokens, 0, Token::Semicolon) {
+        Ok((statement, cursor)) if cursor >= tokens.len() => Ok(statement),
+        Ok((_, cursor)) => Err(("Expected end of statement".to_owned(), cursor)),
+        Err(err) => {
+            let cursor = match err {
+                ParsingError::General { cursor, .. } | ParsingError::Delimiter { cursor, .. } => {
+                    cursor
+                }
+                ParsingError::Lexing { .. } => 0,
+            };
+            Err((err.to_string(), cursor))
+        }
+    }
+}
+
+// What the recovering parser makes up where something is missing, the first that lets it
+// get past it is taken: a name or expression, a closing parenthesis, or all or the end of
+// the condition of a join, without which it can't be parsed
+fn missing_pieces() -> Vec<Vec<Token>> {
+    let placeholder = Token::IdentifierValue {
+        value: Atom::from(PLACEHOLDER_NAME),
+    };
+    vec![
+        vec![
+            Token::On,
+            placeholder.clone(),
+            Token::Equal,
+            placeholder.clone(),
+        ],
+        vec![Token::Equal, placeholder.clone()],
+        vec![placeholder],
+        vec![Token::RightParenthesis],
+    ]
+}
+
+// `token` as if it was before the one at `cursor`, taking no room in the source
+fn made_up_token(tokens: &[TokenContainer], cursor: usize, token: Token) -> TokenContainer {
+    let at = match tokens.get(cursor) {
+        Some(token) => Cursor {
+            pointer: token.span.start,
+            loc: token.loc,
+        },
+        None => Cursor {
+            pointer: tokens.last().map_or(0, |token| token.span.end),
+            loc: tokens
+                .last()
+                .map_or_else(Default::default, |token| token.span.end_loc),
+        },
+    };
+    TokenContainer::new(token, at, at)
+}
+
+// What is expected where the byte `offset` of `source` is, going by the statement it is
+// in up to there. The name it is in the middle of, if any, isn't taken into account.
+pub fn syntax_context(source: &str, offset: usize) -> SyntaxContext {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let source = &source[..offset];
+    // Within an unterminated string or comment, or after anything that can't be lexed
+    let (mut tokens, lexing_error) = Lexer::new().lex_prefix(source);
+    if lexing_error.is_some() {
+        return SyntaxContext::Unknown;
+    }
+    let typing_name = match tokens.last() {
+        Some(token) if token.span.end == offset => match &token.token {
+            Token::IdentifierValue { .. } => true,
+            token => token.is_non_reserved_keyword(),
+        },
+        _ => false,
+    };
+    if typing_name {
+        tokens.pop();
+    }
+    let start = tokens
+        .iter()
+        .rposition(|token| token.token == Token::Semicolon)
+        .map_or(0, |position| position + 1);
+    let cursor = Token::IdentifierValue {
+        value: Atom::from(CURSOR_NAME),
+    };
+    let cursor = made_up_token(&tokens, tokens.len(), cursor);
+    tokens.push(cursor);
+
+    let range = start..tokens.len();
+    match recover_statement(source, &tokens, range, &mut vec![]) {
+        Some(statement) => statement_context(&statement).unwrap_or(SyntaxContext::Unknown),
+        None => SyntaxContext::Unknown,
+    }
+}
+
+fn is_cursor(name: &str) -> bool {
+    name == CURSOR_NAME
+}
+
+fn statement_context(statement: &Statement) -> Option<SyntaxContext> {
+    let unknown = SyntaxContext::Unknown;
+    match statement {
+        Statement::SelectStatement(select) => select_context(select),
+        Statement::CreateTableAsStatement(create_table_as) => {
+            select_context(&create_table_as.query)
+        }
+        Statement::CreateViewStatement(create_view) => select_context(&create_view.query),
+        Statement::ExplainStatement(explain) => select_context(&explain.select),
+        Statement::DeclareCursorStatement(declare) => select_context(&declare.query),
+        Statement::InsertStatement(insert) if is_cursor(&insert.table) => {
+            Some(SyntaxContext::TableName)
+        }
+        Statement::InsertStatement(insert) => insert
+            .rows
+            .iter()
+            .flatten()
+            .find_map(|exp| expression_context(exp, unknown))
+            .or_else(|| insert.query.as_ref().and_then(select_context))
+            .or_else(|| items_context(&insert.returning)),
+        Statement::UpdateStatement(update) if is_cursor(&update.table) => {
+            Some(SyntaxContext::TableName)
+        }
+        Statement::UpdateStatement(update) => update
+            .assignments
+            .iter()
+            .find_map(|assignment| expression_context(&assignment.value, unknown))
+            .or_else(|| expression_context(&update.where_clause, SyntaxContext::ColumnInWhere))
+            .or_else(|| items_context(&update.returning)),
+        Statement::DeleteStatement(delete) if is_cursor(&delete.table) => {
+            Some(SyntaxContext::TableName)
+        }
+        Statement::DeleteStatement(delete) => {
+            expression_context(&delete.where_clause, SyntaxContext::ColumnInWhere)
+                .or_else(|| items_context(&delete.returning))
+        }
+        Statement::WithStatement(with) => with
+            .queries
+            .iter()
+            .find_map(|query| statement_context(&query.statement))
+            .or_else(|| statement_context(&with.statement)),
+        _ => None,
+    }
+}
+
+fn select_context(select: &SelectStatement) -> Option<SyntaxContext> {
+    let unknown = SyntaxContext::Unknown;
+    items_context(&select.items)
+        .or_else(|| select.from.iter().find_map(source_context))
+        .or_else(|| expression_context(&select.where_clause, SyntaxContext::ColumnInWhere))
+        .or_else(|| {
+            select
+                .group_by
+                .iter()
+                .chain([&select.having])
+                .chain(select.order_by.iter().map(|order_by| &order_by.exp))
+                .find_map(|exp| expression_context(exp, unknown))
+        })
+}
+
+fn items_context(items: &[SelectItem]) -> Option<SyntaxContext> {
+    items
+        .iter()
+        .find_map(|item| expression_context(&item.expression, SyntaxContext::SelectItem))
+}
+
+fn source_context(source: &RowDataSource) -> Option<SyntaxContext> {
+    let unknown = SyntaxContext::Unknown;
+    let (found, joins) = match source {
+        RowDataSource::SubSelect { select, joins, .. } => (select_context(select), joins),
+        // The name may be qualified by its schema
+        RowDataSource::Table {
+            table_name, joins, ..
+        } => (
+            Some(SyntaxContext::TableName)
+                .filter(|_| table_name.rsplit('.').next().is_some_and(is_cursor)),
+            joins,
+        ),
+        RowDataSource::Values { rows, joins, .. } => (
+            rows.iter()
+                .flatten()
+                .find_map(|exp| expression_context(exp, unknown)),
+            joins,
+        ),
+        RowDataSource::Function { call, joins, .. } => (
+            call.args
+                .iter()
+                .find_map(|arg| expression_context(arg, unknown)),
+            joins,
+        ),
+    };
+    found.or_else(|| {
+        joins.iter().find_map(|join| {
+            source_context(&join.source).or_else(|| expression_context(&join.on, unknown))
+        })
+    })
+}
+
+// The context of the cursor if it is in `expression`, that of the expression itself being
+// `here`, while subqueries have their own
+fn expression_context(expression: &Expression, here: SyntaxContext) -> Option<SyntaxContext> {
+    match expression {
+        Expression::Literal(LiteralExpression {
+            literal: Token::IdentifierValue { value },
+        }) if is_cursor(value) => Some(here),
+        Expression::TableColumn(column) if is_cursor(&column.col_name) => Some(here),
+        Expression::Binary(binary) => expression_context(&binary.first, here)
+            .or_else(|| expression_context(&binary.second, here)),
+        Expression::Unary(unary) => expression_context(&unary.first, here),
+        Expression::SubSelect(select) | Expression::Exists(select) => select_context(select),
+        Expression::Quantified(quantified) => {
+            expression_context(&quantified.first, here).or_else(|| match &quantified.set {
+                QuantifiedSet::SubSelect(select) => select_context(select),
+                QuantifiedSet::Values(_) => None,
+            })
+        }
+        Expression::Cast { data, .. } | Expression::Collate { data, .. } => {
+            expression_context(data, here)
+        }
+        Expression::FunctionCall(call) => call
+            .args
+            .iter()
+            .chain(call.filter.as_deref())
+            .find_map(|arg| expression_context(arg, here)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use super::*;
+
+    fn select(statement: &Statement) -> &SelectStatement {
+        match statement {
+            Statement::SelectStatement(select) => select,
+            _ => panic!("Expected a select statement, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let source = "SELECT id, FROM users WHERE;\nSELECT name FROM people;\nDELETE FROM;";
+        let (statements, errors) = parse_recovering(source);
+        assert_eq!(statements.len(), 3);
+        let first = select(&statements[0]);
+        assert_eq!(first.items.len(), 2);
+        assert!(first.items[1].expression.is_placeholder());
+        assert!(first.where_clause.is_placeholder());
+        assert_eq!(
+            first.from,
+            vec![RowDataSource::Table {
+                table_name: "users".to_string(),
+                as_clause: None,
+                sample: None,
+                joins: vec![],
+            }]
+        );
+        // The statement in between is parsed as it would be on its own
+        assert_eq!(
+            statements[1],
+            parse("SELECT name FROM people;").unwrap().statements[0]
+        );
+        match &statements[2] {
+            Statement::DeleteStatement(delete) => assert_eq!(delete.table, PLACEHOLDER_NAME),
+            statement => panic!("Expected a delete statement, got {:?}", statement),
+        }
+
+        // One error where each placeholder went, pointing at the token it went before
+        let cursors: Vec<usize> = errors
+            .iter()
+            .map(|err| match err {
+                ParsingError::General { cursor, .. } => *cursor,
+                err => panic!("Expected a general error, got {:?}", err),
+            })
+            .collect();
+        assert_eq!(cursors, [3, 6, 14]);
+        assert!(errors[0].to_string().starts_with("[1, 12]: "));
+        assert!(errors[0]
+            .to_string()
+            .ends_with("SELECT id, FROM users WHERE;\n           ^^^^"));
+    }
+
+    #[test]
+    fn test_parse_recovering_skips_what_it_cant_use() {
+        // `1 2` is skipped up to the comma after the error at `1`
+        let (statements, errors) = parse_recovering("SELECT a 1 2, b FROM t ORDER BY (a;");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].to_string().starts_with("[1, 10]: "));
+        let select = select(&statements[0]);
+        assert_eq!(select.items.len(), 2);
+        assert_eq!(select.order_by.len(), 1);
+
+        // Nothing to recover
+        let source = "SELECT 1; SELECT 2;";
+        let (statements, errors) = parse_recovering(source);
+        assert_eq!(statements, parse(source).unwrap().statements);
+        assert!(errors.is_empty());
+
+        // Lexing stops at its first error, what comes before is still parsed
+        let (statements, errors) = parse_recovering("SELECT 1; SELECT 'oops");
+        assert_eq!(statements.len(), 2);
+        assert!(matches!(errors.last(), Some(ParsingError::Lexing { .. })));
+    }
+
+    #[test]
+    fn test_syntax_context() {
+        let cases = [
+            ("SELECT ", SyntaxContext::SelectItem),
+            ("SELECT id, na", SyntaxContext::SelectItem),
+            ("SELECT id FROM ", SyntaxContext::TableName),
+            ("SELECT id FROM public.us", SyntaxContext::TableName),
+            ("SELECT * FROM a JOIN ", SyntaxContext::TableName),
+            ("SELECT * FROM users WHERE ", SyntaxContext::ColumnInWhere),
+            (
+                "SELECT * FROM users WHERE id = 1 AND na",
+                SyntaxContext::ColumnInWhere,
+            ),
+            (
+                "SELECT * FROM users WHERE upper(",
+                SyntaxContext::ColumnInWhere,
+            ),
+            (
+                "SELECT * FROM t WHERE id IN (SELECT ",
+                SyntaxContext::SelectItem,
+            ),
+            ("SELECT 1; DELETE FROM ", SyntaxContext::TableName),
+            ("UPDATE t SET a = 1 WHERE ", SyntaxContext::ColumnInWhere),
+            ("SELECT * FROM t ORDER BY ", SyntaxContext::Unknown),
+            ("SELECT 'text", SyntaxContext::Unknown),
+            ("", SyntaxContext::Unknown),
+        ];
+        for (source, context) in cases {
+            assert_eq!(
+                syntax_context(source, source.len()),
+                context,
+                "{:?}",
+                source
+            );
+        }
+
+        // Only what is before the offset counts
+        let source = "SELECT id, FROM users WHERE name = 'Baam'";
+        assert_eq!(syntax_context(source, 11), SyntaxContext::SelectItem);
+        assert_eq!(syntax_context(source, 18), SyntaxContext::TableName);
+        assert_eq!(syntax_context(source, 30), SyntaxContext::ColumnInWhere);
+        // Past a whole condition it could be AND as well as the end of the statement
+        assert_eq!(syntax_context(source, 1000), SyntaxContext::Unknown);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::lexer::Lexer;
+use crate::sql_types::{SqlNumeric, SqlText, SqlValue};
+
+// The name as it has to be written to be read back as it is, in double quotes with any
+// double quote in it doubled, unless it is lowercase and not a reserved keyword
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    let keyword = match Lexer::new().lex(name) {
+        Ok(tokens) => tokens
+            .first()
+            .is_some_and(|t| t.token.is_keyword() && !t.token.is_non_reserved_keyword()),
+        Err(_) => false,
+    };
+    if plain && !keyword {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+// The value as a literal the parser reads back as the same value of the same type.
+// Numbers other than INT and DOUBLE PRECISION are cast to their type, and so are
+// infinities and NaN, which have no literal of their own. CHAR and VARCHAR values come
+// back as long as their text, a type as the text of its name as there is no literal for
+// types. Negative numbers are cast in parentheses, `::` binding tighter than `-`.
+pub fn quote_literal(value: &SqlValue) -> String {
+    match value {
+        SqlValue::Null => "NULL".to_string(),
+        SqlValue::Boolean(true) => "TRUE".to_string(),
+        SqlValue::Boolean(false) => "FALSE".to_string(),
+        SqlValue::Numeric(SqlNumeric::SmallInt { value }) => cast(value.to_string(), "SMALLINT"),
+        SqlValue::Numeric(SqlNumeric::Int { value }) => value.to_string(),
+        SqlValue::Numeric(SqlNumeric::BigInt { value }) => cast(value.to_string(), "BIGINT"),
+        // The shortest digits of a REAL read as a DOUBLE PRECISION round to the same REAL
+        SqlValue::Numeric(SqlNumeric::Real { value }) if value.is_finite() => {
+            cast(format!("{:?}", value), "REAL")
+        }
+        SqlValue::Numeric(SqlNumeric::Real { value }) => {
+            format!("{}::REAL", quote_non_finite(f64::from(*value)))
+        }
+        SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) if value.is_finite() => {
+            format!("{:?}", value)
+        }
+        SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => {
+            format!("{}::DOUBLE PRECISION", quote_non_finite(*value))
+        }
+        SqlValue::Text(SqlText::Text { value }) => quote_text(value),
+        SqlValue::Text(SqlText::Char { value, .. }) => format!("{}::CHAR", quote_text(value)),
+        SqlValue::Text(SqlText::VarChar { value, .. }) => {
+            format!("{}::VARCHAR", quote_text(value))
+        }
+        SqlValue::Type(typ) => quote_text(&typ.to_string()),
+    }
+}
+
+fn cast(number: String, typ: &str) -> String {
+    match number.starts_with('-') {
+        true => format!("({})::{}", number, typ),
+        false => format!("{}::{}", number, typ),
+    }
+}
+
+// Text in single quotes, with any single quote in it doubled
+pub(crate) fn quote_text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+// An infinity or NaN as the text casting reads it from
+fn quote_non_finite(value: f64) -> String {
+    match value {
+        _ if value.is_nan() => quote_text("NaN"),
+        _ if value > 0.0 => quote_text("Infinity"),
+        _ => quote_text("-Infinity"),
+    }
+}
+
+#[cfg(test)]
+mod quote_tests {
+    use super::*;
+    use crate::backend::EvalResult;
+    use crate::backend_memory::MemoryBackend;
+    use proptest::prelude::*;
+
+    fn read_back(mb: &mut MemoryBackend, literal: &str) -> SqlValue {
+        match mb
+            .eval_query(&format!("SELECT {};", literal))
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { mut results, .. }) => results.rows.remove(0).remove(0),
+            _ => panic!("Expected select results for {}", literal),
+        }
+    }
+
+    fn any_text() -> impl Strategy<Value = String> {
+        prop::collection::vec(
+            prop_oneof![Just('\''), Just('"'), Just('\\'), Just('\n'), any::<char>()],
+            0..32,
+        )
+        .prop_map(|chars| chars.into_iter().filter(|c| *c != '\0').collect())
+    }
+
+    fn value() -> impl Strategy<Value = SqlValue> {
+        prop_oneof![
+            Just(SqlValue::Null),
+            any::<bool>().prop_map(SqlValue::Boolean),
+            prop_oneof![Just(i16::MIN), Just(i16::MAX), any::<i16>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+            prop_oneof![Just(i32::MIN), Just(i32::MAX), any::<i32>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::Int { value })),
+            prop_oneof![Just(i64::MIN), Just(i64::MAX), any::<i64>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::BigInt { value })),
+            any::<f32>().prop_map(|value| SqlValue::Numeric(SqlNumeric::Real { value })),
+            any::<f64>().prop_map(|value| SqlValue::Numeric(SqlNumeric::DoublePrecision { value })),
+            any_text().prop_map(|value| SqlValue::Text(SqlText::Text { value })),
+        ]
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        let tests = vec![
+            ("people", "people"),
+            ("_id2$", "_id2$"),
+            ("People", "\"People\""),
+            ("first name", "\"first name\""),
+            ("2nd", "\"2nd\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("select", "\"select\""),
+            ("from", "\"from\""),
+            // Non-reserved keywords can be names as they are
+            ("key", "key"),
+            ("", "\"\""),
+        ];
+        for (name, quoted) in tests {
+            assert_eq!(quote_identifier(name), quoted, "{}", name);
+        }
+
+        let mut mb = MemoryBackend::new();
+        let names = ["Mixed Case", "select", "a\"b", "table"];
+        let columns: Vec<String> = names.iter().map(|name| quote_identifier(name)).collect();
+        let table = quote_identifier("Odd \"table\"");
+        mb.eval_query(&format!(
+            "CREATE TABLE {} ({} INT); SELECT {} FROM {} AS {};",
+            table,
+            columns.join(" INT, "),
+            columns.join(", "),
+            table,
+            quote_identifier("from")
+        ))
+        .unwrap();
+        match mb
+            .eval_query(&format!("SELECT * FROM {};", table))
+            .unwrap()
+            .pop()
+        {
+            Some(EvalResult::Select { results, .. }) => {
+                let read: Vec<_> = results.columns.iter().map(|c| c.name.as_str()).collect();
+                assert_eq!(read, names);
+            }
+            _ => panic!("Expected select results"),
+        }
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        let tests = vec![
+            (SqlValue::Null, "NULL"),
+            (SqlValue::Boolean(true), "TRUE"),
+            (SqlValue::Numeric(SqlNumeric::Int { value: -5 }), "-5"),
+            (
+                SqlValue::Numeric(SqlNumeric::SmallInt { value: -5 }),
+                "(-5)::SMALLINT",
+            ),
+            (
+                SqlValue::Numeric(SqlNumeric::BigInt { value: 5 }),
+                "5::BIGINT",
+            ),
+            (
+                SqlValue::Numeric(SqlNumeric::DoublePrecision { value: 2.0 }),
+                "2.0",
+            ),
+            (
+                SqlValue::Numeric(SqlNumeric::Real { value: 0.1 }),
+                "0.1::REAL",
+            ),
+            (
+                SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                    value: f64::NEG_INFINITY,
+                }),
+                "'-Infinity'::DOUBLE PRECISION",
+            ),
+            (
+                SqlValue::Text(SqlText::Text {
+                    value: "it's".to_string(),
+                }),
+                "'it''s'",
+            ),
+            (
+                SqlValue::Text(SqlText::VarChar {
+                    value: "ab".to_string(),
+                    maxlen: 5,
+                    len: 2,
+                }),
+                "'ab'::VARCHAR",
+            ),
+        ];
+        for (value, quoted) in tests {
+            assert_eq!(quote_literal(&value), quoted);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_quote_literal_reads_back(value in value()) {
+            let mut mb = MemoryBackend::new();
+            let read = read_back(&mut mb, &quote_literal(&value));
+            match (&read, &value) {
+                // Any NaN reads back as the one NaN casting gives
+                (
+                    SqlValue::Numeric(SqlNumeric::Real { value: read }),
+                    SqlValue::Numeric(SqlNumeric::Real { value }),
+                ) if value.is_nan() => prop_assert!(read.is_nan()),
+                (
+                    SqlValue::Numeric(SqlNumeric::DoublePrecision { value: read }),
+                    SqlValue::Numeric(SqlNumeric::DoublePrecision { value }),
+                ) if value.is_nan() => prop_assert!(read.is_nan()),
+                _ => prop_assert_eq!(read, value),
+            }
+        }
+    }
+}
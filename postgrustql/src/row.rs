@@ -0,0 +1,316 @@
+use crate::backend::{QueryResults, ResultColumn};
+use crate::sql_types::{SqlNumeric, SqlText, SqlType, SqlValue};
+
+#[cfg(feature = "derive")]
+pub use postgrustql_derive::FromRow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+    MissingColumn {
+        column: String,
+    },
+    UnexpectedNull {
+        column: String,
+    },
+    TypeMismatch {
+        column: String,
+        expected: &'static str,
+        found: SqlType,
+    },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::MissingColumn { column } => {
+                write!(f, "Column \"{}\" is not present in the results.", column)
+            }
+            MapError::UnexpectedNull { column } => write!(
+                f,
+                "Column \"{}\" is NULL but the target field is not an Option.",
+                column
+            ),
+            MapError::TypeMismatch {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Column \"{}\" of type {} cannot be read as {}.",
+                column, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// A single row of a result set, with access to its values by column name or position.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    columns: &'a [ResultColumn],
+    values: &'a [SqlValue],
+}
+
+impl<'a> Row<'a> {
+    pub fn new(columns: &'a [ResultColumn], values: &'a [SqlValue]) -> Self {
+        Row { columns, values }
+    }
+
+    pub fn columns(&self) -> &'a [ResultColumn] {
+        self.columns
+    }
+
+    pub fn values(&self) -> &'a [SqlValue] {
+        self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Reads the value of the first column called `name`.
+    pub fn get<T: FromSql>(&self, name: &str) -> Result<T, MapError> {
+        match self.columns.iter().position(|col| col.name == name) {
+            Some(idx) => self.get_idx(idx),
+            None => Err(MapError::MissingColumn {
+                column: name.to_string(),
+            }),
+        }
+    }
+
+    pub fn get_idx<T: FromSql>(&self, idx: usize) -> Result<T, MapError> {
+        match (self.columns.get(idx), self.values.get(idx)) {
+            (Some(col), Some(value)) => T::from_sql(value, col),
+            _ => Err(MapError::MissingColumn {
+                column: format!("#{}", idx),
+            }),
+        }
+    }
+}
+
+impl QueryResults<SqlValue> {
+    pub fn iter_rows(&self) -> impl Iterator<Item = Row<'_>> {
+        let columns = &self.columns[..];
+        self.rows.iter().map(move |row| Row::new(columns, row))
+    }
+
+    /// Maps every row to `T`, stopping at the first row that fails to map.
+    pub fn map_rows<T: FromRow>(&self) -> Result<Vec<T>, MapError> {
+        self.iter_rows().map(|row| T::from_row(&row)).collect()
+    }
+}
+
+/// Conversion of a whole row into a user type, usually implemented with `#[derive(FromRow)]`.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, MapError>;
+}
+
+/// Conversion of a single value into a Rust type.
+pub trait FromSql: Sized {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError>;
+}
+
+fn mismatch<T>(column: &ResultColumn, expected: &'static str) -> Result<T, MapError> {
+    Err(MapError::TypeMismatch {
+        column: column.name.clone(),
+        expected,
+        found: column.col_type,
+    })
+}
+
+fn check_null(value: &SqlValue, column: &ResultColumn) -> Result<(), MapError> {
+    if value.is_null() {
+        return Err(MapError::UnexpectedNull {
+            column: column.name.clone(),
+        });
+    }
+    Ok(())
+}
+
+impl FromSql for SqlValue {
+    fn from_sql(value: &SqlValue, _: &ResultColumn) -> Result<Self, MapError> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromSql> FromSql for Option<T> {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        match value {
+            SqlValue::Null => Ok(None),
+            _ => T::from_sql(value, column).map(Some),
+        }
+    }
+}
+
+impl FromSql for bool {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Boolean(val) => Ok(*val),
+            _ => mismatch(column, "bool"),
+        }
+    }
+}
+
+impl FromSql for String {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Text(SqlText::Text { value })
+            | SqlValue::Text(SqlText::Char { value, .. })
+            | SqlValue::Text(SqlText::VarChar { value, .. }) => Ok(value.clone()),
+            _ => mismatch(column, "String"),
+        }
+    }
+}
+
+impl FromSql for i16 {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => Ok(*value),
+            _ => mismatch(column, "i16"),
+        }
+    }
+}
+
+impl FromSql for i32 {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => Ok(*value as i32),
+            SqlValue::Numeric(SqlNumeric::Int { value }) => Ok(*value),
+            _ => mismatch(column, "i32"),
+        }
+    }
+}
+
+impl FromSql for i64 {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => Ok(*value as i64),
+            SqlValue::Numeric(SqlNumeric::Int { value }) => Ok(*value as i64),
+            SqlValue::Numeric(SqlNumeric::BigInt { value }) => Ok(*value),
+            _ => mismatch(column, "i64"),
+        }
+    }
+}
+
+impl FromSql for f32 {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Numeric(SqlNumeric::Real { value }) => Ok(*value),
+            _ => mismatch(column, "f32"),
+        }
+    }
+}
+
+impl FromSql for f64 {
+    fn from_sql(value: &SqlValue, column: &ResultColumn) -> Result<Self, MapError> {
+        check_null(value, column)?;
+        match value {
+            SqlValue::Numeric(SqlNumeric::Real { value }) => Ok(*value as f64),
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => Ok(*value),
+            _ => mismatch(column, "f64"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod row_tests {
+    use super::*;
+    use crate::backend_memory::MemoryBackend;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+        active: Option<bool>,
+    }
+
+    // What #[derive(FromRow)] expands to
+    impl FromRow for User {
+        fn from_row(row: &Row) -> Result<Self, MapError> {
+            Ok(User {
+                id: row.get("id")?,
+                name: row.get("username")?,
+                active: row.get("active")?,
+            })
+        }
+    }
+
+    struct MapTest {
+        query: &'static str,
+        expected: Result<Vec<User>, MapError>,
+    }
+
+    #[test]
+    fn test_query_as() {
+        let mut mb = MemoryBackend::new();
+        mb.eval_query(
+            "CREATE TABLE users (id INT, username TEXT, active BOOLEAN, score REAL);
+            INSERT INTO users VALUES (1, 'Baam', true, 1.5);
+            INSERT INTO users VALUES (2, 'Rachel', NULL, NULL);",
+        )
+        .unwrap();
+
+        let tests = vec![
+            MapTest {
+                query: "SELECT id, username, active FROM users;",
+                expected: Ok(vec![
+                    User {
+                        id: 1,
+                        name: "Baam".to_string(),
+                        active: Some(true),
+                    },
+                    User {
+                        id: 2,
+                        name: "Rachel".to_string(),
+                        active: None,
+                    },
+                ]),
+            },
+            MapTest {
+                query: "SELECT id, active FROM users;",
+                expected: Err(MapError::MissingColumn {
+                    column: "username".to_string(),
+                }),
+            },
+            MapTest {
+                query: "SELECT score AS id, username, active FROM users;",
+                expected: Err(MapError::TypeMismatch {
+                    column: "id".to_string(),
+                    expected: "i64",
+                    found: SqlType::Real,
+                }),
+            },
+            MapTest {
+                query: "SELECT id, active AS username, active FROM users WHERE id = 2;",
+                expected: Err(MapError::UnexpectedNull {
+                    column: "username".to_string(),
+                }),
+            },
+        ];
+
+        for test in tests {
+            let result = match mb.eval_query(test.query).unwrap().pop() {
+                Some(crate::backend::EvalResult::Select { results, .. }) => results,
+                _ => panic!("Expected select results for {}", test.query),
+            };
+            assert_eq!(result.map_rows::<User>(), test.expected, "{}", test.query);
+        }
+
+        let users = mb
+            .query_as::<User>("SELECT id, username, active FROM users WHERE id = 1;")
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "Baam");
+    }
+}
@@ -1,9 +1,7 @@
-use byteorder::{BigEndian, ReadBytesExt};
 use std::convert::{TryFrom, TryInto};
-use std::io::Read;
 
 use crate::{
-    backend::{Cell, MemoryCell, MemoryCellData, ERR_INVALID_DATA_TYPE},
+    backend::{MemoryCell, ERR_INVALID_DATA_TYPE},
     lexer::Token,
 };
 use serde::{Deserialize, Serialize, Serializer};
@@ -23,6 +21,20 @@ pub enum SqlType {
     Type,
 }
 
+pub const ALL_TYPES: [SqlType; 11] = [
+    SqlType::SmallInt,
+    SqlType::Int,
+    SqlType::BigInt,
+    SqlType::Real,
+    SqlType::DoublePrecision,
+    SqlType::Text,
+    SqlType::Char,
+    SqlType::VarChar,
+    SqlType::Boolean,
+    SqlType::Null,
+    SqlType::Type,
+];
+
 impl SqlType {
     #[inline]
     pub fn from_token(token: Token) -> Result<Self, SqlTypeError> {
@@ -58,6 +70,24 @@ impl SqlType {
             SqlType::Type => -2000,
         }
     }
+
+    // The type's OID in Postgres' pg_type, which clients go by to decode values
+    #[inline]
+    pub fn oid(&self) -> u32 {
+        match self {
+            SqlType::SmallInt => 21,
+            SqlType::Int => 23,
+            SqlType::BigInt => 20,
+            SqlType::Real => 700,
+            SqlType::DoublePrecision => 701,
+            SqlType::Text => 25,
+            SqlType::Char => 1042,
+            SqlType::VarChar => 1043,
+            SqlType::Boolean => 16,
+            SqlType::Null => 705,
+            SqlType::Type => 2206,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -67,7 +97,12 @@ pub enum SqlTypeError {
     DecodeError(String),
     TypeMismatchError(String),
     OverflowError(String),
+    DivisionByZero,
     OperationError(String),
+    // Bytes given as text that aren't UTF-8
+    CharacterNotInRepertoire(String),
+    // Text with a NUL character, which text values can't hold
+    UntranslatableCharacter(String),
     Infallible,
 }
 
@@ -79,12 +114,28 @@ impl ToString for SqlTypeError {
             SqlTypeError::DecodeError(msg) => msg.clone(),
             SqlTypeError::TypeMismatchError(msg) => msg.clone(),
             SqlTypeError::OverflowError(msg) => msg.clone(),
+            SqlTypeError::DivisionByZero => "Division by zero".to_string(),
             SqlTypeError::OperationError(msg) => msg.clone(),
+            SqlTypeError::CharacterNotInRepertoire(msg) => msg.clone(),
+            SqlTypeError::UntranslatableCharacter(msg) => msg.clone(),
             SqlTypeError::Infallible => "wut".to_string(),
         }
     }
 }
 
+// A MemoryCell whose bytes are not a valid encoding of a value of the type it was read as
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub typ: SqlType,
+    pub len: usize,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to decode {} bytes to {}.", self.len, self.typ)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Deserialize)]
 pub enum SqlValue {
     Null,
@@ -154,6 +205,90 @@ pub enum SqlText {
     },
 }
 
+#[inline]
+fn encode_text(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend_from_slice(&(value.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+// Reads bytes that come from outside as text, like Postgres refusing those that aren't
+// UTF-8 and the NUL character
+pub fn check_text(bytes: &[u8]) -> Result<&str, SqlTypeError> {
+    let text = std::str::from_utf8(bytes).map_err(|err| {
+        let at = err.valid_up_to();
+        SqlTypeError::CharacterNotInRepertoire(format!(
+            "Invalid byte sequence for encoding \"UTF8\": 0x{:02x} at byte {}.",
+            bytes[at],
+            at + 1
+        ))
+    })?;
+    check_no_nul(text)?;
+    Ok(text)
+}
+
+// Text values can't hold the NUL character, though Rust strings can
+pub fn check_no_nul(text: &str) -> Result<(), SqlTypeError> {
+    match text.find('\0') {
+        Some(at) => Err(SqlTypeError::UntranslatableCharacter(format!(
+            "Text can't hold the NUL character, found at byte {}.",
+            at + 1
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[inline]
+fn decode_text(bytes: &[u8]) -> Option<String> {
+    let (len, bytes) = split_len(bytes)?;
+    if len != bytes.len() {
+        return None;
+    }
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+// Splits off the u64 length at the start of an encoded text
+#[inline]
+fn split_len(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (len, rest) = bytes.split_at(8);
+    let len = u64::from_be_bytes(len.try_into().ok()?);
+    Some((usize::try_from(len).ok()?, rest))
+}
+
+const SORT_CLASS_BOOLEAN: u8 = 1;
+const SORT_CLASS_INT: u8 = 2;
+const SORT_CLASS_FLOAT: u8 = 3;
+const SORT_CLASS_TEXT: u8 = 4;
+const SORT_CLASS_TYPE: u8 = 5;
+const SORT_CLASS_NULL: u8 = 0xff;
+
+#[inline]
+fn encode_sortable_int(bytes: &mut Vec<u8>, value: i64) {
+    bytes.push(SORT_CLASS_INT);
+    bytes.extend_from_slice(&((value as u64) ^ (1 << 63)).to_be_bytes());
+}
+
+#[inline]
+fn encode_sortable_float(bytes: &mut Vec<u8>, value: f64) {
+    // The positive quiet NaN
+    let bits = if value.is_nan() {
+        0x7ff8_0000_0000_0000
+    } else if value == 0.0 {
+        0.0f64.to_bits()
+    } else {
+        value.to_bits()
+    };
+    let bits = if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    };
+    bytes.push(SORT_CLASS_FLOAT);
+    bytes.extend_from_slice(&bits.to_be_bytes());
+}
+
 #[inline]
 fn factorial(num: i64) -> Result<i64, SqlTypeError> {
     if num < 0 {
@@ -175,6 +310,27 @@ fn factorial(num: i64) -> Result<i64, SqlTypeError> {
     Ok(acc)
 }
 
+// The lowest value of an integer type has no opposite in the type
+fn opposite_out_of_range(value: impl std::fmt::Display, typ: SqlType) -> SqlTypeError {
+    SqlTypeError::OverflowError(format!(
+        "The opposite of {} is out of range for type {}.",
+        value, typ
+    ))
+}
+
+// Bits are shifted by a count from zero up to below the number of bits of the type. A
+// negative count is made one too large, for checked_shl and checked_shr to refuse.
+fn shift_count(count: impl TryInto<u32>) -> u32 {
+    count.try_into().unwrap_or(u32::MAX)
+}
+
+fn shift_out_of_range(count: impl std::fmt::Display, typ: SqlType) -> SqlTypeError {
+    SqlTypeError::OverflowError(format!(
+        "Shift count {} is out of range for type {}.",
+        count, typ
+    ))
+}
+
 impl SqlValue {
     #[inline]
     pub fn is_numeric(&self) -> bool {
@@ -386,89 +542,283 @@ impl SqlValue {
         }
     }
 
+    // Reverses encode, reading the cell as a value of the given type. See encode for the
+    // byte layout of each type.
     #[inline]
-    pub fn decode_type(data: &MemoryCell, typ: SqlType) -> Result<Self, SqlTypeError> {
-        if data.bytes.len() == 0 {
+    pub fn decode(cell: &MemoryCell, typ: SqlType) -> Result<Self, DecodeError> {
+        SqlValue::decode_bytes(&cell.bytes, typ)
+    }
+
+    // Like decode, for the bytes of a cell borrowed from wherever they are kept
+    #[inline]
+    pub fn decode_bytes(bytes: &[u8], typ: SqlType) -> Result<Self, DecodeError> {
+        if bytes.is_empty() {
             return Ok(SqlValue::Null);
         }
+        let err = || DecodeError {
+            typ,
+            len: bytes.len(),
+        };
         match typ {
-            SqlType::Text => Ok(SqlValue::Text(SqlText::decode_text(data)?)),
-            SqlType::VarChar => Ok(SqlValue::Text(SqlText::decode_varchar(data)?)),
-            SqlType::Char => Ok(SqlValue::Text(SqlText::decode_char(data)?)),
-            SqlType::SmallInt => Ok(SqlValue::Numeric(SqlNumeric::decode_small_int(data)?)),
-            SqlType::Int => Ok(SqlValue::Numeric(SqlNumeric::decode_int(data)?)),
-            SqlType::BigInt => Ok(SqlValue::Numeric(SqlNumeric::decode_big_int(data)?)),
-            SqlType::Real => Ok(SqlValue::Numeric(SqlNumeric::decode_real(data)?)),
-            SqlType::DoublePrecision => Ok(SqlValue::Numeric(SqlNumeric::decode_double_precision(
-                data,
-            )?)),
-            SqlType::Boolean => Ok(SqlValue::Boolean(data.as_bool()?)),
-            SqlType::Null => Ok(SqlValue::Null),
-            SqlType::Type => Ok(SqlValue::Text(SqlText::decode_text(data)?)),
+            SqlType::SmallInt => Ok(SqlValue::Numeric(SqlNumeric::SmallInt {
+                value: i16::from_be_bytes(bytes.try_into().map_err(|_| err())?),
+            })),
+            SqlType::Int => Ok(SqlValue::Numeric(SqlNumeric::Int {
+                value: i32::from_be_bytes(bytes.try_into().map_err(|_| err())?),
+            })),
+            SqlType::BigInt => Ok(SqlValue::Numeric(SqlNumeric::BigInt {
+                value: i64::from_be_bytes(bytes.try_into().map_err(|_| err())?),
+            })),
+            SqlType::Real => Ok(SqlValue::Numeric(SqlNumeric::Real {
+                value: f32::from_be_bytes(bytes.try_into().map_err(|_| err())?),
+            })),
+            SqlType::DoublePrecision => Ok(SqlValue::Numeric(SqlNumeric::DoublePrecision {
+                value: f64::from_be_bytes(bytes.try_into().map_err(|_| err())?),
+            })),
+            SqlType::Boolean => match bytes {
+                [0] => Ok(SqlValue::Boolean(false)),
+                [1] => Ok(SqlValue::Boolean(true)),
+                _ => Err(err()),
+            },
+            SqlType::Text => {
+                let value = decode_text(bytes).ok_or_else(err)?;
+                Ok(SqlValue::Text(SqlText::Text { value }))
+            }
+            SqlType::Char => {
+                let (len, bytes) = split_len(bytes).ok_or_else(err)?;
+                let value = decode_text(bytes).ok_or_else(err)?;
+                Ok(SqlValue::Text(SqlText::Char { value, len }))
+            }
+            SqlType::VarChar => {
+                let (len, bytes) = split_len(bytes).ok_or_else(err)?;
+                let (maxlen, bytes) = split_len(bytes).ok_or_else(err)?;
+                let value = decode_text(bytes).ok_or_else(err)?;
+                Ok(SqlValue::Text(SqlText::VarChar { value, maxlen, len }))
+            }
+            SqlType::Type => {
+                let name = std::str::from_utf8(bytes).map_err(|_| err())?;
+                ALL_TYPES
+                    .iter()
+                    .find(|typ| format!("{:?}", typ) == name)
+                    .map(|typ| SqlValue::Type(*typ))
+                    .ok_or_else(err)
+            }
+            SqlType::Null => Err(err()),
+        }
+    }
+
+    // Encodes the value in Postgres' binary format for its type, None standing for NULL
+    // which the protocol sends as a length of -1. Numbers and booleans are laid out as
+    // in a MemoryCell, text is its UTF-8 bytes alone and a type is its OID as a u32.
+    #[inline]
+    pub fn encode_binary(&self) -> Option<Vec<u8>> {
+        match self {
+            SqlValue::Null => None,
+            SqlValue::Text(SqlText::Text { value })
+            | SqlValue::Text(SqlText::Char { value, .. })
+            | SqlValue::Text(SqlText::VarChar { value, .. }) => Some(value.as_bytes().to_vec()),
+            SqlValue::Type(typ) => Some(typ.oid().to_be_bytes().to_vec()),
+            SqlValue::Numeric(_) | SqlValue::Boolean(_) => Some(self.encode().bytes),
+        }
+    }
+
+    // Reverses encode_binary for a value of the given type. CHAR and VARCHAR values take
+    // their length from the text, as the format has no room for it. Text that isn't UTF-8
+    // or holds a NUL is refused, see check_text.
+    #[inline]
+    pub fn decode_binary(bytes: Option<&[u8]>, typ: SqlType) -> Result<Self, SqlTypeError> {
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => return Ok(SqlValue::Null),
+        };
+        let err = || DecodeError {
+            typ,
+            len: bytes.len(),
+        };
+        let text = || check_text(bytes).map(str::to_string);
+        match typ {
+            SqlType::Text => Ok(SqlValue::Text(SqlText::Text { value: text()? })),
+            SqlType::Char => {
+                let value = text()?;
+                let len = value.chars().count();
+                Ok(SqlValue::Text(SqlText::Char { value, len }))
+            }
+            SqlType::VarChar => {
+                let value = text()?;
+                let len = value.chars().count();
+                Ok(SqlValue::Text(SqlText::VarChar {
+                    value,
+                    maxlen: len,
+                    len,
+                }))
+            }
+            SqlType::Type => {
+                let oid = u32::from_be_bytes(bytes.try_into().map_err(|_| err())?);
+                ALL_TYPES
+                    .iter()
+                    .find(|typ| typ.oid() == oid)
+                    .map(|typ| SqlValue::Type(*typ))
+                    .ok_or_else(|| err().into())
+            }
+            // Otherwise the cell's bytes would stand for NULL
+            SqlType::Null => Err(err().into()),
+            _ if bytes.is_empty() => Err(err().into()),
+            _ => Ok(SqlValue::decode_bytes(bytes, typ)?),
         }
     }
 
     #[inline]
     pub fn from_token(token: &Token) -> Result<Self, SqlTypeError> {
         match token {
-            Token::StringValue { value } => Ok(SqlValue::Text(SqlText::Text {
-                value: value.clone(),
-            })),
-            Token::NumericValue { value } => Ok(SqlValue::Numeric(SqlNumeric::parse(value)?)),
+            Token::StringValue { value } => {
+                // Text can't hold a NUL, which escapes like `\0` can put in a literal
+                check_no_nul(value)?;
+                Ok(SqlValue::Text(SqlText::Text {
+                    value: value.to_string(),
+                }))
+            }
+            Token::NumericValue { value } => {
+                Ok(SqlValue::Numeric(SqlNumeric::parse_literal(value)?))
+            }
             Token::BoolValue { value } => Ok(SqlValue::Boolean(*value)),
             Token::Null => Ok(SqlValue::Null),
+            Token::Default => Err(SqlTypeError::ParseError(
+                "DEFAULT is not allowed in this context.".to_string(),
+            )),
             _ => Err(SqlTypeError::ParseError(
                 "Invalid token type to convert to value".to_string(),
             )),
         }
     }
 
+    // Encodes the value into the bytes of a MemoryCell, numbers being big endian:
+    //   NULL of any type: no bytes at all
+    //   SMALLINT, INT, BIGINT: two's complement in 2, 4 and 8 bytes
+    //   REAL, DOUBLE PRECISION: IEEE 754 in 4 and 8 bytes
+    //   BOOL: one byte, 1 for true and 0 for false
+    //   TEXT: the length of the value in bytes as a u64, then its UTF-8 bytes
+    //   CHAR: len as a u64, then the TEXT layout
+    //   VARCHAR: len and maxlen as u64s, then the TEXT layout
+    //   Type: the name of the type in UTF-8
+    // The length prefix keeps the empty string apart from NULL and lets text hold NULs.
     #[inline]
     pub fn encode(&self) -> MemoryCell {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut bytes);
+        MemoryCell { bytes }
+    }
+
+    // Like encode, adding the bytes to the end of `bytes`
+    #[inline]
+    pub fn encode_into(&self, bytes: &mut Vec<u8>) {
         match self {
-            SqlValue::Null => MemoryCell { bytes: vec![] },
-            SqlValue::Text(text) => match &text {
-                SqlText::Text { value } => MemoryCell {
-                    bytes: value.as_bytes().into(),
-                },
-                SqlText::VarChar {
-                    value,
-                    maxlen: _,
-                    len: _,
-                } => MemoryCell {
-                    bytes: value.as_bytes().into(),
-                },
-                SqlText::Char { value, len: _ } => MemoryCell {
-                    bytes: value.as_bytes().into(),
-                },
-            },
-            SqlValue::Numeric(num) => match &num {
-                SqlNumeric::SmallInt { value } => MemoryCell {
-                    bytes: value.to_be_bytes().into(),
-                },
-                SqlNumeric::Int { value } => MemoryCell {
-                    bytes: value.to_be_bytes().into(),
-                },
-                SqlNumeric::BigInt { value } => MemoryCell {
-                    bytes: value.to_be_bytes().into(),
-                },
-                SqlNumeric::Real { value } => MemoryCell {
-                    bytes: value.to_be_bytes().into(),
-                },
-                SqlNumeric::DoublePrecision { value } => MemoryCell {
-                    bytes: value.to_be_bytes().into(),
-                },
-            },
-            SqlValue::Boolean(val) => match val {
-                true => MemoryCell { bytes: vec![1] },
-                false => MemoryCell { bytes: vec![0] },
-            },
-            SqlValue::Type(typ) => MemoryCell {
-                bytes: format!("{:?}", typ).as_bytes().into(),
-            },
+            SqlValue::Null => {}
+            SqlValue::Text(SqlText::Text { value }) => encode_text(bytes, value),
+            SqlValue::Text(SqlText::Char { value, len }) => {
+                bytes.extend_from_slice(&(*len as u64).to_be_bytes());
+                encode_text(bytes, value);
+            }
+            SqlValue::Text(SqlText::VarChar { value, maxlen, len }) => {
+                bytes.extend_from_slice(&(*len as u64).to_be_bytes());
+                bytes.extend_from_slice(&(*maxlen as u64).to_be_bytes());
+                encode_text(bytes, value);
+            }
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => {
+                bytes.extend_from_slice(&value.to_be_bytes())
+            }
+            SqlValue::Numeric(SqlNumeric::Int { value }) => {
+                bytes.extend_from_slice(&value.to_be_bytes())
+            }
+            SqlValue::Numeric(SqlNumeric::BigInt { value }) => {
+                bytes.extend_from_slice(&value.to_be_bytes())
+            }
+            SqlValue::Numeric(SqlNumeric::Real { value }) => {
+                bytes.extend_from_slice(&value.to_be_bytes())
+            }
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => {
+                bytes.extend_from_slice(&value.to_be_bytes())
+            }
+            SqlValue::Boolean(val) => bytes.push(u8::from(*val)),
+            SqlValue::Type(typ) => bytes.extend_from_slice(format!("{:?}", typ).as_bytes()),
         }
     }
 
+    // Length of the value's encoded MemoryCell, without encoding it
+    #[inline]
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            SqlValue::Null => 0,
+            SqlValue::Text(SqlText::Text { value }) => 8 + value.len(),
+            SqlValue::Text(SqlText::Char { value, .. }) => 16 + value.len(),
+            SqlValue::Text(SqlText::VarChar { value, .. }) => 24 + value.len(),
+            SqlValue::Numeric(SqlNumeric::SmallInt { .. }) => 2,
+            SqlValue::Numeric(SqlNumeric::Int { .. })
+            | SqlValue::Numeric(SqlNumeric::Real { .. }) => 4,
+            SqlValue::Numeric(SqlNumeric::BigInt { .. })
+            | SqlValue::Numeric(SqlNumeric::DoublePrecision { .. }) => 8,
+            SqlValue::Boolean(_) => 1,
+            SqlValue::Type(typ) => format!("{:?}", typ).len(),
+        }
+    }
+
+    // Encodes the value so that comparing the bytes of two encodings orders them the way
+    // ORDER BY and indexes do. It can not be decoded. A class byte comes first, so
+    // booleans, integers, floats, text and types order in that sequence and NULL after
+    // everything. Then:
+    //   BOOL: one byte, 0 for false and 1 for true
+    //   SMALLINT, INT, BIGINT: widened to 8 bytes, big endian with the sign bit flipped
+    //   REAL, DOUBLE PRECISION: widened to 8 bytes, big endian with the sign bit flipped
+    //     for positive values and every bit flipped for negative ones. -0 is written as 0
+    //     and every NaN as the same NaN, greater than infinity.
+    //   TEXT, CHAR, VARCHAR: the UTF-8 bytes with each 0 written as 0 0xff, then 0 0
+    //   Type: one byte, the position of the type in SqlType
+    // Every encoding ends where its value does, so the encodings of a few values put
+    // together order like those values would one after another.
+    #[inline]
+    pub fn encode_sortable(&self) -> MemoryCell {
+        let mut bytes = Vec::with_capacity(9);
+        match self {
+            SqlValue::Boolean(value) => {
+                bytes.push(SORT_CLASS_BOOLEAN);
+                bytes.push(u8::from(*value));
+            }
+            SqlValue::Numeric(SqlNumeric::SmallInt { value }) => {
+                encode_sortable_int(&mut bytes, i64::from(*value))
+            }
+            SqlValue::Numeric(SqlNumeric::Int { value }) => {
+                encode_sortable_int(&mut bytes, i64::from(*value))
+            }
+            SqlValue::Numeric(SqlNumeric::BigInt { value }) => {
+                encode_sortable_int(&mut bytes, *value)
+            }
+            SqlValue::Numeric(SqlNumeric::Real { value }) => {
+                encode_sortable_float(&mut bytes, f64::from(*value))
+            }
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => {
+                encode_sortable_float(&mut bytes, *value)
+            }
+            SqlValue::Text(SqlText::Text { value })
+            | SqlValue::Text(SqlText::Char { value, .. })
+            | SqlValue::Text(SqlText::VarChar { value, .. }) => {
+                bytes.reserve(value.len() + 2);
+                bytes.push(SORT_CLASS_TEXT);
+                for byte in value.bytes() {
+                    bytes.push(byte);
+                    if byte == 0 {
+                        bytes.push(0xff);
+                    }
+                }
+                bytes.extend_from_slice(&[0, 0]);
+            }
+            SqlValue::Type(typ) => {
+                bytes.push(SORT_CLASS_TYPE);
+                bytes.push(*typ as u8);
+            }
+            SqlValue::Null => bytes.push(SORT_CLASS_NULL),
+        }
+        MemoryCell { bytes }
+    }
+
     #[inline]
     pub fn subtract(&self, b: &Self) -> Result<Self, SqlTypeError> {
         let (a, b) = SqlValue::implicist_cast_to_matching_types(self, b)?;
@@ -645,7 +995,7 @@ impl SqlValue {
             b.to_type(SqlType::DoublePrecision)?
         {
             if value == 0.0 {
-                return Err(SqlTypeError::OperationError("Division by zero".to_string()));
+                return Err(SqlTypeError::DivisionByZero);
             }
         }
         match (&a, &b) {
@@ -707,7 +1057,7 @@ impl SqlValue {
             b.to_type(SqlType::DoublePrecision)?
         {
             if value == 0.0 {
-                return Err(SqlTypeError::OperationError("Division by zero".to_string()));
+                return Err(SqlTypeError::DivisionByZero);
             }
         }
         match (&a, &b) {
@@ -769,7 +1119,7 @@ impl SqlValue {
             b.to_type(SqlType::DoublePrecision)?
         {
             if value == 0.0 {
-                return Err(SqlTypeError::OperationError("Division by zero".to_string()));
+                return Err(SqlTypeError::DivisionByZero);
             }
         }
         match (&a, &b) {
@@ -930,13 +1280,22 @@ impl SqlValue {
         match (&a, &b) {
             (SqlValue::Numeric(ref num1), SqlValue::Numeric(ref num2)) => match (num1, num2) {
                 (SqlNumeric::SmallInt { value: v1 }, SqlNumeric::SmallInt { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value: v1 << v2 }))
+                    match v1.checked_shl(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::SmallInt)),
+                    }
                 }
                 (SqlNumeric::Int { value: v1 }, SqlNumeric::Int { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::Int { value: v1 << v2 }))
+                    match v1.checked_shl(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::Int { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::Int)),
+                    }
                 }
                 (SqlNumeric::BigInt { value: v1 }, SqlNumeric::BigInt { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::BigInt { value: v1 << v2 }))
+                    match v1.checked_shl(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::BigInt { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::BigInt)),
+                    }
                 }
                 _ => Err(SqlTypeError::TypeMismatchError(
                     "Type mismatch for modulo".to_string(),
@@ -960,13 +1319,22 @@ impl SqlValue {
         match (&a, &b) {
             (SqlValue::Numeric(ref num1), SqlValue::Numeric(ref num2)) => match (num1, num2) {
                 (SqlNumeric::SmallInt { value: v1 }, SqlNumeric::SmallInt { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value: v1 >> v2 }))
+                    match v1.checked_shr(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::SmallInt)),
+                    }
                 }
                 (SqlNumeric::Int { value: v1 }, SqlNumeric::Int { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::Int { value: v1 >> v2 }))
+                    match v1.checked_shr(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::Int { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::Int)),
+                    }
                 }
                 (SqlNumeric::BigInt { value: v1 }, SqlNumeric::BigInt { value: v2 }) => {
-                    Ok(SqlValue::Numeric(SqlNumeric::BigInt { value: v1 >> v2 }))
+                    match v1.checked_shr(shift_count(*v2)) {
+                        Some(value) => Ok(SqlValue::Numeric(SqlNumeric::BigInt { value })),
+                        None => Err(shift_out_of_range(v2, SqlType::BigInt)),
+                    }
                 }
                 _ => Err(SqlTypeError::TypeMismatchError(
                     "Type mismatch for modulo".to_string(),
@@ -1132,15 +1500,18 @@ impl SqlValue {
     pub fn abs(&self) -> Result<Self, SqlTypeError> {
         match self {
             SqlValue::Numeric(num) => match num {
-                SqlNumeric::SmallInt { value } => Ok(SqlValue::Numeric(SqlNumeric::SmallInt {
-                    value: value.abs(),
-                })),
-                SqlNumeric::Int { value } => {
-                    Ok(SqlValue::Numeric(SqlNumeric::Int { value: value.abs() }))
-                }
-                SqlNumeric::BigInt { value } => {
-                    Ok(SqlValue::Numeric(SqlNumeric::BigInt { value: value.abs() }))
-                }
+                SqlNumeric::SmallInt { value } => match value.checked_abs() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::SmallInt)),
+                },
+                SqlNumeric::Int { value } => match value.checked_abs() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::Int { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::Int)),
+                },
+                SqlNumeric::BigInt { value } => match value.checked_abs() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::BigInt { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::BigInt)),
+                },
                 SqlNumeric::Real { value } => {
                     Ok(SqlValue::Numeric(SqlNumeric::Real { value: value.abs() }))
                 }
@@ -1160,15 +1531,18 @@ impl SqlValue {
     pub fn minus(&self) -> Result<Self, SqlTypeError> {
         match self {
             SqlValue::Numeric(num) => match num {
-                SqlNumeric::SmallInt { value } => {
-                    Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value: -value }))
-                }
-                SqlNumeric::Int { value } => {
-                    Ok(SqlValue::Numeric(SqlNumeric::Int { value: -value }))
-                }
-                SqlNumeric::BigInt { value } => {
-                    Ok(SqlValue::Numeric(SqlNumeric::BigInt { value: -value }))
-                }
+                SqlNumeric::SmallInt { value } => match value.checked_neg() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::SmallInt)),
+                },
+                SqlNumeric::Int { value } => match value.checked_neg() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::Int { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::Int)),
+                },
+                SqlNumeric::BigInt { value } => match value.checked_neg() {
+                    Some(value) => Ok(SqlValue::Numeric(SqlNumeric::BigInt { value })),
+                    None => Err(opposite_out_of_range(value, SqlType::BigInt)),
+                },
                 SqlNumeric::Real { value } => {
                     Ok(SqlValue::Numeric(SqlNumeric::Real { value: -value }))
                 }
@@ -1217,20 +1591,19 @@ impl SqlValue {
         }
     }
 
+    // Like Postgres, a value of any other type is concatenated with text as its text form,
+    // the way SELECT shows it. One of the two has to be text.
     #[inline]
     pub fn concat(&self, b: &Self) -> Result<Self, SqlTypeError> {
-        let (a, b) = SqlValue::implicist_cast_to_matching_types(self, b)?;
-        match (a, b) {
-            (SqlValue::Text(ref text1), SqlValue::Text(ref text2)) => match (text1, text2) {
-                (SqlText::Text { value: v1 }, SqlText::Text { value: v2 }) => {
-                    let mut new_v = v1.clone();
-                    new_v.push_str(v2.as_str());
-                    Ok(SqlValue::Text(SqlText::Text { value: new_v }))
-                }
-                _ => Err(SqlTypeError::TypeMismatchError(
-                    "Type mismatch for concat".to_string(),
-                )),
-            },
+        if self.is_null() || b.is_null() {
+            return Ok(SqlValue::Null);
+        }
+        match (self, b) {
+            (SqlValue::Text(_), _) | (_, SqlValue::Text(_)) => {
+                let mut new_v = self.to_string();
+                new_v.push_str(b.to_string().as_str());
+                Ok(SqlValue::Text(SqlText::Text { value: new_v }))
+            }
             _ => Err(SqlTypeError::TypeMismatchError(
                 "Type mismatch for concat".to_string(),
             )),
@@ -1297,25 +1670,35 @@ impl SqlValue {
         }
     }
 
+    // NULL is unknown, FALSE AND NULL is still FALSE while TRUE AND NULL is NULL
     #[inline]
     pub fn and(&self, b: &Self) -> Result<Self, SqlTypeError> {
-        if let (SqlValue::Boolean(a), SqlValue::Boolean(b)) = (self, b) {
-            Ok(SqlValue::Boolean(*a && *b))
-        } else {
-            Err(SqlTypeError::TypeMismatchError(
-                "Type mismatch for concat".to_string(),
-            ))
+        match (self, b) {
+            (SqlValue::Boolean(a), SqlValue::Boolean(b)) => Ok(SqlValue::Boolean(*a && *b)),
+            (SqlValue::Boolean(false), SqlValue::Null)
+            | (SqlValue::Null, SqlValue::Boolean(false)) => Ok(SqlValue::Boolean(false)),
+            (SqlValue::Boolean(_), SqlValue::Null)
+            | (SqlValue::Null, SqlValue::Boolean(_))
+            | (SqlValue::Null, SqlValue::Null) => Ok(SqlValue::Null),
+            _ => Err(SqlTypeError::TypeMismatchError(
+                "Type mismatch for and".to_string(),
+            )),
         }
     }
 
+    // TRUE OR NULL is still TRUE while FALSE OR NULL is NULL
     #[inline]
     pub fn or(&self, b: &Self) -> Result<Self, SqlTypeError> {
-        if let (SqlValue::Boolean(a), SqlValue::Boolean(b)) = (self, b) {
-            Ok(SqlValue::Boolean(*a || *b))
-        } else {
-            Err(SqlTypeError::TypeMismatchError(
-                "Type mismatch for concat".to_string(),
-            ))
+        match (self, b) {
+            (SqlValue::Boolean(a), SqlValue::Boolean(b)) => Ok(SqlValue::Boolean(*a || *b)),
+            (SqlValue::Boolean(true), SqlValue::Null)
+            | (SqlValue::Null, SqlValue::Boolean(true)) => Ok(SqlValue::Boolean(true)),
+            (SqlValue::Boolean(_), SqlValue::Null)
+            | (SqlValue::Null, SqlValue::Boolean(_))
+            | (SqlValue::Null, SqlValue::Null) => Ok(SqlValue::Null),
+            _ => Err(SqlTypeError::TypeMismatchError(
+                "Type mismatch for or".to_string(),
+            )),
         }
     }
 
@@ -1344,6 +1727,37 @@ impl SqlValue {
         }
     }
 
+    // The value as `typ` if it implicitly casts to it and comes out the same, so that it
+    // compares with values of `typ` as it did before. Numbers cast among themselves, text
+    // between Text and VarChar.
+    pub fn lossless_cast_to_type(&self, typ: SqlType) -> Option<Self> {
+        let from = self.get_type();
+        if from == typ {
+            return Some(self.clone());
+        }
+        match (self, typ) {
+            (
+                SqlValue::Numeric(_),
+                SqlType::SmallInt
+                | SqlType::Int
+                | SqlType::BigInt
+                | SqlType::Real
+                | SqlType::DoublePrecision,
+            ) => {
+                let cast = self.explicit_cast_to_type(typ).ok()?;
+                match cast.explicit_cast_to_type(from) {
+                    Ok(back) if back == *self => Some(cast),
+                    _ => None,
+                }
+            }
+            (
+                SqlValue::Text(SqlText::Text { .. } | SqlText::VarChar { .. }),
+                SqlType::Text | SqlType::VarChar,
+            ) => Some(self.clone()),
+            _ => None,
+        }
+    }
+
     #[inline]
     pub fn explicit_cast_to_type(&self, typ: SqlType) -> Result<Self, SqlTypeError> {
         if self.is_null() {
@@ -1609,6 +2023,9 @@ impl SqlValue {
                         value: "false".to_string(),
                     })),
                 },
+                SqlValue::Type(typ) => Ok(SqlValue::Text(SqlText::Text {
+                    value: typ.to_string(),
+                })),
                 _ => Err(SqlTypeError::TypeMismatchError("Type mismatch".to_string())),
             },
             SqlType::VarChar => match self {
@@ -1685,6 +2102,14 @@ impl SqlValue {
                         value: "false".to_string(),
                     })),
                 },
+                SqlValue::Type(typ) => {
+                    let value = typ.to_string();
+                    Ok(SqlValue::Text(SqlText::VarChar {
+                        len: value.len(),
+                        maxlen: value.len(),
+                        value,
+                    }))
+                }
                 _ => Err(SqlTypeError::TypeMismatchError("Type mismatch".to_string())),
             },
             SqlType::Char => match self {
@@ -1753,6 +2178,13 @@ impl SqlValue {
                         value: "false".to_string(),
                     })),
                 },
+                SqlValue::Type(typ) => {
+                    let value = typ.to_string();
+                    Ok(SqlValue::Text(SqlText::Char {
+                        len: value.len(),
+                        value,
+                    }))
+                }
                 _ => Err(SqlTypeError::TypeMismatchError("Type mismatch".to_string())),
             },
             SqlType::Boolean => match self {
@@ -1767,7 +2199,7 @@ impl SqlValue {
                     SqlText::Text { value } => match value.as_str() {
                         "true" => Ok(SqlValue::Boolean(true)),
                         "false" => Ok(SqlValue::Boolean(false)),
-                        _ => Err(SqlTypeError::ConversionError(format!(
+                        _ => Err(SqlTypeError::ParseError(format!(
                             "Invalid input syntax for type boolean: \"{}\"",
                             value
                         ))),
@@ -1779,7 +2211,7 @@ impl SqlValue {
                     } => match value.as_str() {
                         "true" => Ok(SqlValue::Boolean(true)),
                         "false" => Ok(SqlValue::Boolean(false)),
-                        _ => Err(SqlTypeError::ConversionError(format!(
+                        _ => Err(SqlTypeError::ParseError(format!(
                             "Invalid input syntax for type boolean: \"{}\"",
                             value
                         ))),
@@ -1787,7 +2219,7 @@ impl SqlValue {
                     SqlText::Char { value, len: _ } => match value.as_str() {
                         "true" => Ok(SqlValue::Boolean(true)),
                         "false" => Ok(SqlValue::Boolean(false)),
-                        _ => Err(SqlTypeError::ConversionError(format!(
+                        _ => Err(SqlTypeError::ParseError(format!(
                             "Invalid input syntax for type boolean: \"{}\"",
                             value
                         ))),
@@ -2003,7 +2435,7 @@ impl SqlValue {
 
 impl SqlNumeric {
     #[inline]
-    pub fn parse(data: &String) -> Result<Self, SqlTypeError> {
+    pub fn parse(data: &str) -> Result<Self, SqlTypeError> {
         if let Ok(value) = data.parse::<i16>() {
             Ok(SqlNumeric::SmallInt { value })
         } else if let Ok(value) = data.parse::<i32>() {
@@ -2030,6 +2462,30 @@ impl SqlNumeric {
         }
     }
 
+    // Types a numeric literal of a query as Postgres does. A whole number is an INT if it
+    // fits in one and otherwise a BIGINT, while one too large for that or with a decimal
+    // point or exponent is a DOUBLE PRECISION, there being no NUMERIC type yet.
+    pub fn parse_literal(data: &str) -> Result<Self, SqlTypeError> {
+        if !data.contains(&['.', 'e', 'E'][..]) {
+            if let Ok(value) = data.parse::<i32>() {
+                return Ok(SqlNumeric::Int { value });
+            } else if let Ok(value) = data.parse::<i64>() {
+                return Ok(SqlNumeric::BigInt { value });
+            }
+        }
+        match data.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(SqlNumeric::DoublePrecision { value }),
+            Ok(_) => Err(SqlTypeError::OverflowError(format!(
+                "Value \"{}\" is out of range for type double precision.",
+                data
+            ))),
+            Err(_) => Err(SqlTypeError::ParseError(format!(
+                "Invalid numeric literal \"{}\".",
+                data
+            ))),
+        }
+    }
+
     #[inline]
     pub fn parse_small_int(data: String) -> Result<Self, SqlTypeError> {
         if let Ok(value) = data.parse::<i16>() {
@@ -2084,68 +2540,6 @@ impl SqlNumeric {
             ))
         }
     }
-
-    #[inline]
-    pub fn decode_small_int(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-
-        if let Ok(value) = rdr.read_i16::<BigEndian>() {
-            Ok(SqlNumeric::SmallInt { value })
-        } else {
-            Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to i16.".to_string(),
-            ))
-        }
-    }
-
-    #[inline]
-    pub fn decode_int(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-
-        if let Ok(value) = rdr.read_i32::<BigEndian>() {
-            Ok(SqlNumeric::Int { value })
-        } else {
-            Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to i32.".to_string(),
-            ))
-        }
-    }
-
-    #[inline]
-    pub fn decode_big_int(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-        if let Ok(value) = rdr.read_i64::<BigEndian>() {
-            Ok(SqlNumeric::BigInt { value })
-        } else {
-            Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to i64.".to_string(),
-            ))
-        }
-    }
-
-    #[inline]
-    pub fn decode_real(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-        if let Ok(value) = rdr.read_f32::<BigEndian>() {
-            Ok(SqlNumeric::Real { value })
-        } else {
-            Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to f32.".to_string(),
-            ))
-        }
-    }
-
-    #[inline]
-    pub fn decode_double_precision(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-        if let Ok(value) = rdr.read_f64::<BigEndian>() {
-            Ok(SqlNumeric::DoublePrecision { value })
-        } else {
-            Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to f64.".to_string(),
-            ))
-        }
-    }
 }
 
 impl SqlText {
@@ -2185,29 +2579,6 @@ impl SqlText {
             )))
         }
     }
-
-    #[inline]
-    pub fn decode_text(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        let mut rdr = std::io::Cursor::new(&data.bytes);
-
-        let mut text = "".to_owned();
-        match rdr.read_to_string(&mut text) {
-            Ok(_) => Ok(SqlText::Text { value: text }),
-            Err(_err) => Err(SqlTypeError::DecodeError(
-                "Failed to decode bytes to String.".to_string(),
-            )),
-        }
-    }
-
-    #[inline]
-    pub fn decode_varchar(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        SqlText::decode_text(data)
-    }
-
-    #[inline]
-    pub fn decode_char(data: &MemoryCell) -> Result<Self, SqlTypeError> {
-        SqlText::decode_text(data)
-    }
 }
 
 impl std::fmt::Display for SqlValue {
@@ -2266,6 +2637,18 @@ impl From<&str> for SqlTypeError {
     }
 }
 
+impl From<DecodeError> for SqlTypeError {
+    fn from(err: DecodeError) -> Self {
+        SqlTypeError::DecodeError(err.to_string())
+    }
+}
+
+impl From<DecodeError> for String {
+    fn from(err: DecodeError) -> Self {
+        err.to_string()
+    }
+}
+
 impl From<SqlTypeError> for String {
     fn from(err: SqlTypeError) -> Self {
         err.to_string()
@@ -2280,7 +2663,7 @@ impl From<std::convert::Infallible> for SqlTypeError {
 
 impl From<std::num::TryFromIntError> for SqlTypeError {
     fn from(err: std::num::TryFromIntError) -> Self {
-        SqlTypeError::ConversionError(err.to_string())
+        SqlTypeError::OverflowError(err.to_string())
     }
 }
 
@@ -2295,3 +2678,340 @@ impl From<std::num::ParseFloatError> for SqlTypeError {
         SqlTypeError::ParseError(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod encode_tests {
+    use super::*;
+    use crate::backend::BackendError;
+    use proptest::prelude::*;
+
+    fn any_text() -> impl Strategy<Value = String> {
+        prop::collection::vec(prop_oneof![Just('\0'), any::<char>()], 0..32)
+            .prop_map(|chars| chars.into_iter().collect())
+    }
+
+    fn value() -> impl Strategy<Value = SqlValue> {
+        prop_oneof![
+            Just(SqlValue::Null),
+            any_text().prop_map(|value| SqlValue::Text(SqlText::Text { value })),
+            (any_text(), any::<usize>())
+                .prop_map(|(value, len)| SqlValue::Text(SqlText::Char { value, len })),
+            (any_text(), any::<usize>(), any::<usize>()).prop_map(|(value, maxlen, len)| {
+                SqlValue::Text(SqlText::VarChar { value, maxlen, len })
+            }),
+            prop_oneof![Just(i16::MIN), Just(i16::MAX), any::<i16>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::SmallInt { value })),
+            prop_oneof![Just(i32::MIN), Just(i32::MAX), any::<i32>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::Int { value })),
+            prop_oneof![Just(i64::MIN), Just(i64::MAX), any::<i64>()]
+                .prop_map(|value| SqlValue::Numeric(SqlNumeric::BigInt { value })),
+            any::<f32>().prop_map(|value| SqlValue::Numeric(SqlNumeric::Real { value })),
+            any::<f64>().prop_map(|value| SqlValue::Numeric(SqlNumeric::DoublePrecision { value })),
+            any::<bool>().prop_map(SqlValue::Boolean),
+            prop::sample::select(ALL_TYPES.to_vec()).prop_map(SqlValue::Type),
+        ]
+    }
+
+    fn is_nan(value: &SqlValue) -> bool {
+        match value {
+            SqlValue::Numeric(SqlNumeric::Real { value }) => value.is_nan(),
+            SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => value.is_nan(),
+            _ => false,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_encoded(
+            value in value(),
+            null_type in prop::sample::select(ALL_TYPES.to_vec()),
+        ) {
+            // NULL decodes from any type
+            let typ = match value {
+                SqlValue::Null => null_type,
+                _ => value.get_type(),
+            };
+            let cell = value.encode();
+            prop_assert_eq!(cell.bytes.len(), value.encoded_len());
+            let decoded = SqlValue::decode(&cell, typ).unwrap();
+            // NaN is not equal to itself, so those are compared by their bits
+            if is_nan(&value) {
+                prop_assert_eq!(decoded.encode(), cell);
+            } else {
+                prop_assert_eq!(decoded, value);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_binary_encoded(value in value()) {
+            let typ = value.get_type();
+            let bytes = value.encode_binary();
+            let decoded = SqlValue::decode_binary(bytes.as_deref(), typ);
+            // Text with a NUL is refused rather than read back
+            let decoded = match &value {
+                SqlValue::Text(text) if text.to_string().contains('\0') => {
+                    let refused = matches!(decoded, Err(SqlTypeError::UntranslatableCharacter(_)));
+                    prop_assert!(refused);
+                    return Ok(());
+                }
+                _ => decoded.unwrap(),
+            };
+            match (&decoded, &value) {
+                (SqlValue::Text(decoded), SqlValue::Text(value)) => {
+                    prop_assert_eq!(decoded.to_string(), value.to_string())
+                }
+                _ if is_nan(&value) => prop_assert_eq!(decoded.encode(), value.encode()),
+                _ => prop_assert_eq!(decoded, value),
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_format() {
+        let tests = vec![
+            (small_int(-2), Some(vec![0xff, 0xfe])),
+            (int(1), Some(vec![0, 0, 0, 1])),
+            (big_int(256), Some(vec![0, 0, 0, 0, 0, 0, 1, 0])),
+            (real(1.0), Some(vec![0x3f, 0x80, 0, 0])),
+            (double(-2.0), Some(vec![0xc0, 0, 0, 0, 0, 0, 0, 0])),
+            (SqlValue::Boolean(true), Some(vec![1])),
+            (text("héllo"), Some("héllo".as_bytes().to_vec())),
+            (text(""), Some(vec![])),
+            (SqlValue::Type(SqlType::Int), Some(vec![0, 0, 0, 23])),
+            (SqlValue::Null, None),
+        ];
+        for (value, bytes) in tests {
+            assert_eq!(value.encode_binary(), bytes, "{:?}", value);
+        }
+
+        let errors = vec![
+            (vec![0, 0, 1], SqlType::Int),
+            (vec![], SqlType::BigInt),
+            (vec![2], SqlType::Boolean),
+            (vec![0, 0, 0, 42], SqlType::Type),
+            (vec![], SqlType::Null),
+        ];
+        for (bytes, typ) in errors {
+            let len = bytes.len();
+            assert_eq!(
+                SqlValue::decode_binary(Some(&bytes), typ),
+                Err(DecodeError { typ, len }.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_binary_text_checked() {
+        let invalid = SqlValue::decode_binary(Some(b"ab\xffc"), SqlType::Text).unwrap_err();
+        assert_eq!(
+            invalid,
+            SqlTypeError::CharacterNotInRepertoire(
+                "Invalid byte sequence for encoding \"UTF8\": 0xff at byte 3.".to_string()
+            )
+        );
+        assert_eq!(BackendError::from(invalid).code(), "22021");
+        // A sequence cut short is as bad
+        let cut = SqlValue::decode_binary(Some("é".as_bytes()[..1].as_ref()), SqlType::Char);
+        assert!(matches!(
+            cut,
+            Err(SqlTypeError::CharacterNotInRepertoire(_))
+        ));
+
+        for typ in [SqlType::Text, SqlType::Char, SqlType::VarChar] {
+            let nul = SqlValue::decode_binary(Some(b"a\0"), typ).unwrap_err();
+            assert_eq!(
+                nul,
+                SqlTypeError::UntranslatableCharacter(
+                    "Text can't hold the NUL character, found at byte 2.".to_string()
+                )
+            );
+            assert_eq!(BackendError::from(nul).code(), "22P05");
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let empty = SqlValue::Text(SqlText::Text {
+            value: "".to_string(),
+        });
+        assert_eq!(SqlValue::decode(&empty.encode(), SqlType::Text), Ok(empty));
+
+        let cells = vec![
+            (vec![0, 0, 1], SqlType::Int),
+            (vec![0, 0, 0, 0, 0, 1], SqlType::SmallInt),
+            (vec![2], SqlType::Boolean),
+            (vec![0, 0, 0, 0, 0, 0, 0, 2, b'a'], SqlType::Text),
+            (vec![0, 0, 0, 0, 0, 0, 0, 1, 0xff], SqlType::Text),
+            (vec![0, 0, 0, 0, 0, 0, 0, 0], SqlType::VarChar),
+            (b"Date".to_vec(), SqlType::Type),
+            (vec![1], SqlType::Null),
+        ];
+        for (bytes, typ) in cells {
+            let len = bytes.len();
+            assert_eq!(
+                SqlValue::decode(&MemoryCell { bytes }, typ),
+                Err(DecodeError { typ, len })
+            );
+        }
+    }
+
+    fn small_int(value: i16) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::SmallInt { value })
+    }
+
+    fn int(value: i32) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::Int { value })
+    }
+
+    fn big_int(value: i64) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::BigInt { value })
+    }
+
+    fn real(value: f32) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::Real { value })
+    }
+
+    fn double(value: f64) -> SqlValue {
+        SqlValue::Numeric(SqlNumeric::DoublePrecision { value })
+    }
+
+    fn text(value: &str) -> SqlValue {
+        SqlValue::Text(SqlText::Text {
+            value: value.to_string(),
+        })
+    }
+
+    // Asserts that the encodings of every pair of values order like the groups they are
+    // in, values of the same group being equal
+    fn assert_sortable(groups: &[Vec<SqlValue>]) {
+        for (i, group) in groups.iter().enumerate() {
+            for (j, other_group) in groups.iter().enumerate() {
+                for a in group {
+                    for b in other_group {
+                        assert_eq!(
+                            a.encode_sortable().bytes.cmp(&b.encode_sortable().bytes),
+                            i.cmp(&j),
+                            "{:?} and {:?}",
+                            a,
+                            b
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sortable_ints() {
+        assert_sortable(&[
+            vec![big_int(i64::MIN)],
+            vec![big_int(i64::from(i32::MIN) - 1)],
+            vec![int(i32::MIN), big_int(i64::from(i32::MIN))],
+            vec![small_int(i16::MIN), int(i32::from(i16::MIN))],
+            vec![small_int(-256)],
+            vec![small_int(-1), int(-1), big_int(-1)],
+            vec![small_int(0), int(0), big_int(0)],
+            vec![small_int(1), int(1), big_int(1)],
+            vec![small_int(256)],
+            vec![small_int(i16::MAX), big_int(i64::from(i16::MAX))],
+            vec![int(i32::MAX)],
+            vec![big_int(i64::from(i32::MAX) + 1)],
+            vec![big_int(i64::MAX)],
+        ]);
+    }
+
+    #[test]
+    fn test_sortable_floats() {
+        assert_sortable(&[
+            vec![real(f32::NEG_INFINITY), double(f64::NEG_INFINITY)],
+            vec![double(f64::MIN)],
+            vec![real(f32::MIN), double(f64::from(f32::MIN))],
+            vec![real(-1.5), double(-1.5)],
+            vec![double(-f64::MIN_POSITIVE)],
+            vec![double(-f64::from_bits(1))],
+            vec![real(-0.0), real(0.0), double(-0.0), double(0.0)],
+            vec![double(f64::from_bits(1))],
+            vec![real(f32::MIN_POSITIVE)],
+            // 0.1 rounds up as a REAL
+            vec![double(0.1)],
+            vec![real(0.1)],
+            vec![real(1.0), double(1.0)],
+            vec![double(f64::MAX)],
+            vec![real(f32::INFINITY), double(f64::INFINITY)],
+            vec![
+                real(f32::NAN),
+                real(-f32::NAN),
+                double(f64::NAN),
+                double(-f64::NAN),
+                double(f64::from_bits(0x7ff0_0000_0000_0001)),
+            ],
+        ]);
+    }
+
+    #[test]
+    fn test_sortable_text() {
+        assert_sortable(&[
+            vec![text("")],
+            vec![text("\0")],
+            vec![text("\0\0")],
+            vec![text("\0a")],
+            vec![text("\u{1}")],
+            vec![
+                text("a"),
+                SqlValue::Text(SqlText::Char {
+                    value: "a".to_string(),
+                    len: 1,
+                }),
+                SqlValue::Text(SqlText::VarChar {
+                    value: "a".to_string(),
+                    maxlen: 10,
+                    len: 1,
+                }),
+            ],
+            vec![text("a\0")],
+            vec![text("a\0b")],
+            vec![text("aa")],
+            vec![text("b")],
+            vec![text("\u{ff}")],
+            vec![text("\u{10ffff}")],
+        ]);
+    }
+
+    #[test]
+    fn test_sortable_classes() {
+        assert_sortable(&[
+            vec![SqlValue::Boolean(false)],
+            vec![SqlValue::Boolean(true)],
+            vec![big_int(i64::MIN)],
+            vec![big_int(i64::MAX)],
+            vec![double(f64::NEG_INFINITY)],
+            vec![double(f64::NAN)],
+            vec![text("")],
+            vec![text("\u{10ffff}")],
+            vec![SqlValue::Type(SqlType::SmallInt)],
+            vec![SqlValue::Type(SqlType::Type)],
+            vec![SqlValue::Null],
+        ]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_sortable_random(a in value(), b in value()) {
+            let expected = match (&a, &b) {
+                _ if a.is_int() && b.is_int() => a
+                    .to_type(SqlType::BigInt)
+                    .unwrap()
+                    .cmp(&b.to_type(SqlType::BigInt).unwrap()),
+                _ if a.is_float() && b.is_float() && !is_nan(&a) && !is_nan(&b) => a
+                    .to_type(SqlType::DoublePrecision)
+                    .unwrap()
+                    .cmp(&b.to_type(SqlType::DoublePrecision).unwrap()),
+                (SqlValue::Text(a), SqlValue::Text(b)) => a.to_string().cmp(&b.to_string()),
+                _ => return Ok(()),
+            };
+            prop_assert_eq!(a.encode_sortable().bytes.cmp(&b.encode_sortable().bytes), expected);
+        }
+    }
+}
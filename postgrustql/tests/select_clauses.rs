@@ -0,0 +1,391 @@
+use postgrustql::backend::EvalResult;
+use postgrustql::backend_memory::MemoryBackend;
+
+// Clauses of a SELECT apply in the order join, where, select, distinct, order, offset,
+// limit. The expected rows below are worked out by hand from the data in `backend`.
+struct ClauseTest {
+    query: &'static str,
+    rows: Vec<&'static str>,
+}
+
+fn backend() -> MemoryBackend {
+    let mut mb = MemoryBackend::new();
+    mb.eval_query(
+        "CREATE TABLE people (id INT, name TEXT, age INT);
+        INSERT INTO people VALUES (1, 'Baam', 20);
+        INSERT INTO people VALUES (2, 'Rachel', 25);
+        INSERT INTO people VALUES (3, 'Khun', 30);
+        INSERT INTO people VALUES (4, 'Rak', 35);
+        INSERT INTO people VALUES (5, 'Endorsi', 28);
+        CREATE TABLE pets (owner INT, pet TEXT);
+        INSERT INTO pets VALUES (1, 'Cat');
+        INSERT INTO pets VALUES (1, 'Dog');
+        INSERT INTO pets VALUES (2, 'Eel');
+        INSERT INTO pets VALUES (3, 'Fox');
+        INSERT INTO pets VALUES (3, 'Owl');
+        INSERT INTO pets VALUES (4, 'Yak');
+        INSERT INTO pets VALUES (5, 'Ant');",
+    )
+    .unwrap();
+    mb
+}
+
+// Rows of the query, each one's values joined by commas
+fn rows(mb: &mut MemoryBackend, query: &str) -> Vec<String> {
+    match mb.eval_query(query).unwrap().pop() {
+        Some(EvalResult::Select { results, .. }) => results
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect(),
+        _ => panic!("Expected select results for {}", query),
+    }
+}
+
+#[test]
+fn test_select_clause_order() {
+    let tests = vec![
+        // Pets of people over 20: Eel, Fox, Owl, Yak, Ant. Sorted, then the first is
+        // skipped and three are kept.
+        ClauseTest {
+            query: "SELECT pet FROM people JOIN pets ON id = owner
+                WHERE age > 20 ORDER BY pet LIMIT 3 OFFSET 1;",
+            rows: vec!["Eel", "Fox", "Owl"],
+        },
+        ClauseTest {
+            query: "SELECT pet FROM people INNER JOIN pets ON id = owner
+                WHERE age > 20 ORDER BY pet OFFSET 1 LIMIT 3;",
+            rows: vec!["Eel", "Fox", "Owl"],
+        },
+        // Owners under 33 are Baam twice, Rachel, Khun twice and Endorsi. DISTINCT keeps
+        // four names, DESC gives Rachel, Khun, Endorsi, Baam.
+        ClauseTest {
+            query: "SELECT DISTINCT name FROM people INNER JOIN pets ON id = owner
+                WHERE age < 33 ORDER BY name DESC OFFSET 1 LIMIT 2;",
+            rows: vec!["Khun", "Endorsi"],
+        },
+        // The oldest two, not the first two sorted
+        ClauseTest {
+            query: "SELECT name, age FROM people WHERE age > 21 ORDER BY age DESC LIMIT 2;",
+            rows: vec!["Rak,35", "Khun,30"],
+        },
+        // Without ORDER BY rows keep their insertion order, 1, 1, 2, 3, 3, 4, 5. OFFSET
+        // counts distinct rows.
+        ClauseTest {
+            query: "SELECT DISTINCT owner FROM pets OFFSET 1 LIMIT 3;",
+            rows: vec!["2", "3", "4"],
+        },
+        ClauseTest {
+            query: "SELECT name FROM people WHERE age >= 25 LIMIT 2 OFFSET 1;",
+            rows: vec!["Khun", "Rak"],
+        },
+        ClauseTest {
+            query: "SELECT pet FROM people JOIN pets ON id = owner ORDER BY pet DESC OFFSET 6;",
+            rows: vec!["Ant"],
+        },
+        ClauseTest {
+            query: "SELECT pet FROM people JOIN pets ON id = owner ORDER BY pet OFFSET 7;",
+            rows: vec![],
+        },
+    ];
+
+    let mut mb = backend();
+    for test in tests {
+        assert_eq!(rows(&mut mb, test.query), test.rows, "{}", test.query);
+    }
+}
+
+#[test]
+fn test_select_clause_errors() {
+    let mut mb = backend();
+    for query in &[
+        "SELECT name FROM people LIMIT 1 LIMIT 2;",
+        "SELECT name FROM people OFFSET;",
+        "SELECT name FROM people LIMIT 2 ORDER BY name;",
+        "SELECT name FROM people ORDER BY name WHERE age > 20;",
+    ] {
+        assert!(mb.eval_query(query).is_err(), "{}", query);
+    }
+}
+
+// Without a FROM a SELECT works out its items on a single row of no columns, which
+// WHERE can filter out
+#[test]
+fn test_select_without_from() {
+    let mut mb = backend();
+    let tests = vec![
+        ClauseTest {
+            query: "SELECT 1 + 1, 'hi', upper('a') || 'b';",
+            rows: vec!["2,hi,Ab"],
+        },
+        ClauseTest {
+            query: "SELECT 1 + 1 AS two, 'hi' greeting WHERE 1 = 1;",
+            rows: vec!["2,hi"],
+        },
+        ClauseTest {
+            query: "SELECT 1 AS a WHERE 1 = 2;",
+            rows: vec![],
+        },
+        ClauseTest {
+            query: "SELECT 'on' WHERE NULL;",
+            rows: vec![],
+        },
+        ClauseTest {
+            query: "SELECT 'has pets' WHERE EXISTS (SELECT 1 FROM pets WHERE owner = 1);",
+            rows: vec!["has pets"],
+        },
+        ClauseTest {
+            query: "SELECT 'has pets' WHERE EXISTS (SELECT 1 FROM pets WHERE owner = 9);",
+            rows: vec![],
+        },
+        ClauseTest {
+            query: "SELECT count(*) WHERE 1 = 2;",
+            rows: vec!["0"],
+        },
+    ];
+    for test in tests {
+        assert_eq!(rows(&mut mb, test.query), test.rows, "{}", test.query);
+    }
+
+    let columns = match mb.eval_query("SELECT 1, 2 AS b, now();").unwrap().pop() {
+        Some(EvalResult::Select { results, .. }) => results
+            .columns
+            .into_iter()
+            .map(|column| column.name)
+            .collect::<Vec<_>>(),
+        _ => panic!("Expected select results"),
+    };
+    assert_eq!(columns, vec!["?column?", "b", "now"]);
+
+    // There are no columns to read
+    for (query, code) in &[
+        ("SELECT name;", "42703"),
+        ("SELECT 1 WHERE age > 20;", "42703"),
+        ("SELECT 1, 2 AS two WHERE two = 2;", "42703"),
+        ("SELECT *;", "42601"),
+        ("SELECT 1, * WHERE 1 = 1;", "42601"),
+    ] {
+        assert_eq!(mb.eval_query(query).unwrap_err().code(), *code, "{}", query);
+    }
+}
+
+// ORDER BY compares values by their sortable encoding: numbers by value whatever their
+// width, text byte by byte, and NULLs last, or first when descending
+#[test]
+fn test_order_by_values() {
+    let mut mb = MemoryBackend::new();
+    mb.eval_query(
+        "CREATE TABLE points (name TEXT, x BIGINT, score DOUBLE PRECISION);
+        INSERT INTO points VALUES ('b', 3000000000, 0.5);
+        INSERT INTO points VALUES ('', -1, NULL);
+        INSERT INTO points VALUES (NULL, 7, -2.5);
+        INSERT INTO points VALUES ('B', NULL, 10);
+        INSERT INTO points VALUES ('ab', -3000000000, -0.25);",
+    )
+    .unwrap();
+
+    let tests = vec![
+        ClauseTest {
+            query: "SELECT x FROM points ORDER BY x;",
+            rows: vec!["-3000000000", "-1", "7", "3000000000", "NULL"],
+        },
+        ClauseTest {
+            query: "SELECT x FROM points ORDER BY x DESC;",
+            rows: vec!["NULL", "3000000000", "7", "-1", "-3000000000"],
+        },
+        ClauseTest {
+            query: "SELECT name FROM points ORDER BY name;",
+            rows: vec!["", "B", "ab", "b", "NULL"],
+        },
+        ClauseTest {
+            query: "SELECT score FROM points ORDER BY score;",
+            rows: vec!["-2.5", "-0.25", "0.5", "10", "NULL"],
+        },
+        // Literals are SMALLINT, INT or BIGINT by their size
+        ClauseTest {
+            query: "SELECT * FROM (VALUES (100000), (5), (-70000), (-3000000000)) AS v
+                ORDER BY column1;",
+            rows: vec!["-3000000000", "-70000", "5", "100000"],
+        },
+    ];
+
+    for test in tests {
+        assert_eq!(rows(&mut mb, test.query), test.rows, "{}", test.query);
+    }
+}
+
+// Rows whose ORDER BY values are equal keep the order they were read in, ascending or
+// descending, however many rows there are. Rows tied on the first of several ORDER BY
+// expressions go by the next.
+#[test]
+fn test_order_by_ties() {
+    let mut mb = MemoryBackend::new();
+    mb.eval_query(
+        "CREATE TABLE words (id INT, word TEXT);
+        INSERT INTO words VALUES (1, 'b'), (2, 'A'), (3, 'B'), (4, 'a'), (5, NULL), (6, 'b');",
+    )
+    .unwrap();
+    let tests = vec![
+        ClauseTest {
+            query: "SELECT id FROM words ORDER BY lower(word);",
+            rows: vec!["2", "4", "1", "3", "6", "5"],
+        },
+        ClauseTest {
+            query: "SELECT id FROM words ORDER BY lower(word) DESC;",
+            rows: vec!["5", "1", "3", "6", "2", "4"],
+        },
+        ClauseTest {
+            query: "SELECT id FROM words ORDER BY upper(word) = 'B' LIMIT 2 OFFSET 1;",
+            rows: vec!["4", "1"],
+        },
+        ClauseTest {
+            query: "SELECT id FROM words ORDER BY lower(word) DESC, word;",
+            rows: vec!["5", "3", "1", "6", "2", "4"],
+        },
+        ClauseTest {
+            query: "SELECT id FROM words ORDER BY lower(word), id DESC;",
+            rows: vec!["4", "2", "6", "3", "1", "5"],
+        },
+    ];
+    for test in tests {
+        assert_eq!(rows(&mut mb, test.query), test.rows, "{}", test.query);
+    }
+
+    let values: Vec<String> = (0..5000)
+        .map(|id| format!("({}, 'w{}')", id, (id * 31) % 7))
+        .collect();
+    mb.eval_query(&format!(
+        "DELETE FROM words; INSERT INTO words VALUES {};",
+        values.join(", ")
+    ))
+    .unwrap();
+    for query in &[
+        "SELECT word, id FROM words ORDER BY upper(word);",
+        "SELECT word, id FROM words ORDER BY upper(word) DESC;",
+        "SELECT word, id FROM words ORDER BY upper(word) DESC, word;",
+    ] {
+        let sorted = rows(&mut mb, query);
+        assert_eq!(sorted.len(), 5000);
+        for pair in sorted.windows(2) {
+            let (word, id) = pair[0].split_once(',').unwrap();
+            let (next_word, next_id) = pair[1].split_once(',').unwrap();
+            if word == next_word {
+                assert!(
+                    id.parse::<i32>().unwrap() < next_id.parse::<i32>().unwrap(),
+                    "{}: {:?}",
+                    query,
+                    pair
+                );
+            }
+        }
+    }
+}
+
+// ORDER BY a position or a name of a result column sorts by that column, a name going for
+// a result column before a column of the FROM items
+#[test]
+fn test_order_by_result_columns() {
+    let mut mb = backend();
+    let tests = vec![
+        ClauseTest {
+            query: "SELECT name, age % 10 AS k FROM people ORDER BY k, name;",
+            rows: vec!["Baam,0", "Khun,0", "Rachel,5", "Rak,5", "Endorsi,8"],
+        },
+        ClauseTest {
+            query: "SELECT name AS age, age AS years FROM people ORDER BY age;",
+            rows: vec!["Baam,20", "Endorsi,28", "Khun,30", "Rachel,25", "Rak,35"],
+        },
+        ClauseTest {
+            query: "SELECT id, name FROM people ORDER BY 2 DESC;",
+            rows: vec!["4,Rak", "2,Rachel", "3,Khun", "5,Endorsi", "1,Baam"],
+        },
+        ClauseTest {
+            query: "SELECT id, name FROM people ORDER BY 1 DESC;",
+            rows: vec!["5,Endorsi", "4,Rak", "3,Khun", "2,Rachel", "1,Baam"],
+        },
+        ClauseTest {
+            query: "SELECT * FROM pets ORDER BY 2 DESC LIMIT 2;",
+            rows: vec!["4,Yak", "3,Owl"],
+        },
+        ClauseTest {
+            query: "SELECT owner AS o, count(*) AS n FROM pets GROUP BY owner ORDER BY n DESC, o;",
+            rows: vec!["1,2", "3,2", "2,1", "4,1", "5,1"],
+        },
+        ClauseTest {
+            query: "SELECT owner, count(*) FROM pets GROUP BY owner ORDER BY 2, 1 DESC;",
+            rows: vec!["5,1", "4,1", "2,1", "3,2", "1,2"],
+        },
+    ];
+    for test in tests {
+        assert_eq!(rows(&mut mb, test.query), test.rows, "{}", test.query);
+    }
+
+    for query in &[
+        "SELECT id FROM people ORDER BY 2;",
+        "SELECT id, name FROM people ORDER BY 0;",
+        "SELECT owner, count(*) FROM pets GROUP BY owner ORDER BY 3;",
+    ] {
+        assert_eq!(
+            mb.eval_query(query).unwrap_err().code(),
+            "42P10",
+            "{}",
+            query
+        );
+    }
+}
+
+// The names of the columns after EXCEPT and REPLACE, which describing the query gives too.
+// What they list is tested in sqllogic/select_clauses.slt.
+#[test]
+fn test_select_asterisk_except_replace() {
+    let mut mb = backend();
+    // A replacement keeps the place and name of the column it replaces
+    let tests = vec![
+        (
+            "SELECT * EXCEPT (owner) REPLACE (age + 1 AS age) FROM people
+                JOIN pets ON id = owner;",
+            vec!["id", "name", "age", "pet"],
+        ),
+        (
+            "SELECT pets.* EXCEPT (owner), people.* REPLACE (age + 1 AS age) FROM people
+                JOIN pets ON id = owner;",
+            vec!["pet", "id", "name", "age"],
+        ),
+    ];
+    for (query, expected) in tests {
+        let columns = match mb.eval_query(query).unwrap().pop() {
+            Some(EvalResult::Select { results, .. }) => results
+                .columns
+                .into_iter()
+                .map(|column| column.name)
+                .collect::<Vec<_>>(),
+            _ => panic!("Expected select results"),
+        };
+        assert_eq!(columns, expected, "{}", query);
+        let described: Vec<String> = mb
+            .describe(query)
+            .unwrap()
+            .columns
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        assert_eq!(described, columns, "{}", query);
+    }
+
+    for (query, code) in &[
+        ("SELECT * EXCEPT (nope) FROM people;", "42703"),
+        (
+            "SELECT people.* EXCEPT (pet) FROM people JOIN pets ON id = owner;",
+            "42703",
+        ),
+        ("SELECT * REPLACE (1 AS nope) FROM people;", "42703"),
+        ("SELECT * EXCEPT (id, id) FROM people;", "42701"),
+    ] {
+        assert_eq!(mb.describe(query).unwrap_err().code(), *code, "{}", query);
+    }
+}
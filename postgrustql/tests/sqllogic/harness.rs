@@ -0,0 +1,396 @@
+// Golden file tests of SQL behavior, in a format like sqllogictest's. A file is a list of
+// records separated by blank lines, with `#` comment lines between them:
+//
+//     statement ok
+//     CREATE TABLE t (id INT, name TEXT);
+//
+//     statement error 42P01
+//     SELECT * FROM nope;
+//
+//     query IT rowsort
+//     SELECT id, name FROM t;
+//     ----
+//     1 a
+//     2 (empty)
+//
+// `statement ok` expects the SQL to run without an error. `statement error` and `query
+// error` expect an error, of any kind or one matching what follows: a SQLSTATE like
+// `23505`, else a piece of the message. `query` expects rows, its letters being the
+// types of the columns: I for integers, R for floating point numbers, B for booleans and
+// T for anything else. Rows are lines of values separated by spaces, with NULL for NULLs
+// and (empty) for empty strings. They are compared in order, or sorted first when the
+// letters are followed by `rowsort`.
+//
+// The SQL of a record can be several statements, what the last gives is what counts.
+// Blessing a file rewrites the records that didn't give what they expected with what
+// they gave instead.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+// A value of a row, which decides how it is written
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Text(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+impl ColumnType {
+    fn letter(self) -> char {
+        match self {
+            ColumnType::Integer => 'I',
+            ColumnType::Float => 'R',
+            ColumnType::Boolean => 'B',
+            ColumnType::Text => 'T',
+        }
+    }
+}
+
+// What a statement gave, either rows or nothing
+pub enum Outcome {
+    Done,
+    Rows {
+        types: Vec<ColumnType>,
+        rows: Vec<Vec<Value>>,
+    },
+}
+
+pub struct Failure {
+    pub code: String,
+    pub message: String,
+}
+
+// A database the files can run against, a fresh one for every file
+pub trait Database {
+    fn run(&mut self, sql: &str) -> Result<Outcome, Failure>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ErrorMatch {
+    Any,
+    Code(String),
+    Message(String),
+}
+
+impl ErrorMatch {
+    fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let is_code = text.len() == 5
+            && text[..2].chars().all(|c| c.is_ascii_digit())
+            && text
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase());
+        match text {
+            "" => ErrorMatch::Any,
+            _ if is_code => ErrorMatch::Code(text.to_string()),
+            _ => ErrorMatch::Message(text.to_string()),
+        }
+    }
+
+    fn matches(&self, failure: &Failure) -> bool {
+        match self {
+            ErrorMatch::Any => true,
+            ErrorMatch::Code(code) => *code == failure.code,
+            ErrorMatch::Message(message) => failure.message.contains(message.as_str()),
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        match self {
+            ErrorMatch::Any => {}
+            ErrorMatch::Code(text) | ErrorMatch::Message(text) => {
+                let _ = write!(out, " {}", text);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expected {
+    Ok,
+    Error {
+        query: bool,
+        error: ErrorMatch,
+    },
+    Rows {
+        types: String,
+        rowsort: bool,
+        lines: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Record {
+    // Where its header is, counted from 1
+    line: usize,
+    sql: Vec<String>,
+    expected: Expected,
+}
+
+impl Record {
+    fn write(&self, out: &mut String) {
+        match &self.expected {
+            Expected::Ok => out.push_str("statement ok"),
+            Expected::Error { query, error } => {
+                out.push_str(if *query {
+                    "query error"
+                } else {
+                    "statement error"
+                });
+                error.write(out);
+            }
+            Expected::Rows { types, rowsort, .. } => {
+                let _ = write!(out, "query {}", types);
+                if *rowsort {
+                    out.push_str(" rowsort");
+                }
+            }
+        }
+        out.push('\n');
+        for line in &self.sql {
+            out.push_str(line);
+            out.push('\n');
+        }
+        if let Expected::Rows { lines, .. } = &self.expected {
+            out.push_str("----\n");
+            for line in lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+// The lines of a file, records along with the comments and blank lines around them
+enum Item {
+    Line(String),
+    Record(Record),
+}
+
+fn parse(path: &Path, text: &str) -> Result<Vec<Item>, String> {
+    let mut items = vec![];
+    let mut lines = text.lines().enumerate().peekable();
+    while let Some((idx, line)) = lines.next() {
+        let mut words = line.split_whitespace();
+        let kind = words.next();
+        if kind != Some("statement") && kind != Some("query") {
+            items.push(Item::Line(line.to_string()));
+            continue;
+        }
+        let at = format!("{}:{}", path.display(), idx + 1);
+        let rest: Vec<&str> = words.collect();
+        let mut expected = match (kind, rest.as_slice()) {
+            (Some("statement"), ["ok"]) => Expected::Ok,
+            (_, ["error", ..]) => Expected::Error {
+                query: kind == Some("query"),
+                error: ErrorMatch::parse(line.splitn(3, ' ').nth(2).unwrap_or_default()),
+            },
+            (Some("query"), [types]) | (Some("query"), [types, "rowsort"]) => Expected::Rows {
+                types: types.to_string(),
+                rowsort: rest.len() == 2,
+                lines: vec![],
+            },
+            _ => return Err(format!("{}: Invalid record header {:?}", at, line)),
+        };
+
+        let mut sql = vec![];
+        let mut results: Option<Vec<String>> = None;
+        while let Some((_, line)) = lines.next_if(|(_, line)| !line.trim().is_empty()) {
+            match &mut results {
+                Some(results) => results.push(line.to_string()),
+                None if line == "----" => results = Some(vec![]),
+                None => sql.push(line.to_string()),
+            }
+        }
+        if sql.is_empty() {
+            return Err(format!("{}: Record without SQL", at));
+        }
+        match (&mut expected, results) {
+            (Expected::Rows { lines, .. }, Some(results)) => *lines = results,
+            (Expected::Rows { .. }, None) => {}
+            (_, Some(_)) => return Err(format!("{}: Only queries have results", at)),
+            (_, None) => {}
+        }
+        items.push(Item::Record(Record {
+            line: idx + 1,
+            sql,
+            expected,
+        }));
+    }
+    Ok(items)
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Float(value) => format!("{:.3}", value),
+        Value::Boolean(value) => value.to_string(),
+        Value::Text(value) if value.is_empty() => "(empty)".to_string(),
+        Value::Text(value) => value.clone(),
+    }
+}
+
+// What the record would expect if it expected what it gave
+fn actual(record: &Record, outcome: &Result<Outcome, Failure>) -> Expected {
+    match (outcome, &record.expected) {
+        (Err(failure), Expected::Error { query, error }) => Expected::Error {
+            query: *query,
+            error: match error.matches(failure) {
+                true => error.clone(),
+                false => ErrorMatch::Code(failure.code.clone()),
+            },
+        },
+        (Err(failure), expected) => Expected::Error {
+            query: matches!(expected, Expected::Rows { .. }),
+            error: ErrorMatch::Code(failure.code.clone()),
+        },
+        (Ok(Outcome::Rows { types, rows }), Expected::Rows { rowsort, .. }) => {
+            expected_rows(types, rows, *rowsort)
+        }
+        (Ok(Outcome::Rows { types, rows }), Expected::Error { query: true, .. }) => {
+            expected_rows(types, rows, false)
+        }
+        (Ok(_), _) => Expected::Ok,
+    }
+}
+
+fn expected_rows(types: &[ColumnType], rows: &[Vec<Value>], rowsort: bool) -> Expected {
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|row| row.iter().map(format_value).collect::<Vec<_>>().join(" "))
+        .collect();
+    if rowsort {
+        lines.sort();
+    }
+    Expected::Rows {
+        types: types.iter().map(|typ| typ.letter()).collect(),
+        rowsort,
+        lines,
+    }
+}
+
+fn expected_matches(expected: &Expected, actual: &Expected) -> bool {
+    match (expected, actual) {
+        (
+            Expected::Rows {
+                types,
+                rowsort: true,
+                lines,
+            },
+            Expected::Rows {
+                types: actual_types,
+                lines: actual_lines,
+                ..
+            },
+        ) => {
+            let mut lines = lines.clone();
+            lines.sort();
+            *types == *actual_types && lines == *actual_lines
+        }
+        _ => expected == actual,
+    }
+}
+
+// The lines of `old` and `new` with `-` before those only in the first and `+` before
+// those only in the second, by their longest common subsequence
+fn diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            let _ = writeln!(out, "  {}", old[i]);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            let _ = writeln!(out, "- {}", old[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", new[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+// How running a file went
+#[derive(Debug, Default)]
+pub struct Report {
+    pub records: usize,
+    // A diff of each record that didn't give what it expected
+    pub failures: Vec<String>,
+    pub blessed: bool,
+}
+
+// Runs the records of the file at `path` in order. When blessing, the file is rewritten
+// with what the records that failed gave.
+pub fn run_file<D: Database>(db: &mut D, path: &Path, bless: bool) -> Result<Report, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut items = parse(path, &text)?;
+    let mut report = Report::default();
+    for item in items.iter_mut() {
+        let record = match item {
+            Item::Record(record) => record,
+            Item::Line(_) => continue,
+        };
+        report.records += 1;
+        let outcome = db.run(&record.sql.join("\n"));
+        let actual = actual(record, &outcome);
+        if expected_matches(&record.expected, &actual) {
+            continue;
+        }
+        let mut expected_text = String::new();
+        record.write(&mut expected_text);
+        let mut blessed = record.clone();
+        blessed.expected = actual;
+        let mut actual_text = String::new();
+        blessed.write(&mut actual_text);
+        let mut failure = format!("{}:{}\n", path.display(), record.line);
+        if let Err(error) = &outcome {
+            let _ = writeln!(failure, "error {}: {}", error.code, error.message);
+        }
+        failure.push_str(&diff(&expected_text, &actual_text));
+        report.failures.push(failure);
+        if bless {
+            *record = blessed;
+        }
+    }
+
+    if bless && !report.failures.is_empty() {
+        let mut out = String::with_capacity(text.len());
+        for item in &items {
+            match item {
+                Item::Line(line) => {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                Item::Record(record) => record.write(&mut out),
+            }
+        }
+        std::fs::write(path, out).map_err(|err| err.to_string())?;
+        report.blessed = true;
+    }
+    Ok(report)
+}
@@ -0,0 +1,105 @@
+// Runs the golden files next to this one against a fresh `MemoryBackend` each, see
+// `harness` for their format. Arguments that aren't flags keep only the files whose name
+// has one of them in it, and `--bless` rewrites the expectations the files get wrong:
+//
+//     cargo test --test sqllogic -- joins --bless
+
+mod harness;
+
+use harness::{ColumnType, Database, Failure, Outcome, Value};
+use postgrustql::backend::{BackendError, EvalResult};
+use postgrustql::backend_memory::MemoryBackend;
+use postgrustql::sql_types::{SqlNumeric, SqlType, SqlValue};
+use std::path::Path;
+
+impl Database for MemoryBackend {
+    fn run(&mut self, sql: &str) -> Result<Outcome, Failure> {
+        let results = self.eval_query(sql).map_err(|err: BackendError| Failure {
+            code: err.code().to_string(),
+            message: err.message().to_string(),
+        })?;
+        Ok(match results.into_iter().last() {
+            Some(EvalResult::Select { results, .. }) => Outcome::Rows {
+                types: results
+                    .columns
+                    .iter()
+                    .map(|column| column_type(column.col_type))
+                    .collect(),
+                rows: results
+                    .rows
+                    .into_iter()
+                    .map(|row| row.into_iter().map(value).collect())
+                    .collect(),
+            },
+            _ => Outcome::Done,
+        })
+    }
+}
+
+fn column_type(typ: SqlType) -> ColumnType {
+    match typ {
+        SqlType::SmallInt | SqlType::Int | SqlType::BigInt => ColumnType::Integer,
+        SqlType::Real | SqlType::DoublePrecision => ColumnType::Float,
+        SqlType::Boolean => ColumnType::Boolean,
+        _ => ColumnType::Text,
+    }
+}
+
+fn value(value: SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Boolean(value) => Value::Boolean(value),
+        SqlValue::Numeric(SqlNumeric::SmallInt { value }) => Value::Integer(value.into()),
+        SqlValue::Numeric(SqlNumeric::Int { value }) => Value::Integer(value.into()),
+        SqlValue::Numeric(SqlNumeric::BigInt { value }) => Value::Integer(value),
+        SqlValue::Numeric(SqlNumeric::Real { value }) => Value::Float(value.into()),
+        SqlValue::Numeric(SqlNumeric::DoublePrecision { value }) => Value::Float(value),
+        value => Value::Text(value.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sqllogic");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .expect("The golden file directory can't be read")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "slt"))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str()))
+        })
+        .collect();
+    paths.sort();
+
+    let mut failed = false;
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match harness::run_file(&mut MemoryBackend::new(), path, bless) {
+            Ok(report) => {
+                for failure in &report.failures {
+                    println!("{}", failure);
+                }
+                let status = match (report.failures.len(), report.blessed) {
+                    (0, _) => "ok".to_string(),
+                    (count, true) => format!("blessed {}", count),
+                    (count, false) => {
+                        failed = true;
+                        format!("FAILED {}", count)
+                    }
+                };
+                println!("{}: {} records, {}", name, report.records, status);
+            }
+            Err(err) => {
+                failed = true;
+                println!("{}: {}", name, err);
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
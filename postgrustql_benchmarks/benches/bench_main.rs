@@ -22,6 +22,28 @@ fn lex_select_benchmark(c: &mut Criterion) {
         SELECT id, age, role, job, position, country, address from people WHERE country = 'GR' AND age > 17 INNER LEFT JOIN ON jobs".to_owned().as_str()))));
 }
 
+fn lex_dump_benchmark(c: &mut Criterion) {
+    // A 5MB dump, the kind of script that gets loaded in one go
+    let mut dump =
+        String::from("CREATE TABLE people (id INT PRIMARY KEY, name TEXT, age SMALLINT);\n");
+    let mut i = 0;
+    while dump.len() < 5 * 1024 * 1024 {
+        dump.push_str(&format!(
+            "INSERT INTO people VALUES ({}, 'Person number {}', {});\n",
+            i,
+            i,
+            i % 90
+        ));
+        i += 1;
+    }
+
+    let lexer = lexer::Lexer::new();
+    let mut group = c.benchmark_group("lex_dump");
+    group.sample_size(10);
+    group.bench_function("lex_5mb", |b| b.iter(|| lexer.lex(black_box(&dump))));
+    group.finish();
+}
+
 fn parse_benchmark(c: &mut Criterion) {
     c.bench_function("parse", |b| b.iter(|| parser::parse(black_box("
     CREATE TABLE people (id INT PRIMARY KEY, name TEXT); INSERT INTO people VALUES (1, 'Baam'); INSERT INTO people VALUES (2, 'Rachel'); INSERT INTO people VALUES (3, 'Rak WraithKaiser'); INSERT INTO people VALUES (4, 'Khun Aguero Agnes');
@@ -89,6 +111,50 @@ fn single_insert_benchmark(c: &mut Criterion) {
     );
 }
 
+fn execute_many_benchmark(c: &mut Criterion) {
+    let rows = 10000;
+    let mut group = c.benchmark_group("execute_many");
+    group.sample_size(10);
+
+    group.bench_function("naive_loop_10000", |b| {
+        b.iter(|| {
+            let mut db = backend_memory::MemoryBackend::new();
+            db.eval_query("CREATE TABLE people (id INT PRIMARY KEY, name TEXT);")
+                .unwrap();
+            for i in 0..rows {
+                db.eval_query(black_box(
+                    format!("INSERT INTO people VALUES ({}, 'Baam{}');", i, i).as_str(),
+                ))
+                .unwrap();
+            }
+        })
+    });
+
+    group.bench_function("execute_many_10000", |b| {
+        b.iter(|| {
+            let mut db = backend_memory::MemoryBackend::new();
+            db.eval_query("CREATE TABLE people (id INT PRIMARY KEY, name TEXT);")
+                .unwrap();
+            let insert = db.prepare("INSERT INTO people VALUES ($1, $2);").unwrap();
+            insert
+                .execute_many(
+                    &mut db,
+                    (0..rows).map(|i| {
+                        vec![
+                            sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i }),
+                            sql_types::SqlValue::Text(sql_types::SqlText::Text {
+                                value: format!("Baam{}", i),
+                            }),
+                        ]
+                    }),
+                )
+                .unwrap();
+        })
+    });
+
+    group.finish();
+}
+
 fn select_benchmark(c: &mut Criterion) {
     let mut db = backend_memory::MemoryBackend::new();
     db.eval_query(
@@ -117,6 +183,7 @@ fn select_benchmark(c: &mut Criterion) {
         backend::EvalResult::Select {
             results: _select_results,
             time: _time,
+            ..
         } => {}
         _ => {
             eprintln!("Derp..");
@@ -146,6 +213,246 @@ fn select_benchmark(c: &mut Criterion) {
     );
 }
 
+fn where_filter_benchmark(c: &mut Criterion) {
+    let mut db = backend_memory::MemoryBackend::new();
+    db.eval_query("CREATE TABLE people (id INT, name TEXT, age INT, score DOUBLE PRECISION);")
+        .unwrap();
+    let insert = db
+        .prepare("INSERT INTO people VALUES ($1, $2, $3, $4);")
+        .unwrap();
+    insert
+        .execute_many(
+            &mut db,
+            (0..1000000).map(|i| {
+                vec![
+                    sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i }),
+                    sql_types::SqlValue::Text(sql_types::SqlText::Text {
+                        value: format!("Baam{}", i),
+                    }),
+                    sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i % 90 }),
+                    sql_types::SqlValue::Numeric(sql_types::SqlNumeric::DoublePrecision {
+                        value: (i % 1000) as f64 / 10.0,
+                    }),
+                ]
+            }),
+        )
+        .unwrap();
+
+    let mut group = c.benchmark_group("where_filter");
+    group.sample_size(10);
+    group.bench_function("five_terms_1000000", |b| {
+        b.iter(|| {
+            db.eval_query(black_box(
+                "SELECT id FROM people WHERE age >= 18 AND age < 65 AND score > 25.5 \
+                 AND name <> 'Baam7' AND id % 3 = 0;",
+            ))
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn order_by_expression_benchmark(c: &mut Criterion) {
+    let mut db = backend_memory::MemoryBackend::new();
+    db.eval_query("CREATE TABLE people (id INT, name TEXT);")
+        .unwrap();
+    let insert = db.prepare("INSERT INTO people VALUES ($1, $2);").unwrap();
+    insert
+        .execute_many(
+            &mut db,
+            (0..500000).map(|i| {
+                // Out of order, half of them capitalized, with every key there twice
+                let initial = if i % 2 == 0 { "B" } else { "b" };
+                vec![
+                    sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i }),
+                    sql_types::SqlValue::Text(sql_types::SqlText::Text {
+                        value: format!("{}aam{}", initial, i % 250000 * 7919 % 250000),
+                    }),
+                ]
+            }),
+        )
+        .unwrap();
+
+    // The key of each row is worked out once, before the rows are sorted
+    let mut group = c.benchmark_group("order_by_expression");
+    group.sample_size(10);
+    group.bench_function("lower_500000", |b| {
+        b.iter(|| {
+            db.eval_query(black_box(
+                "SELECT id FROM people ORDER BY lower(name) LIMIT 10;",
+            ))
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn update_benchmark(c: &mut Criterion) {
+    // A third of the rows change, each taking its index entries out by key and back in
+    let mut group = c.benchmark_group("update");
+    group.sample_size(10);
+    for (name, index) in &[("indexed", true), ("unindexed", false)] {
+        let mut db = backend_memory::MemoryBackend::new();
+        db.eval_query(
+            "CREATE TABLE people (id INT, age INT);
+            INSERT INTO people SELECT n, n % 90 FROM generate_series(1, 50000) AS g(n);",
+        )
+        .unwrap();
+        if *index {
+            db.eval_query("CREATE INDEX people_age ON people (age);")
+                .unwrap();
+        }
+        group.bench_function(format!("{}_16667_of_50000", name), |b| {
+            b.iter(|| {
+                db.eval_query(black_box(
+                    "UPDATE people SET age = age + 1 WHERE id % 3 = 0;",
+                ))
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn column_pruning_benchmark(c: &mut Criterion) {
+    // A 40 column table of which queries read 2 columns
+    let columns: Vec<String> = (0..40).map(|i| format!("c{}", i)).collect();
+    let mut db = backend_memory::MemoryBackend::new();
+    db.eval_query(&format!(
+        "CREATE TABLE wide ({} INT);
+        CREATE TABLE tags (tag_id INT, label TEXT);",
+        columns.join(" INT, ")
+    ))
+    .unwrap();
+    let params: Vec<String> = (1..=40).map(|i| format!("${}", i)).collect();
+    let insert = db
+        .prepare(&format!("INSERT INTO wide VALUES ({});", params.join(", ")))
+        .unwrap();
+    insert
+        .execute_many(
+            &mut db,
+            (0..2000).map(|i| {
+                (0..40)
+                    .map(|j| {
+                        sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int {
+                            value: (i * 40 + j) % 100,
+                        })
+                    })
+                    .collect()
+            }),
+        )
+        .unwrap();
+    for i in 0..100 {
+        db.eval_query(&format!("INSERT INTO tags VALUES ({}, 'tag{}');", i, i))
+            .unwrap();
+    }
+
+    let mut group = c.benchmark_group("column_pruning");
+    group.sample_size(10);
+    group.bench_function("select_2_of_40_2000", |b| {
+        b.iter(|| {
+            db.eval_query(black_box("SELECT c0, c1 FROM wide;"))
+                .unwrap()
+        })
+    });
+    group.bench_function("join_select_2_of_40_2000x100", |b| {
+        b.iter(|| {
+            db.eval_query(black_box(
+                "SELECT c0, label FROM wide INNER JOIN tags ON c1 = tag_id;",
+            ))
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn statement_cache_benchmark(c: &mut Criterion) {
+    let mut db = backend_memory::MemoryBackend::new();
+    db.eval_query(
+        "CREATE TABLE people (id INT, name TEXT, age INT);
+        INSERT INTO people VALUES (1, 'Baam', 17);
+        INSERT INTO people VALUES (2, 'Rachel', 19);",
+    )
+    .unwrap();
+    let query = "SELECT id, name, age FROM people WHERE age > 18 AND name <> 'Khun' ORDER BY id;";
+
+    let mut group = c.benchmark_group("statement_cache");
+    group.sample_size(10);
+    for capacity in &[backend_memory::DEFAULT_STATEMENT_CACHE_CAPACITY, 0] {
+        db.set_statement_cache_capacity(*capacity);
+        let name = if *capacity == 0 { "uncached" } else { "cached" };
+        group.bench_function(format!("{}_select_100000", name), |b| {
+            b.iter(|| {
+                for _ in 0..100000 {
+                    db.eval_query(black_box(query)).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn semi_join_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("semi_join");
+    group.sample_size(10);
+    // Run for every outer row the subquery takes time quadratic in the rows, hashed
+    // it takes linear time
+    for size in &[500, 2000] {
+        let mut db = backend_memory::MemoryBackend::new();
+        db.eval_query(
+            "CREATE TABLE orders (id INT, customer INT); CREATE TABLE customers (id INT);",
+        )
+        .unwrap();
+        let insert = db.prepare("INSERT INTO orders VALUES ($1, $2);").unwrap();
+        insert
+            .execute_many(
+                &mut db,
+                (0..*size).map(|i| {
+                    vec![
+                        sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i }),
+                        sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int { value: i * 2 }),
+                    ]
+                }),
+            )
+            .unwrap();
+        let insert = db.prepare("INSERT INTO customers VALUES ($1);").unwrap();
+        insert
+            .execute_many(
+                &mut db,
+                (0..*size).map(|i| {
+                    vec![sql_types::SqlValue::Numeric(sql_types::SqlNumeric::Int {
+                        value: i,
+                    })]
+                }),
+            )
+            .unwrap();
+
+        for hashed in &["on", "off"] {
+            db.eval_query(&format!("SET hash_semi_joins = {};", hashed))
+                .unwrap();
+            let name = if *hashed == "on" { "hashed" } else { "per_row" };
+            group.bench_function(format!("exists_{}_{}", name, size), |b| {
+                b.iter(|| {
+                    db.eval_query(black_box(
+                        "SELECT id FROM orders WHERE EXISTS \
+                         (SELECT 1 FROM customers WHERE customers.id = orders.customer);",
+                    ))
+                    .unwrap()
+                })
+            });
+            group.bench_function(format!("not_in_{}_{}", name, size), |b| {
+                b.iter(|| {
+                    db.eval_query(black_box(
+                        "SELECT id FROM orders WHERE customer NOT IN (SELECT id FROM customers);",
+                    ))
+                    .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
 pub fn million_row_benchmark(_c: &mut Criterion) {
     println!("Million Row Benchmark");
 
@@ -251,12 +558,20 @@ criterion_group!(
     benches,
     lex_benchmark,
     lex_select_benchmark,
+    lex_dump_benchmark,
     parse_benchmark,
     // parse_select_benchmark,
     create_benchmark,
     single_insert_benchmark,
     insert_benchmark,
+    execute_many_benchmark,
     select_benchmark,
+    where_filter_benchmark,
+    order_by_expression_benchmark,
+    update_benchmark,
+    column_pruning_benchmark,
+    statement_cache_benchmark,
+    semi_join_benchmark,
     million_row_benchmark,
 );
 criterion_main!(benches);
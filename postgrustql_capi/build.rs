@@ -0,0 +1,24 @@
+fn main() {
+    // Exposed so the C API integration test can drive `cc` outside of a build script
+    println!(
+        "cargo:rustc-env=PGRQL_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+
+    generate_c_header();
+}
+
+fn generate_c_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Invalid cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/lib.rs", crate_dir))
+        .generate()
+        .expect("Failed to generate C header")
+        .write_to_file(format!("{}/include/postgrustql.h", crate_dir));
+}
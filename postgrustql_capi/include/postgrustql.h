@@ -0,0 +1,210 @@
+/*
+ * PostgRustQL C API.
+ *
+ * Memory ownership:
+ *  - pgrql_open() returns a handle owned by the caller, release it with pgrql_close().
+ *  - pgrql_exec() returns a result owned by the caller, release it with
+ *    pgrql_finalize_result(). Results stay valid after their database is closed.
+ *  - Every string returned by the library is owned by the handle or result it came
+ *    from. It stays valid until that object is released (pgrql_errmsg() and
+ *    pgrql_errcode() strings only until the next call on the same handle) and must
+ *    never be freed by the caller.
+ */
+
+#ifndef POSTGRUSTQL_H
+#define POSTGRUSTQL_H
+
+/* Generated by cbindgen from postgrustql_capi, do not edit by hand. */
+
+#include <stdint.h>
+#include <stddef.h>
+
+/**
+ * Call completed successfully.
+ */
+#define PGRQL_OK 0
+
+/**
+ * The statement failed, the message and SQLSTATE code are available through `pgrql_errmsg`
+ * and `pgrql_errcode`.
+ */
+#define PGRQL_ERROR 1
+
+/**
+ * The library was called with invalid arguments (null handles or SQL text).
+ */
+#define PGRQL_MISUSE 2
+
+/**
+ * A row or column index was out of range.
+ */
+#define PGRQL_RANGE 3
+
+/**
+ * Column types reported by `pgrql_column_type`.
+ */
+typedef enum PgrqlType {
+  PGRQL_NULL = 0,
+  PGRQL_SMALL_INT = 1,
+  PGRQL_INT = 2,
+  PGRQL_BIG_INT = 3,
+  PGRQL_REAL = 4,
+  PGRQL_DOUBLE_PRECISION = 5,
+  PGRQL_TEXT = 6,
+  PGRQL_CHAR = 7,
+  PGRQL_VAR_CHAR = 8,
+  PGRQL_BOOLEAN = 9,
+  PGRQL_TYPE = 10,
+} PgrqlType;
+
+/**
+ * Opaque database handle.
+ */
+typedef struct PgrqlDb PgrqlDb;
+
+/**
+ * Opaque result handle, holding the rows of the last statement executed by `pgrql_exec`.
+ */
+typedef struct PgrqlResult PgrqlResult;
+
+/**
+ * Opens a new, empty in-memory database.
+ *
+ * The returned handle is owned by the caller and must be released with `pgrql_close`.
+ */
+struct PgrqlDb *pgrql_open(void);
+
+/**
+ * Closes a database handle previously returned by `pgrql_open`.
+ *
+ * Results obtained from the handle remain valid and must still be finalized.
+ * Passing NULL is a no-op.
+ *
+ * # Safety
+ * `db` must be NULL or a handle returned by `pgrql_open` that was not closed yet.
+ */
+void pgrql_close(struct PgrqlDb *db);
+
+/**
+ * Executes one or more semicolon separated statements.
+ *
+ * On success, if `out_result` is not NULL it receives a result holding the rows of the
+ * last statement (statements that return no rows produce an empty result). The result
+ * is owned by the caller and must be released with `pgrql_finalize_result`.
+ * On failure `*out_result` is set to NULL and the message is available via `pgrql_errmsg`.
+ *
+ * # Safety
+ * `db` must be a live handle, `sql` a NUL terminated string and `out_result` NULL or a
+ * valid pointer to writable memory.
+ */
+int pgrql_exec(struct PgrqlDb *db, const char *sql, struct PgrqlResult **out_result);
+
+/**
+ * Returns the message of the last failed call on `db`, or NULL if it succeeded.
+ *
+ * The string is owned by the handle and valid until the next call using it.
+ *
+ * # Safety
+ * `db` must be NULL or a live handle.
+ */
+const char *pgrql_errmsg(const struct PgrqlDb *db);
+
+/**
+ * Returns the five character SQLSTATE code of the last failed call on `db`, following
+ * the codes Postgres uses (e.g. "42P01" for an undefined table), or NULL if it succeeded.
+ *
+ * The string is owned by the handle and valid until the next call using it.
+ *
+ * # Safety
+ * `db` must be NULL or a live handle.
+ */
+const char *pgrql_errcode(const struct PgrqlDb *db);
+
+/**
+ * Returns the status of the transaction BEGIN started on `db`, as the byte Postgres sends
+ * in ReadyForQuery: 'I' when there is none, 'T' while it runs and 'E' once it failed, after
+ * which statements fail until ROLLBACK. Returns 0 for NULL.
+ *
+ * # Safety
+ * `db` must be NULL or a live handle.
+ */
+char pgrql_transaction_status(const struct PgrqlDb *db);
+
+/**
+ * Releases a result returned by `pgrql_exec`, invalidating every string obtained from it.
+ * Passing NULL is a no-op.
+ *
+ * # Safety
+ * `result` must be NULL or a result that was not finalized yet.
+ */
+void pgrql_finalize_result(struct PgrqlResult *result);
+
+/**
+ * Number of columns in the result, or -1 if `result` is NULL.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+int pgrql_column_count(const struct PgrqlResult *result);
+
+/**
+ * Number of rows in the result, or -1 if `result` is NULL.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+int64_t pgrql_row_count(const struct PgrqlResult *result);
+
+/**
+ * Name of the column at `col`, or NULL if out of range. Owned by the result.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+const char *pgrql_column_name(const struct PgrqlResult *result, int col);
+
+/**
+ * Declared type of the column at `col`, `PGRQL_NULL` if out of range.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+enum PgrqlType pgrql_column_type(const struct PgrqlResult *result, int col);
+
+/**
+ * Returns 1 if the cell is NULL, 0 if it is not and `PGRQL_RANGE` when out of range.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+int pgrql_value_is_null(const struct PgrqlResult *result, int row, int col);
+
+/**
+ * Cell value converted to a 64 bit integer. NULL, out of range cells and values that
+ * cannot be converted yield 0.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+int64_t pgrql_value_int64(const struct PgrqlResult *result, int row, int col);
+
+/**
+ * Cell value converted to a double. NULL, out of range cells and values that cannot be
+ * converted yield 0.0.
+ *
+ * # Safety
+ * `result` must be NULL or a live result.
+ */
+double pgrql_value_double(const struct PgrqlResult *result, int row, int col);
+
+/**
+ * Text rendition of a cell, NUL terminated. The byte length (without the terminator) is
+ * written to `out_len` when it is not NULL, as text may contain embedded NUL bytes.
+ * Returns NULL for NULL or out of range cells. The string is owned by the result.
+ *
+ * # Safety
+ * `result` must be NULL or a live result, `out_len` NULL or valid for writes.
+ */
+const char *pgrql_value_text(const struct PgrqlResult *result, int row, int col, size_t *out_len);
+
+#endif /* POSTGRUSTQL_H */
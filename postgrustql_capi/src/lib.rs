@@ -0,0 +1,426 @@
+// C API over the memory backend, modelled after the sqlite3 interface.
+//
+// Ownership rules, also exported to the generated header:
+// - `pgrql_open` returns a handle that must be released with `pgrql_close`.
+// - `pgrql_exec` hands out a result that must be released with `pgrql_finalize_result`.
+// - Strings returned by the library are owned by the handle or result they came from and
+//   stay valid until that handle/result is released (or, for `pgrql_errmsg` and
+//   `pgrql_errcode`, until the next call on the same database handle). Callers must never
+//   free them.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use postgrustql::backend::{BackendError, EvalResult, QueryResults};
+use postgrustql::backend_memory::MemoryBackend;
+use postgrustql::sql_types::{check_text, SqlType, SqlValue};
+
+/// Call completed successfully.
+pub const PGRQL_OK: c_int = 0;
+/// The statement failed, the message and SQLSTATE code are available through `pgrql_errmsg`
+/// and `pgrql_errcode`.
+pub const PGRQL_ERROR: c_int = 1;
+/// The library was called with invalid arguments (null handles or SQL text).
+pub const PGRQL_MISUSE: c_int = 2;
+/// A row or column index was out of range.
+pub const PGRQL_RANGE: c_int = 3;
+
+/// Column types reported by `pgrql_column_type`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PgrqlType {
+    PgrqlNull = 0,
+    PgrqlSmallInt = 1,
+    PgrqlInt = 2,
+    PgrqlBigInt = 3,
+    PgrqlReal = 4,
+    PgrqlDoublePrecision = 5,
+    PgrqlText = 6,
+    PgrqlChar = 7,
+    PgrqlVarChar = 8,
+    PgrqlBoolean = 9,
+    PgrqlType = 10,
+}
+
+impl From<SqlType> for PgrqlType {
+    fn from(typ: SqlType) -> Self {
+        match typ {
+            SqlType::Null => PgrqlType::PgrqlNull,
+            SqlType::SmallInt => PgrqlType::PgrqlSmallInt,
+            SqlType::Int => PgrqlType::PgrqlInt,
+            SqlType::BigInt => PgrqlType::PgrqlBigInt,
+            SqlType::Real => PgrqlType::PgrqlReal,
+            SqlType::DoublePrecision => PgrqlType::PgrqlDoublePrecision,
+            SqlType::Text => PgrqlType::PgrqlText,
+            SqlType::Char => PgrqlType::PgrqlChar,
+            SqlType::VarChar => PgrqlType::PgrqlVarChar,
+            SqlType::Boolean => PgrqlType::PgrqlBoolean,
+            SqlType::Type => PgrqlType::PgrqlType,
+        }
+    }
+}
+
+/// Opaque database handle.
+pub struct PgrqlDb {
+    backend: MemoryBackend,
+    last_error: Option<LastError>,
+}
+
+// The SQLSTATE code and message of the last failed call on a handle
+struct LastError {
+    code: CString,
+    msg: CString,
+}
+
+/// Opaque result handle, holding the rows of the last statement executed by `pgrql_exec`.
+pub struct PgrqlResult {
+    results: QueryResults<SqlValue>,
+    column_names: Vec<CString>,
+    // Text renditions are created lazily, each cell keeps a NUL terminated copy so the
+    // pointer handed out stays valid for the lifetime of the result.
+    texts: RefCell<Vec<Option<Vec<u8>>>>,
+}
+
+impl PgrqlResult {
+    fn new(results: QueryResults<SqlValue>) -> Self {
+        let column_names = results
+            .columns
+            .iter()
+            .map(|col| {
+                CString::new(col.name.clone()).unwrap_or_else(|_| CString::new("?").unwrap())
+            })
+            .collect();
+        let cells = results.rows.len() * results.columns.len();
+        PgrqlResult {
+            results,
+            column_names,
+            texts: RefCell::new(vec![None; cells]),
+        }
+    }
+
+    fn value(&self, row: c_int, col: c_int) -> Option<&SqlValue> {
+        if row < 0 || col < 0 {
+            return None;
+        }
+        self.results.rows.get(row as usize)?.get(col as usize)
+    }
+}
+
+fn set_error(db: &mut PgrqlDb, err: BackendError) {
+    db.last_error = Some(LastError {
+        code: CString::new(err.code()).unwrap_or_default(),
+        msg: CString::new(err.message().replace('\0', "")).unwrap_or_default(),
+    });
+}
+
+/// Opens a new, empty in-memory database.
+///
+/// The returned handle is owned by the caller and must be released with `pgrql_close`.
+#[no_mangle]
+pub extern "C" fn pgrql_open() -> *mut PgrqlDb {
+    Box::into_raw(Box::new(PgrqlDb {
+        backend: MemoryBackend::new(),
+        last_error: None,
+    }))
+}
+
+/// Closes a database handle previously returned by `pgrql_open`.
+///
+/// Results obtained from the handle remain valid and must still be finalized.
+/// Passing NULL is a no-op.
+///
+/// # Safety
+/// `db` must be NULL or a handle returned by `pgrql_open` that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_close(db: *mut PgrqlDb) {
+    if !db.is_null() {
+        drop(Box::from_raw(db));
+    }
+}
+
+/// Executes one or more semicolon separated statements.
+///
+/// On success, if `out_result` is not NULL it receives a result holding the rows of the
+/// last statement (statements that return no rows produce an empty result). The result
+/// is owned by the caller and must be released with `pgrql_finalize_result`.
+/// On failure `*out_result` is set to NULL and the message is available via `pgrql_errmsg`.
+///
+/// # Safety
+/// `db` must be a live handle, `sql` a NUL terminated string and `out_result` NULL or a
+/// valid pointer to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_exec(
+    db: *mut PgrqlDb,
+    sql: *const c_char,
+    out_result: *mut *mut PgrqlResult,
+) -> c_int {
+    if !out_result.is_null() {
+        *out_result = ptr::null_mut();
+    }
+    let db = match db.as_mut() {
+        Some(db) => db,
+        None => return PGRQL_MISUSE,
+    };
+    if sql.is_null() {
+        set_error(
+            db,
+            BackendError::ProtocolViolation("SQL text is NULL".to_string()),
+        );
+        return PGRQL_MISUSE;
+    }
+    // Text that isn't UTF-8 fails like it would in a value sent to Postgres
+    let sql = match check_text(CStr::from_ptr(sql).to_bytes()) {
+        Ok(sql) => sql,
+        Err(err) => {
+            set_error(db, err.into());
+            return PGRQL_ERROR;
+        }
+    };
+
+    let eval_results = match db.backend.eval_query(sql) {
+        Ok(eval_results) => eval_results,
+        Err(err) => {
+            set_error(db, err);
+            return PGRQL_ERROR;
+        }
+    };
+    db.last_error = None;
+
+    if !out_result.is_null() {
+        let results = match eval_results.into_iter().last() {
+            Some(EvalResult::Select { results, .. }) => results,
+            _ => QueryResults {
+                columns: vec![],
+                rows: vec![],
+            },
+        };
+        *out_result = Box::into_raw(Box::new(PgrqlResult::new(results)));
+    }
+
+    PGRQL_OK
+}
+
+/// Returns the message of the last failed call on `db`, or NULL if it succeeded.
+///
+/// The string is owned by the handle and valid until the next call using it.
+///
+/// # Safety
+/// `db` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_errmsg(db: *const PgrqlDb) -> *const c_char {
+    match db.as_ref() {
+        Some(PgrqlDb {
+            last_error: Some(err),
+            backend: _,
+        }) => err.msg.as_ptr(),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the five character SQLSTATE code of the last failed call on `db`, following
+/// the codes Postgres uses (e.g. "42P01" for an undefined table), or NULL if it succeeded.
+///
+/// The string is owned by the handle and valid until the next call using it.
+///
+/// # Safety
+/// `db` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_errcode(db: *const PgrqlDb) -> *const c_char {
+    match db.as_ref() {
+        Some(PgrqlDb {
+            last_error: Some(err),
+            backend: _,
+        }) => err.code.as_ptr(),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the status of the transaction BEGIN started on `db`, as the byte Postgres sends
+/// in ReadyForQuery: 'I' when there is none, 'T' while it runs and 'E' once it failed, after
+/// which statements fail until ROLLBACK. Returns 0 for NULL.
+///
+/// # Safety
+/// `db` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_transaction_status(db: *const PgrqlDb) -> c_char {
+    match db.as_ref() {
+        Some(db) => db.backend.transaction_status().ready_for_query() as c_char,
+        None => 0,
+    }
+}
+
+/// Releases a result returned by `pgrql_exec`, invalidating every string obtained from it.
+/// Passing NULL is a no-op.
+///
+/// # Safety
+/// `result` must be NULL or a result that was not finalized yet.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_finalize_result(result: *mut PgrqlResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Number of columns in the result, or -1 if `result` is NULL.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_column_count(result: *const PgrqlResult) -> c_int {
+    match result.as_ref() {
+        Some(result) => result.results.columns.len() as c_int,
+        None => -1,
+    }
+}
+
+/// Number of rows in the result, or -1 if `result` is NULL.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_row_count(result: *const PgrqlResult) -> i64 {
+    match result.as_ref() {
+        Some(result) => result.results.rows.len() as i64,
+        None => -1,
+    }
+}
+
+/// Name of the column at `col`, or NULL if out of range. Owned by the result.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_column_name(
+    result: *const PgrqlResult,
+    col: c_int,
+) -> *const c_char {
+    match result.as_ref() {
+        Some(result) if col >= 0 => match result.column_names.get(col as usize) {
+            Some(name) => name.as_ptr(),
+            None => ptr::null(),
+        },
+        _ => ptr::null(),
+    }
+}
+
+/// Declared type of the column at `col`, `PGRQL_NULL` if out of range.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_column_type(result: *const PgrqlResult, col: c_int) -> PgrqlType {
+    match result.as_ref() {
+        Some(result) if col >= 0 => match result.results.columns.get(col as usize) {
+            Some(column) => PgrqlType::from(column.col_type),
+            None => PgrqlType::PgrqlNull,
+        },
+        _ => PgrqlType::PgrqlNull,
+    }
+}
+
+/// Returns 1 if the cell is NULL, 0 if it is not and `PGRQL_RANGE` when out of range.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_value_is_null(
+    result: *const PgrqlResult,
+    row: c_int,
+    col: c_int,
+) -> c_int {
+    match result.as_ref().and_then(|result| result.value(row, col)) {
+        Some(value) => value.is_null() as c_int,
+        None => PGRQL_RANGE,
+    }
+}
+
+/// Cell value converted to a 64 bit integer. NULL, out of range cells and values that
+/// cannot be converted yield 0.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_value_int64(
+    result: *const PgrqlResult,
+    row: c_int,
+    col: c_int,
+) -> i64 {
+    match result
+        .as_ref()
+        .and_then(|result| result.value(row, col))
+        .map(|value| value.explicit_cast_to_type(SqlType::BigInt))
+    {
+        Some(Ok(SqlValue::Numeric(num))) => num.to_string().parse::<i64>().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Cell value converted to a double. NULL, out of range cells and values that cannot be
+/// converted yield 0.0.
+///
+/// # Safety
+/// `result` must be NULL or a live result.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_value_double(
+    result: *const PgrqlResult,
+    row: c_int,
+    col: c_int,
+) -> f64 {
+    match result
+        .as_ref()
+        .and_then(|result| result.value(row, col))
+        .map(|value| value.explicit_cast_to_type(SqlType::DoublePrecision))
+    {
+        Some(Ok(SqlValue::Numeric(num))) => num.to_string().parse::<f64>().unwrap_or(0.0),
+        _ => 0.0,
+    }
+}
+
+/// Text rendition of a cell, NUL terminated. The byte length (without the terminator) is
+/// written to `out_len` when it is not NULL, as text may contain embedded NUL bytes.
+/// Returns NULL for NULL or out of range cells. The string is owned by the result.
+///
+/// # Safety
+/// `result` must be NULL or a live result, `out_len` NULL or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pgrql_value_text(
+    result: *const PgrqlResult,
+    row: c_int,
+    col: c_int,
+    out_len: *mut usize,
+) -> *const c_char {
+    if !out_len.is_null() {
+        *out_len = 0;
+    }
+    let result = match result.as_ref() {
+        Some(result) => result,
+        None => return ptr::null(),
+    };
+    let value = match result.value(row, col) {
+        Some(value) if !value.is_null() => value,
+        _ => return ptr::null(),
+    };
+
+    let cell = row as usize * result.results.columns.len() + col as usize;
+    let mut texts = result.texts.borrow_mut();
+    let text = match texts.get_mut(cell) {
+        Some(text) => text,
+        None => return ptr::null(),
+    };
+    if text.is_none() {
+        let mut bytes = value.to_string().into_bytes();
+        bytes.push(0);
+        *text = Some(bytes);
+    }
+    match text {
+        Some(bytes) => {
+            if !out_len.is_null() {
+                *out_len = bytes.len() - 1;
+            }
+            // The heap buffer never moves once created, so the pointer outlives the borrow
+            bytes.as_ptr() as *const c_char
+        }
+        None => ptr::null(),
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// Compiles tests/capi/lifecycle.c against the cdylib and the generated header, then runs it
+#[test]
+fn test_c_api_lifecycle() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The cdylib of the same build is in target/<profile>/deps along with the test. The
+    // one a level above is only copied there by `cargo build`, so it may be stale.
+    let lib_dir = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let out_dir = std::env::temp_dir().join(format!("pgrql_capi_{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let exe = out_dir.join("lifecycle");
+
+    let compiler = cc::Build::new()
+        .target(env!("PGRQL_TARGET"))
+        .host(env!("PGRQL_TARGET"))
+        .opt_level(0)
+        .cargo_metadata(false)
+        .get_compiler();
+    let status = compiler
+        .to_command()
+        .arg(manifest_dir.join("tests").join("capi").join("lifecycle.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lpostgrustql")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success(), "Compiling lifecycle.c failed");
+
+    let output = Command::new(&exe)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .env("DYLD_LIBRARY_PATH", &lib_dir)
+        .output()
+        .expect("Failed to run the C test program");
+    let _ = std::fs::remove_dir_all(&out_dir);
+
+    assert!(
+        output.status.success(),
+        "C test program failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
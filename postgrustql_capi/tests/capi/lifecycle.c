@@ -0,0 +1,70 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "postgrustql.h"
+
+#define CHECK(cond)                                                      \
+    do {                                                                 \
+        if (!(cond)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,       \
+                    __LINE__, #cond);                                    \
+            return 1;                                                    \
+        }                                                                \
+    } while (0)
+
+int main(void) {
+    PgrqlDb *db = pgrql_open();
+    PgrqlResult *result = NULL;
+    size_t len = 0;
+
+    CHECK(db != NULL);
+    CHECK(pgrql_exec(db, "CREATE TABLE people (id INT, name TEXT, score REAL);", NULL) == PGRQL_OK);
+    CHECK(pgrql_exec(db, "INSERT INTO people VALUES (1, 'Baam', 2.5);", NULL) == PGRQL_OK);
+    CHECK(pgrql_exec(db, "INSERT INTO people VALUES (2, 'Rachel', NULL);", NULL) == PGRQL_OK);
+
+    CHECK(pgrql_exec(db, "SELECT id, name, score FROM people;", &result) == PGRQL_OK);
+    CHECK(result != NULL);
+    CHECK(pgrql_column_count(result) == 3);
+    CHECK(pgrql_row_count(result) == 2);
+    CHECK(strcmp(pgrql_column_name(result, 0), "id") == 0);
+    CHECK(strcmp(pgrql_column_name(result, 1), "name") == 0);
+    CHECK(pgrql_column_name(result, 3) == NULL);
+    CHECK(pgrql_column_type(result, 0) == PGRQL_INT);
+    CHECK(pgrql_column_type(result, 1) == PGRQL_TEXT);
+
+    CHECK(pgrql_value_int64(result, 0, 0) == 1);
+    CHECK(pgrql_value_int64(result, 1, 0) == 2);
+    CHECK(pgrql_value_double(result, 0, 2) == 2.5);
+    CHECK(strcmp(pgrql_value_text(result, 1, 1, &len), "Rachel") == 0);
+    CHECK(len == 6);
+    CHECK(pgrql_value_is_null(result, 0, 2) == 0);
+    CHECK(pgrql_value_is_null(result, 1, 2) == 1);
+    CHECK(pgrql_value_text(result, 1, 2, &len) == NULL);
+    CHECK(pgrql_value_is_null(result, 5, 0) == PGRQL_RANGE);
+    pgrql_finalize_result(result);
+
+    CHECK(pgrql_exec(db, "SELECT id FROM missing;", &result) == PGRQL_ERROR);
+    CHECK(result == NULL);
+    CHECK(pgrql_errmsg(db) != NULL);
+    CHECK(strcmp(pgrql_errcode(db), "42P01") == 0);
+    CHECK(pgrql_exec(NULL, "SELECT 1;", NULL) == PGRQL_MISUSE);
+    CHECK(pgrql_exec(db, "SELECT '\xff';", NULL) == PGRQL_ERROR);
+    CHECK(strcmp(pgrql_errcode(db), "22021") == 0);
+
+    CHECK(pgrql_transaction_status(db) == 'I');
+    CHECK(pgrql_exec(db, "BEGIN; SELECT id FROM missing;", NULL) == PGRQL_ERROR);
+    CHECK(pgrql_transaction_status(db) == 'E');
+    CHECK(strcmp(pgrql_errcode(db), "42P01") == 0);
+    CHECK(pgrql_exec(db, "ROLLBACK;", NULL) == PGRQL_OK);
+    CHECK(pgrql_transaction_status(db) == 'I');
+    CHECK(pgrql_transaction_status(NULL) == 0);
+
+    /* Results outlive the database handle */
+    CHECK(pgrql_exec(db, "SELECT name FROM people;", &result) == PGRQL_OK);
+    pgrql_close(db);
+    CHECK(strcmp(pgrql_value_text(result, 0, 0, NULL), "Baam") == 0);
+    pgrql_finalize_result(result);
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,91 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta};
+
+/// Derives `postgrustql::row::FromRow`, mapping each named field to the column of the same
+/// name. A field can read a differently named column with `#[column = "name"]`, and
+/// `Option<T>` fields accept NULL values.
+#[proc_macro_derive(FromRow, attributes(column))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_from_row(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_from_row(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "FromRow can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "FromRow can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut field_inits = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let column = match column_name(field)? {
+            Some(column) => column,
+            None => ident.to_string().trim_start_matches("r#").to_string(),
+        };
+        field_inits.push(quote! {
+            #ident: row.get(#column)?
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::postgrustql::row::FromRow for #name #ty_generics #where_clause {
+            fn from_row(
+                row: &::postgrustql::row::Row,
+            ) -> ::std::result::Result<Self, ::postgrustql::row::MapError> {
+                ::std::result::Result::Ok(#name {
+                    #(#field_inits,)*
+                })
+            }
+        }
+    })
+}
+
+fn column_name(field: &syn::Field) -> syn::Result<Option<String>> {
+    let mut column = None;
+    for attr in &field.attrs {
+        if !attr.path.is_ident("column") {
+            continue;
+        }
+        match attr.parse_meta()? {
+            Meta::NameValue(meta) => match meta.lit {
+                Lit::Str(name) => column = Some(name.value()),
+                lit => {
+                    return Err(syn::Error::new_spanned(
+                        lit,
+                        "expected a string literal, as in #[column = \"name\"]",
+                    ))
+                }
+            },
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[column = \"name\"]",
+                ))
+            }
+        }
+    }
+    Ok(column)
+}
@@ -0,0 +1,98 @@
+use postgrustql::backend_memory::MemoryBackend;
+use postgrustql::row::{FromRow, MapError, Row};
+
+#[derive(Debug, PartialEq, FromRow)]
+struct User {
+    id: i64,
+    #[column = "username"]
+    name: String,
+    active: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Score {
+    r#type: String,
+    value: Option<f64>,
+}
+
+fn backend() -> MemoryBackend {
+    let mut mb = MemoryBackend::new();
+    mb.eval_query(
+        "CREATE TABLE users (id INT, username TEXT, active BOOLEAN);
+        INSERT INTO users VALUES (1, 'Baam', true);
+        INSERT INTO users VALUES (2, 'Rachel', NULL);",
+    )
+    .unwrap();
+    mb
+}
+
+#[test]
+fn test_derive_from_row() {
+    let mut mb = backend();
+
+    let users = mb
+        .query_as::<User>("SELECT id, username, active FROM users;")
+        .unwrap();
+    assert_eq!(
+        users,
+        vec![
+            User {
+                id: 1,
+                name: "Baam".to_string(),
+                active: Some(true),
+            },
+            User {
+                id: 2,
+                name: "Rachel".to_string(),
+                active: None,
+            },
+        ]
+    );
+
+    let scores = mb
+        .query_as::<Score>("SELECT username AS type, NULL AS value FROM users WHERE id = 1;")
+        .unwrap();
+    assert_eq!(
+        scores,
+        vec![Score {
+            r#type: "Baam".to_string(),
+            value: None,
+        }]
+    );
+}
+
+#[test]
+fn test_derive_from_row_errors() {
+    let mut mb = backend();
+
+    let err = mb
+        .query_as::<User>("SELECT id, active FROM users;")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Column \"username\" is not present in the results."
+    );
+
+    let err = mb
+        .query_as::<User>("SELECT username AS id, username, active FROM users;")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Column \"id\" of type Text cannot be read as i64."
+    );
+
+    let results = match mb.eval_query("SELECT id, username, active FROM users;") {
+        Ok(mut results) => match results.pop() {
+            Some(postgrustql::backend::EvalResult::Select { results, .. }) => results,
+            _ => panic!("Expected select results"),
+        },
+        Err(err) => panic!("{}", err),
+    };
+    let row = Row::new(&results.columns, &results.rows[1]);
+    assert_eq!(
+        Score::from_row(&row),
+        Err(MapError::MissingColumn {
+            column: "type".to_string()
+        })
+    );
+}
@@ -3,8 +3,9 @@ use postgrustql::{
     sql_types::{SqlType, SqlValue},
 };
 
-use postgrustql::backend::{Cell, EvalResult};
+use postgrustql::backend::{Cell, EvalResult, QueryResults};
 use postgrustql::backend_memory::*;
+use postgrustql::lexer::Lexer;
 
 use rustyline::{error::ReadlineError, Editor};
 use std::io::{stdout, Write};
@@ -14,6 +15,7 @@ use tokio;
 fn main() {
     let mut mb = MemoryBackend::new();
     let mut rl = Editor::<()>::new();
+    let mut timing = true;
 
     match rl.load_history("history.txt") {
         Ok(_) => {}
@@ -67,101 +69,218 @@ fn main() {
         };
 
         let cmd = input.trim_end().replace("\n", "");
+        if let Some(message) = toggle_timing(&mut timing, &cmd) {
+            println!("{}", message);
+            continue;
+        }
+        if let Some(output) = diff_command(&mut mb, &cmd) {
+            println!("{}", output);
+            continue;
+        }
         match cmd.as_str() {
             "quit" | "exit" | "\\q" => {
                 break;
             }
             _ => {
-                println!("{}", repl_eval(&mut mb, cmd));
+                println!("{}", repl_eval(&mut mb, cmd, timing));
             }
         }
     }
     rl.save_history("history.txt").unwrap();
 }
 
-pub fn repl_eval(mb: &mut MemoryBackend, cmd: String) -> String {
+// Milliseconds with microsecond precision, like psql's \timing shows them
+fn format_ms(time: Duration) -> String {
+    format!("{:.3} ms", time.as_secs_f64() * 1000.0)
+}
+
+// `\timing` turns showing how long each statement took to plan and run on or off,
+// `\timing on` and `\timing off` set it
+fn toggle_timing(timing: &mut bool, cmd: &str) -> Option<String> {
+    let mut words = cmd.split_whitespace();
+    if words.next() != Some("\\timing") {
+        return None;
+    }
+    *timing = match words.next() {
+        None => !*timing,
+        Some("on") => true,
+        Some("off") => false,
+        Some(value) => {
+            return Some(format!(
+                "unrecognized value \"{}\" for \"\\timing\": Boolean expected",
+                value
+            ))
+        }
+    };
+    Some(format!("Timing is {}.", if *timing { "on" } else { "off" }))
+}
+
+// The rows as a table with the name and type of every column above them
+fn results_table(results: &QueryResults<SqlValue>) -> String {
+    let mut output_text = String::new();
+    let mut titles = Vec::with_capacity(10);
+    let mut table = prettytable::Table::new();
+    for col in &results.columns {
+        let title = format!("{}({:?})", col.name, col.col_type);
+        let title_cell = prettytable::Cell::new(&title)
+            .with_style(prettytable::Attr::Bold)
+            .with_style(prettytable::Attr::ForegroundColor(
+                prettytable::color::GREEN,
+            ));
+        titles.push(title_cell);
+    }
+
+    table.set_titles(prettytable::Row::new(titles));
+
+    for result in &results.rows {
+        let mut table_row = Vec::with_capacity(10);
+        for cell in result {
+            table_row.push(prettytable::Cell::new(&cell.to_string()));
+        }
+        table.add_row(prettytable::Row::new(table_row));
+    }
+    table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    if results.rows.len() > 0 {
+        output_text.push_str(table.to_string().as_str());
+    }
+    output_text.push_str(format!("({} Results)\n", results.rows.len()).as_str());
+    output_text
+}
+
+// `\diff t1 t2 (id, ...)` shows the rows the tables differ in, matched by the columns
+// in parentheses, or by all of them when none are given. See `MemoryBackend::diff_tables`.
+fn diff_command(mb: &mut MemoryBackend, cmd: &str) -> Option<String> {
+    let args = cmd.strip_prefix("\\diff ")?;
+    let (tables, key) = match args.find('(') {
+        Some(open) => (
+            &args[..open],
+            args[open + 1..].trim_end().trim_end_matches(')'),
+        ),
+        None => (args, ""),
+    };
+    let tables: Vec<&str> = tables.split_whitespace().collect();
+    let key: Vec<&str> = key
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .collect();
+    Some(match tables.as_slice() {
+        [left, right] => match mb.diff_tables(left, right, &key) {
+            Ok(diff) if diff.is_empty() => "The tables have the same rows.".to_string(),
+            Ok(diff) => results_table(&diff.to_results()),
+            Err(err) => format!("ERROR {}: {}", err.code(), err),
+        },
+        _ => "Usage: \\diff table1 table2 [(key_column, ...)]".to_string(),
+    })
+}
+
+pub fn repl_eval(mb: &mut MemoryBackend, cmd: String, timing: bool) -> String {
     let mut output_text = String::from("");
 
     let mut total_time: Duration = Duration::from_millis(0);
     let mut multiple_results = false;
 
+    // Report every lexing error of the input at once, and run none of it
+    let (_, lexing_errors) = Lexer::new().lex_recovering(&cmd);
+    if !lexing_errors.is_empty() {
+        return lexing_errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
     match mb.eval_query(&cmd) {
         Ok(eval_results) => {
             if eval_results.len() > 1 {
                 multiple_results = true;
             }
             for eval_result in eval_results {
+                let warnings = eval_result.warnings().to_vec();
+                let time = eval_result.time();
                 match eval_result {
-                    EvalResult::Select { results, time } => {
-                        let mut titles = Vec::with_capacity(10);
-                        let mut table = prettytable::Table::new();
-                        for col in &results.columns {
-                            let title = format!("{}({:?})", col.name, col.col_type);
-                            let title_cell = prettytable::Cell::new(&title)
-                                .with_style(prettytable::Attr::Bold)
-                                .with_style(prettytable::Attr::ForegroundColor(
-                                    prettytable::color::GREEN,
-                                ));
-                            titles.push(title_cell);
-                        }
-
-                        table.set_titles(prettytable::Row::new(titles));
-
-                        for result in &results.rows {
-                            let mut table_row = Vec::with_capacity(10);
-                            for i in 0..result.len() {
-                                let cell = &result[i];
-                                let typ = results.columns[i].col_type;
-                                let s = cell.to_string();
-                                table_row.push(prettytable::Cell::new(&s));
-                            }
-                            table.add_row(prettytable::Row::new(table_row));
-                        }
-                        table
-                            .set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-                        if results.rows.len() > 0 {
-                            output_text.push_str(table.to_string().as_str());
-                        }
+                    EvalResult::Select { results, .. } => {
+                        output_text.push_str(&results_table(&results));
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::CreateTable { .. } => {
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::CreateTableAs {
+                        success: _,
+                        rows_inserted,
+                        ..
+                    } => {
                         output_text
-                            .push_str(format!("({} Results)\n", results.rows.len()).as_str());
-
+                            .push_str(format!("({} Rows inserted)\n", rows_inserted).as_str());
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::Insert { .. } => {
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::Update { rows_updated, .. } => {
+                        output_text.push_str(format!("({} Rows updated)\n", rows_updated).as_str());
                         output_text.push_str("Ok!\n");
-                        if multiple_results {
-                            total_time += time;
-                        }
-                        output_text.push_str(format!("Elapsed time : {:.2?}\n", time).as_str());
                     }
-                    EvalResult::CreateTable { success: _, time } => {
+                    EvalResult::Delete { rows_deleted, .. } => {
+                        output_text.push_str(format!("({} Rows deleted)\n", rows_deleted).as_str());
                         output_text.push_str("Ok!\n");
-                        if multiple_results {
-                            total_time += time;
-                        }
-                        output_text.push_str(format!("Elapsed time : {:.2?}\n", time).as_str());
                     }
-                    EvalResult::Insert { success: _, time } => {
+                    EvalResult::DropTable { .. } => {
                         output_text.push_str("Ok!\n");
-                        if multiple_results {
-                            total_time += time;
-                        }
-                        output_text.push_str(format!("Elapsed time : {:.2?}\n", time).as_str());
                     }
-                    EvalResult::DropTable { success: _, time } => {
+                    EvalResult::DropIndex { .. } => {
                         output_text.push_str("Ok!\n");
-                        if multiple_results {
-                            total_time += time;
-                        }
-                        output_text.push_str(format!("Elapsed time : {:.2?}\n", time).as_str());
                     }
+                    EvalResult::AlterTable { .. } => {
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::Reindex { .. } => {
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::Vacuum {
+                        rows_reclaimed,
+                        bytes_reclaimed,
+                        ..
+                    } => {
+                        output_text.push_str(
+                            format!(
+                                "({} Rows, {} Bytes reclaimed)\n",
+                                rows_reclaimed, bytes_reclaimed
+                            )
+                            .as_str(),
+                        );
+                        output_text.push_str("Ok!\n");
+                    }
+                    EvalResult::Set { .. }
+                    | EvalResult::Listen { .. }
+                    | EvalResult::Notify { .. }
+                    | EvalResult::Unlisten { .. }
+                    | EvalResult::DeclareCursor { .. }
+                    | EvalResult::CloseCursor { .. }
+                    | EvalResult::Begin { .. }
+                    | EvalResult::Commit { .. }
+                    | EvalResult::Rollback { .. } => {
+                        output_text.push_str("Ok!\n");
+                    }
+                }
+                if timing {
+                    output_text.push_str(format!("Time: {}\n", format_ms(time)).as_str());
+                }
+                total_time += time;
+                for warning in warnings {
+                    output_text
+                        .push_str(format!("WARNING {}: {}\n", warning.code, warning).as_str());
                 }
             }
 
-            if multiple_results {
-                output_text.push_str(format!("Total time : {:.2?}", total_time).as_str());
+            if timing && multiple_results {
+                output_text.push_str(format!("Total time: {}", format_ms(total_time)).as_str());
             }
         }
 
         Err(err) => {
-            return err;
+            return format!("ERROR {}: {}", err.code(), err);
         }
     }
 
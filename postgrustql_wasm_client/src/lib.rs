@@ -3,7 +3,10 @@ extern crate lazy_static;
 
 use backend_memory::MemoryBackend;
 pub use postgrustql::backend_memory;
-use postgrustql::{backend::EvalResult, sql_types::SqlValue};
+use postgrustql::{
+    backend::{BackendError, EvalResult},
+    sql_types::SqlValue,
+};
 use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
@@ -41,18 +44,18 @@ pub fn eval_raw(cmd: String) -> JsValue {
     query_results_to_js(result)
 }
 
-pub fn eval_query(cmd: String) -> Result<Vec<EvalResult<SqlValue>>, String> {
+pub fn eval_query(cmd: String) -> Result<Vec<EvalResult<SqlValue>>, BackendError> {
     let backend = &mut BACKEND.lock().unwrap();
     let result = backend.eval_query(&cmd);
     result
 }
 
-pub fn query_results_to_js(results: Result<Vec<EvalResult<SqlValue>>, String>) -> JsValue {
+pub fn query_results_to_js(results: Result<Vec<EvalResult<SqlValue>>, BackendError>) -> JsValue {
     let result = match results {
         Err(err) => JSResults {
             success: false,
             results: None,
-            error: Some(err),
+            error: Some(err.to_string()),
         },
         Ok(results) => JSResults {
             success: true,
@@ -61,7 +64,7 @@ pub fn query_results_to_js(results: Result<Vec<EvalResult<SqlValue>>, String>) -
                 results
                     .iter()
                     .map(|r| match r {
-                        EvalResult::Select { results, time } => JSQueryResults {
+                        EvalResult::Select { results, time, .. } => JSQueryResults {
                             success: true,
                             rows: Some(results.rows.clone()),
                             time: format!("{:.2?}", time),
@@ -73,24 +76,75 @@ pub fn query_results_to_js(results: Result<Vec<EvalResult<SqlValue>>, String>) -
                                     .collect(),
                             ),
                         },
-                        EvalResult::Insert { success, time } => JSQueryResults {
+                        EvalResult::Insert { success, time, .. } => JSQueryResults {
                             success: *success,
                             rows: None,
                             time: format!("{:.2?}", time),
                             columns: None,
                         },
-                        EvalResult::CreateTable { success, time } => JSQueryResults {
+                        EvalResult::Update { time, .. } => JSQueryResults {
+                            success: true,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::Delete { time, .. } => JSQueryResults {
+                            success: true,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::CreateTable { success, time, .. } => JSQueryResults {
                             success: *success,
                             rows: None,
                             time: format!("{:.2?}", time),
                             columns: None,
                         },
-                        EvalResult::DropTable { success, time } => JSQueryResults {
+                        EvalResult::CreateTableAs { success, time, .. } => JSQueryResults {
                             success: *success,
                             rows: None,
                             time: format!("{:.2?}", time),
                             columns: None,
                         },
+                        EvalResult::DropTable { success, time, .. } => JSQueryResults {
+                            success: *success,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::DropIndex { success, time, .. } => JSQueryResults {
+                            success: *success,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::AlterTable { success, time, .. } => JSQueryResults {
+                            success: *success,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::Reindex { success, time, .. } => JSQueryResults {
+                            success: *success,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
+                        EvalResult::Set { time, .. }
+                        | EvalResult::Vacuum { time, .. }
+                        | EvalResult::Listen { time, .. }
+                        | EvalResult::Notify { time, .. }
+                        | EvalResult::Unlisten { time, .. }
+                        | EvalResult::DeclareCursor { time, .. }
+                        | EvalResult::CloseCursor { time, .. }
+                        | EvalResult::Begin { time, .. }
+                        | EvalResult::Commit { time, .. }
+                        | EvalResult::Rollback { time, .. } => JSQueryResults {
+                            success: true,
+                            rows: None,
+                            time: format!("{:.2?}", time),
+                            columns: None,
+                        },
                     })
                     .collect(),
             ),